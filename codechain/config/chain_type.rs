@@ -18,6 +18,7 @@ use std::str::FromStr;
 use std::{fmt, fs};
 
 use ccore::Scheme;
+use serde::{Serialize, Serializer};
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -25,6 +26,7 @@ pub enum ChainType {
     Solo,
     #[serde(rename = "simple_poa")]
     SimplePoA,
+    Clique,
     Tendermint,
     Cuckoo,
     #[serde(rename = "blake_pow")]
@@ -47,6 +49,7 @@ impl FromStr for ChainType {
         let scheme = match s {
             "solo" => ChainType::Solo,
             "simple_poa" => ChainType::SimplePoA,
+            "clique" => ChainType::Clique,
             "tendermint" => ChainType::Tendermint,
             "cuckoo" => ChainType::Cuckoo,
             "blake_pow" => ChainType::BlakePoW,
@@ -63,6 +66,7 @@ impl fmt::Display for ChainType {
         f.write_str(match self {
             ChainType::Solo => "solo",
             ChainType::SimplePoA => "simple_poa",
+            ChainType::Clique => "clique",
             ChainType::Tendermint => "tendermint",
             ChainType::Cuckoo => "cuckoo",
             ChainType::BlakePoW => "blake_pow",
@@ -73,11 +77,20 @@ impl fmt::Display for ChainType {
     }
 }
 
+impl Serialize for ChainType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer, {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
 impl ChainType {
     pub fn scheme(&self) -> Result<Scheme, String> {
         match self {
             ChainType::Solo => Ok(Scheme::new_test_solo()),
             ChainType::SimplePoA => Ok(Scheme::new_test_simple_poa()),
+            ChainType::Clique => Ok(Scheme::new_test_clique()),
             ChainType::Tendermint => Ok(Scheme::new_test_tendermint()),
             ChainType::Cuckoo => Ok(Scheme::new_test_cuckoo()),
             ChainType::BlakePoW => Ok(Scheme::new_test_blake_pow()),
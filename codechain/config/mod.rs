@@ -18,19 +18,25 @@ mod chain_type;
 
 use std::fs;
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::str::{self, FromStr};
 use std::time::Duration;
 
-use ccore::{MinerOptions, ShardValidatorConfig, StratumConfig};
-use ckey::PlatformAddress;
+use ccore::{MinerOptions, SealingRole, ShardValidatorConfig, StratumConfig};
+use ckey::{PlatformAddress, Private};
 use clap;
 use cnetwork::{NetworkConfig, SocketAddr};
+use crpc::{BatchConfig, HealthConfig, LimitsConfig, RateLimit};
+use import_hook::ImportHookConfig;
 use rpc::{RpcHttpConfig, RpcIpcConfig};
 use toml;
 
 pub use self::chain_type::ChainType;
 
-#[derive(Deserialize)]
+/// The number of leases the primary can miss before the standby begins sealing.
+const DEFAULT_STANDBY_MISSED_SLOTS: u32 = 3;
+
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub ipc: Ipc,
@@ -42,6 +48,7 @@ pub struct Config {
     pub snapshot: Snapshot,
     pub stratum: Stratum,
     pub shard_validator: ShardValidator,
+    pub import_hook: ImportHook,
 }
 
 impl Config {
@@ -54,6 +61,7 @@ impl Config {
         self.snapshot.merge(&other.snapshot);
         self.stratum.merge(&other.stratum);
         self.shard_validator.merge(&other.shard_validator);
+        self.import_hook.merge(&other.import_hook);
     }
 
     pub fn miner_options(&self) -> Result<MinerOptions, String> {
@@ -71,6 +79,17 @@ impl Config {
                 }
                 None => unreachable!(),
             };
+        let sealing_role = match self.mining.sealing_role.as_ref().map(|s| s.as_str()) {
+            Some("primary") => Some(SealingRole::Primary),
+            Some("standby") => Some(SealingRole::Standby {
+                missed_slots: self.mining.standby_missed_slots.unwrap_or(DEFAULT_STANDBY_MISSED_SLOTS),
+            }),
+            Some(x) => {
+                return Err(format!("{} isn't a valid value for sealing-role. Possible values are primary, standby", x))
+            }
+            None => None,
+        };
+        let default_options = MinerOptions::default();
 
         Ok(MinerOptions {
             mem_pool_size: self.mining.mem_pool_size.unwrap(),
@@ -78,26 +97,89 @@ impl Config {
                 0 => None,
                 mem_size => Some(mem_size * 1024 * 1024),
             },
+            mem_pool_sender_limit: match self.mining.mem_pool_sender_limit {
+                Some(0) => None,
+                Some(limit) => Some(limit),
+                None => default_options.mem_pool_sender_limit,
+            },
+            mem_pool_future_limit: match self.mining.mem_pool_future_limit {
+                Some(0) => None,
+                Some(limit) => Some(limit),
+                None => default_options.mem_pool_future_limit,
+            },
             new_work_notify: self.mining.notify_work.clone().unwrap(),
             force_sealing: self.mining.force_sealing.unwrap(),
             reseal_on_own_parcel,
             reseal_on_external_parcel,
             reseal_min_period: Duration::from_millis(self.mining.reseal_min_period.unwrap()),
             reseal_max_period: Duration::from_millis(self.mining.reseal_max_period.unwrap()),
+            urgent_reseal_fee: self.mining.urgent_reseal_fee.map(Into::into),
+            urgent_reseal_fullness: self.mining.urgent_reseal_fullness,
+            reseal_backoff: self.mining.reseal_backoff.unwrap_or(default_options.reseal_backoff),
             work_queue_size: self.mining.work_queue_size.unwrap(),
-            ..MinerOptions::default()
+            sealing_role,
+            lease_period: self.mining.lease_period.map_or(default_options.lease_period, Duration::from_millis),
+            max_body_size: self.mining.max_body_size,
+            max_parcels_per_block: self.mining.max_parcels_per_block,
+            minimal_fee: self.mining.min_fee.map_or(default_options.minimal_fee, Into::into),
+            replacement_fee_bump: self.mining.replacement_fee_bump.unwrap_or(default_options.replacement_fee_bump),
+            parcel_ttl: match self.mining.parcel_ttl {
+                Some(0) => None,
+                Some(ttl) => Some(Duration::from_secs(ttl)),
+                None => default_options.parcel_ttl,
+            },
+            ..default_options
         })
     }
 
+    pub fn health_config(&self) -> HealthConfig {
+        HealthConfig {
+            max_block_age: self.rpc.health_max_block_age,
+            min_peers: self.rpc.health_min_peers.unwrap_or(0),
+        }
+    }
+
     pub fn rpc_http_config(&self) -> RpcHttpConfig {
         debug_assert!(!self.rpc.disable.unwrap());
 
-        // FIXME: Add interface, cors and hosts options.
         RpcHttpConfig {
             interface: self.rpc.interface.clone().unwrap(),
             port: self.rpc.port.unwrap(),
-            cors: None,
-            hosts: None,
+            hosts: parse_domains(self.rpc.hosts.as_ref().unwrap()),
+        }
+    }
+
+    /// The origins allowed to call the RPC from a browser. Every origin is allowed if it's None.
+    pub fn rpc_cors(&self) -> Option<Vec<String>> {
+        parse_domains(self.rpc.cors.as_ref().unwrap())
+    }
+
+    pub fn rpc_limits_config(&self) -> LimitsConfig {
+        let rate_limit = match self.rpc.rate_limit.unwrap() {
+            0 => None,
+            per_second => Some(RateLimit {
+                per_second,
+                burst: self.rpc.rate_limit_burst.unwrap_or(per_second).max(1),
+            }),
+        };
+        LimitsConfig {
+            rate_limit,
+            max_payload: self.rpc.max_payload.unwrap() * 1024,
+            max_parcel_payload: self.rpc.max_parcel_payload.unwrap() * 1024,
+            max_concurrent_requests: match self.rpc.max_concurrent_requests.unwrap() {
+                0 => None,
+                max_concurrent_requests => Some(max_concurrent_requests),
+            },
+        }
+    }
+
+    pub fn rpc_batch_config(&self) -> BatchConfig {
+        BatchConfig {
+            threads: self.rpc.batch_threads.unwrap(),
+            max_batch_size: match self.rpc.max_batch_size.unwrap() {
+                0 => None,
+                max_batch_size => Some(max_batch_size),
+            },
         }
     }
 
@@ -138,17 +220,39 @@ impl Config {
             .map(|s| SocketAddr::from_str(s).unwrap())
             .collect::<Vec<_>>();
 
-        let whitelist = make_ipaddr_list(self.network.whitelist_path.as_ref(), "white")?;
-        let blacklist = make_ipaddr_list(self.network.blacklist_path.as_ref(), "black")?;
+        let trusted_nodes = make_ipaddr_list(self.network.trusted_nodes_path.as_ref(), "trusted node ")?;
+        let io_workers = self.network.io_workers.unwrap();
+        if io_workers == 0 {
+            return Err("The network needs at least one IO worker".to_string())
+        }
+        let node_key = match &self.network.node_key {
+            Some(key) => Some(key.parse::<Private>().map_err(|err| format!("Invalid node key: {}", err))?),
+            None => None,
+        };
+        let proxy = match &self.network.proxy {
+            Some(proxy) => Some(SocketAddr::from_str(proxy).map_err(|_| format!("Invalid proxy address: {}", proxy))?),
+            None => None,
+        };
 
         Ok(NetworkConfig {
             address: self.network.interface.clone().unwrap(),
             port: self.network.port.unwrap(),
             bootstrap_addresses,
             min_peers: self.network.min_peers.unwrap(),
-            max_peers: self.network.max_peers.unwrap(),
-            whitelist,
-            blacklist,
+            max_inbound_peers: self.network.max_inbound_peers.unwrap(),
+            max_outbound_peers: self.network.max_outbound_peers.unwrap(),
+            trusted_nodes,
+            whitelist_path: self.network.whitelist_path.as_ref().map(PathBuf::from),
+            blacklist_path: self.network.blacklist_path.as_ref().map(PathBuf::from),
+            pending_messages_path: self.network.pending_messages_path.as_ref().map(PathBuf::from),
+            node_key,
+            node_key_file: self.network.node_key_file.as_ref().map(PathBuf::from).unwrap(),
+            proxy,
+            hide_address: self.network.hide_address.unwrap(),
+            dual_stack: self.network.dual_stack.unwrap(),
+            // A seed node shares only the verified addresses.
+            probe_inbound: self.network.seed_node.unwrap(),
+            io_workers,
         })
     }
 
@@ -170,43 +274,83 @@ impl Config {
             account: self.shard_validator.account.unwrap().into_address(),
         }
     }
+
+    pub fn import_hook_config(&self) -> Result<ImportHookConfig, String> {
+        debug_assert!(!self.import_hook.disable.unwrap());
+
+        Ok(ImportHookConfig {
+            url: self.import_hook.url.clone().ok_or("The import hook is enabled but its URL is not specified")?,
+            queue_size: self.import_hook.queue_size.unwrap(),
+            max_retries: self.import_hook.max_retries.unwrap(),
+        })
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Ipc {
     pub disable: Option<bool>,
     pub path: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Operating {
     pub quiet: Option<bool>,
     pub instance_id: Option<usize>,
     pub db_path: Option<String>,
+    /// "rocksdb" or "memory". The database in memory is lost when the node stops.
+    pub db_backend: Option<String>,
     pub keys_path: Option<String>,
     pub password_path: Option<String>,
     pub chain: Option<ChainType>,
+    pub cache_size: Option<usize>,
+    /// Number of the imported blocks which can wait for a background flush. 0 flushes every block synchronously.
+    pub db_flush_queue_size: Option<usize>,
+    pub read_only: Option<bool>,
+    pub max_reorg_depth: Option<u64>,
+    pub body_history: Option<u64>,
+    pub log_levels: Option<String>,
+    pub log_json: Option<bool>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Mining {
     pub disable: Option<bool>,
     pub author: Option<PlatformAddress>,
     pub engine_signer: Option<PlatformAddress>,
+    /// The address where the remote signer keeping the key of the engine signer connects.
+    pub remote_signer: Option<String>,
+    /// The file keeping the secret with which the remote signer authorizes.
+    pub remote_signer_secret_path: Option<String>,
     pub mem_pool_size: Option<usize>,
     pub mem_pool_mem_limit: Option<usize>,
+    /// Maximum number of parcels of an account in the mem pool. 0 disables limiting.
+    pub mem_pool_sender_limit: Option<usize>,
+    /// Maximum number of parcels with future nonces in the mem pool. 0 disables limiting.
+    pub mem_pool_future_limit: Option<usize>,
     pub notify_work: Option<Vec<String>>,
     pub force_sealing: Option<bool>,
     pub reseal_on_txs: Option<String>,
     pub reseal_min_period: Option<u64>,
     pub reseal_max_period: Option<u64>,
+    pub urgent_reseal_fee: Option<u64>,
+    pub urgent_reseal_fullness: Option<u8>,
+    pub reseal_backoff: Option<bool>,
     pub work_queue_size: Option<usize>,
+    pub sealing_role: Option<String>,
+    pub standby_missed_slots: Option<u32>,
+    pub lease_period: Option<u64>,
+    pub max_body_size: Option<usize>,
+    pub max_parcels_per_block: Option<usize>,
+    pub min_fee: Option<u64>,
+    pub replacement_fee_bump: Option<u8>,
+    /// Seconds for which a parcel stays in the mem pool. 0 keeps the parcels until they are mined.
+    pub parcel_ttl: Option<u64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Network {
     pub interface: Option<String>,
@@ -214,18 +358,37 @@ pub struct Network {
     pub port: Option<u16>,
     pub bootstrap_addresses: Option<Vec<String>>,
     pub min_peers: Option<usize>,
+    /// Deprecated. It is split into `max_inbound_peers` and `max_outbound_peers`, which take precedence over it.
     pub max_peers: Option<usize>,
+    pub max_inbound_peers: Option<usize>,
+    pub max_outbound_peers: Option<usize>,
+    pub trusted_nodes_path: Option<String>,
     pub sync: Option<bool>,
+    pub head_lag_timeout: Option<u64>,
     pub parcel_relay: Option<bool>,
+    pub light_server: Option<bool>,
     pub discovery: Option<bool>,
     pub discovery_type: Option<String>,
     pub discovery_refresh: Option<u32>,
     pub discovery_bucket_size: Option<u8>,
     pub blacklist_path: Option<String>,
     pub whitelist_path: Option<String>,
+    /// The directory where the messages to the disconnected peers are kept.
+    pub pending_messages_path: Option<String>,
+    /// The private key of the node in hex. It overrides the key file.
+    pub node_key: Option<String>,
+    pub node_key_file: Option<String>,
+    /// The SOCKS5 proxy for the outbound connections, e.g. "127.0.0.1:9050" for Tor.
+    pub proxy: Option<String>,
+    pub hide_address: Option<bool>,
+    /// Whether the node listening on an IPv6 interface also accepts IPv4.
+    pub dual_stack: Option<bool>,
+    /// The number of the threads which run the handlers of the extensions.
+    pub io_workers: Option<usize>,
+    pub seed_node: Option<bool>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Rpc {
     pub disable: Option<bool>,
@@ -233,33 +396,80 @@ pub struct Rpc {
     pub port: Option<u16>,
     #[serde(default = "default_enable_devel_api")]
     pub enable_devel_api: bool,
+    /// Whether the methods which change the settings of the node are served.
+    #[serde(default)]
+    pub enable_admin_api: bool,
+    /// The methods whose results are signed by the engine signer on request.
+    pub signed_methods: Option<Vec<String>>,
+    /// The node is not ready if the best block is older than this, in seconds.
+    pub health_max_block_age: Option<u64>,
+    /// The node is not ready if it has fewer peers than this.
+    pub health_min_peers: Option<usize>,
+    /// The comma separated origins allowed to call the RPC from a browser, "all" or "none".
+    pub cors: Option<String>,
+    /// The comma separated values of the Host header which the RPC server accepts, "all" or "none".
+    pub hosts: Option<String>,
+    /// The HTTP requests allowed from an IP per second. Not limited if it's 0.
+    pub rate_limit: Option<u32>,
+    /// The HTTP requests which an idle IP can send at once. It's the same as `rate_limit` if not given.
+    pub rate_limit_burst: Option<u32>,
+    /// The maximum size of a request, in kilobytes.
+    pub max_payload: Option<usize>,
+    /// The maximum size of a request which only sends parcels, in kilobytes.
+    pub max_parcel_payload: Option<usize>,
+    /// The maximum number of the requests handled at the same time. Not limited if it's 0.
+    pub max_concurrent_requests: Option<usize>,
+    /// The maximum number of the calls in a batch request. Not limited if it's 0.
+    pub max_batch_size: Option<usize>,
+    /// The number of the threads executing the read-only calls of the batch requests.
+    pub batch_threads: Option<usize>,
 }
 
 fn default_enable_devel_api() -> bool {
     cfg!(debug_assertions)
 }
 
-#[derive(Deserialize)]
+/// Parses the value of the CORS domains and the allowed hosts. It's None if every domain is allowed.
+fn parse_domains(value: &str) -> Option<Vec<String>> {
+    match value {
+        "all" | "*" => None,
+        "none" => Some(Vec::new()),
+        domains => Some(
+            domains.split(',').map(str::trim).filter(|domain| !domain.is_empty()).map(ToString::to_string).collect(),
+        ),
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Snapshot {
     pub disable: Option<bool>,
     pub path: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Stratum {
     pub disable: Option<bool>,
     pub port: Option<u16>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct ShardValidator {
     pub disable: Option<bool>,
     pub account: Option<PlatformAddress>,
 }
 
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportHook {
+    pub disable: Option<bool>,
+    pub url: Option<String>,
+    pub queue_size: Option<usize>,
+    pub max_retries: Option<u32>,
+}
+
 impl Ipc {
     pub fn merge(&mut self, other: &Ipc) {
         if other.disable.is_some() {
@@ -292,6 +502,9 @@ impl Operating {
         if other.db_path.is_some() {
             self.db_path = other.db_path.clone();
         }
+        if other.db_backend.is_some() {
+            self.db_backend = other.db_backend.clone();
+        }
         if other.keys_path.is_some() {
             self.keys_path = other.keys_path.clone();
         }
@@ -301,6 +514,27 @@ impl Operating {
         if other.chain.is_some() {
             self.chain = other.chain.clone();
         }
+        if other.cache_size.is_some() {
+            self.cache_size = other.cache_size;
+        }
+        if other.db_flush_queue_size.is_some() {
+            self.db_flush_queue_size = other.db_flush_queue_size;
+        }
+        if other.read_only.is_some() {
+            self.read_only = other.read_only;
+        }
+        if other.max_reorg_depth.is_some() {
+            self.max_reorg_depth = other.max_reorg_depth;
+        }
+        if other.body_history.is_some() {
+            self.body_history = other.body_history;
+        }
+        if other.log_levels.is_some() {
+            self.log_levels = other.log_levels.clone();
+        }
+        if other.log_json.is_some() {
+            self.log_json = other.log_json;
+        }
     }
 
     pub fn overwrite_with(&mut self, matches: &clap::ArgMatches) -> Result<(), String> {
@@ -313,6 +547,9 @@ impl Operating {
         if let Some(db_path) = matches.value_of("db-path") {
             self.db_path = Some(db_path.to_string());
         }
+        if let Some(db_backend) = matches.value_of("db-backend") {
+            self.db_backend = Some(db_backend.to_string());
+        }
         if let Some(keys_path) = matches.value_of("keys-path") {
             self.keys_path = Some(keys_path.to_string());
         }
@@ -322,6 +559,28 @@ impl Operating {
         if let Some(chain) = matches.value_of("chain") {
             self.chain = Some(chain.parse()?);
         }
+        if let Some(cache_size) = matches.value_of("cache-size") {
+            self.cache_size = Some(cache_size.parse().map_err(|_| "Invalid cache size")?);
+        }
+        if let Some(db_flush_queue_size) = matches.value_of("db-flush-queue-size") {
+            self.db_flush_queue_size =
+                Some(db_flush_queue_size.parse().map_err(|_| "Invalid database flush queue size")?);
+        }
+        if matches.is_present("read-only") {
+            self.read_only = Some(true);
+        }
+        if let Some(max_reorg_depth) = matches.value_of("max-reorg-depth") {
+            self.max_reorg_depth = Some(max_reorg_depth.parse().map_err(|_| "Invalid max reorg depth")?);
+        }
+        if let Some(body_history) = matches.value_of("body-history") {
+            self.body_history = Some(body_history.parse().map_err(|_| "Invalid body history")?);
+        }
+        if let Some(log_levels) = matches.value_of("log-levels") {
+            self.log_levels = Some(log_levels.to_string());
+        }
+        if matches.is_present("log-json") {
+            self.log_json = Some(true);
+        }
         Ok(())
     }
 }
@@ -337,12 +596,24 @@ impl Mining {
         if other.engine_signer.is_some() {
             self.engine_signer = other.engine_signer.clone();
         }
+        if other.remote_signer.is_some() {
+            self.remote_signer = other.remote_signer.clone();
+        }
+        if other.remote_signer_secret_path.is_some() {
+            self.remote_signer_secret_path = other.remote_signer_secret_path.clone();
+        }
         if other.mem_pool_size.is_some() {
             self.mem_pool_size = other.mem_pool_size;
         }
         if other.mem_pool_mem_limit.is_some() {
             self.mem_pool_mem_limit = other.mem_pool_mem_limit;
         }
+        if other.mem_pool_sender_limit.is_some() {
+            self.mem_pool_sender_limit = other.mem_pool_sender_limit;
+        }
+        if other.mem_pool_future_limit.is_some() {
+            self.mem_pool_future_limit = other.mem_pool_future_limit;
+        }
         if other.notify_work.is_some() {
             self.notify_work = other.notify_work.clone();
         }
@@ -358,9 +629,42 @@ impl Mining {
         if other.reseal_max_period.is_some() {
             self.reseal_max_period = other.reseal_max_period;
         }
+        if other.urgent_reseal_fee.is_some() {
+            self.urgent_reseal_fee = other.urgent_reseal_fee;
+        }
+        if other.urgent_reseal_fullness.is_some() {
+            self.urgent_reseal_fullness = other.urgent_reseal_fullness;
+        }
+        if other.reseal_backoff.is_some() {
+            self.reseal_backoff = other.reseal_backoff;
+        }
         if other.work_queue_size.is_some() {
             self.work_queue_size = other.work_queue_size;
         }
+        if other.sealing_role.is_some() {
+            self.sealing_role = other.sealing_role.clone();
+        }
+        if other.standby_missed_slots.is_some() {
+            self.standby_missed_slots = other.standby_missed_slots;
+        }
+        if other.lease_period.is_some() {
+            self.lease_period = other.lease_period;
+        }
+        if other.max_body_size.is_some() {
+            self.max_body_size = other.max_body_size;
+        }
+        if other.max_parcels_per_block.is_some() {
+            self.max_parcels_per_block = other.max_parcels_per_block;
+        }
+        if other.min_fee.is_some() {
+            self.min_fee = other.min_fee;
+        }
+        if other.replacement_fee_bump.is_some() {
+            self.replacement_fee_bump = other.replacement_fee_bump;
+        }
+        if other.parcel_ttl.is_some() {
+            self.parcel_ttl = other.parcel_ttl;
+        }
     }
 
     pub fn overwrite_with(&mut self, matches: &clap::ArgMatches) -> Result<(), String> {
@@ -374,12 +678,24 @@ impl Mining {
         if let Some(engine_signer) = matches.value_of("engine-signer") {
             self.engine_signer = Some(engine_signer.parse().map_err(|_| "Invalid address format")?);
         }
+        if let Some(remote_signer) = matches.value_of("remote-signer") {
+            self.remote_signer = Some(remote_signer.to_string());
+        }
+        if let Some(path) = matches.value_of("remote-signer-secret-path") {
+            self.remote_signer_secret_path = Some(path.to_string());
+        }
         if let Some(mem_pool_mem_limit) = matches.value_of("mem-pool-mem-limit") {
             self.mem_pool_mem_limit = Some(mem_pool_mem_limit.parse().map_err(|_| "Invalid mem limit")?);
         }
         if let Some(mem_pool_size) = matches.value_of("mem-pool-size") {
             self.mem_pool_size = Some(mem_pool_size.parse().map_err(|_| "Invalid size")?);
         }
+        if let Some(limit) = matches.value_of("mem-pool-sender-limit") {
+            self.mem_pool_sender_limit = Some(limit.parse().map_err(|_| "Invalid sender limit")?);
+        }
+        if let Some(limit) = matches.value_of("mem-pool-future-limit") {
+            self.mem_pool_future_limit = Some(limit.parse().map_err(|_| "Invalid future limit")?);
+        }
         if let Some(notify_work) = matches.values_of("notify-work") {
             self.notify_work = Some(notify_work.into_iter().map(|a| a.into()).collect());
         }
@@ -395,9 +711,42 @@ impl Mining {
         if let Some(reseal_max_period) = matches.value_of("reseal-max-period") {
             self.reseal_max_period = Some(reseal_max_period.parse().map_err(|_| "Invalid period")?);
         }
+        if let Some(urgent_reseal_fee) = matches.value_of("urgent-reseal-fee") {
+            self.urgent_reseal_fee = Some(urgent_reseal_fee.parse().map_err(|_| "Invalid fee")?);
+        }
+        if let Some(urgent_reseal_fullness) = matches.value_of("urgent-reseal-fullness") {
+            self.urgent_reseal_fullness = Some(urgent_reseal_fullness.parse().map_err(|_| "Invalid percentage")?);
+        }
+        if matches.is_present("reseal-backoff") {
+            self.reseal_backoff = Some(true);
+        }
         if let Some(work_queue_size) = matches.value_of("work-queue-size") {
             self.work_queue_size = Some(work_queue_size.parse().map_err(|_| "Invalid size")?);
         }
+        if let Some(sealing_role) = matches.value_of("sealing-role") {
+            self.sealing_role = Some(sealing_role.to_string());
+        }
+        if let Some(standby_missed_slots) = matches.value_of("standby-missed-slots") {
+            self.standby_missed_slots = Some(standby_missed_slots.parse().map_err(|_| "Invalid number of slots")?);
+        }
+        if let Some(lease_period) = matches.value_of("lease-period") {
+            self.lease_period = Some(lease_period.parse().map_err(|_| "Invalid period")?);
+        }
+        if let Some(max_body_size) = matches.value_of("max-body-size") {
+            self.max_body_size = Some(max_body_size.parse().map_err(|_| "Invalid size")?);
+        }
+        if let Some(max_parcels_per_block) = matches.value_of("max-parcels-per-block") {
+            self.max_parcels_per_block = Some(max_parcels_per_block.parse().map_err(|_| "Invalid number of parcels")?);
+        }
+        if let Some(min_fee) = matches.value_of("min-fee") {
+            self.min_fee = Some(min_fee.parse().map_err(|_| "Invalid fee")?);
+        }
+        if let Some(replacement_fee_bump) = matches.value_of("replacement-fee-bump") {
+            self.replacement_fee_bump = Some(replacement_fee_bump.parse().map_err(|_| "Invalid percentage")?);
+        }
+        if let Some(parcel_ttl) = matches.value_of("parcel-ttl") {
+            self.parcel_ttl = Some(parcel_ttl.parse().map_err(|_| "Invalid parcel TTL")?);
+        }
         Ok(())
     }
 }
@@ -419,15 +768,30 @@ impl Network {
         if other.min_peers.is_some() {
             self.min_peers = other.min_peers;
         }
-        if other.max_peers.is_some() {
-            self.max_peers = other.max_peers;
+        if let Some(max_peers) = other.max_peers {
+            self.set_max_peers(max_peers);
+        }
+        if other.max_inbound_peers.is_some() {
+            self.max_inbound_peers = other.max_inbound_peers;
+        }
+        if other.max_outbound_peers.is_some() {
+            self.max_outbound_peers = other.max_outbound_peers;
+        }
+        if other.trusted_nodes_path.is_some() {
+            self.trusted_nodes_path = other.trusted_nodes_path.clone();
         }
         if other.sync.is_some() {
             self.sync = other.sync;
         }
+        if other.head_lag_timeout.is_some() {
+            self.head_lag_timeout = other.head_lag_timeout;
+        }
         if other.parcel_relay.is_some() {
             self.parcel_relay = other.parcel_relay;
         }
+        if other.light_server.is_some() {
+            self.light_server = other.light_server;
+        }
         if other.discovery.is_some() {
             self.discovery = other.discovery;
         }
@@ -446,6 +810,30 @@ impl Network {
         if other.whitelist_path.is_some() {
             self.whitelist_path = other.whitelist_path.clone();
         }
+        if other.pending_messages_path.is_some() {
+            self.pending_messages_path = other.pending_messages_path.clone();
+        }
+        if other.node_key.is_some() {
+            self.node_key = other.node_key.clone();
+        }
+        if other.node_key_file.is_some() {
+            self.node_key_file = other.node_key_file.clone();
+        }
+        if other.proxy.is_some() {
+            self.proxy = other.proxy.clone();
+        }
+        if other.hide_address.is_some() {
+            self.hide_address = other.hide_address;
+        }
+        if other.dual_stack.is_some() {
+            self.dual_stack = other.dual_stack;
+        }
+        if other.io_workers.is_some() {
+            self.io_workers = other.io_workers;
+        }
+        if other.seed_node.is_some() {
+            self.seed_node = other.seed_node;
+        }
     }
 
     pub fn overwrite_with(&mut self, matches: &clap::ArgMatches) -> Result<(), String> {
@@ -467,19 +855,34 @@ impl Network {
         if let Some(min_peers) = matches.value_of("min-peers") {
             self.min_peers = Some(min_peers.parse().map_err(|_| "Invalid min-peers")?);
         }
-        if let Some(max_peers) = matches.value_of("min-peers") {
-            self.max_peers = Some(max_peers.parse().map_err(|_| "Invalid max-peers")?);
+        if let Some(max_peers) = matches.value_of("max-peers") {
+            self.set_max_peers(max_peers.parse().map_err(|_| "Invalid max-peers")?);
+        }
+        if let Some(max_inbound_peers) = matches.value_of("max-inbound-peers") {
+            self.max_inbound_peers = Some(max_inbound_peers.parse().map_err(|_| "Invalid max-inbound-peers")?);
         }
-        if self.min_peers > self.max_peers {
-            return Err("Invalid min/max peers".to_string())
+        if let Some(max_outbound_peers) = matches.value_of("max-outbound-peers") {
+            self.max_outbound_peers = Some(max_outbound_peers.parse().map_err(|_| "Invalid max-outbound-peers")?);
+        }
+        if self.min_peers > self.max_outbound_peers {
+            return Err("Invalid min/max outbound peers".to_string())
+        }
+        if let Some(file_path) = matches.value_of("trusted-nodes-path") {
+            self.trusted_nodes_path = Some(file_path.to_string());
         }
 
         if matches.is_present("no-sync") {
             self.sync = Some(false);
         }
+        if let Some(timeout) = matches.value_of("head-lag-timeout") {
+            self.head_lag_timeout = Some(timeout.parse().map_err(|_| "Invalid head-lag-timeout")?);
+        }
         if matches.is_present("no-parcel-relay") {
             self.parcel_relay = Some(false);
         }
+        if matches.is_present("light-server") {
+            self.light_server = Some(true);
+        }
 
         if matches.is_present("no-discovery") {
             self.discovery = Some(false);
@@ -500,9 +903,42 @@ impl Network {
         if let Some(file_path) = matches.value_of("blacklist-path") {
             self.blacklist_path = Some(file_path.to_string());
         }
+        if let Some(path) = matches.value_of("pending-messages-path") {
+            self.pending_messages_path = Some(path.to_string());
+        }
+        if let Some(key) = matches.value_of("node-key") {
+            self.node_key = Some(key.to_string());
+        }
+        if let Some(path) = matches.value_of("node-key-file") {
+            self.node_key_file = Some(path.to_string());
+        }
+        if let Some(proxy) = matches.value_of("proxy") {
+            self.proxy = Some(proxy.to_string());
+        }
+        if matches.is_present("hide-address") {
+            self.hide_address = Some(true);
+        }
+        if matches.is_present("no-dual-stack") {
+            self.dual_stack = Some(false);
+        }
+        if let Some(io_workers) = matches.value_of("io-workers") {
+            self.io_workers = Some(io_workers.parse().map_err(|_| "Invalid io-workers")?);
+        }
+
+        if matches.is_present("seed-node") {
+            self.seed_node = Some(true);
+        }
 
         Ok(())
     }
+
+    /// Splits the deprecated total limit into the inbound and outbound limits, in the ratio of the presets.
+    fn set_max_peers(&mut self, max_peers: usize) {
+        let max_outbound_peers = max_peers / 3;
+        self.max_peers = Some(max_peers);
+        self.max_inbound_peers = Some(max_peers - max_outbound_peers);
+        self.max_outbound_peers = Some(max_outbound_peers);
+    }
 }
 
 impl Rpc {
@@ -516,6 +952,45 @@ impl Rpc {
         if other.port.is_some() {
             self.port = other.port;
         }
+        if other.enable_admin_api {
+            self.enable_admin_api = true;
+        }
+        if other.signed_methods.is_some() {
+            self.signed_methods = other.signed_methods.clone();
+        }
+        if other.health_max_block_age.is_some() {
+            self.health_max_block_age = other.health_max_block_age;
+        }
+        if other.health_min_peers.is_some() {
+            self.health_min_peers = other.health_min_peers;
+        }
+        if other.cors.is_some() {
+            self.cors = other.cors.clone();
+        }
+        if other.hosts.is_some() {
+            self.hosts = other.hosts.clone();
+        }
+        if other.rate_limit.is_some() {
+            self.rate_limit = other.rate_limit;
+        }
+        if other.rate_limit_burst.is_some() {
+            self.rate_limit_burst = other.rate_limit_burst;
+        }
+        if other.max_payload.is_some() {
+            self.max_payload = other.max_payload;
+        }
+        if other.max_parcel_payload.is_some() {
+            self.max_parcel_payload = other.max_parcel_payload;
+        }
+        if other.max_concurrent_requests.is_some() {
+            self.max_concurrent_requests = other.max_concurrent_requests;
+        }
+        if other.max_batch_size.is_some() {
+            self.max_batch_size = other.max_batch_size;
+        }
+        if other.batch_threads.is_some() {
+            self.batch_threads = other.batch_threads;
+        }
     }
 
     pub fn overwrite_with(&mut self, matches: &clap::ArgMatches) -> Result<(), String> {
@@ -532,6 +1007,46 @@ impl Rpc {
         if matches.is_present("enable-devel-api") {
             self.enable_devel_api = true;
         }
+        if matches.is_present("enable-admin-api") {
+            self.enable_admin_api = true;
+        }
+        if let Some(signed_methods) = matches.values_of("rpc-signed-methods") {
+            self.signed_methods = Some(signed_methods.into_iter().map(|method| method.into()).collect());
+        }
+        if let Some(max_block_age) = matches.value_of("health-max-block-age") {
+            self.health_max_block_age = Some(max_block_age.parse().map_err(|_| "Invalid max block age")?);
+        }
+        if let Some(min_peers) = matches.value_of("health-min-peers") {
+            self.health_min_peers = Some(min_peers.parse().map_err(|_| "Invalid min peers")?);
+        }
+        if let Some(cors) = matches.value_of("rpc-cors") {
+            self.cors = Some(cors.to_string());
+        }
+        if let Some(hosts) = matches.value_of("rpc-hosts") {
+            self.hosts = Some(hosts.to_string());
+        }
+        if let Some(rate_limit) = matches.value_of("rpc-rate-limit") {
+            self.rate_limit = Some(rate_limit.parse().map_err(|_| "Invalid rate limit")?);
+        }
+        if let Some(burst) = matches.value_of("rpc-rate-limit-burst") {
+            self.rate_limit_burst = Some(burst.parse().map_err(|_| "Invalid rate limit burst")?);
+        }
+        if let Some(max_payload) = matches.value_of("rpc-max-payload") {
+            self.max_payload = Some(max_payload.parse().map_err(|_| "Invalid max payload")?);
+        }
+        if let Some(max_parcel_payload) = matches.value_of("rpc-max-parcel-payload") {
+            self.max_parcel_payload = Some(max_parcel_payload.parse().map_err(|_| "Invalid max parcel payload")?);
+        }
+        if let Some(max_concurrent_requests) = matches.value_of("rpc-max-concurrent-requests") {
+            self.max_concurrent_requests =
+                Some(max_concurrent_requests.parse().map_err(|_| "Invalid max concurrent requests")?);
+        }
+        if let Some(max_batch_size) = matches.value_of("rpc-max-batch-size") {
+            self.max_batch_size = Some(max_batch_size.parse().map_err(|_| "Invalid max batch size")?);
+        }
+        if let Some(batch_threads) = matches.value_of("rpc-batch-threads") {
+            self.batch_threads = Some(batch_threads.parse().map_err(|_| "Invalid batch threads")?);
+        }
         Ok(())
     }
 }
@@ -603,6 +1118,35 @@ impl ShardValidator {
     }
 }
 
+impl ImportHook {
+    pub fn merge(&mut self, other: &ImportHook) {
+        if other.disable.is_some() {
+            self.disable = other.disable;
+        }
+        if other.url.is_some() {
+            self.url = other.url.clone();
+        }
+        if other.queue_size.is_some() {
+            self.queue_size = other.queue_size;
+        }
+        if other.max_retries.is_some() {
+            self.max_retries = other.max_retries;
+        }
+    }
+
+    pub fn overwrite_with(&mut self, matches: &clap::ArgMatches) -> Result<(), String> {
+        if let Some(url) = matches.value_of("import-hook-url") {
+            self.url = Some(url.to_string());
+            self.disable = Some(false);
+        }
+
+        if matches.is_present("no-import-hook") {
+            self.disable = Some(true);
+        }
+        Ok(())
+    }
+}
+
 #[cfg(not(debug_assertions))]
 pub fn read_preset_config() -> &'static str {
     let bytes = include_bytes!("presets/config.prod.toml");
@@ -636,6 +1180,58 @@ pub fn load_config(matches: &clap::ArgMatches) -> Result<Config, String> {
     config.snapshot.overwrite_with(&matches)?;
     config.stratum.overwrite_with(&matches)?;
     config.shard_validator.overwrite_with(&matches)?;
+    config.import_hook.overwrite_with(&matches)?;
+
+    // A read-only node neither syncs nor seals, so nothing writes to the database.
+    if config.operating.read_only.unwrap() {
+        config.mining.disable = Some(true);
+        config.mining.force_sealing = Some(false);
+        config.network.disable = Some(true);
+        config.stratum.disable = Some(true);
+    }
+
+    // A seed node only shares the peers, so it neither seals nor syncs the chain.
+    if config.network.seed_node.unwrap() {
+        if config.network.disable.unwrap() || !config.network.discovery.unwrap() {
+            return Err("A seed node needs the network and the discovery".to_string())
+        }
+        config.mining.disable = Some(true);
+        config.mining.force_sealing = Some(false);
+        config.network.sync = Some(false);
+        config.network.parcel_relay = Some(false);
+        config.network.light_server = Some(false);
+        config.stratum.disable = Some(true);
+    }
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_network(network: &str) -> Config {
+        let sections = ["codechain", "ipc", "mining", "rpc", "snapshot", "stratum", "shard_validator", "import_hook"];
+        let mut toml_string = format!("[network]\n{}\n", network);
+        for section in &sections {
+            toml_string.push_str(&format!("[{}]\n", section));
+        }
+        toml::from_str(&toml_string).unwrap()
+    }
+
+    #[test]
+    fn deprecated_max_peers_is_split_into_the_inbound_and_outbound_limits() {
+        let mut config: Config = toml::from_str(read_preset_config()).unwrap();
+        config.merge(&config_with_network("max_peers = 60"));
+        assert_eq!(Some(40), config.network.max_inbound_peers);
+        assert_eq!(Some(20), config.network.max_outbound_peers);
+    }
+
+    #[test]
+    fn inbound_and_outbound_limits_take_precedence_over_max_peers() {
+        let mut config: Config = toml::from_str(read_preset_config()).unwrap();
+        config.merge(&config_with_network("max_peers = 60\nmax_outbound_peers = 5"));
+        assert_eq!(Some(40), config.network.max_inbound_peers);
+        assert_eq!(Some(5), config.network.max_outbound_peers);
+    }
+}
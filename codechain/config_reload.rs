@@ -0,0 +1,317 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use ccore::{Miner, MinerOptions, MinerService};
+use clap::ArgMatches;
+use clogger;
+use cnetwork::{check_peer_limits, NetworkControl, NetworkControlError};
+use crpc::{ConfigReload, Cors, ReloadReport};
+use libc;
+use parking_lot::Mutex;
+use toml;
+
+use super::config::{load_config, Config};
+
+/// The keys which are applied to the running node. The other keys take effect after a restart.
+const RELOADABLE_KEYS: &[&str] = &[
+    "codechain.log_levels",
+    "mining.min_fee",
+    "mining.replacement_fee_bump",
+    "mining.urgent_reseal_fee",
+    "network.max_inbound_peers",
+    "network.max_outbound_peers",
+    "network.max_peers",
+    "network.min_peers",
+    "rpc.cors",
+];
+
+/// How often the reload thread checks whether SIGHUP is received.
+const SIGHUP_POLL_INTERVAL_MS: u64 = 500;
+
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Reloads the configuration from the same sources as the startup: the preset, the config file and the
+/// command line options. The options given on the command line still take precedence over the file.
+pub struct ConfigReloader {
+    matches: ArgMatches<'static>,
+    /// The keys of the running configuration.
+    running: Mutex<BTreeMap<String, toml::Value>>,
+    miner: Arc<Miner>,
+    network_control: Arc<NetworkControl>,
+    cors: Arc<Cors>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        matches: ArgMatches<'static>,
+        config: &Config,
+        miner: Arc<Miner>,
+        network_control: Arc<NetworkControl>,
+        cors: Arc<Cors>,
+    ) -> Result<Arc<Self>, String> {
+        Ok(Arc::new(Self {
+            matches,
+            running: Mutex::new(flatten(config)?),
+            miner,
+            network_control,
+            cors,
+        }))
+    }
+
+    fn apply_peer_limits(&self, config: &Config) -> Result<(), String> {
+        let min_peers = config.network.min_peers.unwrap();
+        let max_inbound_peers = config.network.max_inbound_peers.unwrap();
+        let max_outbound_peers = config.network.max_outbound_peers.unwrap();
+        match self.network_control.set_peer_limits(min_peers, max_inbound_peers, max_outbound_peers) {
+            Ok(()) | Err(NetworkControlError::Disabled) => Ok(()),
+            Err(NetworkControlError::InvalidPeerLimits(err)) => Err(err),
+            Err(err) => Err(format!("Cannot change the peer limits: {:?}", err)),
+        }
+    }
+
+    fn apply_fees(&self, options: &MinerOptions) {
+        self.miner.set_minimal_fee(options.minimal_fee);
+        self.miner.set_urgent_reseal_fee(options.urgent_reseal_fee);
+        self.miner.set_replacement_fee_bump(options.replacement_fee_bump);
+    }
+}
+
+impl ConfigReload for ConfigReloader {
+    fn reload(&self) -> Result<ReloadReport, String> {
+        let mut running = self.running.lock();
+        let config = load_config(&self.matches)?;
+        let reloaded = flatten(&config)?;
+
+        let (applied, requires_restart): (Vec<_>, Vec<_>) =
+            changed_keys(&running, &reloaded).into_iter().partition(|key| RELOADABLE_KEYS.contains(&key.as_str()));
+
+        // Every key is validated before any of them is applied, so a rejected reload changes nothing.
+        let miner_options = config.miner_options()?;
+        check_peer_limits(config.network.max_inbound_peers.unwrap(), config.network.max_outbound_peers.unwrap())?;
+
+        if applied.iter().any(|key| key.starts_with("network.")) {
+            self.apply_peer_limits(&config)?;
+        }
+        if applied.iter().any(|key| key.starts_with("mining.")) {
+            self.apply_fees(&miner_options);
+        }
+        if applied.iter().any(|key| key == "codechain.log_levels") {
+            clogger::set_levels(config.operating.log_levels.clone());
+        }
+        if applied.iter().any(|key| key == "rpc.cors") {
+            self.cors.set(config.rpc_cors());
+        }
+
+        // The keys requiring a restart keep their running values, so they are reported until the restart.
+        for key in &applied {
+            match reloaded.get(key) {
+                Some(value) => running.insert(key.clone(), value.clone()),
+                None => running.remove(key),
+            };
+        }
+
+        cinfo!(
+            CONFIG,
+            "The configuration is reloaded. Applied: {:?}, requires restart: {:?}",
+            applied,
+            requires_restart
+        );
+        Ok(ReloadReport {
+            applied,
+            requires_restart,
+        })
+    }
+}
+
+/// Reloads the configuration whenever the process receives SIGHUP.
+pub fn reload_on_sighup(reloader: &Arc<ConfigReloader>) -> Result<(), String> {
+    extern "C" fn request_reload(_: libc::c_int) {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+    }
+    // Only an atomic flag is set in the signal handler. The reload itself runs on its own thread.
+    unsafe {
+        libc::signal(libc::SIGHUP, request_reload as libc::sighandler_t);
+    }
+
+    let reloader: Weak<ConfigReloader> = Arc::downgrade(reloader);
+    thread::Builder::new()
+        .name("config_reload".to_string())
+        .spawn(move || loop {
+            thread::sleep(Duration::from_millis(SIGHUP_POLL_INTERVAL_MS));
+            if !RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+                continue
+            }
+            let reloader = match reloader.upgrade() {
+                Some(reloader) => reloader,
+                None => break,
+            };
+            cinfo!(CONFIG, "SIGHUP is received");
+            if let Err(err) = reloader.reload() {
+                cerror!(CONFIG, "Cannot reload the configuration: {}", err);
+            }
+        })
+        .map_err(|e| format!("Cannot start the config reload thread: {:?}", e))?;
+    Ok(())
+}
+
+/// Flattens the configuration into `section.key` pairs. The keys without a value are omitted.
+fn flatten(config: &Config) -> Result<BTreeMap<String, toml::Value>, String> {
+    let sections = match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(sections)) => sections,
+        Ok(_) => unreachable!("The configuration is a table of the sections"),
+        Err(err) => return Err(format!("Cannot serialize the configuration: {}", err)),
+    };
+    let mut keys = BTreeMap::new();
+    for (section, values) in sections {
+        if let toml::Value::Table(values) = values {
+            for (key, value) in values {
+                keys.insert(format!("{}.{}", section, key), value);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+fn changed_keys(running: &BTreeMap<String, toml::Value>, reloaded: &BTreeMap<String, toml::Value>) -> Vec<String> {
+    let mut keys: Vec<String> = running
+        .iter()
+        .filter(|(key, value)| reloaded.get(*key) != Some(value))
+        .map(|(key, _)| key.clone())
+        .chain(reloaded.keys().filter(|key| !running.contains_key(*key)).cloned())
+        .collect();
+    keys.sort();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    use ccore::Scheme;
+    use clap;
+    use primitives::U256;
+
+    use super::super::config::read_preset_config;
+    use super::super::dummy_network_service::DummyNetworkService;
+    use super::*;
+
+    fn write_config(path: &Path, mining: &str, network: &str) {
+        let sections = ["codechain", "ipc", "rpc", "snapshot", "stratum", "shard_validator", "import_hook"];
+        let mut toml = format!("[mining]\n{}\n[network]\n{}\n", mining, network);
+        for section in &sections {
+            toml.push_str(&format!("[{}]\n", section));
+        }
+        fs::write(path, toml).unwrap();
+    }
+
+    fn reloader(path: &Path) -> (Arc<ConfigReloader>, Arc<Miner>) {
+        let yaml = Box::leak(Box::new(load_yaml!("codechain.yml").clone()));
+        let matches =
+            clap::App::from_yaml(yaml).get_matches_from(vec!["codechain", "--config", path.to_str().unwrap()]);
+        let config = load_config(&matches).unwrap();
+        let miner = Miner::new(config.miner_options().unwrap(), &Scheme::new_test(), None);
+        let network_control: Arc<NetworkControl> = Arc::new(DummyNetworkService::new());
+        let cors = Arc::new(Cors::new(config.rpc_cors()));
+        let reloader = ConfigReloader::new(matches, &config, Arc::clone(&miner), network_control, cors).unwrap();
+        (reloader, miner)
+    }
+
+    fn config_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("codechain-{}-{}.toml", name, process::id()))
+    }
+
+    #[test]
+    fn reload_applies_the_reloadable_keys() {
+        let path = config_path("reload-applies");
+        write_config(&path, "min_fee = 50", "port = 3485");
+        let (reloader, miner) = reloader(&path);
+
+        write_config(&path, "min_fee = 70", "port = 3486");
+        let report = reloader.reload().unwrap();
+        assert_eq!(vec!["mining.min_fee".to_string()], report.applied);
+        assert_eq!(vec!["network.port".to_string()], report.requires_restart);
+        assert_eq!(U256::from(70), miner.minimal_fee());
+
+        // The applied key is running, but the key requiring a restart is still reported.
+        let report = reloader.reload().unwrap();
+        assert!(report.applied.is_empty());
+        assert_eq!(vec!["network.port".to_string()], report.requires_restart);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejected_reload_applies_nothing() {
+        let path = config_path("reload-rejected");
+        write_config(&path, "min_fee = 50", "max_inbound_peers = 20");
+        let (reloader, miner) = reloader(&path);
+
+        write_config(&path, "min_fee = 70", "max_inbound_peers = 1000");
+        assert!(reloader.reload().is_err());
+        assert_eq!(U256::from(50), miner.minimal_fee());
+
+        write_config(&path, "min_fee = 70", "max_inbound_peers = 20");
+        let report = reloader.reload().unwrap();
+        assert_eq!(vec!["mining.min_fee".to_string()], report.applied);
+        assert_eq!(U256::from(70), miner.minimal_fee());
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn table(pairs: &[(&str, toml::Value)]) -> BTreeMap<String, toml::Value> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.clone())).collect()
+    }
+
+    #[test]
+    fn changed_keys_include_added_and_removed_keys() {
+        let running = table(&[
+            ("codechain.log_levels", toml::Value::String("sync=debug".to_string())),
+            ("mining.min_fee", toml::Value::Integer(10)),
+            ("network.port", toml::Value::Integer(3485)),
+        ]);
+        let reloaded = table(&[
+            ("mining.min_fee", toml::Value::Integer(20)),
+            ("mining.urgent_reseal_fee", toml::Value::Integer(1000)),
+            ("network.port", toml::Value::Integer(3485)),
+        ]);
+        let changed = vec![
+            "codechain.log_levels".to_string(),
+            "mining.min_fee".to_string(),
+            "mining.urgent_reseal_fee".to_string(),
+        ];
+        assert_eq!(changed, changed_keys(&running, &reloaded));
+        assert_eq!(Vec::<String>::new(), changed_keys(&running, &running));
+    }
+
+    #[test]
+    fn preset_is_flattened_into_section_keys() {
+        let config: Config = toml::from_str(read_preset_config()).unwrap();
+        let keys = flatten(&config).unwrap();
+        assert_eq!(Some(&toml::Value::Integer(10)), keys.get("network.min_peers"));
+        assert_eq!(Some(&toml::Value::Integer(12)), keys.get("mining.replacement_fee_bump"));
+        assert!(keys.contains_key("rpc.enable_devel_api"));
+        assert_eq!(Some(&toml::Value::String("none".to_string())), keys.get("rpc.cors"));
+        // The commented out keys have no value.
+        assert!(!keys.contains_key("codechain.log_levels"));
+    }
+}
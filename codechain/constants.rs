@@ -14,4 +14,5 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub const DEFAULT_DB_PATH: &'static str = "db";
 pub const DEFAULT_KEYS_PATH: &'static str = "keys";
@@ -14,9 +14,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::net::IpAddr;
+use std::collections::HashMap;
+use std::time::Duration;
 
-use cnetwork::{NetworkControl, NetworkControlError, SocketAddr};
+use ckey::Signature;
+use cnetwork::{ExtensionStats, FilterEntry, LocalInfo, NetworkControl, NetworkControlError, SocketAddr};
 use primitives::H256;
 
 pub struct DummyNetworkService {}
@@ -48,6 +50,10 @@ impl NetworkControl for DummyNetworkService {
         Err(NetworkControlError::Disabled)
     }
 
+    fn local_info(&self) -> Result<LocalInfo, NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
     fn get_peer_count(&self) -> Result<usize, NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
@@ -56,19 +62,23 @@ impl NetworkControl for DummyNetworkService {
         Err(NetworkControlError::Disabled)
     }
 
-    fn add_to_whitelist(&self, _addr: IpAddr) -> Result<(), NetworkControlError> {
+    fn peer_rtts(&self) -> Result<HashMap<SocketAddr, Duration>, NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
+    fn add_to_whitelist(&self, _entry: FilterEntry) -> Result<(), NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
 
-    fn remove_from_whitelist(&self, _addr: &IpAddr) -> Result<(), NetworkControlError> {
+    fn remove_from_whitelist(&self, _entry: &FilterEntry) -> Result<(), NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
 
-    fn add_to_blacklist(&self, _addr: IpAddr) -> Result<(), NetworkControlError> {
+    fn add_to_blacklist(&self, _entry: FilterEntry) -> Result<(), NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
 
-    fn remove_from_blacklist(&self, _addr: &IpAddr) -> Result<(), NetworkControlError> {
+    fn remove_from_blacklist(&self, _entry: &FilterEntry) -> Result<(), NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
 
@@ -88,11 +98,41 @@ impl NetworkControl for DummyNetworkService {
         Err(NetworkControlError::Disabled)
     }
 
-    fn get_whitelist(&self) -> Result<(Vec<IpAddr>, bool), NetworkControlError> {
+    fn get_whitelist(&self) -> Result<(Vec<FilterEntry>, bool), NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
+    fn get_blacklist(&self) -> Result<(Vec<FilterEntry>, bool), NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
+    fn set_node_allow_list(
+        &self,
+        _version: u64,
+        _nodes: Vec<SocketAddr>,
+        _signature: Signature,
+    ) -> Result<(), NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
+    fn get_node_allow_list(&self) -> Result<(Option<u64>, Vec<SocketAddr>), NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
+    fn extension_stats(&self) -> Result<HashMap<String, ExtensionStats>, NetworkControlError> {
+        Err(NetworkControlError::Disabled)
+    }
+
+    fn refresh_discovery(&self) -> Result<(), NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
 
-    fn get_blacklist(&self) -> Result<(Vec<IpAddr>, bool), NetworkControlError> {
+    fn set_peer_limits(
+        &self,
+        _min_peers: usize,
+        _max_inbound_peers: usize,
+        _max_outbound_peers: usize,
+    ) -> Result<(), NetworkControlError> {
         Err(NetworkControlError::Disabled)
     }
 }
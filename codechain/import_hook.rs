@@ -0,0 +1,285 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, sleep};
+use std::time::Duration;
+
+use ccore::{BlockChainClient, BlockId, BlockInfo, ChainNotify, Client};
+use parking_lot::{Condvar, Mutex};
+use primitives::H256;
+use serde_json;
+
+const INITIAL_BACKOFF_MS: u64 = 1_000;
+const MAX_BACKOFF_MS: u64 = 60_000;
+const IO_TIMEOUT_MS: u64 = 10_000;
+
+pub struct ImportHookConfig {
+    pub url: String,
+    /// The number of undelivered events kept before the oldest one is dropped
+    pub queue_size: usize,
+    /// The number of retries before an event is dropped
+    pub max_retries: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockSummary {
+    hash: String,
+    number: u64,
+    parent_hash: String,
+    timestamp: u64,
+    parcel_count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportEvent {
+    enacted: Vec<BlockSummary>,
+    retracted: Vec<BlockSummary>,
+}
+
+struct Queue {
+    events: Mutex<VecDeque<String>>,
+    condvar: Condvar,
+    capacity: usize,
+    stopped: AtomicBool,
+}
+
+/// Posts the enacted and retracted blocks to an HTTP endpoint after every import.
+///
+/// Events are delivered in order by a dedicated thread. A failed delivery is retried with
+/// an exponential backoff, and later events wait behind it.
+pub struct ImportHook {
+    client: Arc<Client>,
+    queue: Arc<Queue>,
+}
+
+impl ImportHook {
+    pub fn start(client: Arc<Client>, config: ImportHookConfig) -> Result<Arc<Self>, String> {
+        let endpoint = Endpoint::parse(&config.url)?;
+        let queue = Arc::new(Queue {
+            events: Mutex::new(VecDeque::new()),
+            condvar: Condvar::new(),
+            capacity: config.queue_size,
+            stopped: AtomicBool::new(false),
+        });
+
+        let worker_queue = Arc::clone(&queue);
+        let max_retries = config.max_retries;
+        thread::Builder::new()
+            .name("import_hook".to_string())
+            .spawn(move || deliver(&worker_queue, &endpoint, max_retries))
+            .map_err(|e| format!("Cannot start the import hook: {:?}", e))?;
+        cinfo!(IMPORT_HOOK, "Import hook posts to {}", config.url);
+
+        Ok(Arc::new(Self {
+            client,
+            queue,
+        }))
+    }
+
+    fn summary(&self, hash: &H256) -> Option<BlockSummary> {
+        let header = self.client.block_header(BlockId::Hash(*hash))?;
+        let parcel_count = self.client.block_body(BlockId::Hash(*hash)).map(|body| body.parcels_count()).unwrap_or(0);
+        Some(BlockSummary {
+            hash: format!("0x{:x}", hash),
+            number: header.number(),
+            parent_hash: format!("0x{:x}", header.parent_hash()),
+            timestamp: header.timestamp(),
+            parcel_count,
+        })
+    }
+}
+
+impl Drop for ImportHook {
+    fn drop(&mut self) {
+        self.queue.stopped.store(true, Ordering::SeqCst);
+        self.queue.condvar.notify_all();
+    }
+}
+
+impl ChainNotify for ImportHook {
+    fn new_blocks(
+        &self,
+        _imported: Vec<H256>,
+        _invalid: Vec<H256>,
+        enacted: Vec<H256>,
+        retracted: Vec<H256>,
+        _sealed: Vec<H256>,
+        _duration: u64,
+    ) {
+        if enacted.is_empty() && retracted.is_empty() {
+            return
+        }
+        let event = ImportEvent {
+            enacted: enacted.iter().filter_map(|hash| self.summary(hash)).collect(),
+            retracted: retracted.iter().filter_map(|hash| self.summary(hash)).collect(),
+        };
+        let payload = serde_json::to_string(&event).expect("The import event is always serializable");
+
+        let mut events = self.queue.events.lock();
+        events.push_back(payload);
+        if events.len() > self.queue.capacity {
+            events.pop_front();
+            cwarn!(IMPORT_HOOK, "The import hook queue is full. The oldest event is dropped");
+        }
+        self.queue.condvar.notify_one();
+    }
+}
+
+fn deliver(queue: &Queue, endpoint: &Endpoint, max_retries: u32) {
+    loop {
+        let payload = {
+            let mut events = queue.events.lock();
+            while events.is_empty() && !queue.stopped.load(Ordering::SeqCst) {
+                queue.condvar.wait(&mut events);
+            }
+            if queue.stopped.load(Ordering::SeqCst) {
+                return
+            }
+            events.pop_front().expect("The queue is not empty")
+        };
+
+        let mut backoff = INITIAL_BACKOFF_MS;
+        let mut retries = 0;
+        while let Err(err) = endpoint.post(&payload) {
+            if retries == max_retries {
+                cwarn!(IMPORT_HOOK, "Cannot deliver an import event after {} retries: {}", retries, err);
+                break
+            }
+            cdebug!(IMPORT_HOOK, "Cannot deliver an import event, retry in {}ms: {}", backoff, err);
+            retries += 1;
+            sleep(Duration::from_millis(backoff));
+            backoff = ::std::cmp::min(backoff * 2, MAX_BACKOFF_MS);
+            if queue.stopped.load(Ordering::SeqCst) {
+                return
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    /// Only plain `http://` URLs are supported.
+    fn parse(url: &str) -> Result<Self, String> {
+        let rest = if url.starts_with("http://") {
+            &url["http://".len()..]
+        } else {
+            return Err(format!("Invalid import hook URL {}: only http:// is supported", url))
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(index) => {
+                let port = authority[index + 1..].parse().map_err(|_| format!("Invalid port in {}", url))?;
+                (&authority[..index], port)
+            }
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("Invalid import hook URL {}: the host is missing", url))
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn post(&self, body: &str) -> io::Result<()> {
+        let timeout = Some(Duration::from_millis(IO_TIMEOUT_MS));
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            self.port,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        if is_success(&status_line) {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, format!("Unexpected response: {}", status_line.trim())))
+        }
+    }
+}
+
+fn is_success(status_line: &str) -> bool {
+    let mut words = status_line.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(version), Some(code)) => version.starts_with("HTTP/") && code.starts_with('2') && code.len() == 3,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_endpoint() {
+        assert_eq!(
+            Ok(Endpoint {
+                host: "127.0.0.1".to_string(),
+                port: 9000,
+                path: "/blocks?source=codechain".to_string(),
+            }),
+            Endpoint::parse("http://127.0.0.1:9000/blocks?source=codechain")
+        );
+        assert_eq!(
+            Ok(Endpoint {
+                host: "indexer".to_string(),
+                port: 80,
+                path: "/".to_string(),
+            }),
+            Endpoint::parse("http://indexer")
+        );
+        assert!(Endpoint::parse("https://indexer/blocks").is_err());
+        assert!(Endpoint::parse("http://indexer:port/").is_err());
+        assert!(Endpoint::parse("http://:80/").is_err());
+    }
+
+    #[test]
+    fn success_status() {
+        assert!(is_success("HTTP/1.1 200 OK\r\n"));
+        assert!(is_success("HTTP/1.0 204 No Content\r\n"));
+        assert!(!is_success("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(!is_success("HTTP/1.1 301 Moved Permanently\r\n"));
+        assert!(!is_success(""));
+    }
+}
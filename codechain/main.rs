@@ -25,10 +25,12 @@ extern crate tokio_core;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 
 extern crate app_dirs;
 extern crate codechain_core as ccore;
+extern crate codechain_crypto as ccrypto;
 extern crate codechain_discovery as cdiscovery;
 extern crate codechain_finally as cfinally;
 extern crate codechain_key as ckey;
@@ -44,15 +46,21 @@ extern crate codechain_types as ctypes;
 extern crate ctrlc;
 extern crate env_logger;
 extern crate fdlimit;
+extern crate kvdb;
+extern crate libc;
 extern crate panic_hook;
 extern crate parking_lot;
 extern crate primitives;
+extern crate rlp;
 extern crate rpassword;
+extern crate rustc_hex;
 extern crate toml;
 
 mod config;
+mod config_reload;
 mod constants;
 mod dummy_network_service;
+mod import_hook;
 mod json;
 mod rpc;
 mod rpc_apis;
@@ -61,7 +69,7 @@ mod subcommand;
 
 use app_dirs::AppInfo;
 
-use self::run_node::run_node;
+use self::run_node::{run_light_node, run_node};
 use self::subcommand::run_subcommand;
 
 pub const APP_INFO: AppInfo = AppInfo {
@@ -80,11 +88,13 @@ fn main() -> Result<(), String> {
 }
 
 fn run() -> Result<(), String> {
-    let yaml = load_yaml!("codechain.yml");
+    // The matches are kept by the config reloader, so the YAML lives as long as the process.
+    let yaml = Box::leak(Box::new(load_yaml!("codechain.yml").clone()));
     let matches = clap::App::from_yaml(yaml).get_matches();
 
     match matches.subcommand {
         Some(_) => run_subcommand(matches),
+        None if matches.is_present("light") => run_light_node(matches),
         None => run_node(matches),
     }
 }
@@ -19,38 +19,45 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use crpc::{start_http, start_ipc, HttpServer, IpcServer};
-use crpc::{Compatibility, MetaIoHandler};
-use rpc_apis;
+use crpc::{
+    BatchMiddleware, Compatibility, LimitMiddleware, MaintenanceMiddleware, MetaIoHandler, SigningMiddleware,
+    TracingMiddleware,
+};
+use rpc_apis::{self, RpcServerMiddleware};
 
 #[derive(Debug, PartialEq)]
 pub struct RpcHttpConfig {
     pub interface: String,
     pub port: u16,
-    pub cors: Option<Vec<String>>,
+    /// The values of the Host header which are accepted. Every host is accepted if it's None.
     pub hosts: Option<Vec<String>>,
 }
 
 pub fn rpc_http_start(
     cfg: RpcHttpConfig,
     enable_devel_api: bool,
+    enable_admin_api: bool,
     deps: Arc<rpc_apis::ApiDependencies>,
 ) -> Result<HttpServer, String> {
     let url = format!("{}:{}", cfg.interface, cfg.port);
     let addr = url.parse().map_err(|_| format!("Invalid JSONRPC listen host/port given: {}", url))?;
-    let server = setup_http_rpc_server(&addr, cfg.cors, cfg.hosts, enable_devel_api, deps)?;
+    let server = setup_http_rpc_server(&addr, cfg.hosts, enable_devel_api, enable_admin_api, deps)?;
     cinfo!(RPC, "RPC Listening on {}", url);
     Ok(server)
 }
 
 fn setup_http_rpc_server(
     url: &SocketAddr,
-    cors_domains: Option<Vec<String>>,
     allowed_hosts: Option<Vec<String>>,
     enable_devel_api: bool,
+    enable_admin_api: bool,
     deps: Arc<rpc_apis::ApiDependencies>,
 ) -> Result<HttpServer, String> {
-    let server = setup_rpc_server(enable_devel_api, deps);
-    let start_result = start_http(url, cors_domains, allowed_hosts, server);
+    let cors = Arc::clone(&deps.cors);
+    let limits = Arc::clone(&deps.limits);
+    let health = Some(Arc::clone(&deps.health));
+    let server = setup_rpc_server(enable_devel_api, enable_admin_api, deps);
+    let start_result = start_http(url, cors, limits, allowed_hosts, health, server);
     match start_result {
         Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
             Err(format!("RPC address {} is already in use, make sure that another instance of a CodeChain node is not running or change the address using the --jsonrpc-port option.", url))
@@ -68,9 +75,10 @@ pub struct RpcIpcConfig {
 pub fn rpc_ipc_start(
     cfg: RpcIpcConfig,
     enable_devel_api: bool,
+    enable_admin_api: bool,
     deps: Arc<rpc_apis::ApiDependencies>,
 ) -> Result<IpcServer, String> {
-    let server = setup_rpc_server(enable_devel_api, deps);
+    let server = setup_rpc_server(enable_devel_api, enable_admin_api, deps);
     let start_result = start_ipc(&cfg.socket_addr, server);
     match start_result {
         Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
@@ -84,8 +92,19 @@ pub fn rpc_ipc_start(
     }
 }
 
-fn setup_rpc_server(enable_devel_api: bool, deps: Arc<rpc_apis::ApiDependencies>) -> MetaIoHandler<()> {
-    let mut handler = MetaIoHandler::with_compatibility(Compatibility::Both);
-    deps.extend_api(enable_devel_api, &mut handler);
-    rpc_apis::setup_rpc(handler)
+fn setup_rpc_server(
+    enable_devel_api: bool,
+    enable_admin_api: bool,
+    deps: Arc<rpc_apis::ApiDependencies>,
+) -> MetaIoHandler<(), RpcServerMiddleware> {
+    let maintenance = MaintenanceMiddleware::new(Arc::clone(&deps.maintenance));
+    let signing = SigningMiddleware::new(maintenance, deps.response_signer.clone());
+    let mut handler = MetaIoHandler::new(Compatibility::Both, signing);
+    deps.extend_api(enable_devel_api, enable_admin_api, &mut handler);
+    let handler = rpc_apis::setup_rpc(handler);
+
+    // The requests are traced and limited as a whole before the batch is split.
+    let batch = BatchMiddleware::new(handler, Arc::clone(&deps.batch));
+    let limit = LimitMiddleware::new(batch, Arc::clone(&deps.limits));
+    MetaIoHandler::new(Compatibility::Both, TracingMiddleware::new(limit))
 }
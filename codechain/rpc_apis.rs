@@ -16,27 +16,55 @@
 
 use std::sync::Arc;
 
-use ccore::{AccountProvider, Client, Miner, ShardValidator};
+use ccore::{AccountProvider, ChainStatistics, Client, Miner, ShardValidator};
 use cnetwork::NetworkControl;
-use crpc::{MetaIoHandler, Params, Value};
+use crpc::{
+    BatchExecutor, BatchMiddleware, ConfigReload, Cors, HealthCheck, LimitMiddleware, Limits, Maintenance,
+    MaintenanceMiddleware, MetaIoHandler, Params, ResponseSigner, SigningMiddleware, TracingMiddleware, Value,
+};
+
+/// The middleware of the handler which has the methods.
+pub type RpcMiddleware = SigningMiddleware<MaintenanceMiddleware>;
+/// The middleware of the handler given to the servers. It dispatches the requests to the handler of the methods.
+pub type RpcServerMiddleware = TracingMiddleware<LimitMiddleware<BatchMiddleware<(), RpcMiddleware>>>;
 
 pub struct ApiDependencies {
     pub client: Arc<Client>,
     pub miner: Arc<Miner>,
+    pub chain_stats: Arc<ChainStatistics>,
     pub network_control: Arc<NetworkControl>,
     pub account_provider: Arc<AccountProvider>,
     pub shard_validator: Option<Arc<ShardValidator>>,
+    pub maintenance: Arc<Maintenance>,
+    pub response_signer: Option<Arc<ResponseSigner>>,
+    pub health: Arc<HealthCheck>,
+    pub cors: Arc<Cors>,
+    pub limits: Arc<Limits>,
+    pub batch: Arc<BatchExecutor>,
+    pub config_reload: Arc<ConfigReload>,
 }
 
 impl ApiDependencies {
-    pub fn extend_api(&self, enable_devel_api: bool, handler: &mut MetaIoHandler<()>) {
+    pub fn extend_api(
+        &self,
+        enable_devel_api: bool,
+        enable_admin_api: bool,
+        handler: &mut MetaIoHandler<(), RpcMiddleware>,
+    ) {
         use crpc::v1::*;
-        handler.extend_with(ChainClient::new(&self.client, &self.miner).to_delegate());
+        handler.extend_with(ChainClient::new(&self.client, &self.miner, &self.chain_stats).to_delegate());
         if enable_devel_api {
             handler.extend_with(DevelClient::new(&self.client, &self.miner).to_delegate());
         }
+        handler.extend_with(FilterClient::new(&self.client, &self.miner).to_delegate());
+        handler.extend_with(MempoolClient::new(&self.client, &self.miner).to_delegate());
         handler.extend_with(MinerClient::new(&self.client, &self.miner).to_delegate());
-        handler.extend_with(NetClient::new(&self.network_control).to_delegate());
+        if enable_admin_api {
+            handler.extend_with(AdminClient::new(&self.maintenance).to_delegate());
+            handler.extend_with(ConfigClient::new(&self.config_reload).to_delegate());
+            handler.extend_with(MinerAdminClient::new(&self.client, &self.miner).to_delegate());
+        }
+        handler.extend_with(NetClient::new(&self.network_control, &self.health).to_delegate());
         handler.extend_with(
             AccountClient::new(&self.account_provider, self.client.engine().params().network_id).to_delegate(),
         );
@@ -46,7 +74,7 @@ impl ApiDependencies {
     }
 }
 
-pub fn setup_rpc(mut handler: MetaIoHandler<()>) -> MetaIoHandler<()> {
+pub fn setup_rpc(mut handler: MetaIoHandler<(), RpcMiddleware>) -> MetaIoHandler<(), RpcMiddleware> {
     handler.add_method("ping", |_params: Params| Ok(Value::String("pong".to_string())));
     handler.add_method("version", |_params: Params| Ok(Value::String(env!("CARGO_PKG_VERSION").to_string())));
     handler.add_method("commitHash", |_params: Params| Ok(Value::String(env!("VERGEN_SHA").to_string())));
@@ -16,56 +16,122 @@
 
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use ccore::{
-    AccountProvider, AccountProviderError, Client, ClientService, EngineType, Miner, MinerService, Scheme,
-    ShardValidator, Stratum, StratumConfig, StratumError,
+    open_database, AccountProvider, AccountProviderError, ChainStatistics, Client, ClientConfig, ClientService,
+    DatabaseBackend, EngineType, LightClient, Miner, MinerService, RemoteSigner, Scheme, ShardValidator, Stratum,
+    StratumConfig, StratumError, DEFAULT_CHAIN_STATS_WINDOW,
 };
+use ccrypto::blake256;
 use cdiscovery::{KademliaConfig, KademliaExtension, UnstructuredConfig, UnstructuredExtension};
 use cfinally::finally;
-use ckey::Address;
+use ckey::{Address, KeyPair};
 use ckeystore::accounts_dir::RootDiskDirectory;
 use ckeystore::KeyStore;
 use clap::ArgMatches;
 use clogger::{self, LoggerConfig};
-use cnetwork::{Filters, NetworkConfig, NetworkControl, NetworkService, SocketAddr};
+use cnetwork::{
+    load_node_key, ChainHead, ChainIdentity, Filters, NetworkConfig, NetworkControl, NetworkService, NodeAllowList,
+    PendingMessages, SocketAddr,
+};
 use creactor::EventLoop;
-use csync::{BlockSyncExtension, ParcelSyncExtension, SnapshotService};
+use crpc::{BatchExecutor, Cors, HealthCheck, Limits, Maintenance, ResponseSigner};
+use csync::{BlockSyncExtension, HeadLagWatchdog, LightSyncExtension, ParcelSyncExtension, SnapshotService};
 use ctrlc::CtrlC;
 use fdlimit::raise_fd_limit;
 use parking_lot::{Condvar, Mutex};
 
 use super::config::{self, load_config};
+use super::config_reload::{reload_on_sighup, ConfigReloader};
 use super::constants::DEFAULT_KEYS_PATH;
 use super::dummy_network_service::DummyNetworkService;
+use super::import_hook::ImportHook;
 use super::json::PasswordFile;
 use super::rpc::{rpc_http_start, rpc_ipc_start};
 use super::rpc_apis::ApiDependencies;
 
-fn network_start(cfg: &NetworkConfig) -> Result<Arc<NetworkService>, String> {
-    cinfo!(NETWORK, "Handshake Listening on {}:{}", cfg.address, cfg.port);
-
+fn network_start(
+    cfg: &NetworkConfig,
+    allow_list: Option<Arc<NodeAllowList>>,
+    chain: ChainIdentity,
+) -> Result<Arc<NetworkService>, String> {
     let addr = cfg.address.parse().map_err(|_| format!("Invalid NETWORK listen host given: {}", cfg.address))?;
     let sockaddress = SocketAddr::new(addr, cfg.port);
-    let filters = Filters::new(cfg.whitelist.clone(), cfg.blacklist.clone());
-    let service = NetworkService::start(sockaddress, cfg.min_peers, cfg.max_peers, filters)
-        .map_err(|e| format!("Network service error: {:?}", e))?;
+    cinfo!(NETWORK, "Handshake Listening on {}", sockaddress);
+    let filters = Filters::load(
+        cfg.whitelist_path.as_ref().map(PathBuf::as_path),
+        cfg.blacklist_path.as_ref().map(PathBuf::as_path),
+    )?;
+    let pending_messages = match &cfg.pending_messages_path {
+        Some(path) => Some(Arc::new(PendingMessages::load(path)?)),
+        None => None,
+    };
+    let node_key = match &cfg.node_key {
+        Some(private) => KeyPair::from_private(private.clone()).map_err(|err| format!("Invalid node key: {}", err))?,
+        None => load_node_key(&cfg.node_key_file)?,
+    };
+    cinfo!(NETWORK, "Node key: {:?}", node_key.public());
+    if let Some(proxy) = &cfg.proxy {
+        cinfo!(NETWORK, "Outbound connections are dialed through the proxy {}", proxy);
+    }
+    if cfg.hide_address {
+        cinfo!(NETWORK, "Inbound connections and the sessions over UDP are refused to hide the local address");
+    }
+    let service = NetworkService::start(
+        sockaddress,
+        cfg.min_peers,
+        cfg.max_inbound_peers,
+        cfg.max_outbound_peers,
+        cfg.trusted_nodes.clone(),
+        filters,
+        allow_list,
+        pending_messages,
+        node_key,
+        cfg.proxy,
+        cfg.hide_address,
+        cfg.dual_stack,
+        cfg.probe_inbound,
+        cfg.io_workers,
+        chain,
+    ).map_err(|e| format!("Network service error: {:?}", e))?;
 
     Ok(service)
 }
 
+fn node_allow_list(scheme: &Scheme) -> Result<Option<Arc<NodeAllowList>>, String> {
+    let list = match &scheme.node_allow_list {
+        Some(list) => list,
+        None => return Ok(None),
+    };
+    let allow_list = NodeAllowList::new(list.authority);
+    // The nodes are not allowed until the list signed by the authority is given.
+    if let Some(signature) = &list.signature {
+        let nodes = list.nodes.iter().map(|address| SocketAddr::from(*address).into()).collect();
+        allow_list
+            .update(list.version.into(), nodes, signature)
+            .map_err(|err| format!("Invalid node allow-list in the scheme: {}", err))?;
+    }
+    cinfo!(NETWORK, "Only the nodes in the allow-list can connect");
+    Ok(Some(Arc::new(allow_list)))
+}
+
+fn chain_identity(scheme: &Scheme, head: Arc<ChainHead>) -> ChainIdentity {
+    ChainIdentity::new(scheme.params().network_id, scheme.genesis_header().hash(), scheme.fork_blocks.clone(), head)
+}
+
 fn discovery_start(service: &NetworkService, cfg: &config::Network) -> Result<(), String> {
     match cfg.discovery_type.as_ref().map(|s| s.as_str()) {
         Some("unstructured") => {
             let config = UnstructuredConfig {
                 bucket_size: cfg.discovery_bucket_size.unwrap(),
                 t_refresh: cfg.discovery_refresh.unwrap(),
+                verified_only: cfg.seed_node.unwrap(),
             };
             let unstructured = UnstructuredExtension::new(config);
-            service.set_routing_table(&*unstructured);
+            service.set_discovery(unstructured.clone());
             service.register_extension(unstructured);
             cinfo!(DISCOVERY, "Node runs with unstructured discovery");
         }
@@ -73,9 +139,10 @@ fn discovery_start(service: &NetworkService, cfg: &config::Network) -> Result<()
             let config = KademliaConfig {
                 bucket_size: cfg.discovery_bucket_size.unwrap(),
                 t_refresh: cfg.discovery_refresh.unwrap(),
+                verified_only: cfg.seed_node.unwrap(),
             };
             let kademlia = KademliaExtension::new(config);
-            service.set_routing_table(&*kademlia);
+            service.set_discovery(kademlia.clone());
             service.register_extension(kademlia);
             cinfo!(DISCOVERY, "Node runs with kademlia discovery");
         }
@@ -89,7 +156,23 @@ fn client_start(cfg: &config::Operating, scheme: &Scheme, miner: Arc<Miner>) ->
     cinfo!(CLIENT, "Starting client");
     let db_path = cfg.db_path.as_ref().map(|s| s.as_str()).unwrap();
     let client_path = Path::new(db_path);
-    let client_config = Default::default();
+    let mut client_config = ClientConfig::default();
+    client_config.db_backend = cfg.db_backend.as_ref().unwrap().parse()?;
+    if let Some(cache_size) = cfg.cache_size {
+        client_config.blockchain_cache_size = cache_size * 1024 * 1024;
+    }
+    if let Some(db_flush_queue_size) = cfg.db_flush_queue_size {
+        client_config.db_flush_queue_size = db_flush_queue_size;
+    }
+    client_config.db_read_only = cfg.read_only.unwrap();
+    client_config.max_reorg_depth = cfg.max_reorg_depth;
+    client_config.body_history = cfg.body_history;
+    if client_config.db_read_only {
+        cinfo!(CLIENT, "The database is opened read-only");
+    }
+    if client_config.db_backend == DatabaseBackend::Memory {
+        cwarn!(CLIENT, "The database is kept in memory instead of {}. The chain is lost when the node stops", db_path);
+    }
     let service = ClientService::start(client_config, &scheme, &client_path, miner)
         .map_err(|e| format!("Client service error: {}", e))?;
 
@@ -122,6 +205,26 @@ fn new_miner(config: &config::Config, scheme: &Scheme, ap: Arc<AccountProvider>)
                 None => return Err("The author is missing. Specify the author using --author option.".to_string()),
             },
             EngineType::InternalSealing => match &config.mining.engine_signer {
+                Some(ref engine_signer) if config.mining.remote_signer.is_some() => {
+                    let remote_signer = config.mining.remote_signer.as_ref().unwrap();
+                    let addr = remote_signer
+                        .parse::<::std::net::SocketAddr>()
+                        .map_err(|_| format!("Invalid remote signer address: {}", remote_signer))?;
+                    let secret_path = config.mining.remote_signer_secret_path.as_ref().ok_or_else(|| {
+                        "The remote signer secret is missing. Specify the secret file path using --remote-signer-secret-path option."
+                            .to_string()
+                    })?;
+                    let secret = fs::read_to_string(secret_path)
+                        .map_err(|e| format!("Could not read remote signer secret file at {}: {}", secret_path, e))?;
+                    let secret = secret.trim();
+                    if secret.is_empty() {
+                        return Err(format!("The remote signer secret file {} is empty", secret_path))
+                    }
+                    let remote = RemoteSigner::start(&addr, (*engine_signer).into_address(), blake256(secret))
+                        .map_err(|e| format!("Cannot listen for the remote signer on {}: {:?}", addr, e))?;
+                    cinfo!(MINER, "Listening for the remote signer of {} on {}", engine_signer, addr);
+                    miner.set_remote_signer(Arc::new(remote));
+                }
                 Some(ref engine_signer) => match miner.set_author((*engine_signer).into_address(), None) {
                     Err(AccountProviderError::NotUnlocked) => {
                         return Err(
@@ -146,6 +249,32 @@ fn new_miner(config: &config::Config, scheme: &Scheme, ap: Arc<AccountProvider>)
     Ok(miner)
 }
 
+fn logger_start(cfg: &config::Operating) {
+    let instance_id = cfg.instance_id.unwrap_or(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Current time should be later than unix epoch")
+            .subsec_nanos() as usize,
+    );
+    let mut logger_config = LoggerConfig::new(instance_id);
+    logger_config.levels = cfg.log_levels.clone();
+    logger_config.json = cfg.log_json.unwrap();
+    clogger::init(&logger_config).expect("Logger must be successfully initialized");
+}
+
+/// The options are read before the logger starts, so the deprecated ones are reported after it.
+fn warn_deprecated_options(config: &config::Config) {
+    if let Some(max_peers) = config.network.max_peers {
+        cwarn!(
+            CONFIG,
+            "max_peers({}) is deprecated. Use max_inbound_peers and max_outbound_peers, which are {} and {} now",
+            max_peers,
+            config.network.max_inbound_peers.unwrap(),
+            config.network.max_outbound_peers.unwrap()
+        );
+    }
+}
+
 fn wait_for_exit() {
     let exit = Arc::new((Mutex::new(()), Condvar::new()));
 
@@ -186,7 +315,7 @@ fn unlock_accounts(ap: Arc<AccountProvider>, pf: &PasswordFile) -> Result<(), St
     Ok(())
 }
 
-pub fn run_node(matches: ArgMatches) -> Result<(), String> {
+pub fn run_node(matches: ArgMatches<'static>) -> Result<(), String> {
     // increase max number of open files
     raise_fd_limit();
 
@@ -209,13 +338,8 @@ pub fn run_node(matches: ArgMatches) -> Result<(), String> {
         None => return Err("chain is not specified".to_string()),
     };
 
-    let instance_id = config.operating.instance_id.unwrap_or(
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Current time should be later than unix epoch")
-            .subsec_nanos() as usize,
-    );
-    clogger::init(&LoggerConfig::new(instance_id)).expect("Logger must be successfully initialized");
+    logger_start(&config.operating);
+    warn_deprecated_options(&config);
 
     let pf = load_password_file(config.operating.password_path.clone())?;
     let keys_path = match config.operating.keys_path {
@@ -236,10 +360,11 @@ pub fn run_node(matches: ArgMatches) -> Result<(), String> {
         Some(ShardValidator::new(Some(config.shard_validator_config().account), Arc::clone(&ap)))
     };
 
-    let network_service: Arc<NetworkControl> = {
+    let (network_service, _head_lag_watchdog): (Arc<NetworkControl>, _) = {
         if !config.network.disable.unwrap() {
             let network_config = config.network_config()?;
-            let service = network_start(&network_config)?;
+            let chain = chain_identity(&scheme, client.client());
+            let service = network_start(&network_config, node_allow_list(&scheme)?, chain)?;
 
             if config.network.discovery.unwrap() {
                 discovery_start(&service, &config.network)?;
@@ -247,42 +372,119 @@ pub fn run_node(matches: ArgMatches) -> Result<(), String> {
                 cwarn!(DISCOVERY, "Node runs without discovery extension");
             }
 
-            if config.network.sync.unwrap() {
+            let sync = if config.network.sync.unwrap() {
                 let sync = BlockSyncExtension::new(client.client());
                 service.register_extension(sync.clone());
                 client.client().add_notify(sync.clone());
-            }
+                Some(sync)
+            } else {
+                None
+            };
             if config.network.parcel_relay.unwrap() {
                 service.register_extension(ParcelSyncExtension::new(client.client()));
             }
-            if let Some(consensus_extension) = scheme.engine.network_extension() {
-                service.register_extension(consensus_extension);
+            if config.network.light_server.unwrap() {
+                service.register_extension(LightSyncExtension::server(client.client()));
             }
+            if config.network.seed_node.unwrap() {
+                cinfo!(NETWORK, "Node runs as a seed node");
+            } else {
+                for consensus_extension in scheme.engine.network_extensions() {
+                    service.register_extension(consensus_extension);
+                }
+                if let Some(sealing_lease) = miner.sealing_lease_extension() {
+                    service.register_extension(sealing_lease);
+                }
 
-            if let Some(shard_validator) = &shard_validator {
-                service.register_extension(shard_validator.clone());
+                if let Some(shard_validator) = &shard_validator {
+                    service.register_extension(shard_validator.clone());
+                }
             }
+            service.initialize_extensions();
 
-            for address in network_config.bootstrap_addresses {
-                service.connect_to(address)?;
+            for address in &network_config.bootstrap_addresses {
+                service.connect_to(*address)?;
             }
-            service
+
+            let head_lag_watchdog = match (sync, config.network.head_lag_timeout) {
+                (Some(sync), Some(timeout)) if timeout > 0 => Some(HeadLagWatchdog::start(
+                    client.client(),
+                    service.clone(),
+                    sync,
+                    network_config.bootstrap_addresses,
+                    Duration::from_millis(timeout),
+                )),
+                _ => None,
+            };
+            (service, head_lag_watchdog)
         } else {
-            Arc::new(DummyNetworkService::new())
+            (Arc::new(DummyNetworkService::new()), None)
+        }
+    };
+
+    let signed_methods = config.rpc.signed_methods.clone().unwrap_or_default();
+    let response_signer = match config.mining.engine_signer {
+        _ if signed_methods.is_empty() => None,
+        _ if config.mining.remote_signer.is_some() => {
+            return Err("The results of the RPC methods cannot be signed by the remote signer.".to_string())
+        }
+        Some(signer) => {
+            let response_signer = ResponseSigner::new(Arc::clone(&ap), signer, signed_methods).map_err(|e| match e {
+                AccountProviderError::NotUnlocked => {
+                    "The engine signer is not unlocked. Specify the password path using --password-path option."
+                        .to_string()
+                }
+                e => format!("Cannot sign the results of the RPC methods: {}", e),
+            })?;
+            Some(Arc::new(response_signer))
+        }
+        None => {
+            return Err("The RPC methods are signed by the engine signer. Specify it using --engine-signer option."
+                .to_string())
         }
     };
 
+    let chain_stats = ChainStatistics::new(client.client(), DEFAULT_CHAIN_STATS_WINDOW);
+    client.client().add_notify(chain_stats.clone());
+
+    let cors = Arc::new(Cors::new(config.rpc_cors()));
+    let config_reloader = ConfigReloader::new(
+        matches.clone(),
+        &config,
+        Arc::clone(&miner),
+        Arc::clone(&network_service),
+        Arc::clone(&cors),
+    )?;
+    reload_on_sighup(&config_reloader)?;
+
     let rpc_apis_deps = Arc::new(ApiDependencies {
         client: client.client(),
         miner: Arc::clone(&miner),
+        chain_stats,
         network_control: Arc::clone(&network_service),
         account_provider: ap,
         shard_validator,
+        maintenance: if config.operating.read_only.unwrap() {
+            Arc::new(Maintenance::read_only())
+        } else {
+            Default::default()
+        },
+        response_signer,
+        health: Arc::new(HealthCheck::new(client.client(), Arc::clone(&network_service), config.health_config())),
+        cors,
+        limits: Arc::new(Limits::new(config.rpc_limits_config())),
+        batch: Arc::new(BatchExecutor::new(config.rpc_batch_config())),
+        config_reload: config_reloader,
     });
 
     let _rpc_server = {
         if !config.rpc.disable.unwrap() {
-            Some(rpc_http_start(config.rpc_http_config(), config.rpc.enable_devel_api, Arc::clone(&rpc_apis_deps))?)
+            Some(rpc_http_start(
+                config.rpc_http_config(),
+                config.rpc.enable_devel_api,
+                config.rpc.enable_admin_api,
+                Arc::clone(&rpc_apis_deps),
+            )?)
         } else {
             None
         }
@@ -290,7 +492,12 @@ pub fn run_node(matches: ArgMatches) -> Result<(), String> {
 
     let _ipc_server = {
         if !config.ipc.disable.unwrap() {
-            Some(rpc_ipc_start(config.rpc_ipc_config(), config.rpc.enable_devel_api, Arc::clone(&rpc_apis_deps))?)
+            Some(rpc_ipc_start(
+                config.rpc_ipc_config(),
+                config.rpc.enable_devel_api,
+                config.rpc.enable_admin_api,
+                Arc::clone(&rpc_apis_deps),
+            )?)
         } else {
             None
         }
@@ -311,6 +518,16 @@ pub fn run_node(matches: ArgMatches) -> Result<(), String> {
         }
     };
 
+    let _import_hook = {
+        if !config.import_hook.disable.unwrap() {
+            let hook = ImportHook::start(client.client(), config.import_hook_config()?)?;
+            client.client().add_notify(hook.clone());
+            Some(hook)
+        } else {
+            None
+        }
+    };
+
     // drop the scheme to free up genesis state.
     drop(scheme);
 
@@ -320,3 +537,109 @@ pub fn run_node(matches: ArgMatches) -> Result<(), String> {
 
     Ok(())
 }
+
+/// Runs a light client, which keeps only the recent headers synced from the nodes run with `--light-server`.
+/// The headers are kept in the `light` directory under the database path.
+pub fn run_light_node(matches: ArgMatches<'static>) -> Result<(), String> {
+    raise_fd_limit();
+
+    let _event_loop = EventLoop::spawn();
+    let config = load_config(&matches)?;
+    let scheme = match &config.operating.chain {
+        Some(chain) => chain.scheme()?,
+        None => return Err("chain is not specified".to_string()),
+    };
+    logger_start(&config.operating);
+    warn_deprecated_options(&config);
+
+    if config.network.disable.unwrap() {
+        return Err("The light client syncs the headers through the network, which is disabled.".to_string())
+    }
+
+    let mut client_config = ClientConfig::default();
+    client_config.db_backend = config.operating.db_backend.as_ref().unwrap().parse()?;
+    let db_path = Path::new(config.operating.db_path.as_ref().unwrap()).join("light");
+    let db =
+        open_database(&client_config, &db_path).map_err(|e| format!("Cannot open the light client database: {}", e))?;
+    let client = LightClient::new(&scheme, db).map_err(|e| format!("Light client error: {}", e))?;
+    let (best_header, _) = client.best_header();
+    cinfo!(CLIENT, "Light client starts from #{} ({})", best_header.number(), best_header.hash());
+
+    let network_config = config.network_config()?;
+    let chain = chain_identity(&scheme, client.clone());
+    let service = network_start(&network_config, node_allow_list(&scheme)?, chain)?;
+    if config.network.discovery.unwrap() {
+        discovery_start(&service, &config.network)?;
+    } else {
+        cwarn!(DISCOVERY, "Node runs without discovery extension");
+    }
+    service.register_extension(LightSyncExtension::client(client));
+    service.initialize_extensions();
+
+    for address in &network_config.bootstrap_addresses {
+        service.connect_to(*address)?;
+    }
+
+    cinfo!(TEST_SCRIPT, "Initialization complete");
+
+    wait_for_exit();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net;
+
+    use ckey::{sign, Generator, NetworkId, PlatformAddress, Random, Signature};
+    use cnetwork::{node_allow_list_hash, NodeId};
+    use serde_json::{self, Value};
+
+    use super::*;
+
+    fn scheme_with_allow_list(
+        authority: Address,
+        version: u64,
+        nodes: &[net::SocketAddr],
+        signature: &Signature,
+    ) -> Scheme {
+        let mut scheme: Value = serde_json::from_str(include_str!("../core/res/null.json")).unwrap();
+        scheme["nodeAllowList"] = json!({
+            "authority": PlatformAddress::create(0, NetworkId::default(), authority).to_string(),
+            "version": version,
+            "nodes": nodes,
+            "signature": signature,
+        });
+        Scheme::load(scheme.to_string().as_bytes()).unwrap()
+    }
+
+    fn addresses() -> Vec<net::SocketAddr> {
+        vec!["192.168.0.1:3485".parse().unwrap(), "192.168.0.2:3485".parse().unwrap()]
+    }
+
+    fn node_ids(addresses: &[net::SocketAddr]) -> Vec<NodeId> {
+        addresses.iter().map(|address| SocketAddr::from(*address).into()).collect()
+    }
+
+    #[test]
+    fn signed_allow_list_in_scheme_is_in_use() {
+        let authority = Random.generate().unwrap();
+        let addresses = addresses();
+        let signature = sign(authority.private(), &node_allow_list_hash(3, &node_ids(&addresses))).unwrap();
+        let scheme = scheme_with_allow_list(authority.address(), 3, &addresses, &signature);
+        assert_eq!(3, scheme.node_allow_list.as_ref().unwrap().version);
+
+        let allow_list = node_allow_list(&scheme).unwrap().unwrap();
+        assert_eq!(Some(3), allow_list.version());
+        assert_eq!(node_ids(&addresses), allow_list.nodes());
+    }
+
+    #[test]
+    fn allow_list_signed_with_another_version_is_rejected() {
+        let authority = Random.generate().unwrap();
+        let addresses = addresses();
+        let signature = sign(authority.private(), &node_allow_list_hash(2, &node_ids(&addresses))).unwrap();
+        let scheme = scheme_with_allow_list(authority.address(), 3, &addresses, &signature);
+        assert!(node_allow_list(&scheme).is_err());
+    }
+}
@@ -19,16 +19,19 @@ use std::str::FromStr;
 
 use rpassword;
 
-use ccore::AccountProvider;
+use ccore::{AccountProvider, KeyRotationAction};
 use ckey::{NetworkId, Password, PlatformAddress, Private};
 use ckeystore::accounts_dir::RootDiskDirectory;
 use ckeystore::KeyStore;
 use clap::ArgMatches;
 use clogger::{self, LoggerConfig};
 use primitives::clean_0x;
+use rlp::Encodable;
+use rustc_hex::ToHex;
 
 use super::super::config::ChainType;
 use super::super::constants::DEFAULT_KEYS_PATH;
+use super::get_global_argument;
 
 pub fn run_account_command(matches: ArgMatches) -> Result<(), String> {
     if matches.subcommand.is_none() {
@@ -64,6 +67,14 @@ pub fn run_account_command(matches: ArgMatches) -> Result<(), String> {
             let address = matches.value_of("ADDRESS").expect("ADDRESS arg is required and its index is 1");
             change_password(&ap, address)
         }
+        ("sign-key-rotation", Some(matches)) => {
+            let validator = matches.value_of("VALIDATOR").expect("VALIDATOR arg is required and its index is 1");
+            let new_key = matches.value_of("NEW_KEY").expect("NEW_KEY arg is required and its index is 2");
+            let activation = matches.value_of("ACTIVATION").expect("ACTIVATION arg is required and its index is 3");
+            let seq = matches.value_of("SEQ").expect("SEQ arg is required and its index is 4");
+            let signer = matches.value_of("signer").unwrap_or(validator);
+            sign_key_rotation(&ap, validator, new_key, activation, seq, signer)
+        }
         _ => Err("Invalid subcommand".to_string()),
     }
 }
@@ -151,6 +162,33 @@ fn change_password(ap: &AccountProvider, address: &str) -> Result<(), String> {
     Ok(())
 }
 
+fn sign_key_rotation(
+    ap: &AccountProvider,
+    validator: &str,
+    new_key: &str,
+    activation: &str,
+    seq: &str,
+    signer: &str,
+) -> Result<(), String> {
+    let validator = PlatformAddress::from_str(validator).map_err(|e| format!("{:?}", e))?.into_address();
+    let new_key = PlatformAddress::from_str(new_key).map_err(|e| format!("{:?}", e))?.into_address();
+    let activation = activation.parse().map_err(|_| format!("Invalid block number: {}", activation))?;
+    let seq = seq.parse().map_err(|_| format!("Invalid seq: {}", seq))?;
+    let signer = PlatformAddress::from_str(signer).map_err(|e| format!("{:?}", e))?.into_address();
+    let password = prompt_password("Password: ");
+    let message = KeyRotationAction::message(&validator, &new_key, activation, seq);
+    let signature = ap.sign(signer, Some(password), message).map_err(|e| format!("{:?}", e))?;
+    let action = KeyRotationAction {
+        validator,
+        new_key,
+        activation,
+        seq,
+        signature,
+    };
+    println!("0x{}", action.rlp_bytes().to_hex());
+    Ok(())
+}
+
 fn prompt_password(prompt: &str) -> Password {
     Password::from(rpassword::prompt_password_stdout(prompt).unwrap())
 }
@@ -164,13 +202,3 @@ fn read_password_and_confirm() -> Option<Password> {
         None
     }
 }
-
-fn get_global_argument(matches: &ArgMatches, arg_name: &str) -> Option<String> {
-    match matches.value_of(arg_name) {
-        Some(value) => Some(value.to_string()),
-        None => match matches.subcommand() {
-            (_, Some(matches)) => matches.value_of(arg_name).map(|s| s.to_string()),
-            _ => None,
-        },
-    }
-}
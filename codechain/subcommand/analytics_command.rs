@@ -0,0 +1,340 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ccore::{parcel_topics, BlockChainClient, BlockId, ChainInfo, EngineInfo, ParcelId, SignedParcel, UnverifiedParcel};
+use ckey::{public_to_address, NetworkId, PlatformAddress};
+use clap::ArgMatches;
+use clogger::{self, LoggerConfig};
+use ctypes::invoice::{ParcelInvoice, TransactionInvoice};
+use ctypes::parcel::Action;
+use ctypes::transaction::Transaction;
+use primitives::H256;
+use serde_json;
+
+use super::blocks_command::{check_bodies_kept, parse_block_number, start_client};
+
+/// The versions of the column layout that this binary can write.
+/// A new version is added whenever a column is added, removed or changes its meaning.
+/// Version 2 adds the topics of the parcels in logs.csv.
+const SCHEMA_VERSIONS: [u32; 2] = [1, 2];
+
+const BLOCK_COLUMNS: [&str; 10] = [
+    "number",
+    "hash",
+    "parent_hash",
+    "author",
+    "timestamp",
+    "score",
+    "state_root",
+    "parcels_root",
+    "invoices_root",
+    "parcel_count",
+];
+const PARCEL_COLUMNS: [&str; 9] =
+    ["block_number", "block_hash", "index", "hash", "signer", "nonce", "fee", "network_id", "action"];
+const TRANSACTION_COLUMNS: [&str; 7] =
+    ["block_number", "parcel_hash", "parcel_index", "index", "hash", "type", "network_id"];
+const EVENT_COLUMNS: [&str; 5] = ["block_number", "parcel_hash", "transaction_index", "outcome", "error"];
+const LOG_COLUMNS: [&str; 5] = ["block_number", "parcel_hash", "parcel_index", "topic_index", "topic"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata<'a> {
+    schema_version: u32,
+    format: &'a str,
+    network_id: String,
+    from: u64,
+    to: u64,
+}
+
+pub fn run_export_analytics_command(matches: ArgMatches) -> Result<(), String> {
+    clogger::init(&LoggerConfig::new(0)).expect("Logger must be successfully initialized");
+
+    let schema_version = match matches.value_of("schema-version") {
+        Some(version) => version.parse().map_err(|_| format!("Invalid schema version {}", version))?,
+        None => *SCHEMA_VERSIONS.last().unwrap(),
+    };
+    if !SCHEMA_VERSIONS.contains(&schema_version) {
+        return Err(format!(
+            "Unsupported schema version {}. Supported versions are {:?}",
+            schema_version, SCHEMA_VERSIONS
+        ))
+    }
+
+    let service = start_client(&matches)?;
+    let client = service.client();
+    let network_id = client.common_params().network_id;
+
+    let best_block_number = client.chain_info().best_block_number;
+    let from = parse_block_number(matches.value_of("from"), 0)?;
+    let to = parse_block_number(matches.value_of("to"), best_block_number)?;
+    if from > to {
+        return Err(format!("--from({}) is greater than --to({})", from, to))
+    }
+    if to > best_block_number {
+        return Err(format!("--to({}) is greater than the best block number({})", to, best_block_number))
+    }
+    check_bodies_kept(&*client, from)?;
+
+    let dir = Path::new(matches.value_of("DIR").expect("DIR arg is required and its index is 1"));
+    fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let mut blocks = CsvWriter::create(&dir.join("blocks.csv"), &BLOCK_COLUMNS)?;
+    let mut parcels = CsvWriter::create(&dir.join("parcels.csv"), &PARCEL_COLUMNS)?;
+    let mut transactions = CsvWriter::create(&dir.join("transactions.csv"), &TRANSACTION_COLUMNS)?;
+    let mut events = CsvWriter::create(&dir.join("events.csv"), &EVENT_COLUMNS)?;
+    let mut logs = if schema_version >= 2 {
+        Some(CsvWriter::create(&dir.join("logs.csv"), &LOG_COLUMNS)?)
+    } else {
+        None
+    };
+
+    for number in from..=to {
+        let block = client.block(BlockId::Number(number)).ok_or_else(|| format!("The body of #{} is pruned", number))?;
+        let block_hash = block.hash();
+        let block_parcels = block.parcels();
+        blocks.write_row(&[
+            number.to_string(),
+            hex(&block_hash),
+            hex(&block.parent_hash()),
+            PlatformAddress::create(0, network_id, block.author()).to_string(),
+            block.timestamp().to_string(),
+            block.score().to_string(),
+            hex(&block.state_root()),
+            hex(&block.parcels_root()),
+            hex(&block.invoices_root()),
+            block_parcels.len().to_string(),
+        ])?;
+
+        for (index, parcel) in block_parcels.into_iter().enumerate() {
+            let parcel_hash = parcel.hash();
+            parcels.write_row(&[
+                number.to_string(),
+                hex(&block_hash),
+                index.to_string(),
+                hex(&parcel_hash),
+                signer(parcel.clone(), network_id)?,
+                parcel.nonce.to_string(),
+                parcel.fee.to_string(),
+                parcel.network_id.to_string(),
+                action_name(&parcel.action).to_string(),
+            ])?;
+
+            if let Some(logs) = logs.as_mut() {
+                for (topic_index, topic) in parcel_topics(&parcel).iter().enumerate() {
+                    logs.write_row(&[
+                        number.to_string(),
+                        hex(&parcel_hash),
+                        index.to_string(),
+                        topic_index.to_string(),
+                        hex(topic),
+                    ])?;
+                }
+            }
+
+            if let Action::AssetTransactionGroup {
+                transactions: parcel_transactions,
+                ..
+            } = &parcel.action
+            {
+                for (transaction_index, transaction) in parcel_transactions.iter().enumerate() {
+                    transactions.write_row(&[
+                        number.to_string(),
+                        hex(&parcel_hash),
+                        index.to_string(),
+                        transaction_index.to_string(),
+                        hex(&transaction.hash()),
+                        transaction_type(transaction).to_string(),
+                        transaction.network_id().to_string(),
+                    ])?;
+                }
+            }
+
+            match client.parcel_invoice(ParcelId::Hash(parcel_hash)) {
+                Some(ParcelInvoice::SingleSuccess) => events.write_row(&[
+                    number.to_string(),
+                    hex(&parcel_hash),
+                    String::new(),
+                    "success".to_string(),
+                    String::new(),
+                ])?,
+                Some(ParcelInvoice::SingleFail(error)) => events.write_row(&[
+                    number.to_string(),
+                    hex(&parcel_hash),
+                    String::new(),
+                    "fail".to_string(),
+                    error.to_string(),
+                ])?,
+                Some(ParcelInvoice::Multiple(invoices)) => {
+                    for (transaction_index, invoice) in invoices.into_iter().enumerate() {
+                        let (outcome, error) = match invoice {
+                            TransactionInvoice::Success => ("success", String::new()),
+                            TransactionInvoice::Fail(error) => ("fail", error.to_string()),
+                        };
+                        events.write_row(&[
+                            number.to_string(),
+                            hex(&parcel_hash),
+                            transaction_index.to_string(),
+                            outcome.to_string(),
+                            error,
+                        ])?;
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+    blocks.finish()?;
+    parcels.finish()?;
+    transactions.finish()?;
+    events.finish()?;
+    if let Some(logs) = logs {
+        logs.finish()?;
+    }
+
+    let metadata = Metadata {
+        schema_version,
+        format: "csv",
+        network_id: network_id.to_string(),
+        from,
+        to,
+    };
+    let metadata_path = dir.join("metadata.json");
+    let metadata = serde_json::to_string_pretty(&metadata).expect("Metadata is always serializable");
+    fs::write(&metadata_path, metadata).map_err(|e| format!("Cannot write to {}: {}", metadata_path.display(), e))?;
+
+    println!("Blocks from #{} to #{} are exported to {}", from, to, dir.display());
+    Ok(())
+}
+
+/// Writes the rows one by one so that the whole range never has to be in memory.
+pub struct CsvWriter {
+    path: String,
+    file: BufWriter<File>,
+}
+
+impl CsvWriter {
+    pub fn create(path: &Path, columns: &[&str]) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+        let mut writer = CsvWriter {
+            path: path.display().to_string(),
+            file: BufWriter::new(file),
+        };
+        let columns: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+        writer.write_row(&columns)?;
+        Ok(writer)
+    }
+
+    pub fn write_row(&mut self, fields: &[String]) -> Result<(), String> {
+        let line = fields.iter().map(|field| escape(field)).collect::<Vec<_>>().join(",");
+        writeln!(self.file, "{}", line).map_err(|e| format!("Cannot write to {}: {}", self.path, e))
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.file.flush().map_err(|e| format!("Cannot write to {}: {}", self.path, e))
+    }
+}
+
+/// Quotes the field as RFC 4180 requires.
+fn escape(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn hex(hash: &H256) -> String {
+    format!("0x{:x}", hash)
+}
+
+fn signer(parcel: UnverifiedParcel, network_id: NetworkId) -> Result<String, String> {
+    let hash = parcel.hash();
+    let parcel = SignedParcel::new(parcel).map_err(|e| format!("Cannot recover the signer of {}: {}", hex(&hash), e))?;
+    Ok(PlatformAddress::create(0, network_id, public_to_address(&parcel.signer_public())).to_string())
+}
+
+fn action_name(action: &Action) -> &'static str {
+    match action {
+        Action::AssetTransactionGroup {
+            ..
+        } => "assetTransactionGroup",
+        Action::Payment {
+            ..
+        } => "payment",
+        Action::SetRegularKey {
+            ..
+        } => "setRegularKey",
+        Action::CreateShard => "createShard",
+        Action::SetShardOwners {
+            ..
+        } => "setShardOwners",
+        Action::SetShardUsers {
+            ..
+        } => "setShardUsers",
+        Action::RegisterName {
+            ..
+        } => "registerName",
+        Action::UpdateName {
+            ..
+        } => "updateName",
+        Action::TransferName {
+            ..
+        } => "transferName",
+        Action::Custom(_) => "custom",
+    }
+}
+
+fn transaction_type(transaction: &Transaction) -> &'static str {
+    match transaction {
+        Transaction::CreateWorld {
+            ..
+        } => "createWorld",
+        Transaction::SetWorldOwners {
+            ..
+        } => "setWorldOwners",
+        Transaction::SetWorldUsers {
+            ..
+        } => "setWorldUsers",
+        Transaction::AssetMint {
+            ..
+        } => "assetMint",
+        Transaction::AssetTransfer {
+            ..
+        } => "assetTransfer",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_fields_are_not_quoted() {
+        assert_eq!("payment", escape("payment"));
+    }
+
+    #[test]
+    fn fields_with_separators_are_quoted() {
+        assert_eq!("\"a,b\"", escape("a,b"));
+        assert_eq!("\"say \"\"hi\"\"\"", escape("say \"hi\""));
+        assert_eq!("\"a\nb\"", escape("a\nb"));
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use ccore::{
+    BlockChainClient, BlockId, BlockImportError, BlockInfo, BlockStatus, ChainInfo, ClientService, ImportBlock,
+    ImportError, Miner, Scheme,
+};
+use clap::ArgMatches;
+use clogger::{self, LoggerConfig};
+use primitives::H256;
+use rlp::{DecoderError, PayloadInfo};
+
+use super::super::config::ChainType;
+use super::super::constants::DEFAULT_DB_PATH;
+
+const QUEUE_POLL_INTERVAL_MS: u64 = 100;
+
+/// Fails if the bodies of the blocks from `from` are pruned.
+pub fn check_bodies_kept<C: BlockChainClient>(client: &C, from: u64) -> Result<(), String> {
+    let earliest_body_number = client.earliest_body_number();
+    if from < earliest_body_number {
+        return Err(format!(
+            "The bodies below #{} are pruned. Use --from {} or a node which keeps every body",
+            earliest_body_number, earliest_body_number
+        ))
+    }
+    Ok(())
+}
+
+pub fn run_export_blocks_command(matches: ArgMatches) -> Result<(), String> {
+    clogger::init(&LoggerConfig::new(0)).expect("Logger must be successfully initialized");
+
+    let service = start_client(&matches)?;
+    let client = service.client();
+
+    let best_block_number = client.chain_info().best_block_number;
+    let from = parse_block_number(matches.value_of("from"), 0)?;
+    let to = parse_block_number(matches.value_of("to"), best_block_number)?;
+    if from > to {
+        return Err(format!("--from({}) is greater than --to({})", from, to))
+    }
+    if to > best_block_number {
+        return Err(format!("--to({}) is greater than the best block number({})", to, best_block_number))
+    }
+    check_bodies_kept(&*client, from)?;
+
+    let path = matches.value_of("FILE").expect("FILE arg is required and its index is 1");
+    let mut file = File::create(path).map_err(|e| format!("Cannot create {}: {}", path, e))?;
+    for number in from..=to {
+        let block = client.block(BlockId::Number(number)).ok_or_else(|| format!("The body of #{} is pruned", number))?;
+        file.write_all(&block.into_inner()).map_err(|e| format!("Cannot write to {}: {}", path, e))?;
+    }
+    file.flush().map_err(|e| format!("Cannot write to {}: {}", path, e))?;
+    println!("Blocks from #{} to #{} are exported to {}", from, to, path);
+    Ok(())
+}
+
+pub fn run_import_blocks_command(matches: ArgMatches) -> Result<(), String> {
+    clogger::init(&LoggerConfig::new(0)).expect("Logger must be successfully initialized");
+
+    let path = matches.value_of("FILE").expect("FILE arg is required and its index is 1");
+    let mut reader = File::open(path).map(BufReader::new).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+
+    let service = start_client(&matches)?;
+    let client = service.client();
+
+    let mut imported = 0;
+    let mut skipped = 0;
+    let mut failed = Vec::new();
+    // The hashes and the offsets of the blocks which are not verified yet.
+    let mut queued = VecDeque::new();
+    let mut offset = 0;
+    while let Some(block) = read_block(&mut reader, path, offset)? {
+        let size = block.len();
+        while client.queue_info().is_full() {
+            settle(&*client, &mut queued, &mut imported, &mut failed);
+            thread::sleep(Duration::from_millis(QUEUE_POLL_INTERVAL_MS));
+        }
+        match client.import_block(block) {
+            Ok(hash) => queued.push_back((hash, offset)),
+            Err(BlockImportError::Import(ImportError::AlreadyInChain))
+            | Err(BlockImportError::Import(ImportError::AlreadyQueued)) => skipped += 1,
+            Err(e) => return Err(format!("Cannot import the block at byte {}: {:?}", offset, e)),
+        }
+        offset += size;
+    }
+
+    // Blocks are verified and imported asynchronously.
+    while !client.queue_info().is_empty() {
+        thread::sleep(Duration::from_millis(QUEUE_POLL_INTERVAL_MS));
+    }
+    settle(&*client, &mut queued, &mut imported, &mut failed);
+
+    let best_block_number = client.chain_info().best_block_number;
+    println!("{} blocks are imported and {} are skipped. The best block is #{}", imported, skipped, best_block_number);
+    if let Some(offset) = failed.first() {
+        return Err(format!("{} blocks are rejected. The first one is at byte {}", failed.len(), offset))
+    }
+    Ok(())
+}
+
+/// Reads the next block of the concatenated RLP. Returns `None` at the end of the file.
+fn read_block<R: BufRead>(reader: &mut R, path: &str, offset: usize) -> Result<Option<Vec<u8>>, String> {
+    let read_error = |e: io::Error| match e.kind() {
+        ErrorKind::UnexpectedEof => format!("The block at byte {} is truncated", offset),
+        _ => format!("Cannot read {}: {}", path, e),
+    };
+    let first = match reader.fill_buf().map_err(&read_error)?.first() {
+        Some(first) => *first,
+        None => return Ok(None),
+    };
+    let header_len = match first {
+        0xc0...0xf7 => 1,
+        0xf8...0xff => 1 + (first - 0xf7) as usize,
+        _ => return Err(format!("Invalid block at byte {}: {}", offset, DecoderError::RlpExpectedToBeList)),
+    };
+    let mut block = vec![0; header_len];
+    reader.read_exact(&mut block).map_err(&read_error)?;
+    let info = PayloadInfo::from(&block).map_err(|e| format!("Invalid block at byte {}: {}", offset, e))?;
+    // The payload is read as it arrives, so a corrupted length doesn't allocate the memory at once.
+    reader.by_ref().take(info.value_len as u64).read_to_end(&mut block).map_err(&read_error)?;
+    if block.len() < info.total() {
+        return Err(format!("The block at byte {} is truncated", offset))
+    }
+    Ok(Some(block))
+}
+
+/// Counts the queued blocks which are imported or rejected, in the order they are queued.
+fn settle<C: BlockChainClient>(
+    client: &C,
+    queued: &mut VecDeque<(H256, usize)>,
+    imported: &mut usize,
+    failed: &mut Vec<usize>,
+) {
+    loop {
+        let (hash, offset) = match queued.front() {
+            Some(&front) => front,
+            None => break,
+        };
+        match client.block_status(BlockId::Hash(hash)) {
+            BlockStatus::Queued => break,
+            BlockStatus::InChain => *imported += 1,
+            _ => failed.push(offset),
+        }
+        queued.pop_front();
+    }
+}
+
+pub fn start_client(matches: &ArgMatches) -> Result<ClientService, String> {
+    let scheme = load_scheme(matches)?;
+    start_client_with_scheme(matches, &scheme)
+}
+
+pub fn load_scheme(matches: &ArgMatches) -> Result<Scheme, String> {
+    let chain = matches.value_of("chain").unwrap_or("solo");
+    ChainType::from_str(chain)?.scheme()
+}
+
+pub fn start_client_with_scheme(matches: &ArgMatches, scheme: &Scheme) -> Result<ClientService, String> {
+    let db_path = matches.value_of("db-path").unwrap_or(DEFAULT_DB_PATH);
+
+    let miner = Miner::new(Default::default(), scheme, None);
+    ClientService::start(Default::default(), scheme, Path::new(db_path), miner)
+        .map_err(|e| format!("Client service error: {}", e))
+}
+
+pub fn parse_block_number(value: Option<&str>, default: u64) -> Result<u64, String> {
+    match value {
+        Some(value) => value.parse().map_err(|_| format!("Invalid block number {}", value)),
+        None => Ok(default),
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::Arc;
+
+use ccore::{migrate_database, open_database, DB_VERSION};
+use clap::ArgMatches;
+use clogger::{self, LoggerConfig};
+use kvdb::KeyValueDB;
+use serde_json::{self, Value};
+
+use super::super::constants::DEFAULT_DB_PATH;
+use super::get_global_argument;
+
+pub fn run_db_command(matches: ArgMatches) -> Result<(), String> {
+    if matches.subcommand.is_none() {
+        println!("{}", matches.usage());
+        return Ok(())
+    }
+
+    clogger::init(&LoggerConfig::new(0)).expect("Logger must be successfully initialized");
+
+    let db_path = get_global_argument(&matches, "db-path").unwrap_or(DEFAULT_DB_PATH.into());
+
+    match matches.subcommand() {
+        ("backup", Some(matches)) => {
+            let path = matches.value_of("PATH").expect("PATH arg is required and its index is 1");
+            match matches.value_of("ipc") {
+                // A running node holds the lock of its database, so it copies the database itself.
+                Some(ipc_path) => backup_running_node(ipc_path, path),
+                None => backup(&db_path, path),
+            }
+        }
+        ("restore", Some(matches)) => {
+            let path = matches.value_of("PATH").expect("PATH arg is required and its index is 1");
+            restore(&db_path, path)
+        }
+        ("migrate", _) => migrate(&db_path),
+        _ => Err("Invalid subcommand".to_string()),
+    }
+}
+
+fn open(path: &str) -> Result<Arc<KeyValueDB>, String> {
+    open_database(&Default::default(), Path::new(path))
+        .map_err(|e| format!("Cannot open the database at {}: {}", path, e))
+}
+
+fn backup(db_path: &str, path: &str) -> Result<(), String> {
+    open(db_path)?.backup(path).map_err(|e| format!("{}", e))?;
+    println!("The database is copied to {}", path);
+    Ok(())
+}
+
+fn backup_running_node(ipc_path: &str, path: &str) -> Result<(), String> {
+    // The node may run in another directory.
+    let path = env::current_dir().map_err(|e| format!("{}", e))?.join(path);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "method": "devel_backupDatabase",
+        "params": [path],
+        "id": 1
+    });
+
+    let mut stream =
+        UnixStream::connect(ipc_path).map_err(|e| format!("Cannot connect to the node at {}: {}", ipc_path, e))?;
+    writeln!(stream, "{}", request).map_err(|e| format!("Cannot send the request to the node: {}", e))?;
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .map_err(|e| format!("Cannot read the response of the node: {}", e))?;
+
+    let response: Value =
+        serde_json::from_str(&response).map_err(|e| format!("Invalid response from the node: {}", e))?;
+    if let Some(error) = response.get("error") {
+        // The devel API must be enabled on the node.
+        return Err(format!("The node cannot back up the database: {}", error))
+    }
+    println!("The node copied the database to {}", path.display());
+    Ok(())
+}
+
+fn restore(db_path: &str, path: &str) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        return Err(format!("There is no backup at {}", path))
+    }
+    // Restoring moves the database into place, so restore a copy to keep the backup.
+    let copy_path = Path::new(db_path).with_file_name("restoring_db");
+    let copy_path = copy_path.to_str().expect("DB path could not be converted to string.");
+    open(path)?.backup(copy_path).map_err(|e| format!("{}", e))?;
+
+    let db = open(db_path)?;
+    db.restore(copy_path).map_err(|e| format!("{}", e))?;
+    println!("The database is restored from {}", path);
+    Ok(())
+}
+
+fn migrate(db_path: &str) -> Result<(), String> {
+    let db = open(db_path)?;
+    let from = migrate_database(&*db).map_err(|e| format!("Cannot migrate the database: {}", e))?;
+    if from == DB_VERSION {
+        println!("The database is already at version {}", DB_VERSION);
+    } else {
+        println!("The database is migrated from version {} to {}", from, DB_VERSION);
+    }
+    Ok(())
+}
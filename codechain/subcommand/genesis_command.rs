@@ -0,0 +1,483 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use ccore::Scheme;
+use ckey::{Address, NetworkId, PlatformAddress};
+use clap::ArgMatches;
+use primitives::{H256, U256};
+use serde_json;
+use toml;
+
+const ENGINES: [&str; 4] = ["solo", "simple_poa", "clique", "tendermint"];
+const DEFAULT_MIN_PARCEL_COST: &str = "10";
+
+/// The description of a new chain. It's read from a TOML file, or asked interactively.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct GenesisInput {
+    name: String,
+    network_id: String,
+    /// One of `ENGINES`.
+    engine: String,
+    /// The addresses of the validators. Every engine except solo needs at least one.
+    #[serde(default)]
+    validators: Vec<String>,
+    block_reward: Option<String>,
+    /// Minimum seconds between blocks. Only for clique.
+    block_period: Option<u64>,
+    min_parcel_cost: Option<String>,
+    #[serde(default)]
+    timestamp: u64,
+    /// The balances of the premined accounts, keyed by their addresses.
+    #[serde(default)]
+    accounts: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainSpec {
+    name: String,
+    engine: Engine,
+    params: Params,
+    genesis: Genesis,
+    accounts: BTreeMap<String, AccountSpec>,
+    shards: BTreeMap<u16, ()>,
+}
+
+#[derive(Serialize)]
+enum Engine {
+    #[serde(rename = "solo")]
+    Solo {
+        params: EngineParams,
+    },
+    #[serde(rename = "simplePoA")]
+    SimplePoA {
+        params: EngineParams,
+    },
+    #[serde(rename = "clique")]
+    Clique {
+        params: EngineParams,
+    },
+    #[serde(rename = "tendermint")]
+    Tendermint {
+        params: EngineParams,
+    },
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EngineParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validators: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_reward: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Params {
+    max_extra_data_size: String,
+    max_metadata_size: String,
+    #[serde(rename = "networkID")]
+    network_id: String,
+    min_parcel_cost: String,
+    max_body_size: u64,
+    snapshot_period: u64,
+    use_shard_validator: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Seal {
+    Generic(String),
+    Tendermint {
+        round: String,
+        proposal: String,
+        precommits: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Genesis {
+    seal: Seal,
+    score: String,
+    author: String,
+    timestamp: String,
+    parent_hash: String,
+    extra_data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_root: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AccountSpec {
+    balance: String,
+    nonce: String,
+}
+
+pub fn run_genesis_command(matches: ArgMatches) -> Result<(), String> {
+    if matches.subcommand.is_none() {
+        println!("{}", matches.usage());
+        return Ok(())
+    }
+
+    match matches.subcommand() {
+        ("new", Some(matches)) => new(matches),
+        _ => Err("Invalid subcommand".to_string()),
+    }
+}
+
+fn new(matches: &ArgMatches) -> Result<(), String> {
+    let path = Path::new(matches.value_of("PATH").expect("PATH arg is required and its index is 1"));
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()))
+    }
+
+    let input = match matches.value_of("input") {
+        Some(input_path) => {
+            let mut content = String::new();
+            File::open(input_path)
+                .and_then(|mut file| file.read_to_string(&mut content))
+                .map_err(|e| format!("Cannot read {}: {}", input_path, e))?;
+            toml::from_str(&content).map_err(|e| format!("Invalid input {}: {}", input_path, e))?
+        }
+        None => {
+            let stdin = io::stdin();
+            let mut lines = stdin.lock();
+            ask(&mut lines)?
+        }
+    };
+
+    let (spec, scheme) = generate(&input)?;
+    fs::write(path, spec).map_err(|e| format!("Cannot write to {}: {}", path.display(), e))?;
+    println!("The chain spec is written to {}", path.display());
+    println!("Genesis state root: 0x{:x}", scheme.state_root());
+    println!("Genesis block hash: 0x{:x}", scheme.genesis_header().hash());
+    Ok(())
+}
+
+/// Returns the chain spec and the scheme loaded from it. The spec has the genesis state root, which is
+/// computed by loading the spec once without it.
+fn generate(input: &GenesisInput) -> Result<(String, Scheme), String> {
+    let mut spec = build(input)?;
+    let without_root = serde_json::to_string_pretty(&spec).expect("The chain spec is always serializable");
+    let scheme = Scheme::load(without_root.as_bytes()).map_err(|e| format!("Invalid chain spec: {}", e))?;
+
+    spec.genesis.state_root = Some(format!("0x{:x}", scheme.state_root()));
+    let spec = serde_json::to_string_pretty(&spec).expect("The chain spec is always serializable");
+    Ok((spec, scheme))
+}
+
+fn build(input: &GenesisInput) -> Result<ChainSpec, String> {
+    if input.name.is_empty() {
+        return Err("The name of the chain is empty".to_string())
+    }
+    let network_id = NetworkId::from_str(&input.network_id)
+        .map_err(|e| format!("Invalid network id {}: {}", input.network_id, e))?;
+    if !ENGINES.contains(&input.engine.as_str()) {
+        return Err(format!("Invalid engine {}. It must be one of {:?}", input.engine, ENGINES))
+    }
+
+    let validators = input
+        .validators
+        .iter()
+        .map(|validator| parse_address(validator, network_id).map(|address| address.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if input.engine == "solo" && !validators.is_empty() {
+        return Err("The solo engine doesn't have validators".to_string())
+    }
+    if input.engine != "solo" && validators.is_empty() {
+        return Err(format!("The {} engine needs at least one validator", input.engine))
+    }
+    if input.block_period.is_some() && input.engine != "clique" {
+        return Err("Only the clique engine has the block period".to_string())
+    }
+    let block_reward = match &input.block_reward {
+        Some(block_reward) => Some(parse_amount(block_reward)?.to_string()),
+        None => None,
+    };
+    let min_parcel_cost =
+        parse_amount(input.min_parcel_cost.as_ref().map(String::as_str).unwrap_or(DEFAULT_MIN_PARCEL_COST))?;
+
+    let mut accounts = BTreeMap::new();
+    for (address, balance) in &input.accounts {
+        let address = parse_address(address, network_id)?.to_string();
+        let balance = parse_amount(balance)?;
+        let account = AccountSpec {
+            balance: balance.to_string(),
+            nonce: "0".to_string(),
+        };
+        if accounts.contains_key(&address) {
+            return Err(format!("{} is allocated twice", address))
+        }
+        accounts.insert(address, account);
+    }
+
+    let params = EngineParams {
+        validators: if validators.is_empty() {
+            None
+        } else {
+            Some(validators)
+        },
+        period: input.block_period.map(|period| period.to_string()),
+        block_reward,
+    };
+    let (engine, seal) = match input.engine.as_str() {
+        "solo" => (
+            Engine::Solo {
+                params,
+            },
+            Seal::Generic("0x0".to_string()),
+        ),
+        "simple_poa" => (
+            Engine::SimplePoA {
+                params,
+            },
+            Seal::Generic("0xc180".to_string()),
+        ),
+        "clique" => (
+            Engine::Clique {
+                params,
+            },
+            Seal::Generic("0xc180".to_string()),
+        ),
+        "tendermint" => (
+            Engine::Tendermint {
+                params,
+            },
+            Seal::Tendermint {
+                round: "0x0".to_string(),
+                proposal: empty_signature(),
+                precommits: vec![empty_signature()],
+            },
+        ),
+        _ => unreachable!("The engine is checked above"),
+    };
+
+    Ok(ChainSpec {
+        name: input.name.clone(),
+        engine,
+        params: Params {
+            max_extra_data_size: "0x20".to_string(),
+            max_metadata_size: "0x0400".to_string(),
+            network_id: network_id.to_string(),
+            min_parcel_cost: min_parcel_cost.to_string(),
+            max_body_size: 4_194_304,
+            snapshot_period: 16_384,
+            use_shard_validator: false,
+        },
+        genesis: Genesis {
+            seal,
+            score: "0x20000".to_string(),
+            author: PlatformAddress::create(0, network_id, Address::default()).to_string(),
+            timestamp: format!("0x{:x}", input.timestamp),
+            parent_hash: format!("0x{:x}", H256::zero()),
+            extra_data: "0x".to_string(),
+            state_root: None,
+        },
+        accounts,
+        shards: BTreeMap::new(),
+    })
+}
+
+/// Accepts only the addresses of the network, so that a key of another network is not allocated by mistake.
+fn parse_address(address: &str, network_id: NetworkId) -> Result<PlatformAddress, String> {
+    let parsed = PlatformAddress::from_str(address).map_err(|e| format!("Invalid address {}: {}", address, e))?;
+    parsed.try_address().map_err(|e| format!("Invalid address {}: {}", address, e))?;
+    if parsed.network_id != network_id {
+        return Err(format!("{} is an address of the network {}, not {}", address, parsed.network_id, network_id))
+    }
+    Ok(parsed)
+}
+
+/// The genesis block isn't signed, so its seal has the signatures filled with zeros.
+fn empty_signature() -> String {
+    format!("0x{}", "00".repeat(65))
+}
+
+fn parse_amount(amount: &str) -> Result<U256, String> {
+    U256::from_dec_str(amount).map_err(|_| format!("Invalid amount {}. It must be a decimal number", amount))
+}
+
+/// Asks the fields of `GenesisInput` one by one.
+fn ask<R: BufRead>(lines: &mut R) -> Result<GenesisInput, String> {
+    let name = prompt(lines, "Chain name")?;
+    let network_id = prompt(lines, "Network id (two characters)")?;
+    let engine = prompt(lines, &format!("Engine ({})", ENGINES.join(", ")))?;
+    let validators = if engine == "solo" {
+        Vec::new()
+    } else {
+        split_list(&prompt(lines, "Validator addresses (comma-separated)")?)
+    };
+    let block_reward = optional(prompt(lines, "Block reward (empty for none)")?);
+    let block_period = if engine == "clique" {
+        match optional(prompt(lines, "Minimum seconds between blocks (empty for none)")?) {
+            Some(period) => Some(period.parse().map_err(|_| format!("Invalid block period {}", period))?),
+            None => None,
+        }
+    } else {
+        None
+    };
+    let min_parcel_cost =
+        optional(prompt(lines, &format!("Minimum parcel cost (empty for {})", DEFAULT_MIN_PARCEL_COST))?);
+
+    let mut accounts = BTreeMap::new();
+    loop {
+        let allocation = prompt(lines, "Premine as ADDRESS BALANCE (empty to finish)")?;
+        if allocation.is_empty() {
+            break
+        }
+        let mut words = allocation.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some(address), Some(balance), None) => {
+                accounts.insert(address.to_string(), balance.to_string());
+            }
+            _ => println!("Type an address and a balance separated by a space"),
+        }
+    }
+
+    Ok(GenesisInput {
+        name,
+        network_id,
+        engine,
+        validators,
+        block_reward,
+        block_period,
+        min_parcel_cost,
+        timestamp: 0,
+        accounts,
+    })
+}
+
+fn prompt<R: BufRead>(lines: &mut R, message: &str) -> Result<String, String> {
+    print!("{}: ", message);
+    io::stdout().flush().map_err(|e| format!("{}", e))?;
+    let mut line = String::new();
+    lines.read_line(&mut line).map_err(|e| format!("Cannot read the input: {}", e))?;
+    Ok(line.trim().to_string())
+}
+
+fn optional(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALIDATOR: &str = "tccqqtk3q3rea46cq4cpa4h5tm43nw3supd6uxtltxv";
+    const ACCOUNT: &str = "tccqzn9jjm3j6qg69smd7cn0eup4w7z2yu9my9a2k78";
+
+    fn input(engine: &str) -> GenesisInput {
+        let mut accounts = BTreeMap::new();
+        accounts.insert(ACCOUNT.to_string(), "1000000".to_string());
+        GenesisInput {
+            name: "Test".to_string(),
+            network_id: "tc".to_string(),
+            engine: engine.to_string(),
+            validators: if engine == "solo" {
+                vec![]
+            } else {
+                vec![VALIDATOR.to_string()]
+            },
+            accounts,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn generated_specs_are_loadable() {
+        for engine in ENGINES.iter() {
+            let (spec, scheme) = generate(&input(engine)).unwrap();
+            let reloaded = Scheme::load(spec.as_bytes()).unwrap();
+            assert_eq!(scheme.state_root(), reloaded.state_root());
+            assert_eq!(scheme.genesis_header().hash(), reloaded.genesis_header().hash());
+        }
+    }
+
+    #[test]
+    fn input_from_toml() {
+        let content = r#"
+            name = "Test"
+            network_id = "tc"
+            engine = "clique"
+            validators = ["tccqqtk3q3rea46cq4cpa4h5tm43nw3supd6uxtltxv"]
+            block_period = 5
+
+            [accounts]
+            tccqzn9jjm3j6qg69smd7cn0eup4w7z2yu9my9a2k78 = "1000000"
+        "#;
+        let parsed: GenesisInput = toml::from_str(content).unwrap();
+        let mut expected = input("clique");
+        expected.block_period = Some(5);
+        assert_eq!(expected, parsed);
+    }
+
+    #[test]
+    fn invalid_inputs_are_rejected() {
+        let mut wrong_network = input("solo");
+        wrong_network.network_id = "cc".to_string();
+        assert!(build(&wrong_network).is_err());
+
+        let mut no_validator = input("tendermint");
+        no_validator.validators.clear();
+        assert!(build(&no_validator).is_err());
+
+        let mut invalid_balance = input("solo");
+        invalid_balance.accounts.insert(VALIDATOR.to_string(), "0x10".to_string());
+        assert!(build(&invalid_balance).is_err());
+
+        let mut invalid_address = input("solo");
+        invalid_address.accounts.insert("tccq".to_string(), "10".to_string());
+        assert!(build(&invalid_address).is_err());
+
+        let mut unknown_engine = input("solo");
+        unknown_engine.engine = "pow".to_string();
+        assert!(build(&unknown_engine).is_err());
+    }
+
+    #[test]
+    fn account_allocated_twice_is_rejected() {
+        let mut duplicated = input("solo");
+        // Bech32 addresses are case-insensitive, so both keys are the same account.
+        duplicated.accounts.insert(ACCOUNT.to_uppercase(), "10".to_string());
+        assert_eq!(Err(format!("{} is allocated twice", ACCOUNT)), build(&duplicated).map(|_| ()));
+    }
+
+    #[test]
+    fn ask_the_input() {
+        let answers = format!("Test\ntc\nsimple_poa\n{}\n\n\n{} 1000000\n\n", VALIDATOR, ACCOUNT);
+        let asked = ask(&mut answers.as_bytes()).unwrap();
+        assert_eq!(input("simple_poa"), asked);
+    }
+}
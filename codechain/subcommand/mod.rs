@@ -15,16 +15,48 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod account_command;
+mod analytics_command;
+mod blocks_command;
+mod db_command;
+mod genesis_command;
+mod state_command;
 
 use clap::ArgMatches;
 
 use self::account_command::run_account_command;
+use self::analytics_command::run_export_analytics_command;
+use self::blocks_command::{run_export_blocks_command, run_import_blocks_command};
+use self::db_command::run_db_command;
+use self::genesis_command::run_genesis_command;
+use self::state_command::run_state_command;
 
 pub fn run_subcommand(matches: ArgMatches) -> Result<(), String> {
     let subcommand = matches.subcommand.unwrap();
     if subcommand.name == "account" {
         run_account_command(subcommand.matches)
+    } else if subcommand.name == "db" {
+        run_db_command(subcommand.matches)
+    } else if subcommand.name == "export-blocks" {
+        run_export_blocks_command(subcommand.matches)
+    } else if subcommand.name == "import-blocks" {
+        run_import_blocks_command(subcommand.matches)
+    } else if subcommand.name == "export-analytics" {
+        run_export_analytics_command(subcommand.matches)
+    } else if subcommand.name == "state" {
+        run_state_command(subcommand.matches)
+    } else if subcommand.name == "genesis" {
+        run_genesis_command(subcommand.matches)
     } else {
         Err("Invalid subcommand".to_string())
     }
 }
+
+fn get_global_argument(matches: &ArgMatches, arg_name: &str) -> Option<String> {
+    match matches.value_of(arg_name) {
+        Some(value) => Some(value.to_string()),
+        None => match matches.subcommand() {
+            (_, Some(matches)) => matches.value_of(arg_name).map(|s| s.to_string()),
+            _ => None,
+        },
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ccore::{encoded, BlockChainClient, BlockId, BlockInfo, ChainInfo, EngineInfo, StateInfo, UnverifiedParcel};
+use ccrypto::blake256;
+use ckey::{public_to_address, Address, NetworkId, PlatformAddress};
+use clap::ArgMatches;
+use clogger::{self, LoggerConfig};
+use cstate::{Account, OwnedAsset, OwnedAssetAddress};
+use ctypes::parcel::Action;
+use ctypes::transaction::Transaction;
+use ctypes::{BlockNumber, ShardId};
+use primitives::H256;
+use rustc_hex::ToHex;
+use serde::Serialize;
+use serde_json;
+
+use super::analytics_command::{hex, CsvWriter};
+use super::blocks_command::{load_scheme, parse_block_number, start_client_with_scheme};
+
+const ACCOUNT_COLUMNS: [&str; 5] = ["address", "address_hash", "balance", "nonce", "regular_key"];
+const ASSET_COLUMNS: [&str; 8] =
+    ["shard_id", "transaction_hash", "index", "address_hash", "asset_type", "lock_script_hash", "parameters", "amount"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountEntry {
+    /// None if the address doesn't appear in the chain, so it can't be recovered from its hash.
+    address: Option<String>,
+    address_hash: String,
+    balance: String,
+    nonce: String,
+    regular_key: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AssetEntry {
+    shard_id: ShardId,
+    /// The output that created the asset. None if it can't be recovered from the hash of the asset address.
+    transaction_hash: Option<String>,
+    index: Option<usize>,
+    address_hash: String,
+    asset_type: String,
+    lock_script_hash: String,
+    parameters: Vec<String>,
+    amount: u64,
+}
+
+pub fn run_state_command(matches: ArgMatches) -> Result<(), String> {
+    if matches.subcommand.is_none() {
+        println!("{}", matches.usage());
+        return Ok(())
+    }
+
+    match matches.subcommand() {
+        ("export", Some(matches)) => export(matches),
+        _ => Err("Invalid subcommand".to_string()),
+    }
+}
+
+fn export(matches: &ArgMatches) -> Result<(), String> {
+    clogger::init(&LoggerConfig::new(0)).expect("Logger must be successfully initialized");
+
+    let format = matches.value_of("format").unwrap_or("json");
+    if format != "json" && format != "csv" {
+        return Err(format!("Invalid format {}. It must be json or csv", format))
+    }
+
+    let scheme = load_scheme(matches)?;
+    let service = start_client_with_scheme(matches, &scheme)?;
+    let client = service.client();
+    let network_id = client.common_params().network_id;
+
+    let best_block_number = client.chain_info().best_block_number;
+    let number = parse_block_number(matches.value_of("block"), best_block_number)?;
+    if number > best_block_number {
+        return Err(format!("--block({}) is greater than the best block number({})", number, best_block_number))
+    }
+    let header = client
+        .block_header(BlockId::Number(number))
+        .ok_or_else(|| format!("The header of #{} is not found", number))?;
+    let state =
+        client.state_info(BlockId::Number(number)).ok_or_else(|| format!("The state of #{} is pruned", number))?;
+
+    let mut known = KnownAddresses::default();
+    for address in scheme.genesis_addresses() {
+        known.add_account(address);
+    }
+    // The addresses which only appear in the pruned bodies are not found.
+    let mut pruned_bodies = 0;
+    for block_number in 0..number + 1 {
+        let header = client
+            .block_header(BlockId::Number(block_number))
+            .ok_or_else(|| format!("The header of #{} is not found", block_number))?;
+        known.add_account(header.author());
+        let body = match client.block_body(BlockId::Number(block_number)) {
+            Some(body) => body,
+            None => {
+                pruned_bodies += 1;
+                continue
+            }
+        };
+        for parcel in body.parcels() {
+            known.add_parcel(&parcel)?;
+        }
+    }
+
+    let path = Path::new(matches.value_of("PATH").expect("PATH arg is required and its index is 1"));
+    let mut output = Output::create(format, path, number, &header)?;
+    let mut write_result = Ok(());
+    let (mut accounts, mut unknown_accounts) = (0, 0);
+    state
+        .for_each_account(&mut |hash, account| {
+            if write_result.is_err() {
+                return
+            }
+            let entry = known.account_entry(hash, &account, network_id);
+            accounts += 1;
+            if entry.address.is_none() {
+                unknown_accounts += 1;
+            }
+            write_result = output.write_account(&entry);
+        })
+        .map_err(|e| format!("Cannot read the state of #{}: {}", number, e))?;
+    write_result.clone()?;
+
+    output.start_assets()?;
+    let (mut assets, mut unknown_assets) = (0, 0);
+    let number_of_shards = state.number_of_shards().map_err(|e| format!("Cannot read the state: {}", e))?;
+    for shard_id in 0..number_of_shards {
+        state
+            .for_each_asset(shard_id, &mut |hash, asset| {
+                if write_result.is_err() {
+                    return
+                }
+                let entry = known.asset_entry(shard_id, hash, &asset);
+                assets += 1;
+                if entry.transaction_hash.is_none() {
+                    unknown_assets += 1;
+                }
+                write_result = output.write_asset(&entry);
+            })
+            .map_err(|e| format!("Cannot read the shard {} of #{}: {}", shard_id, number, e))?;
+        write_result.clone()?;
+    }
+    output.finish()?;
+
+    println!(
+        "{} accounts and {} assets in the state of #{} are exported to {}",
+        accounts,
+        assets,
+        number,
+        path.display()
+    );
+    if unknown_accounts > 0 || unknown_assets > 0 {
+        println!(
+            "{} account addresses and {} asset origins are not found in the chain. Only their hashes are exported",
+            unknown_accounts, unknown_assets
+        );
+        if pruned_bodies > 0 {
+            println!("{} bodies are pruned, so the addresses only used in them are not found", pruned_bodies);
+        }
+    }
+    Ok(())
+}
+
+/// Writes the entries one by one as the state is read, so the whole state never has to be in memory.
+enum Output {
+    Json(JsonWriter),
+    Csv {
+        accounts: CsvWriter,
+        assets: CsvWriter,
+    },
+}
+
+impl Output {
+    fn create(format: &str, path: &Path, number: BlockNumber, header: &encoded::Header) -> Result<Self, String> {
+        if format == "json" {
+            return Ok(Output::Json(JsonWriter::create(path, number, header)?))
+        }
+        fs::create_dir_all(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+        Ok(Output::Csv {
+            accounts: CsvWriter::create(&path.join("accounts.csv"), &ACCOUNT_COLUMNS)?,
+            assets: CsvWriter::create(&path.join("assets.csv"), &ASSET_COLUMNS)?,
+        })
+    }
+
+    fn write_account(&mut self, account: &AccountEntry) -> Result<(), String> {
+        match self {
+            Output::Json(writer) => writer.write_entry(account),
+            Output::Csv {
+                accounts,
+                ..
+            } => accounts.write_row(&[
+                account.address.clone().unwrap_or_default(),
+                account.address_hash.clone(),
+                account.balance.clone(),
+                account.nonce.clone(),
+                account.regular_key.clone().unwrap_or_default(),
+            ]),
+        }
+    }
+
+    /// Must be called after all the accounts are written.
+    fn start_assets(&mut self) -> Result<(), String> {
+        match self {
+            Output::Json(writer) => writer.start_assets(),
+            Output::Csv {
+                ..
+            } => Ok(()),
+        }
+    }
+
+    fn write_asset(&mut self, asset: &AssetEntry) -> Result<(), String> {
+        match self {
+            Output::Json(writer) => writer.write_entry(asset),
+            Output::Csv {
+                assets,
+                ..
+            } => assets.write_row(&[
+                asset.shard_id.to_string(),
+                asset.transaction_hash.clone().unwrap_or_default(),
+                asset.index.map(|index| index.to_string()).unwrap_or_default(),
+                asset.address_hash.clone(),
+                asset.asset_type.clone(),
+                asset.lock_script_hash.clone(),
+                asset.parameters.join(" "),
+                asset.amount.to_string(),
+            ]),
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            Output::Json(writer) => writer.finish(),
+            Output::Csv {
+                accounts,
+                assets,
+            } => {
+                accounts.finish()?;
+                assets.finish()
+            }
+        }
+    }
+}
+
+/// Writes `{"blockNumber", "blockHash", "stateRoot", "accounts": [..], "assets": [..]}` with an entry per line.
+struct JsonWriter {
+    path: String,
+    file: BufWriter<File>,
+    /// Whether the array being written has an entry.
+    has_entries: bool,
+}
+
+impl JsonWriter {
+    fn create(path: &Path, number: BlockNumber, header: &encoded::Header) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+        let mut writer = JsonWriter {
+            path: path.display().to_string(),
+            file: BufWriter::new(file),
+            has_entries: false,
+        };
+        writer.write(&format!(
+            "{{\"blockNumber\":{},\"blockHash\":\"{}\",\"stateRoot\":\"{}\",\"accounts\":[",
+            number,
+            hex(&header.hash()),
+            hex(&header.state_root())
+        ))?;
+        Ok(writer)
+    }
+
+    fn write_entry<T: Serialize>(&mut self, entry: &T) -> Result<(), String> {
+        if self.has_entries {
+            self.write(",")?;
+        }
+        self.has_entries = true;
+        self.write("\n")?;
+        serde_json::to_writer(&mut self.file, entry).map_err(|e| format!("Cannot write to {}: {}", self.path, e))
+    }
+
+    fn start_assets(&mut self) -> Result<(), String> {
+        self.has_entries = false;
+        self.write("\n],\"assets\":[")
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.write("\n]}\n")?;
+        self.file.flush().map_err(|e| format!("Cannot write to {}: {}", self.path, e))
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        self.file.write_all(text.as_bytes()).map_err(|e| format!("Cannot write to {}: {}", self.path, e))
+    }
+}
+
+/// The state tries are keyed by the hashes of the addresses, so the addresses are recovered from
+/// the ones that appear in the chain up to the exported block.
+#[derive(Default)]
+struct KnownAddresses {
+    accounts: HashMap<H256, Address>,
+    /// The transaction hash and the output index of each asset address.
+    assets: HashMap<H256, (H256, usize)>,
+}
+
+impl KnownAddresses {
+    fn add_account(&mut self, address: Address) {
+        self.accounts.insert(blake256(&address), address);
+    }
+
+    fn add_output(&mut self, transaction_hash: H256, index: usize, shard_id: ShardId) {
+        let address = OwnedAssetAddress::new(transaction_hash, index, shard_id);
+        self.assets.insert(blake256(&address), (transaction_hash, index));
+    }
+
+    fn add_parcel(&mut self, parcel: &UnverifiedParcel) -> Result<(), String> {
+        // An account which signed parcels signed its first one with the nonce 0,
+        // so the signers of the others are already known and their signatures are not recovered.
+        if parcel.nonce.is_zero() {
+            let signer = parcel
+                .recover_public()
+                .map_err(|e| format!("Cannot recover the signer of {}: {}", hex(&parcel.hash()), e))?;
+            self.add_account(public_to_address(&signer));
+        }
+        match &parcel.action {
+            Action::Payment {
+                receiver,
+                ..
+            } => self.add_account(*receiver),
+            Action::SetShardOwners {
+                owners: addresses,
+                ..
+            }
+            | Action::SetShardUsers {
+                users: addresses,
+                ..
+            } => {
+                for address in addresses {
+                    self.add_account(*address);
+                }
+            }
+            Action::AssetTransactionGroup {
+                transactions,
+                ..
+            } => {
+                for transaction in transactions {
+                    self.add_transaction(transaction);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn add_transaction(&mut self, transaction: &Transaction) {
+        let hash = transaction.hash();
+        match transaction {
+            Transaction::AssetMint {
+                shard_id,
+                ..
+            } => self.add_output(hash, 0, *shard_id),
+            Transaction::AssetTransfer {
+                outputs,
+                ..
+            } => {
+                for shard_id in transaction.related_shards() {
+                    for index in 0..outputs.len() {
+                        self.add_output(hash, index, shard_id);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn account_entry(&self, hash: &H256, account: &Account, network_id: NetworkId) -> AccountEntry {
+        AccountEntry {
+            address: self
+                .accounts
+                .get(hash)
+                .map(|address| PlatformAddress::create(0, network_id, *address).to_string()),
+            address_hash: hex(hash),
+            balance: account.balance().to_string(),
+            nonce: account.nonce().to_string(),
+            regular_key: account.regular_key().map(|key| format!("0x{:x}", key)),
+        }
+    }
+
+    fn asset_entry(&self, shard_id: ShardId, hash: &H256, asset: &OwnedAsset) -> AssetEntry {
+        let origin = self.assets.get(hash);
+        AssetEntry {
+            shard_id,
+            transaction_hash: origin.map(|(transaction_hash, _)| hex(transaction_hash)),
+            index: origin.map(|(_, index)| *index),
+            address_hash: hex(hash),
+            asset_type: hex(asset.asset_type()),
+            lock_script_hash: hex(asset.lock_script_hash()),
+            parameters: asset.parameters().iter().map(|parameter| format!("0x{}", parameter.to_hex())).collect(),
+            amount: *asset.amount(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ccore::SignedParcel;
+    use ckey::{Generator, Random};
+    use ctypes::parcel::Parcel;
+
+    use super::*;
+
+    fn payment(nonce: u64, receiver: Address) -> (Address, UnverifiedParcel) {
+        let keypair = Random.generate().unwrap();
+        let parcel = Parcel {
+            nonce: nonce.into(),
+            fee: 10.into(),
+            network_id: NetworkId::default(),
+            action: Action::Payment {
+                receiver,
+                amount: 1.into(),
+            },
+        };
+        (keypair.address(), (*SignedParcel::new_with_sign(parcel, keypair.private())).clone())
+    }
+
+    #[test]
+    fn only_the_signers_of_the_first_parcels_are_recovered() {
+        let mut known = KnownAddresses::default();
+        let receiver = Address::random();
+        let (first_signer, first) = payment(0, receiver);
+        let (second_signer, second) = payment(1, receiver);
+        known.add_parcel(&first).unwrap();
+        known.add_parcel(&second).unwrap();
+
+        assert!(known.accounts.contains_key(&blake256(&first_signer)));
+        assert!(!known.accounts.contains_key(&blake256(&second_signer)));
+        assert!(known.accounts.contains_key(&blake256(&receiver)));
+    }
+
+    #[test]
+    fn accounts_are_found_by_the_hash_of_the_address() {
+        let mut known = KnownAddresses::default();
+        let address = Address::random();
+        known.add_account(address);
+
+        let account = Account::new(100.into(), 1.into());
+        let entry = known.account_entry(&blake256(&address), &account, NetworkId::default());
+        assert_eq!(Some(PlatformAddress::create(0, NetworkId::default(), address).to_string()), entry.address);
+        assert_eq!("100", entry.balance);
+        assert_eq!("1", entry.nonce);
+
+        let entry = known.account_entry(&blake256(&Address::random()), &account, NetworkId::default());
+        assert_eq!(None, entry.address);
+    }
+
+    #[test]
+    fn assets_are_found_by_the_hash_of_the_address() {
+        let mut known = KnownAddresses::default();
+        let transaction_hash = H256::random();
+        known.add_output(transaction_hash, 2, 3);
+
+        let asset = OwnedAsset::new(H256::random(), H256::random(), vec![vec![1, 2]], 10);
+        let hash = blake256(&OwnedAssetAddress::new(transaction_hash, 2, 3));
+        let entry = known.asset_entry(3, &hash, &asset);
+        assert_eq!(Some(hex(&transaction_hash)), entry.transaction_hash);
+        assert_eq!(Some(2), entry.index);
+        assert_eq!(vec!["0x0102".to_string()], entry.parameters);
+
+        let hash = blake256(&OwnedAssetAddress::new(transaction_hash, 2, 4));
+        assert_eq!(None, known.asset_entry(4, &hash, &asset).index);
+    }
+}
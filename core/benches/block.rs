@@ -0,0 +1,89 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Benchmarks for decoding blocks and recovering parcel signers.
+
+#[macro_use]
+extern crate criterion;
+extern crate codechain_core as ccore;
+extern crate codechain_key as ckey;
+extern crate codechain_types as ctypes;
+extern crate rayon;
+extern crate rlp;
+
+use ccore::{Block, Header, Seal, SignedParcel, UnverifiedParcel};
+use ckey::{Generator, NetworkId, Random};
+use criterion::Criterion;
+use ctypes::parcel::{Action, Parcel};
+use rayon::prelude::*;
+
+const PARCELS_PER_BLOCK: usize = 100;
+
+fn signed_parcel() -> SignedParcel {
+    let keypair = Random.generate().unwrap();
+    let parcel = Parcel {
+        nonce: 0.into(),
+        fee: 10.into(),
+        network_id: NetworkId::default(),
+        action: Action::Payment {
+            receiver: Random.generate().unwrap().address(),
+            amount: 1.into(),
+        },
+    };
+    SignedParcel::new_with_sign(parcel, keypair.private())
+}
+
+fn block_bytes() -> Vec<u8> {
+    let block = Block {
+        header: Header::default(),
+        parcels: (0..PARCELS_PER_BLOCK).map(|_| signed_parcel().into()).collect(),
+    };
+    block.rlp_bytes(Seal::With)
+}
+
+fn decode_block(c: &mut Criterion) {
+    let bytes = block_bytes();
+    c.bench_function("decode a block with 100 parcels", move |b| b.iter(|| rlp::decode::<Block>(&bytes)));
+}
+
+fn recover_signer(c: &mut Criterion) {
+    let parcel: UnverifiedParcel = signed_parcel().into();
+    c.bench_function("recover the signer of a parcel", move |b| b.iter(|| parcel.recover_public().unwrap()));
+}
+
+fn verify_block_parcels(c: &mut Criterion) {
+    let bytes = block_bytes();
+    c.bench_function("decode a block and recover all signers", move |b| {
+        b.iter(|| {
+            let block = rlp::decode::<Block>(&bytes);
+            block.parcels.into_iter().map(SignedParcel::new).collect::<Result<Vec<_>, _>>().unwrap()
+        })
+    });
+}
+
+fn verify_block_parcels_in_parallel(c: &mut Criterion) {
+    let bytes = block_bytes();
+    c.bench_function("decode a block and recover all signers in parallel", move |b| {
+        b.iter(|| {
+            let block = rlp::decode::<Block>(&bytes);
+            block.parcels.into_par_iter().map(SignedParcel::new).collect::<Result<Vec<_>, _>>().unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, decode_block, recover_signer, verify_block_parcels, verify_block_parcels_in_parallel);
+criterion_main!(benches);
@@ -0,0 +1,69 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Benchmarks for looking up blocks through the chain extras.
+
+#[macro_use]
+extern crate criterion;
+extern crate codechain_core as ccore;
+extern crate tempdir;
+
+use ccore::{BlockChainClient, BlockId, BlockInfo, ChainInfo, ClientService, EngineClient, Miner, MinerOptions, Scheme};
+use criterion::Criterion;
+use tempdir::TempDir;
+
+const BLOCKS: usize = 256;
+
+/// Starts a client on a temporary database and seals at least `BLOCKS` empty blocks on it.
+fn sealed_chain() -> (TempDir, ClientService) {
+    let scheme = Scheme::new_test_solo();
+    let options = MinerOptions {
+        force_sealing: true,
+        ..Default::default()
+    };
+    let miner = Miner::new(options, &scheme, None);
+    let dir = TempDir::new("codechain-bench").unwrap();
+    let service = ClientService::start(Default::default(), &scheme, dir.path(), miner).unwrap();
+    let client = service.client();
+    for _ in 0..BLOCKS {
+        client.update_sealing();
+    }
+    (dir, service)
+}
+
+fn lookups(c: &mut Criterion) {
+    let (_dir, service) = sealed_chain();
+    let client = service.client();
+    let best = client.chain_info().best_block_number;
+    let hashes: Vec<_> = (1..=best).map(|n| client.block_hash(BlockId::Number(n)).unwrap()).collect();
+
+    let by_number = client.clone();
+    c.bench_function("look up block hashes by number", move |b| {
+        b.iter(|| (1..=best).map(|n| by_number.block_hash(BlockId::Number(n))).count())
+    });
+    let by_hash = client.clone();
+    let numbered = hashes.clone();
+    c.bench_function("look up block numbers by hash", move |b| {
+        b.iter(|| numbered.iter().map(|hash| by_hash.block_number(BlockId::Hash(*hash))).count())
+    });
+    c.bench_function("look up block headers by hash", move |b| {
+        b.iter(|| hashes.iter().map(|hash| client.block_header(BlockId::Hash(*hash))).count())
+    });
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Benchmarks for importing parcels into the mem pool.
+
+#[macro_use]
+extern crate criterion;
+extern crate codechain_core as ccore;
+extern crate codechain_key as ckey;
+extern crate codechain_types as ctypes;
+
+use std::sync::Arc;
+
+use ccore::{Miner, MinerOptions, MinerService, Scheme, SignedParcel, TestBlockChainClient, UnverifiedParcel};
+use ckey::{public_to_address, Generator, NetworkId, Random};
+use criterion::Criterion;
+use ctypes::parcel::{Action, Parcel};
+
+/// Parcels imported in a single call.
+const BATCH: usize = 100;
+/// Parcels already pending in a loaded pool.
+const LOAD: usize = 5000;
+
+fn parcels(client: &TestBlockChainClient, count: usize) -> Vec<UnverifiedParcel> {
+    (0..count)
+        .map(|_| {
+            let keypair = Random.generate().unwrap();
+            let parcel = Parcel {
+                nonce: 0.into(),
+                fee: 10.into(),
+                network_id: NetworkId::default(),
+                action: Action::Payment {
+                    receiver: Random.generate().unwrap().address(),
+                    amount: 1.into(),
+                },
+            };
+            let signed = SignedParcel::new_with_sign(parcel, keypair.private());
+            client.set_balance(public_to_address(&signed.signer_public()), 10_000_000_000u64.into());
+            signed.into()
+        })
+        .collect()
+}
+
+fn miner() -> Arc<Miner> {
+    let options = MinerOptions {
+        reseal_on_external_parcel: false,
+        ..Default::default()
+    };
+    Miner::new(options, &Scheme::new_test(), None)
+}
+
+fn import_into_empty_pool(c: &mut Criterion) {
+    let client = TestBlockChainClient::new();
+    let batch = parcels(&client, BATCH);
+    c.bench_function("import 100 parcels into an empty pool", move |b| {
+        b.iter_with_setup(miner, |miner| miner.import_external_parcels(&client, batch.clone()))
+    });
+}
+
+fn import_into_loaded_pool(c: &mut Criterion) {
+    let client = TestBlockChainClient::new();
+    let load = parcels(&client, LOAD);
+    let batch = parcels(&client, BATCH);
+    c.bench_function("import 100 parcels into a pool with 5000 pending", move |b| {
+        b.iter_with_setup(
+            || {
+                let miner = miner();
+                miner.import_external_parcels(&client, load.clone());
+                miner
+            },
+            |miner| miner.import_external_parcels(&client, batch.clone()),
+        )
+    });
+}
+
+criterion_group!(benches, import_into_empty_pool, import_into_loaded_pool);
+criterion_main!(benches);
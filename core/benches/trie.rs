@@ -0,0 +1,83 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Benchmarks for inserting into the state trie and committing it to the database.
+
+#[macro_use]
+extern crate criterion;
+extern crate codechain_core as ccore;
+extern crate codechain_crypto as ccrypto;
+extern crate codechain_merkle as cmerkle;
+extern crate journaldb;
+extern crate kvdb;
+extern crate kvdb_memorydb;
+extern crate primitives;
+
+use std::sync::Arc;
+
+use ccore::COL_STATE;
+use ccrypto::blake256;
+use cmerkle::{TrieFactory, TrieMut};
+use criterion::Criterion;
+use journaldb::{Algorithm, JournalDB};
+use kvdb::KeyValueDB;
+use primitives::H256;
+
+const KEYS: usize = 1000;
+
+fn entries() -> Vec<(H256, H256)> {
+    (0..KEYS as u64).map(|i| (blake256(H256::from(i)), H256::from(i))).collect()
+}
+
+fn database() -> (Arc<KeyValueDB>, Box<JournalDB>) {
+    let backing: Arc<KeyValueDB> = Arc::new(kvdb_memorydb::create(1));
+    let journal_db = journaldb::new(Arc::clone(&backing), Algorithm::Archive, COL_STATE);
+    (backing, journal_db)
+}
+
+fn insert(journal_db: &mut JournalDB, entries: &[(H256, H256)]) -> H256 {
+    let mut root = H256::zero();
+    {
+        let mut trie = TrieFactory::create(journal_db.as_hashdb_mut(), &mut root);
+        for (key, value) in entries {
+            trie.insert(&key[..], &value[..]).unwrap();
+        }
+    }
+    root
+}
+
+fn trie_insert(c: &mut Criterion) {
+    let entries = entries();
+    c.bench_function("insert 1000 keys into an empty trie", move |b| {
+        b.iter_with_setup(database, |(_backing, mut journal_db)| insert(&mut *journal_db, &entries))
+    });
+}
+
+fn trie_commit(c: &mut Criterion) {
+    let entries = entries();
+    c.bench_function("insert 1000 keys and commit the trie", move |b| {
+        b.iter_with_setup(database, |(backing, mut journal_db)| {
+            let root = insert(&mut *journal_db, &entries);
+            let mut batch = backing.transaction();
+            journal_db.journal_under(&mut batch, 0, &root).unwrap();
+            backing.write(batch).unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, trie_insert, trie_commit);
+criterion_main!(benches);
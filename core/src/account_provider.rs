@@ -57,6 +57,8 @@ pub enum SignError {
     KeyError(KeyError),
     /// Keystore error.
     KeystoreError(KeystoreError),
+    /// The remote signer didn't sign in time.
+    RemoteSignerUnavailable,
 }
 
 impl From<KeyError> for SignError {
@@ -78,6 +80,7 @@ impl fmt::Display for SignError {
             SignError::NotFound => write!(f, "Account does not exist"),
             SignError::KeyError(e) => write!(f, "{}", e),
             SignError::KeystoreError(e) => write!(f, "{}", e),
+            SignError::RemoteSignerUnavailable => write!(f, "The remote signer is unavailable"),
         }
     }
 }
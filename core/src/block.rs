@@ -17,20 +17,21 @@
 use std::collections::HashSet;
 
 use ccrypto::BLAKE_NULL_RLP;
-use ckey::Address;
+use ckey::{public_to_address, Address};
 use cmerkle::skewed_merkle_root;
-use cstate::{StateDB, StateError, StateWithCache, TopLevelState};
+use cstate::{StateDB, StateError, StateWithCache, TopLevelState, TopStateInfo};
 use ctypes::invoice::ParcelInvoice;
 use ctypes::machine::{LiveBlock, Parcels};
 use ctypes::parcel::Error as ParcelError;
 use ctypes::util::unexpected::Mismatch;
-use primitives::{Bytes, H256};
+use primitives::{Bloom, Bytes, H256};
 use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
 use super::consensus::CodeChainEngine;
 use super::error::{BlockError, Error};
 use super::header::{Header, Seal};
 use super::parcel::{SignedParcel, UnverifiedParcel};
+use super::types::{parcel_topics, topics_bloom};
 
 /// A block, encoded as it is on the block chain.
 #[derive(Debug, Clone, PartialEq)]
@@ -73,6 +74,8 @@ pub struct ExecutedBlock {
     state: TopLevelState,
     parcels: Vec<SignedParcel>,
     invoices: Vec<ParcelInvoice>,
+    /// The accounts which sent the parcels, which are the owners of the regular keys which signed them.
+    senders: Vec<Address>,
     parcels_set: HashSet<H256>,
 }
 
@@ -83,6 +86,7 @@ impl ExecutedBlock {
             state,
             parcels: Default::default(),
             invoices: Default::default(),
+            senders: Default::default(),
             parcels_set: Default::default(),
         }
     }
@@ -109,6 +113,26 @@ impl LiveBlock for ExecutedBlock {
     }
 }
 
+/// Parcels pushed onto an open block and the state after them.
+///
+/// They can be restored onto another open block on the same parent instead of being executed again.
+#[derive(Clone)]
+pub struct ExecutedParcels {
+    state: TopLevelState,
+    parcels: Vec<SignedParcel>,
+    invoices: Vec<ParcelInvoice>,
+    senders: Vec<Address>,
+    parcels_set: HashSet<H256>,
+    log_bloom: Bloom,
+}
+
+impl ExecutedParcels {
+    /// Parcels in the order they were pushed.
+    pub fn parcels(&self) -> &[SignedParcel] {
+        &self.parcels
+    }
+}
+
 /// Block that is ready for parcels to be added.
 pub struct OpenBlock<'x> {
     block: ExecutedBlock,
@@ -153,19 +177,83 @@ impl<'x> OpenBlock<'x> {
             return Err(StateError::Parcel(ParcelError::ParcelAlreadyImported).into())
         }
 
-        let invoice = self.block.state.apply(&parcel, &parcel.signer_public())?;
+        // The owner of a regular key is read before the parcel, which may change it.
+        let signer_public = parcel.signer_public();
+        let sender = self.block.state.regular_key_owner(&signer_public).map_err(StateError::from)?;
+        let sender = sender.unwrap_or_else(|| public_to_address(&signer_public));
+
+        let invoice = self.block.state.apply(
+            &parcel,
+            &signer_public,
+            &self.engine.params().min_balance,
+            &self.engine.params().name_fee_per_block,
+            self.block.header.number(),
+        )?;
+
+        let mut log_bloom = *self.block.header.log_bloom();
+        log_bloom.accrue_bloom(&topics_bloom(&parcel_topics(&parcel)));
+        self.block.header.set_log_bloom(log_bloom);
 
         self.block.parcels_set.insert(h.unwrap_or_else(|| parcel.hash()));
         self.block.parcels.push(parcel.into());
         self.block.invoices.push(invoice);
+        self.block.senders.push(sender);
         Ok(())
     }
 
+    /// Snapshot of the parcels pushed so far.
+    pub fn executed_parcels(&self) -> ExecutedParcels {
+        ExecutedParcels {
+            state: self.block.state.clone(),
+            parcels: self.block.parcels.clone(),
+            invoices: self.block.invoices.clone(),
+            senders: self.block.senders.clone(),
+            parcels_set: self.block.parcels_set.clone(),
+            log_bloom: *self.block.header.log_bloom(),
+        }
+    }
+
+    /// Replaces the pushed parcels with the ones executed on another open block.
+    ///
+    /// The caller must ensure that the open block and `executed` share the parent, and no parcel was pushed yet.
+    pub fn restore_parcels(&mut self, executed: ExecutedParcels) {
+        debug_assert!(self.block.parcels.is_empty());
+        self.block.state = executed.state;
+        self.block.parcels = executed.parcels;
+        self.block.invoices = executed.invoices;
+        self.block.senders = executed.senders;
+        self.block.parcels_set = executed.parcels_set;
+        self.block.header.set_log_bloom(executed.log_bloom);
+    }
+
     /// Push parcels onto the block.
+    /// The independent shards of the parcels are executed in parallel.
     pub fn push_parcels(&mut self, parcels: &[SignedParcel]) -> Result<(), Error> {
+        let mut hashes = HashSet::with_capacity(parcels.len());
         for parcel in parcels {
-            self.push_parcel(parcel.clone(), None)?;
+            let hash = parcel.hash();
+            if self.block.parcels_set.contains(&hash) || !hashes.insert(hash) {
+                return Err(StateError::Parcel(ParcelError::ParcelAlreadyImported).into())
+            }
+        }
+
+        let signed: Vec<_> = parcels.iter().map(|parcel| (&***parcel, parcel.signer_public())).collect();
+        let outcomes = self.block.state.apply_parcels(
+            &signed,
+            &self.engine.params().min_balance,
+            &self.engine.params().name_fee_per_block,
+            self.block.header.number(),
+        )?;
+
+        let mut log_bloom = *self.block.header.log_bloom();
+        for (parcel, (sender, invoice)) in parcels.iter().zip(outcomes) {
+            log_bloom.accrue_bloom(&topics_bloom(&parcel_topics(parcel)));
+            self.block.parcels_set.insert(parcel.hash());
+            self.block.parcels.push(parcel.clone().into());
+            self.block.invoices.push(invoice);
+            self.block.senders.push(sender);
         }
+        self.block.header.set_log_bloom(log_bloom);
         Ok(())
     }
 
@@ -194,6 +282,10 @@ impl<'x> OpenBlock<'x> {
             self.block.parcels.iter().map(|e| e.rlp_bytes()),
         ));
         self.block.header.set_state_root(self.block.state.root().clone());
+        match shard_roots(&self.block.state) {
+            Ok(roots) => self.block.header.set_shard_roots(roots),
+            Err(e) => warn!("Encountered error on reading the shard roots: {}", e),
+        }
         self.block.header.set_invoices_root(skewed_merkle_root(
             parent_invoices_root,
             self.block.invoices.iter().flat_map(|invoices| invoices.iter_result().map(|invoice| invoice.rlp_bytes())),
@@ -230,6 +322,10 @@ impl<'x> OpenBlock<'x> {
             ));
         }
         self.block.header.set_state_root(self.block.state.root().clone());
+        match shard_roots(&self.block.state) {
+            Ok(roots) => self.block.header.set_shard_roots(roots),
+            Err(e) => warn!("Encountered error on reading the shard roots: {}", e),
+        }
 
         LockedBlock {
             block: self.block,
@@ -360,6 +456,11 @@ pub trait IsBlock {
         &self.block().invoices
     }
 
+    /// Get the accounts which sent the parcels in this block.
+    fn senders(&self) -> &[Address] {
+        &self.block().senders
+    }
+
     /// Get the final state associated with this object's block.
     fn state(&self) -> &TopLevelState {
         &self.block().state
@@ -414,6 +515,18 @@ impl Drain for SealedBlock {
     }
 }
 
+/// The roots of the shards in the state, in the order of the shard ids.
+fn shard_roots(state: &TopLevelState) -> Result<Vec<H256>, StateError> {
+    let number_of_shards = state.number_of_shards()?;
+    let mut roots = Vec::with_capacity(number_of_shards as usize);
+    for shard_id in 0..number_of_shards {
+        // The shards are never removed, so a missing one means the state is broken.
+        let root = state.shard_root(shard_id)?.ok_or(ParcelError::InvalidShardId(shard_id))?;
+        roots.push(root);
+    }
+    Ok(roots)
+}
+
 /// Enact the block given by block header, parcels and uncles
 pub fn enact(
     header: &Header,
@@ -433,11 +546,15 @@ pub fn enact(
 
 #[cfg(test)]
 mod tests {
-    use ckey::Address;
+    use ckey::{Address, Generator, Random};
+    use cstate::{StateDB, TopState};
+    use ctypes::parcel::{Action, Parcel};
+    use primitives::U256;
 
+    use super::super::parcel::SignedParcel;
     use super::super::scheme::Scheme;
     use super::super::tests::helpers::get_temp_state_db;
-    use super::OpenBlock;
+    use super::{IsBlock, OpenBlock};
 
     #[test]
     fn open_block() {
@@ -450,4 +567,82 @@ mod tests {
         let b = b.close_and_lock(parent_parcels_root, parent_invoices_root);
         let _ = b.seal(&*scheme.engine, vec![]);
     }
+
+    #[test]
+    fn restored_parcels_result_in_the_same_state() {
+        let scheme = Scheme::new_test();
+        let genesis_header = scheme.genesis_header();
+        let db = scheme.ensure_genesis_state(get_temp_state_db()).unwrap();
+        let keypair = Random.generate().unwrap();
+        let parcel = SignedParcel::new_with_sign(
+            Parcel {
+                nonce: U256::zero(),
+                fee: U256::from(10),
+                network_id: Default::default(),
+                action: Action::Payment {
+                    receiver: Address::random(),
+                    amount: U256::from(100),
+                },
+            },
+            keypair.private(),
+        );
+
+        let open = |db: StateDB| {
+            let mut b = OpenBlock::new(&*scheme.engine, db, &genesis_header, Address::default(), vec![], false).unwrap();
+            b.block.state_mut().add_balance(&keypair.address(), &U256::from(1000)).unwrap();
+            b
+        };
+        let mut executed = open(db.clone_canon(&genesis_header.hash()));
+        executed.push_parcel(parcel.clone(), None).unwrap();
+        let mut restored = open(db);
+        restored.restore_parcels(executed.executed_parcels());
+        assert_eq!(&[parcel], restored.executed_parcels().parcels());
+
+        let parcels_root = *genesis_header.parcels_root();
+        let invoices_root = *genesis_header.invoices_root();
+        let executed = executed.close_and_lock(parcels_root, invoices_root);
+        let restored = restored.close_and_lock(parcels_root, invoices_root);
+        assert_eq!(executed.header().state_root(), restored.header().state_root());
+        assert_eq!(executed.header().invoices_root(), restored.header().invoices_root());
+    }
+
+    #[test]
+    fn parcel_signed_by_regular_key_is_sent_by_the_owner() {
+        let scheme = Scheme::new_test();
+        let genesis_header = scheme.genesis_header();
+        let db = scheme.ensure_genesis_state(get_temp_state_db()).unwrap();
+        let owner = Random.generate().unwrap();
+        let regular_key = Random.generate().unwrap();
+        let parcel = |nonce: u64, action: Action| Parcel {
+            nonce: U256::from(nonce),
+            fee: U256::from(10),
+            network_id: Default::default(),
+            action,
+        };
+        let set_regular_key = SignedParcel::new_with_sign(
+            parcel(
+                0,
+                Action::SetRegularKey {
+                    key: *regular_key.public(),
+                },
+            ),
+            owner.private(),
+        );
+        let payment = SignedParcel::new_with_sign(
+            parcel(
+                1,
+                Action::Payment {
+                    receiver: Address::random(),
+                    amount: U256::from(100),
+                },
+            ),
+            regular_key.private(),
+        );
+
+        let mut b = OpenBlock::new(&*scheme.engine, db, &genesis_header, Address::default(), vec![], false).unwrap();
+        b.block.state_mut().add_balance(&owner.address(), &U256::from(1000)).unwrap();
+        b.push_parcel(set_regular_key, None).unwrap();
+        b.push_parcel(payment, None).unwrap();
+        assert_eq!(&[owner.address(), owner.address()], b.senders());
+    }
 }
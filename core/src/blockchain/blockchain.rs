@@ -14,38 +14,52 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::mem;
 use std::sync::Arc;
 
+use ckey::Address;
 use ctypes::invoice::{BlockInvoices, ParcelInvoice};
 use ctypes::parcel::Action;
 use ctypes::transaction::Transaction;
 use ctypes::BlockNumber;
 use kvdb::{DBTransaction, KeyValueDB};
 use parking_lot::RwLock;
-use primitives::H256;
+use primitives::{Bloom, Bytes, H256};
 use rlp::RlpStream;
 
 use super::super::blockchain_info::BlockChainInfo;
 use super::super::consensus::epoch::{PendingTransition as PendingEpochTransition, Transition as EpochTransition};
-use super::super::db::{self, Readable, Writable};
+use super::super::consensus::fork_choice::{ForkChoice, ForkHead};
+use super::super::consensus::CodeChainEngine;
+use super::super::db::{self, Key, Readable, Writable};
 use super::super::encoded;
 use super::super::parcel::LocalizedParcel;
 use super::super::views::{BlockView, HeaderView};
 use super::block_info::BlockLocation;
 use super::body_db::{BodyDB, BodyProvider};
-use super::extras::{BlockDetails, EpochTransitions, ParcelAddress, TransactionAddress, EPOCH_KEY_PREFIX};
+use super::cache::{BlockChainCacheStats, CacheCapacities};
+use super::extras::{
+    BlockDetails, EpochTransitions, FinalityProof, ParcelAddress, TransactionAddress, TransactionAddresses,
+    EPOCH_KEY_PREFIX,
+};
 use super::headerchain::{HeaderChain, HeaderProvider};
 use super::invoice_db::{InvoiceDB, InvoiceProvider};
 use super::route::{tree_route, ImportRoute};
 
 const BEST_BLOCK_KEY: &[u8] = b"best-block";
+const EARLIEST_BODY_KEY: &[u8] = b"earliest-body";
+
+/// Maximum number of the bodies pruned at once, so that enabling the pruning on a long chain doesn't stall an import.
+const MAX_PRUNED_BODIES: BlockNumber = 128;
 
 /// Structure providing fast access to blockchain data.
 ///
 /// **Does not do input data verification.**
 pub struct BlockChain {
     best_block_hash: RwLock<H256>,
+    /// The number of the earliest canon block whose body is kept.
+    earliest_body_number: RwLock<BlockNumber>,
 
     headerchain: HeaderChain,
     body_db: BodyDB,
@@ -58,8 +72,10 @@ pub struct BlockChain {
 
 impl BlockChain {
     /// Create new instance of blockchain from given Genesis.
-    pub fn new(genesis: &[u8], db: Arc<KeyValueDB>) -> Self {
+    /// `cache_size` is the memory budget in bytes shared by the header, body, detail and address caches.
+    pub fn new(genesis: &[u8], db: Arc<KeyValueDB>, cache_size: usize) -> Self {
         let genesis_block = BlockView::new(genesis);
+        let capacities = CacheCapacities::from_budget(cache_size);
 
         // load best block
         let best_block_hash = match db.get(db::COL_EXTRA, BEST_BLOCK_KEY).unwrap() {
@@ -74,11 +90,17 @@ impl BlockChain {
             }
         };
 
+        let earliest_body_number: BlockNumber = match db.get(db::COL_EXTRA, EARLIEST_BODY_KEY).unwrap() {
+            Some(number) => ::rlp::decode(&number),
+            None => 0,
+        };
+
         Self {
             best_block_hash: RwLock::new(best_block_hash),
+            earliest_body_number: RwLock::new(earliest_body_number),
 
-            headerchain: HeaderChain::new(&genesis_block.header_view(), db.clone()),
-            body_db: BodyDB::new(&genesis_block, db.clone()),
+            headerchain: HeaderChain::new(&genesis_block.header_view(), db.clone(), &capacities),
+            body_db: BodyDB::new(&genesis_block, db.clone(), &capacities),
             invoice_db: InvoiceDB::new(db.clone()),
 
             db,
@@ -87,8 +109,13 @@ impl BlockChain {
         }
     }
 
-    pub fn insert_header(&self, batch: &mut DBTransaction, header: &HeaderView) -> ImportRoute {
-        match self.headerchain.insert_header(batch, header) {
+    pub fn insert_header(
+        &self,
+        batch: &mut DBTransaction,
+        header: &HeaderView,
+        engine: &CodeChainEngine,
+    ) -> ImportRoute {
+        match self.headerchain.insert_header(batch, header, engine) {
             Some(l) => ImportRoute::new(&header.hash(), &l),
             None => ImportRoute::none(),
         }
@@ -97,7 +124,15 @@ impl BlockChain {
     /// Inserts the block into backing cache database.
     /// Expects the block to be valid and already verified.
     /// If the block is already known, does nothing.
-    pub fn insert_block(&self, batch: &mut DBTransaction, bytes: &[u8], invoices: Vec<ParcelInvoice>) -> ImportRoute {
+    /// `senders` are the senders of the parcels of the block, in the same order.
+    pub fn insert_block(
+        &self,
+        batch: &mut DBTransaction,
+        bytes: &[u8],
+        invoices: Vec<ParcelInvoice>,
+        senders: &[Address],
+        engine: &CodeChainEngine,
+    ) -> ImportRoute {
         // create views onto rlp
         let block = BlockView::new(bytes);
         let header = block.header_view();
@@ -109,13 +144,22 @@ impl BlockChain {
 
         assert!(self.pending_best_block_hash.read().is_none());
 
-        let location = self.block_location(&block);
+        let location = self.block_location(&block, engine);
 
-        self.headerchain.insert_header(batch, &header);
-        self.body_db.insert_body(batch, &block, &location);
+        self.headerchain.insert_header(batch, &header, engine);
+        self.body_db.insert_body(batch, &block, &location, senders);
         self.invoice_db.insert_invoice(batch, &hash, invoices);
 
+        if let BlockLocation::BranchBecomingCanonChain(ref route) = location {
+            for enacted in &route.enacted {
+                let enacted_header = self.block_header_data(enacted).expect("Enacted block must be in database.");
+                batch.write(db::COL_EXTRA, &enacted_header.number(), &enacted_header.log_bloom());
+            }
+        }
+
         if location != BlockLocation::Branch {
+            batch.write(db::COL_EXTRA, &header.number(), &header.log_bloom());
+
             let mut pending_best_block_hash = self.pending_best_block_hash.write();
             batch.put(db::COL_EXTRA, BEST_BLOCK_KEY, &header.hash());
             *pending_best_block_hash = Some(header.hash());
@@ -139,25 +183,59 @@ impl BlockChain {
     }
 
     /// Calculate insert location for new block
-    fn block_location(&self, block: &BlockView) -> BlockLocation {
+    fn block_location(&self, block: &BlockView, engine: &CodeChainEngine) -> BlockLocation {
         let header = block.header_view();
         let parent_hash = header.parent_hash();
         let parent_details = self.block_details(&parent_hash).expect("Invalid parent hash");
+        let best_hash = self.best_block_hash();
+        let best_detail = self.best_block_detail();
+
+        let route =
+            tree_route(self, best_hash, parent_hash).expect("blocks being imported always within recent history; qed");
+        let ancestor_number = self.block_details(&route.ancestor).expect("Ancestor is always known").number;
+        let finalized_number = self.block_details(&self.finalized_block_hash()).map_or(0, |details| details.number);
+
+        let new = ForkHead {
+            hash: header.hash(),
+            number: header.number(),
+            total_score: parent_details.total_score + header.score(),
+            is_finalized: ancestor_number >= finalized_number,
+        };
+        let current = ForkHead {
+            hash: best_hash,
+            number: best_detail.number,
+            total_score: best_detail.total_score,
+            is_finalized: true,
+        };
 
-        if parent_details.total_score + header.score() > self.best_block_detail().total_score {
-            let best_hash = self.best_block_hash();
-            let route = tree_route(self, best_hash, parent_hash)
-                .expect("blocks being imported always within recent history; qed");
-
-            match route.retracted.len() {
+        match engine.fork_choice(&new, &current) {
+            ForkChoice::New => match route.retracted.len() {
                 0 => BlockLocation::CanonChain,
                 _ => BlockLocation::BranchBecomingCanonChain(route),
-            }
-        } else {
-            BlockLocation::Branch
+            },
+            ForkChoice::Old => BlockLocation::Branch,
         }
     }
 
+    /// The number of the earliest canon block whose body is kept. The bodies below it are pruned.
+    pub fn earliest_body_number(&self) -> BlockNumber {
+        *self.earliest_body_number.read()
+    }
+
+    /// Delete the bodies of the canon blocks below `number`, keeping their headers and extras.
+    /// The caller must not prune the blocks which can be retracted.
+    pub fn prune_bodies(&self, batch: &mut DBTransaction, number: BlockNumber) {
+        let mut earliest_body_number = self.earliest_body_number.write();
+        let number = cmp::min(number, *earliest_body_number + MAX_PRUNED_BODIES);
+        if number <= *earliest_body_number {
+            return
+        }
+        let pruned: Vec<H256> = (*earliest_body_number..number).filter_map(|number| self.block_hash(number)).collect();
+        self.body_db.prune(batch, &pruned);
+        batch.put(db::COL_EXTRA, EARLIEST_BODY_KEY, &::rlp::encode(&number));
+        *earliest_body_number = number;
+    }
+
     /// Returns general blockchain information
     pub fn chain_info(&self) -> BlockChainInfo {
         let best_block_hash = self.best_block_hash();
@@ -195,6 +273,23 @@ impl BlockChain {
         self.headerchain.best_header()
     }
 
+    /// Returns numbers of the canon blocks in `[from_block, to_block]`
+    /// whose log bloom contains any of the given blooms.
+    pub fn blocks_with_bloom(
+        &self,
+        blooms: &[Bloom],
+        from_block: BlockNumber,
+        to_block: BlockNumber,
+    ) -> Vec<BlockNumber> {
+        let to_block = cmp::min(to_block, self.best_block_detail().number);
+        (from_block..=to_block)
+            .filter(|number| {
+                let block_bloom: Option<Bloom> = self.db.read(db::COL_EXTRA, number);
+                block_bloom.map_or(false, |block_bloom| blooms.iter().any(|bloom| block_bloom.contains_bloom(bloom)))
+            })
+            .collect()
+    }
+
     /// Insert an epoch transition. Provide an epoch number being transitioned to
     /// and epoch transition object.
     ///
@@ -217,7 +312,6 @@ impl BlockChain {
 
     /// Iterate over all epoch transitions.
     /// This will only return transitions within the canonical chain.
-    #[allow(dead_code)]
     pub fn epoch_transitions(&self) -> EpochTransitionIter {
         let iter = self.db.iter_from_prefix(db::COL_EXTRA, &EPOCH_KEY_PREFIX[..]);
         EpochTransitionIter {
@@ -240,7 +334,6 @@ impl BlockChain {
     /// This will give the epoch that any children of this parent belong to.
     ///
     /// The block corresponding the the parent hash must be stored already.
-    #[allow(dead_code)]
     pub fn epoch_transition_for(&self, parent_hash: H256) -> Option<EpochTransition> {
         // slow path: loop back block by block
         for hash in self.ancestry_iter(parent_hash)? {
@@ -266,9 +359,9 @@ impl BlockChain {
     }
 
     /// Iterator that lists `first` and then all of `first`'s ancestors, by hash.
-    #[allow(dead_code)]
     pub fn ancestry_iter(&self, first: H256) -> Option<AncestryIter> {
-        if self.is_known(&first) {
+        // The bodies may be pruned. The headers are enough to walk back.
+        if self.is_known_header(&first) {
             Some(AncestryIter {
                 current: first,
                 chain: self,
@@ -289,6 +382,74 @@ impl BlockChain {
     pub fn get_pending_transition(&self, hash: H256) -> Option<PendingEpochTransition> {
         self.db.read(db::COL_EXTRA, &hash)
     }
+
+    /// Mark the block as the latest finalized block, with the proof given by the engine if any.
+    ///
+    /// The block should have already been inserted into the canonical chain.
+    pub fn insert_finalized_block(&self, batch: &mut DBTransaction, hash: H256, proof: Option<Bytes>) {
+        if let Some(proof) = proof {
+            batch.write(
+                db::COL_EXTRA,
+                &hash,
+                &FinalityProof {
+                    proof,
+                },
+            );
+        }
+        self.headerchain.insert_finalized_header(batch, &hash);
+    }
+
+    /// Make the canon block with the given hash the best block again, deleting the canon blocks after it.
+    /// The blocks on the other branches are kept. It's meant for the test networks.
+    ///
+    /// Returns the hashes of the deleted blocks, or `None` if the block is not in the canon chain.
+    pub fn revert_to(&self, batch: &mut DBTransaction, hash: &H256) -> Option<Vec<H256>> {
+        let number = self.block_number(hash)?;
+        if self.block_hash(number) != Some(*hash) {
+            return None
+        }
+        assert!(self.pending_best_block_hash.read().is_none());
+
+        let finalized_number = self.block_number(&self.finalized_block_hash());
+        let best_number = self.best_block_detail().number;
+        let reverted = self.headerchain.revert_to(batch, hash);
+        self.body_db.revert(batch, &reverted);
+        self.invoice_db.revert(batch, &reverted);
+        for reverted_number in (number + 1)..=best_number {
+            batch.delete(db::COL_EXTRA, &Key::<Bloom>::key(&reverted_number));
+        }
+        for reverted_hash in &reverted {
+            batch.delete(db::COL_EXTRA, &Key::<FinalityProof>::key(reverted_hash));
+            batch.delete(db::COL_EXTRA, &Key::<PendingEpochTransition>::key(reverted_hash));
+        }
+        if finalized_number.map_or(false, |finalized_number| finalized_number > number) {
+            self.headerchain.insert_finalized_header(batch, hash);
+        }
+
+        batch.put(db::COL_EXTRA, BEST_BLOCK_KEY, hash);
+        *self.best_block_hash.write() = *hash;
+        Some(reverted)
+    }
+
+    /// Get the hash of the latest finalized block. The genesis block is final from the start.
+    pub fn finalized_block_hash(&self) -> H256 {
+        self.headerchain.finalized_header_hash()
+    }
+
+    /// Get the finality proof of the block, if the engine gave one.
+    pub fn finality_proof(&self, hash: &H256) -> Option<Bytes> {
+        self.db.read(db::COL_EXTRA, hash).map(|finality: FinalityProof| finality.proof)
+    }
+
+    /// Get the hit/miss counters and the occupancy of the caches.
+    pub fn cache_stats(&self) -> BlockChainCacheStats {
+        BlockChainCacheStats {
+            headers: self.headerchain.header_cache_stats(),
+            bodies: self.body_db.body_cache_stats(),
+            block_details: self.headerchain.detail_cache_stats(),
+            transaction_addresses: self.body_db.transaction_address_cache_stats(),
+        }
+    }
 }
 
 /// An iterator which walks the blockchain towards the genesis.
@@ -311,7 +472,6 @@ impl<'a> Iterator for AncestryIter<'a> {
 
 /// An iterator which walks all epoch transitions.
 /// Returns epoch transitions.
-#[allow(dead_code)]
 pub struct EpochTransitionIter<'a> {
     chain: &'a BlockChain,
     prefix_iter: Box<Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>,
@@ -375,6 +535,37 @@ pub trait BlockProvider: HeaderProvider + BodyProvider + InvoiceProvider {
         })
     }
 
+    /// Get the address of transaction with given hash in the canon chain.
+    fn transaction_address(&self, hash: &H256) -> Option<TransactionAddress> {
+        self.transaction_addresses(hash)?.addresses.into_iter().find(|address| {
+            let block_hash = address.parcel_address.block_hash;
+            self.block_number(&block_hash).and_then(|number| self.block_hash(number)) == Some(block_hash)
+        })
+    }
+
+    /// Get the addresses of the parcels sent by the account in the canon chain, in the order of the chain.
+    /// The first `offset` of them are skipped, and at most `limit` of them are read.
+    fn canon_sender_parcel_addresses(&self, sender: &Address, offset: usize, limit: usize) -> Vec<ParcelAddress> {
+        let mut addresses = Vec::new();
+        if limit == 0 {
+            return addresses
+        }
+        let mut skipped = 0;
+        self.for_each_sender_parcel_address(sender, &mut |address| {
+            let block_hash = address.block_hash;
+            if self.block_number(&block_hash).and_then(|number| self.block_hash(number)) != Some(block_hash) {
+                return true
+            }
+            if skipped < offset {
+                skipped += 1;
+                return true
+            }
+            addresses.push(address);
+            addresses.len() < limit
+        });
+        addresses
+    }
+
     /// Get the transaction with given transaction hash.
     fn transaction(&self, transaction: &TransactionAddress) -> Option<Transaction> {
         self.parcel(&transaction.parcel_address).and_then(|parcel| match &parcel.signed.as_unsigned().action {
@@ -430,8 +621,12 @@ impl BodyProvider for BlockChain {
         self.body_db.parcel_address(hash)
     }
 
-    fn transaction_address(&self, hash: &H256) -> Option<TransactionAddress> {
-        self.body_db.transaction_address(hash)
+    fn transaction_addresses(&self, hash: &H256) -> Option<TransactionAddresses> {
+        self.body_db.transaction_addresses(hash)
+    }
+
+    fn for_each_sender_parcel_address(&self, sender: &Address, f: &mut FnMut(ParcelAddress) -> bool) {
+        self.body_db.for_each_sender_parcel_address(sender, f)
     }
 
     fn block_body(&self, hash: &H256) -> Option<encoded::Body> {
@@ -18,6 +18,7 @@ use std::collections::HashMap;
 use std::mem;
 use std::sync::Arc;
 
+use ckey::Address;
 use ctypes::parcel::Action;
 use kvdb::{DBTransaction, KeyValueDB};
 use parking_lot::RwLock;
@@ -25,33 +26,37 @@ use primitives::{Bytes, H256};
 use rlp::RlpStream;
 use rlp_compress::{blocks_swapper, compress, decompress};
 
-use super::super::db::{self, CacheUpdatePolicy, Readable, Writable};
+use super::super::db::{self, CacheUpdatePolicy, Key, Readable, Writable};
 use super::super::encoded;
 use super::super::views::BlockView;
 use super::block_info::BlockLocation;
-use super::extras::{ParcelAddress, TransactionAddress};
+use super::cache::{CacheCapacities, CacheStats, LruCache};
+use super::extras::{
+    sender_parcel_key, sender_parcel_key_prefix, ParcelAddress, TransactionAddress, TransactionAddresses,
+    SENDER_PARCEL_KEY_PREFIX,
+};
 
 pub struct BodyDB {
     // block cache
-    body_cache: RwLock<HashMap<H256, Bytes>>,
+    body_cache: RwLock<LruCache<H256, Bytes>>,
     parcel_address_cache: RwLock<HashMap<H256, ParcelAddress>>,
     pending_parcel_addresses: RwLock<HashMap<H256, Option<ParcelAddress>>>,
 
-    transaction_address_cache: RwLock<HashMap<H256, TransactionAddress>>,
-    pending_transaction_addresses: RwLock<HashMap<H256, Option<TransactionAddress>>>,
+    transaction_address_cache: RwLock<LruCache<H256, TransactionAddresses>>,
+    pending_transaction_addresses: RwLock<HashMap<H256, TransactionAddresses>>,
 
     db: Arc<KeyValueDB>,
 }
 
 impl BodyDB {
     /// Create new instance of blockchain from given Genesis.
-    pub fn new(genesis: &BlockView, db: Arc<KeyValueDB>) -> Self {
+    pub fn new(genesis: &BlockView, db: Arc<KeyValueDB>, capacities: &CacheCapacities) -> Self {
         let bdb = Self {
-            body_cache: RwLock::new(HashMap::new()),
+            body_cache: RwLock::new(LruCache::new(capacities.bodies)),
             parcel_address_cache: RwLock::new(HashMap::new()),
             pending_parcel_addresses: RwLock::new(HashMap::new()),
 
-            transaction_address_cache: RwLock::new(HashMap::new()),
+            transaction_address_cache: RwLock::new(LruCache::new(capacities.transaction_addresses)),
             pending_transaction_addresses: RwLock::new(HashMap::new()),
 
             db,
@@ -74,7 +79,14 @@ impl BodyDB {
     /// Inserts the block body into backing cache database.
     /// Expects the body to be valid and already verified.
     /// If the body is already known, does nothing.
-    pub fn insert_body(&self, batch: &mut DBTransaction, block: &BlockView, location: &BlockLocation) {
+    /// `senders` are the senders of the parcels of the block, in the same order.
+    pub fn insert_body(
+        &self,
+        batch: &mut DBTransaction,
+        block: &BlockView,
+        location: &BlockLocation,
+        senders: &[Address],
+    ) {
         let hash = block.hash();
 
         if self.is_known_body(&hash) {
@@ -95,12 +107,105 @@ impl BodyDB {
             self.new_parcel_address_entries(block, location),
             CacheUpdatePolicy::Overwrite,
         );
-        batch.extend_with_option_cache(
+        let new_transaction_addresses = self.new_transaction_address_entries(block, &*pending_transaction_addresses);
+        batch.extend_with_cache(
             db::COL_EXTRA,
             &mut *pending_transaction_addresses,
-            self.new_transaction_address_entries(block, location),
+            new_transaction_addresses,
             CacheUpdatePolicy::Overwrite,
         );
+        // Like the transaction addresses, the ones in the retracted blocks are kept.
+        let number = block.header_view().number();
+        for (index, sender) in senders.iter().enumerate() {
+            let address = ParcelAddress {
+                block_hash: hash,
+                index,
+            };
+            batch.put(db::COL_EXTRA, &sender_parcel_key(sender, number, &address), &::rlp::encode(&address));
+        }
+    }
+
+    /// Delete the bodies of the reverted blocks and the addresses of their parcels and transactions.
+    pub fn revert(&self, batch: &mut DBTransaction, reverted: &[H256]) {
+        self.revert_sender_parcel_addresses(batch, reverted);
+
+        let bodies: Vec<encoded::Body> = reverted.iter().filter_map(|hash| self.block_body(hash)).collect();
+        let parcel_hashes: Vec<H256> = bodies.iter().flat_map(encoded::Body::parcel_hashes).collect();
+
+        let mut transaction_addresses: HashMap<H256, TransactionAddresses> = HashMap::new();
+        for parcel in bodies.iter().flat_map(encoded::Body::parcels) {
+            let transactions = match &parcel.action {
+                Action::AssetTransactionGroup {
+                    transactions,
+                    ..
+                } => transactions,
+                _ => continue,
+            };
+            for transaction in transactions {
+                let hash = transaction.hash();
+                if transaction_addresses.contains_key(&hash) {
+                    continue
+                }
+                if let Some(mut addresses) = self.transaction_addresses(&hash) {
+                    addresses.addresses.retain(|address| !reverted.contains(&address.parcel_address.block_hash));
+                    transaction_addresses.insert(hash, addresses);
+                }
+            }
+        }
+
+        let mut body_cache = self.body_cache.write();
+        let mut parcel_address_cache = self.parcel_address_cache.write();
+        let mut transaction_address_cache = self.transaction_address_cache.write();
+        for hash in reverted {
+            batch.delete(db::COL_BODIES, hash);
+            body_cache.remove(hash);
+        }
+        for hash in parcel_hashes {
+            batch.delete(db::COL_EXTRA, &Key::<ParcelAddress>::key(&hash));
+            parcel_address_cache.remove(&hash);
+        }
+        for (hash, addresses) in transaction_addresses {
+            if addresses.addresses.is_empty() {
+                batch.delete(db::COL_EXTRA, &Key::<TransactionAddresses>::key(&hash));
+                transaction_address_cache.remove(&hash);
+            } else {
+                batch.write(db::COL_EXTRA, &hash, &addresses);
+                transaction_address_cache.insert(hash, addresses);
+            }
+        }
+    }
+
+    /// The sender of a parcel signed by a regular key is the owner of the key at that block, which is not kept in the
+    /// body. So the whole index is scanned for the keys of the reverted blocks, which are rarely reverted.
+    fn revert_sender_parcel_addresses(&self, batch: &mut DBTransaction, reverted: &[H256]) {
+        let prefix = [SENDER_PARCEL_KEY_PREFIX];
+        let keys = self.db.iter_from_prefix(db::COL_EXTRA, &prefix).take_while(|(key, _)| key.starts_with(&prefix));
+        for (key, _) in keys {
+            // The prefix and the address of the sender, the block number, the block hash and the parcel index
+            if key.len() != 21 + 8 + 32 + 8 {
+                continue
+            }
+            if reverted.contains(&H256::from_slice(&key[29..61])) {
+                batch.delete(db::COL_EXTRA, &key);
+            }
+        }
+    }
+
+    /// Delete the bodies of the blocks, keeping the addresses of their parcels.
+    pub fn prune(&self, batch: &mut DBTransaction, pruned: &[H256]) {
+        let mut body_cache = self.body_cache.write();
+        for hash in pruned {
+            batch.delete(db::COL_BODIES, hash);
+            body_cache.remove(hash);
+        }
+    }
+
+    pub fn body_cache_stats(&self) -> CacheStats {
+        self.body_cache.read().stats()
+    }
+
+    pub fn transaction_address_cache_stats(&self) -> CacheStats {
+        self.transaction_address_cache.read().stats()
     }
 
     /// Apply pending insertion updates
@@ -123,15 +228,7 @@ impl BodyDB {
         }
 
         let new_transactions = mem::replace(&mut *pending_transaction_addresses, HashMap::new());
-        let (retracted_transactions, enacted_transactions) =
-            new_transactions.into_iter().partition::<HashMap<_, _>, _>(|&(_, ref value)| value.is_none());
-
-        transaction_address_cache
-            .extend(enacted_transactions.into_iter().map(|(k, v)| (k, v.expect("Parcels were partitioned; qed"))));
-
-        for hash in retracted_transactions.keys() {
-            transaction_address_cache.remove(hash);
-        }
+        transaction_address_cache.extend(new_transactions);
     }
 
     /// This function returns modified parcel addresses.
@@ -198,122 +295,38 @@ impl BodyDB {
         }
     }
 
+    /// This function returns the transaction addresses appended with the ones in the block.
+    /// The addresses in the retracted blocks are kept, and resolved against the canon chain when queried.
     fn new_transaction_address_entries(
         &self,
         block: &BlockView,
-        location: &BlockLocation,
-    ) -> HashMap<H256, Option<TransactionAddress>> {
-        match location {
-            BlockLocation::CanonChain => block
-                .parcels()
-                .into_iter()
-                .enumerate()
-                .flat_map(|(parcel_index, parcel)| {
-                    match &parcel.action {
-                        Action::AssetTransactionGroup {
-                            transactions,
-                            ..
-                        } => Some(transactions),
-                        _ => None,
-                    }.iter()
-                        .flat_map(|transactions| transactions.iter())
-                        .enumerate()
-                        .map(|(index, transaction)| {
-                            let parcel_address = ParcelAddress {
-                                block_hash: block.hash(),
-                                index: parcel_index,
-                            };
-                            (
-                                transaction.hash(),
-                                Some(TransactionAddress {
-                                    parcel_address,
-                                    index,
-                                }),
-                            )
-                        })
-                        .collect::<Vec<_>>() // FIXME: Find a way to remove collect.
-                })
-                .collect(),
-            BlockLocation::BranchBecomingCanonChain(ref data) => {
-                let addresses = data.enacted.iter().flat_map(|hash| {
-                    let body = self.block_body(hash).expect("Enacted block must be in database.");
-                    body.parcels()
-                        .into_iter()
-                        .enumerate()
-                        .flat_map(|(parcel_index, parcel)| {
-                            match &parcel.action {
-                                Action::AssetTransactionGroup {
-                                    transactions,
-                                    ..
-                                } => Some(transactions),
-                                _ => None,
-                            }.iter()
-                                .flat_map(|transactions| transactions.iter())
-                                .enumerate()
-                                .map(|(index, transaction)| {
-                                    let parcel_address = ParcelAddress {
-                                        block_hash: *hash,
-                                        index: parcel_index,
-                                    };
-                                    (
-                                        transaction.hash(),
-                                        Some(TransactionAddress {
-                                            parcel_address,
-                                            index,
-                                        }),
-                                    )
-                                })
-                                .collect::<Vec<_>>() // FIXME: Find a way to remove collect
-                        })
-                        .collect::<Vec<_>>()
+        pending: &HashMap<H256, TransactionAddresses>,
+    ) -> HashMap<H256, TransactionAddresses> {
+        let block_hash = block.hash();
+        let mut entries: HashMap<H256, TransactionAddresses> = HashMap::new();
+        for (parcel_index, parcel) in block.parcels().into_iter().enumerate() {
+            let transactions = match &parcel.action {
+                Action::AssetTransactionGroup {
+                    transactions,
+                    ..
+                } => transactions,
+                _ => continue,
+            };
+            for (index, transaction) in transactions.iter().enumerate() {
+                let hash = transaction.hash();
+                let entry = entries.entry(hash).or_insert_with(|| {
+                    pending.get(&hash).cloned().or_else(|| self.transaction_addresses(&hash)).unwrap_or_default()
                 });
-
-                let current_addresses = block.parcels().into_iter().enumerate().flat_map(|(parcel_index, parcel)| {
-                    match &parcel.action {
-                        Action::AssetTransactionGroup {
-                            transactions,
-                            ..
-                        } => Some(transactions),
-                        _ => None,
-                    }.iter()
-                        .flat_map(|transactions| transactions.iter())
-                        .enumerate()
-                        .map(|(index, transaction)| {
-                            let parcel_address = ParcelAddress {
-                                block_hash: block.hash(),
-                                index: parcel_index,
-                            };
-                            (
-                                transaction.hash(),
-                                Some(TransactionAddress {
-                                    parcel_address,
-                                    index,
-                                }),
-                            )
-                        })
-                        .collect::<Vec<_>>() // FIXME: Find a way to remove collect
-                });
-
-                let retracted = data.retracted.iter().flat_map(|hash| {
-                    let body = self.block_body(hash).expect("Retracted block must be in database.");
-                    body.parcels()
-                        .into_iter()
-                        .map(|parcel| (*parcel).clone())
-                        .filter_map(|parcel| match parcel.action {
-                            Action::AssetTransactionGroup {
-                                transactions,
-                                ..
-                            } => Some(transactions),
-                            _ => None,
-                        })
-                        .flat_map(|transactions| transactions.into_iter().map(|transaction| (transaction.hash(), None)))
+                entry.addresses.push(TransactionAddress {
+                    parcel_address: ParcelAddress {
+                        block_hash,
+                        index: parcel_index,
+                    },
+                    index,
                 });
-
-                // The order here is important! Don't remove parcel if it was part of enacted blocks as well.
-                retracted.chain(addresses).chain(current_addresses).collect()
             }
-            BlockLocation::Branch => HashMap::new(),
         }
+        entries
     }
 
     /// Create a block body from a block.
@@ -333,7 +346,12 @@ pub trait BodyProvider {
     /// Get the address of parcel with given hash.
     fn parcel_address(&self, hash: &H256) -> Option<ParcelAddress>;
 
-    fn transaction_address(&self, hash: &H256) -> Option<TransactionAddress>;
+    /// Get the addresses of transaction with given hash, including the ones not in the canon chain.
+    fn transaction_addresses(&self, hash: &H256) -> Option<TransactionAddresses>;
+
+    /// Visit the addresses of the parcels sent by the account in the order of the chain, including the ones not in the
+    /// canon chain, until `f` returns false.
+    fn for_each_sender_parcel_address(&self, sender: &Address, f: &mut FnMut(ParcelAddress) -> bool);
 
     /// Get the block body (uncles and parcels).
     fn block_body(&self, hash: &H256) -> Option<encoded::Body>;
@@ -350,10 +368,19 @@ impl BodyProvider for BodyDB {
         Some(result)
     }
 
-    fn transaction_address(&self, hash: &H256) -> Option<TransactionAddress> {
+    fn transaction_addresses(&self, hash: &H256) -> Option<TransactionAddresses> {
         Some(self.db.read_with_cache(db::COL_EXTRA, &self.transaction_address_cache, hash)?)
     }
 
+    fn for_each_sender_parcel_address(&self, sender: &Address, f: &mut FnMut(ParcelAddress) -> bool) {
+        let prefix = sender_parcel_key_prefix(sender);
+        for (key, value) in self.db.iter_from_prefix(db::COL_EXTRA, &prefix) {
+            if !key.starts_with(&prefix) || !f(::rlp::decode(&value)) {
+                break
+            }
+        }
+    }
+
     /// Get block body data
     fn block_body(&self, hash: &H256) -> Option<encoded::Body> {
         // Check cache first
@@ -375,3 +402,70 @@ impl BodyProvider for BodyDB {
         Some(encoded::Body::new(raw_body))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ckey::{Generator, Random};
+    use ctypes::parcel::Parcel;
+    use kvdb_memorydb;
+    use primitives::U256;
+
+    use super::super::super::header::Header;
+    use super::super::super::parcel::SignedParcel;
+    use super::super::super::tests::helpers::{create_test_block, create_test_block_with_data};
+    use super::*;
+
+    fn sender_parcel_addresses(body_db: &BodyDB, sender: &Address) -> Vec<ParcelAddress> {
+        let mut addresses = Vec::new();
+        body_db.for_each_sender_parcel_address(sender, &mut |address| {
+            addresses.push(address);
+            true
+        });
+        addresses
+    }
+
+    #[test]
+    fn reverted_parcels_are_removed_from_the_sender_index() {
+        let db: Arc<KeyValueDB> = Arc::new(kvdb_memorydb::create(db::NUM_COLUMNS.unwrap_or(0)));
+        let genesis = Header::default();
+        let capacities = CacheCapacities::from_budget(1024 * 1024);
+        let body_db = BodyDB::new(&BlockView::new(&create_test_block(&genesis)), Arc::clone(&db), &capacities);
+
+        let parcel = Parcel {
+            nonce: U256::zero(),
+            fee: U256::from(100),
+            network_id: "tc".into(),
+            action: Action::AssetTransactionGroup {
+                transactions: vec![],
+                changes: vec![],
+                signatures: vec![],
+            },
+        };
+        let keypair = Random.generate().unwrap();
+        // The owner of the regular key which signs the parcel
+        let sender = Random.generate().unwrap().address();
+        let mut header = Header::default();
+        header.set_number(1);
+        header.set_parent_hash(genesis.hash());
+        let parcel = SignedParcel::new_with_sign(parcel, keypair.private());
+        let block = create_test_block_with_data(&header, &[parcel], &[]);
+        let block = BlockView::new(&block);
+
+        let mut batch = DBTransaction::new();
+        body_db.insert_body(&mut batch, &block, &BlockLocation::CanonChain, &[sender]);
+        db.write(batch).unwrap();
+        body_db.commit();
+        assert_eq!(
+            vec![ParcelAddress {
+                block_hash: block.hash(),
+                index: 0,
+            }],
+            sender_parcel_addresses(&body_db, &sender)
+        );
+
+        let mut batch = DBTransaction::new();
+        body_db.revert(&mut batch, &[block.hash()]);
+        db.write(batch).unwrap();
+        assert_eq!(Vec::<ParcelAddress>::new(), sender_parcel_addresses(&body_db, &sender));
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::super::db::Cache;
+
+/// The share of the cache budget for each cache, in percent.
+const HEADER_CACHE_RATIO: usize = 25;
+const BODY_CACHE_RATIO: usize = 50;
+const DETAILS_CACHE_RATIO: usize = 14;
+const TRANSACTION_ADDRESS_CACHE_RATIO: usize = 10;
+const STATE_ROOT_CACHE_RATIO: usize = 1;
+
+/// Rough sizes of the cached items, used to turn the budget into item counts.
+const AVERAGE_HEADER_SIZE: usize = 512;
+const AVERAGE_BODY_SIZE: usize = 8 * 1024;
+const AVERAGE_DETAILS_SIZE: usize = 160;
+const AVERAGE_TRANSACTION_ADDRESSES_SIZE: usize = 128;
+/// A block hash and its state root.
+const AVERAGE_STATE_ROOT_SIZE: usize = 64;
+
+/// When a cache overflows, this fraction of its capacity is evicted at once
+/// so that the cost of finding the least recently used items is amortized.
+const EVICTION_DIVISOR: usize = 8;
+
+/// The number of items each blockchain cache can hold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheCapacities {
+    pub headers: usize,
+    pub bodies: usize,
+    pub block_details: usize,
+    pub transaction_addresses: usize,
+    pub recent_state_roots: usize,
+}
+
+impl CacheCapacities {
+    /// Splits the memory budget in bytes across the caches.
+    pub fn from_budget(budget: usize) -> Self {
+        Self {
+            headers: budget * HEADER_CACHE_RATIO / 100 / AVERAGE_HEADER_SIZE,
+            bodies: budget * BODY_CACHE_RATIO / 100 / AVERAGE_BODY_SIZE,
+            block_details: budget * DETAILS_CACHE_RATIO / 100 / AVERAGE_DETAILS_SIZE,
+            transaction_addresses: budget * TRANSACTION_ADDRESS_CACHE_RATIO / 100 / AVERAGE_TRANSACTION_ADDRESSES_SIZE,
+            recent_state_roots: budget * STATE_ROOT_CACHE_RATIO / 100 / AVERAGE_STATE_ROOT_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BlockChainCacheStats {
+    pub headers: CacheStats,
+    pub bodies: CacheStats,
+    pub block_details: CacheStats,
+    pub transaction_addresses: CacheStats,
+}
+
+struct Entry<V> {
+    value: V,
+    last_used: AtomicUsize,
+}
+
+/// A cache which keeps at most `capacity` items, evicting the least recently used ones.
+///
+/// Lookups take `&self` so that they can be done under a read lock;
+/// the recency and the hit/miss counters are updated atomically.
+pub struct LruCache<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    capacity: usize,
+    clock: AtomicUsize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<K, V> LruCache<K, V>
+where
+    K: Hash + Eq,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            clock: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn get(&self, k: &K) -> Option<&V> {
+        match self.entries.get(k) {
+            Some(entry) => {
+                entry.last_used.store(self.tick(), Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(&entry.value)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Looks up without touching the recency or the counters.
+    pub fn peek(&self, k: &K) -> Option<&V> {
+        self.entries.get(k).map(|entry| &entry.value)
+    }
+
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let entry = Entry {
+            value: v,
+            last_used: AtomicUsize::new(self.tick()),
+        };
+        let old = self.entries.insert(k, entry).map(|entry| entry.value);
+        self.shrink();
+        old
+    }
+
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        self.entries.remove(k).map(|entry| entry.value)
+    }
+
+    pub fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>, {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            len: self.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    fn tick(&self) -> usize {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn shrink(&mut self) {
+        if self.entries.len() <= self.capacity {
+            return
+        }
+        let target = self.capacity - self.capacity / EVICTION_DIVISOR;
+        let excess = self.entries.len() - target;
+
+        // Every tick is unique, so exactly `excess` entries are at or below the threshold.
+        let mut ticks: Vec<usize> =
+            self.entries.values().map(|entry| entry.last_used.load(Ordering::Relaxed)).collect();
+        ticks.sort_unstable();
+        let threshold = ticks[excess - 1];
+        self.entries.retain(|_, entry| entry.last_used.load(Ordering::Relaxed) > threshold);
+    }
+}
+
+impl<K, V> Cache<K, V> for LruCache<K, V>
+where
+    K: Hash + Eq,
+{
+    fn insert(&mut self, k: K, v: V) -> Option<V> {
+        LruCache::insert(self, k, v)
+    }
+
+    fn remove(&mut self, k: &K) -> Option<V> {
+        LruCache::remove(self, k)
+    }
+
+    fn get(&self, k: &K) -> Option<&V> {
+        LruCache::get(self, k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(8);
+        for i in 0..8 {
+            cache.insert(i, i);
+        }
+        // Touch the oldest one so that it survives the eviction.
+        assert_eq!(Some(&0), cache.get(&0));
+
+        cache.insert(8, 8);
+        assert_eq!(7, cache.len());
+        assert_eq!(Some(&0), cache.peek(&0));
+        assert_eq!(None, cache.peek(&1));
+        assert_eq!(None, cache.peek(&2));
+        assert_eq!(Some(&8), cache.peek(&8));
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut cache = LruCache::new(4);
+        cache.insert(1, "one");
+        assert_eq!(Some(&"one"), cache.get(&1));
+        assert_eq!(None, cache.get(&2));
+        assert_eq!(Some(&"one"), cache.peek(&1));
+        assert_eq!(
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                len: 1,
+                capacity: 4,
+            },
+            cache.stats()
+        );
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut cache = LruCache::new(0);
+        cache.insert(1, 1);
+        assert_eq!(0, cache.len());
+    }
+
+    #[test]
+    fn budget_is_split_across_caches() {
+        let capacities = CacheCapacities::from_budget(16 * 1024 * 1024);
+        assert_eq!(8192, capacities.headers);
+        assert_eq!(1024, capacities.bodies);
+        assert_eq!(2621, capacities.recent_state_roots);
+    }
+}
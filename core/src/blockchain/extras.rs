@@ -17,11 +17,12 @@
 use std::io::Write;
 use std::ops::{self, Deref};
 
+use ckey::Address;
 use ctypes::invoice::BlockInvoices;
 use ctypes::BlockNumber;
 use heapsize::HeapSizeOf;
 use kvdb::PREFIX_LEN as DB_PREFIX_LEN;
-use primitives::{H256, H264, U256};
+use primitives::{Bloom, Bytes, H256, H264, U256};
 
 use super::super::consensus::epoch::{PendingTransition as PendingEpochTransition, Transition as EpochTransition};
 use super::super::db::Key;
@@ -36,7 +37,7 @@ enum ExtrasIndex {
     BlockHash = 1,
     /// Parcel address index
     ParcelAddress = 2,
-    /// Transaction address index
+    /// Transaction addresses index
     TransactionAddress = 3,
     /// Block invoices index
     BlockInvoices = 4,
@@ -44,6 +45,12 @@ enum ExtrasIndex {
     EpochTransitions = 5,
     /// Pending epoch transition data index.
     PendingEpochTransition = 6,
+    /// Block log bloom index
+    BlocksBlooms = 7,
+    /// Finality proof index
+    FinalityProof = 8,
+    /// Parcel address by sender index
+    SenderParcelAddress = 9,
 }
 
 fn with_index(hash: &H256, i: ExtrasIndex) -> H264 {
@@ -62,19 +69,46 @@ impl Deref for BlockNumberKey {
         &self.0
     }
 }
+fn with_number(number: BlockNumber, i: ExtrasIndex) -> BlockNumberKey {
+    let mut result = [0u8; 5];
+    result[0] = i as u8;
+    result[1] = (number >> 24) as u8;
+    result[2] = (number >> 16) as u8;
+    result[3] = (number >> 8) as u8;
+    result[4] = number as u8;
+    BlockNumberKey(result)
+}
+
+pub struct AddressKey([u8; 21]);
+
+impl Deref for AddressKey {
+    type Target = [u8];
 
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+fn with_address(address: &Address, i: ExtrasIndex) -> AddressKey {
+    let mut result = [0u8; 21];
+    result[0] = i as u8;
+    result[1..].copy_from_slice(address);
+    AddressKey(result)
+}
 
 impl Key<H256> for BlockNumber {
     type Target = BlockNumberKey;
 
     fn key(&self) -> Self::Target {
-        let mut result = [0u8; 5];
-        result[0] = ExtrasIndex::BlockHash as u8;
-        result[1] = (self >> 24) as u8;
-        result[2] = (self >> 16) as u8;
-        result[3] = (self >> 8) as u8;
-        result[4] = *self as u8;
-        BlockNumberKey(result)
+        with_number(*self, ExtrasIndex::BlockHash)
+    }
+}
+
+impl Key<Bloom> for BlockNumber {
+    type Target = BlockNumberKey;
+
+    fn key(&self) -> Self::Target {
+        with_number(*self, ExtrasIndex::BlocksBlooms)
     }
 }
 
@@ -102,7 +136,7 @@ impl Key<BlockInvoices> for H256 {
     }
 }
 
-impl Key<TransactionAddress> for H256 {
+impl Key<TransactionAddresses> for H256 {
     type Target = H264;
 
     fn key(&self) -> H264 {
@@ -110,6 +144,28 @@ impl Key<TransactionAddress> for H256 {
     }
 }
 
+/// The first byte of the keys of the transaction addresses, which are followed by the hash of the transaction.
+pub const TRANSACTION_ADDRESS_KEY_PREFIX: u8 = ExtrasIndex::TransactionAddress as u8;
+
+/// The first byte of the keys of the parcels by sender, which are followed by the address of the sender.
+pub const SENDER_PARCEL_KEY_PREFIX: u8 = ExtrasIndex::SenderParcelAddress as u8;
+
+/// The prefix of the keys of the parcels sent by the account.
+pub fn sender_parcel_key_prefix(sender: &Address) -> AddressKey {
+    with_address(sender, ExtrasIndex::SenderParcelAddress)
+}
+
+/// The key of a parcel sent by the account, which is the prefix followed by the block number, the block hash and the
+/// parcel index. So the parcels of an account are iterated in the order of the chain.
+pub fn sender_parcel_key(sender: &Address, block_number: BlockNumber, address: &ParcelAddress) -> Vec<u8> {
+    let mut key = Vec::with_capacity(21 + 8 + 32 + 8);
+    key.extend_from_slice(&sender_parcel_key_prefix(sender));
+    key.extend((0..8).rev().map(|i| (block_number >> (i * 8)) as u8));
+    key.extend_from_slice(&address.block_hash);
+    key.extend((0..8).rev().map(|i| (address.index as u64 >> (i * 8)) as u8));
+    key
+}
+
 /// length of epoch keys.
 const EPOCH_KEY_LEN: usize = DB_PREFIX_LEN + 16;
 
@@ -150,6 +206,14 @@ impl Key<PendingEpochTransition> for H256 {
     }
 }
 
+impl Key<FinalityProof> for H256 {
+    type Target = H264;
+
+    fn key(&self) -> H264 {
+        with_index(self, ExtrasIndex::FinalityProof)
+    }
+}
+
 /// Familial details concerning a block
 #[derive(Debug, Clone, RlpEncodable, RlpDecodable)]
 pub struct BlockDetails {
@@ -193,9 +257,22 @@ pub struct TransactionAddress {
     pub index: usize,
 }
 
+/// Every address of a transaction, including the ones in blocks that are not in the canon chain.
+/// At most one of them is in the canon chain.
+#[derive(Debug, Default, PartialEq, Clone, RlpEncodable, RlpDecodable)]
+pub struct TransactionAddresses {
+    pub addresses: Vec<TransactionAddress>,
+}
+
 /// Candidate transitions to an epoch with specific number.
 #[derive(Clone, RlpEncodable, RlpDecodable)]
 pub struct EpochTransitions {
     pub number: u64,
     pub candidates: Vec<EpochTransition>,
 }
+
+/// Proof given by the engine that a block is final, e.g. the precommits of a Tendermint commit.
+#[derive(Debug, Clone, PartialEq, RlpEncodable, RlpDecodable)]
+pub struct FinalityProof {
+    pub proof: Bytes,
+}
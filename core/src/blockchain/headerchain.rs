@@ -24,15 +24,19 @@ use parking_lot::RwLock;
 use primitives::{Bytes, H256};
 use rlp_compress::{blocks_swapper, compress, decompress};
 
-use super::super::db::{self, CacheUpdatePolicy, Readable, Writable};
+use super::super::consensus::fork_choice::{ForkChoice, ForkHead};
+use super::super::consensus::CodeChainEngine;
+use super::super::db::{self, CacheUpdatePolicy, Key, Readable, Writable};
 use super::super::encoded;
 use super::super::header::Header;
 use super::super::views::HeaderView;
 use super::block_info::BlockLocation;
+use super::cache::{CacheCapacities, CacheStats, LruCache};
 use super::extras::BlockDetails;
 use super::route::tree_route;
 
 const BEST_HEADER_KEY: &[u8] = b"best-header";
+const FINALIZED_BLOCK_KEY: &[u8] = b"finalized-block";
 
 /// Structure providing fast access to blockchain data.
 ///
@@ -42,8 +46,8 @@ pub struct HeaderChain {
     best_header_hash: RwLock<H256>,
 
     // cache
-    header_cache: RwLock<HashMap<H256, Bytes>>,
-    detail_cache: RwLock<HashMap<H256, BlockDetails>>,
+    header_cache: RwLock<LruCache<H256, Bytes>>,
+    detail_cache: RwLock<LruCache<H256, BlockDetails>>,
     hash_cache: RwLock<HashMap<BlockNumber, H256>>,
 
     db: Arc<KeyValueDB>,
@@ -55,7 +59,7 @@ pub struct HeaderChain {
 
 impl HeaderChain {
     /// Create new instance of blockchain from given Genesis.
-    pub fn new(genesis: &HeaderView, db: Arc<KeyValueDB>) -> Self {
+    pub fn new(genesis: &HeaderView, db: Arc<KeyValueDB>, capacities: &CacheCapacities) -> Self {
         // load best header
         let best_header_hash = match db.get(db::COL_EXTRA, BEST_HEADER_KEY).unwrap() {
             Some(hash) => H256::from_slice(&hash),
@@ -86,8 +90,8 @@ impl HeaderChain {
         Self {
             best_header_hash: RwLock::new(best_header_hash),
 
-            header_cache: RwLock::new(HashMap::new()),
-            detail_cache: RwLock::new(HashMap::new()),
+            header_cache: RwLock::new(LruCache::new(capacities.headers)),
+            detail_cache: RwLock::new(LruCache::new(capacities.block_details)),
             hash_cache: RwLock::new(HashMap::new()),
 
             db,
@@ -102,7 +106,12 @@ impl HeaderChain {
     /// Expects the header to be valid and already verified.
     /// If the header is already known, does nothing.
     // FIXME: Find better return type. Returning `None` at duplication is not natural
-    pub fn insert_header(&self, batch: &mut DBTransaction, header: &HeaderView) -> Option<BlockLocation> {
+    pub fn insert_header(
+        &self,
+        batch: &mut DBTransaction,
+        header: &HeaderView,
+        engine: &CodeChainEngine,
+    ) -> Option<BlockLocation> {
         let hash = header.hash();
 
         if self.is_known_header(&hash) {
@@ -115,7 +124,7 @@ impl HeaderChain {
         let compressed_header = compress(header.rlp().as_raw(), blocks_swapper());
         batch.put(db::COL_HEADERS, &hash, &compressed_header);
 
-        let location = self.block_location(header);
+        let location = self.block_location(header, engine);
 
         let new_hashes = self.new_hash_entries(header, &location);
         let new_details = self.new_detail_entries(header);
@@ -135,6 +144,14 @@ impl HeaderChain {
         Some(location)
     }
 
+    pub fn header_cache_stats(&self) -> CacheStats {
+        self.header_cache.read().stats()
+    }
+
+    pub fn detail_cache_stats(&self) -> CacheStats {
+        self.detail_cache.read().stats()
+    }
+
     /// Apply pending insertion updates
     pub fn commit(&self) {
         let mut pending_best_header_hash = self.pending_best_header_hash.write();
@@ -202,25 +219,39 @@ impl HeaderChain {
     }
 
     /// Calculate insert location for new block
-    fn block_location(&self, header: &HeaderView) -> BlockLocation {
+    fn block_location(&self, header: &HeaderView, engine: &CodeChainEngine) -> BlockLocation {
         let parent_hash = header.parent_hash();
         let parent_details = self.block_details(&parent_hash).expect("Invalid parent hash");
-        let is_new_best = parent_details.total_score + header.score() > self.best_header_detail().total_score;
+        let best_hash = self.best_header_hash();
+        let best_detail = self.best_header_detail();
 
-        if is_new_best {
+        // find the route between old best block and the new one
+        let route =
+            tree_route(self, best_hash, parent_hash).expect("blocks being imported always within recent history; qed");
+        let ancestor_number = self.block_details(&route.ancestor).expect("Ancestor is always known").number;
+        let finalized_number = self.block_details(&self.finalized_header_hash()).map_or(0, |details| details.number);
+
+        let new = ForkHead {
+            hash: header.hash(),
+            number: header.number(),
+            total_score: parent_details.total_score + header.score(),
+            is_finalized: ancestor_number >= finalized_number,
+        };
+        let current = ForkHead {
+            hash: best_hash,
+            number: best_detail.number,
+            total_score: best_detail.total_score,
+            is_finalized: true,
+        };
+
+        match engine.fork_choice(&new, &current) {
             // on new best block we need to make sure that all ancestors
             // are moved to "canon chain"
-            // find the route between old best block and the new one
-            let best_hash = self.best_header_hash();
-            let route = tree_route(self, best_hash, parent_hash)
-                .expect("blocks being imported always within recent history; qed");
-
-            match route.retracted.len() {
+            ForkChoice::New => match route.retracted.len() {
                 0 => BlockLocation::CanonChain,
                 _ => BlockLocation::BranchBecomingCanonChain(route),
-            }
-        } else {
-            BlockLocation::Branch
+            },
+            ForkChoice::Old => BlockLocation::Branch,
         }
     }
 
@@ -237,6 +268,56 @@ impl HeaderChain {
     pub fn best_header_detail(&self) -> BlockDetails {
         self.block_details(&self.best_header_hash()).expect("Best header always exists")
     }
+
+    /// Make the canon header with the given hash the best header again, deleting the canon headers after it.
+    /// Returns the hashes of the deleted headers, from the latest one.
+    pub fn revert_to(&self, batch: &mut DBTransaction, hash: &H256) -> Vec<H256> {
+        assert!(self.pending_best_header_hash.read().is_none());
+
+        let number = self.block_number(hash).expect("Reverted header must be in the chain");
+        let best_number = self.best_header_detail().number;
+        let reverted: Vec<H256> = ((number + 1)..=best_number)
+            .rev()
+            .map(|number| self.block_hash(number).expect("Canon headers always exist"))
+            .collect();
+        let mut details = self.block_details(hash).expect("Reverted header must be in the chain");
+        details.children.retain(|child| !reverted.contains(child));
+
+        let mut best_header_hash = self.best_header_hash.write();
+        let mut header_cache = self.header_cache.write();
+        let mut detail_cache = self.detail_cache.write();
+        let mut hash_cache = self.hash_cache.write();
+
+        for (index, reverted_hash) in reverted.iter().enumerate() {
+            let reverted_number = best_number - index as BlockNumber;
+            batch.delete(db::COL_HEADERS, reverted_hash);
+            batch.delete(db::COL_EXTRA, &Key::<BlockDetails>::key(reverted_hash));
+            batch.delete(db::COL_EXTRA, &Key::<H256>::key(&reverted_number));
+            header_cache.remove(reverted_hash);
+            detail_cache.remove(reverted_hash);
+            hash_cache.remove(&reverted_number);
+        }
+        batch.write_with_cache(db::COL_EXTRA, &mut *detail_cache, *hash, details, CacheUpdatePolicy::Overwrite);
+        batch.put(db::COL_EXTRA, BEST_HEADER_KEY, hash);
+        *best_header_hash = *hash;
+
+        reverted
+    }
+
+    /// Mark the block as the latest finalized one.
+    pub fn insert_finalized_header(&self, batch: &mut DBTransaction, hash: &H256) {
+        batch.put(db::COL_EXTRA, FINALIZED_BLOCK_KEY, hash);
+    }
+
+    /// Get the hash of the latest finalized block. The genesis block is final from the start.
+    pub fn finalized_header_hash(&self) -> H256 {
+        let finalized =
+            self.db.get(db::COL_EXTRA, FINALIZED_BLOCK_KEY).expect("Low level database error. Some issue with disk?");
+        match finalized {
+            Some(hash) => H256::from_slice(&hash),
+            None => self.block_hash(0).expect("Genesis block is always inserted"),
+        }
+    }
 }
 
 /// Interface for querying blocks by hash and by number.
@@ -299,7 +380,7 @@ impl HeaderProvider for HeaderChain {
 }
 
 /// Get block header data
-fn block_header_data(hash: &H256, header_cache: &RwLock<HashMap<H256, Bytes>>, db: &KeyValueDB) -> Option<Vec<u8>> {
+fn block_header_data(hash: &H256, header_cache: &RwLock<LruCache<H256, Bytes>>, db: &KeyValueDB) -> Option<Vec<u8>> {
     // Check cache first
     {
         let read = header_cache.read();
@@ -313,7 +394,7 @@ fn block_header_data(hash: &H256, header_cache: &RwLock<HashMap<H256, Bytes>>, d
     let bytes = decompress(&b, blocks_swapper()).into_vec();
 
     let mut write = header_cache.write();
-    if let Some(v) = write.get(hash) {
+    if let Some(v) = write.peek(hash) {
         assert_eq!(&bytes, v);
         return Some(v.clone())
     }
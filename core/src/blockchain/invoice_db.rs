@@ -22,7 +22,7 @@ use kvdb::{DBTransaction, KeyValueDB};
 use parking_lot::RwLock;
 use primitives::H256;
 
-use super::super::db::{self, CacheUpdatePolicy, Readable, Writable};
+use super::super::db::{self, CacheUpdatePolicy, Key, Readable, Writable};
 use super::extras::ParcelAddress;
 
 /// Structure providing fast access to blockchain data.
@@ -58,6 +58,15 @@ impl InvoiceDB {
         let mut invoice_cache = self.invoice_cache.write();
         batch.extend_with_cache(db::COL_EXTRA, &mut *invoice_cache, invoice_map, CacheUpdatePolicy::Remove);
     }
+
+    /// Delete the invoices of the reverted blocks.
+    pub fn revert(&self, batch: &mut DBTransaction, reverted: &[H256]) {
+        let mut invoice_cache = self.invoice_cache.write();
+        for hash in reverted {
+            batch.delete(db::COL_EXTRA, &Key::<BlockInvoices>::key(hash));
+            invoice_cache.remove(hash);
+        }
+    }
 }
 
 /// Interface for querying invoices.
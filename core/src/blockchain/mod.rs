@@ -17,6 +17,7 @@
 mod block_info;
 mod blockchain;
 mod body_db;
+mod cache;
 mod extras;
 mod headerchain;
 mod invoice_db;
@@ -24,7 +25,11 @@ mod route;
 
 pub use self::blockchain::{BlockChain, BlockProvider};
 pub use self::body_db::BodyProvider;
-pub use self::extras::{BlockDetails, ParcelAddress, TransactionAddress};
+pub use self::cache::{BlockChainCacheStats, CacheCapacities, CacheStats, LruCache};
+pub use self::extras::{
+    sender_parcel_key, sender_parcel_key_prefix, BlockDetails, ParcelAddress, TransactionAddress,
+    TransactionAddresses, SENDER_PARCEL_KEY_PREFIX, TRANSACTION_ADDRESS_KEY_PREFIX,
+};
 pub use self::headerchain::HeaderProvider;
 pub use self::invoice_db::InvoiceProvider;
 pub use self::route::ImportRoute;
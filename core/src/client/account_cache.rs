@@ -0,0 +1,156 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+
+use ckey::Address;
+use primitives::{H256, U256};
+
+use super::super::blockchain::{CacheStats, LruCache};
+
+pub const DEFAULT_ACCOUNT_CACHE_SIZE: usize = 64 * 1024;
+/// The reorgs deeper than this clear the whole cache, since the modifications of the blocks are forgotten.
+const MODIFICATION_HISTORY: usize = 64;
+
+/// The nonces and the balances of the accounts on the best block, so that the queries and the mem pool don't traverse
+/// the trie for them.
+///
+/// The entries are valid only for `head`. When the best block changes, the accounts modified by the enacted and the
+/// retracted blocks are removed, and the others are kept.
+pub struct AccountCache {
+    head: H256,
+    /// The nonce and the balance of each address
+    accounts: LruCache<Address, (U256, U256)>,
+    /// The addresses modified by each recent block, ordered by the import
+    modifications: VecDeque<(H256, Vec<Address>)>,
+}
+
+impl AccountCache {
+    pub fn new(head: H256, capacity: usize) -> Self {
+        Self {
+            head,
+            accounts: LruCache::new(capacity),
+            modifications: VecDeque::new(),
+        }
+    }
+
+    /// Returns the nonce and the balance of the address if they are cached for the block `head`.
+    pub fn get(&self, head: &H256, address: &Address) -> Option<(U256, U256)> {
+        if *head != self.head {
+            return None
+        }
+        self.accounts.get(address).cloned()
+    }
+
+    /// Ignored if the best block is not `head` anymore.
+    pub fn insert(&mut self, head: &H256, address: Address, nonce: U256, balance: U256) {
+        if *head != self.head {
+            return
+        }
+        self.accounts.insert(address, (nonce, balance));
+    }
+
+    /// Records the accounts modified by an imported block, which doesn't need to be the best one.
+    pub fn record(&mut self, hash: H256, modified: Vec<Address>) {
+        if self.modifications.len() == MODIFICATION_HISTORY {
+            self.modifications.pop_front();
+        }
+        self.modifications.push_back((hash, modified));
+    }
+
+    /// Moves the cache to the new best block along the tree route.
+    pub fn update(&mut self, head: H256, enacted: &[H256], retracted: &[H256]) {
+        for hash in enacted.iter().chain(retracted) {
+            let modified = match self.modifications.iter().find(|(block, _)| block == hash) {
+                Some((_, modified)) => modified,
+                None => {
+                    ctrace!(CLIENT, "Clearing the account cache: the modifications of {} are unknown", hash);
+                    self.accounts.clear();
+                    break
+                }
+            };
+            for address in modified {
+                self.accounts.remove(address);
+            }
+        }
+        self.head = head;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.accounts.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_accounts_not_modified_by_the_enacted_block() {
+        let (genesis, block) = (H256::random(), H256::random());
+        let (modified, untouched) = (Address::random(), Address::random());
+        let mut cache = AccountCache::new(genesis, 16);
+        cache.insert(&genesis, modified, 0.into(), 100.into());
+        cache.insert(&genesis, untouched, 0.into(), 200.into());
+
+        cache.record(block, vec![modified]);
+        cache.update(block, &[block], &[]);
+
+        assert_eq!(None, cache.get(&genesis, &untouched));
+        assert_eq!(None, cache.get(&block, &modified));
+        assert_eq!(Some((0.into(), 200.into())), cache.get(&block, &untouched));
+    }
+
+    #[test]
+    fn reorg_removes_the_accounts_of_both_branches() {
+        let (parent, old, new) = (H256::random(), H256::random(), H256::random());
+        let addresses: Vec<_> = (0..3).map(|_| Address::random()).collect();
+        let mut cache = AccountCache::new(parent, 16);
+        cache.record(old, vec![addresses[0]]);
+        cache.update(old, &[old], &[]);
+        for address in &addresses {
+            cache.insert(&old, *address, 1.into(), 1.into());
+        }
+
+        cache.record(new, vec![addresses[1]]);
+        cache.update(new, &[new], &[old]);
+
+        assert_eq!(None, cache.get(&new, &addresses[0]));
+        assert_eq!(None, cache.get(&new, &addresses[1]));
+        assert_eq!(Some((1.into(), 1.into())), cache.get(&new, &addresses[2]));
+    }
+
+    #[test]
+    fn unknown_block_clears_the_cache() {
+        let (genesis, unknown) = (H256::random(), H256::random());
+        let address = Address::random();
+        let mut cache = AccountCache::new(genesis, 16);
+        cache.insert(&genesis, address, 0.into(), 0.into());
+
+        cache.update(unknown, &[unknown], &[]);
+
+        assert_eq!(None, cache.get(&unknown, &address));
+    }
+
+    #[test]
+    fn ignores_the_insertion_for_a_stale_head() {
+        let (stale, head) = (H256::random(), H256::random());
+        let address = Address::random();
+        let mut cache = AccountCache::new(head, 16);
+        cache.insert(&stale, address, 0.into(), 0.into());
+        assert_eq!(None, cache.get(&head, &address));
+    }
+}
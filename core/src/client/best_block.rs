@@ -0,0 +1,131 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{fence, spin_loop_hint, AtomicUsize, Ordering};
+
+use byteorder::{BigEndian, ByteOrder};
+use ctypes::BlockNumber;
+use primitives::H256;
+
+/// The number and the hash of the best block, which are read without a lock.
+///
+/// It's a sequence lock made of atomic words, each of which holds 32 bits. The writer makes the sequence odd while it
+/// writes the words, and the reader retries if the sequence was odd or changed while it read them. So the pollers
+/// never contend with the import on the locks of the chain.
+#[derive(Default)]
+pub struct BestBlock {
+    sequence: AtomicUsize,
+    number: [AtomicUsize; 2],
+    hash: [AtomicUsize; 8],
+}
+
+impl BestBlock {
+    pub fn new(number: BlockNumber, hash: &H256) -> Self {
+        let best_block = Self::default();
+        best_block.set(number, hash);
+        best_block
+    }
+
+    pub fn get(&self) -> (BlockNumber, H256) {
+        loop {
+            let before = self.sequence.load(Ordering::Acquire);
+            if before & 1 == 0 {
+                let number = (self.number[0].load(Ordering::Relaxed) as u64)
+                    | ((self.number[1].load(Ordering::Relaxed) as u64) << 32);
+                let mut hash = H256::zero();
+                for (word, bytes) in self.hash.iter().zip(hash.chunks_mut(4)) {
+                    BigEndian::write_u32(bytes, word.load(Ordering::Relaxed) as u32);
+                }
+                fence(Ordering::Acquire);
+                if self.sequence.load(Ordering::Relaxed) == before {
+                    return (number, hash)
+                }
+            }
+            spin_loop_hint();
+        }
+    }
+
+    pub fn set(&self, number: BlockNumber, hash: &H256) {
+        let mut sequence = self.sequence.load(Ordering::Relaxed);
+        loop {
+            if sequence & 1 == 0 {
+                match self.sequence.compare_exchange_weak(sequence, sequence + 1, Ordering::Acquire, Ordering::Relaxed)
+                {
+                    Ok(_) => break,
+                    Err(current) => sequence = current,
+                }
+            } else {
+                spin_loop_hint();
+                sequence = self.sequence.load(Ordering::Relaxed);
+            }
+        }
+        fence(Ordering::Release);
+
+        self.number[0].store(number as u32 as usize, Ordering::Relaxed);
+        self.number[1].store((number >> 32) as u32 as usize, Ordering::Relaxed);
+        for (word, bytes) in self.hash.iter().zip(hash.chunks(4)) {
+            word.store(BigEndian::read_u32(bytes) as usize, Ordering::Relaxed);
+        }
+        self.sequence.store(sequence.wrapping_add(2), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn get_returns_set() {
+        let hash = H256::random();
+        let best_block = BestBlock::new(0x1_0000_0002, &hash);
+        assert_eq!((0x1_0000_0002, hash), best_block.get());
+
+        let hash = H256::random();
+        best_block.set(3, &hash);
+        assert_eq!((3, hash), best_block.get());
+    }
+
+    #[test]
+    fn reader_never_sees_torn_value() {
+        let best_block = Arc::new(BestBlock::new(0, &H256::from(0)));
+        let writer = {
+            let best_block = Arc::clone(&best_block);
+            thread::spawn(move || {
+                for number in 1..20_000u64 {
+                    best_block.set(number, &H256::from(number));
+                }
+            })
+        };
+        let readers: Vec<_> = (0..2)
+            .map(|_| {
+                let best_block = Arc::clone(&best_block);
+                thread::spawn(move || {
+                    for _ in 0..20_000 {
+                        let (number, hash) = best_block.get();
+                        assert_eq!(H256::from(number), hash);
+                    }
+                })
+            })
+            .collect();
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
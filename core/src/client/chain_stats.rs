@@ -0,0 +1,397 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use ctypes::parcel::Action;
+use ctypes::BlockNumber;
+use parking_lot::{Mutex, RwLock};
+use primitives::{H256, U256};
+
+use super::super::encoded;
+use super::super::types::BlockId;
+use super::{BlockChainClient, BlockInfo, ChainInfo, ChainNotify};
+
+/// The number of the latest blocks whose statistics are kept by default.
+pub const DEFAULT_CHAIN_STATS_WINDOW: usize = 10_000;
+
+/// Statistics of a contiguous range of canonical blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainStats {
+    pub from: BlockNumber,
+    pub to: BlockNumber,
+    /// Seconds between the timestamps of consecutive blocks. None if the range has a single block.
+    pub average_block_interval: Option<f64>,
+    pub parcels_per_block: f64,
+    pub transactions_per_block: f64,
+    pub total_fees: U256,
+}
+
+struct BlockRecord {
+    hash: H256,
+    number: BlockNumber,
+    timestamp: u64,
+    parcels: u64,
+    transactions: u64,
+    fees: U256,
+    /// The running totals up to and including this block, counted from the oldest record ever kept.
+    total_parcels: u64,
+    total_transactions: u64,
+    total_fees: U256,
+}
+
+/// The records of the latest canonical blocks, kept contiguous and in ascending order.
+struct Window {
+    capacity: usize,
+    records: VecDeque<BlockRecord>,
+}
+
+impl Window {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: VecDeque::new(),
+        }
+    }
+
+    fn index_of(&self, number: BlockNumber) -> Option<usize> {
+        let first = self.records.front()?.number;
+        if number < first {
+            return None
+        }
+        let index = (number - first) as usize;
+        if index < self.records.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    fn contains(&self, number: BlockNumber, hash: &H256) -> bool {
+        self.index_of(number).map_or(false, |index| self.records[index].hash == *hash)
+    }
+
+    /// Drops the records above the given block number.
+    fn truncate_after(&mut self, number: BlockNumber) {
+        if let Some(index) = self.index_of(number + 1) {
+            self.records.truncate(index);
+        }
+    }
+
+    /// Appends the block, replacing the records at and above its number. The running totals are filled in here.
+    fn push(&mut self, parent_hash: &H256, mut record: BlockRecord) {
+        if let Some(index) = self.index_of(record.number) {
+            self.records.truncate(index);
+        }
+        let (total_parcels, total_transactions, total_fees) = match self.records.back() {
+            Some(last) if last.number + 1 == record.number && last.hash == *parent_hash => {
+                (last.total_parcels, last.total_transactions, last.total_fees)
+            }
+            _ => {
+                // Not a child of the latest record, so the window starts over.
+                self.records.clear();
+                (0, 0, U256::zero())
+            }
+        };
+        record.total_parcels = total_parcels + record.parcels;
+        record.total_transactions = total_transactions + record.transactions;
+        record.total_fees = total_fees + record.fees;
+        self.records.push_back(record);
+        while self.records.len() > self.capacity {
+            self.records.pop_front();
+        }
+    }
+
+    fn stats(&self, from: BlockNumber, to: BlockNumber) -> Option<ChainStats> {
+        if from > to {
+            return None
+        }
+        let first = &self.records[self.index_of(from)?];
+        let last = &self.records[self.index_of(to)?];
+        let count = to - from + 1;
+        let parcels = last.total_parcels - first.total_parcels + first.parcels;
+        let transactions = last.total_transactions - first.total_transactions + first.transactions;
+        let average_block_interval = if from == to {
+            None
+        } else {
+            Some(last.timestamp.saturating_sub(first.timestamp) as f64 / (to - from) as f64)
+        };
+        Some(ChainStats {
+            from,
+            to,
+            average_block_interval,
+            parcels_per_block: parcels as f64 / count as f64,
+            transactions_per_block: transactions as f64 / count as f64,
+            total_fees: last.total_fees - first.total_fees + first.fees,
+        })
+    }
+}
+
+/// Keeps the statistics of the latest canonical blocks up to date as blocks are imported,
+/// so a range is answered from running totals instead of reading the blocks again.
+///
+/// The blocks are read on a background thread, which follows the canonical chain from the best block back to the
+/// window. So the blocks imported while the notifications are suppressed, e.g. during the sync, are filled in later.
+pub struct ChainStatistics {
+    window: Arc<RwLock<Window>>,
+    sender: Mutex<Option<SyncSender<()>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ChainStatistics {
+    /// Starts loading the latest `capacity` blocks of the canonical chain in the background.
+    pub fn new(client: Arc<BlockChainClient>, capacity: usize) -> Arc<Self> {
+        let window = Arc::new(RwLock::new(Window::new(capacity)));
+        let (sender, receiver) = sync_channel(1);
+        let thread = {
+            let window = Arc::clone(&window);
+            thread::Builder::new()
+                .name("chain_stats".to_string())
+                .spawn(move || run(&*client, &window, &receiver))
+                .expect("Cannot spawn the chain statistics updater")
+        };
+        let stats = Arc::new(Self {
+            window,
+            sender: Mutex::new(Some(sender)),
+            thread: Mutex::new(Some(thread)),
+        });
+        stats.request_update();
+        stats
+    }
+
+    /// Returns None if any block of the range is not in the window.
+    pub fn stats(&self, from: BlockNumber, to: BlockNumber) -> Option<ChainStats> {
+        self.window.read().stats(from, to)
+    }
+
+    fn request_update(&self) {
+        if let Some(ref sender) = *self.sender.lock() {
+            // The queue is full only if an update is already requested, and it covers this one.
+            let _ = sender.try_send(());
+        }
+    }
+}
+
+impl Drop for ChainStatistics {
+    fn drop(&mut self) {
+        self.sender.lock().take();
+        if let Some(thread) = self.thread.lock().take() {
+            if thread.join().is_err() {
+                cerror!(CLIENT, "The chain statistics updater panicked");
+            }
+        }
+    }
+}
+
+fn run(client: &BlockChainClient, window: &RwLock<Window>, receiver: &Receiver<()>) {
+    while receiver.recv().is_ok() {
+        update(client, window);
+    }
+}
+
+/// Reads the canonical blocks which are not in the window yet, and appends them.
+/// The blocks are read before taking the write lock, so the readers are not blocked by the database.
+fn update(client: &BlockChainClient, window: &RwLock<Window>) {
+    let best = client.chain_info().best_block_number;
+    let capacity = window.read().capacity;
+    let oldest = best.saturating_sub(capacity.saturating_sub(1) as BlockNumber);
+
+    let mut records = Vec::new();
+    let mut number = best;
+    loop {
+        let hash = match client.block_hash(BlockId::Number(number)) {
+            Some(hash) => hash,
+            None => break,
+        };
+        if window.read().contains(number, &hash) {
+            break
+        }
+        let (header, body) = match (client.block_header(BlockId::Hash(hash)), client.block_body(BlockId::Hash(hash))) {
+            (Some(header), Some(body)) => (header, body),
+            _ => break,
+        };
+        records.push((header.parent_hash(), record(hash, &header, &body)));
+        if number == oldest {
+            break
+        }
+        number -= 1;
+    }
+
+    let mut window = window.write();
+    window.truncate_after(best);
+    for (parent_hash, record) in records.into_iter().rev() {
+        window.push(&parent_hash, record);
+    }
+}
+
+fn record(hash: H256, header: &encoded::Header, body: &encoded::Body) -> BlockRecord {
+    let parcels = body.parcels();
+    let transactions = parcels
+        .iter()
+        .map(|parcel| match parcel.action {
+            Action::AssetTransactionGroup {
+                ref transactions,
+                ..
+            } => transactions.len() as u64,
+            _ => 0,
+        })
+        .sum();
+    BlockRecord {
+        hash,
+        number: header.number(),
+        timestamp: header.timestamp(),
+        parcels: parcels.len() as u64,
+        transactions,
+        fees: parcels.iter().fold(U256::zero(), |sum, parcel| sum + parcel.fee),
+        total_parcels: 0,
+        total_transactions: 0,
+        total_fees: U256::zero(),
+    }
+}
+
+impl ChainNotify for ChainStatistics {
+    fn new_blocks(
+        &self,
+        _imported: Vec<H256>,
+        _invalid: Vec<H256>,
+        enacted: Vec<H256>,
+        retracted: Vec<H256>,
+        _sealed: Vec<H256>,
+        _duration: u64,
+    ) {
+        if !enacted.is_empty() || !retracted.is_empty() {
+            self.request_update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TestBlockChainClient;
+    use super::*;
+
+    fn record(hash: H256, number: BlockNumber, timestamp: u64, parcels: u64, fees: u64) -> BlockRecord {
+        BlockRecord {
+            hash,
+            number,
+            timestamp,
+            parcels,
+            transactions: parcels * 2,
+            fees: fees.into(),
+            total_parcels: 0,
+            total_transactions: 0,
+            total_fees: U256::zero(),
+        }
+    }
+
+    fn push(window: &mut Window, number: BlockNumber, timestamp: u64, parcels: u64, fees: u64) {
+        window.push(&H256::from(number), record(H256::from(number + 1), number, timestamp, parcels, fees));
+    }
+
+    #[test]
+    fn stats_of_a_range() {
+        let mut window = Window::new(16);
+        push(&mut window, 1, 100, 1, 10);
+        push(&mut window, 2, 110, 3, 30);
+        push(&mut window, 3, 130, 2, 20);
+
+        let stats = window.stats(1, 3).unwrap();
+        assert_eq!(Some(15.0), stats.average_block_interval);
+        assert_eq!(2.0, stats.parcels_per_block);
+        assert_eq!(4.0, stats.transactions_per_block);
+        assert_eq!(U256::from(60), stats.total_fees);
+
+        let stats = window.stats(2, 3).unwrap();
+        assert_eq!(Some(20.0), stats.average_block_interval);
+        assert_eq!(2.5, stats.parcels_per_block);
+        assert_eq!(U256::from(50), stats.total_fees);
+
+        let stats = window.stats(2, 2).unwrap();
+        assert_eq!(None, stats.average_block_interval);
+        assert_eq!(U256::from(30), stats.total_fees);
+
+        assert_eq!(None, window.stats(3, 2));
+        assert_eq!(None, window.stats(0, 2));
+        assert_eq!(None, window.stats(2, 4));
+    }
+
+    #[test]
+    fn old_records_are_dropped() {
+        let mut window = Window::new(2);
+        push(&mut window, 1, 100, 1, 10);
+        push(&mut window, 2, 110, 3, 30);
+        push(&mut window, 3, 130, 2, 20);
+
+        assert_eq!(None, window.stats(1, 3));
+        assert_eq!(U256::from(50), window.stats(2, 3).unwrap().total_fees);
+    }
+
+    #[test]
+    fn retracted_blocks_are_replaced() {
+        let mut window = Window::new(16);
+        push(&mut window, 1, 100, 1, 10);
+        push(&mut window, 2, 110, 3, 30);
+        push(&mut window, 3, 130, 2, 20);
+
+        window.truncate_after(1);
+        assert_eq!(None, window.stats(1, 2));
+        assert_eq!(U256::from(10), window.stats(1, 1).unwrap().total_fees);
+
+        window.push(&H256::from(2), record(H256::random(), 2, 105, 0, 5));
+        let stats = window.stats(1, 2).unwrap();
+        assert_eq!(Some(5.0), stats.average_block_interval);
+        assert_eq!(U256::from(15), stats.total_fees);
+    }
+
+    #[test]
+    fn a_block_from_another_branch_restarts_the_window() {
+        let mut window = Window::new(16);
+        push(&mut window, 1, 100, 1, 10);
+        push(&mut window, 2, 110, 3, 30);
+
+        window.push(&H256::random(), record(H256::random(), 3, 120, 1, 1));
+        assert_eq!(None, window.stats(2, 3));
+        assert_eq!(U256::from(1), window.stats(3, 3).unwrap().total_fees);
+    }
+
+    #[test]
+    fn blocks_imported_without_notification_are_filled_in() {
+        let client = TestBlockChainClient::new();
+        let window = RwLock::new(Window::new(16));
+        client.add_blocks(5, 2);
+        update(&client, &window);
+        assert_eq!(U256::from(100), window.read().stats(1, 5).unwrap().total_fees);
+
+        client.add_blocks(3, 1);
+        update(&client, &window);
+        let stats = window.read().stats(1, 8).unwrap();
+        assert_eq!(U256::from(130), stats.total_fees);
+        assert_eq!(13.0 / 8.0, stats.parcels_per_block);
+    }
+
+    #[test]
+    fn only_the_latest_blocks_are_loaded() {
+        let client = TestBlockChainClient::new();
+        let window = RwLock::new(Window::new(4));
+        client.add_blocks(8, 1);
+        update(&client, &window);
+        assert_eq!(None, window.read().stats(4, 8));
+        assert_eq!(U256::from(40), window.read().stats(5, 8).unwrap().total_fees);
+    }
+}
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::{Arc, Weak};
@@ -22,48 +23,57 @@ use std::time::Instant;
 use cio::IoChannel;
 use ckey::{Address, Public};
 use cmerkle::Result as TrieResult;
-use cnetwork::NodeId;
+use cnetwork::{ChainHead, NodeId};
 use cstate::{
     ActionHandler, AssetScheme, AssetSchemeAddress, OwnedAsset, OwnedAssetAddress, StateDB, TopBackend, TopLevelState,
-    TopStateInfo,
+    TopStateInfo, Trace,
 };
 use ctypes::invoice::ParcelInvoice;
 use ctypes::parcel::ShardChange;
 use ctypes::transaction::Transaction;
+use ctypes::util::unexpected::OutOfBounds;
 use ctypes::{BlockNumber, ShardId};
 use journaldb;
-use kvdb::{DBTransaction, KeyValueDB};
+use kvdb::{self, DBTransaction, KeyValueDB};
 use parking_lot::{Mutex, RwLock};
 use primitives::{Bytes, H256, U256};
 use rlp::{Encodable, UntrustedRlp};
 
 use super::super::block::{enact, ClosedBlock, Drain, IsBlock, LockedBlock, OpenBlock, SealedBlock};
 use super::super::blockchain::{
-    BlockChain, BlockProvider, BodyProvider, HeaderProvider, ImportRoute, InvoiceProvider, ParcelAddress,
-    TransactionAddress,
+    BlockChain, BlockChainCacheStats, BlockProvider, BodyProvider, CacheCapacities, HeaderProvider, ImportRoute,
+    InvoiceProvider, LruCache, ParcelAddress, TransactionAddress,
 };
 use super::super::consensus::epoch::Transition as EpochTransition;
 use super::super::consensus::CodeChainEngine;
 use super::super::encoded;
-use super::super::error::{BlockImportError, Error, ImportError, SchemeError};
+use super::super::error::{BlockError, BlockImportError, Error, ImportError, SchemeError};
 use super::super::header::Header;
 use super::super::miner::{Miner, MinerService};
 use super::super::parcel::{LocalizedParcel, SignedParcel, UnverifiedParcel};
-use super::super::scheme::{CommonParams, Scheme};
+use super::super::scheme::{Checkpoints, CommonParams, Scheme};
 use super::super::service::ClientIoMessage;
-use super::super::types::{BlockId, BlockStatus, ParcelId, TransactionId, VerificationQueueInfo as BlockQueueInfo};
+use super::super::types::{
+    parcel_topics, BlockId, BlockStatus, Filter, LocalizedLog, ParcelId, SyncProgress, TransactionId,
+    VerificationQueueInfo as BlockQueueInfo,
+};
 use super::super::verification::queue::{BlockQueue, HeaderQueue};
 use super::super::verification::{self, PreverifiedBlock, Verifier};
 use super::super::views::{BlockView, HeaderView};
+use super::account_cache::{AccountCache, DEFAULT_ACCOUNT_CACHE_SIZE};
+use super::best_block::BestBlock;
+use super::flusher::Flusher;
 use super::{
     AccountData, AssetClient, Balance, BlockChain as BlockChainTrait, BlockChainClient, BlockChainInfo, BlockInfo,
     BlockProducer, ChainInfo, ChainNotify, ClientConfig, DatabaseClient, EngineClient, EngineInfo,
     Error as ClientError, ExecuteClient, ImportBlock, ImportResult, ImportSealedBlock, MiningBlockChainClient, Nonce,
-    ParcelInfo, PrepareOpenBlock, RegularKey, RegularKeyOwner, ReopenBlock, Shard, StateOrBlock, TransactionInfo,
-    TransactionInvoice,
+    ParcelInfo, PrepareOpenBlock, RegularKey, RegularKeyOwner, ReopenBlock, RevertChain, Shard, StateInfo,
+    StateOrBlock, TraceClient, TransactionInfo, TransactionInvoice,
 };
 
 const MAX_MEM_POOL_SIZE: usize = 4096;
+/// The number of the recently imported headers whose fork points are kept.
+const MAX_FORK_POINTS: usize = 4096;
 
 pub struct Client {
     engine: Arc<CodeChainEngine>,
@@ -77,12 +87,28 @@ pub struct Client {
 
     state_db: RwLock<StateDB>,
 
+    /// State roots of recently imported or queried blocks, keyed by block hash.
+    /// The states built from them share the global caches of `state_db`, so a hit copies no account data.
+    recent_state_roots: Mutex<LruCache<H256, H256>>,
+
     /// List of actors to be notified on certain chain events
     notify: RwLock<Vec<Weak<ChainNotify>>>,
 
     /// Count of pending parcels in the queue
     queue_parcels: AtomicUsize,
 
+    /// The progress reported by the sync extension
+    sync_progress: RwLock<Option<SyncProgress>>,
+
+    /// The best block, updated whenever the chain commits a new one
+    best_block: BestBlock,
+
+    /// The nonces and the balances of the accounts on the best block
+    accounts: RwLock<AccountCache>,
+
+    /// Flushes the buffered writes of the imported blocks in the background
+    flusher: Flusher,
+
     importer: Importer,
 }
 
@@ -108,12 +134,17 @@ impl Client {
         }
 
         let gb = scheme.genesis_block();
-        let chain = BlockChain::new(&gb, db.clone());
+        let chain = BlockChain::new(&gb, db.clone(), config.blockchain_cache_size);
         scheme.check_genesis_common_params(&chain)?;
 
+        let best_block = BestBlock::new(chain.best_block_detail().number, &chain.best_block_hash());
+        let accounts = AccountCache::new(chain.best_block_hash(), DEFAULT_ACCOUNT_CACHE_SIZE);
         let engine = scheme.engine.clone();
+        let recent_state_roots = CacheCapacities::from_budget(config.blockchain_cache_size).recent_state_roots;
 
-        let importer = Importer::new(&config, engine.clone(), message_channel.clone(), miner)?;
+        let flusher = Flusher::new(Arc::clone(&db), config.db_flush_queue_size);
+        let importer =
+            Importer::new(&config, engine.clone(), scheme.checkpoints.clone(), message_channel.clone(), miner)?;
 
         let client = Arc::new(Client {
             engine,
@@ -121,8 +152,13 @@ impl Client {
             chain: RwLock::new(chain),
             db: RwLock::new(db),
             state_db: RwLock::new(state_db),
+            recent_state_roots: Mutex::new(LruCache::new(recent_state_roots)),
             notify: RwLock::new(Vec::new()),
             queue_parcels: AtomicUsize::new(0),
+            sync_progress: RwLock::new(None),
+            best_block,
+            accounts: RwLock::new(accounts),
+            flusher,
             importer,
         });
 
@@ -136,11 +172,35 @@ impl Client {
         &*self.engine
     }
 
+    pub fn set_sync_progress(&self, progress: SyncProgress) {
+        *self.sync_progress.write() = Some(progress);
+    }
+
     /// Adds an actor to be notified on certain events
     pub fn add_notify(&self, target: Arc<ChainNotify>) {
         self.notify.write().push(Arc::downgrade(&target));
     }
 
+    /// Must be called after the chain commits, while the import lock is held.
+    fn update_best_block(&self, chain: &BlockChain, enacted: &[H256], retracted: &[H256]) {
+        let hash = chain.best_block_hash();
+        self.best_block.set(chain.best_block_detail().number, &hash);
+        self.accounts.write().update(hash, enacted, retracted);
+    }
+
+    /// The nonce and the balance of the account on the best block.
+    fn latest_account(&self, address: &Address) -> Option<(U256, U256)> {
+        let (_, head) = self.best_block.get();
+        if let Some(account) = self.accounts.read().get(&head, address) {
+            return Some(account)
+        }
+        let state = self.state_at(BlockId::Hash(head))?;
+        let nonce = state.nonce(address).ok()?;
+        let balance = state.balance(address).ok()?;
+        self.accounts.write().insert(&head, *address, nonce, balance);
+        Some((nonce, balance))
+    }
+
     fn notify<F>(&self, f: F)
     where
         F: Fn(&ChainNotify), {
@@ -204,6 +264,15 @@ impl Client {
         results.len()
     }
 
+    /// Seals the parcels whose reseal was deferred by the minimum period between reseals.
+    pub fn update_deferred_sealing(&self) {
+        self.importer.miner.update_deferred_sealing(self);
+    }
+
+    pub fn remove_expired_parcels(&self) {
+        self.importer.miner.remove_expired_parcels(self);
+    }
+
     fn block_number_ref(&self, id: &BlockId) -> Option<BlockNumber> {
         match id {
             BlockId::Number(number) => Some(number.clone()),
@@ -220,24 +289,58 @@ impl Client {
             .expect("State root of best block header always valid.")
     }
 
+    /// Re-executes the first `count` parcels of the block on the state of its parent, tracing each parcel.
+    fn replay_parcels(&self, block_hash: H256, count: usize) -> Result<Option<Vec<Vec<Trace>>>, Error> {
+        let block_id = BlockId::Hash(block_hash);
+        let (header, body) = match (self.block_header(block_id), self.block_body(block_id)) {
+            (Some(header), Some(body)) => (header, body),
+            _ => return Ok(None),
+        };
+        let mut state = match self.state_at(BlockId::Hash(header.parent_hash())) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        state.start_tracing();
+
+        let params = self.engine.params();
+        let mut traces = Vec::new();
+        for parcel in body.parcels().into_iter().take(count) {
+            let parcel = SignedParcel::new(parcel)?;
+            state.apply(
+                &parcel,
+                &parcel.signer_public(),
+                &params.min_balance,
+                &params.name_fee_per_block,
+                header.number(),
+            )?;
+            traces.push(state.take_trace());
+        }
+        Ok(Some(traces))
+    }
+
     /// Attempt to get a copy of a specific block's final state.
     ///
     /// This will not fail if given BlockId::Latest.
     /// Otherwise, this can fail (but may not) if the DB prunes state or the block
     /// is unknown.
+    /// The state roots of recently imported or queried blocks are kept in memory, so their headers aren't re-read.
     pub fn state_at(&self, id: BlockId) -> Option<TopLevelState> {
-        // fast path for latest state.
-        match id {
-            BlockId::Latest => return Some(self.latest_state()),
-            _ => {}
-        }
-
-        self.block_header(id).and_then(|header| {
-            let db = self.state_db.read().clone_with_immutable_global_cache();
-
-            let root = header.state_root();
-            TopLevelState::from_existing(db, root).ok()
-        })
+        let hash = Self::block_hash(&self.chain.read(), id)?;
+        let cached_root = self.recent_state_roots.lock().get(&hash).cloned();
+        let root = match cached_root {
+            Some(root) => root,
+            None => {
+                let root = self.block_header(BlockId::Hash(hash))?.state_root();
+                self.recent_state_roots.lock().insert(hash, root);
+                root
+            }
+        };
+        let db = match id {
+            // The best block's state may use the canonical global cache.
+            BlockId::Latest => self.state_db.read().clone_canon(&hash),
+            _ => self.state_db.read().clone_with_immutable_global_cache(),
+        };
+        TopLevelState::from_existing(db, root).ok()
     }
 }
 
@@ -245,6 +348,17 @@ impl DatabaseClient for Client {
     fn database(&self) -> Arc<KeyValueDB> {
         Arc::clone(&self.db.read())
     }
+
+    fn flush_database(&self) -> kvdb::Result<()> {
+        self.flusher.flush()
+    }
+
+    fn backup_database(&self, path: &str) -> kvdb::Result<()> {
+        // The disk only gets the whole writes of the blocks, so the backup taken after the flush is consistent
+        // even if more blocks are imported while it is copied.
+        self.flusher.flush()?;
+        self.db.read().backup(path)
+    }
 }
 
 impl AssetClient for Client {
@@ -337,18 +451,75 @@ impl ExecuteClient for Client {
     }
 }
 
+impl TraceClient for Client {
+    fn replay_block(&self, id: BlockId) -> Result<Option<Vec<Vec<Trace>>>, Error> {
+        match Self::block_hash(&self.chain.read(), id) {
+            Some(block_hash) => self.replay_parcels(block_hash, usize::max_value()),
+            None => Ok(None),
+        }
+    }
+
+    fn trace_parcel(&self, hash: H256) -> Result<Option<Vec<Trace>>, Error> {
+        let address = match self.parcel_address(ParcelId::Hash(hash)) {
+            Some(address) => address,
+            None => return Ok(None),
+        };
+        let traces = self.replay_parcels(address.block_hash, address.index + 1)?;
+        Ok(traces.and_then(|mut traces| traces.pop()))
+    }
+
+    fn call_parcel(&self, parcel: &SignedParcel, id: BlockId) -> Result<Option<(ParcelInvoice, Vec<Trace>)>, Error> {
+        let header = match self.block_header(id) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        // The state is a copy, so the changes are dropped with it.
+        let mut state = match self.state_at(BlockId::Hash(header.hash())) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        self.engine.verify_parcel_basic(parcel, &header.decode())?;
+        state.start_tracing();
+
+        let params = self.engine.params();
+        let invoice = state.apply(
+            parcel,
+            &parcel.signer_public(),
+            &params.min_balance,
+            &params.name_fee_per_block,
+            header.number() + 1,
+        )?;
+        Ok(Some((invoice, state.take_trace())))
+    }
+}
+
 impl ChainInfo for Client {
     fn chain_info(&self) -> BlockChainInfo {
         let mut chain_info = self.chain.read().chain_info();
         chain_info.pending_total_score = chain_info.total_score + self.importer.block_queue.total_score();
         chain_info
     }
+
+    fn best_block_number_and_hash(&self) -> (BlockNumber, H256) {
+        self.best_block.get()
+    }
+}
+
+impl ChainHead for Client {
+    fn best_block_number(&self) -> BlockNumber {
+        self.best_block.get().0
+    }
 }
 
 impl EngineInfo for Client {
     fn common_params(&self) -> &CommonParams {
         self.engine().params()
     }
+
+    fn validators(&self, id: BlockId) -> Option<Vec<Address>> {
+        let header = self.block_header(id)?.decode();
+        self.engine().validators(&header)
+    }
 }
 
 impl EngineClient for Client {
@@ -368,6 +539,14 @@ impl EngineClient for Client {
     fn score_to_target(&self, score: &U256) -> U256 {
         self.engine.score_to_target(score)
     }
+
+    fn epoch_transition_for(&self, parent_hash: H256) -> Option<Option<EpochTransition>> {
+        let chain = self.chain.read();
+        if !chain.is_known_header(&parent_hash) {
+            return None
+        }
+        Some(chain.epoch_transition_for(parent_hash))
+    }
 }
 
 impl BlockInfo for Client {
@@ -409,21 +588,35 @@ impl ImportBlock for Client {
         use super::super::verification::queue::kind::blocks::Unverified;
         use super::super::verification::queue::kind::BlockLike;
 
-        let unverified = Unverified::new(bytes);
+        let mut unverified = Unverified::new(bytes).map_err(Error::from)?;
         {
-            if self.chain.read().is_known(&unverified.hash()) {
+            let chain = self.chain.read();
+            if chain.is_known(&unverified.hash()) {
                 return Err(BlockImportError::Import(ImportError::AlreadyInChain))
             }
+            let checkpoints = &self.importer.checkpoints;
+            checkpoints
+                .verify(unverified.header(), |number| chain.block_hash(number))
+                .map_err(BlockImportError::Block)?;
+            let seal_guaranteed = checkpoints.guarantees(unverified.header(), |number| chain.block_hash(number));
+            unverified.set_seal_guaranteed(seal_guaranteed);
+            self.importer.verify_reorg_depth(unverified.header(), &chain).map_err(BlockImportError::Block)?;
         }
         Ok(self.importer.block_queue.import(unverified)?)
     }
 
     fn import_header(&self, bytes: Bytes) -> Result<H256, BlockImportError> {
-        let unverified = ::encoded::Header::new(bytes).decode();
+        let unverified: Header = UntrustedRlp::new_checked(&bytes).and_then(|rlp| rlp.as_val()).map_err(Error::from)?;
         {
-            if self.chain.read().is_known_header(&unverified.hash()) {
+            let chain = self.chain.read();
+            if chain.is_known_header(&unverified.hash()) {
                 return Err(BlockImportError::Import(ImportError::AlreadyInChain))
             }
+            self.importer
+                .checkpoints
+                .verify(&unverified, |number| chain.block_hash(number))
+                .map_err(BlockImportError::Block)?;
+            self.importer.verify_reorg_depth(&unverified, &chain).map_err(BlockImportError::Block)?;
         }
         Ok(self.importer.header_queue.import(unverified)?)
     }
@@ -458,10 +651,30 @@ impl BlockChainClient for Client {
         self.importer.miner.ready_parcels()
     }
 
+    fn note_parcels_broadcast(&self, hashes: &[H256]) {
+        self.importer.miner.note_parcels_broadcast(hashes)
+    }
+
     fn block_number(&self, id: BlockId) -> Option<BlockNumber> {
         self.block_number_ref(&id)
     }
 
+    fn finalized_block_hash(&self) -> H256 {
+        self.chain.read().finalized_block_hash()
+    }
+
+    fn finality_proof(&self, hash: &H256) -> Option<Bytes> {
+        self.chain.read().finality_proof(hash)
+    }
+
+    fn earliest_body_number(&self) -> BlockNumber {
+        self.chain.read().earliest_body_number()
+    }
+
+    fn sync_progress(&self) -> Option<SyncProgress> {
+        self.sync_progress.read().clone()
+    }
+
     fn block_body(&self, id: BlockId) -> Option<encoded::Body> {
         let chain = self.chain.read();
 
@@ -493,6 +706,10 @@ impl BlockChainClient for Client {
         self.parcel_address(id).and_then(|address| chain.parcel(&address))
     }
 
+    fn sender_parcel_addresses(&self, sender: &Address, offset: usize, limit: usize) -> Vec<ParcelAddress> {
+        self.chain.read().canon_sender_parcel_addresses(sender, offset, limit)
+    }
+
     fn parcel_invoice(&self, id: ParcelId) -> Option<ParcelInvoice> {
         let chain = self.chain.read();
         self.parcel_address(id).and_then(|address| chain.parcel_invoice(&address))
@@ -516,9 +733,55 @@ impl BlockChainClient for Client {
         })
     }
 
+    fn logs(&self, filter: Filter) -> Vec<LocalizedLog> {
+        let (from_block, to_block) =
+            match (self.block_number_ref(&filter.from_block), self.block_number_ref(&filter.to_block)) {
+                (Some(from_block), Some(to_block)) => (from_block, to_block),
+                _ => return Vec::new(),
+            };
+
+        let chain = self.chain.read();
+        let numbers = if filter.topics.is_empty() {
+            let best_block_number = chain.best_block_detail().number;
+            (from_block..=cmp::min(to_block, best_block_number)).collect()
+        } else {
+            chain.blocks_with_bloom(&filter.bloom_possibilities(), from_block, to_block)
+        };
+
+        numbers
+            .into_iter()
+            .filter_map(|number| chain.block_hash(number).map(|hash| (number, hash)))
+            .filter_map(|(number, hash)| chain.block_body(&hash).map(|body| (number, hash, body)))
+            .flat_map(|(block_number, block_hash, body)| {
+                body.parcels()
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(parcel_index, parcel)| {
+                        let topics = parcel_topics(&parcel);
+                        if !filter.matches(&topics) {
+                            return None
+                        }
+                        Some(LocalizedLog {
+                            topics,
+                            block_number,
+                            block_hash,
+                            parcel_hash: parcel.hash(),
+                            parcel_index,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .take(filter.limit)
+            .collect()
+    }
+
     fn custom_handlers(&self) -> Vec<Arc<ActionHandler>> {
         self.state_db.read().custom_handlers().to_vec()
     }
+
+    fn cache_stats(&self) -> BlockChainCacheStats {
+        self.chain.read().cache_stats()
+    }
 }
 
 pub struct Importer {
@@ -539,12 +802,25 @@ pub struct Importer {
 
     /// CodeChain engine to be used during import
     pub engine: Arc<CodeChainEngine>,
+
+    /// Checkpoints of the chain scheme
+    pub checkpoints: Checkpoints,
+
+    /// Maximum number of the canon blocks which a new block can retract
+    pub max_reorg_depth: Option<u64>,
+
+    /// Fork points of the recently imported headers, so that the ancestors of a branch are walked once
+    fork_points: Mutex<LruCache<H256, ForkPoint>>,
+
+    /// Number of the recent blocks whose bodies are kept
+    pub body_history: Option<u64>,
 }
 
 impl Importer {
     pub fn new(
         config: &ClientConfig,
         engine: Arc<CodeChainEngine>,
+        checkpoints: Checkpoints,
         message_channel: IoChannel<ClientIoMessage>,
         miner: Arc<Miner>,
     ) -> Result<Importer, Error> {
@@ -569,9 +845,55 @@ impl Importer {
             header_queue,
             miner,
             engine,
+            checkpoints,
+            max_reorg_depth: config.max_reorg_depth,
+            fork_points: Mutex::new(LruCache::new(MAX_FORK_POINTS)),
+            body_history: config.body_history,
         })
     }
 
+    /// Rejects a block which forks from the canon chain below the finalized block, or deeper than `max_reorg_depth`
+    /// from the best header. The finalized block bounds the fork only if the engine never reverts it; otherwise
+    /// a heavier chain must be able to win after a partition of any length.
+    /// The fork point is found by following the ancestors of the block which are in the chain.
+    /// If they are not there yet, they are checked when they are imported.
+    fn verify_reorg_depth(&self, header: &Header, chain: &BlockChain) -> Result<(), BlockError> {
+        let finalized_number = if self.engine.is_finality_absolute() {
+            chain.block_number(&chain.finalized_block_hash()).unwrap_or(0)
+        } else {
+            0
+        };
+        let best_number = chain.best_header().number();
+        let lowest_fork_number = match self.max_reorg_depth {
+            Some(depth) => cmp::max(finalized_number, best_number.saturating_sub(depth)),
+            None => finalized_number,
+        };
+        if lowest_fork_number == 0 {
+            return Ok(())
+        }
+
+        let mut fork_points = self.fork_points.lock();
+        let fork_number = match find_fork_point(header, chain, lowest_fork_number, &fork_points) {
+            Ok(Some(fork)) => {
+                fork_points.insert(header.hash(), fork);
+                fork.number
+            }
+            Ok(None) => return Ok(()),
+            Err(branch_number) => branch_number - 1,
+        };
+        if fork_number < finalized_number {
+            return Err(BlockError::ForkBelowFinalized(finalized_number))
+        }
+        if fork_number < lowest_fork_number {
+            return Err(BlockError::ReorgTooDeep(OutOfBounds {
+                min: None,
+                max: self.max_reorg_depth,
+                found: best_number - fork_number,
+            }))
+        }
+        Ok(())
+    }
+
     /// This is triggered by a message coming from a block queue when the block is ready for insertion
     pub fn import_verified_blocks(&self, client: &Client) -> usize {
         let max_blocks_to_import = 4;
@@ -602,6 +924,7 @@ impl Importer {
                         imported_blocks.push(header.hash());
 
                         let route = self.commit_block(closed_block, &header, &block.bytes, client);
+                        client.flusher.request();
                         import_results.push(route);
                     }
                 } else {
@@ -644,7 +967,6 @@ impl Importer {
             }
         }
 
-        client.db.read().flush().expect("DB flush failed.");
         imported
     }
 
@@ -687,11 +1009,14 @@ impl Importer {
 
         // Commit results
         let invoices = block.invoices().to_owned();
+        let senders = block.senders().to_owned();
 
         assert_eq!(header.hash(), BlockView::new(block_data).header_view().hash());
 
         let mut batch = DBTransaction::new();
 
+        client.recent_state_roots.lock().insert(*hash, header.state_root());
+
         // CHECK! I *think* this is fine, even if the state_root is equal to another
         // already-imported block of the same number.
         // TODO: Prove it with a test.
@@ -701,19 +1026,85 @@ impl Importer {
         self.check_epoch_end_signal(&header, &chain, &mut batch);
 
         state.journal_under(&mut batch, number, hash).expect("DB commit failed");
-        let route = chain.insert_block(&mut batch, block_data, invoices.clone());
+        let route = chain.insert_block(&mut batch, block_data, invoices.clone(), &senders, &*self.engine);
 
         let is_canon = route.enacted.last().map_or(false, |h| h == hash);
+        client.accounts.write().record(*hash, state.modified_accounts());
         state.sync_cache(&route.enacted, &route.retracted, is_canon);
-        // Final commit to the DB
-        client.db.read().write_buffered(batch);
-        chain.commit();
-
-        self.check_epoch_end(&header, &chain, client);
+        {
+            // All the writes of the block are buffered under the lock, so that they reach the disk in one flush.
+            let _writes = client.flusher.lock_writes();
+            client.db.read().write_buffered(batch);
+            chain.commit();
+            client.update_best_block(&chain, &route.enacted, &route.retracted);
+
+            if is_canon {
+                self.check_finality(&header, &chain, client);
+                self.prune_bodies(&header, &chain, client);
+            }
+            self.check_epoch_end(&header, &chain, client);
+        }
 
         route
     }
 
+    // check for a newly finalized block and write it if the finality advances.
+    fn check_finality(&self, header: &Header, chain: &BlockChain, client: &Client) {
+        let (hash, proof) = match self.engine.finality_proof(header) {
+            Some(proof) => (header.hash(), Some(proof)),
+            None => {
+                // Blocks of an engine with instant finality are final only by their proofs.
+                let finality_depth = self.engine.finality_depth();
+                if finality_depth == 0 {
+                    return
+                }
+                let final_number = match header.number().checked_sub(finality_depth) {
+                    Some(number) => number,
+                    None => return,
+                };
+                match chain.block_hash(final_number) {
+                    Some(hash) => (hash, None),
+                    None => return,
+                }
+            }
+        };
+
+        let number = chain.block_number(&hash).expect("Finalized block is in the canonical chain");
+        let finalized_number =
+            chain.block_number(&chain.finalized_block_hash()).expect("Finalized block is in the canonical chain");
+        if number <= finalized_number {
+            return
+        }
+
+        ctrace!(CLIENT, "Block #{} ({}) is finalized", number, hash);
+        let mut batch = DBTransaction::new();
+        chain.insert_finalized_block(&mut batch, hash, proof);
+        // The finality is written synchronously with the blocks before it, so that the reported finality never goes
+        // backwards after a crash.
+        let db = client.db.read();
+        db.flush().expect("DB flush failed");
+        db.write(batch).expect("DB flush failed");
+    }
+
+    // prune the bodies older than the history, which are also final so that they are never retracted.
+    fn prune_bodies(&self, header: &Header, chain: &BlockChain, client: &Client) {
+        let history = match self.body_history {
+            Some(history) => history,
+            None => return,
+        };
+        let finalized_number =
+            chain.block_number(&chain.finalized_block_hash()).expect("Finalized block is in the canonical chain");
+        let number = cmp::min(header.number().saturating_sub(history), finalized_number);
+        if number <= chain.earliest_body_number() {
+            return
+        }
+
+        ctrace!(CLIENT, "Pruning the bodies below #{}", number);
+        let mut batch = DBTransaction::new();
+        chain.prune_bodies(&mut batch, number);
+        client.db.read().write_buffered(batch);
+    }
+
     // check for ending of epoch and write transition if it occurs.
     fn check_epoch_end(&self, header: &Header, chain: &BlockChain, client: &Client) {
         let is_epoch_end = self.engine.is_epoch_end(
@@ -738,8 +1129,11 @@ impl Importer {
 
             // always write the batch directly since epoch transition proofs are
             // fetched from a DB iterator and DB iterators are only available on
-            // flushed data.
-            client.db.read().write(batch).expect("DB flush failed");
+            // flushed data. The buffered writes of the block are flushed first so that
+            // the transition never reaches the disk without its block.
+            let db = client.db.read();
+            db.flush().expect("DB flush failed");
+            db.write(batch).expect("DB flush failed");
         }
     }
 
@@ -827,7 +1221,12 @@ impl Importer {
             return Err(())
         };
 
-        let verify_external_result = self.verifier.verify_block_external(header, engine);
+        // The seals of the canon ancestors of a checkpoint are guaranteed by the checkpoint.
+        let verify_external_result = if self.checkpoints.guarantees(header, |number| chain.block_hash(number)) {
+            Ok(())
+        } else {
+            self.verifier.verify_block_external(header, engine)
+        };
         if let Err(e) = verify_external_result {
             cwarn!(
                 CLIENT,
@@ -864,6 +1263,64 @@ impl Importer {
     }
 }
 
+/// Where a branch leaves the canon chain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ForkPoint {
+    /// The number of the last canon block of the branch
+    number: BlockNumber,
+    /// The hash of the last canon block of the branch
+    hash: H256,
+    /// The hash of the block of the branch next to the fork point
+    branch_hash: H256,
+}
+
+impl ForkPoint {
+    /// Whether the branch still leaves the canon chain at this point. The canon chain may have moved away from
+    /// the fork point, or onto the branch.
+    fn is_valid(&self, chain: &BlockChain) -> bool {
+        chain.block_hash(self.number) == Some(self.hash) && chain.block_hash(self.number + 1) != Some(self.branch_hash)
+    }
+}
+
+/// Finds the fork point of the branch which ends at the header, reusing the fork points of its ancestors.
+/// Returns `Ok(None)` if an ancestor is not in the chain yet, and `Err` with the number of a branch block if the
+/// branch goes down to `lowest_fork_number` without meeting the canon chain.
+fn find_fork_point(
+    header: &Header,
+    chain: &BlockChain,
+    lowest_fork_number: BlockNumber,
+    fork_points: &LruCache<H256, ForkPoint>,
+) -> Result<Option<ForkPoint>, BlockNumber> {
+    let mut number = header.number().saturating_sub(1);
+    let mut hash = *header.parent_hash();
+    let mut branch_hash = header.hash();
+    loop {
+        if chain.block_hash(number) == Some(hash) {
+            return Ok(Some(ForkPoint {
+                number,
+                hash,
+                branch_hash,
+            }))
+        }
+        if let Some(fork) = fork_points.get(&hash) {
+            if fork.is_valid(chain) {
+                return Ok(Some(*fork))
+            }
+        }
+        if number <= lowest_fork_number {
+            return Err(number)
+        }
+        match chain.block_header_data(&hash) {
+            Some(parent) => {
+                branch_hash = hash;
+                hash = parent.parent_hash();
+                number -= 1;
+            }
+            None => return Ok(None),
+        }
+    }
+}
+
 impl Importer {
     /// This is triggered by a message coming from a header queue when the header is ready for insertion
     pub fn import_verified_headers(&self, client: &Client) -> usize {
@@ -953,7 +1410,7 @@ impl Importer {
         let mut batch = DBTransaction::new();
         // FIXME: Check if this line is still necessary.
         // self.check_epoch_end_signal(header, &chain, &mut batch);
-        let route = chain.insert_header(&mut batch, &HeaderView::new(&header.rlp_bytes()));
+        let route = chain.insert_header(&mut batch, &HeaderView::new(&header.rlp_bytes()), &*self.engine);
         client.db.read().write_buffered(batch);
         chain.commit();
 
@@ -968,6 +1425,9 @@ impl AccountData for Client {}
 
 impl Nonce for Client {
     fn nonce(&self, address: &Address, id: BlockId) -> Option<U256> {
+        if id == BlockId::Latest {
+            return self.latest_account(address).map(|(nonce, _)| nonce)
+        }
         self.state_at(id).and_then(|s| s.nonce(address).ok())
     }
 }
@@ -976,6 +1436,7 @@ impl Balance for Client {
     fn balance(&self, address: &Address, state: StateOrBlock) -> Option<U256> {
         match state {
             StateOrBlock::State(s) => s.balance(address).ok(),
+            StateOrBlock::Block(BlockId::Latest) => self.latest_account(address).map(|(_, balance)| balance),
             StateOrBlock::Block(id) => self.state_at(id).and_then(|s| s.balance(address).ok()),
         }
     }
@@ -999,6 +1460,12 @@ impl RegularKeyOwner for Client {
     }
 }
 
+impl StateInfo for Client {
+    fn state_info(&self, id: BlockId) -> Option<Box<TopStateInfo>> {
+        self.state_at(id).map(|state| Box::new(state) as Box<TopStateInfo>)
+    }
+}
+
 impl Shard for Client {
     fn number_of_shards(&self, state: StateOrBlock) -> Option<ShardId> {
         let state = match state {
@@ -1058,6 +1525,9 @@ impl ImportSealedBlock for Client {
             let header = block.header().clone();
 
             let route = self.importer.commit_block(block, &header, &block_data, self);
+            // The own block must be on the disk before it's announced. Otherwise the node could seal another block
+            // of the same height after a crash.
+            self.flusher.flush().expect("DB flush failed");
             ctrace!(CLIENT, "Imported sealed block #{} ({})", number, h);
             self.state_db.write().sync_cache(&route.enacted, &route.retracted, false);
             route
@@ -1070,9 +1540,327 @@ impl ImportSealedBlock for Client {
                 elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64
             });
         });
-        self.db.read().flush().expect("DB flush failed.");
         Ok(h)
     }
 }
 
+impl RevertChain for Client {
+    fn revert_to(&self, hash: &H256) -> bool {
+        let reverted = {
+            let _import_lock = self.importer.import_lock.lock();
+
+            let chain = self.chain.read();
+            let mut batch = DBTransaction::new();
+            let reverted = match chain.revert_to(&mut batch, hash) {
+                Some(reverted) => reverted,
+                None => return false,
+            };
+            self.db.read().write_buffered(batch);
+            chain.commit();
+            self.update_best_block(&chain, &[], &reverted);
+            self.state_db.write().sync_cache(&[], &reverted, true);
+            reverted
+        };
+        cinfo!(CLIENT, "Reverted {} blocks to {}", reverted.len(), hash);
+        self.importer.miner.chain_new_blocks(self, &[], &[], &[], &[]);
+        self.flusher.flush().expect("DB flush failed.");
+        true
+    }
+}
+
 impl MiningBlockChainClient for Client {}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{Generator, KeyPair, Private, Random};
+    use ctypes::parcel::{Action, Parcel};
+    use ctypes::transaction::AssetMintOutput;
+    use kvdb_memorydb;
+    use primitives::{Bloom, BloomInput};
+    use rlp::RlpStream;
+
+    use super::super::super::db::{Readable, COL_EXTRA, NUM_COLUMNS};
+    use super::*;
+
+    struct TestChain {
+        scheme: Scheme,
+        db: Arc<KeyValueDB>,
+        chain: BlockChain,
+    }
+
+    impl TestChain {
+        fn new() -> Self {
+            let scheme = Scheme::new_test();
+            let db: Arc<KeyValueDB> = Arc::new(kvdb_memorydb::create(NUM_COLUMNS.unwrap_or(0)));
+            let chain = BlockChain::new(&scheme.genesis_block(), db.clone(), 1024 * 1024);
+            Self {
+                scheme,
+                db,
+                chain,
+            }
+        }
+
+        fn genesis(&self) -> Header {
+            self.scheme.genesis_header()
+        }
+
+        fn child(parent: &Header, score: u64) -> Header {
+            let mut header = Header::new();
+            header.set_parent_hash(parent.hash());
+            header.set_number(parent.number() + 1);
+            header.set_timestamp(parent.timestamp() + 1);
+            header.set_score(score.into());
+            header
+        }
+
+        fn import(&self, parent: &Header, score: u64) -> Header {
+            let header = Self::child(parent, score);
+            let mut batch = DBTransaction::new();
+            self.chain.insert_header(&mut batch, &HeaderView::new(&header.rlp_bytes()), &*self.scheme.engine);
+            self.db.write(batch).unwrap();
+            self.chain.commit();
+            header
+        }
+
+        fn import_chain(&self, parent: &Header, len: usize, score: u64) -> Vec<Header> {
+            let mut headers: Vec<Header> = Vec::with_capacity(len);
+            for _ in 0..len {
+                let header = self.import(headers.last().unwrap_or(parent), score);
+                headers.push(header);
+            }
+            headers
+        }
+    }
+
+    #[test]
+    fn fork_point_of_branch_is_found() {
+        let test = TestChain::new();
+        let canon = test.import_chain(&test.genesis(), 4, 10);
+        let branch = test.import_chain(&canon[1], 2, 1);
+
+        let header = TestChain::child(&branch[1], 1);
+        let expected = ForkPoint {
+            number: 2,
+            hash: canon[1].hash(),
+            branch_hash: branch[0].hash(),
+        };
+        assert_eq!(Ok(Some(expected)), find_fork_point(&header, &test.chain, 1, &LruCache::new(MAX_FORK_POINTS)));
+    }
+
+    #[test]
+    fn walk_stops_at_lowest_fork_number() {
+        let test = TestChain::new();
+        let canon = test.import_chain(&test.genesis(), 4, 10);
+        let branch = test.import_chain(&canon[1], 2, 1);
+
+        let header = TestChain::child(&branch[1], 1);
+        assert_eq!(Err(3), find_fork_point(&header, &test.chain, 3, &LruCache::new(MAX_FORK_POINTS)));
+    }
+
+    #[test]
+    fn fork_point_of_parent_is_reused() {
+        let test = TestChain::new();
+        let canon = test.import_chain(&test.genesis(), 4, 10);
+        let branch = test.import_chain(&canon[1], 2, 1);
+
+        let fork = ForkPoint {
+            number: 2,
+            hash: canon[1].hash(),
+            branch_hash: branch[0].hash(),
+        };
+        let mut fork_points = LruCache::new(MAX_FORK_POINTS);
+        fork_points.insert(branch[1].hash(), fork);
+
+        // The walk would stop at the branch block #3 otherwise.
+        let header = TestChain::child(&branch[1], 1);
+        assert_eq!(Ok(Some(fork)), find_fork_point(&header, &test.chain, 3, &fork_points));
+    }
+
+    #[test]
+    fn fork_point_is_found_again_after_reorg() {
+        let test = TestChain::new();
+        let canon = test.import_chain(&test.genesis(), 4, 10);
+        let branch = test.import_chain(&canon[1], 2, 1);
+
+        let mut fork_points = LruCache::new(MAX_FORK_POINTS);
+        fork_points.insert(
+            branch[1].hash(),
+            ForkPoint {
+                number: 2,
+                hash: canon[1].hash(),
+                branch_hash: branch[0].hash(),
+            },
+        );
+
+        // The canon chain moves to a heavier fork from #1.
+        test.import_chain(&canon[0], 5, 100);
+        assert_ne!(Some(canon[1].hash()), test.chain.block_hash(2));
+
+        let header = TestChain::child(&branch[1], 1);
+        let expected = ForkPoint {
+            number: 1,
+            hash: canon[0].hash(),
+            branch_hash: canon[1].hash(),
+        };
+        assert_eq!(Ok(Some(expected)), find_fork_point(&header, &test.chain, 1, &fork_points));
+    }
+
+    #[test]
+    fn bodies_are_pruned_gradually_and_the_progress_is_kept() {
+        let test = TestChain::new();
+        test.import_chain(&test.genesis(), 200, 10);
+        assert!(test.chain.block_body(&test.genesis().hash()).is_some());
+
+        let mut batch = DBTransaction::new();
+        test.chain.prune_bodies(&mut batch, 150);
+        test.db.write(batch).unwrap();
+        assert_eq!(128, test.chain.earliest_body_number());
+        assert_eq!(None, test.chain.block_body(&test.genesis().hash()));
+
+        let mut batch = DBTransaction::new();
+        test.chain.prune_bodies(&mut batch, 150);
+        test.db.write(batch).unwrap();
+        assert_eq!(150, test.chain.earliest_body_number());
+
+        let reopened = BlockChain::new(&test.scheme.genesis_block(), test.db.clone(), 1024 * 1024);
+        assert_eq!(150, reopened.earliest_body_number());
+    }
+
+    #[test]
+    fn reverted_blocks_leave_no_indices() {
+        let test = TestChain::new();
+        let genesis = test.genesis();
+        let transaction = Transaction::AssetMint {
+            network_id: "tc".into(),
+            shard_id: 0,
+            world_id: 0,
+            metadata: "Metadata".to_string(),
+            registrar: None,
+            nonce: 0,
+            output: AssetMintOutput {
+                lock_script_hash: H256::zero(),
+                parameters: vec![],
+                amount: None,
+            },
+        };
+        let parcel = SignedParcel::new_with_sign(
+            Parcel {
+                nonce: 0.into(),
+                fee: 10.into(),
+                network_id: "tc".into(),
+                action: Action::AssetTransactionGroup {
+                    transactions: vec![transaction.clone()],
+                    changes: vec![],
+                    signatures: vec![],
+                },
+            },
+            faucet().private(),
+        );
+        let bloom = Bloom::from(BloomInput::Raw(b"topic"));
+        let mut header = TestChain::child(&genesis, 10);
+        header.set_log_bloom(bloom);
+        let mut block = RlpStream::new_list(2);
+        block.append(&header);
+        block.append_list(&[parcel]);
+
+        let mut batch = DBTransaction::new();
+        let invoices = vec![ParcelInvoice::SingleSuccess];
+        test.chain.insert_block(&mut batch, &block.out(), invoices, &[faucet().address()], &*test.scheme.engine);
+        test.chain.insert_finalized_block(&mut batch, header.hash(), Some(vec![1]));
+        test.db.write(batch).unwrap();
+        test.chain.commit();
+        assert!(test.chain.transaction_addresses(&transaction.hash()).is_some());
+        assert!(test.chain.block_invoices(&header.hash()).is_some());
+        assert!(test.chain.finality_proof(&header.hash()).is_some());
+        assert_eq!(vec![1], test.chain.blocks_with_bloom(&[bloom], 0, 1));
+
+        let mut batch = DBTransaction::new();
+        assert_eq!(Some(vec![header.hash()]), test.chain.revert_to(&mut batch, &genesis.hash()));
+        test.db.write(batch).unwrap();
+        test.chain.commit();
+        assert_eq!(None, test.chain.transaction_addresses(&transaction.hash()));
+        assert!(test.chain.block_invoices(&header.hash()).is_none());
+        assert_eq!(None, test.chain.finality_proof(&header.hash()));
+        assert_eq!(genesis.hash(), test.chain.finalized_block_hash());
+        let reverted_bloom: Option<Bloom> = test.db.read(COL_EXTRA, &header.number());
+        assert_eq!(None, reverted_bloom);
+    }
+
+    fn new_client() -> Arc<Client> {
+        let scheme = Scheme::new_test();
+        let db: Arc<KeyValueDB> = Arc::new(kvdb_memorydb::create(NUM_COLUMNS.unwrap_or(0)));
+        let miner = Arc::new(Miner::with_scheme(&scheme));
+        Client::new(ClientConfig::default(), &scheme, db, miner, IoChannel::disconnected()).unwrap()
+    }
+
+    /// The account which has the balance in the genesis state of the test scheme.
+    fn faucet() -> KeyPair {
+        KeyPair::from_private(Private::from("ede1d4ccb4ec9a8bbbae9a13db3f4a7b56ea04189be86ac3a6a439d9a0a1addd")).unwrap()
+    }
+
+    fn payment(nonce: u64, fee: u64, receiver: Address) -> SignedParcel {
+        let parcel = Parcel {
+            nonce: nonce.into(),
+            fee: fee.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver,
+                amount: 100.into(),
+            },
+        };
+        SignedParcel::new_with_sign(parcel, faucet().private())
+    }
+
+    #[test]
+    fn queried_state_root_is_reused() {
+        let client = new_client();
+        let genesis = client.block_header(BlockId::Number(0)).unwrap();
+        let stats = || {
+            let stats = client.recent_state_roots.lock().stats();
+            (stats.hits, stats.misses)
+        };
+        let (hits, misses) = stats();
+
+        let state = client.state_at(BlockId::Number(0)).unwrap();
+        assert_eq!(genesis.state_root(), *state.root());
+        assert_eq!((hits, misses + 1), stats());
+
+        let state = client.state_at(BlockId::Hash(genesis.hash())).unwrap();
+        assert_eq!(genesis.state_root(), *state.root());
+        assert_eq!((hits + 1, misses + 1), stats(), "The root is not read from the header again");
+
+        let unknown = H256::random();
+        assert!(client.state_at(BlockId::Hash(unknown)).is_none());
+        assert_eq!(None, client.recent_state_roots.lock().peek(&unknown), "Unknown blocks are not cached");
+    }
+
+    #[test]
+    fn called_parcel_is_not_imported() {
+        let client = new_client();
+        let receiver = Random.generate().unwrap().address();
+        let parcel = payment(0, 10, receiver);
+
+        let (invoice, traces) = client.call_parcel(&parcel, BlockId::Latest).unwrap().unwrap();
+        assert_eq!(ParcelInvoice::SingleSuccess, invoice);
+        assert!(traces.iter().any(|trace| match trace {
+            Trace::Transfer {
+                receiver: to,
+                ..
+            } => *to == receiver,
+            _ => false,
+        }));
+
+        assert_eq!(U256::zero(), client.latest_balance(&receiver));
+        assert_eq!(U256::zero(), client.latest_nonce(&faucet().address()));
+        assert_eq!(Some((invoice, traces)), client.call_parcel(&parcel, BlockId::Latest).unwrap());
+    }
+
+    #[test]
+    fn called_parcel_is_verified() {
+        let client = new_client();
+        let receiver = Address::random();
+        assert!(client.call_parcel(&payment(1, 10, receiver), BlockId::Latest).is_err());
+        assert!(client.call_parcel(&payment(0, 9, receiver), BlockId::Latest).is_err());
+        assert!(client.call_parcel(&payment(0, 10, receiver), BlockId::Number(1)).unwrap().is_none());
+    }
+}
@@ -62,34 +62,81 @@ impl FromStr for DatabaseCompactionProfile {
     }
 }
 
+/// The key-value store of the client database.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DatabaseBackend {
+    /// RocksDB on the disk
+    RocksDB,
+    /// Kept in memory and lost when the node stops. It's fast and starts from the genesis every time, which suits
+    /// the tests and the development.
+    Memory,
+}
+
+impl Default for DatabaseBackend {
+    fn default() -> Self {
+        DatabaseBackend::RocksDB
+    }
+}
+
+impl FromStr for DatabaseBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rocksdb" => Ok(DatabaseBackend::RocksDB),
+            "memory" => Ok(DatabaseBackend::Memory),
+            _ => Err("Invalid database backend given. Expected rocksdb/memory.".into()),
+        }
+    }
+}
+
 /// Client configuration. Includes configs for all sub-systems.
 #[derive(Debug, PartialEq)]
 pub struct ClientConfig {
     /// Block queue configuration.
     pub queue: QueueConfig,
+    /// The key-value store of the database
+    pub db_backend: DatabaseBackend,
     /// RocksDB column cache-size if not default
     pub db_cache_size: Option<usize>,
     /// State db compaction profile
     pub db_compaction: DatabaseCompactionProfile,
     /// Should db have WAL enabled?
     pub db_wal: bool,
+    /// Open the existing db without writing to it.
+    pub db_read_only: bool,
+    /// Number of the imported blocks which can wait for a background flush. The flushes are synchronous if it's 0.
+    pub db_flush_queue_size: usize,
     /// State db cache-size.
     pub state_cache_size: usize,
+    /// Memory budget of the blockchain header, body, detail and address caches.
+    pub blockchain_cache_size: usize,
     /// Type of block verifier used by client.
     pub verifier_type: VerifierType,
+    /// Maximum number of the canon blocks which a new block can retract. Unlimited if it's None.
+    pub max_reorg_depth: Option<u64>,
+    /// Number of the recent blocks whose bodies are kept. Every body is kept if it's None.
+    pub body_history: Option<u64>,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
         let mb = 1024 * 1024;
         const DEFAULT_STATE_CACHE_SIZE: u32 = 25;
+        const DEFAULT_BLOCKCHAIN_CACHE_SIZE: u32 = 16;
         Self {
             queue: Default::default(),
+            db_backend: Default::default(),
             db_cache_size: Default::default(),
             db_compaction: Default::default(),
             db_wal: true,
+            db_read_only: false,
+            db_flush_queue_size: 16,
             state_cache_size: DEFAULT_STATE_CACHE_SIZE as usize * mb,
+            blockchain_cache_size: DEFAULT_BLOCKCHAIN_CACHE_SIZE as usize * mb,
             verifier_type: Default::default(),
+            max_reorg_depth: None,
+            body_history: None,
         }
     }
 }
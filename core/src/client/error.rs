@@ -26,6 +26,11 @@ pub enum Error {
     Database(kvdb::Error),
     /// Util error
     Util(UtilError),
+    /// The database schema is not the version this binary writes
+    SchemaVersion {
+        found: u32,
+        expected: u32,
+    },
 }
 
 impl From<UtilError> for Error {
@@ -48,6 +53,18 @@ impl Display for Error {
         match self {
             Error::Util(err) => write!(f, "{}", err),
             Error::Database(s) => write!(f, "Database error: {}", s),
+            Error::SchemaVersion {
+                found,
+                expected,
+            } if found < expected => write!(
+                f,
+                "The database schema version {} is older than {}. Run `codechain db migrate` to upgrade it",
+                found, expected
+            ),
+            Error::SchemaVersion {
+                found,
+                expected,
+            } => write!(f, "The database schema version {} is newer than {}. Upgrade CodeChain", found, expected),
         }
     }
 }
@@ -0,0 +1,153 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use kvdb::{self, KeyValueDB};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+
+/// Flushes the buffered writes of the database on a background thread, so that the import doesn't wait for the disk.
+///
+/// Each imported block requests a flush. A flush writes all the buffered blocks at once, and the import waits only if
+/// `queue_size` blocks are still waiting for a flush. The blocks which were not flushed are lost if the node crashes,
+/// and they are synchronized again. So only the blocks from the peers are flushed here; the blocks sealed by this node
+/// and the finality are flushed before they are announced.
+pub struct Flusher {
+    db: Arc<KeyValueDB>,
+    /// A flush takes the write lock, so that it never writes a part of the writes of a block.
+    writes: Arc<RwLock<()>>,
+    sender: Mutex<Option<SyncSender<()>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Flusher {
+    /// The flushes are synchronous if `queue_size` is 0.
+    pub fn new(db: Arc<KeyValueDB>, queue_size: usize) -> Self {
+        let writes = Arc::new(RwLock::new(()));
+        let (sender, thread) = if queue_size == 0 {
+            (None, None)
+        } else {
+            let (sender, receiver) = sync_channel(queue_size);
+            let thread = {
+                let db = Arc::clone(&db);
+                let writes = Arc::clone(&writes);
+                thread::Builder::new()
+                    .name("db_flusher".to_string())
+                    .spawn(move || run(&*db, &writes, &receiver))
+                    .expect("Cannot spawn the database flusher")
+            };
+            (Some(sender), Some(thread))
+        };
+        Self {
+            db,
+            writes,
+            sender: Mutex::new(sender),
+            thread: Mutex::new(thread),
+        }
+    }
+
+    /// Must be held while the writes of a block are buffered.
+    pub fn lock_writes(&self) -> RwLockReadGuard<()> {
+        self.writes.read()
+    }
+
+    /// Requests a flush of the buffered writes. Blocks if the queue is full.
+    pub fn request(&self) {
+        let sender = self.sender.lock().clone();
+        let sender = match sender {
+            Some(sender) => sender,
+            None => return self.flush().expect("DB flush failed."),
+        };
+        if let Err(TrySendError::Full(())) = sender.try_send(()) {
+            cdebug!(CLIENT, "The database flush queue is full");
+            if sender.send(()).is_err() {
+                self.flush().expect("DB flush failed.");
+            }
+        }
+    }
+
+    /// Flushes the buffered writes on the calling thread.
+    pub fn flush(&self) -> kvdb::Result<()> {
+        let _writes = self.writes.write();
+        self.db.flush()
+    }
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        // The thread flushes the remaining writes and exits when the sender is dropped.
+        self.sender.lock().take();
+        if let Some(thread) = self.thread.lock().take() {
+            if thread.join().is_err() {
+                cerror!(CLIENT, "The database flusher panicked");
+            }
+        }
+    }
+}
+
+fn run(db: &KeyValueDB, writes: &RwLock<()>, receiver: &Receiver<()>) {
+    while receiver.recv().is_ok() {
+        // The requests which arrived during the last flush are covered by this one.
+        while receiver.try_recv().is_ok() {}
+        let _writes = writes.write();
+        if let Err(err) = db.flush() {
+            cerror!(CLIENT, "Cannot flush the database: {}", err);
+        }
+    }
+    let _writes = writes.write();
+    if let Err(err) = db.flush() {
+        cerror!(CLIENT, "Cannot flush the database: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kvdb::DBTransaction;
+    use kvdb_memorydb;
+
+    use super::*;
+
+    #[test]
+    fn buffered_writes_are_flushed_on_drop() {
+        let db = Arc::new(kvdb_memorydb::create(0));
+        {
+            let flusher = Flusher::new(Arc::clone(&db) as Arc<KeyValueDB>, 2);
+            for i in 0..10u8 {
+                let mut batch = DBTransaction::new();
+                batch.put(None, &[i], &[i]);
+                let _writes = flusher.lock_writes();
+                db.write_buffered(batch);
+                flusher.request();
+            }
+        }
+        for i in 0..10u8 {
+            assert_eq!(Some(vec![i]), db.get(None, &[i]).unwrap().map(|value| value.to_vec()));
+        }
+    }
+
+    #[test]
+    fn synchronous_without_queue() {
+        let db = Arc::new(kvdb_memorydb::create(0));
+        let flusher = Flusher::new(Arc::clone(&db) as Arc<KeyValueDB>, 0);
+        let mut batch = DBTransaction::new();
+        batch.put(None, b"key", b"value");
+        db.write_buffered(batch);
+        flusher.request();
+        assert_eq!(Some(b"value".to_vec()), db.get(None, b"key").unwrap().map(|value| value.to_vec()));
+    }
+}
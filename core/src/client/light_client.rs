@@ -0,0 +1,520 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use ckey::Address;
+use cnetwork::ChainHead;
+use cstate::{Account, TopStateInfo};
+use ctypes::BlockNumber;
+use kvdb::{DBTransaction, KeyValueDB};
+use linked_hash_map::LinkedHashMap;
+use parking_lot::{Mutex, RwLock};
+use primitives::{Bytes, H256, U256};
+use rlp::{RlpStream, UntrustedRlp};
+use util_error::UtilError;
+
+use super::super::blockchain_info::BlockChainInfo;
+use super::super::consensus::epoch::Transition as EpochTransition;
+use super::super::consensus::CodeChainEngine;
+use super::super::db::{COL_EXTRA, COL_HEADERS};
+use super::super::encoded;
+use super::super::error::{BlockError, BlockImportError, Error, ImportError};
+use super::super::header::Header;
+use super::super::proof::verify_account_proof;
+use super::super::scheme::Scheme;
+use super::super::types::BlockId;
+use super::super::verification::{verify_header_family, verify_header_params};
+use super::{BlockInfo, ChainInfo, EngineClient, Error as ClientError, ImportBlock, StateInfo};
+
+/// The number of the accounts read through the proofs, which are kept for the queries.
+const MAX_ACCOUNTS: usize = 4096;
+/// The number of the recent canonical headers which are kept.
+/// The older ones are dropped with the forks which branched off before them.
+const MAX_HEADERS: usize = 4096;
+/// The key of the best header hash in `COL_EXTRA`.
+const BEST_HEADER_KEY: &[u8] = b"light-best";
+
+/// A client which keeps only the recent headers of the chain, verifying each of them with the engine.
+/// It doesn't have the state, so it reads the accounts through the proofs made by the full nodes.
+pub struct LightClient {
+    engine: Arc<CodeChainEngine>,
+    genesis: Header,
+    db: Arc<KeyValueDB>,
+    chain: RwLock<HeaderChain>,
+    /// The accounts read through the verified proofs, by the blocks and the addresses.
+    accounts: Mutex<LinkedHashMap<(H256, Address), Option<Account>>>,
+}
+
+struct HeaderChain {
+    /// The headers with the total scores of the chains they end.
+    headers: HashMap<H256, (Header, U256)>,
+    /// The hashes of the recent canonical headers. The first of them is at `first_number`.
+    canon: VecDeque<H256>,
+    first_number: BlockNumber,
+}
+
+impl HeaderChain {
+    /// Makes the chain which ends with `best_hash` canonical, and drops the headers which don't descend from it.
+    /// Returns the hashes of the dropped headers.
+    fn new(headers: HashMap<H256, (Header, U256)>, best_hash: H256) -> (Self, Vec<H256>) {
+        let mut canon = VecDeque::new();
+        let mut hash = best_hash;
+        loop {
+            canon.push_front(hash);
+            let parent = *headers[&hash].0.parent_hash();
+            if canon.len() == MAX_HEADERS || !headers.contains_key(&parent) {
+                break
+            }
+            hash = parent;
+        }
+        let first_number = headers[&canon[0]].0.number();
+        let mut chain = Self {
+            headers,
+            canon,
+            first_number,
+        };
+        let dropped = chain.retain_descendants();
+        (chain, dropped)
+    }
+
+    fn best(&self) -> &(Header, U256) {
+        let best_hash = self.canon.back().expect("The canon chain always has the best header");
+        &self.headers[best_hash]
+    }
+
+    fn canon_hash(&self, number: BlockNumber) -> Option<H256> {
+        let index = number.checked_sub(self.first_number)?;
+        self.canon.get(index as usize).cloned()
+    }
+
+    /// Makes the chain which ends with the header canonical.
+    /// Returns the hashes of the headers dropped because they are too old.
+    fn set_best(&mut self, hash: H256) -> Vec<H256> {
+        let mut index = (self.headers[&hash].0.number() - self.first_number) as usize;
+        self.canon.resize(index + 1, H256::zero());
+        let mut hash = hash;
+        // The first canonical header is the ancestor of all the headers, so the walk stops there at the latest.
+        while self.canon[index] != hash {
+            self.canon[index] = hash;
+            hash = *self.headers[&hash].0.parent_hash();
+            index -= 1;
+        }
+        self.prune()
+    }
+
+    /// Drops the old canonical headers once there are twice as many as `MAX_HEADERS`,
+    /// so that the cost of dropping is shared by the imports.
+    fn prune(&mut self) -> Vec<H256> {
+        if self.canon.len() <= 2 * MAX_HEADERS {
+            return Vec::new()
+        }
+        let count = self.canon.len() - MAX_HEADERS;
+        self.canon.drain(..count);
+        self.first_number += count as BlockNumber;
+        self.retain_descendants()
+    }
+
+    /// Drops the headers which don't descend from the first canonical header.
+    fn retain_descendants(&mut self) -> Vec<H256> {
+        let mut kept: HashSet<H256> = self.canon.iter().cloned().collect();
+        let mut forks: Vec<_> = self
+            .headers
+            .values()
+            .filter(|(header, _)| header.number() > self.first_number && !kept.contains(&header.hash()))
+            .map(|(header, _)| (header.number(), header.hash(), *header.parent_hash()))
+            .collect();
+        forks.sort();
+        for (_, hash, parent) in forks {
+            if kept.contains(&parent) {
+                kept.insert(hash);
+            }
+        }
+        let dropped: Vec<_> = self.headers.keys().filter(|hash| !kept.contains(hash)).cloned().collect();
+        for hash in &dropped {
+            self.headers.remove(hash);
+        }
+        dropped
+    }
+}
+
+fn encode_entry(header: &Header, total_score: &U256) -> Bytes {
+    let mut s = RlpStream::new_list(2);
+    s.append(header);
+    s.append(total_score);
+    s.out()
+}
+
+impl LightClient {
+    /// Opens the headers kept in the database, and registers the client to the engine.
+    /// The engines which read the validators from the state are not supported, since there is no state to read.
+    pub fn new(scheme: &Scheme, db: Arc<KeyValueDB>) -> Result<Arc<Self>, Error> {
+        if scheme.engine.verifies_seal_with_state() {
+            return Err(UtilError::from(format!(
+                "The light client cannot verify the seals of {}, which reads the validators from the state",
+                scheme.engine.name()
+            )).into())
+        }
+
+        let genesis = scheme.genesis_header();
+        let genesis_hash = genesis.hash();
+        let (chain, dropped) = match db.get(COL_EXTRA, BEST_HEADER_KEY).map_err(ClientError::Database)? {
+            Some(best_hash) => {
+                if db.get(COL_HEADERS, &genesis_hash).map_err(ClientError::Database)?.is_none() {
+                    return Err(UtilError::from("The light client database belongs to another chain".to_string()).into())
+                }
+                let mut headers = HashMap::new();
+                for (key, value) in db.iter(COL_HEADERS) {
+                    let rlp = UntrustedRlp::new(&value);
+                    let header: Header = rlp.val_at(0)?;
+                    let total_score: U256 = rlp.val_at(1)?;
+                    headers.insert(H256::from_slice(&key), (header, total_score));
+                }
+                HeaderChain::new(headers, H256::from_slice(&best_hash))
+            }
+            None => {
+                let score = *genesis.score();
+                let mut batch = DBTransaction::new();
+                batch.put(COL_HEADERS, &genesis_hash, &encode_entry(&genesis, &score));
+                batch.put(COL_EXTRA, BEST_HEADER_KEY, &genesis_hash);
+                db.write(batch).map_err(ClientError::Database)?;
+
+                let mut headers = HashMap::new();
+                headers.insert(genesis_hash, (genesis.clone(), score));
+                HeaderChain::new(headers, genesis_hash)
+            }
+        };
+
+        let client = Arc::new(Self {
+            engine: Arc::clone(&scheme.engine),
+            genesis,
+            db,
+            chain: RwLock::new(chain),
+            accounts: Mutex::new(LinkedHashMap::new()),
+        });
+        client.forget(dropped)?;
+        scheme.engine.register_client(Arc::downgrade(&client) as _);
+        Ok(client)
+    }
+
+    /// Removes the dropped headers from the database. The genesis header is kept to identify the chain.
+    fn forget(&self, dropped: Vec<H256>) -> Result<(), Error> {
+        if dropped.is_empty() {
+            return Ok(())
+        }
+        let genesis_hash = self.genesis.hash();
+        let mut batch = DBTransaction::new();
+        for hash in dropped.iter().filter(|hash| **hash != genesis_hash) {
+            batch.delete(COL_HEADERS, hash);
+        }
+        self.db.write(batch).map_err(ClientError::Database)?;
+        Ok(())
+    }
+
+    pub fn genesis_hash(&self) -> H256 {
+        self.genesis.hash()
+    }
+
+    /// The best header and the total score of its chain.
+    pub fn best_header(&self) -> (Header, U256) {
+        self.chain.read().best().clone()
+    }
+
+    /// Returns the header if it's kept. Only the recent canonical headers and the genesis header are kept.
+    pub fn block_header(&self, id: BlockId) -> Option<Header> {
+        let chain = self.chain.read();
+        let hash = match id {
+            BlockId::Hash(hash) => hash,
+            BlockId::Number(0) | BlockId::Earliest => return Some(self.genesis.clone()),
+            BlockId::Number(number) => chain.canon_hash(number)?,
+            BlockId::Latest => *chain.canon.back()?,
+        };
+        if hash == self.genesis.hash() {
+            return Some(self.genesis.clone())
+        }
+        chain.headers.get(&hash).map(|(header, _)| header.clone())
+    }
+
+    /// Verifies the header with the engine and adds it to the chain.
+    /// The parent of the header must be imported first.
+    pub fn import_header(&self, bytes: &[u8]) -> Result<H256, BlockImportError> {
+        let header: Header = UntrustedRlp::new(bytes).as_val().map_err(Error::from)?;
+        let hash = header.hash();
+        let (parent, parent_total_score) = {
+            let chain = self.chain.read();
+            if chain.headers.contains_key(&hash) {
+                return Err(BlockImportError::Import(ImportError::AlreadyInChain))
+            }
+            chain
+                .headers
+                .get(header.parent_hash())
+                .cloned()
+                .ok_or_else(|| Error::from(BlockError::UnknownParent(*header.parent_hash())))?
+        };
+        // The engine may read the chain through `EngineClient`, so the lock is not held here.
+        verify_header_params(&header, &*self.engine)?;
+        self.engine.verify_block_basic(&header)?;
+        self.engine.verify_block_unordered(&header)?;
+        verify_header_family(&header, &parent, &*self.engine)?;
+        self.engine.verify_block_external(&header)?;
+
+        let total_score = parent_total_score + *header.score();
+        let mut chain = self.chain.write();
+        if !chain.headers.contains_key(header.parent_hash()) {
+            // The parent is dropped while the header is verified.
+            return Err(Error::from(BlockError::UnknownParent(*header.parent_hash())).into())
+        }
+        let mut batch = DBTransaction::new();
+        batch.put(COL_HEADERS, &hash, &encode_entry(&header, &total_score));
+        let is_new_best = total_score > chain.best().1;
+        chain.headers.insert(hash, (header, total_score));
+        let dropped = if is_new_best {
+            batch.put(COL_EXTRA, BEST_HEADER_KEY, &hash);
+            chain.set_best(hash)
+        } else {
+            Vec::new()
+        };
+        self.db.write(batch).map_err(|err| Error::from(ClientError::Database(err)))?;
+        self.forget(dropped)?;
+        Ok(hash)
+    }
+
+    /// Verifies the proof of the account against the state root of the block, which must be kept.
+    pub fn verify_account(
+        &self,
+        block_hash: &H256,
+        address: &Address,
+        proof: &[Bytes],
+    ) -> Result<Option<Account>, Error> {
+        let header = self
+            .block_header(BlockId::Hash(*block_hash))
+            .ok_or_else(|| UtilError::from(format!("Unknown block {}", block_hash)))?;
+        verify_account_proof(&header, address, proof)
+    }
+
+    /// Verifies the proof of the account and keeps the account for `account`.
+    pub fn import_account_proof(&self, block_hash: &H256, address: &Address, proof: &[Bytes]) -> Result<(), Error> {
+        let account = self.verify_account(block_hash, address, proof)?;
+        let mut accounts = self.accounts.lock();
+        accounts.insert((*block_hash, *address), account);
+        if accounts.len() > MAX_ACCOUNTS {
+            accounts.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Returns the account read through a proof, or `None` if no proof of it is imported.
+    /// The inner `None` means that the proof shows that there is no such account.
+    pub fn account(&self, block_hash: &H256, address: &Address) -> Option<Option<Account>> {
+        self.accounts.lock().get(&(*block_hash, *address)).cloned()
+    }
+}
+
+impl ChainInfo for LightClient {
+    fn chain_info(&self) -> BlockChainInfo {
+        let (best_header, total_score) = self.best_header();
+        BlockChainInfo {
+            total_score,
+            pending_total_score: total_score,
+            genesis_hash: self.genesis.hash(),
+            best_block_hash: best_header.hash(),
+            best_block_number: best_header.number(),
+            best_block_timestamp: best_header.timestamp(),
+        }
+    }
+
+    fn best_block_number_and_hash(&self) -> (BlockNumber, H256) {
+        let chain = self.chain.read();
+        let (best_header, _) = chain.best();
+        (best_header.number(), best_header.hash())
+    }
+}
+
+impl ChainHead for LightClient {
+    fn best_block_number(&self) -> BlockNumber {
+        self.best_block_number_and_hash().0
+    }
+}
+
+impl BlockInfo for LightClient {
+    fn block_header(&self, id: BlockId) -> Option<encoded::Header> {
+        LightClient::block_header(self, id).map(|header| encoded::Header::new(::rlp::encode(&header).into_vec()))
+    }
+
+    fn best_block_header(&self) -> encoded::Header {
+        BlockInfo::best_header(self)
+    }
+
+    fn best_header(&self) -> encoded::Header {
+        encoded::Header::new(::rlp::encode(&LightClient::best_header(self).0).into_vec())
+    }
+
+    /// The light client has no bodies.
+    fn block(&self, _id: BlockId) -> Option<encoded::Block> {
+        None
+    }
+}
+
+impl ImportBlock for LightClient {
+    fn import_block(&self, _bytes: Bytes) -> Result<H256, BlockImportError> {
+        Err(BlockImportError::Other("The light client imports only the headers".to_string()))
+    }
+
+    fn import_header(&self, bytes: Bytes) -> Result<H256, BlockImportError> {
+        LightClient::import_header(self, &bytes)
+    }
+}
+
+impl StateInfo for LightClient {
+    /// The light client has no state.
+    fn state_info(&self, _id: BlockId) -> Option<Box<TopStateInfo>> {
+        None
+    }
+}
+
+impl EngineClient for LightClient {
+    /// The light client doesn't seal.
+    fn update_sealing(&self) {}
+
+    /// The light client doesn't seal.
+    fn submit_seal(&self, _block_hash: H256, _seal: Vec<Bytes>) {}
+
+    fn score_to_target(&self, score: &U256) -> U256 {
+        self.engine.score_to_target(score)
+    }
+
+    /// The light client doesn't keep the epoch transitions.
+    fn epoch_transition_for(&self, _parent_hash: H256) -> Option<Option<EpochTransition>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{sign, Generator, Random};
+    use kvdb_memorydb;
+
+    use super::super::super::db::NUM_COLUMNS;
+    use super::*;
+
+    fn new_db() -> Arc<KeyValueDB> {
+        Arc::new(kvdb_memorydb::create(NUM_COLUMNS.unwrap_or(0)))
+    }
+
+    fn child_of(parent: &Header, score: u64) -> Header {
+        let mut header = Header::default();
+        header.set_parent_hash(parent.hash());
+        header.set_number(parent.number() + 1);
+        header.set_timestamp(parent.timestamp() + 1);
+        header.set_score(score.into());
+        header
+    }
+
+    #[test]
+    fn import_headers_and_follow_the_heaviest_chain() {
+        let scheme = Scheme::new_test();
+        let client = LightClient::new(&scheme, new_db()).unwrap();
+        let genesis = client.block_header(BlockId::Earliest).unwrap();
+
+        let light = child_of(&genesis, 1);
+        let light_child = child_of(&light, 1);
+        client.import_header(&::rlp::encode(&light)).unwrap();
+        client.import_header(&::rlp::encode(&light_child)).unwrap();
+        assert_eq!(light_child.hash(), client.best_header().0.hash());
+
+        let heavy = child_of(&genesis, 10);
+        client.import_header(&::rlp::encode(&heavy)).unwrap();
+        assert_eq!(heavy.hash(), client.best_header().0.hash());
+        assert_eq!(Some(heavy.hash()), client.block_header(BlockId::Number(1)).map(|header| header.hash()));
+        assert!(client.block_header(BlockId::Number(2)).is_none());
+        assert!(client.block_header(BlockId::Hash(light_child.hash())).is_some());
+    }
+
+    #[test]
+    fn header_without_parent_is_rejected() {
+        let scheme = Scheme::new_test();
+        let client = LightClient::new(&scheme, new_db()).unwrap();
+        let genesis = client.block_header(BlockId::Earliest).unwrap();
+        let orphan = child_of(&child_of(&genesis, 1), 1);
+        assert!(client.import_header(&::rlp::encode(&orphan)).is_err());
+        assert_eq!(genesis.hash(), client.best_header().0.hash());
+    }
+
+    #[test]
+    fn header_sealed_by_non_validator_is_rejected() {
+        let scheme = Scheme::new_test_simple_poa();
+        let client = LightClient::new(&scheme, new_db()).unwrap();
+        let genesis = client.block_header(BlockId::Earliest).unwrap();
+
+        let stranger = Random.generate().unwrap();
+        let mut header = child_of(&genesis, 1);
+        header.set_author(stranger.address());
+        let signature = sign(stranger.private(), &header.bare_hash()).unwrap();
+        header.set_seal(vec![::rlp::encode(&signature).into_vec()]);
+        match client.import_header(&::rlp::encode(&header)) {
+            Err(BlockImportError::Block(BlockError::InvalidSeal)) => {}
+            result => panic!("The header must be rejected by the external verification: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn engine_reading_validators_from_state_is_not_supported() {
+        let scheme = Scheme::new_test_tendermint();
+        assert!(LightClient::new(&scheme, new_db()).is_err());
+    }
+
+    #[test]
+    fn headers_are_restored_from_the_database() {
+        let scheme = Scheme::new_test();
+        let db = new_db();
+        let best = {
+            let client = LightClient::new(&scheme, Arc::clone(&db)).unwrap();
+            let genesis = client.block_header(BlockId::Earliest).unwrap();
+            let header = child_of(&child_of(&genesis, 1), 1);
+            client.import_header(&::rlp::encode(&child_of(&genesis, 1))).unwrap();
+            client.import_header(&::rlp::encode(&header)).unwrap();
+            header
+        };
+
+        let client = LightClient::new(&scheme, db).unwrap();
+        assert_eq!(best.hash(), client.best_header().0.hash());
+        assert_eq!(Some(best.hash()), client.block_header(BlockId::Number(2)).map(|header| header.hash()));
+    }
+
+    #[test]
+    fn old_headers_and_forks_are_pruned() {
+        let scheme = Scheme::new_test();
+        let db = new_db();
+        let client = LightClient::new(&scheme, Arc::clone(&db)).unwrap();
+        let genesis = client.block_header(BlockId::Earliest).unwrap();
+
+        let fork = child_of(&genesis, 1);
+        client.import_header(&::rlp::encode(&fork)).unwrap();
+        let mut best = genesis.clone();
+        for _ in 0..2 * MAX_HEADERS {
+            best = child_of(&best, 2);
+            client.import_header(&::rlp::encode(&best)).unwrap();
+        }
+
+        assert_eq!(best.hash(), client.best_header().0.hash());
+        assert!(client.block_header(BlockId::Number(1)).is_none());
+        assert!(client.block_header(BlockId::Hash(fork.hash())).is_none());
+        assert!(db.get(COL_HEADERS, &fork.hash()).unwrap().is_none());
+        assert_eq!(Some(genesis.hash()), client.block_header(BlockId::Earliest).map(|header| header.hash()));
+        assert_eq!(MAX_HEADERS, client.chain.read().headers.len());
+    }
+}
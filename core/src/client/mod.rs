@@ -14,17 +14,24 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod account_cache;
+mod best_block;
 mod chain_notify;
+mod chain_stats;
 mod client;
 mod config;
 mod error;
+mod flusher;
+mod light_client;
 mod test_client;
 
 pub use self::chain_notify::ChainNotify;
+pub use self::chain_stats::{ChainStatistics, ChainStats, DEFAULT_CHAIN_STATS_WINDOW};
 
 pub use self::client::Client;
-pub use self::config::ClientConfig;
+pub use self::config::{ClientConfig, DatabaseBackend};
 pub use self::error::Error;
+pub use self::light_client::LightClient;
 pub use self::test_client::TestBlockChainClient;
 
 use std::sync::Arc;
@@ -32,27 +39,34 @@ use std::sync::Arc;
 use ckey::{Address, Public};
 use cmerkle::Result as TrieResult;
 use cnetwork::NodeId;
-use cstate::{ActionHandler, AssetScheme, AssetSchemeAddress, OwnedAsset, TopStateInfo};
+use cstate::{ActionHandler, AssetScheme, AssetSchemeAddress, OwnedAsset, TopStateInfo, Trace};
 use ctypes::invoice::{ParcelInvoice, TransactionInvoice};
 use ctypes::parcel::ShardChange;
 use ctypes::transaction::Transaction;
 use ctypes::{BlockNumber, ShardId};
-use kvdb::KeyValueDB;
+use kvdb::{self, KeyValueDB};
 use primitives::{Bytes, H256, U256};
 
 use super::block::{ClosedBlock, OpenBlock, SealedBlock};
-use super::blockchain::ParcelAddress;
+use super::blockchain::{BlockChainCacheStats, ParcelAddress};
 use super::blockchain_info::BlockChainInfo;
+use super::consensus::epoch::Transition as EpochTransition;
 use super::encoded;
 use super::error::{BlockImportError, Error as CoreError};
 use super::parcel::{LocalizedParcel, SignedParcel};
 use super::scheme::CommonParams;
-use super::types::{BlockId, BlockStatus, ParcelId, TransactionId, VerificationQueueInfo as BlockQueueInfo};
+use super::types::{
+    BlockId, BlockStatus, Filter, LocalizedLog, ParcelId, SyncProgress, TransactionId,
+    VerificationQueueInfo as BlockQueueInfo,
+};
 
 /// Provides `chain_info` method
 pub trait ChainInfo {
     /// Get blockchain information.
     fn chain_info(&self) -> BlockChainInfo;
+
+    /// Get the number and the hash of the best block. Unlike `chain_info`, it doesn't take the lock of the chain.
+    fn best_block_number_and_hash(&self) -> (BlockNumber, H256);
 }
 
 /// Provides various information on a block by it's ID
@@ -91,10 +105,14 @@ pub trait TransactionInfo {
 
 pub trait EngineInfo: Send + Sync {
     fn common_params(&self) -> &CommonParams;
+
+    /// Returns the validators which seal the child of the given block.
+    /// `None` if the block is unknown or the engine has no validators.
+    fn validators(&self, id: BlockId) -> Option<Vec<Address>>;
 }
 
 /// Client facilities used by internally sealing Engines.
-pub trait EngineClient: Sync + Send + ChainInfo + ImportBlock {
+pub trait EngineClient: Sync + Send + ChainInfo + ImportBlock + BlockInfo + StateInfo {
     /// Make a new block and seal it.
     fn update_sealing(&self);
 
@@ -103,6 +121,10 @@ pub trait EngineClient: Sync + Send + ChainInfo + ImportBlock {
 
     /// Convert PoW difficulty to target.
     fn score_to_target(&self, score: &U256) -> U256;
+
+    /// The latest epoch transition at or before the block, which begins the epoch of its children.
+    /// `Some(None)` if the block is in the first epoch. `None` if the block is unknown or the transitions are not kept.
+    fn epoch_transition_for(&self, parent_hash: H256) -> Option<Option<EpochTransition>>;
 }
 
 /// Provides `nonce` and `latest_nonce` methods
@@ -172,6 +194,12 @@ pub trait Shard {
     fn shard_root(&self, shard_id: ShardId, state: StateOrBlock) -> Option<H256>;
 }
 
+/// Provides a state handle to read several values at one block.
+pub trait StateInfo {
+    /// Returns None if the block is unknown or its state has been pruned.
+    fn state_info(&self, id: BlockId) -> Option<Box<TopStateInfo>>;
+}
+
 /// Provides methods to access account info
 pub trait AccountData: Nonce + Balance {}
 
@@ -198,6 +226,9 @@ pub trait BlockChainClient: Sync + Send + AccountData + BlockChain + ImportBlock
     /// List all parcels that are allowed into the next block.
     fn ready_parcels(&self) -> Vec<SignedParcel>;
 
+    /// Records that the parcels are propagated to peers.
+    fn note_parcels_broadcast(&self, hashes: &[H256]);
+
     /// Look up the block number for the given block ID.
     fn block_number(&self, id: BlockId) -> Option<BlockNumber>;
 
@@ -214,9 +245,26 @@ pub trait BlockChainClient: Sync + Send + AccountData + BlockChain + ImportBlock
     /// Get block hash.
     fn block_hash(&self, id: BlockId) -> Option<H256>;
 
+    /// Get the hash of the latest finalized block.
+    fn finalized_block_hash(&self) -> H256;
+
+    /// Get the finality proof of the block, if the engine gave one.
+    fn finality_proof(&self, hash: &H256) -> Option<Bytes>;
+
+    /// Get the number of the earliest block whose body is kept.
+    fn earliest_body_number(&self) -> BlockNumber;
+
+    /// Get the progress of the block sync. None if the sync extension hasn't reported it.
+    fn sync_progress(&self) -> Option<SyncProgress>;
+
     /// Get parcel with given hash.
     fn parcel(&self, id: ParcelId) -> Option<LocalizedParcel>;
 
+    /// Get the addresses of the parcels sent by the account, in the order they appear in the canon chain.
+    /// The sender of a parcel signed by a regular key is the owner of the key.
+    /// The first `offset` of them are skipped, and at most `limit` of them are returned.
+    fn sender_parcel_addresses(&self, sender: &Address, offset: usize, limit: usize) -> Vec<ParcelAddress>;
+
     /// Get parcel invoice with given hash.
     fn parcel_invoice(&self, id: ParcelId) -> Option<ParcelInvoice>;
 
@@ -225,7 +273,13 @@ pub trait BlockChainClient: Sync + Send + AccountData + BlockChain + ImportBlock
 
     fn transaction_invoice(&self, id: TransactionId) -> Option<TransactionInvoice>;
 
+    /// Returns the parcels matching given filter, in the order they appear in the canon chain.
+    fn logs(&self, filter: Filter) -> Vec<LocalizedLog>;
+
     fn custom_handlers(&self) -> Vec<Arc<ActionHandler>>;
+
+    /// Get the hit/miss counters of the blockchain caches.
+    fn cache_stats(&self) -> BlockChainCacheStats;
 }
 
 /// Result of import block operation.
@@ -258,6 +312,20 @@ pub trait MiningBlockChainClient: BlockChainClient + BlockProducer + ImportSeale
 /// Provides methods to access database.
 pub trait DatabaseClient {
     fn database(&self) -> Arc<KeyValueDB>;
+
+    /// Writes the buffered writes of the imported blocks to the disk.
+    /// The backups and the iterators of the database see the flushed data only.
+    fn flush_database(&self) -> kvdb::Result<()>;
+
+    /// Copies the database to the given path with every write buffered before the call.
+    fn backup_database(&self, path: &str) -> kvdb::Result<()>;
+}
+
+/// Provides `revert_to` method, which rewinds the chain of a test network.
+pub trait RevertChain {
+    /// Makes the canon block with the given hash the best block again, deleting the blocks after it.
+    /// The parcels in the deleted blocks are dropped. Returns false if the block is not in the canon chain.
+    fn revert_to(&self, hash: &H256) -> bool;
 }
 
 /// Provides methods to access asset
@@ -282,3 +350,17 @@ pub trait ExecuteClient {
         sender: &Address,
     ) -> Result<Vec<ShardChange>, CoreError>;
 }
+
+pub trait TraceClient {
+    /// Re-executes the parcels of the block on the state of its parent, returning the effects of each parcel.
+    /// The block reward is not included.
+    fn replay_block(&self, id: BlockId) -> Result<Option<Vec<Vec<Trace>>>, CoreError>;
+
+    /// Re-executes the parcel on the state just before it, returning its effects.
+    fn trace_parcel(&self, hash: H256) -> Result<Option<Vec<Trace>>, CoreError>;
+
+    /// Executes the parcel on the state of the block as if it were in the next block, without importing it.
+    /// Returns the invoice and the effects, or None if the state of the block is not available.
+    fn call_parcel(&self, parcel: &SignedParcel, id: BlockId)
+        -> Result<Option<(ParcelInvoice, Vec<Trace>)>, CoreError>;
+}
@@ -50,7 +50,7 @@ use primitives::{Bytes, H256, U256};
 use rlp::*;
 
 use super::super::block::{ClosedBlock, OpenBlock, SealedBlock};
-use super::super::blockchain::ParcelAddress;
+use super::super::blockchain::{BlockChainCacheStats, ParcelAddress};
 use super::super::blockchain_info::BlockChainInfo;
 use super::super::client::ImportResult;
 use super::super::client::{
@@ -65,7 +65,9 @@ use super::super::header::Header as BlockHeader;
 use super::super::miner::{Miner, MinerService, ParcelImportResult};
 use super::super::parcel::{LocalizedParcel, SignedParcel};
 use super::super::scheme::Scheme;
-use super::super::types::{BlockId, ParcelId, TransactionId, VerificationQueueInfo as QueueInfo};
+use super::super::types::{
+    BlockId, Filter, LocalizedLog, ParcelId, SyncProgress, TransactionId, VerificationQueueInfo as QueueInfo,
+};
 
 /// Test client.
 pub struct TestBlockChainClient {
@@ -373,6 +375,10 @@ impl ChainInfo for TestBlockChainClient {
             best_block_timestamp: number,
         }
     }
+
+    fn best_block_number_and_hash(&self) -> (BlockNumber, H256) {
+        (self.blocks.read().len() as BlockNumber - 1, self.last_hash.read().clone())
+    }
 }
 
 impl BlockInfo for TestBlockChainClient {
@@ -480,6 +486,10 @@ impl BlockChainClient for TestBlockChainClient {
         self.miner.ready_parcels()
     }
 
+    fn note_parcels_broadcast(&self, hashes: &[H256]) {
+        self.miner.note_parcels_broadcast(hashes)
+    }
+
     fn block_number(&self, _id: BlockId) -> Option<BlockNumber> {
         unimplemented!()
     }
@@ -511,10 +521,30 @@ impl BlockChainClient for TestBlockChainClient {
         Self::block_hash(self, id)
     }
 
+    fn finalized_block_hash(&self) -> H256 {
+        self.genesis_hash
+    }
+
+    fn finality_proof(&self, _hash: &H256) -> Option<Bytes> {
+        None
+    }
+
+    fn earliest_body_number(&self) -> BlockNumber {
+        0
+    }
+
+    fn sync_progress(&self) -> Option<SyncProgress> {
+        None
+    }
+
     fn parcel(&self, _id: ParcelId) -> Option<LocalizedParcel> {
         unimplemented!();
     }
 
+    fn sender_parcel_addresses(&self, _sender: &Address, _offset: usize, _limit: usize) -> Vec<ParcelAddress> {
+        unimplemented!();
+    }
+
     fn parcel_invoice(&self, _id: ParcelId) -> Option<ParcelInvoice> {
         unimplemented!();
     }
@@ -527,9 +557,17 @@ impl BlockChainClient for TestBlockChainClient {
         unimplemented!()
     }
 
+    fn logs(&self, _filter: Filter) -> Vec<LocalizedLog> {
+        unimplemented!()
+    }
+
     fn custom_handlers(&self) -> Vec<Arc<ActionHandler>> {
         unimplemented!()
     }
+
+    fn cache_stats(&self) -> BlockChainCacheStats {
+        Default::default()
+    }
 }
 
 impl super::EngineClient for TestBlockChainClient {
@@ -27,15 +27,19 @@ use super::error::Error;
 use super::header::Header;
 use super::parcel::{SignedParcel, UnverifiedParcel};
 use super::scheme::CommonParams;
+use super::signer_cache::{SignerCache, DEFAULT_SIGNER_CACHE_SIZE};
 
 pub struct CodeChainMachine {
     params: CommonParams,
+    /// The signers recovered by both the mem pool and the block verifier, since both verify parcels here.
+    signers: SignerCache,
 }
 
 impl CodeChainMachine {
     pub fn new(params: CommonParams) -> Self {
         CodeChainMachine {
             params,
+            signers: SignerCache::new(DEFAULT_SIGNER_CACHE_SIZE),
         }
     }
 
@@ -55,9 +59,10 @@ impl CodeChainMachine {
 
     /// Does basic verification of the parcel.
     pub fn verify_parcel_basic(&self, p: &UnverifiedParcel, _header: &Header) -> Result<(), Error> {
-        if p.fee < self.params.min_parcel_cost {
+        let minimal = self.params.min_parcel_cost.saturating_add(self.signature_fee(p.signature().count()));
+        if p.fee < minimal {
             return Err(StateError::Parcel(ParcelError::InsufficientFee {
-                minimal: self.params.min_parcel_cost,
+                minimal,
                 got: p.fee,
             }).into())
         }
@@ -66,9 +71,19 @@ impl CodeChainMachine {
         Ok(())
     }
 
+    /// The additional fee of the signatures. The first signature is covered by the minimum parcel cost.
+    fn signature_fee(&self, signature_count: usize) -> U256 {
+        let additional = signature_count.saturating_sub(1);
+        let (fee, overflow) = self.params.additional_signature_fee.overflowing_mul((additional as u64).into());
+        if overflow {
+            return U256::max_value()
+        }
+        fee
+    }
+
     /// Verify a particular parcel is valid, regardless of order.
     pub fn verify_parcel_unordered(&self, p: UnverifiedParcel, _header: &Header) -> Result<SignedParcel, Error> {
-        Ok(SignedParcel::new(p)?)
+        Ok(self.signers.recover(p)?)
     }
 
     /// Does verification of the parcel against the parent state.
@@ -106,3 +121,64 @@ impl WithBalances for CodeChainMachine {
         Ok(live.state_mut().add_balance(address, amount).map_err(StateError::from)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ckey::{sign, Generator, KeyPair, Random};
+    use ctypes::parcel::Parcel;
+
+    use super::super::parcel::MultiSig;
+    use super::*;
+
+    fn multisig_payment(fee: u64, keys: &[KeyPair], threshold: u8) -> UnverifiedParcel {
+        let parcel = Parcel {
+            nonce: 0.into(),
+            fee: fee.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver: Address::default(),
+                amount: 300.into(),
+            },
+        };
+        let multisig = MultiSig::new(threshold, keys.iter().map(|key| *key.public()).collect());
+        let message = multisig.message(&parcel.hash());
+        let mut parcel = UnverifiedParcel::new_multisig(parcel, multisig);
+        for (index, key) in keys.iter().enumerate().take(threshold as usize) {
+            let sig = sign(key.private(), &message).unwrap();
+            parcel = parcel.update_multisig(|multisig| multisig.add_signature(index as u8, sig)).unwrap();
+        }
+        parcel
+    }
+
+    fn insufficient_fee(result: Result<(), Error>) -> Option<(U256, U256)> {
+        match result {
+            Err(Error::State(StateError::Parcel(ParcelError::InsufficientFee {
+                minimal,
+                got,
+            }))) => Some((minimal, got)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn multisig_parcel_pays_for_the_additional_signatures() {
+        let machine = CodeChainMachine::new(CommonParams {
+            network_id: "tc".into(),
+            min_parcel_cost: 10.into(),
+            max_body_size: 4 * 1024 * 1024,
+            additional_signature_fee: 5.into(),
+            ..Default::default()
+        });
+        let keys: Vec<KeyPair> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        let header = Header::default();
+
+        // 10 for the parcel and 5 for each of the second and the third signatures
+        assert_eq!(
+            Some((20.into(), 19.into())),
+            insufficient_fee(machine.verify_parcel_basic(&multisig_payment(19, &keys, 3), &header))
+        );
+        assert!(machine.verify_parcel_basic(&multisig_payment(20, &keys, 3), &header).is_ok());
+        assert!(machine.verify_parcel_basic(&multisig_payment(15, &keys, 2), &header).is_ok());
+        assert!(machine.verify_parcel_basic(&multisig_payment(10, &keys, 1), &header).is_ok());
+    }
+}
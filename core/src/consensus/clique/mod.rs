@@ -0,0 +1,458 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+mod params;
+
+use std::sync::{Arc, Weak};
+
+use ckey::{public_to_address, recover, Address, Password, Signature};
+use cnetwork::{Api, NetworkExtension, TimerToken};
+use ctypes::machine::WithBalances;
+use ctypes::util::unexpected::{Mismatch, OutOfBounds};
+use parking_lot::RwLock;
+use primitives::{H256, U256};
+use rlp::UntrustedRlp;
+use time::{get_time, Duration};
+
+pub use self::params::CliqueParams;
+use super::super::account_provider::AccountProvider;
+use super::super::block::{ExecutedBlock, IsBlock};
+use super::super::client::EngineClient;
+use super::super::codechain_machine::CodeChainMachine;
+use super::super::consensus::EngineType;
+use super::super::error::{BlockError, Error};
+use super::super::header::Header;
+use super::super::miner::RemoteSigner;
+use super::super::types::BlockId;
+use super::signer::{EngineSigner, SignableMessage};
+use super::validator_set::validator_list::ValidatorList;
+use super::validator_set::ValidatorSet;
+use super::{ConsensusEngine, EngineError, Seal};
+
+/// Score of a block sealed by the validator whose turn it is.
+const IN_TURN_SCORE: u64 = 2;
+/// Score of a block sealed by any other validator.
+const OUT_OF_TURN_SCORE: u64 = 1;
+
+const SEALING_TIMER_TOKEN: TimerToken = 0;
+
+/// Proof-of-authority engine where validators take turns sealing blocks.
+///
+/// The validator at `number % validators.len()` is in turn for block `number`. Others may
+/// seal the block too once twice the period has passed, but their blocks get a lower score
+/// so the in-turn block wins a fork.
+///
+/// A validator may seal only one of any `validators.len() / 2 + 1` consecutive blocks,
+/// so that a minority of the validators can't take over the chain.
+pub struct Clique {
+    machine: CodeChainMachine,
+    signer: RwLock<EngineSigner>,
+    validators: Box<ValidatorSet>,
+    /// Minimum seconds between blocks.
+    period: u64,
+    /// Reward per block, in base units.
+    block_reward: U256,
+    extension: Arc<CliqueExtension>,
+}
+
+impl Clique {
+    /// Create a new instance of Clique engine
+    pub fn new(params: CliqueParams, machine: CodeChainMachine) -> Self {
+        Clique {
+            machine,
+            signer: Default::default(),
+            validators: Box::new(ValidatorList::new(params.validators)),
+            period: params.period,
+            block_reward: params.block_reward,
+            extension: Arc::new(CliqueExtension::new()),
+        }
+    }
+
+    fn is_in_turn(&self, header: &Header) -> bool {
+        self.validators.get(header.parent_hash(), header.number() as usize) == *header.author()
+    }
+
+    fn score(in_turn: bool) -> U256 {
+        if in_turn {
+            IN_TURN_SCORE.into()
+        } else {
+            OUT_OF_TURN_SCORE.into()
+        }
+    }
+
+    fn has_sealed_recently(&self, author: &Address, parent: &Header) -> bool {
+        let recents = self.validators.count(&parent.hash()) / 2;
+        let client = self.extension.client();
+        has_sealed_recently(author, parent, recents, |hash| {
+            client.as_ref().and_then(|client| client.block_header(BlockId::Hash(*hash))).map(|header| header.decode())
+        })
+    }
+
+    /// The earliest timestamp of a block on top of `parent`.
+    fn earliest_timestamp(&self, parent: &Header, in_turn: bool) -> u64 {
+        if in_turn {
+            parent.timestamp() + self.period
+        } else {
+            parent.timestamp() + 2 * self.period
+        }
+    }
+}
+
+impl ConsensusEngine<CodeChainMachine> for Clique {
+    fn name(&self) -> &str {
+        "Clique"
+    }
+
+    fn machine(&self) -> &CodeChainMachine {
+        &self.machine
+    }
+
+    // One field - the signature
+    fn seal_fields(&self, _header: &Header) -> usize {
+        1
+    }
+
+    fn seals_internally(&self) -> Option<bool> {
+        Some(self.signer.read().is_some())
+    }
+
+    fn engine_type(&self) -> EngineType {
+        EngineType::InternalSealing
+    }
+
+    /// Attempt to seal the block internally.
+    fn generate_seal(&self, block: &ExecutedBlock, parent: &Header) -> Seal {
+        let header = block.header();
+        if !self.validators.contains(header.parent_hash(), header.author()) {
+            return Seal::None
+        }
+        if self.has_sealed_recently(header.author(), parent) {
+            ctrace!(ENGINE, "generate_seal: {} sealed one of the recent blocks", header.author());
+            return Seal::None
+        }
+        // The step timer retries once the block is due.
+        if header.timestamp() > get_time().sec as u64 {
+            ctrace!(ENGINE, "generate_seal: block #{} is not due yet", header.number());
+            return Seal::None
+        }
+        // account should be permanently unlocked, otherwise sealing will fail
+        match self.sign(SignableMessage::header(header)) {
+            Ok(signature) => Seal::Regular(vec![::rlp::encode(&signature).into_vec()]),
+            Err(_) => {
+                ctrace!(ENGINE, "generate_seal: FAIL: accounts secret key unavailable");
+                Seal::None
+            }
+        }
+    }
+
+    fn verify_local_seal(&self, _header: &Header) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn verify_block_basic(&self, header: &Header) -> Result<(), Error> {
+        let seal_length = header.seal().len();
+        if seal_length != 1 {
+            return Err(EngineError::BadSealFieldSize(OutOfBounds {
+                min: Some(1),
+                max: Some(1),
+                found: seal_length,
+            }).into())
+        }
+        Ok(())
+    }
+
+    fn verify_block_unordered(&self, header: &Header) -> Result<(), Error> {
+        let signature = UntrustedRlp::new(&header.seal()[0]).as_val::<Signature>()?;
+        let signer = public_to_address(&recover(&signature, &header.bare_hash())?);
+        if *header.author() != signer {
+            return Err(EngineError::NotAuthorized(signer).into())
+        }
+        Ok(())
+    }
+
+    fn verify_block_family(&self, header: &Header, parent: &Header) -> Result<(), Error> {
+        if !self.validators.contains(header.parent_hash(), header.author()) {
+            return Err(EngineError::NotAuthorized(*header.author()).into())
+        }
+        if self.has_sealed_recently(header.author(), parent) {
+            return Err(EngineError::RecentlySealed(*header.author()).into())
+        }
+
+        let in_turn = self.is_in_turn(header);
+        let expected_score = Self::score(in_turn);
+        if *header.score() != expected_score {
+            return Err(BlockError::InvalidScore(Mismatch {
+                expected: expected_score,
+                found: *header.score(),
+            }).into())
+        }
+
+        let earliest = self.earliest_timestamp(parent, in_turn);
+        if header.timestamp() < earliest {
+            return Err(BlockError::InvalidTimestamp(OutOfBounds {
+                min: Some(earliest),
+                max: None,
+                found: header.timestamp(),
+            }).into())
+        }
+        Ok(())
+    }
+
+    fn verifies_seal_with_state(&self) -> bool {
+        self.validators.reads_state()
+    }
+
+    fn populate_from_parent(&self, header: &mut Header, parent: &Header) {
+        let in_turn = self.is_in_turn(header);
+        header.set_score(Self::score(in_turn));
+
+        let earliest = self.earliest_timestamp(parent, in_turn);
+        if header.timestamp() < earliest {
+            header.set_timestamp(earliest);
+        }
+    }
+
+    fn on_close_block(&self, block: &mut ExecutedBlock) -> Result<(), Error> {
+        let author = *block.header().author();
+        let total_reward = block.parcels().iter().fold(self.block_reward, |sum, parcel| sum + parcel.fee);
+        self.machine.add_balance(block, &author, &total_reward)
+    }
+
+    fn register_client(&self, client: Weak<EngineClient>) {
+        self.extension.register_client(client.clone());
+        self.validators.register_client(client);
+    }
+
+    fn validators(&self, header: &Header) -> Option<Vec<Address>> {
+        self.validators.validators(&header.hash())
+    }
+
+    /// Register an account which signs consensus messages.
+    fn set_signer(&self, ap: Arc<AccountProvider>, address: Address, password: Option<Password>) {
+        self.signer.write().set(ap, address, password);
+    }
+
+    fn set_remote_signer(&self, remote: Arc<RemoteSigner>) {
+        self.signer.write().set_remote(remote);
+    }
+
+    fn sign(&self, message: SignableMessage) -> Result<Signature, Error> {
+        let signer = self.signer.read().clone();
+        signer.sign(&message).map_err(Into::into)
+    }
+
+    fn network_extension(&self) -> Option<Arc<NetworkExtension>> {
+        Some(Arc::clone(&self.extension) as Arc<NetworkExtension>)
+    }
+}
+
+/// Step timer which makes the miner retry sealing once a delayed block is due.
+struct CliqueExtension {
+    client: RwLock<Option<Weak<EngineClient>>>,
+}
+
+impl CliqueExtension {
+    fn new() -> Self {
+        CliqueExtension {
+            client: RwLock::new(None),
+        }
+    }
+
+    fn register_client(&self, client: Weak<EngineClient>) {
+        *self.client.write() = Some(client);
+    }
+
+    fn client(&self) -> Option<Arc<EngineClient>> {
+        self.client.read().as_ref().and_then(Weak::upgrade)
+    }
+}
+
+/// Whether the author sealed one of the `recents` blocks ending at the parent.
+/// The blocks whose headers are not found, which precede a snapshot, are not checked.
+fn has_sealed_recently<F>(author: &Address, parent: &Header, recents: usize, ancestor: F) -> bool
+where
+    F: Fn(&H256) -> Option<Header>, {
+    let mut header = parent.clone();
+    for _ in 0..recents {
+        // The genesis block is not sealed by a validator.
+        if header.number() == 0 {
+            return false
+        }
+        if header.author() == author {
+            return true
+        }
+        header = match ancestor(header.parent_hash()) {
+            Some(header) => header,
+            None => return false,
+        };
+    }
+    false
+}
+
+impl NetworkExtension for CliqueExtension {
+    fn name(&self) -> &'static str {
+        "clique"
+    }
+
+    fn need_encryption(&self) -> bool {
+        false
+    }
+
+    fn versions(&self) -> &[u64] {
+        const VERSIONS: &'static [u64] = &[0];
+        &VERSIONS
+    }
+
+    fn on_initialize(&self, api: Arc<Api>) {
+        api.set_timer(SEALING_TIMER_TOKEN, Duration::seconds(1)).expect("Timer set succeeds");
+    }
+
+    fn on_timeout(&self, timer: TimerToken) {
+        match timer {
+            SEALING_TIMER_TOKEN => {
+                if let Some(ref weak) = *self.client.read() {
+                    if let Some(c) = weak.upgrade() {
+                        c.update_sealing();
+                    }
+                }
+            }
+            _ => debug_assert!(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{sign, Generator, KeyPair, Random};
+
+    use super::super::super::codechain_machine::CodeChainMachine;
+    use super::super::super::header::Header;
+    use super::super::ConsensusEngine;
+    use super::{has_sealed_recently, Clique, CliqueParams};
+
+    fn engine(validators: &[KeyPair]) -> Clique {
+        let params = CliqueParams {
+            validators: validators.iter().map(KeyPair::address).collect(),
+            period: 5,
+            block_reward: 0.into(),
+        };
+        Clique::new(params, CodeChainMachine::new(Default::default()))
+    }
+
+    fn sealed_header(parent: &Header, author: &KeyPair, engine: &Clique) -> Header {
+        let mut header = Header::default();
+        header.set_parent_hash(parent.hash());
+        header.set_number(parent.number() + 1);
+        header.set_author(author.address());
+        header.set_timestamp(parent.timestamp());
+        engine.populate_from_parent(&mut header, parent);
+        let signature = sign(author.private(), &header.bare_hash()).unwrap();
+        header.set_seal(vec![::rlp::encode(&signature).into_vec()]);
+        header
+    }
+
+    #[test]
+    fn validators_take_turns() {
+        let validators: Vec<_> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        let engine = engine(&validators);
+        let genesis = Header::default();
+
+        let in_turn = sealed_header(&genesis, &validators[1], &engine);
+        assert_eq!(2, in_turn.score().low_u64());
+        assert_eq!(5, in_turn.timestamp());
+        assert!(engine.verify_block_family(&in_turn, &genesis).is_ok());
+
+        let out_of_turn = sealed_header(&genesis, &validators[2], &engine);
+        assert_eq!(1, out_of_turn.score().low_u64());
+        assert_eq!(10, out_of_turn.timestamp());
+        assert!(engine.verify_block_family(&out_of_turn, &genesis).is_ok());
+    }
+
+    #[test]
+    fn rejects_early_or_overscored_blocks() {
+        let validators: Vec<_> = (0..2).map(|_| Random.generate().unwrap()).collect();
+        let engine = engine(&validators);
+        let genesis = Header::default();
+
+        let mut early = sealed_header(&genesis, &validators[0], &engine);
+        early.set_timestamp(7);
+        assert!(engine.verify_block_family(&early, &genesis).is_err());
+
+        let mut overscored = sealed_header(&genesis, &validators[0], &engine);
+        overscored.set_score(2.into());
+        assert!(engine.verify_block_family(&overscored, &genesis).is_err());
+    }
+
+    #[test]
+    fn verifies_the_seal_of_the_author() {
+        let validators: Vec<_> = (0..2).map(|_| Random.generate().unwrap()).collect();
+        let engine = engine(&validators);
+        let genesis = Header::default();
+
+        let header = sealed_header(&genesis, &validators[1], &engine);
+        assert!(engine.verify_block_basic(&header).is_ok());
+        assert!(engine.verify_block_unordered(&header).is_ok());
+
+        let mut forged = header.clone();
+        forged.set_author(validators[0].address());
+        assert!(engine.verify_block_unordered(&forged).is_err());
+    }
+
+    #[test]
+    fn rejects_consecutive_blocks_of_a_validator() {
+        let validators: Vec<_> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        let engine = engine(&validators);
+        let genesis = Header::default();
+
+        let first = sealed_header(&genesis, &validators[1], &engine);
+        assert!(engine.verify_block_family(&first, &genesis).is_ok());
+        let again = sealed_header(&first, &validators[1], &engine);
+        assert!(engine.verify_block_family(&again, &first).is_err());
+        let other = sealed_header(&first, &validators[2], &engine);
+        assert!(engine.verify_block_family(&other, &first).is_ok());
+    }
+
+    #[test]
+    fn looks_back_half_of_the_validators() {
+        let validators: Vec<_> = (0..5).map(|_| Random.generate().unwrap()).collect();
+        let engine = engine(&validators);
+        let genesis = Header::default();
+        let first = sealed_header(&genesis, &validators[1], &engine);
+        let second = sealed_header(&first, &validators[2], &engine);
+        let third = sealed_header(&second, &validators[3], &engine);
+        let ancestors = vec![genesis.clone(), first.clone(), second.clone()];
+        let ancestor = |hash: &_| ancestors.iter().find(|header| header.hash() == *hash).cloned();
+
+        let recents = validators.len() / 2;
+        assert!(has_sealed_recently(&validators[2].address(), &third, recents, &ancestor));
+        assert!(!has_sealed_recently(&validators[1].address(), &third, recents, &ancestor));
+        assert!(has_sealed_recently(&validators[1].address(), &second, recents, &ancestor));
+        assert!(!has_sealed_recently(&validators[0].address(), &second, recents, &ancestor));
+    }
+
+    #[test]
+    fn rejects_outsiders() {
+        let validators: Vec<_> = (0..2).map(|_| Random.generate().unwrap()).collect();
+        let outsider = Random.generate().unwrap();
+        let engine = engine(&validators);
+        let genesis = Header::default();
+
+        let header = sealed_header(&genesis, &outsider, &engine);
+        assert!(engine.verify_block_unordered(&header).is_ok());
+        assert!(engine.verify_block_family(&header, &genesis).is_err());
+    }
+}
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use ctypes::machine::Machine;
+use ctypes::BlockNumber;
 use primitives::H256;
 use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
@@ -73,6 +74,19 @@ impl Decodable for Transition {
     }
 }
 
+/// Combines the "transition/epoch" proof with the finality proof into the proof of a `Transition`.
+pub fn combine_proofs(signal_number: BlockNumber, set_proof: &[u8], finality_proof: &[u8]) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(3);
+    stream.append(&signal_number).append(&set_proof).append(&finality_proof);
+    stream.out()
+}
+
+/// Splits the proof of a `Transition` into the signal number, the "transition/epoch" proof and the finality proof.
+pub fn destructure_proofs(combined: &[u8]) -> Result<(BlockNumber, &[u8], &[u8]), DecoderError> {
+    let rlp = UntrustedRlp::new(combined);
+    Ok((rlp.at(0)?.as_val()?, rlp.at(1)?.data()?, rlp.at(2)?.data()?))
+}
+
 /// An epoch transition pending a finality proof.
 /// Not all transitions need one.
 pub struct PendingTransition {
@@ -0,0 +1,94 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use ctypes::BlockNumber;
+use primitives::{H256, U256};
+
+/// The head chosen between the current best block and a newly imported block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkChoice {
+    /// Choose the new block.
+    New,
+    /// Keep the current best block.
+    Old,
+}
+
+/// The head of a fork competing to be the best block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForkHead {
+    pub hash: H256,
+    pub number: BlockNumber,
+    pub total_score: U256,
+    /// Whether the fork contains the latest finalized block.
+    pub is_finalized: bool,
+}
+
+/// The fork with the higher total score wins. The current best block wins a tie.
+pub fn by_total_score(new: &ForkHead, current: &ForkHead) -> ForkChoice {
+    if new.total_score > current.total_score {
+        ForkChoice::New
+    } else {
+        ForkChoice::Old
+    }
+}
+
+/// A fork which reverts the finalized block never wins, whatever its score is.
+pub fn by_finality(new: &ForkHead, current: &ForkHead) -> ForkChoice {
+    match (new.is_finalized, current.is_finalized) {
+        (true, false) => ForkChoice::New,
+        (false, true) => ForkChoice::Old,
+        _ => by_total_score(new, current),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head(total_score: u64, is_finalized: bool) -> ForkHead {
+        ForkHead {
+            hash: H256::random(),
+            number: 1,
+            total_score: total_score.into(),
+            is_finalized,
+        }
+    }
+
+    #[test]
+    fn higher_total_score_wins() {
+        assert_eq!(ForkChoice::New, by_total_score(&head(2, true), &head(1, true)));
+        assert_eq!(ForkChoice::Old, by_total_score(&head(1, true), &head(2, true)));
+    }
+
+    #[test]
+    fn current_wins_tie() {
+        assert_eq!(ForkChoice::Old, by_total_score(&head(1, true), &head(1, true)));
+        assert_eq!(ForkChoice::Old, by_finality(&head(1, true), &head(1, true)));
+    }
+
+    #[test]
+    fn fork_reverting_finalized_block_never_wins() {
+        assert_eq!(ForkChoice::New, by_total_score(&head(10, false), &head(1, true)));
+        assert_eq!(ForkChoice::Old, by_finality(&head(10, false), &head(1, true)));
+    }
+
+    #[test]
+    fn total_score_decides_between_finalized_forks() {
+        assert_eq!(ForkChoice::New, by_finality(&head(2, true), &head(1, true)));
+        assert_eq!(ForkChoice::Old, by_finality(&head(1, true), &head(2, true)));
+    }
+}
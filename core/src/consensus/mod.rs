@@ -15,8 +15,11 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod blake_pow;
+mod clique;
 mod cuckoo;
 pub mod epoch;
+pub mod fork_choice;
+mod multi;
 mod null_engine;
 mod signer;
 mod simple_poa;
@@ -26,11 +29,16 @@ mod validator_set;
 mod vote_collector;
 
 pub use self::blake_pow::BlakePoW;
+pub use self::clique::Clique;
 pub use self::cuckoo::Cuckoo;
+pub use self::multi::Multi;
 pub use self::null_engine::NullEngine;
+pub use self::signer::{EngineSigner, SignableMessage};
 pub use self::simple_poa::SimplePoA;
 pub use self::solo::Solo;
 pub use self::tendermint::{Tendermint, TendermintParams};
+pub use self::validator_set::key_rotation::{KeyRotation, KeyRotationAction};
+pub use self::validator_set::validator_change::{ChangeValidatorsAction, ValidatorChange};
 pub use self::validator_set::validator_list::ValidatorList;
 pub use self::validator_set::ValidatorSet;
 
@@ -39,16 +47,19 @@ use std::sync::{Arc, Weak};
 
 use ckey::{Address, Password, Signature};
 use cnetwork::NetworkExtension;
+use cstate::ActionHandler;
 use ctypes::machine::Machine;
 use ctypes::util::unexpected::{Mismatch, OutOfBounds};
 use primitives::{Bytes, H256, U256};
 
 use self::epoch::{EpochVerifier, NoOp, PendingTransition};
+use self::fork_choice::{ForkChoice, ForkHead};
 use super::account_provider::AccountProvider;
 use super::block::SealedBlock;
 use super::codechain_machine::CodeChainMachine;
 use super::error::Error;
 use super::header::Header;
+use super::miner::RemoteSigner;
 use super::parcel::{SignedParcel, UnverifiedParcel};
 use super::scheme::CommonParams;
 
@@ -94,6 +105,26 @@ pub trait ConsensusEngine<M: Machine>: Sync + Send {
     /// The type of this engine.
     fn engine_type(&self) -> EngineType;
 
+    /// The number of blocks which must be built on a block before it is considered final.
+    fn finality_depth(&self) -> u64 {
+        12
+    }
+
+    /// Proof that the block is final by itself, without waiting for `finality_depth` blocks.
+    fn finality_proof(&self, _header: &M::Header) -> Option<Bytes> {
+        None
+    }
+
+    /// Whether a finalized block is never reverted. Otherwise a fork with a higher score may still revert it.
+    fn is_finality_absolute(&self) -> bool {
+        false
+    }
+
+    /// Choose the best block between the current one and a newly imported one.
+    fn fork_choice(&self, new: &ForkHead, current: &ForkHead) -> ForkChoice {
+        fork_choice::by_total_score(new, current)
+    }
+
     /// Attempt to seal the block internally.
     ///
     /// If `Some` is returned, then you get a valid seal.
@@ -140,6 +171,11 @@ pub trait ConsensusEngine<M: Machine>: Sync + Send {
         Ok(())
     }
 
+    /// Whether the seals are verified with the state, which a light client doesn't have.
+    fn verifies_seal_with_state(&self) -> bool {
+        false
+    }
+
     /// Genesis epoch data.
     fn genesis_epoch_data(&self, _header: &M::Header) -> Result<Vec<u8>, String> {
         Ok(Vec::new())
@@ -199,6 +235,12 @@ pub trait ConsensusEngine<M: Machine>: Sync + Send {
     /// Add Client which can be used for sealing, potentially querying the state and sending messages.
     fn register_client(&self, _client: Weak<M::EngineClient>) {}
 
+    /// Returns the validators which seal the child of the given block.
+    /// `None` if the engine has no validators.
+    fn validators(&self, _header: &M::Header) -> Option<Vec<Address>> {
+        None
+    }
+
     /// Handle any potential consensus messages;
     /// updating consensus state and potentially issuing a new one.
     fn handle_message(&self, _message: &[u8]) -> Result<(), EngineError> {
@@ -217,8 +259,11 @@ pub trait ConsensusEngine<M: Machine>: Sync + Send {
     /// Register an account which signs consensus messages.
     fn set_signer(&self, _ap: Arc<AccountProvider>, _address: Address, _password: Option<Password>) {}
 
+    /// Register a remote signer which keeps the key signing consensus messages.
+    fn set_remote_signer(&self, _remote: Arc<RemoteSigner>) {}
+
     /// Sign using the EngineSigner, to be used for consensus parcel signing.
-    fn sign(&self, _hash: H256) -> Result<Signature, Error> {
+    fn sign(&self, _message: SignableMessage) -> Result<Signature, Error> {
         unimplemented!()
     }
 
@@ -226,6 +271,16 @@ pub trait ConsensusEngine<M: Machine>: Sync + Send {
         None
     }
 
+    /// All the network extensions that the engine needs, including the one of `network_extension`.
+    fn network_extensions(&self) -> Vec<Arc<NetworkExtension>> {
+        self.network_extension().into_iter().collect()
+    }
+
+    /// Handlers of the custom actions that the engine needs.
+    fn action_handlers(&self) -> Vec<Arc<ActionHandler>> {
+        Vec::new()
+    }
+
     fn score_to_target(&self, _score: &U256) -> U256 {
         U256::zero()
     }
@@ -279,6 +334,8 @@ pub enum EngineError {
     BadSealFieldSize(OutOfBounds<usize>),
     /// Malformed consensus message.
     MalformedMessage(String),
+    /// The author sealed one of the recent blocks.
+    RecentlySealed(Address),
 }
 
 impl fmt::Display for EngineError {
@@ -291,6 +348,7 @@ impl fmt::Display for EngineError {
             UnexpectedMessage => "This Engine should not be fed messages.".into(),
             BadSealFieldSize(oob) => format!("Seal field has an unexpected length: {}", oob),
             MalformedMessage(msg) => format!("Received malformed consensus message: {}", msg),
+            RecentlySealed(address) => format!("Author {} sealed one of the recent blocks.", address),
         };
 
         f.write_fmt(format_args!("Engine error ({})", msg))
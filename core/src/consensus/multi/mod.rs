@@ -0,0 +1,313 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::sync::{Arc, Weak};
+
+use ckey::{Address, Password, Signature};
+use cnetwork::NetworkExtension;
+use cstate::ActionHandler;
+use ctypes::BlockNumber;
+use parking_lot::RwLock;
+use primitives::{Bytes, U256};
+
+use super::super::account_provider::AccountProvider;
+use super::super::block::{ExecutedBlock, IsBlock, SealedBlock};
+use super::super::client::EngineClient;
+use super::super::codechain_machine::CodeChainMachine;
+use super::super::error::Error;
+use super::super::header::Header;
+use super::super::miner::RemoteSigner;
+use super::fork_choice::{ForkChoice, ForkHead};
+use super::{
+    CodeChainEngine, ConsensusEngine, ConstructedVerifier, EngineError, EngineType, EpochChange, Headers,
+    PendingTransitionStore, Seal, SignableMessage,
+};
+
+/// An engine which hands the chain over to another engine at the configured blocks.
+///
+/// Every block is verified and sealed by the engine of its number. The methods which are not bound to a block
+/// are delegated to the engine of the next block of the best block.
+pub struct Multi {
+    /// Engines and the first block of each of them, in ascending order.
+    engines: Vec<(BlockNumber, Arc<CodeChainEngine>)>,
+    client: RwLock<Option<Weak<EngineClient>>>,
+    machine: CodeChainMachine,
+}
+
+impl Multi {
+    /// Returns `None` unless the first engine starts from the genesis block and
+    /// the transitions are in ascending order.
+    pub fn new(engines: Vec<(BlockNumber, Arc<CodeChainEngine>)>, machine: CodeChainMachine) -> Option<Self> {
+        if engines.first().map(|(block, _)| *block) != Some(0) {
+            return None
+        }
+        if engines.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return None
+        }
+        Some(Multi {
+            engines,
+            client: RwLock::new(None),
+            machine,
+        })
+    }
+
+    fn engine_at(&self, number: BlockNumber) -> &CodeChainEngine {
+        let (_, engine) = self
+            .engines
+            .iter()
+            .rev()
+            .find(|(block, _)| *block <= number)
+            .expect("The first engine starts from the genesis block");
+        &**engine
+    }
+
+    fn engine_of(&self, header: &Header) -> &CodeChainEngine {
+        self.engine_at(header.number())
+    }
+
+    fn current(&self) -> &CodeChainEngine {
+        let next_number = self
+            .client
+            .read()
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map_or(0, |client| client.chain_info().best_block_number + 1);
+        self.engine_at(next_number)
+    }
+}
+
+impl ConsensusEngine<CodeChainMachine> for Multi {
+    fn name(&self) -> &str {
+        self.current().name()
+    }
+
+    fn machine(&self) -> &CodeChainMachine {
+        &self.machine
+    }
+
+    fn seal_fields(&self, header: &Header) -> usize {
+        self.engine_of(header).seal_fields(header)
+    }
+
+    fn seals_internally(&self) -> Option<bool> {
+        self.current().seals_internally()
+    }
+
+    /// Internal sealing engines need a signer before they take over the chain,
+    /// so this is `InternalSealing` if any of the engines is.
+    fn engine_type(&self) -> EngineType {
+        let internal_sealing =
+            self.engines.iter().any(|(_, engine)| engine.engine_type() == EngineType::InternalSealing);
+        if internal_sealing {
+            EngineType::InternalSealing
+        } else {
+            self.current().engine_type()
+        }
+    }
+
+    fn generate_seal(&self, block: &ExecutedBlock, parent: &Header) -> Seal {
+        self.engine_of(block.header()).generate_seal(block, parent)
+    }
+
+    fn verify_local_seal(&self, header: &Header) -> Result<(), Error> {
+        self.engine_of(header).verify_local_seal(header)
+    }
+
+    fn verify_block_basic(&self, header: &Header) -> Result<(), Error> {
+        self.engine_of(header).verify_block_basic(header)
+    }
+
+    fn verify_block_unordered(&self, header: &Header) -> Result<(), Error> {
+        self.engine_of(header).verify_block_unordered(header)
+    }
+
+    fn verify_block_family(&self, header: &Header, parent: &Header) -> Result<(), Error> {
+        self.engine_of(header).verify_block_family(header, parent)
+    }
+
+    fn verify_block_external(&self, header: &Header) -> Result<(), Error> {
+        self.engine_of(header).verify_block_external(header)
+    }
+
+    fn verifies_seal_with_state(&self) -> bool {
+        self.engines.iter().any(|(_, engine)| engine.verifies_seal_with_state())
+    }
+
+    fn genesis_epoch_data(&self, header: &Header) -> Result<Vec<u8>, String> {
+        self.engine_of(header).genesis_epoch_data(header)
+    }
+
+    fn signals_epoch_end(&self, header: &Header) -> EpochChange {
+        self.engine_of(header).signals_epoch_end(header)
+    }
+
+    fn is_epoch_end(
+        &self,
+        chain_head: &Header,
+        chain: &Headers<Header>,
+        transition_store: &PendingTransitionStore,
+    ) -> Option<Vec<u8>> {
+        self.engine_of(chain_head).is_epoch_end(chain_head, chain, transition_store)
+    }
+
+    fn epoch_verifier<'a>(&self, header: &Header, proof: &'a [u8]) -> ConstructedVerifier<'a, CodeChainMachine> {
+        self.engine_of(header).epoch_verifier(header, proof)
+    }
+
+    fn populate_from_parent(&self, header: &mut Header, parent: &Header) {
+        self.engine_at(header.number()).populate_from_parent(header, parent)
+    }
+
+    fn fork_choice(&self, new: &ForkHead, current: &ForkHead) -> ForkChoice {
+        self.engine_at(new.number).fork_choice(new, current)
+    }
+
+    fn finality_depth(&self) -> u64 {
+        self.current().finality_depth()
+    }
+
+    fn finality_proof(&self, header: &Header) -> Option<Bytes> {
+        self.engine_of(header).finality_proof(header)
+    }
+
+    fn is_finality_absolute(&self) -> bool {
+        self.current().is_finality_absolute()
+    }
+
+    fn step(&self) {
+        self.current().step()
+    }
+
+    fn stop(&self) {
+        for (_, engine) in &self.engines {
+            engine.stop();
+        }
+    }
+
+    fn on_new_block(&self, block: &mut ExecutedBlock, epoch_begin: bool) -> Result<(), Error> {
+        let number = block.header().number();
+        self.engine_at(number).on_new_block(block, epoch_begin)
+    }
+
+    fn on_close_block(&self, block: &mut ExecutedBlock) -> Result<(), Error> {
+        let number = block.header().number();
+        self.engine_at(number).on_close_block(block)
+    }
+
+    fn register_client(&self, client: Weak<EngineClient>) {
+        for (_, engine) in &self.engines {
+            engine.register_client(client.clone());
+        }
+        *self.client.write() = Some(client);
+    }
+
+    fn validators(&self, header: &Header) -> Option<Vec<Address>> {
+        self.engine_at(header.number() + 1).validators(header)
+    }
+
+    fn handle_message(&self, message: &[u8]) -> Result<(), EngineError> {
+        self.current().handle_message(message)
+    }
+
+    fn is_proposal(&self, verified_header: &Header) -> bool {
+        self.engine_of(verified_header).is_proposal(verified_header)
+    }
+
+    fn broadcast_proposal_block(&self, block: SealedBlock) {
+        let number = block.header().number();
+        self.engine_at(number).broadcast_proposal_block(block)
+    }
+
+    fn set_signer(&self, ap: Arc<AccountProvider>, address: Address, password: Option<Password>) {
+        for (_, engine) in &self.engines {
+            engine.set_signer(Arc::clone(&ap), address, password.clone());
+        }
+    }
+
+    fn set_remote_signer(&self, remote: Arc<RemoteSigner>) {
+        for (_, engine) in &self.engines {
+            engine.set_remote_signer(Arc::clone(&remote));
+        }
+    }
+
+    fn sign(&self, message: SignableMessage) -> Result<Signature, Error> {
+        self.current().sign(message)
+    }
+
+    fn network_extension(&self) -> Option<Arc<NetworkExtension>> {
+        self.current().network_extension()
+    }
+
+    /// The extensions of the future engines are registered from the start,
+    /// so that they are connected to the peers before the transition.
+    fn network_extensions(&self) -> Vec<Arc<NetworkExtension>> {
+        self.engines.iter().flat_map(|(_, engine)| engine.network_extensions()).collect()
+    }
+
+    fn action_handlers(&self) -> Vec<Arc<ActionHandler>> {
+        self.engines.iter().flat_map(|(_, engine)| engine.action_handlers()).collect()
+    }
+
+    fn score_to_target(&self, score: &U256) -> U256 {
+        self.current().score_to_target(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::scheme::CommonParams;
+    use super::super::{NullEngine, Solo};
+    use super::*;
+
+    fn machine() -> CodeChainMachine {
+        CodeChainMachine::new(CommonParams::default())
+    }
+
+    fn solo() -> Arc<CodeChainEngine> {
+        Arc::new(Solo::new(Default::default(), machine()))
+    }
+
+    fn null_engine() -> Arc<CodeChainEngine> {
+        Arc::new(NullEngine::new(Default::default(), machine()))
+    }
+
+    fn header(number: BlockNumber) -> Header {
+        let mut header = Header::default();
+        header.set_number(number);
+        header
+    }
+
+    #[test]
+    fn rejects_invalid_transitions() {
+        assert!(Multi::new(vec![], machine()).is_none());
+        assert!(Multi::new(vec![(1, solo())], machine()).is_none());
+        assert!(Multi::new(vec![(0, solo()), (5, null_engine()), (5, solo())], machine()).is_none());
+        assert!(Multi::new(vec![(0, solo()), (5, null_engine())], machine()).is_some());
+    }
+
+    #[test]
+    fn selects_engine_by_block_number() {
+        let multi = Multi::new(vec![(0, solo()), (10, null_engine())], machine()).unwrap();
+
+        assert_eq!("Solo", multi.engine_of(&header(0)).name());
+        assert_eq!("Solo", multi.engine_of(&header(9)).name());
+        assert_eq!("NullEngine", multi.engine_of(&header(10)).name());
+        assert_eq!("NullEngine", multi.engine_of(&header(100)).name());
+        // Without a client, the next block is the genesis block.
+        assert_eq!("Solo", multi.name());
+    }
+}
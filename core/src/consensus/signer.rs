@@ -16,16 +16,61 @@
 
 use std::sync::Arc;
 
+use ccrypto::blake256;
 use ckey::{Address, Password, Signature};
-use primitives::H256;
+use primitives::{Bytes, H256};
 
 use super::super::account_provider::{AccountProvider, SignError};
+use super::super::header::{Header, Seal};
+use super::super::miner::RemoteSigner;
+
+/// A message signed by the engine signer.
+/// The remote signer receives the message itself, not only its hash, so that it can check what it signs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignableMessage {
+    /// RLP of a header without the seal.
+    Header(Bytes),
+    /// RLP of the information of a consensus vote.
+    Vote(Bytes),
+    /// RLP of a sealing lease.
+    Lease(Bytes),
+}
+
+impl SignableMessage {
+    pub fn header(header: &Header) -> Self {
+        SignableMessage::Header(header.rlp(Seal::Without))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SignableMessage::Header(_) => "header",
+            SignableMessage::Vote(_) => "vote",
+            SignableMessage::Lease(_) => "lease",
+        }
+    }
+
+    pub fn rlp(&self) -> &[u8] {
+        match self {
+            SignableMessage::Header(rlp) | SignableMessage::Vote(rlp) | SignableMessage::Lease(rlp) => rlp,
+        }
+    }
+
+    /// The hash which is signed.
+    pub fn hash(&self) -> H256 {
+        blake256(self.rlp())
+    }
+}
 
 /// Everything that an Engine needs to sign messages.
+///
+/// Clone it out of the lock before signing; the remote signer may take a while.
+#[derive(Clone)]
 pub struct EngineSigner {
     account_provider: Arc<AccountProvider>,
     address: Option<Address>,
     password: Option<Password>,
+    /// Signs instead of the account provider if it exists.
+    remote: Option<Arc<RemoteSigner>>,
 }
 
 impl Default for EngineSigner {
@@ -34,6 +79,7 @@ impl Default for EngineSigner {
             account_provider: AccountProvider::transient_provider(),
             address: Default::default(),
             password: Default::default(),
+            remote: None,
         }
     }
 }
@@ -44,12 +90,25 @@ impl EngineSigner {
         self.account_provider = ap;
         self.address = Some(address);
         self.password = password;
+        self.remote = None;
         cdebug!(ENGINE, "Setting Engine signer to {}", address);
     }
 
-    /// Sign a consensus message hash.
-    pub fn sign(&self, hash: H256) -> Result<Signature, SignError> {
-        self.account_provider.sign(self.address.unwrap_or_else(Default::default), self.password.clone(), hash)
+    /// Set up the signer to sign with the key kept by the remote signer.
+    pub fn set_remote(&mut self, remote: Arc<RemoteSigner>) {
+        let address = remote.address();
+        self.address = Some(address);
+        self.password = None;
+        self.remote = Some(remote);
+        cdebug!(ENGINE, "Setting Engine signer to {} signing remotely", address);
+    }
+
+    /// Sign a consensus message.
+    pub fn sign(&self, message: &SignableMessage) -> Result<Signature, SignError> {
+        if let Some(remote) = &self.remote {
+            return remote.sign(message)
+        }
+        self.account_provider.sign(self.address.unwrap_or_else(Default::default), self.password.clone(), message.hash())
     }
 
     /// Signing address.
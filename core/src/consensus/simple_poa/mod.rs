@@ -21,7 +21,7 @@ use std::sync::{Arc, Weak};
 use ckey::{public_to_address, recover, Address, Password, Signature};
 use ctypes::machine::WithBalances;
 use parking_lot::RwLock;
-use primitives::{H256, U256};
+use primitives::U256;
 
 use self::params::SimplePoAParams;
 use super::super::account_provider::AccountProvider;
@@ -31,7 +31,8 @@ use super::super::codechain_machine::CodeChainMachine;
 use super::super::consensus::EngineType;
 use super::super::error::{BlockError, Error};
 use super::super::header::Header;
-use super::signer::EngineSigner;
+use super::super::miner::RemoteSigner;
+use super::signer::{EngineSigner, SignableMessage};
 use super::validator_set::validator_list::ValidatorList;
 use super::validator_set::ValidatorSet;
 use super::{ConsensusEngine, ConstructedVerifier, EngineError, Seal};
@@ -111,7 +112,7 @@ impl ConsensusEngine<CodeChainMachine> for SimplePoA {
         let author = header.author();
         if self.validators.contains(header.parent_hash(), author) {
             // account should be permanently unlocked, otherwise sealing will fail
-            if let Ok(signature) = self.sign(header.bare_hash()) {
+            if let Ok(signature) = self.sign(SignableMessage::header(header)) {
                 return Seal::Regular(vec![::rlp::encode(&signature).into_vec()])
             } else {
                 ctrace!(ENGINE, "generate_seal: FAIL: accounts secret key unavailable");
@@ -128,6 +129,10 @@ impl ConsensusEngine<CodeChainMachine> for SimplePoA {
         verify_external(header, &*self.validators)
     }
 
+    fn verifies_seal_with_state(&self) -> bool {
+        self.validators.reads_state()
+    }
+
     fn genesis_epoch_data(&self, header: &Header) -> Result<Vec<u8>, String> {
         self.validators.genesis_epoch_data(header)
     }
@@ -186,13 +191,22 @@ impl ConsensusEngine<CodeChainMachine> for SimplePoA {
         self.validators.register_client(client);
     }
 
+    fn validators(&self, header: &Header) -> Option<Vec<Address>> {
+        self.validators.validators(&header.hash())
+    }
+
     /// Register an account which signs consensus messages.
     fn set_signer(&self, ap: Arc<AccountProvider>, address: Address, password: Option<Password>) {
         self.signer.write().set(ap, address, password);
     }
 
-    fn sign(&self, hash: H256) -> Result<Signature, Error> {
-        self.signer.read().sign(hash).map_err(Into::into)
+    fn set_remote_signer(&self, remote: Arc<RemoteSigner>) {
+        self.signer.write().set_remote(remote);
+    }
+
+    fn sign(&self, message: SignableMessage) -> Result<Signature, Error> {
+        let signer = self.signer.read().clone();
+        signer.sign(&message).map_err(Into::into)
     }
 }
 
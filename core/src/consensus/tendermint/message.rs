@@ -152,15 +152,15 @@ impl ConsensusMessage {
 }
 
 /// Header consensus view.
-pub fn consensus_view(header: &Header) -> Result<View, ::rlp::DecoderError> {
-    let view_rlp = header.seal().get(0).expect("seal passed basic verification; seal has 3 fields; qed");
+pub fn consensus_view(header: &Header) -> Result<View, DecoderError> {
+    let view_rlp = header.seal().get(0).ok_or(DecoderError::RlpIsTooShort)?;
     UntrustedRlp::new(view_rlp.as_slice()).as_val()
 }
 
 /// Proposal signature.
-pub fn proposal_signature(header: &Header) -> Result<Signature, ::rlp::DecoderError> {
-    UntrustedRlp::new(header.seal().get(1).expect("seal passed basic verification; seal has 3 fields; qed").as_slice())
-        .as_val()
+pub fn proposal_signature(header: &Header) -> Result<Signature, DecoderError> {
+    let signature_rlp = header.seal().get(1).ok_or(DecoderError::RlpIsTooShort)?;
+    UntrustedRlp::new(signature_rlp.as_slice()).as_val()
 }
 
 impl Message for ConsensusMessage {
@@ -25,6 +25,7 @@ use std::sync::{Arc, Weak};
 use ccrypto::blake256;
 use ckey::{public_to_address, recover, Address, Message, Password, Signature};
 use cnetwork::{Api, NetworkExtension, NodeId, TimerToken};
+use cstate::ActionHandler;
 use ctypes::machine::WithBalances;
 use ctypes::util::unexpected::{Mismatch, OutOfBounds};
 use ctypes::BlockNumber;
@@ -43,7 +44,12 @@ use super::super::codechain_machine::CodeChainMachine;
 use super::super::consensus::EngineType;
 use super::super::error::{BlockError, Error};
 use super::super::header::Header;
-use super::signer::EngineSigner;
+use super::super::miner::RemoteSigner;
+use super::epoch::{combine_proofs, destructure_proofs};
+use super::fork_choice::{self, ForkChoice, ForkHead};
+use super::signer::{EngineSigner, SignableMessage};
+use super::validator_set::key_rotation::KeyRotationHandler;
+use super::validator_set::validator_change::ValidatorChangeHandler;
 use super::validator_set::validator_list::ValidatorList;
 use super::validator_set::ValidatorSet;
 use super::vote_collector::VoteCollector;
@@ -174,6 +180,10 @@ pub struct Tendermint {
     block_reward: U256,
     /// Network extension,
     extension: Arc<TendermintExtension>,
+    /// Records the key rotations of the validators.
+    key_rotation_handler: Arc<KeyRotationHandler>,
+    /// Records the changes of the validators if they are changeable.
+    validator_change_handler: Option<Arc<ValidatorChangeHandler>>,
     /// codechain machine descriptor
     machine: CodeChainMachine,
 }
@@ -197,6 +207,8 @@ impl Tendermint {
             validators: our_params.validators,
             block_reward: our_params.block_reward,
             extension: Arc::new(extension),
+            key_rotation_handler: our_params.key_rotation_handler,
+            validator_change_handler: our_params.validator_change_handler,
             machine,
         });
         engine.extension.register_tendermint(Arc::downgrade(&engine));
@@ -214,7 +226,7 @@ impl Tendermint {
     /// Check if address is a proposer for given view.
     fn check_view_proposer(&self, bh: &H256, height: Height, view: View, address: &Address) -> Result<(), EngineError> {
         let proposer = self.view_proposer(bh, height, view);
-        if proposer == *address {
+        if self.validators.validator_of(bh, address) == Some(proposer) {
             Ok(())
         } else {
             Err(EngineError::NotProposer(Mismatch {
@@ -228,7 +240,10 @@ impl Tendermint {
     fn is_signer_proposer(&self, bh: &H256) -> bool {
         let proposer =
             self.view_proposer(bh, self.height.load(AtomicOrdering::SeqCst), self.view.load(AtomicOrdering::SeqCst));
-        self.signer.read().is_address(&proposer)
+        match self.signer.read().address() {
+            Some(signer) => self.validators.validator_of(bh, &signer) == Some(proposer),
+            None => false,
+        }
     }
 
     fn is_view(&self, message: &ConsensusMessage) -> bool {
@@ -248,8 +263,10 @@ impl Tendermint {
     }
 
     fn check_above_threshold(&self, n: usize) -> Result<(), EngineError> {
-        let threshold = self.validators.count(&*self.proposal_parent.read()) * 2 / 3;
-        if n > threshold {
+        let count = self.validators.count(&*self.proposal_parent.read());
+        let threshold = count * 2 / 3;
+        // No vote is enough if the validators are unknown.
+        if count > 0 && n > threshold {
             Ok(())
         } else {
             Err(EngineError::BadSealFieldSize(OutOfBounds {
@@ -370,7 +387,8 @@ impl Tendermint {
         let r = self.view.load(AtomicOrdering::SeqCst);
         let s = *self.step.read();
         let vote_info = message_info_rlp(&VoteStep::new(h, r, s), block_hash);
-        match (self.signer.read().address(), self.sign(blake256(&vote_info))) {
+        let validator = self.signer.read().address();
+        match (validator, self.sign(SignableMessage::Vote(vote_info.clone()))) {
             (Some(validator), Ok(signature)) => {
                 let message_rlp = message_full_rlp(&signature, &vote_info);
                 let message = ConsensusMessage::new(signature, h, r, s, block_hash);
@@ -463,6 +481,30 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
         EngineType::InternalSealing
     }
 
+    /// A block is committed by the precommits of the validators before it is imported.
+    fn finality_depth(&self) -> u64 {
+        0
+    }
+
+    /// The precommits in the seal of a committed block. Proposals have no precommits yet.
+    fn finality_proof(&self, header: &Header) -> Option<Bytes> {
+        let precommits = header.seal().get(2).cloned()?;
+        // An empty list rlp.
+        if precommits.len() == 1 {
+            return None
+        }
+        Some(precommits)
+    }
+
+    fn is_finality_absolute(&self) -> bool {
+        true
+    }
+
+    /// A committed block can't be reverted by a fork with a higher score.
+    fn fork_choice(&self, new: &ForkHead, current: &ForkHead) -> ForkChoice {
+        fork_choice::by_finality(new, current)
+    }
+
     /// Attempt to seal generate a proposal seal.
     ///
     /// This operation is synchronous and may (quite reasonably) not be available, in which case
@@ -481,7 +523,7 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
         let view = self.view.load(AtomicOrdering::SeqCst);
         let bh = Some(header.bare_hash());
         let vote_info = message_info_rlp(&VoteStep::new(height, view, Step::Propose), bh.clone());
-        if let Ok(signature) = self.sign(blake256(&vote_info)) {
+        if let Ok(signature) = self.sign(SignableMessage::Vote(vote_info)) {
             // Insert Propose vote.
             cdebug!(ENGINE, "Submitting proposal {} at height {} view {}.", header.bare_hash(), height, view);
             let sender = self.signer.read().address().expect("seals_internally already returned true");
@@ -551,11 +593,13 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
                     Some(a) => a,
                     None => public_to_address(&recover(&precommit.signature.into(), &precommit_hash)?),
                 };
-                if !self.validators.contains(header.parent_hash(), &address) {
-                    return Err(EngineError::NotAuthorized(address.to_owned()).into())
-                }
+                // A validator which is rotating its key may sign with either key.
+                let validator = match self.validators.validator_of(header.parent_hash(), &address) {
+                    Some(validator) => validator,
+                    None => return Err(EngineError::NotAuthorized(address.to_owned()).into()),
+                };
 
-                if !origins.insert(address) {
+                if !origins.insert(validator) {
                     cwarn!(ENGINE, "verify_block_unordered: Duplicate signature from {} on the seal.", address);
                     return Err(BlockError::InvalidSeal.into())
                 }
@@ -565,6 +609,10 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
         }
     }
 
+    fn verifies_seal_with_state(&self) -> bool {
+        self.validators.reads_state()
+    }
+
     fn on_new_block(&self, block: &mut ExecutedBlock, epoch_begin: bool) -> Result<(), Error> {
         if !epoch_begin {
             return Ok(())
@@ -588,7 +636,7 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
             EngineError::MalformedMessage(format!("{:?}", x))
         }
 
-        let rlp = UntrustedRlp::new(rlp);
+        let rlp = UntrustedRlp::new_checked(rlp).map_err(fmt_err)?;
         let message: ConsensusMessage = rlp.as_val().map_err(fmt_err)?;
         if !self.votes.is_old_or_known(&message) {
             let msg_hash = blake256(rlp.at(1).map_err(fmt_err)?.as_raw());
@@ -662,6 +710,10 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
         self.validators.register_client(client);
     }
 
+    fn validators(&self, header: &Header) -> Option<Vec<Address>> {
+        self.validators.validators(&header.hash())
+    }
+
     fn signals_epoch_end(&self, header: &Header) -> EpochChange {
         let first = header.number() == 0;
         self.validators.signals_epoch_end(first, header)
@@ -690,7 +742,7 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
     fn epoch_verifier<'a>(&self, _header: &Header, proof: &'a [u8]) -> ConstructedVerifier<'a, CodeChainMachine> {
         let (signal_number, set_proof, finality_proof) = match destructure_proofs(proof) {
             Ok(x) => x,
-            Err(e) => return ConstructedVerifier::Err(e),
+            Err(e) => return ConstructedVerifier::Err(e.into()),
         };
 
         let first = signal_number == 0;
@@ -713,10 +765,15 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
     }
 
     fn populate_from_parent(&self, header: &mut Header, parent: &Header) {
+        // The parent of the first block after an engine transition is sealed by the previous engine.
+        let parent_view = if parent.seal().len() == self.seal_fields(parent) {
+            consensus_view(parent).expect("Header has been verified; qed")
+        } else {
+            0
+        };
         // Chain scoring: total weight is sqrt(U256::max_value())*height - view
-        let new_score = U256::from(U128::max_value())
-            + consensus_view(parent).expect("Header has been verified; qed").into()
-            - self.view.load(AtomicOrdering::SeqCst).into();
+        let new_score =
+            U256::from(U128::max_value()) + parent_view.into() - self.view.load(AtomicOrdering::SeqCst).into();
 
         header.set_score(new_score);
     }
@@ -728,8 +785,16 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
         self.to_step(Step::Propose);
     }
 
-    fn sign(&self, hash: H256) -> Result<Signature, Error> {
-        self.signer.read().sign(hash).map_err(Into::into)
+    fn set_remote_signer(&self, remote: Arc<RemoteSigner>) {
+        {
+            self.signer.write().set_remote(remote);
+        }
+        self.to_step(Step::Propose);
+    }
+
+    fn sign(&self, message: SignableMessage) -> Result<Signature, Error> {
+        let signer = self.signer.read().clone();
+        signer.sign(&message).map_err(Into::into)
     }
 
     fn stop(&self) {}
@@ -763,6 +828,14 @@ impl ConsensusEngine<CodeChainMachine> for Tendermint {
     fn network_extension(&self) -> Option<Arc<NetworkExtension>> {
         Some(Arc::clone(&self.extension) as Arc<NetworkExtension>)
     }
+
+    fn action_handlers(&self) -> Vec<Arc<ActionHandler>> {
+        let mut handlers = vec![Arc::clone(&self.key_rotation_handler) as Arc<ActionHandler>];
+        if let Some(handler) = &self.validator_change_handler {
+            handlers.push(Arc::clone(handler) as Arc<ActionHandler>);
+        }
+        handlers
+    }
 }
 
 struct EpochVerifier<F>
@@ -805,22 +878,11 @@ where
     }
 
     fn check_finality_proof(&self, proof: &[u8]) -> Option<Vec<H256>> {
-        let header: Header = ::rlp::decode(proof);
+        let header: Header = UntrustedRlp::new_checked(proof).and_then(|rlp| rlp.as_val()).ok()?;
         self.verify_light(&header).ok().map(|_| vec![header.hash()])
     }
 }
 
-fn combine_proofs(signal_number: BlockNumber, set_proof: &[u8], finality_proof: &[u8]) -> Vec<u8> {
-    let mut stream = ::rlp::RlpStream::new_list(3);
-    stream.append(&signal_number).append(&set_proof).append(&finality_proof);
-    stream.out()
-}
-
-fn destructure_proofs(combined: &[u8]) -> Result<(BlockNumber, &[u8], &[u8]), Error> {
-    let rlp = UntrustedRlp::new(combined);
-    Ok((rlp.at(0)?.as_val()?, rlp.at(1)?.data()?, rlp.at(2)?.data()?))
-}
-
 /// Timeouts lookup
 pub trait Timeouts<S: Sync + Send + Clone>: Send + Sync {
     /// Return the first timeout.
@@ -840,6 +902,10 @@ struct TendermintExtension {
 
 const MIN_PEERS_PROPAGATION: usize = 4;
 const MAX_PEERS_PROPAGATION: usize = 128;
+/// A proposal carries the whole block.
+const MAX_MESSAGE_SIZE: usize = 8 * 1024 * 1024;
+/// The messages to a peer which is disconnected shortly are sent when it connects again within this many seconds.
+const PENDING_MESSAGE_TTL: i64 = 30;
 
 impl TendermintExtension {
     fn new(timeouts: TendermintTimeouts) -> Self {
@@ -871,7 +937,9 @@ impl TendermintExtension {
         let message = TendermintMessage::ConsensusMessage(message).rlp_bytes().into_vec();
         self.api.lock().as_ref().map(|api| {
             for token in tokens {
-                api.send(&token, &message);
+                if let Err(err) = api.send(&token, &message) {
+                    cdebug!(ENGINE, "Cannot send a consensus message to {}: {:?}", token, err);
+                }
             }
         });
     }
@@ -880,7 +948,9 @@ impl TendermintExtension {
         let message = TendermintMessage::ProposalBlock(message).rlp_bytes().into_vec();
         self.api.lock().as_ref().map(|api| {
             for token in self.peers.read().iter() {
-                api.send(&token, &message);
+                if let Err(err) = api.send(&token, &message) {
+                    cdebug!(ENGINE, "Cannot send a proposal block to {}: {:?}", token, err);
+                }
             }
         });
     }
@@ -910,6 +980,14 @@ impl NetworkExtension for TendermintExtension {
         &VERSIONS
     }
 
+    fn max_message_size(&self) -> usize {
+        MAX_MESSAGE_SIZE
+    }
+
+    fn pending_message_ttl(&self) -> Option<Duration> {
+        Some(Duration::seconds(PENDING_MESSAGE_TTL))
+    }
+
     fn on_initialize(&self, api: Arc<Api>) {
         let initial = self.timeouts.initial();
         ctrace!(ENGINE, "Setting the initial timeout to {}.", initial);
@@ -926,8 +1004,7 @@ impl NetworkExtension for TendermintExtension {
     }
 
     fn on_message(&self, token: &NodeId, data: &[u8]) {
-        let m = UntrustedRlp::new(data);
-        match m.as_val() {
+        match UntrustedRlp::new_checked(data).and_then(|m| m.as_val()) {
             Ok(TendermintMessage::ConsensusMessage(ref bytes)) => {
                 if let Some(ref weak) = *self.tendermint.read() {
                     if let Some(c) = weak.upgrade() {
@@ -953,7 +1030,10 @@ impl NetworkExtension for TendermintExtension {
     fn on_local_message(&self, data: &[u8]) {
         let next: Step = rlp::decode(data);
         self.api.lock().as_ref().map(|api| {
-            api.clear_timer(ENGINE_TIMEOUT_TOKEN).expect("Timer clear succeeds");
+            // The timer is already expired if the step is changed by the timeout.
+            if let Err(err) = api.clear_timer(ENGINE_TIMEOUT_TOKEN) {
+                ctrace!(ENGINE, "Cannot clear the timeout timer: {:?}", err);
+            }
             api.set_timer_once(ENGINE_TIMEOUT_TOKEN, self.timeouts.timeout(&next)).expect("Timer set succeeds");
         });
     }
@@ -14,12 +14,16 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::sync::Arc;
+
 use cjson;
 use ckey::PlatformAddress;
 use primitives::U256;
 use time::Duration;
 
-use super::super::validator_set::{new_validator_set, ValidatorSet};
+use super::super::validator_set::key_rotation::{KeyRotationHandler, DEFAULT_GRACE_PERIOD};
+use super::super::validator_set::validator_change::ValidatorChangeHandler;
+use super::super::validator_set::{new_changeable_validator_set, new_validator_set, ValidatorSet};
 use super::{Step, Timeouts};
 
 /// `Tendermint` params.
@@ -30,13 +34,24 @@ pub struct TendermintParams {
     pub timeouts: TendermintTimeouts,
     /// Reward per block in base units.
     pub block_reward: U256,
+    /// Records the key rotations of the validators.
+    pub key_rotation_handler: Arc<KeyRotationHandler>,
+    /// Records the changes of the validators if they are changeable.
+    pub validator_change_handler: Option<Arc<ValidatorChangeHandler>>,
 }
 
 impl From<cjson::scheme::TendermintParams> for TendermintParams {
     fn from(p: cjson::scheme::TendermintParams) -> Self {
         let dt = TendermintTimeouts::default();
+        let validators: Vec<_> = p.validators.into_iter().map(PlatformAddress::into_address).collect();
+        let grace_period = p.key_rotation_grace_period.map_or(DEFAULT_GRACE_PERIOD, Into::into);
+        let changeable = p.changeable_validators.unwrap_or(false);
         TendermintParams {
-            validators: new_validator_set(p.validators.into_iter().map(PlatformAddress::into_address).collect()),
+            validators: if changeable {
+                new_changeable_validator_set(validators.clone())
+            } else {
+                new_validator_set(validators.clone(), grace_period)
+            },
             timeouts: TendermintTimeouts {
                 propose: p.timeout_propose.map_or(dt.propose, to_duration),
                 prevote: p.timeout_prevote.map_or(dt.prevote, to_duration),
@@ -44,6 +59,12 @@ impl From<cjson::scheme::TendermintParams> for TendermintParams {
                 commit: p.timeout_commit.map_or(dt.commit, to_duration),
             },
             block_reward: p.block_reward.map_or(U256::default(), Into::into),
+            key_rotation_handler: Arc::new(KeyRotationHandler::new(validators.clone())),
+            validator_change_handler: if changeable {
+                Some(Arc::new(ValidatorChangeHandler::new(validators)))
+            } else {
+                None
+            },
         }
     }
 }
@@ -0,0 +1,408 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::sync::Weak;
+
+use ccrypto::blake256;
+use ckey::{verify_address, Address, Signature};
+use cmerkle::TrieMut;
+use cstate::{ActionHandler, StateResult, TopLevelState, TopState, TopStateInfo};
+use ctypes::invoice::ParcelInvoice;
+use ctypes::parcel::Error as ParcelError;
+use ctypes::BlockNumber;
+use parking_lot::RwLock;
+use primitives::{Bytes, H256};
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+use super::super::super::client::EngineClient;
+use super::super::super::codechain_machine::CodeChainMachine;
+use super::super::super::error::Error;
+use super::super::super::header::Header;
+use super::super::super::types::BlockId;
+use super::super::EpochChange;
+use super::validator_list::ValidatorList;
+use super::ValidatorSet;
+
+const ACTION_ID: u8 = 1;
+
+/// The number of blocks for which both keys are accepted after a rotation takes effect.
+pub const DEFAULT_GRACE_PERIOD: BlockNumber = 100;
+
+/// Custom action which schedules the rotation of a validator's sealing key.
+///
+/// It must be signed by the current key of the validator. A rotation which is not activated yet is replaced by the
+/// action, so it is signed by the key which the replaced rotation retires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyRotationAction {
+    pub validator: Address,
+    pub new_key: Address,
+    /// The first block which the new key seals.
+    pub activation: BlockNumber,
+    /// The number of the rotations recorded for the validator before this one.
+    pub seq: u64,
+    pub signature: Signature,
+}
+
+impl KeyRotationAction {
+    /// The message to be signed by the current key of the validator.
+    pub fn message(validator: &Address, new_key: &Address, activation: BlockNumber, seq: u64) -> H256 {
+        let mut s = RlpStream::new_list(5);
+        s.append(&ACTION_ID).append(validator).append(new_key).append(&activation).append(&seq);
+        blake256(s.as_raw())
+    }
+}
+
+impl Encodable for KeyRotationAction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(6)
+            .append(&ACTION_ID)
+            .append(&self.validator)
+            .append(&self.new_key)
+            .append(&self.activation)
+            .append(&self.seq)
+            .append(&self.signature);
+    }
+}
+
+impl Decodable for KeyRotationAction {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 6 {
+            return Err(DecoderError::RlpIncorrectListLen)
+        }
+        if rlp.val_at::<u8>(0)? != ACTION_ID {
+            return Err(DecoderError::Custom("Unknown message id detected"))
+        }
+        Ok(Self {
+            validator: rlp.val_at(1)?,
+            new_key: rlp.val_at(2)?,
+            activation: rlp.val_at(3)?,
+            seq: rlp.val_at(4)?,
+            signature: rlp.val_at(5)?,
+        })
+    }
+}
+
+/// The latest rotation of a validator's key, recorded in the state.
+#[derive(Debug, Clone, PartialEq, RlpEncodable, RlpDecodable)]
+pub struct KeyRotation {
+    pub previous_key: Address,
+    pub new_key: Address,
+    pub activation: BlockNumber,
+    /// The number of the rotations recorded for the validator, including this one.
+    pub seq: u64,
+}
+
+impl KeyRotation {
+    /// Keys which seal the block of `number` on behalf of the validator.
+    fn keys_at(&self, number: BlockNumber, grace_period: BlockNumber) -> Vec<Address> {
+        if number < self.activation {
+            vec![self.previous_key]
+        } else if number < self.activation + grace_period {
+            vec![self.previous_key, self.new_key]
+        } else {
+            vec![self.new_key]
+        }
+    }
+}
+
+fn rotation_address(validator: &Address) -> H256 {
+    let mut bytes = b"validator key rotation".to_vec();
+    bytes.extend_from_slice(&**validator);
+    let mut hash = blake256(&bytes);
+    hash[0] = b'M';
+    hash
+}
+
+/// Reads the latest key rotation of the validator.
+pub fn key_rotation(state: &TopStateInfo, validator: &Address) -> StateResult<Option<KeyRotation>> {
+    let data = state.action_data(&rotation_address(validator))?;
+    Ok(UntrustedRlp::new(&data).as_val().ok())
+}
+
+/// Records `KeyRotationAction`s of the validators in the state.
+pub struct KeyRotationHandler {
+    validators: Vec<Address>,
+}
+
+impl KeyRotationHandler {
+    pub fn new(validators: Vec<Address>) -> Self {
+        Self {
+            validators,
+        }
+    }
+}
+
+impl ActionHandler for KeyRotationHandler {
+    fn init(&self, _state: &mut TrieMut) -> StateResult<()> {
+        Ok(())
+    }
+
+    fn is_target(&self, bytes: &Bytes) -> bool {
+        KeyRotationAction::decode(&UntrustedRlp::new(bytes)).is_ok()
+    }
+
+    fn execute(
+        &self,
+        bytes: &Bytes,
+        state: &mut TopLevelState,
+        block_number: BlockNumber,
+    ) -> Option<StateResult<ParcelInvoice>> {
+        KeyRotationAction::decode(&UntrustedRlp::new(bytes)).ok().map(|action| {
+            if !self.validators.contains(&action.validator) {
+                return Err(ParcelError::InsufficientPermission.into())
+            }
+            let latest = key_rotation(&*state, &action.validator)?;
+            let seq = latest.as_ref().map_or(0, |rotation| rotation.seq);
+            if action.seq != seq {
+                let error = ParcelError::InvalidNonce {
+                    expected: seq.into(),
+                    got: action.seq.into(),
+                };
+                return Err(error.into())
+            }
+            if action.activation <= block_number {
+                let error = ParcelError::ActivationTooEarly {
+                    minimal: block_number + 1,
+                    got: action.activation,
+                };
+                return Err(error.into())
+            }
+            let current_key = match latest {
+                // The rotation which is not activated yet is replaced.
+                Some(ref rotation) if block_number < rotation.activation => rotation.previous_key,
+                Some(ref rotation) => rotation.new_key,
+                None => action.validator,
+            };
+            let message = KeyRotationAction::message(&action.validator, &action.new_key, action.activation, action.seq);
+            match verify_address(&current_key, &action.signature, &message) {
+                Ok(true) => {}
+                Ok(false) => return Err(ParcelError::InsufficientPermission.into()),
+                Err(err) => return Err(ParcelError::InvalidSignature(format!("{}", err)).into()),
+            }
+
+            let rotation = KeyRotation {
+                previous_key: current_key,
+                new_key: action.new_key,
+                activation: action.activation,
+                seq: seq + 1,
+            };
+            state.update_action_data(&rotation_address(&action.validator), rotation.rlp_bytes().to_vec())?;
+            Ok(ParcelInvoice::SingleSuccess)
+        })
+    }
+}
+
+/// Validator list whose validators can rotate their keys with `KeyRotationAction`.
+///
+/// The validators are identified by the addresses in the list, whichever key they seal with.
+/// Both the previous and the new keys are accepted for `grace_period` blocks from the activation.
+pub struct RotatableValidatorList {
+    validators: ValidatorList,
+    grace_period: BlockNumber,
+    client: RwLock<Option<Weak<EngineClient>>>,
+}
+
+impl RotatableValidatorList {
+    pub fn new(validators: Vec<Address>, grace_period: BlockNumber) -> Self {
+        Self {
+            validators: ValidatorList::new(validators),
+            grace_period,
+            client: RwLock::new(None),
+        }
+    }
+
+    /// Keys of each validator for the child block of `parent`.
+    fn keys(&self, parent: &H256) -> Vec<(Address, Vec<Address>)> {
+        let client = self.client.read().as_ref().and_then(Weak::upgrade);
+        let state_and_number = client.and_then(|client| {
+            let number = client.block_header(BlockId::Hash(*parent))?.number() + 1;
+            let state = client.state_info(BlockId::Hash(*parent))?;
+            Some((state, number))
+        });
+        self.validators
+            .iter()
+            .map(|validator| {
+                let rotation = state_and_number.as_ref().and_then(|(state, number)| {
+                    key_rotation(&**state, validator).ok().and_then(|rotation| rotation).map(|r| (r, *number))
+                });
+                let keys = match rotation {
+                    Some((rotation, number)) => rotation.keys_at(number, self.grace_period),
+                    None => vec![*validator],
+                };
+                (*validator, keys)
+            })
+            .collect()
+    }
+}
+
+impl ValidatorSet for RotatableValidatorList {
+    fn contains(&self, parent: &H256, address: &Address) -> bool {
+        self.validator_of(parent, address).is_some()
+    }
+
+    fn get(&self, parent: &H256, nonce: usize) -> Address {
+        self.validators.get(parent, nonce)
+    }
+
+    fn count(&self, parent: &H256) -> usize {
+        self.validators.count(parent)
+    }
+
+    fn validators(&self, parent: &H256) -> Option<Vec<Address>> {
+        self.validators.validators(parent)
+    }
+
+    fn validator_of(&self, parent: &H256, key: &Address) -> Option<Address> {
+        self.keys(parent).into_iter().find(|(_, keys)| keys.contains(key)).map(|(validator, _)| validator)
+    }
+
+    fn is_epoch_end(&self, first: bool, chain_head: &Header) -> Option<Vec<u8>> {
+        self.validators.is_epoch_end(first, chain_head)
+    }
+
+    fn signals_epoch_end(&self, first: bool, header: &Header) -> EpochChange {
+        self.validators.signals_epoch_end(first, header)
+    }
+
+    fn epoch_set(
+        &self,
+        first: bool,
+        machine: &CodeChainMachine,
+        number: BlockNumber,
+        proof: &[u8],
+    ) -> Result<(ValidatorList, Option<H256>), Error> {
+        self.validators.epoch_set(first, machine, number, proof)
+    }
+
+    fn register_client(&self, client: Weak<EngineClient>) {
+        *self.client.write() = Some(client);
+    }
+
+    fn reads_state(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{sign, Generator, KeyPair, Random};
+
+    use super::super::super::super::tests::helpers::get_temp_state_db;
+    use super::*;
+
+    fn action(signer: &KeyPair, validator: Address, new_key: Address, activation: BlockNumber, seq: u64) -> Bytes {
+        let message = KeyRotationAction::message(&validator, &new_key, activation, seq);
+        let action = KeyRotationAction {
+            validator,
+            new_key,
+            activation,
+            seq,
+            signature: sign(signer.private(), &message).unwrap(),
+        };
+        action.rlp_bytes().into_vec()
+    }
+
+    #[test]
+    fn action_rlp() {
+        let keypair = Random.generate().unwrap();
+        let new_key = Address::random();
+        let message = KeyRotationAction::message(&keypair.address(), &new_key, 10, 3);
+        let action = KeyRotationAction {
+            validator: keypair.address(),
+            new_key,
+            activation: 10,
+            seq: 3,
+            signature: sign(keypair.private(), &message).unwrap(),
+        };
+        rlp_encode_and_decode_test!(action);
+    }
+
+    #[test]
+    fn replayed_rotation_is_rejected() {
+        let validator = Random.generate().unwrap();
+        let new_key = Random.generate().unwrap();
+        let handler = KeyRotationHandler::new(vec![validator.address()]);
+        let mut state = TopLevelState::new(get_temp_state_db());
+
+        let to_new_key = action(&validator, validator.address(), new_key.address(), 10, 0);
+        assert!(handler.execute(&to_new_key, &mut state, 1).unwrap().is_ok());
+        let back = action(&new_key, validator.address(), validator.address(), 30, 1);
+        assert!(handler.execute(&back, &mut state, 20).unwrap().is_ok());
+        assert!(handler.execute(&to_new_key, &mut state, 40).unwrap().is_err());
+
+        let rotation = key_rotation(&state, &validator.address()).unwrap().unwrap();
+        assert_eq!(validator.address(), rotation.new_key);
+        assert_eq!(2, rotation.seq);
+    }
+
+    #[test]
+    fn activation_must_be_after_the_block() {
+        let validator = Random.generate().unwrap();
+        let handler = KeyRotationHandler::new(vec![validator.address()]);
+        let mut state = TopLevelState::new(get_temp_state_db());
+
+        let rotation = action(&validator, validator.address(), Address::random(), 10, 0);
+        assert!(handler.execute(&rotation, &mut state, 10).unwrap().is_err());
+        assert_eq!(None, key_rotation(&state, &validator.address()).unwrap());
+        assert!(handler.execute(&rotation, &mut state, 9).unwrap().is_ok());
+    }
+
+    #[test]
+    fn rotation_before_activation_replaces_the_pending_one() {
+        let validator = Random.generate().unwrap();
+        let pending_key = Random.generate().unwrap();
+        let new_key = Address::random();
+        let handler = KeyRotationHandler::new(vec![validator.address()]);
+        let mut state = TopLevelState::new(get_temp_state_db());
+
+        let pending = action(&validator, validator.address(), pending_key.address(), 10, 0);
+        assert!(handler.execute(&pending, &mut state, 1).unwrap().is_ok());
+        // The pending key never sealed, so it can't sign the next rotation.
+        let signed_by_pending_key = action(&pending_key, validator.address(), new_key, 20, 1);
+        assert!(handler.execute(&signed_by_pending_key, &mut state, 5).unwrap().is_err());
+        let replacing = action(&validator, validator.address(), new_key, 20, 1);
+        assert!(handler.execute(&replacing, &mut state, 5).unwrap().is_ok());
+
+        let rotation = key_rotation(&state, &validator.address()).unwrap().unwrap();
+        assert_eq!(validator.address(), rotation.previous_key);
+        assert_eq!(new_key, rotation.new_key);
+    }
+
+    #[test]
+    fn both_keys_are_accepted_in_grace_period() {
+        let previous_key = Address::random();
+        let new_key = Address::random();
+        let rotation = KeyRotation {
+            previous_key,
+            new_key,
+            activation: 10,
+            seq: 1,
+        };
+        assert_eq!(vec![previous_key], rotation.keys_at(9, 5));
+        assert_eq!(vec![previous_key, new_key], rotation.keys_at(10, 5));
+        assert_eq!(vec![previous_key, new_key], rotation.keys_at(14, 5));
+        assert_eq!(vec![new_key], rotation.keys_at(15, 5));
+    }
+
+    #[test]
+    fn validator_is_identified_by_its_address_without_client() {
+        let validator = Address::random();
+        let set = RotatableValidatorList::new(vec![validator], DEFAULT_GRACE_PERIOD);
+        assert_eq!(Some(validator), set.validator_of(&Default::default(), &validator));
+        assert_eq!(None, set.validator_of(&Default::default(), &Address::random()));
+    }
+}
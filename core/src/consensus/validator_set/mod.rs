@@ -20,6 +20,8 @@ use ckey::Address;
 use ctypes::BlockNumber;
 use primitives::{Bytes, H256};
 
+use self::key_rotation::RotatableValidatorList;
+use self::validator_change::ChangeableValidatorList;
 use self::validator_list::ValidatorList;
 use super::super::client::EngineClient;
 use super::super::codechain_machine::CodeChainMachine;
@@ -27,11 +29,20 @@ use super::super::error::Error;
 use super::super::header::Header;
 use super::EpochChange;
 
+pub mod key_rotation;
+pub mod validator_change;
 pub mod validator_list;
 
 /// Creates a validator set from validator addresses.
-pub fn new_validator_set(validators: Vec<Address>) -> Box<ValidatorSet> {
-    Box::new(ValidatorList::new(validators))
+/// The validators can rotate their keys, and both keys are accepted for `grace_period` blocks.
+pub fn new_validator_set(validators: Vec<Address>, grace_period: BlockNumber) -> Box<ValidatorSet> {
+    Box::new(RotatableValidatorList::new(validators, grace_period))
+}
+
+/// Creates a validator set which starts from the validator addresses.
+/// The validators can be replaced with `ChangeValidatorsAction`, and the change takes effect at the next block.
+pub fn new_changeable_validator_set(validators: Vec<Address>) -> Box<ValidatorSet> {
+    Box::new(ChangeableValidatorList::new(validators))
 }
 
 /// A validator set.
@@ -46,6 +57,19 @@ pub trait ValidatorSet: Send + Sync {
     /// Returns the current number of validators.
     fn count(&self, parent: &H256) -> usize;
 
+    /// Returns the validators of the child block of `parent`.
+    /// `None` if they are unknown.
+    fn validators(&self, parent: &H256) -> Option<Vec<Address>>;
+
+    /// Returns the validator on behalf of which the given key signs.
+    fn validator_of(&self, parent: &H256, key: &Address) -> Option<Address> {
+        if self.contains(parent, key) {
+            Some(*key)
+        } else {
+            None
+        }
+    }
+
     /// Signalling that a new epoch has begun.
     ///
     /// The caller provided here may not generate proofs.
@@ -97,4 +121,9 @@ pub trait ValidatorSet: Send + Sync {
     fn report_benign(&self, _validator: &Address, _set_block: BlockNumber, _block: BlockNumber) {}
     /// Allows blockchain state access.
     fn register_client(&self, _client: Weak<EngineClient>) {}
+
+    /// Whether the validators are read from the state, which a light client doesn't have.
+    fn reads_state(&self) -> bool {
+        false
+    }
 }
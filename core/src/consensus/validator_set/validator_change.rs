@@ -0,0 +1,329 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashSet;
+use std::sync::Weak;
+
+use ccrypto::blake256;
+use ckey::{public_to_address, recover, Address, Signature};
+use cmerkle::TrieMut;
+use cstate::{ActionHandler, StateResult, TopLevelState, TopState, TopStateInfo};
+use ctypes::invoice::ParcelInvoice;
+use ctypes::parcel::Error as ParcelError;
+use ctypes::BlockNumber;
+use parking_lot::RwLock;
+use primitives::{Bytes, H256};
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+use super::super::super::client::EngineClient;
+use super::super::super::codechain_machine::CodeChainMachine;
+use super::super::super::error::Error;
+use super::super::super::header::Header;
+use super::super::super::types::BlockId;
+use super::super::epoch::destructure_proofs;
+use super::super::EpochChange;
+use super::validator_list::ValidatorList;
+use super::ValidatorSet;
+
+const ACTION_ID: u8 = 2;
+
+/// Custom action which replaces the validators from the next block.
+///
+/// It must be signed by more than two thirds of the current validators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeValidatorsAction {
+    pub validators: Vec<Address>,
+    /// The number of changes made so far, which keeps the action from being replayed.
+    pub seq: u64,
+    pub signatures: Vec<Signature>,
+}
+
+impl ChangeValidatorsAction {
+    /// The message to be signed by the current validators.
+    pub fn message(validators: &[Address], seq: u64) -> H256 {
+        let mut s = RlpStream::new_list(3);
+        s.append(&ACTION_ID).append_list::<Address, _>(validators).append(&seq);
+        blake256(s.as_raw())
+    }
+}
+
+impl Encodable for ChangeValidatorsAction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4)
+            .append(&ACTION_ID)
+            .append_list::<Address, _>(&self.validators)
+            .append(&self.seq)
+            .append_list::<Signature, _>(&self.signatures);
+    }
+}
+
+impl Decodable for ChangeValidatorsAction {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen)
+        }
+        if rlp.val_at::<u8>(0)? != ACTION_ID {
+            return Err(DecoderError::Custom("Unknown message id detected"))
+        }
+        let validators: Vec<Address> = rlp.list_at(1)?;
+        if validators.is_empty() {
+            return Err(DecoderError::Custom("The validators must not be empty"))
+        }
+        Ok(Self {
+            validators,
+            seq: rlp.val_at(2)?,
+            signatures: rlp.list_at(3)?,
+        })
+    }
+}
+
+/// The latest change of the validators, recorded in the state.
+#[derive(Debug, Clone, PartialEq, RlpEncodable, RlpDecodable)]
+pub struct ValidatorChange {
+    pub validators: Vec<Address>,
+    pub seq: u64,
+}
+
+fn change_address() -> H256 {
+    let mut hash = blake256(b"validator set change");
+    hash[0] = b'M';
+    hash
+}
+
+/// Reads the latest change of the validators.
+pub fn validator_change(state: &TopStateInfo) -> StateResult<Option<ValidatorChange>> {
+    let data = state.action_data(&change_address())?;
+    Ok(UntrustedRlp::new(&data).as_val().ok())
+}
+
+/// Records `ChangeValidatorsAction`s in the state.
+pub struct ValidatorChangeHandler {
+    genesis_validators: Vec<Address>,
+}
+
+impl ValidatorChangeHandler {
+    pub fn new(genesis_validators: Vec<Address>) -> Self {
+        Self {
+            genesis_validators,
+        }
+    }
+}
+
+impl ActionHandler for ValidatorChangeHandler {
+    fn init(&self, _state: &mut TrieMut) -> StateResult<()> {
+        Ok(())
+    }
+
+    fn is_target(&self, bytes: &Bytes) -> bool {
+        ChangeValidatorsAction::decode(&UntrustedRlp::new(bytes)).is_ok()
+    }
+
+    fn execute(
+        &self,
+        bytes: &Bytes,
+        state: &mut TopLevelState,
+        _block_number: BlockNumber,
+    ) -> Option<StateResult<ParcelInvoice>> {
+        ChangeValidatorsAction::decode(&UntrustedRlp::new(bytes)).ok().map(|action| {
+            let current = validator_change(&*state)?.unwrap_or_else(|| ValidatorChange {
+                validators: self.genesis_validators.clone(),
+                seq: 0,
+            });
+            if action.seq != current.seq {
+                let error = ParcelError::InvalidNonce {
+                    expected: current.seq.into(),
+                    got: action.seq.into(),
+                };
+                return Err(error.into())
+            }
+
+            let message = ChangeValidatorsAction::message(&action.validators, action.seq);
+            let mut approvers = HashSet::new();
+            for signature in &action.signatures {
+                let signer = match recover(signature, &message) {
+                    Ok(public) => public_to_address(&public),
+                    Err(err) => return Err(ParcelError::InvalidSignature(format!("{}", err)).into()),
+                };
+                if current.validators.contains(&signer) {
+                    approvers.insert(signer);
+                }
+            }
+            if approvers.len() * 3 <= current.validators.len() * 2 {
+                return Err(ParcelError::InsufficientPermission.into())
+            }
+
+            let change = ValidatorChange {
+                validators: action.validators,
+                seq: current.seq + 1,
+            };
+            state.update_action_data(&change_address(), change.rlp_bytes().to_vec())?;
+            Ok(ParcelInvoice::SingleSuccess)
+        })
+    }
+}
+
+/// Validator set which starts from the genesis validators and is replaced by `ChangeValidatorsAction`.
+///
+/// The validators of a block are the ones recorded in the state of its parent, so a change takes effect
+/// from the block after the one including the action, which ends the epoch.
+/// If the state is pruned, they are read from the epoch transition recorded in the extras.
+pub struct ChangeableValidatorList {
+    genesis_validators: ValidatorList,
+    client: RwLock<Option<Weak<EngineClient>>>,
+}
+
+impl ChangeableValidatorList {
+    pub fn new(genesis_validators: Vec<Address>) -> Self {
+        Self {
+            genesis_validators: ValidatorList::new(genesis_validators),
+            client: RwLock::new(None),
+        }
+    }
+
+    /// Validators recorded in the state of the block, if it is known.
+    fn recorded(&self, block: BlockId) -> Option<Vec<Address>> {
+        let client = self.client.read().as_ref().and_then(Weak::upgrade)?;
+        let state = client.state_info(block)?;
+        Some(match validator_change(&*state).ok().and_then(|change| change) {
+            Some(change) => change.validators,
+            None => self.genesis_validators.to_vec(),
+        })
+    }
+
+    /// Validators of the children of the parent. `None` if neither the state nor the epoch transitions tell them.
+    fn list(&self, parent: &H256) -> Option<ValidatorList> {
+        if let Some(validators) = self.recorded(BlockId::Hash(*parent)) {
+            return Some(ValidatorList::new(validators))
+        }
+        let client = self.client.read().as_ref().and_then(Weak::upgrade)?;
+        match client.epoch_transition_for(*parent)? {
+            Some(transition) => {
+                let (_, set_proof, _) = destructure_proofs(&transition.proof).ok()?;
+                UntrustedRlp::new(set_proof).as_list().ok().map(ValidatorList::new)
+            }
+            // No change has been made before the parent.
+            None => Some(self.genesis_validators.clone()),
+        }
+    }
+}
+
+impl ValidatorSet for ChangeableValidatorList {
+    fn contains(&self, parent: &H256, address: &Address) -> bool {
+        self.list(parent).map_or(false, |list| list.contains(address))
+    }
+
+    /// The zero address, which nobody can sign for, if the validators are unknown.
+    fn get(&self, parent: &H256, nonce: usize) -> Address {
+        self.list(parent).map_or_else(Address::default, |list| list.get(parent, nonce))
+    }
+
+    /// Zero if the validators are unknown.
+    fn count(&self, parent: &H256) -> usize {
+        self.list(parent).map_or(0, |list| list.len())
+    }
+
+    fn validators(&self, parent: &H256) -> Option<Vec<Address>> {
+        self.list(parent).map(ValidatorList::into_inner)
+    }
+
+    /// The block which changes the validators ends the epoch, and the proof is the new validators.
+    fn is_epoch_end(&self, first: bool, chain_head: &Header) -> Option<Vec<u8>> {
+        if first {
+            return Some(::rlp::encode_list::<Address, _>(&*self.genesis_validators).into_vec())
+        }
+        let validators = self.recorded(BlockId::Hash(chain_head.hash()))?;
+        let parent_validators = self.recorded(BlockId::Hash(*chain_head.parent_hash()))?;
+        if validators == parent_validators {
+            return None
+        }
+        Some(::rlp::encode_list::<Address, _>(&validators).into_vec())
+    }
+
+    fn signals_epoch_end(&self, _first: bool, _header: &Header) -> EpochChange {
+        EpochChange::No
+    }
+
+    fn epoch_set(
+        &self,
+        _first: bool,
+        _machine: &CodeChainMachine,
+        _number: BlockNumber,
+        proof: &[u8],
+    ) -> Result<(ValidatorList, Option<H256>), Error> {
+        let validators: Vec<Address> = UntrustedRlp::new(proof).as_list()?;
+        Ok((ValidatorList::new(validators), None))
+    }
+
+    fn register_client(&self, client: Weak<EngineClient>) {
+        *self.client.write() = Some(client);
+    }
+
+    fn reads_state(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{sign, Generator, KeyPair, Random};
+
+    use super::*;
+
+    fn action(validators: Vec<Address>, seq: u64, signers: &[&KeyPair]) -> ChangeValidatorsAction {
+        let message = ChangeValidatorsAction::message(&validators, seq);
+        ChangeValidatorsAction {
+            validators,
+            seq,
+            signatures: signers.iter().map(|signer| sign(signer.private(), &message).unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn action_rlp() {
+        let signer = Random.generate().unwrap();
+        let action = action(vec![Address::random(), Address::random()], 3, &[&signer]);
+        rlp_encode_and_decode_test!(action);
+    }
+
+    #[test]
+    fn empty_validators_are_not_decoded() {
+        let signer = Random.generate().unwrap();
+        let action = action(vec![], 0, &[&signer]);
+        assert!(ChangeValidatorsAction::decode(&UntrustedRlp::new(&action.rlp_bytes())).is_err());
+    }
+
+    #[test]
+    fn validators_are_unknown_without_client() {
+        let validator = Address::random();
+        let set = ChangeableValidatorList::new(vec![validator]);
+        assert_eq!(None, set.validators(&Default::default()));
+        assert!(!set.contains(&Default::default(), &validator));
+        assert_eq!(0, set.count(&Default::default()));
+        assert_eq!(Address::default(), set.get(&Default::default(), 0));
+    }
+
+    #[test]
+    fn epoch_set_is_recovered_from_proof() {
+        let validators = vec![Address::random(), Address::random()];
+        let set = ChangeableValidatorList::new(vec![Address::random()]);
+        let proof = ::rlp::encode_list::<Address, _>(&validators).into_vec();
+        let machine = CodeChainMachine::new(Default::default());
+        let (list, finalize) = set.epoch_set(false, &machine, 1, &proof).unwrap();
+        assert_eq!(validators, list.into_inner());
+        assert_eq!(None, finalize);
+    }
+}
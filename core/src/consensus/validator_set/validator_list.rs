@@ -87,6 +87,10 @@ impl ValidatorSet for ValidatorList {
         self.validators.len()
     }
 
+    fn validators(&self, _bh: &H256) -> Option<Vec<Address>> {
+        Some(self.validators.clone())
+    }
+
     fn is_epoch_end(&self, first: bool, _chain_head: &Header) -> Option<Vec<u8>> {
         match first {
             true => Some(Vec::new()), // allow transition to fixed list, and instantly
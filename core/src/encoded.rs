@@ -27,7 +27,7 @@ use ccrypto::blake256;
 use ckey::Address;
 use ctypes::BlockNumber;
 use heapsize::HeapSizeOf;
-use primitives::{H256, U256};
+use primitives::{Bloom, H256, U256};
 use rlp::Rlp;
 
 use super::block::Block as FullBlock;
@@ -128,6 +128,16 @@ impl Header {
         self.view().extra_data()
     }
 
+    /// Log bloom of this block.
+    pub fn log_bloom(&self) -> Bloom {
+        self.view().log_bloom()
+    }
+
+    /// State roots of the shards after this block.
+    pub fn shard_roots(&self) -> Vec<H256> {
+        self.view().shard_roots()
+    }
+
     /// Engine-specific seal fields.
     pub fn seal(&self) -> Vec<Vec<u8>> {
         self.view().seal()
@@ -304,6 +314,16 @@ impl Block {
         self.header_view().extra_data()
     }
 
+    /// Log bloom of this block.
+    pub fn log_bloom(&self) -> Bloom {
+        self.header_view().log_bloom()
+    }
+
+    /// State roots of the shards after this block.
+    pub fn shard_roots(&self) -> Vec<H256> {
+        self.header_view().shard_roots()
+    }
+
     /// Engine-specific seal fields.
     pub fn seal(&self) -> Vec<Vec<u8>> {
         self.header_view().seal()
@@ -21,7 +21,7 @@ use ckey::{Address, Error as KeyError};
 use cstate::StateError;
 use ctypes::util::unexpected::{Mismatch, OutOfBounds};
 use ctypes::BlockNumber;
-use primitives::{H256, U256};
+use primitives::{Bloom, H256, U256};
 
 use util_error::UtilError;
 
@@ -98,6 +98,10 @@ pub enum BlockError {
     InvalidSeal,
     /// Invoices trie root header field is invalid.
     InvalidInvoicesRoot(Mismatch<H256>),
+    /// Log bloom header field is invalid.
+    InvalidLogBloom(Mismatch<Bloom>),
+    /// The hash of the shard roots header field is invalid.
+    InvalidShardRoots(Mismatch<H256>),
     /// Timestamp header field is invalid.
     InvalidTimestamp(OutOfBounds<u64>),
     /// Timestamp header field is too far in future.
@@ -115,12 +119,21 @@ pub enum BlockError {
     UnknownParent(H256),
     /// Body size limit is exceeded.
     BodySizeIsTooBig,
+    /// The block at a checkpoint number has a hash other than the checkpoint.
+    CheckpointMismatch(Mismatch<H256>),
+    /// The block forks from the canon chain below the checkpoint of the given number.
+    ForkBelowCheckpoint(BlockNumber),
+    /// The block forks from the canon chain below the finalized block of the given number.
+    ForkBelowFinalized(BlockNumber),
+    /// The block would retract more canon blocks than the maximum depth of a reorg.
+    ReorgTooDeep(OutOfBounds<u64>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SchemeError {
     InvalidCommonParams,
     InvalidState,
+    InvalidEngineTransitions,
 }
 
 impl fmt::Display for SchemeError {
@@ -129,6 +142,7 @@ impl fmt::Display for SchemeError {
         let msg: String = match self {
             InvalidCommonParams => "Common params are not matched with gensis block".into(),
             InvalidState => "Genesis state is not same with spec".into(),
+            InvalidEngineTransitions => "Engine transitions must be in ascending order from the genesis block".into(),
         };
         f.write_fmt(format_args!("Scheme file error ({})", msg))
     }
@@ -149,6 +163,8 @@ impl fmt::Display for BlockError {
             PowOutOfBounds(oob) => format!("Invalid proof of work: {}", oob),
             InvalidSeal => "Block has invalid seal.".into(),
             InvalidInvoicesRoot(mis) => format!("Invalid invoices trie root in header: {}", mis),
+            InvalidLogBloom(mis) => format!("Invalid log bloom in header: {}", mis),
+            InvalidShardRoots(mis) => format!("Invalid shard roots in header: {}", mis),
             InvalidTimestamp(oob) => format!("Invalid timestamp in header: {}", oob),
             TemporarilyInvalid(oob) => format!("Future timestamp in header: {}", oob),
             InvalidParentHash(mis) => format!("Invalid parent hash: {}", mis),
@@ -157,6 +173,10 @@ impl fmt::Display for BlockError {
             UnknownParent(hash) => format!("Unknown parent: {}", hash),
             TooManyParcels(address) => format!("Too many parcels from: {}", address),
             BodySizeIsTooBig => format!("Block's body size is too big"),
+            CheckpointMismatch(mis) => format!("Block doesn't match the checkpoint: {}", mis),
+            ForkBelowCheckpoint(number) => format!("Block forks below the checkpoint #{}", number),
+            ForkBelowFinalized(number) => format!("Block forks below the finalized block #{}", number),
+            ReorgTooDeep(oob) => format!("Block reorganizes too many blocks: {}", oob),
         };
 
         f.write_fmt(format_args!("Block error ({})", msg))
@@ -22,7 +22,7 @@ use ccrypto::{blake256, BLAKE_NULL_RLP};
 use ckey::Address;
 use ctypes::BlockNumber;
 use heapsize::HeapSizeOf;
-use primitives::{Bytes, H256, U256};
+use primitives::{Bloom, Bytes, H256, U256};
 use rlp::*;
 
 /// Semantic boolean for when a seal/signature is included.
@@ -54,6 +54,10 @@ pub struct Header {
     state_root: H256,
     /// Block invoices root.
     invoices_root: H256,
+    /// Block log bloom.
+    log_bloom: Bloom,
+    /// The state roots of the shards, in the order of the shard ids.
+    shard_roots: Vec<H256>,
 
     /// Block score.
     score: U256,
@@ -79,6 +83,8 @@ impl Default for Header {
             parcels_root: BLAKE_NULL_RLP,
             state_root: BLAKE_NULL_RLP,
             invoices_root: BLAKE_NULL_RLP,
+            log_bloom: Bloom::default(),
+            shard_roots: vec![],
 
             score: U256::default(),
             seal: vec![],
@@ -133,6 +139,18 @@ impl Header {
     pub fn parcels_root(&self) -> &H256 {
         &self.parcels_root
     }
+    /// Get the log bloom field of the header.
+    pub fn log_bloom(&self) -> &Bloom {
+        &self.log_bloom
+    }
+    /// Get the shard roots field of the header.
+    pub fn shard_roots(&self) -> &[H256] {
+        &self.shard_roots
+    }
+    /// Get the hash of the shard roots field of the header.
+    pub fn shard_roots_hash(&self) -> H256 {
+        blake256(encode_list(&self.shard_roots))
+    }
 
     /// Get the score field of the header.
     pub fn score(&self) -> &U256 {
@@ -193,6 +211,16 @@ impl Header {
         self.invoices_root = a;
         self.note_dirty()
     }
+    /// Set the log bloom field of the header.
+    pub fn set_log_bloom(&mut self, a: Bloom) {
+        self.log_bloom = a;
+        self.note_dirty()
+    }
+    /// Set the shard roots field of the header.
+    pub fn set_shard_roots(&mut self, a: Vec<H256>) {
+        self.shard_roots = a;
+        self.note_dirty()
+    }
 
     /// Set the score field of the header.
     pub fn set_score(&mut self, a: U256) {
@@ -234,7 +262,7 @@ impl Header {
     /// Place this header into an RLP stream `s`, optionally `with_seal`.
     pub fn stream_rlp(&self, s: &mut RlpStream, with_seal: Seal) {
         s.begin_list(
-            9 + match with_seal {
+            11 + match with_seal {
                 Seal::With => self.seal.len(),
                 _ => 0,
             },
@@ -248,6 +276,8 @@ impl Header {
         s.append(&self.number);
         s.append(&self.timestamp);
         s.append(&self.extra_data);
+        s.append(&self.log_bloom);
+        s.append_list(&self.shard_roots);
         if let Seal::With = with_seal {
             for b in &self.seal {
                 s.append_raw(b, 1);
@@ -292,12 +322,14 @@ impl Decodable for Header {
             number: r.val_at(6)?,
             timestamp: cmp::min(r.val_at::<U256>(7)?, u64::max_value().into()).as_u64(),
             extra_data: r.val_at(8)?,
+            log_bloom: r.val_at(9)?,
+            shard_roots: r.list_at(10)?,
             seal: vec![],
             hash: RefCell::new(Some(blake256(r.as_raw()))),
             bare_hash: RefCell::new(None),
         };
 
-        for i in 9..r.item_count()? {
+        for i in 11..r.item_count()? {
             blockheader.seal.push(r.at(i)?.as_raw().to_vec())
         }
 
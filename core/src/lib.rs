@@ -40,6 +40,7 @@ extern crate multimap;
 extern crate num_cpus;
 extern crate primitives;
 extern crate rand;
+extern crate rayon;
 #[cfg_attr(test, macro_use)]
 extern crate rlp;
 extern crate rlp_compress;
@@ -64,11 +65,14 @@ mod db;
 pub mod encoded;
 mod error;
 mod header;
+mod migration;
 mod miner;
 mod parcel;
+mod proof;
 mod scheme;
 mod service;
 mod shard_validator;
+mod signer_cache;
 mod types;
 mod verification;
 mod views;
@@ -78,18 +82,25 @@ mod tests;
 
 pub use account_provider::{AccountProvider, SignError as AccountProviderError};
 pub use block::Block;
+pub use blockchain::{BlockChainCacheStats, CacheStats};
 pub use client::{
-    AssetClient, Balance, BlockChainClient, BlockInfo, ChainInfo, ChainNotify, Client, DatabaseClient, EngineClient,
-    EngineInfo, ExecuteClient, ImportBlock, MiningBlockChainClient, Nonce, RegularKey, RegularKeyOwner, Shard,
-    TestBlockChainClient,
+    AssetClient, Balance, BlockChainClient, BlockInfo, ChainInfo, ChainNotify, ChainStatistics, ChainStats, Client,
+    ClientConfig, DatabaseBackend, DatabaseClient, EngineClient, EngineInfo, ExecuteClient, ImportBlock, LightClient,
+    MiningBlockChainClient, Nonce, RegularKey, RegularKeyOwner, RevertChain, Shard, StateInfo, TestBlockChainClient,
+    TraceClient, DEFAULT_CHAIN_STATS_WINDOW,
 };
-pub use consensus::EngineType;
-pub use db::COL_STATE;
-pub use error::{BlockImportError, Error, ImportError};
+pub use consensus::{ChangeValidatorsAction, EngineType, KeyRotation, KeyRotationAction, ValidatorChange};
+pub use db::{COL_HEADERS, COL_STATE};
+pub use error::{BlockError, BlockImportError, Error, ImportError};
 pub use header::{Header, Seal};
-pub use miner::{Miner, MinerOptions, MinerService, Stratum, StratumConfig, StratumError};
-pub use parcel::{LocalizedParcel, SignedParcel, UnverifiedParcel};
+pub use migration::{database_version, migrate_database, DB_VERSION};
+pub use miner::{
+    DropReason, Miner, MinerOptions, MinerService, ParcelPoolStatus, ParcelTimestamps, RemoteSigner, ResealStatus,
+    SealingLeaseExtension, SealingRole, Stratum, StratumConfig, StratumError,
+};
+pub use parcel::{multisig_public, LocalizedParcel, MultiSig, ParcelSignature, SignedParcel, UnverifiedParcel};
+pub use proof::verify_account_proof;
 pub use scheme::Scheme;
-pub use service::ClientService;
+pub use service::{open_database, ClientService};
 pub use shard_validator::{ShardValidator, ShardValidatorClient, ShardValidatorConfig};
-pub use types::{BlockId, ParcelId};
+pub use types::{parcel_topics, BlockId, BlockStatus, Filter, LocalizedLog, ParcelId, SyncProgress};
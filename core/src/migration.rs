@@ -0,0 +1,529 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashMap;
+
+use ckey::{public_to_address, Address};
+use ctypes::parcel::Action;
+use kvdb::{self, DBTransaction, KeyValueDB};
+use primitives::{Bloom, H256};
+use rlp::{self, UntrustedRlp};
+use rlp_compress::{blocks_swapper, decompress};
+
+use super::blockchain::{
+    sender_parcel_key, ParcelAddress, TransactionAddress, TransactionAddresses, SENDER_PARCEL_KEY_PREFIX,
+    TRANSACTION_ADDRESS_KEY_PREFIX,
+};
+use super::client::Error as ClientError;
+use super::db::{Readable, COL_BODIES, COL_EXTRA, COL_HEADERS};
+use super::encoded;
+use super::parcel::SignedParcel;
+
+/// The version of the database schema written by this binary.
+///
+/// Bump it together with a new `Migration` whenever the layout of the columns
+/// or the encoding of the stored values changes.
+pub const DB_VERSION: u32 = 5;
+
+const DB_VERSION_KEY: &[u8] = b"db-version";
+
+/// Number of the values rewritten in a batch by a migration.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// A step which upgrades a database from `version() - 1` to `version()`.
+///
+/// A migration may write in several batches, but it must be safe to run again
+/// because the version is stamped only after it finishes.
+trait Migration {
+    fn version(&self) -> u32;
+
+    fn description(&self) -> &'static str;
+
+    fn migrate(&self, db: &KeyValueDB) -> kvdb::Result<()>;
+}
+
+/// Marks a database created before the schema was versioned.
+///
+/// Such a database may have been written by any binary before the versioning, so it doesn't have one fixed
+/// layout. The migrations after this one detect the layout of each value they upgrade, and leave the values
+/// already in the new layout as they are.
+struct StampVersion;
+
+impl Migration for StampVersion {
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn description(&self) -> &'static str {
+        "stamp the schema version"
+    }
+
+    fn migrate(&self, _db: &KeyValueDB) -> kvdb::Result<()> {
+        Ok(())
+    }
+}
+
+/// Replaces the address of a transaction with the list of every address of it.
+///
+/// The old value is the list of the parcel address and the index, while the new one is a list of one item, which
+/// is the list of the addresses. So the values already converted are told apart by their item count.
+struct TransactionAddressList;
+
+impl Migration for TransactionAddressList {
+    fn version(&self) -> u32 {
+        2
+    }
+
+    fn description(&self) -> &'static str {
+        "keep every location of a transaction"
+    }
+
+    fn migrate(&self, db: &KeyValueDB) -> kvdb::Result<()> {
+        let prefix = [TRANSACTION_ADDRESS_KEY_PREFIX];
+        let mut batch = DBTransaction::new();
+        let mut count = 0;
+        for (key, value) in db.iter_from_prefix(COL_EXTRA, &prefix).take_while(|(key, _)| key.starts_with(&prefix)) {
+            // The prefix and the hash of the transaction
+            if key.len() != 33 {
+                continue
+            }
+            let rlp = UntrustedRlp::new(&value);
+            if rlp.item_count().map_err(|e| format!("Invalid transaction address: {}", e))? != 2 {
+                continue
+            }
+            let address: TransactionAddress =
+                rlp.as_val().map_err(|e| format!("Invalid transaction address: {}", e))?;
+            let addresses = TransactionAddresses {
+                addresses: vec![address],
+            };
+            batch.put(COL_EXTRA, &key, &rlp::encode(&addresses));
+
+            count += 1;
+            if count % MIGRATION_BATCH_SIZE == 0 {
+                db.write(::std::mem::replace(&mut batch, DBTransaction::new()))?;
+            }
+        }
+        db.write(batch)
+    }
+}
+
+/// Indexes the parcels of the canon chain under their senders, one key for each parcel.
+///
+/// It replaces the list of the addresses under each signer, which is removed. The sender of a parcel signed by a
+/// regular key is its owner, which is followed through the `SetRegularKey` parcels of the chain since the states of
+/// the old blocks may be pruned. The parcels in the pruned bodies are not indexed.
+struct SenderParcelIndex;
+
+impl Migration for SenderParcelIndex {
+    fn version(&self) -> u32 {
+        3
+    }
+
+    fn description(&self) -> &'static str {
+        "index the parcels by sender"
+    }
+
+    fn migrate(&self, db: &KeyValueDB) -> kvdb::Result<()> {
+        let prefix = [SENDER_PARCEL_KEY_PREFIX];
+        let mut batch = DBTransaction::new();
+        let mut count = 0;
+        for (key, _) in db.iter_from_prefix(COL_EXTRA, &prefix).take_while(|(key, _)| key.starts_with(&prefix)) {
+            // The prefix and the address of the signer
+            if key.len() == 21 {
+                batch.delete(COL_EXTRA, &key);
+            }
+        }
+
+        // The owners of the regular keys by the addresses of the keys, and the reverse.
+        let mut owners: HashMap<Address, Address> = HashMap::new();
+        let mut regular_keys: HashMap<Address, Address> = HashMap::new();
+        for number in 0u64.. {
+            let block_hash: H256 = match db.read(COL_EXTRA, &number) {
+                Some(hash) => hash,
+                None => break,
+            };
+            let compressed_body = match db.get(COL_BODIES, &block_hash)? {
+                Some(body) => body,
+                None => continue,
+            };
+            let body = encoded::Body::new(decompress(&compressed_body, blocks_swapper()).into_vec());
+            for (index, parcel) in body.parcels().into_iter().enumerate() {
+                let parcel = SignedParcel::new(parcel).map_err(|e| format!("Cannot recover the signer: {}", e))?;
+                let signer = public_to_address(&parcel.signer_public());
+                let sender = owners.get(&signer).cloned().unwrap_or(signer);
+                if let Action::SetRegularKey {
+                    key,
+                } = &parcel.action
+                {
+                    if let Some(previous) = regular_keys.insert(sender, public_to_address(key)) {
+                        owners.remove(&previous);
+                    }
+                    owners.insert(public_to_address(key), sender);
+                }
+
+                let address = ParcelAddress {
+                    block_hash,
+                    index,
+                };
+                batch.put(COL_EXTRA, &sender_parcel_key(&sender, number, &address), &rlp::encode(&address));
+                count += 1;
+                if count % MIGRATION_BATCH_SIZE == 0 {
+                    db.write(::std::mem::replace(&mut batch, DBTransaction::new()))?;
+                }
+            }
+        }
+        db.write(batch)
+    }
+}
+
+/// Refuses the chain whose headers don't have the log bloom.
+///
+/// The log bloom is hashed with the other fields of the header, so the old headers cannot be converted without
+/// changing the hashes of the whole chain. The genesis header tells which layout the chain has.
+struct LogBloomHeaders;
+
+/// The position of the log bloom in the header.
+const LOG_BLOOM_INDEX: usize = 9;
+
+impl Migration for LogBloomHeaders {
+    fn version(&self) -> u32 {
+        4
+    }
+
+    fn description(&self) -> &'static str {
+        "check the log blooms of the headers"
+    }
+
+    fn migrate(&self, db: &KeyValueDB) -> kvdb::Result<()> {
+        let genesis_hash: H256 = match db.read(COL_EXTRA, &0u64) {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+        let header = match db.get(COL_HEADERS, &genesis_hash)? {
+            Some(header) => decompress(&header, blocks_swapper()).into_vec(),
+            None => return Ok(()),
+        };
+        if UntrustedRlp::new(&header).val_at::<Bloom>(LOG_BLOOM_INDEX).is_err() {
+            return Err("The headers don't have the log bloom. Remove the database and sync the chain again".into())
+        }
+        Ok(())
+    }
+}
+
+/// Refuses the chain whose headers don't have the shard roots, as `LogBloomHeaders` does.
+struct ShardRootsHeaders;
+
+/// The position of the shard roots in the header.
+const SHARD_ROOTS_INDEX: usize = 10;
+
+impl Migration for ShardRootsHeaders {
+    fn version(&self) -> u32 {
+        5
+    }
+
+    fn description(&self) -> &'static str {
+        "check the shard roots of the headers"
+    }
+
+    fn migrate(&self, db: &KeyValueDB) -> kvdb::Result<()> {
+        let genesis_hash: H256 = match db.read(COL_EXTRA, &0u64) {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+        let header = match db.get(COL_HEADERS, &genesis_hash)? {
+            Some(header) => decompress(&header, blocks_swapper()).into_vec(),
+            None => return Ok(()),
+        };
+        if UntrustedRlp::new(&header).list_at::<H256>(SHARD_ROOTS_INDEX).is_err() {
+            return Err("The headers don't have the shard roots. Remove the database and sync the chain again".into())
+        }
+        Ok(())
+    }
+}
+
+fn migrations() -> Vec<Box<Migration>> {
+    vec![
+        Box::new(StampVersion),
+        Box::new(TransactionAddressList),
+        Box::new(SenderParcelIndex),
+        Box::new(LogBloomHeaders),
+        Box::new(ShardRootsHeaders),
+    ]
+}
+
+/// Returns the schema version of the database, or `None` if it is a new database.
+/// Databases which have data but no version are the version 0.
+pub fn database_version(db: &KeyValueDB) -> kvdb::Result<Option<u32>> {
+    match db.get(COL_EXTRA, DB_VERSION_KEY)? {
+        Some(version) => {
+            let version = UntrustedRlp::new(&version)
+                .as_val()
+                .map_err(|e| format!("The database schema version is corrupted: {}", e))?;
+            Ok(Some(version))
+        }
+        None if db.iter(COL_EXTRA).next().is_none() => Ok(None),
+        None => Ok(Some(0)),
+    }
+}
+
+fn write_version(db: &KeyValueDB, version: u32) -> kvdb::Result<()> {
+    let mut batch = DBTransaction::new();
+    batch.put(COL_EXTRA, DB_VERSION_KEY, &rlp::encode(&version));
+    db.write(batch)
+}
+
+/// Makes sure that the database can be used by this binary.
+/// A new database is stamped with the current version.
+pub fn check_database_version(db: &KeyValueDB) -> Result<(), ClientError> {
+    match database_version(db).map_err(ClientError::Database)? {
+        None => write_version(db, DB_VERSION).map_err(ClientError::Database),
+        Some(DB_VERSION) => Ok(()),
+        Some(found) => Err(ClientError::SchemaVersion {
+            found,
+            expected: DB_VERSION,
+        }),
+    }
+}
+
+/// Upgrades the database to `DB_VERSION` in place and returns the version it had.
+pub fn migrate_database(db: &KeyValueDB) -> kvdb::Result<u32> {
+    let from = match database_version(db)? {
+        None => {
+            write_version(db, DB_VERSION)?;
+            return Ok(DB_VERSION)
+        }
+        Some(version) if version > DB_VERSION => {
+            return Err(format!("The database schema version {} is newer than this binary supports", version).into())
+        }
+        Some(version) => version,
+    };
+
+    for migration in migrations().into_iter().filter(|migration| migration.version() > from) {
+        cinfo!(CLIENT, "Migrating the database to version {}: {}", migration.version(), migration.description());
+        migration.migrate(db)?;
+        write_version(db, migration.version())?;
+    }
+    db.flush()?;
+    Ok(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use ccrypto::blake256;
+    use ckey::{Generator, NetworkId, Random};
+    use ctypes::parcel::Parcel;
+    use kvdb_memorydb;
+    use rlp::RlpStream;
+    use rlp_compress::compress;
+
+    use super::super::blockchain::sender_parcel_key_prefix;
+    use super::super::db::{Writable, NUM_COLUMNS};
+    use super::super::header::Header;
+    use super::super::parcel::UnverifiedParcel;
+    use super::*;
+
+    #[test]
+    fn new_database_is_stamped() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        assert_eq!(None, database_version(&db).unwrap());
+        check_database_version(&db).unwrap();
+        assert_eq!(Some(DB_VERSION), database_version(&db).unwrap());
+    }
+
+    #[test]
+    fn unversioned_database_must_be_migrated() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let mut batch = DBTransaction::new();
+        batch.put(COL_EXTRA, b"best-block", &[0u8; 32]);
+        db.write(batch).unwrap();
+
+        assert_eq!(Some(0), database_version(&db).unwrap());
+        match check_database_version(&db) {
+            Err(ClientError::SchemaVersion {
+                found: 0,
+                expected: DB_VERSION,
+            }) => {}
+            _ => panic!("An unversioned database must be rejected"),
+        }
+
+        assert_eq!(0, migrate_database(&db).unwrap());
+        assert_eq!(Some(DB_VERSION), database_version(&db).unwrap());
+        check_database_version(&db).unwrap();
+    }
+
+    #[test]
+    fn newer_database_is_rejected() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        write_version(&db, DB_VERSION + 1).unwrap();
+        assert!(check_database_version(&db).is_err());
+        assert!(migrate_database(&db).is_err());
+    }
+
+    #[test]
+    fn transaction_addresses_are_converted_to_lists() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let address = |block: u64, index: usize| TransactionAddress {
+            parcel_address: ParcelAddress {
+                block_hash: H256::from(block),
+                index,
+            },
+            index,
+        };
+        let key = |hash: u64| {
+            let mut key = vec![TRANSACTION_ADDRESS_KEY_PREFIX];
+            key.extend_from_slice(&H256::from(hash));
+            key
+        };
+        let converted = TransactionAddresses {
+            addresses: vec![address(2, 0), address(3, 1)],
+        };
+        let mut batch = DBTransaction::new();
+        batch.put(COL_EXTRA, &key(1), &rlp::encode(&address(1, 2)));
+        batch.put(COL_EXTRA, &key(2), &rlp::encode(&converted));
+        db.write(batch).unwrap();
+        write_version(&db, 1).unwrap();
+
+        assert_eq!(1, migrate_database(&db).unwrap());
+        let read = |hash: u64| rlp::decode::<TransactionAddresses>(&db.get(COL_EXTRA, &key(hash)).unwrap().unwrap());
+        assert_eq!(
+            TransactionAddresses {
+                addresses: vec![address(1, 2)],
+            },
+            read(1)
+        );
+        assert_eq!(converted, read(2));
+    }
+
+    #[test]
+    fn parcels_are_indexed_under_their_senders() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let owner = Random.generate().unwrap();
+        let regular_key = Random.generate().unwrap();
+        let parcel = |action: Action, nonce: u64| Parcel {
+            nonce: nonce.into(),
+            fee: 10.into(),
+            network_id: NetworkId::default(),
+            action,
+        };
+        let blocks = vec![
+            vec![SignedParcel::new_with_sign(
+                parcel(
+                    Action::SetRegularKey {
+                        key: *regular_key.public(),
+                    },
+                    0,
+                ),
+                owner.private(),
+            )],
+            vec![SignedParcel::new_with_sign(parcel(Action::CreateShard, 1), regular_key.private())],
+        ];
+        let mut batch = DBTransaction::new();
+        for (number, parcels) in blocks.into_iter().enumerate() {
+            let block_hash = H256::from(number as u64 + 1);
+            let mut body = RlpStream::new_list(1);
+            body.append_list(&parcels.into_iter().map(UnverifiedParcel::from).collect::<Vec<_>>());
+            batch.write(COL_EXTRA, &(number as u64), &block_hash);
+            batch.put(COL_BODIES, &block_hash, &compress(&body.out(), blocks_swapper()));
+        }
+        // The list of the addresses under the signer
+        batch.put(COL_EXTRA, &sender_parcel_key_prefix(&regular_key.address()), &[0xc0]);
+        db.write(batch).unwrap();
+        write_version(&db, 2).unwrap();
+
+        assert_eq!(2, migrate_database(&db).unwrap());
+        let indexed = |sender: &Address| {
+            let prefix = sender_parcel_key_prefix(sender);
+            db.iter_from_prefix(COL_EXTRA, &prefix)
+                .take_while(|(key, _)| key.starts_with(&prefix))
+                .map(|(_, value)| rlp::decode::<ParcelAddress>(&value))
+                .collect::<Vec<_>>()
+        };
+        let address = |block: u64| ParcelAddress {
+            block_hash: H256::from(block),
+            index: 0,
+        };
+        assert_eq!(vec![address(1), address(2)], indexed(&owner.address()));
+        assert_eq!(Vec::<ParcelAddress>::new(), indexed(&regular_key.address()));
+    }
+
+    #[test]
+    fn corrupted_version_is_an_error() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let mut batch = DBTransaction::new();
+        batch.put(COL_EXTRA, DB_VERSION_KEY, &[0xc1, 0xff, 0xff]);
+        db.write(batch).unwrap();
+
+        assert!(database_version(&db).is_err());
+        assert!(check_database_version(&db).is_err());
+    }
+
+    #[test]
+    fn headers_without_log_bloom_are_refused() {
+        let header = Header::default();
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let mut batch = DBTransaction::new();
+        batch.write(COL_EXTRA, &0u64, &header.hash());
+        batch.put(COL_HEADERS, &header.hash(), &rlp::encode(&header));
+        db.write(batch).unwrap();
+        assert!(LogBloomHeaders.migrate(&db).is_ok());
+
+        let mut old = RlpStream::new_list(LOG_BLOOM_INDEX);
+        for _ in 0..LOG_BLOOM_INDEX {
+            old.append_empty_data();
+        }
+        let old = old.out();
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let mut batch = DBTransaction::new();
+        batch.write(COL_EXTRA, &0u64, &blake256(&old));
+        batch.put(COL_HEADERS, &blake256(&old), &old);
+        db.write(batch).unwrap();
+        assert!(LogBloomHeaders.migrate(&db).is_err());
+    }
+
+    #[test]
+    fn headers_without_shard_roots_are_refused() {
+        let header = Header::default();
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let mut batch = DBTransaction::new();
+        batch.write(COL_EXTRA, &0u64, &header.hash());
+        batch.put(COL_HEADERS, &header.hash(), &rlp::encode(&header));
+        db.write(batch).unwrap();
+        assert!(ShardRootsHeaders.migrate(&db).is_ok());
+
+        let mut old = RlpStream::new_list(SHARD_ROOTS_INDEX);
+        for _ in 0..LOG_BLOOM_INDEX {
+            old.append_empty_data();
+        }
+        old.append(&Bloom::default());
+        let old = old.out();
+        let db = kvdb_memorydb::create(NUM_COLUMNS.unwrap());
+        let mut batch = DBTransaction::new();
+        batch.write(COL_EXTRA, &0u64, &blake256(&old));
+        batch.put(COL_HEADERS, &blake256(&old), &old);
+        db.write(batch).unwrap();
+        assert!(LogBloomHeaders.migrate(&db).is_ok());
+        assert!(ShardRootsHeaders.migrate(&db).is_err());
+    }
+
+    #[test]
+    fn migrations_are_consecutive() {
+        for (index, migration) in migrations().iter().enumerate() {
+            assert_eq!(index as u32 + 1, migration.version());
+        }
+        assert_eq!(DB_VERSION, migrations().len() as u32);
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use linked_hash_map::LinkedHashMap;
+use primitives::H256;
+
+use super::DropReason;
+
+/// Keeps track of the parcels which recently left the mem pool without being mined.
+pub struct DroppedParcels {
+    limit: usize,
+    parcels: LinkedHashMap<H256, DropReason>,
+}
+
+impl Default for DroppedParcels {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl DroppedParcels {
+    pub fn new(limit: usize) -> Self {
+        DroppedParcels {
+            limit,
+            parcels: Default::default(),
+        }
+    }
+
+    pub fn mark(&mut self, hash: H256, reason: DropReason) {
+        self.parcels.insert(hash, reason);
+        while self.parcels.len() > self.limit {
+            self.parcels.pop_front();
+        }
+    }
+
+    /// Forgets the parcel which is imported again.
+    pub fn remove(&mut self, hash: &H256) {
+        self.parcels.remove(hash);
+    }
+
+    pub fn get(&self, hash: &H256) -> Option<DropReason> {
+        self.parcels.get(hash).cloned()
+    }
+
+    /// The oldest first
+    pub fn hashes(&self) -> Vec<H256> {
+        self.parcels.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_the_oldest() {
+        let mut dropped = DroppedParcels::new(2);
+        dropped.mark(1.into(), DropReason::Limit);
+        dropped.mark(2.into(), DropReason::Invalid);
+        dropped.mark(3.into(), DropReason::Replaced(4.into()));
+
+        assert_eq!(None, dropped.get(&1.into()));
+        assert_eq!(Some(DropReason::Invalid), dropped.get(&2.into()));
+        assert_eq!(Some(DropReason::Replaced(4.into())), dropped.get(&3.into()));
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use primitives::H256;
+
+use super::super::block::ExecutedParcels;
+
+/// Parcels executed by the latest pending block.
+///
+/// Resealing on the same parent rebuilds the pending block from the mem pool. As long as the mem pool still
+/// starts with the parcels of the previous pending block, they are restored instead of executed again.
+#[derive(Default)]
+pub struct ExecutionCache {
+    latest: Option<(H256, Vec<H256>, ExecutedParcels)>,
+}
+
+impl ExecutionCache {
+    /// Returns the executed parcels if `hashes` begins with them and they were executed on `parent`.
+    pub fn reusable(&self, parent: &H256, hashes: &[H256]) -> Option<ExecutedParcels> {
+        match &self.latest {
+            Some((cached_parent, cached_hashes, executed))
+                if cached_parent == parent && !cached_hashes.is_empty() && hashes.starts_with(cached_hashes) =>
+            {
+                Some(executed.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn update(&mut self, parent: H256, executed: ExecutedParcels) {
+        let hashes = executed.parcels().iter().map(|parcel| parcel.hash()).collect();
+        self.latest = Some((parent, hashes, executed));
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+    }
+}
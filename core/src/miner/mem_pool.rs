@@ -16,7 +16,8 @@
 
 use std::cmp;
 use std::cmp::Ordering;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::{Duration, Instant};
 
 use ckey::{public_to_address, Public};
 use ctypes::parcel::{Action, Error as ParcelError};
@@ -29,12 +30,12 @@ use rlp;
 use table::Table;
 
 use super::super::parcel::SignedParcel;
+use super::dropped_parcels::DroppedParcels;
 use super::local_parcels::{LocalParcelsList, Status as LocalParcelStatus};
-use super::ParcelImportResult;
+use super::{DropReason, ParcelImportResult, ParcelPoolStatus};
 
-/// Parcel with the same (sender, nonce) can be replaced only if
-/// `new_fee > old_fee + old_fee >> SHIFT`
-const FEE_BUMP_SHIFT: usize = 3; // 2 = 25%, 3 = 12.5%, 4 = 6.25%
+/// Parcel with the same (sender, nonce) can be replaced only by a parcel paying at least this percentage more fee.
+pub const DEFAULT_REPLACEMENT_FEE_BUMP: u8 = 12;
 
 /// Point in time when parcel was inserted.
 pub type PoolingInstant = BlockNumber;
@@ -91,6 +92,8 @@ struct ParcelOrder {
     fee_per_byte: U256,
     /// Heap usage of this parcel.
     mem_usage: usize,
+    /// Size of the rlp serialized parcel.
+    size: usize,
     /// Hash to identify associated parcel
     hash: H256,
     /// Incremental id assigned when parcel is inserted to the pool.
@@ -108,6 +111,7 @@ impl ParcelOrder {
             nonce_height: item.nonce() - base_nonce,
             fee,
             mem_usage: item.parcel.heap_size_of_children(),
+            size: rlp_bytes_len,
             fee_per_byte: fee / rlp_bytes_len.into(),
             hash: item.hash(),
             insertion_id: item.insertion_id,
@@ -168,16 +172,25 @@ struct MemPoolItem {
     origin: ParcelOrigin,
     /// Insertion time
     insertion_time: PoolingInstant,
+    /// Monotonic time of the insertion
+    inserted_at: Instant,
     /// ID assigned upon insertion, should be unique.
     insertion_id: u64,
 }
 
 impl MemPoolItem {
-    fn new(parcel: SignedParcel, origin: ParcelOrigin, insertion_time: PoolingInstant, insertion_id: u64) -> Self {
+    fn new(
+        parcel: SignedParcel,
+        origin: ParcelOrigin,
+        insertion_time: PoolingInstant,
+        inserted_at: Instant,
+        insertion_id: u64,
+    ) -> Self {
         MemPoolItem {
             parcel,
             origin,
             insertion_time,
+            inserted_at,
             insertion_id,
         }
     }
@@ -210,6 +223,8 @@ struct ParcelSet {
     by_priority: BTreeSet<ParcelOrder>,
     by_signer_public: Table<Public, U256, ParcelOrder>,
     by_fee: MultiMap<U256, H256>,
+    /// Total size of the rlp serialized parcels in this set.
+    size: usize,
     limit: usize,
     memory_limit: usize,
 }
@@ -224,8 +239,10 @@ impl ParcelSet {
         }
         let order_hash = order.hash.clone();
         let order_fee = order.fee.clone();
+        self.size += order.size;
         let by_signer_public_replaced = self.by_signer_public.insert(signer_public, nonce, order);
         if let Some(ref old_order) = by_signer_public_replaced {
+            self.size -= old_order.size;
             assert!(
                 self.by_priority.remove(old_order),
                 "hash is in `by_signer_public`; all parcels in `by_signer_public` must be in `by_priority`; qed"
@@ -249,6 +266,7 @@ impl ParcelSet {
         &mut self,
         by_hash: &mut HashMap<H256, MemPoolItem>,
         local: &mut LocalParcelsList,
+        dropped: &mut DroppedParcels,
     ) -> Option<HashMap<Public, U256>> {
         let mut count = 0;
         let mut mem_usage = 0;
@@ -278,6 +296,7 @@ impl ParcelSet {
                 .drop(&sender, &nonce)
                 .expect("Parcel has just been found in `by_priority`; so it is in `by_signer_public` also.");
             ctrace!(MEM_POOL, "Dropped out of limit parcel: {:?}", order.hash);
+            dropped.mark(order.hash, DropReason::Limit);
 
             let order = by_hash
                 .remove(&order.hash)
@@ -304,6 +323,7 @@ impl ParcelSet {
                 self.by_priority.remove(&parcel_order),
                 "hash is in `by_signer_public`; all parcels in `by_signer_public` must be in `by_priority`; qed"
             );
+            self.size -= parcel_order.size;
             assert_eq!(self.by_priority.len(), self.by_signer_public.len());
             assert_eq!(self.by_fee.values().map(|v| v.len()).fold(0, |a, b| a + b), self.by_signer_public.len());
             return Some(parcel_order)
@@ -318,6 +338,7 @@ impl ParcelSet {
     fn clear(&mut self) {
         self.by_priority.clear();
         self.by_signer_public.clear();
+        self.size = 0;
     }
 
     /// Sets new limit for number of parcels in this `ParcelSet`.
@@ -353,8 +374,18 @@ pub struct MemPool {
     last_nonces: HashMap<Public, U256>,
     /// List of local parcels and their statuses.
     local_parcels: LocalParcelsList,
+    /// Parcels which recently left the pool without being mined.
+    dropped_parcels: DroppedParcels,
     /// Next id that should be assigned to a parcel imported to the pool.
     next_parcel_id: u64,
+    /// Parcels pinned by the operator to the top of the block regardless of their fees.
+    prioritized: HashSet<H256>,
+    /// How much more fee, in percent, a parcel pays to replace the parcel of the same signer and nonce.
+    replacement_fee_bump: u8,
+    /// Maximum number of parcels a sender can keep in `current` and `future` together.
+    sender_limit: usize,
+    /// Maximum number of parcels in `future`. It can't be larger than the limit of the pool.
+    future_limit: usize,
 }
 
 impl Default for MemPool {
@@ -375,6 +406,7 @@ impl MemPool {
             by_priority: BTreeSet::new(),
             by_signer_public: Table::new(),
             by_fee: MultiMap::default(),
+            size: 0,
             limit,
             memory_limit,
         };
@@ -383,6 +415,7 @@ impl MemPool {
             by_priority: BTreeSet::new(),
             by_signer_public: Table::new(),
             by_fee: MultiMap::default(),
+            size: 0,
             limit,
             memory_limit,
         };
@@ -395,17 +428,22 @@ impl MemPool {
             by_hash: HashMap::new(),
             last_nonces: HashMap::new(),
             local_parcels: LocalParcelsList::default(),
+            dropped_parcels: DroppedParcels::default(),
             next_parcel_id: 0,
+            prioritized: HashSet::new(),
+            replacement_fee_bump: DEFAULT_REPLACEMENT_FEE_BUMP,
+            sender_limit: usize::max_value(),
+            future_limit: usize::max_value(),
         }
     }
 
     /// Set the new limit for `current` and `future` queue.
     pub fn set_limit(&mut self, limit: usize) {
         self.current.set_limit(limit);
-        self.future.set_limit(limit);
+        self.future.set_limit(cmp::min(limit, self.future_limit));
         // And ensure the limits
-        self.current.enforce_limit(&mut self.by_hash, &mut self.local_parcels);
-        self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels);
+        self.current.enforce_limit(&mut self.by_hash, &mut self.local_parcels, &mut self.dropped_parcels);
+        self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels, &mut self.dropped_parcels);
     }
 
     /// Returns current limit of parcels in the pool.
@@ -413,6 +451,24 @@ impl MemPool {
         self.current.limit
     }
 
+    /// Returns the maximum number of parcels a sender can keep in the pool.
+    pub fn sender_limit(&self) -> usize {
+        self.sender_limit
+    }
+
+    /// Sets the maximum number of parcels a sender can keep in the pool.
+    /// The parcels already in the pool are not affected.
+    pub fn set_sender_limit(&mut self, limit: usize) {
+        self.sender_limit = limit;
+    }
+
+    /// Sets the maximum number of parcels with future nonces, and drops the lowest priority ones above it.
+    pub fn set_future_limit(&mut self, limit: usize) {
+        self.future_limit = limit;
+        self.future.set_limit(cmp::min(self.current.limit, limit));
+        self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels, &mut self.dropped_parcels);
+    }
+
     /// Get the minimal fee.
     pub fn minimal_fee(&self) -> &U256 {
         &self.minimal_fee
@@ -424,6 +480,17 @@ impl MemPool {
         self.minimal_fee = min_fee;
     }
 
+    /// Get the percentage by which a replacement must raise the fee.
+    pub fn replacement_fee_bump(&self) -> u8 {
+        self.replacement_fee_bump
+    }
+
+    /// Sets the percentage by which a replacement must raise the fee.
+    /// The parcels already in the pool are not affected.
+    pub fn set_replacement_fee_bump(&mut self, fee_bump: u8) {
+        self.replacement_fee_bump = fee_bump;
+    }
+
     /// Get one more than the lowest fee in the pool iff the pool is
     /// full, otherwise 0.
     pub fn effective_minimum_fee(&self) -> U256 {
@@ -438,6 +505,11 @@ impl MemPool {
         }
     }
 
+    /// Returns the total size of the rlp serialized pending parcels.
+    pub fn pending_size(&self) -> usize {
+        self.current.size
+    }
+
     /// Add signed parcel to pool to be verified and imported.
     ///
     /// NOTE details_provider methods should be cheap to compute
@@ -523,6 +595,23 @@ impl MemPool {
         }
     }
 
+    /// Removes the parcels which have stayed in the pool longer than `ttl`, including the local ones.
+    /// Returns the hashes of the removed parcels.
+    pub fn remove_expired<F>(&mut self, ttl: Duration, now: Instant, fetch_nonce: &F) -> Vec<H256>
+    where
+        F: Fn(&Public) -> U256, {
+        let expired: Vec<H256> = self
+            .by_hash
+            .iter()
+            .filter(|(_, parcel)| now > parcel.inserted_at + ttl)
+            .map(|(hash, _)| *hash)
+            .collect();
+        for hash in &expired {
+            self.remove(hash, fetch_nonce, RemovalReason::Expired);
+        }
+        expired
+    }
+
     /// Removes invalid parcel identified by hash from pool.
     /// Assumption is that this parcel nonce is not related to client nonce,
     /// so parcels left in pool are processed according to client nonce.
@@ -544,12 +633,18 @@ impl MemPool {
         let current_nonce = fetch_nonce(&signer_public);
 
         ctrace!(MEM_POOL, "Removing invalid parcel: {:?}", parcel.hash());
+        self.dropped_parcels.mark(*parcel_hash, match reason {
+            RemovalReason::Invalid => DropReason::Invalid,
+            RemovalReason::Canceled => DropReason::Canceled,
+            RemovalReason::Expired => DropReason::Expired,
+        });
 
         // Mark in locals
         if self.local_parcels.contains(parcel_hash) {
             match reason {
                 RemovalReason::Invalid => self.local_parcels.mark_invalid(parcel.parcel.into()),
                 RemovalReason::Canceled => self.local_parcels.mark_canceled(parcel.parcel.into()),
+                RemovalReason::Expired => self.local_parcels.mark_dropped(parcel.parcel.into()),
             }
         }
 
@@ -603,6 +698,19 @@ impl MemPool {
         self.future.clear();
         self.by_hash.clear();
         self.last_nonces.clear();
+        self.prioritized.clear();
+    }
+
+    /// Pins the parcel to the top of the block, with the parcels of the same signer which must precede it.
+    /// Returns false if the parcel is not in the pool.
+    pub fn prioritize(&mut self, hash: &H256) -> bool {
+        let by_hash = &self.by_hash;
+        self.prioritized.retain(|hash| by_hash.contains_key(hash));
+        if !self.by_hash.contains_key(hash) {
+            return false
+        }
+        self.prioritized.insert(*hash);
+        true
     }
 
     /// Finds parcel in the pool by hash (if any)
@@ -611,18 +719,57 @@ impl MemPool {
         self.by_hash.get(hash).map(|parcel| parcel.parcel.clone())
     }
 
+    /// Finds the parcel in the pool by hash, if it's signed by a local account.
+    pub fn find_local(&self, hash: &H256) -> Option<SignedParcel> {
+        self.by_hash.get(hash).filter(|item| item.origin.is_local()).map(|item| item.parcel.clone())
+    }
+
+    /// The parcels which recently left the pool without being mined, the oldest first.
+    pub fn dropped_parcels(&self) -> Vec<H256> {
+        self.dropped_parcels.hashes()
+    }
+
+    /// Where the parcel is in the pool, or why it recently left the pool without being mined.
+    pub fn parcel_status(&self, hash: &H256) -> Option<ParcelPoolStatus> {
+        match self.by_hash.get(hash) {
+            Some(parcel) => {
+                let in_current = self
+                    .current
+                    .by_signer_public
+                    .get(&parcel.signer_public(), &parcel.nonce())
+                    .map_or(false, |order| order.hash == *hash);
+                if in_current {
+                    Some(ParcelPoolStatus::Pending)
+                } else {
+                    Some(ParcelPoolStatus::Future)
+                }
+            }
+            None => self.dropped_parcels.get(hash).map(ParcelPoolStatus::Dropped),
+        }
+    }
+
     /// Returns highest parcel nonce for given signer.
     #[allow(dead_code)]
     pub fn last_nonce(&self, signer_public: &Public) -> Option<U256> {
         self.last_nonces.get(signer_public).cloned()
     }
 
+    /// Returns the nonce of the last parcel in `current` of each signer.
+    /// Parcels in `future` don't count, since they wait for the gap to be filled.
+    pub fn last_nonces(&self) -> Vec<(Public, U256)> {
+        self.last_nonces.iter().map(|(signer_public, nonce)| (*signer_public, *nonce)).collect()
+    }
+
     /// Returns top parcels from the pool ordered by priority.
+    /// The prioritized parcels and the parcels which must precede them come first.
     pub fn top_parcels(&self, size_limit: usize) -> Vec<SignedParcel> {
+        let pinned = self.pinned_parcels();
         let mut current_size: usize = 0;
-        self.current
-            .by_priority
-            .iter()
+        let (first, rest): (Vec<_>, Vec<_>) =
+            self.current.by_priority.iter().partition(|order| pinned.contains(&order.hash));
+        first
+            .into_iter()
+            .chain(rest)
             .map(|t| {
                 self.by_hash
                     .get(&t.hash)
@@ -638,6 +785,21 @@ impl MemPool {
             .collect()
     }
 
+    /// Returns the hashes of the prioritized parcels in `current`, and of the parcels of the same signers with lower
+    /// nonces, which must be included before them.
+    fn pinned_parcels(&self) -> HashSet<H256> {
+        let mut pinned = HashSet::new();
+        for item in self.prioritized.iter().filter_map(|hash| self.by_hash.get(hash)) {
+            let (signer_public, nonce) = (item.signer_public(), item.nonce());
+            if self.current.by_signer_public.get(&signer_public, &nonce).map(|order| order.hash) != Some(item.hash()) {
+                continue
+            }
+            let by_nonce = self.current.by_signer_public.row(&signer_public).expect("The parcel is in current");
+            pinned.extend(by_nonce.iter().filter(|(n, _)| **n <= nonce).map(|(_, order)| order.hash));
+        }
+        pinned
+    }
+
     /// Return all future parcels.
     pub fn future_parcels(&self) -> Vec<SignedParcel> {
         self.future
@@ -724,7 +886,7 @@ impl MemPool {
         // No invalid parcels beyond this point.
         let id = self.next_parcel_id;
         self.next_parcel_id += 1;
-        let vparcel = MemPoolItem::new(parcel, origin, time, id);
+        let vparcel = MemPoolItem::new(parcel, origin, time, Instant::now(), id);
         let r = self.import_parcel(vparcel, client_account.nonce);
         assert_eq!(self.future.by_priority.len() + self.current.by_priority.len(), self.by_hash.len());
         r
@@ -771,6 +933,12 @@ impl MemPool {
             self.mark_parcels_local(&signer_public);
         }
 
+        // Local and retracted parcels are allowed to go above the sender limit. The miner checks the local parcels
+        // which are not signed by the accounts of the node.
+        if parcel.origin == ParcelOrigin::External {
+            self.check_sender_limit(&signer_public, &nonce)?;
+        }
+
         // Future parcel
         if nonce > next_nonce {
             // We have a gap - put to future.
@@ -778,14 +946,24 @@ impl MemPool {
             check_too_cheap(Self::replace_parcel(
                 parcel,
                 state_nonce,
+                self.replacement_fee_bump,
                 &mut self.future,
                 &mut self.by_hash,
                 &mut self.local_parcels,
+                &mut self.dropped_parcels,
             ))?;
             // Enforce limit in Future
-            let removed = self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels);
+            let removed =
+                self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels, &mut self.dropped_parcels);
             // Return an error if this parcel was not imported because of limit.
-            check_if_removed(&signer_public, &nonce, removed)?;
+            let is_future_limited = self.future.limit < self.current.limit;
+            check_if_removed(&signer_public, &nonce, removed).map_err(|err| {
+                if is_future_limited {
+                    ParcelError::FutureLimitReached
+                } else {
+                    err
+                }
+            })?;
 
             cdebug!(MEM_POOL, "Importing parcel to future: {:?}", hash);
             cdebug!(MEM_POOL, "status: {:?}", self.status());
@@ -800,16 +978,19 @@ impl MemPool {
         check_too_cheap(Self::replace_parcel(
             parcel,
             state_nonce,
+            self.replacement_fee_bump,
             &mut self.current,
             &mut self.by_hash,
             &mut self.local_parcels,
+            &mut self.dropped_parcels,
         ))?;
         // Keep track of highest nonce stored in current
         let new_max = self.last_nonces.get(&signer_public).map_or(nonce, |n| cmp::max(nonce, *n));
         self.last_nonces.insert(signer_public, new_max);
 
         // Also enforce the limit
-        let removed = self.current.enforce_limit(&mut self.by_hash, &mut self.local_parcels);
+        let removed =
+            self.current.enforce_limit(&mut self.by_hash, &mut self.local_parcels, &mut self.dropped_parcels);
         // If some parcel were removed because of limit we need to update last_nonces also.
         self.update_last_nonces(&removed);
         // Trigger error if the parcel we are importing was removed.
@@ -820,6 +1001,27 @@ impl MemPool {
         Ok(ParcelImportResult::Current)
     }
 
+    /// Fails if the signer already has as many parcels as the sender limit. A parcel replacing the one of the same
+    /// nonce doesn't increase the number, so it passes.
+    pub fn check_sender_limit(&self, signer_public: &Public, nonce: &U256) -> Result<(), ParcelError> {
+        if self.has_parcel_of(signer_public, nonce) {
+            return Ok(())
+        }
+        let count = self.current.by_signer_public.row(signer_public).map_or(0, |row| row.len())
+            + self.future.by_signer_public.row(signer_public).map_or(0, |row| row.len());
+        if count >= self.sender_limit {
+            ctrace!(MEM_POOL, "Dropping parcel of {:?} over the sender limit ({} parcels)", signer_public, count);
+            return Err(ParcelError::SenderLimitReached)
+        }
+        Ok(())
+    }
+
+    /// Returns true if the pool has a parcel of the signer with the nonce.
+    fn has_parcel_of(&self, signer_public: &Public, nonce: &U256) -> bool {
+        self.current.by_signer_public.get(signer_public, nonce).is_some()
+            || self.future.by_signer_public.get(signer_public, nonce).is_some()
+    }
+
     /// Always updates future and moves parcel from current to future.
     fn cull_internal(&mut self, sender: Public, client_nonce: U256) {
         // We will either move parcel to future or remove it completely
@@ -883,6 +1085,7 @@ impl MemPool {
                 // remove also from priority and fee
                 self.future.by_priority.remove(&order);
                 self.future.by_fee.remove(&order.fee, &order.hash);
+                self.future.size -= order.size;
                 // Put to current
                 let order = order.update_height(current_nonce, first_nonce);
                 if order.origin.is_local() {
@@ -894,9 +1097,11 @@ impl MemPool {
                         current_nonce,
                         old,
                         order,
+                        self.replacement_fee_bump,
                         &mut self.current,
                         &mut self.by_hash,
                         &mut self.local_parcels,
+                        &mut self.dropped_parcels,
                     );
                 }
                 update_last_nonce_to = Some(current_nonce);
@@ -935,9 +1140,11 @@ impl MemPool {
                         k,
                         old,
                         order,
+                        self.replacement_fee_bump,
                         &mut self.future,
                         &mut self.by_hash,
                         &mut self.local_parcels,
+                        &mut self.dropped_parcels,
                     );
                 }
             } else {
@@ -948,7 +1155,7 @@ impl MemPool {
                 }
             }
         }
-        self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels);
+        self.future.enforce_limit(&mut self.by_hash, &mut self.local_parcels, &mut self.dropped_parcels);
     }
 
     /// Marks all parcels from particular sender as local parcels
@@ -988,7 +1195,8 @@ impl MemPool {
 
     /// Replaces parcel in given set (could be `future` or `current`).
     ///
-    /// If there is already parcel with same `(sender, nonce)` it will be replaced iff `fee` is higher.
+    /// If there is already parcel with same `(sender, nonce)` it will be replaced iff `fee` is higher
+    /// by at least `fee_bump` percent, and strictly higher in any case.
     /// One of the parcels is dropped from set and also removed from pool entirely (from `by_hash`).
     ///
     /// Returns `true` if parcel actually got to the pool (`false` if there was already a parcel with higher
@@ -996,9 +1204,11 @@ impl MemPool {
     fn replace_parcel(
         parcel: MemPoolItem,
         base_nonce: U256,
+        fee_bump: u8,
         set: &mut ParcelSet,
         by_hash: &mut HashMap<H256, MemPoolItem>,
         local: &mut LocalParcelsList,
+        dropped: &mut DroppedParcels,
     ) -> bool {
         let order = ParcelOrder::for_parcel(&parcel, base_nonce);
         let hash = parcel.hash();
@@ -1007,11 +1217,12 @@ impl MemPool {
 
         let old_hash = by_hash.insert(hash, parcel);
         assert!(old_hash.is_none(), "Each hash has to be inserted exactly once.");
+        dropped.remove(&hash);
 
         ctrace!(MEM_POOL, "Inserting: {:?}", order);
 
         if let Some(old) = set.insert(signer_public, nonce, order.clone()) {
-            Self::replace_orders(signer_public, nonce, old, order, set, by_hash, local)
+            Self::replace_orders(signer_public, nonce, old, order, fee_bump, set, by_hash, local, dropped)
         } else {
             true
         }
@@ -1022,9 +1233,11 @@ impl MemPool {
         nonce: U256,
         old: ParcelOrder,
         order: ParcelOrder,
+        fee_bump: u8,
         set: &mut ParcelSet,
         by_hash: &mut HashMap<H256, MemPoolItem>,
         local: &mut LocalParcelsList,
+        dropped: &mut DroppedParcels,
     ) -> bool {
         // There was already parcel in pool. Let's check which one should stay
         let old_hash = old.hash;
@@ -1032,7 +1245,7 @@ impl MemPool {
 
         let old_fee = old.fee;
         let new_fee = order.fee;
-        let min_required_fee = old_fee + (old_fee >> FEE_BUMP_SHIFT);
+        let min_required_fee = min_replacement_fee(&old_fee, fee_bump);
 
         if min_required_fee > new_fee {
             ctrace!(
@@ -1056,6 +1269,7 @@ impl MemPool {
             // Make sure we remove old parcel entirely
             let old =
                 by_hash.remove(&old.hash).expect("The hash is coming from `future` so it has to be in `by_hash`.");
+            dropped.mark(old_hash, DropReason::Replaced(new_hash));
             if old.origin.is_local() {
                 local.mark_replaced(old.parcel, new_fee, new_hash);
             }
@@ -1088,8 +1302,18 @@ pub enum RemovalReason {
     /// Parcel is invalid
     Invalid,
     /// Parcel was canceled
-    #[allow(dead_code)]
     Canceled,
+    /// Parcel stayed in the pool longer than its time-to-live
+    Expired,
+}
+
+/// The lowest fee of a parcel which replaces the parcel of the same signer and nonce in the pool.
+/// `fee_bump` is the percentage by which the fee must be raised, and the fee must be higher even if it's 0.
+pub fn min_replacement_fee(fee: &U256, fee_bump: u8) -> U256 {
+    let fee_bump = U256::from(fee_bump);
+    // Divide first so that the multiplication doesn't overflow.
+    let bumped = fee + (fee / 100) * fee_bump + (fee % 100) * fee_bump / 100;
+    cmp::max(bumped, fee.saturating_add(1.into()))
 }
 
 fn check_too_cheap(is_in: bool) -> Result<(), ParcelError> {
@@ -1114,10 +1338,11 @@ fn check_if_removed(sender: &Public, nonce: &U256, dropped: Option<HashMap<Publi
 pub mod test {
     use std::cmp::Ordering;
 
-    use ckey::{Generator, Random};
+    use ckey::{sign, Generator, KeyPair, Random};
     use ctypes::parcel::{Parcel, ShardChange};
     use ctypes::transaction::{AssetMintOutput, Transaction};
 
+    use super::super::super::parcel::{MultiSig, UnverifiedParcel};
     use super::*;
 
     #[test]
@@ -1146,7 +1371,7 @@ pub mod test {
         };
         let keypair = Random.generate().unwrap();
         let signed = SignedParcel::new_with_sign(parcel, keypair.private());
-        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, 0);
+        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, Instant::now(), 0);
 
         assert_eq!(fee, item.cost());
     }
@@ -1186,7 +1411,7 @@ pub mod test {
         };
         let keypair = Random.generate().unwrap();
         let signed = SignedParcel::new_with_sign(parcel, keypair.private());
-        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, 0);
+        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, Instant::now(), 0);
 
         assert_eq!(fee, item.cost());
     }
@@ -1235,7 +1460,7 @@ pub mod test {
         };
         let keypair = Random.generate().unwrap();
         let signed = SignedParcel::new_with_sign(parcel, keypair.private());
-        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, 0);
+        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, Instant::now(), 0);
 
         assert_eq!(fee, item.cost());
     }
@@ -1256,11 +1481,37 @@ pub mod test {
             },
         };
         let signed = SignedParcel::new_with_sign(parcel, keypair.private());
-        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, 0);
+        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, Instant::now(), 0);
 
         assert_eq!(fee + amount, item.cost());
     }
 
+    #[test]
+    fn cost_of_multisig_payment_is_charged_to_the_account() {
+        let keys: Vec<KeyPair> = (0..2).map(|_| Random.generate().unwrap()).collect();
+        let parcel = Parcel {
+            nonce: U256::zero(),
+            fee: 100.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver: 1u64.into(),
+                amount: 100000.into(),
+            },
+        };
+        let multisig = MultiSig::new(2, keys.iter().map(|key| *key.public()).collect());
+        let message = multisig.message(&parcel.hash());
+        let mut unverified = UnverifiedParcel::new_multisig(parcel, multisig.clone());
+        for (index, key) in keys.iter().enumerate() {
+            let sig = sign(key.private(), &message).unwrap();
+            unverified = unverified.update_multisig(|multisig| multisig.add_signature(index as u8, sig)).unwrap();
+        }
+        let item =
+            MemPoolItem::new(SignedParcel::new(unverified).unwrap(), ParcelOrigin::External, 0, Instant::now(), 0);
+
+        assert_eq!(multisig.public(), item.signer_public());
+        assert_eq!(U256::from(100 + 100000), item.cost());
+    }
+
     #[test]
     fn fee_per_byte_order_simple() {
         let order1 = create_parcel_order(U256::from(1000_000_000), 100);
@@ -1294,6 +1545,421 @@ pub mod test {
         assert_eq!(prev_orders[3], sorted_orders[4]);
     }
 
+    #[test]
+    fn status_of_replaced_parcel_is_dropped() {
+        let keypair = Random.generate().unwrap();
+        let payment = |nonce: u64, fee: u64| {
+            let parcel = Parcel {
+                nonce: nonce.into(),
+                fee: fee.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+
+        let first = payment(0, 100);
+        let future = payment(2, 100);
+        pool.add(first.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        pool.add(future.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(Some(ParcelPoolStatus::Pending), pool.parcel_status(&first.hash()));
+        assert_eq!(Some(ParcelPoolStatus::Future), pool.parcel_status(&future.hash()));
+
+        let replacement = payment(0, 200);
+        pool.add(replacement.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(Some(ParcelPoolStatus::Pending), pool.parcel_status(&replacement.hash()));
+        assert_eq!(
+            Some(ParcelPoolStatus::Dropped(DropReason::Replaced(replacement.hash()))),
+            pool.parcel_status(&first.hash())
+        );
+        assert_eq!(None, pool.parcel_status(&payment(1, 100).hash()));
+    }
+
+    #[test]
+    fn pending_size_excludes_future_parcels() {
+        let keypair = Random.generate().unwrap();
+        let payment = |nonce: u64| {
+            let parcel = Parcel {
+                nonce: nonce.into(),
+                fee: 100.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        assert_eq!(0, pool.pending_size());
+
+        let first = payment(0);
+        pool.add(first.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        pool.add(payment(2), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(rlp::encode(&first).len(), pool.pending_size());
+
+        // The future parcel becomes pending once the gap is filled.
+        pool.add(payment(1), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        let size: usize = (0..3).map(|nonce| rlp::encode(&payment(nonce)).len()).sum();
+        assert_eq!(size, pool.pending_size());
+
+        pool.clear();
+        assert_eq!(0, pool.pending_size());
+    }
+
+    #[test]
+    fn cancellation_replaces_local_parcel_with_min_bumped_fee() {
+        let keypair = Random.generate().unwrap();
+        let payment = |fee: U256, amount: u64| {
+            let parcel = Parcel {
+                nonce: 0.into(),
+                fee,
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: amount.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+
+        let local = payment(100.into(), 10);
+        pool.add(local.clone(), ParcelOrigin::Local, 0, &fetch_account).unwrap();
+        assert_eq!(Some(local.clone()), pool.find_local(&local.hash()));
+
+        let fee = min_replacement_fee(&local.fee, DEFAULT_REPLACEMENT_FEE_BUMP);
+        assert!(pool.add(payment(fee - U256::from(1), 0), ParcelOrigin::Local, 0, &fetch_account).is_err());
+        let cancel = payment(fee, 0);
+        pool.add(cancel.clone(), ParcelOrigin::Local, 0, &fetch_account).unwrap();
+        assert_eq!(None, pool.find_local(&local.hash()));
+        assert_eq!(Some(ParcelPoolStatus::Pending), pool.parcel_status(&cancel.hash()));
+    }
+
+    #[test]
+    fn min_replacement_fee_raises_by_percentage() {
+        assert_eq!(U256::from(112), min_replacement_fee(&100.into(), 12));
+        assert_eq!(U256::from(1234 + 123), min_replacement_fee(&1234.into(), 10));
+        assert_eq!(U256::from(8), min_replacement_fee(&7.into(), 0));
+        assert_eq!(U256::from(8), min_replacement_fee(&7.into(), 10));
+        assert_eq!(U256::max_value() / 100 * 2, min_replacement_fee(&(U256::max_value() / 100), 100));
+    }
+
+    #[test]
+    fn replacement_fee_bump_is_configurable() {
+        let keypair = Random.generate().unwrap();
+        let payment = |fee: u64| {
+            let parcel = Parcel {
+                nonce: 0.into(),
+                fee: fee.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        pool.set_replacement_fee_bump(50);
+
+        let first = payment(100);
+        pool.add(first.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(
+            Err(ParcelError::TooCheapToReplace),
+            pool.add(payment(149), ParcelOrigin::External, 0, &fetch_account)
+        );
+        let replacement = payment(150);
+        pool.add(replacement.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(
+            Some(ParcelPoolStatus::Dropped(DropReason::Replaced(replacement.hash()))),
+            pool.parcel_status(&first.hash())
+        );
+    }
+
+    #[test]
+    fn parcel_with_the_same_fee_does_not_replace_without_fee_bump() {
+        let keypair = Random.generate().unwrap();
+        let payment = |fee: u64, amount: u64| {
+            let parcel = Parcel {
+                nonce: 0.into(),
+                fee: fee.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: amount.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        pool.set_replacement_fee_bump(0);
+
+        let first = payment(100, 0);
+        pool.add(first.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(
+            Err(ParcelError::TooCheapToReplace),
+            pool.add(payment(100, 1), ParcelOrigin::External, 0, &fetch_account)
+        );
+        assert!(pool.find(&first.hash()).is_some());
+        pool.add(payment(101, 1), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(None, pool.find(&first.hash()));
+    }
+
+    #[test]
+    fn status_of_removed_parcel_is_canceled() {
+        let keypair = Random.generate().unwrap();
+        let parcel = Parcel {
+            nonce: 0.into(),
+            fee: 100.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver: Default::default(),
+                amount: 0.into(),
+            },
+        };
+        let parcel = SignedParcel::new_with_sign(parcel, keypair.private());
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        pool.add(parcel.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+
+        pool.remove(&parcel.hash(), &|_: &Public| 0.into(), RemovalReason::Canceled);
+        assert_eq!(None, pool.find(&parcel.hash()));
+        assert_eq!(Some(ParcelPoolStatus::Dropped(DropReason::Canceled)), pool.parcel_status(&parcel.hash()));
+    }
+
+    #[test]
+    fn parcels_expire_after_ttl() {
+        let keypair = Random.generate().unwrap();
+        let payment = |nonce: u64| {
+            let parcel = Parcel {
+                nonce: nonce.into(),
+                fee: 100.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        let (first, second) = (payment(0), payment(1));
+        pool.add(first.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        pool.add(second.clone(), ParcelOrigin::Local, 0, &fetch_account).unwrap();
+
+        let ttl = Duration::from_secs(60);
+        let now = Instant::now();
+        assert!(pool.remove_expired(ttl, now, &|_: &Public| 0.into()).is_empty());
+        assert_eq!(2, pool.status().pending);
+
+        let mut expired = pool.remove_expired(ttl, now + ttl + Duration::from_millis(1), &|_: &Public| 0.into());
+        expired.sort();
+        let mut hashes = vec![first.hash(), second.hash()];
+        hashes.sort();
+        assert_eq!(hashes, expired);
+        assert_eq!(0, pool.status().pending + pool.status().future);
+        assert_eq!(Some(ParcelPoolStatus::Dropped(DropReason::Expired)), pool.parcel_status(&first.hash()));
+    }
+
+    #[test]
+    fn sender_cannot_add_parcels_over_the_sender_limit() {
+        let keypair = Random.generate().unwrap();
+        let payment = |nonce: u64, fee: u64| {
+            let parcel = Parcel {
+                nonce: nonce.into(),
+                fee: fee.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        pool.set_sender_limit(2);
+        pool.add(payment(0, 100), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        pool.add(payment(1, 100), ParcelOrigin::External, 0, &fetch_account).unwrap();
+
+        assert_eq!(
+            Err(ParcelError::SenderLimitReached),
+            pool.add(payment(2, 100), ParcelOrigin::External, 0, &fetch_account)
+        );
+        assert_eq!(
+            Err(ParcelError::SenderLimitReached),
+            pool.add(payment(5, 100), ParcelOrigin::External, 0, &fetch_account)
+        );
+        // Replacing a parcel doesn't increase the number of the parcels of the sender.
+        assert_eq!(
+            Ok(ParcelImportResult::Current),
+            pool.add(payment(1, 200), ParcelOrigin::External, 0, &fetch_account)
+        );
+        assert_eq!(Ok(ParcelImportResult::Current), pool.add(payment(2, 100), ParcelOrigin::Local, 0, &fetch_account));
+        assert_eq!(3, pool.status().pending);
+    }
+
+    #[test]
+    fn future_parcels_are_limited_separately() {
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let future_payment = |fee: u64| {
+            let parcel = Parcel {
+                nonce: 1.into(),
+                fee: fee.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, Random.generate().unwrap().private())
+        };
+        let mut pool = MemPool::new();
+        pool.set_future_limit(1);
+        assert_eq!(
+            Ok(ParcelImportResult::Future),
+            pool.add(future_payment(200), ParcelOrigin::External, 0, &fetch_account)
+        );
+        assert_eq!(
+            Err(ParcelError::FutureLimitReached),
+            pool.add(future_payment(100), ParcelOrigin::External, 0, &fetch_account)
+        );
+        assert_eq!(1, pool.status().future);
+
+        pool.set_limit(16);
+        assert_eq!(
+            Err(ParcelError::FutureLimitReached),
+            pool.add(future_payment(100), ParcelOrigin::External, 0, &fetch_account)
+        );
+    }
+
+    #[test]
+    fn external_parcel_is_not_found_as_local() {
+        let keypair = Random.generate().unwrap();
+        let parcel = Parcel {
+            nonce: 0.into(),
+            fee: 100.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver: Default::default(),
+                amount: 0.into(),
+            },
+        };
+        let parcel = SignedParcel::new_with_sign(parcel, keypair.private());
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        pool.add(parcel.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(None, pool.find_local(&parcel.hash()));
+    }
+
+    #[test]
+    fn last_nonces_skip_the_gap_before_future_parcels() {
+        let keypair = Random.generate().unwrap();
+        let payment = |nonce: u64| {
+            let parcel = Parcel {
+                nonce: nonce.into(),
+                fee: 100.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 3.into(),
+            balance: 1_000_000.into(),
+        };
+        let mut pool = MemPool::new();
+        assert!(pool.last_nonces().is_empty());
+
+        pool.add(payment(3), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        let future = payment(5);
+        pool.add(future.clone(), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(Some(ParcelPoolStatus::Future), pool.parcel_status(&future.hash()));
+        assert_eq!(vec![(*keypair.public(), U256::from(3))], pool.last_nonces());
+
+        pool.add(payment(4), ParcelOrigin::External, 0, &fetch_account).unwrap();
+        assert_eq!(Some(ParcelPoolStatus::Pending), pool.parcel_status(&future.hash()));
+        assert_eq!(vec![(*keypair.public(), U256::from(5))], pool.last_nonces());
+    }
+
+    #[test]
+    fn prioritized_parcel_comes_first_with_its_predecessors() {
+        let payment = |keypair: &KeyPair, nonce: u64, fee: u64| {
+            let parcel = Parcel {
+                nonce: nonce.into(),
+                fee: fee.into(),
+                network_id: "tc".into(),
+                action: Action::Payment {
+                    receiver: Default::default(),
+                    amount: 0.into(),
+                },
+            };
+            SignedParcel::new_with_sign(parcel, keypair.private())
+        };
+        let fetch_account = |_: &Public| AccountDetails {
+            nonce: 0.into(),
+            balance: 1_000_000.into(),
+        };
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let mut pool = MemPool::new();
+        let cheap_first = payment(&alice, 0, 10);
+        let cheap_second = payment(&alice, 1, 10);
+        let expensive = payment(&bob, 0, 10_000);
+        for parcel in vec![cheap_first.clone(), cheap_second.clone(), expensive.clone()] {
+            pool.add(parcel, ParcelOrigin::External, 0, &fetch_account).unwrap();
+        }
+        assert!(!pool.prioritize(&H256::random()));
+
+        assert!(pool.prioritize(&cheap_second.hash()));
+        let top: Vec<_> = pool.top_parcels(usize::max_value()).iter().map(|parcel| parcel.hash()).collect();
+        assert_eq!(vec![cheap_first.hash(), cheap_second.hash(), expensive.hash()], top);
+    }
+
     fn create_parcel_order(fee: U256, transaction_count: usize) -> ParcelOrder {
         let transaction = Transaction::AssetTransfer {
             network_id: "tc".into(),
@@ -1318,7 +1984,7 @@ pub mod test {
             },
         };
         let signed = SignedParcel::new_with_sign(parcel, keypair.private());
-        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, 0);
+        let item = MemPoolItem::new(signed, ParcelOrigin::Local, 0, Instant::now(), 0);
         ParcelOrder::for_parcel(&item, 0.into())
     }
 }
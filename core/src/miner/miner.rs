@@ -14,14 +14,16 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use ckey::{public_to_address, Address, Password, Public};
+use clogger;
 use cstate::{StateError, TopLevelState};
-use ctypes::parcel::Error as ParcelError;
+use ctypes::parcel::{Action, Error as ParcelError, Parcel};
 use ctypes::BlockNumber;
 use parking_lot::{Mutex, RwLock};
 use primitives::{Bytes, H256, U256};
@@ -37,10 +39,16 @@ use super::super::header::Header;
 use super::super::parcel::{SignedParcel, UnverifiedParcel};
 use super::super::scheme::Scheme;
 use super::super::types::{BlockId, ParcelId};
-use super::mem_pool::{AccountDetails, MemPool, ParcelOrigin, RemovalReason};
+use super::execution_cache::ExecutionCache;
+use super::mem_pool::{
+    min_replacement_fee, AccountDetails, MemPool, ParcelOrigin, RemovalReason, DEFAULT_REPLACEMENT_FEE_BUMP,
+};
+use super::parcel_timestamps::{now_millis, TimestampedParcels};
+use super::remote_signer::RemoteSigner;
+use super::sealing_lease::{SealingLeaseExtension, SealingRole};
 use super::sealing_queue::SealingQueue;
 use super::work_notify::{NotifyWork, WorkPoster};
-use super::{MinerService, MinerStatus, ParcelImportResult};
+use super::{MinerService, MinerStatus, ParcelImportResult, ParcelPoolStatus, ParcelTimestamps, ResealStatus};
 
 /// Configures the behaviour of the miner.
 #[derive(Debug, PartialEq)]
@@ -57,12 +65,36 @@ pub struct MinerOptions {
     pub reseal_min_period: Duration,
     /// Maximum period between blocks (enables force sealing after that).
     pub reseal_max_period: Duration,
+    /// Parcels paying at least this fee are sealed right away, ignoring the minimum period between reseals.
+    pub urgent_reseal_fee: Option<U256>,
+    /// Reseal right away when the pending parcels fill this percentage of the next block.
+    pub urgent_reseal_fullness: Option<u8>,
+    /// Double the minimum period between reseals while the mem pool is idle, up to the maximum period.
+    pub reseal_backoff: bool,
     /// Maximum size of the mem pool.
     pub mem_pool_size: usize,
     /// Maximum memory usage of parcels in the queue (current / future).
     pub mem_pool_memory_limit: Option<usize>,
+    /// Maximum number of parcels of a sender in the mem pool. The parcels of the accounts of the node are not limited.
+    pub mem_pool_sender_limit: Option<usize>,
+    /// Maximum number of parcels in the mem pool which can't be mined until the parcels of lower nonces come.
+    pub mem_pool_future_limit: Option<usize>,
     /// How many historical work packages can we store before running out?
     pub work_queue_size: usize,
+    /// Role of this node if another node holds the same sealing key.
+    pub sealing_role: Option<SealingRole>,
+    /// Period of the leases announced by the primary.
+    pub lease_period: Duration,
+    /// Maximum size of the block body. The limit of the scheme applies if it's larger.
+    pub max_body_size: Option<usize>,
+    /// Maximum number of parcels in a block.
+    pub max_parcels_per_block: Option<usize>,
+    /// Minimal fee of parcels accepted to the mem pool.
+    pub minimal_fee: U256,
+    /// How much more fee, in percent, a parcel pays to replace the pending parcel of the same signer and nonce.
+    pub replacement_fee_bump: u8,
+    /// Parcels are removed from the mem pool after staying this long. They are kept until mined if it's None.
+    pub parcel_ttl: Option<Duration>,
 }
 
 impl Default for MinerOptions {
@@ -74,9 +106,21 @@ impl Default for MinerOptions {
             reseal_on_own_parcel: true,
             reseal_min_period: Duration::from_secs(2),
             reseal_max_period: Duration::from_secs(120),
+            urgent_reseal_fee: None,
+            urgent_reseal_fullness: None,
+            reseal_backoff: false,
             mem_pool_size: 8192,
             mem_pool_memory_limit: Some(2 * 1024 * 1024),
+            mem_pool_sender_limit: None,
+            mem_pool_future_limit: None,
             work_queue_size: 20,
+            sealing_role: None,
+            lease_period: Duration::from_secs(3),
+            max_body_size: None,
+            max_parcels_per_block: None,
+            minimal_fee: U256::zero(),
+            replacement_fee_bump: DEFAULT_REPLACEMENT_FEE_BUMP,
+            parcel_ttl: None,
         }
     }
 }
@@ -88,20 +132,33 @@ struct SealingWork {
 
 pub struct Miner {
     mem_pool: Arc<RwLock<MemPool>>,
+    timestamped_parcels: Mutex<TimestampedParcels>,
+    execution_cache: Mutex<ExecutionCache>,
     parcel_listener: RwLock<Vec<Box<Fn(&[H256]) + Send + Sync>>>,
     next_allowed_reseal: Mutex<Instant>,
     next_mandatory_reseal: RwLock<Instant>,
+    /// The minimum period between parcel-inspired reseals, which grows while the mem pool is idle.
+    reseal_period: Mutex<Duration>,
+    /// Set when the mem pool changes within the minimum period. The changes are sealed together when it passes.
+    reseal_deferred: AtomicBool,
+    /// Set when an urgent reseal skips the minimum period. The next urgent reseal waits for a new block or the period.
+    urgent_resealed: AtomicBool,
+    /// The latest block assembled from the mem pool. It is cleared when the best block or the mem pool changes.
+    pending_block: Mutex<Option<Block>>,
     author: RwLock<Address>,
     extra_data: RwLock<Bytes>,
     sealing_block_last_request: Mutex<u64>,
     sealing_work: Mutex<SealingWork>,
     engine: Arc<CodeChainEngine>,
-    options: MinerOptions,
+    options: RwLock<MinerOptions>,
 
     sealing_enabled: AtomicBool,
+    /// Seconds added to the clock when a new block is timestamped.
+    block_time_offset: RwLock<i64>,
 
     accounts: Option<Arc<AccountProvider>>,
     notifiers: RwLock<Vec<Box<NotifyWork>>>,
+    sealing_lease: Option<Arc<SealingLeaseExtension>>,
 }
 
 impl Miner {
@@ -120,17 +177,34 @@ impl Miner {
 
     fn new_raw(options: MinerOptions, scheme: &Scheme, accounts: Option<Arc<AccountProvider>>) -> Self {
         let mem_limit = options.mem_pool_memory_limit.unwrap_or_else(usize::max_value);
-        let mem_pool = Arc::new(RwLock::new(MemPool::with_limits(options.mem_pool_size, mem_limit)));
+        let mut mem_pool = MemPool::with_limits(options.mem_pool_size, mem_limit);
+        mem_pool.set_minimal_fee(options.minimal_fee);
+        mem_pool.set_replacement_fee_bump(options.replacement_fee_bump);
+        if let Some(limit) = options.mem_pool_sender_limit {
+            mem_pool.set_sender_limit(limit);
+        }
+        if let Some(limit) = options.mem_pool_future_limit {
+            mem_pool.set_future_limit(limit);
+        }
+        let mem_pool = Arc::new(RwLock::new(mem_pool));
         let notifiers: Vec<Box<NotifyWork>> = match options.new_work_notify.is_empty() {
             true => Vec::new(),
             false => vec![Box::new(WorkPoster::new(&options.new_work_notify))],
         };
+        let sealing_lease =
+            options.sealing_role.map(|role| Arc::new(SealingLeaseExtension::new(role, options.lease_period)));
 
         Self {
             mem_pool,
+            timestamped_parcels: Default::default(),
+            execution_cache: Default::default(),
             parcel_listener: RwLock::new(vec![]),
             next_allowed_reseal: Mutex::new(Instant::now()),
             next_mandatory_reseal: RwLock::new(Instant::now() + options.reseal_max_period),
+            reseal_period: Mutex::new(options.reseal_min_period),
+            reseal_deferred: AtomicBool::new(false),
+            urgent_resealed: AtomicBool::new(false),
+            pending_block: Mutex::new(None),
             author: RwLock::new(Address::default()),
             extra_data: RwLock::new(Vec::new()),
             sealing_block_last_request: Mutex::new(0),
@@ -139,11 +213,32 @@ impl Miner {
                 enabled: options.force_sealing || scheme.engine.seals_internally().is_some(),
             }),
             engine: scheme.engine.clone(),
-            options,
+            options: RwLock::new(options),
             sealing_enabled: AtomicBool::new(true),
+            block_time_offset: RwLock::new(0),
             accounts,
             notifiers: RwLock::new(notifiers),
+            sealing_lease,
+        }
+    }
+
+    /// The network extension which coordinates the nodes sharing the sealing key, if the role is configured.
+    pub fn sealing_lease_extension(&self) -> Option<Arc<SealingLeaseExtension>> {
+        self.sealing_lease.clone()
+    }
+
+    /// Seals as the address of the remote signer, which keeps the key out of this node.
+    pub fn set_remote_signer(&self, remote: Arc<RemoteSigner>) {
+        debug_assert!(self.engine_type() == EngineType::InternalSealing);
+        let address = remote.address();
+        ctrace!(MINER, "Set author to {:?} signing remotely", address);
+        *self.author.write() = address;
+        self.invalidate_pending_block();
+        self.sealing_work.lock().enabled = true;
+        if let Some(sealing_lease) = &self.sealing_lease {
+            sealing_lease.set_remote_signer(Arc::clone(&remote));
         }
+        self.engine.set_remote_signer(remote);
     }
 
     /// Set a callback to be notified about imported parcels' hashes.
@@ -156,24 +251,32 @@ impl Miner {
         self.map_pending_block(|b| b.state().clone(), latest_block_number)
     }
 
-    /// Get `Some` `clone()` of the current pending block or `None` if we're not sealing.
-    pub fn pending_block(&self, latest_block_number: BlockNumber) -> Option<Block> {
-        self.map_pending_block(|b| b.to_base(), latest_block_number)
-    }
-
     /// Get `Some` `clone()` of the current pending block header or `None` if we're not sealing.
     pub fn pending_block_header(&self, latest_block_number: BlockNumber) -> Option<Header> {
         self.map_pending_block(|b| b.header().clone(), latest_block_number)
     }
 
+    fn invalidate_pending_block(&self) {
+        *self.pending_block.lock() = None;
+    }
+
+    /// The maximum size of the block body, which is the smaller of the option and the limit of the scheme.
+    fn max_body_size(&self) -> usize {
+        let max_body_size = self.engine.params().max_body_size;
+        self.options.read().max_body_size.map_or(max_body_size, |size| cmp::min(size, max_body_size))
+    }
+
     /// Check is reseal is allowed and necessary.
     fn requires_reseal(&self, best_block: BlockNumber) -> bool {
-        let has_local_parcels = self.mem_pool.read().has_local_pending_parcels();
+        let (has_local_parcels, is_idle) = {
+            let mem_pool = self.mem_pool.read();
+            (mem_pool.has_local_pending_parcels(), mem_pool.status().pending == 0)
+        };
         let mut sealing_work = self.sealing_work.lock();
         if sealing_work.enabled {
             ctrace!(MINER, "requires_reseal: sealing enabled");
             let last_request = *self.sealing_block_last_request.lock();
-            let should_disable_sealing = !self.options.force_sealing
+            let should_disable_sealing = !self.options.read().force_sealing
                 && !has_local_parcels
                 && self.engine.seals_internally().is_none()
                 && best_block > last_request
@@ -194,7 +297,7 @@ impl Miner {
                 false
             } else {
                 // sealing enabled and we don't want to sleep.
-                *self.next_allowed_reseal.lock() = Instant::now() + self.options.reseal_min_period;
+                *self.next_allowed_reseal.lock() = Instant::now() + self.next_reseal_period(is_idle);
                 true
             }
         } else {
@@ -203,6 +306,56 @@ impl Miner {
         }
     }
 
+    /// Returns the minimum period until the next parcel-inspired reseal.
+    /// With the backoff enabled, it doubles every reseal while the mem pool is idle, up to the maximum period.
+    fn next_reseal_period(&self, is_idle: bool) -> Duration {
+        let options = self.options.read();
+        let mut period = self.reseal_period.lock();
+        *period = if options.reseal_backoff && is_idle {
+            let lower_bound = cmp::max(options.reseal_min_period, Duration::from_secs(1));
+            cmp::min(cmp::max(*period * 2, lower_bound), options.reseal_max_period)
+        } else {
+            options.reseal_min_period
+        };
+        *period
+    }
+
+    /// How much the pending parcels fill the next block, in percent.
+    fn pending_fullness(&self) -> u8 {
+        let (pending_size, pending_count) = {
+            let mem_pool = self.mem_pool.read();
+            (mem_pool.pending_size(), mem_pool.status().pending)
+        };
+        let by_size = pending_size * 100 / cmp::max(self.max_body_size(), 1);
+        let max_parcels = self.options.read().max_parcels_per_block;
+        let by_count = max_parcels.map_or(0, |max_parcels| pending_count * 100 / cmp::max(max_parcels, 1));
+        cmp::min(cmp::max(by_size, by_count), 100) as u8
+    }
+
+    /// Whether the new parcels should be sealed regardless of the minimum period between reseals.
+    fn is_reseal_urgent<'a, I>(&self, fees: I) -> bool
+    where
+        I: IntoIterator<Item = &'a U256>, {
+        let (urgent_fee, urgent_fullness) = {
+            let options = self.options.read();
+            (options.urgent_reseal_fee, options.urgent_reseal_fullness)
+        };
+        if let Some(urgent_fee) = urgent_fee {
+            if fees.into_iter().any(|fee| *fee >= urgent_fee) {
+                cdebug!(MINER, "Reseal for a parcel paying more than {}", urgent_fee);
+                return true
+            }
+        }
+        if let Some(urgent_fullness) = urgent_fullness {
+            let fullness = self.pending_fullness();
+            if fullness >= urgent_fullness {
+                cdebug!(MINER, "Reseal for the pending parcels filling {}% of a block", fullness);
+                return true
+            }
+        }
+        false
+    }
+
     fn add_parcels_to_pool<C: AccountData + BlockChain + RegularKeyOwner>(
         &self,
         client: &C,
@@ -238,15 +391,22 @@ impl Miner {
                         // This check goes here because verify_parcel takes SignedParcel parameter
                         self.engine.machine().verify_parcel(&parcel, &best_block_header, client)?;
 
-                        let origin = self
+                        let is_own = self
                             .accounts
                             .as_ref()
-                            .and_then(|accounts| match accounts.has_public(&parcel.signer_public()) {
-                                Ok(true) => Some(ParcelOrigin::Local),
-                                Ok(false) => None,
-                                Err(_) => None,
-                            })
-                            .unwrap_or(default_origin);
+                            .map_or(false, |accounts| accounts.has_public(&parcel.signer_public()).unwrap_or(false));
+                        let origin = if is_own {
+                            ParcelOrigin::Local
+                        } else {
+                            default_origin
+                        };
+                        // The parcels sent through RPC are local too, but only the parcels signed by the accounts
+                        // of the node can go above the sender limit.
+                        if origin == ParcelOrigin::Local && !is_own {
+                            mem_pool
+                                .check_sender_limit(&parcel.signer_public(), &parcel.nonce)
+                                .map_err(StateError::from)?;
+                        }
 
                         let fetch_account = |p: &Public| -> AccountDetails {
                             let a = client
@@ -268,6 +428,21 @@ impl Miner {
             })
             .collect();
 
+        {
+            let now = now_millis();
+            let span = clogger::current_span();
+            let mut timestamped_parcels = self.timestamped_parcels.lock();
+            for hash in &inserted {
+                timestamped_parcels.mark_seen(*hash, now);
+                if let Some(span) = span {
+                    timestamped_parcels.mark_span(*hash, span);
+                }
+            }
+        }
+
+        if !inserted.is_empty() {
+            self.invalidate_pending_block();
+        }
         for listener in &*self.parcel_listener.read() {
             listener(&inserted);
         }
@@ -275,6 +450,42 @@ impl Miner {
         results
     }
 
+    /// Records the inclusion of the parcels in the enacted blocks, and the finality of the parcels in the blocks
+    /// which become deep enough.
+    fn update_parcel_timestamps<C: BlockChain>(&self, chain: &C, enacted: &[H256], retracted: &[H256]) {
+        let now = now_millis();
+        let finality_depth = self.engine.finality_depth();
+        let mut timestamped_parcels = self.timestamped_parcels.lock();
+        for hash in retracted {
+            if let Some(block) = chain.block((*hash).into()) {
+                for parcel_hash in block.parcel_hashes() {
+                    timestamped_parcels.mark_retracted(&parcel_hash);
+                }
+            }
+        }
+        for hash in enacted {
+            let block = match chain.block((*hash).into()) {
+                Some(block) => block,
+                None => continue,
+            };
+            for parcel_hash in block.parcel_hashes() {
+                if let Some(span) = timestamped_parcels.mark_included(parcel_hash, now) {
+                    let _span = clogger::enter_span(span);
+                    cdebug!(MINER, "Parcel {} is included in block #{}", parcel_hash, block.number());
+                }
+            }
+            let final_number = match block.number().checked_sub(finality_depth) {
+                Some(number) => number,
+                None => continue,
+            };
+            if let Some(final_block) = chain.block(BlockId::Number(final_number)) {
+                for parcel_hash in final_block.parcel_hashes() {
+                    timestamped_parcels.mark_finalized(&parcel_hash, now);
+                }
+            }
+        }
+    }
+
     /// Returns true if we had to prepare new pending block.
     fn prepare_work_sealing<C: AccountData + BlockChain + BlockProducer + RegularKeyOwner>(&self, client: &C) -> bool {
         ctrace!(MINER, "prepare_work_sealing: entering");
@@ -367,23 +578,44 @@ impl Miner {
         chain: &C,
     ) -> (ClosedBlock, Option<H256>) {
         let (parcels, mut open_block, original_work_hash) = {
-            let max_body_size = self.engine.params().max_body_size;
-            let parcels = self.mem_pool.read().top_parcels(max_body_size);
+            let parcels = self.mem_pool.read().top_parcels(self.max_body_size());
             let mut sealing_work = self.sealing_work.lock();
             let last_work_hash = sealing_work.queue.peek_last_ref().map(|pb| pb.block().header().hash());
 
             ctrace!(MINER, "prepare_block: No existing work - making new block");
-            let open_block = chain.prepare_open_block(self.author(), self.extra_data());
+            let mut open_block = chain.prepare_open_block(self.author(), self.extra_data());
+            let block_time_offset = *self.block_time_offset.read();
+            if block_time_offset != 0 {
+                let parent_hash = *open_block.header().parent_hash();
+                let parent_header = chain.block_header(BlockId::Hash(parent_hash)).expect("Parent header MUST exist");
+                let now = now_millis() as i64 / 1000 + block_time_offset;
+                open_block.set_timestamp(cmp::max(now, parent_header.timestamp() as i64 + 1) as u64);
+            }
 
             (parcels, open_block, last_work_hash)
         };
 
         let mut invalid_parcels = HashSet::new();
         let block_number = open_block.block().header().number();
+        let parent_hash = *open_block.header().parent_hash();
+
+        let max_parcels = self.options.read().max_parcels_per_block.unwrap_or_else(usize::max_value);
+
+        // Restore the parcels that the previous pending block on the same parent already executed.
+        let mut reused: usize = 0;
+        let hashes: Vec<_> = parcels.iter().map(|parcel| parcel.hash()).collect();
+        let reusable = self.execution_cache.lock().reusable(&parent_hash, &hashes);
+        if let Some(executed) = reusable.filter(|executed| executed.parcels().len() <= max_parcels) {
+            reused = executed.parcels().len();
+            open_block.restore_parcels(executed);
+        }
 
-        let mut parcel_count: usize = 0;
+        let mut parcel_count: usize = reused;
         let parcel_total = parcels.len();
-        for parcel in parcels {
+        for parcel in parcels.into_iter().skip(reused) {
+            if parcel_count >= max_parcels {
+                break
+            }
             let hash = parcel.hash();
             let start = Instant::now();
             // Check whether parcel type is allowed for sender
@@ -413,7 +645,8 @@ impl Miner {
                 } // imported ok
             }
         }
-        ctrace!(MINER, "Pushed {}/{} parcels", parcel_count, parcel_total);
+        ctrace!(MINER, "Pushed {}/{} parcels, reusing {}", parcel_count, parcel_total, reused);
+        self.execution_cache.lock().update(parent_hash, open_block.executed_parcels());
 
         let (parcels_root, invoices_root) = {
             let parent_hash = open_block.header().parent_hash();
@@ -434,6 +667,7 @@ impl Miner {
                 queue.remove(&hash, &fetch_nonce, RemovalReason::Invalid);
             }
         }
+        *self.pending_block.lock() = Some(block.to_base());
         (block, original_work_hash)
     }
 
@@ -442,7 +676,7 @@ impl Miner {
     where
         C: BlockChain + ImportSealedBlock, {
         if block.parcels().is_empty()
-            && !self.options.force_sealing
+            && !self.options.read().force_sealing
             && Instant::now() <= *self.next_mandatory_reseal.read()
         {
             ctrace!(MINER, "seal_block_internally: no sealing.");
@@ -459,7 +693,7 @@ impl Miner {
             // Save proposal for later seal submission and broadcast it.
             Seal::Proposal(seal) => {
                 ctrace!(MINER, "Received a Proposal seal.");
-                *self.next_mandatory_reseal.write() = Instant::now() + self.options.reseal_max_period;
+                *self.next_mandatory_reseal.write() = Instant::now() + self.options.read().reseal_max_period;
                 {
                     let mut sealing_work = self.sealing_work.lock();
                     sealing_work.queue.push(block.clone());
@@ -479,7 +713,7 @@ impl Miner {
             }
             // Directly import a regular sealed block.
             Seal::Regular(seal) => {
-                *self.next_mandatory_reseal.write() = Instant::now() + self.options.reseal_max_period;
+                *self.next_mandatory_reseal.write() = Instant::now() + self.options.read().reseal_max_period;
                 block
                     .lock()
                     .seal(&*self.engine, seal)
@@ -496,9 +730,22 @@ impl Miner {
         }
     }
 
-    /// Are we allowed to do a non-mandatory reseal?
-    fn parcel_reseal_allowed(&self) -> bool {
-        self.sealing_enabled.load(Ordering::Relaxed) && (Instant::now() > *self.next_allowed_reseal.lock())
+    /// Are we allowed to do a non-mandatory reseal? An urgent reseal doesn't wait for the minimum period, but only once
+    /// until a new block is imported or the period passes, so that a burst of urgent parcels doesn't reseal each time.
+    /// A reseal which is not allowed yet is deferred until the minimum period passes.
+    fn parcel_reseal_allowed(&self, is_urgent: bool) -> bool {
+        if !self.sealing_enabled.load(Ordering::Relaxed) {
+            return false
+        }
+        if Instant::now() > *self.next_allowed_reseal.lock() {
+            self.urgent_resealed.store(false, Ordering::Relaxed);
+            return true
+        }
+        if is_urgent && !self.urgent_resealed.swap(true, Ordering::Relaxed) {
+            return true
+        }
+        self.reseal_deferred.store(true, Ordering::Relaxed);
+        false
     }
 
     fn map_pending_block<F, T>(&self, f: F, latest_block_number: BlockNumber) -> Option<T>
@@ -535,18 +782,21 @@ impl MinerService for Miner {
     }
 
     fn set_author(&self, address: Address, password: Option<Password>) -> Result<(), SignError> {
-        *self.author.write() = address;
-
-        if self.engine_type() == EngineType::InternalSealing && self.engine.seals_internally().is_some() {
+        if self.engine_type() == EngineType::InternalSealing {
             if let Some(ref ap) = self.accounts {
                 ctrace!(MINER, "Set author to {:?}", address);
-                // Sign test message
+                // Sign test message before changing the author, so that a failure keeps the previous one.
                 ap.sign(address, password.clone(), Default::default())?;
+                *self.author.write() = address;
+                self.invalidate_pending_block();
                 // Limit the scope of the locks.
                 {
                     let mut sealing_work = self.sealing_work.lock();
                     sealing_work.enabled = true;
                 }
+                if let Some(sealing_lease) = &self.sealing_lease {
+                    sealing_lease.set_signer(ap.clone(), address, password.clone());
+                }
                 self.engine.set_signer(ap.clone(), address, password);
                 Ok(())
             } else {
@@ -554,6 +804,8 @@ impl MinerService for Miner {
                 Err(SignError::NotFound)
             }
         } else {
+            *self.author.write() = address;
+            self.invalidate_pending_block();
             Ok(())
         }
     }
@@ -582,17 +834,80 @@ impl MinerService for Miner {
         self.mem_pool.write().set_limit(limit)
     }
 
+    fn set_max_body_size(&self, size: usize) {
+        self.options.write().max_body_size = Some(size);
+    }
+
+    fn set_max_parcels_per_block(&self, count: usize) {
+        self.options.write().max_parcels_per_block = Some(count);
+    }
+
+    fn set_reseal_min_period(&self, period: Duration) {
+        self.options.write().reseal_min_period = period;
+    }
+
+    fn set_reseal_max_period(&self, period: Duration) {
+        self.options.write().reseal_max_period = period;
+    }
+
+    fn set_urgent_reseal_fee(&self, fee: Option<U256>) {
+        self.options.write().urgent_reseal_fee = fee;
+    }
+
+    fn set_urgent_reseal_fullness(&self, fullness: Option<u8>) {
+        self.options.write().urgent_reseal_fullness = fullness;
+    }
+
+    fn set_reseal_backoff(&self, backoff: bool) {
+        self.options.write().reseal_backoff = backoff;
+    }
+
+    fn set_replacement_fee_bump(&self, fee_bump: u8) {
+        self.options.write().replacement_fee_bump = fee_bump;
+        self.mem_pool.write().set_replacement_fee_bump(fee_bump);
+    }
+
+    fn reseal_status(&self) -> ResealStatus {
+        let now = Instant::now();
+        let until = |instant: Instant| {
+            if instant > now {
+                instant - now
+            } else {
+                Duration::from_secs(0)
+            }
+        };
+        let (urgent_fee, urgent_fullness, backoff) = {
+            let options = self.options.read();
+            (options.urgent_reseal_fee, options.urgent_reseal_fullness, options.reseal_backoff)
+        };
+        ResealStatus {
+            period: *self.reseal_period.lock(),
+            next_allowed_reseal: until(*self.next_allowed_reseal.lock()),
+            deferred: self.reseal_deferred.load(Ordering::Relaxed),
+            next_mandatory_reseal: until(*self.next_mandatory_reseal.read()),
+            pending_fullness: self.pending_fullness(),
+            urgent_fee,
+            urgent_fullness,
+            backoff,
+        }
+    }
+
     fn chain_new_blocks<C>(
         &self,
         chain: &C,
         _imported: &[H256],
         _invalid: &[H256],
-        _enacted: &[H256],
+        enacted: &[H256],
         retracted: &[H256],
     ) where
         C: AccountData + BlockChain + BlockProducer + ImportSealedBlock + RegularKeyOwner, {
         ctrace!(MINER, "chain_new_blocks");
 
+        self.update_parcel_timestamps(chain, enacted, retracted);
+
+        self.execution_cache.lock().clear();
+        self.invalidate_pending_block();
+
         // Then import all parcels...
         {
             let mut mem_pool = self.mem_pool.write();
@@ -619,6 +934,15 @@ impl MinerService for Miner {
             let mut mem_pool = self.mem_pool.write();
             mem_pool.remove_old(&fetch_account, time);
         }
+
+        if !enacted.is_empty() {
+            self.urgent_resealed.store(false, Ordering::Relaxed);
+        }
+        // The pending block is stale on the new best block. It's rebuilt without waiting for the minimum period.
+        if !enacted.is_empty() && self.mem_pool.read().status().pending != 0 {
+            *self.next_allowed_reseal.lock() = Instant::now();
+            self.reseal_deferred.store(true, Ordering::Relaxed);
+        }
     }
 
     fn can_produce_work_package(&self) -> bool {
@@ -634,6 +958,10 @@ impl MinerService for Miner {
         C: AccountData + BlockChain + BlockProducer + ImportSealedBlock + RegularKeyOwner, {
         ctrace!(MINER, "update_sealing: preparing a block");
 
+        if !self.sealing_lease.as_ref().map_or(true, |sealing_lease| sealing_lease.is_holding()) {
+            ctrace!(MINER, "update_sealing: the standby does not hold the sealing lease");
+            return
+        }
         if self.requires_reseal(chain.chain_info().best_block_number) {
             let (block, original_work_hash) = self.prepare_block(chain);
 
@@ -653,6 +981,18 @@ impl MinerService for Miner {
         }
     }
 
+    fn update_deferred_sealing<C>(&self, chain: &C)
+    where
+        C: AccountData + BlockChain + BlockProducer + ImportSealedBlock + RegularKeyOwner, {
+        if !self.reseal_deferred.load(Ordering::Relaxed) || !self.parcel_reseal_allowed(false) {
+            return
+        }
+        if self.reseal_deferred.swap(false, Ordering::Relaxed) {
+            ctrace!(MINER, "update_deferred_sealing: sealing the deferred changes of the mem pool");
+            self.update_sealing(chain);
+        }
+    }
+
     fn submit_seal<C: ImportSealedBlock>(&self, chain: &C, block_hash: H256, seal: Vec<Bytes>) -> Result<(), Error> {
         let result = if let Some(b) = self.sealing_work.lock().queue.take_used_if(|b| &b.hash() == &block_hash) {
             ctrace!(
@@ -699,12 +1039,18 @@ impl MinerService for Miner {
         parcels: Vec<UnverifiedParcel>,
     ) -> Vec<Result<ParcelImportResult, Error>> {
         ctrace!(EXTERNAL_PARCEL, "Importing external parcels");
+        let fees: Vec<U256> = parcels.iter().map(|parcel| parcel.fee).collect();
         let results = {
             let mut mem_pool = self.mem_pool.write();
             self.add_parcels_to_pool(client, parcels, ParcelOrigin::External, &mut mem_pool)
         };
 
-        if !results.is_empty() && self.options.reseal_on_external_parcel && self.parcel_reseal_allowed() {
+        let reseal_on_external_parcel = self.options.read().reseal_on_external_parcel;
+        let is_urgent = || {
+            let imported_fees = results.iter().zip(&fees).filter(|(result, _)| result.is_ok()).map(|(_, fee)| fee);
+            self.is_reseal_urgent(imported_fees)
+        };
+        if !results.is_empty() && reseal_on_external_parcel && self.parcel_reseal_allowed(is_urgent()) {
             // ------------------------------------------------------------------
             // | NOTE Code below requires mem_pool and sealing_queue locks.     |
             // | Make sure to release the locks before calling that method.     |
@@ -720,6 +1066,7 @@ impl MinerService for Miner {
         parcel: SignedParcel,
     ) -> Result<ParcelImportResult, Error> {
         ctrace!(OWN_PARCEL, "Importing parcel: {:?}", parcel);
+        let fee = parcel.fee;
 
         let imported = {
             // Be sure to release the lock before we call prepare_work_sealing
@@ -746,7 +1093,8 @@ impl MinerService for Miner {
         // | NOTE Code below requires mem_pool and sealing_queue locks.     |
         // | Make sure to release the locks before calling that method.     |
         // ------------------------------------------------------------------
-        if imported.is_ok() && self.options.reseal_on_own_parcel && self.parcel_reseal_allowed() {
+        let reseal_on_own_parcel = self.options.read().reseal_on_own_parcel;
+        if imported.is_ok() && reseal_on_own_parcel && self.parcel_reseal_allowed(self.is_reseal_urgent(&[fee])) {
             // Make sure to do it after parcel is imported and lock is dropped.
             // We need to create pending block and enable sealing.
             if self.engine.seals_internally().unwrap_or(false) || !self.prepare_work_sealing(chain) {
@@ -759,9 +1107,84 @@ impl MinerService for Miner {
         imported
     }
 
+    fn cancel_parcel<C: MiningBlockChainClient>(&self, chain: &C, hash: &H256) -> Result<Option<H256>, Error> {
+        let parcel = match self.mem_pool.read().find_local(hash) {
+            Some(parcel) => parcel,
+            None => return Ok(None),
+        };
+        let accounts = self.accounts.as_ref().expect("Local parcels are signed by the keys of the account provider");
+
+        let signer_public = parcel.signer_public();
+        let fee_payer = chain
+            .regular_key_owner(&signer_public, BlockId::Latest.into())
+            .unwrap_or_else(|| public_to_address(&signer_public));
+        let fee_bump = self.mem_pool.read().replacement_fee_bump();
+        let fee = cmp::max(min_replacement_fee(&parcel.fee, fee_bump), self.engine.params().min_parcel_cost);
+        let replacement = Parcel {
+            nonce: parcel.nonce,
+            fee: cmp::max(fee, self.minimal_fee()),
+            network_id: parcel.network_id,
+            action: Action::Payment {
+                receiver: fee_payer,
+                amount: 0.into(),
+            },
+        };
+        let signature = accounts.sign(public_to_address(&signer_public), None, replacement.hash())?;
+        let replacement = SignedParcel::new(UnverifiedParcel::new(replacement, signature))?;
+        let replacement_hash = replacement.hash();
+        self.import_own_parcel(chain, replacement)?;
+        cinfo!(OWN_PARCEL, "Replaced {} with {} to cancel it", hash, replacement_hash);
+        Ok(Some(replacement_hash))
+    }
+
+    fn remove_parcel<C: MiningBlockChainClient>(&self, chain: &C, hash: &H256) -> bool {
+        let fetch_nonce = |p: &Public| {
+            let a = chain.regular_key_owner(p, BlockId::Latest.into()).unwrap_or_else(|| public_to_address(p));
+            chain.latest_nonce(&a)
+        };
+        let mut mem_pool = self.mem_pool.write();
+        if mem_pool.find(hash).is_none() {
+            return false
+        }
+        mem_pool.remove(hash, &fetch_nonce, RemovalReason::Canceled);
+        drop(mem_pool);
+        self.invalidate_pending_block();
+        cinfo!(MINER, "Parcel {} is removed by the operator", hash);
+        true
+    }
+
+    fn remove_expired_parcels<C: MiningBlockChainClient>(&self, chain: &C) -> Vec<H256> {
+        let ttl = match self.options.read().parcel_ttl {
+            Some(ttl) => ttl,
+            None => return Vec::new(),
+        };
+        let fetch_nonce = |p: &Public| {
+            let a = chain.regular_key_owner(p, BlockId::Latest.into()).unwrap_or_else(|| public_to_address(p));
+            chain.latest_nonce(&a)
+        };
+        let expired = self.mem_pool.write().remove_expired(ttl, Instant::now(), &fetch_nonce);
+        if !expired.is_empty() {
+            self.invalidate_pending_block();
+            cdebug!(MINER, "{} parcels expired in the mem pool", expired.len());
+        }
+        expired
+    }
+
+    fn prioritize_parcel(&self, hash: &H256) -> bool {
+        let prioritized = self.mem_pool.write().prioritize(hash);
+        if prioritized {
+            cinfo!(MINER, "Parcel {} is prioritized by the operator", hash);
+        }
+        prioritized
+    }
+
+    fn pending_block<C: MiningBlockChainClient>(&self, chain: &C) -> Option<Block> {
+        let best_block_hash = chain.chain_info().best_block_hash;
+        self.pending_block.lock().as_ref().filter(|block| *block.header.parent_hash() == best_block_hash).cloned()
+    }
+
     fn ready_parcels(&self) -> Vec<SignedParcel> {
-        let max_body_size = self.engine.params().max_body_size;
-        self.mem_pool.read().top_parcels(max_body_size)
+        self.mem_pool.read().top_parcels(self.max_body_size())
     }
 
     /// Get a list of all future parcels.
@@ -769,6 +1192,43 @@ impl MinerService for Miner {
         self.mem_pool.read().future_parcels()
     }
 
+    fn next_nonce<C: MiningBlockChainClient>(&self, chain: &C, address: &Address) -> U256 {
+        let state_nonce = chain.latest_nonce(address);
+        // The owners are read from the state after the mem pool is unlocked.
+        let last_nonces = self.mem_pool.read().last_nonces();
+        last_nonces
+            .into_iter()
+            .filter(|(signer_public, _)| {
+                let owner = chain.regular_key_owner(signer_public, BlockId::Latest.into());
+                owner.unwrap_or_else(|| public_to_address(signer_public)) == *address
+            })
+            .map(|(_, nonce)| nonce + U256::one())
+            .fold(state_nonce, cmp::max)
+    }
+
+    fn parcel_pool_status(&self, hash: &H256) -> Option<ParcelPoolStatus> {
+        self.mem_pool.read().parcel_status(hash)
+    }
+
+    fn dropped_parcels(&self) -> Vec<H256> {
+        self.mem_pool.read().dropped_parcels()
+    }
+
+    fn parcel_timestamps(&self, hash: &H256) -> Option<ParcelTimestamps> {
+        self.timestamped_parcels.lock().get(hash)
+    }
+
+    fn note_parcels_broadcast(&self, hashes: &[H256]) {
+        let now = now_millis();
+        let mut timestamped_parcels = self.timestamped_parcels.lock();
+        for hash in hashes {
+            if let Some(span) = timestamped_parcels.mark_broadcast(*hash, now) {
+                let _span = clogger::enter_span(span);
+                cdebug!(SYNC_PARCEL, "Parcel {} is broadcast", hash);
+            }
+        }
+    }
+
     fn start_sealing<C: MiningBlockChainClient>(&self, client: &C) {
         cdebug!(MINER, "Start sealing");
         self.sealing_enabled.store(true, Ordering::Relaxed);
@@ -776,7 +1236,7 @@ impl MinerService for Miner {
         // | NOTE Code below requires mem_pool and sealing_queue locks.     |
         // | Make sure to release the locks before calling that method.     |
         // ------------------------------------------------------------------
-        if self.parcel_reseal_allowed() {
+        if self.parcel_reseal_allowed(false) {
             cdebug!(MINER, "Update sealing");
             self.update_sealing(client);
         }
@@ -786,4 +1246,207 @@ impl MinerService for Miner {
         cdebug!(MINER, "Stop sealing");
         self.sealing_enabled.store(false, Ordering::Relaxed);
     }
+
+    fn mine_block<C: MiningBlockChainClient>(&self, client: &C) -> Option<H256> {
+        let (block, _) = self.prepare_block(client);
+        let parent_header = client.block_header((*block.header().parent_hash()).into())?.decode();
+        match self.engine.generate_seal(block.block(), &parent_header) {
+            Seal::Regular(seal) => {
+                *self.next_mandatory_reseal.write() = Instant::now() + self.options.read().reseal_max_period;
+                let sealed = block
+                    .lock()
+                    .seal(&*self.engine, seal)
+                    .map_err(|e| cwarn!(MINER, "ERROR: seal failed when given internally generated seal: {}", e))
+                    .ok()?;
+                client.import_sealed_block(sealed).ok()
+            }
+            Seal::Proposal(_) | Seal::None => None,
+        }
+    }
+
+    fn set_block_time(&self, timestamp: u64) {
+        let now = now_millis() / 1000;
+        *self.block_time_offset.write() = timestamp as i64 - now as i64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{Generator, Private, Random};
+
+    use super::super::super::client::{ChainInfo, TestBlockChainClient};
+    use super::*;
+
+    fn payment(nonce: u64, private: &Private) -> SignedParcel {
+        let parcel = Parcel {
+            nonce: nonce.into(),
+            fee: 10.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver: Default::default(),
+                amount: 0.into(),
+            },
+        };
+        SignedParcel::new_with_sign(parcel, private)
+    }
+
+    #[test]
+    fn pending_block_is_none_until_prepared() {
+        let client = TestBlockChainClient::new();
+        let miner = Miner::with_scheme(&client.scheme);
+        assert!(miner.pending_block(&client).is_none());
+        assert!(miner.pending_block(&client).is_none(), "Asking for the pending block must not prepare it");
+    }
+
+    #[test]
+    fn pending_block_returns_the_prepared_block() {
+        let client = TestBlockChainClient::new();
+        let miner = Miner::with_scheme(&client.scheme);
+        let (block, _) = miner.prepare_block(&client);
+        let pending = miner.pending_block(&client).expect("The block is prepared");
+        assert_eq!(block.header().hash(), pending.header.hash());
+    }
+
+    #[test]
+    fn outdated_pending_block_is_not_returned() {
+        let client = TestBlockChainClient::new();
+        let miner = Miner::with_scheme(&client.scheme);
+        miner.prepare_block(&client);
+        client.add_blocks(1, 0);
+        assert!(miner.pending_block(&client).is_none());
+    }
+
+    #[test]
+    fn only_parcels_of_own_accounts_go_above_sender_limit() {
+        let client = TestBlockChainClient::new();
+        let accounts = AccountProvider::transient_provider();
+        let own = Random.generate().unwrap();
+        accounts.insert_account(*own.private(), &"password".into()).unwrap();
+        let other = Random.generate().unwrap();
+        client.set_balance(own.address(), 1_000.into());
+        client.set_balance(other.address(), 1_000.into());
+
+        let options = MinerOptions {
+            reseal_on_own_parcel: false,
+            mem_pool_sender_limit: Some(1),
+            ..Default::default()
+        };
+        let miner = Miner::new(options, &client.scheme, Some(accounts));
+
+        // A parcel sent through RPC is local, but it isn't signed by the accounts of the node.
+        assert!(miner.import_own_parcel(&client, payment(0, other.private())).is_ok());
+        match miner.import_own_parcel(&client, payment(1, other.private())) {
+            Err(Error::State(StateError::Parcel(ParcelError::SenderLimitReached))) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        assert!(miner.import_own_parcel(&client, payment(0, own.private())).is_ok());
+        assert!(miner.import_own_parcel(&client, payment(1, own.private())).is_ok());
+    }
+
+    #[test]
+    fn next_nonce_follows_the_pending_parcels() {
+        let client = TestBlockChainClient::new();
+        let sender = Random.generate().unwrap();
+        client.set_balance(sender.address(), 1_000.into());
+        let options = MinerOptions {
+            reseal_on_own_parcel: false,
+            ..Default::default()
+        };
+        let miner = Miner::new(options, &client.scheme, None);
+        assert_eq!(U256::zero(), miner.next_nonce(&client, &sender.address()));
+
+        miner.import_own_parcel(&client, payment(0, sender.private())).unwrap();
+        miner.import_own_parcel(&client, payment(2, sender.private())).unwrap();
+        assert_eq!(U256::one(), miner.next_nonce(&client, &sender.address()));
+        assert_eq!(U256::zero(), miner.next_nonce(&client, &Address::default()));
+    }
+
+    #[test]
+    fn reseal_within_min_period_is_deferred() {
+        let client = TestBlockChainClient::new();
+        let miner = Miner::with_scheme(&client.scheme);
+        *miner.next_allowed_reseal.lock() = Instant::now() + Duration::from_secs(60);
+
+        assert!(miner.parcel_reseal_allowed(true), "Urgent reseals don't wait for the minimum period");
+        assert!(!miner.reseal_deferred.load(Ordering::Relaxed));
+
+        assert!(!miner.parcel_reseal_allowed(false));
+        assert!(miner.reseal_deferred.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn urgent_reseal_skips_min_period_once_per_block() {
+        let client = TestBlockChainClient::new();
+        let miner = Miner::with_scheme(&client.scheme);
+        *miner.next_allowed_reseal.lock() = Instant::now() + Duration::from_secs(60);
+
+        assert!(miner.parcel_reseal_allowed(true));
+        assert!(!miner.parcel_reseal_allowed(true), "The second urgent parcel waits for the minimum period");
+
+        client.add_blocks(1, 0);
+        let enacted = vec![client.chain_info().best_block_hash];
+        miner.chain_new_blocks(&client, &[], &[], &enacted, &[]);
+        *miner.next_allowed_reseal.lock() = Instant::now() + Duration::from_secs(60);
+        assert!(miner.parcel_reseal_allowed(true), "An urgent parcel skips the minimum period again on a new block");
+    }
+
+    #[test]
+    fn reseal_is_urgent_for_high_fee_or_full_block() {
+        let client = TestBlockChainClient::new();
+        let sender = Random.generate().unwrap();
+        client.set_balance(sender.address(), 1_000.into());
+        let options = MinerOptions {
+            reseal_on_own_parcel: false,
+            max_parcels_per_block: Some(2),
+            ..Default::default()
+        };
+        let miner = Miner::new(options, &client.scheme, None);
+        assert!(!miner.is_reseal_urgent(&[U256::from(1_000)]), "Nothing is urgent unless the thresholds are set");
+
+        miner.set_urgent_reseal_fee(Some(100.into()));
+        assert!(!miner.is_reseal_urgent(&[U256::from(10), U256::from(99)]));
+        assert!(miner.is_reseal_urgent(&[U256::from(10), U256::from(100)]));
+
+        miner.set_urgent_reseal_fullness(Some(50));
+        assert!(!miner.is_reseal_urgent(&[]));
+        miner.import_own_parcel(&client, payment(0, sender.private())).unwrap();
+        assert_eq!(50, miner.pending_fullness());
+        assert!(miner.is_reseal_urgent(&[]));
+    }
+
+    #[test]
+    fn reseal_period_backs_off_while_idle() {
+        let client = TestBlockChainClient::new();
+        let options = MinerOptions {
+            reseal_min_period: Duration::from_secs(2),
+            reseal_max_period: Duration::from_secs(10),
+            reseal_backoff: true,
+            ..Default::default()
+        };
+        let miner = Miner::new(options, &client.scheme, None);
+
+        assert_eq!(Duration::from_secs(4), miner.next_reseal_period(true));
+        assert_eq!(Duration::from_secs(8), miner.next_reseal_period(true));
+        assert_eq!(Duration::from_secs(10), miner.next_reseal_period(true), "The period is capped by the maximum");
+        assert_eq!(Duration::from_secs(2), miner.next_reseal_period(false), "A parcel resets the period");
+
+        miner.set_reseal_backoff(false);
+        assert_eq!(Duration::from_secs(2), miner.next_reseal_period(true));
+    }
+
+    #[test]
+    fn deferred_reseal_is_sealed_once_min_period_passes() {
+        let client = TestBlockChainClient::new();
+        let miner = Miner::with_scheme(&client.scheme);
+        *miner.next_allowed_reseal.lock() = Instant::now() + Duration::from_secs(60);
+        assert!(!miner.parcel_reseal_allowed(false));
+
+        miner.update_deferred_sealing(&client);
+        assert!(miner.reseal_deferred.load(Ordering::Relaxed), "The reseal is still deferred within the period");
+
+        *miner.next_allowed_reseal.lock() = Instant::now() - Duration::from_secs(1);
+        miner.update_deferred_sealing(&client);
+        assert!(!miner.reseal_deferred.load(Ordering::Relaxed));
+    }
 }
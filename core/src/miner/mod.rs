@@ -14,21 +14,30 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod dropped_parcels;
+mod execution_cache;
 mod local_parcels;
 mod mem_pool;
 mod miner;
+mod parcel_timestamps;
+mod remote_signer;
+mod sealing_lease;
 mod sealing_queue;
 mod stratum;
 mod work_notify;
 
+use std::time::Duration;
+
 use ckey::{Address, Password};
 use cstate::TopStateInfo;
 use primitives::{Bytes, H256, U256};
 
 pub use self::miner::{Miner, MinerOptions};
+pub use self::remote_signer::RemoteSigner;
+pub use self::sealing_lease::{SealingLeaseExtension, SealingRole};
 pub use self::stratum::{Config as StratumConfig, Error as StratumError, Stratum};
 use super::account_provider::SignError;
-use super::block::ClosedBlock;
+use super::block::{Block, ClosedBlock};
 use super::client::{
     AccountData, BlockChain, BlockProducer, ImportSealedBlock, MiningBlockChainClient, RegularKeyOwner,
 };
@@ -68,6 +77,33 @@ pub trait MinerService: Send + Sync {
     /// Set maximal number of parcels kept in the queue (both current and future).
     fn set_parcels_limit(&self, limit: usize);
 
+    /// Set maximal size of the block body. The limit of the scheme applies if it's larger.
+    fn set_max_body_size(&self, size: usize);
+
+    /// Set maximal number of parcels in a block.
+    fn set_max_parcels_per_block(&self, count: usize);
+
+    /// Set minimum period between parcel-inspired reseals.
+    fn set_reseal_min_period(&self, period: Duration);
+
+    /// Set maximum period between blocks.
+    fn set_reseal_max_period(&self, period: Duration);
+
+    /// Set the fee of parcels which are sealed right away, ignoring the minimum period between reseals.
+    fn set_urgent_reseal_fee(&self, fee: Option<U256>);
+
+    /// Set the percentage of the next block which the pending parcels fill to be sealed right away.
+    fn set_urgent_reseal_fullness(&self, fullness: Option<u8>);
+
+    /// Set whether the minimum period between reseals grows while the mem pool is idle.
+    fn set_reseal_backoff(&self, backoff: bool);
+
+    /// Set how much more fee, in percent, a parcel pays to replace the parcel of the same signer and nonce.
+    fn set_replacement_fee_bump(&self, fee_bump: u8);
+
+    /// Returns how the miner currently reseals blocks.
+    fn reseal_status(&self) -> ResealStatus;
+
     /// Called when blocks are imported to chain, updates parcels queue.
     fn chain_new_blocks<C>(&self, chain: &C, imported: &[H256], invalid: &[H256], enacted: &[H256], retracted: &[H256])
     where
@@ -84,6 +120,11 @@ pub trait MinerService: Send + Sync {
     where
         C: AccountData + BlockChain + BlockProducer + ImportSealedBlock + RegularKeyOwner;
 
+    /// Reseals the mem pool changes deferred by the minimum period between reseals, once the period passes.
+    fn update_deferred_sealing<C>(&self, chain: &C)
+    where
+        C: AccountData + BlockChain + BlockProducer + ImportSealedBlock + RegularKeyOwner;
+
     /// Submit `seal` as a valid solution for the header of `pow_hash`.
     /// Will check the seal, but not actually insert the block into the chain.
     fn submit_seal<C: ImportSealedBlock>(&self, chain: &C, pow_hash: H256, seal: Vec<Bytes>) -> Result<(), Error>;
@@ -109,17 +150,60 @@ pub trait MinerService: Send + Sync {
         parcel: SignedParcel,
     ) -> Result<ParcelImportResult, Error>;
 
+    /// Replaces the pending parcel of a local account with a parcel of the same nonce,
+    /// which pays nothing to the fee payer itself, with the lowest fee that can replace it.
+    /// Returns the hash of the replacement, or `None` if the parcel is not a local one in the pool.
+    fn cancel_parcel<C: MiningBlockChainClient>(&self, chain: &C, hash: &H256) -> Result<Option<H256>, Error>;
+
+    /// Removes the parcel from the mem pool. Its status becomes `DropReason::Canceled`.
+    /// Returns false if the parcel is not in the mem pool.
+    fn remove_parcel<C: MiningBlockChainClient>(&self, chain: &C, hash: &H256) -> bool;
+
+    /// Removes the parcels which have stayed in the mem pool longer than the time-to-live, returning their hashes.
+    /// Their status becomes `DropReason::Expired`.
+    fn remove_expired_parcels<C: MiningBlockChainClient>(&self, chain: &C) -> Vec<H256>;
+
+    /// Pins the parcel in the mem pool to the top of the next blocks regardless of its fee.
+    /// Returns false if the parcel is not in the mem pool.
+    fn prioritize_parcel(&self, hash: &H256) -> bool;
+
+    /// The block which would be sealed next, assembled from the pending parcels on top of the best block.
+    /// Returns the block which the miner already prepared, or `None` if it is outdated.
+    fn pending_block<C: MiningBlockChainClient>(&self, chain: &C) -> Option<Block>;
+
     /// Get a list of all pending parcels in the mem pool.
     fn ready_parcels(&self) -> Vec<SignedParcel>;
 
     /// Get a list of all future parcels.
     fn future_parcels(&self) -> Vec<SignedParcel>;
 
+    /// Returns the nonce which the next parcel of the account should use, counting its pending parcels.
+    fn next_nonce<C: MiningBlockChainClient>(&self, chain: &C, address: &Address) -> U256;
+
+    /// Where the parcel is in the mem pool, or why it recently left the mem pool without being mined.
+    fn parcel_pool_status(&self, hash: &H256) -> Option<ParcelPoolStatus>;
+
+    /// Gets the hashes of the parcels which recently left the mem pool without being mined, the oldest first.
+    fn dropped_parcels(&self) -> Vec<H256>;
+
+    /// When the parcel went through each step of its lifecycle, if it is seen recently.
+    fn parcel_timestamps(&self, hash: &H256) -> Option<ParcelTimestamps>;
+
+    /// Records that the parcels are propagated to peers.
+    fn note_parcels_broadcast(&self, hashes: &[H256]);
+
     /// Start sealing.
     fn start_sealing<C: MiningBlockChainClient>(&self, client: &C);
 
     /// Stop sealing.
     fn stop_sealing(&self);
+
+    /// Seal a block right away, even if it has no parcels, and return its hash.
+    /// Returns `None` if the engine can't seal the block by itself now.
+    fn mine_block<C: MiningBlockChainClient>(&self, client: &C) -> Option<H256>;
+
+    /// Set the current time used for the timestamps of new blocks. The time keeps flowing from it.
+    fn set_block_time(&self, timestamp: u64);
 }
 
 /// Mining status
@@ -133,6 +217,68 @@ pub struct MinerStatus {
     pub parcels_in_pending_block: usize,
 }
 
+/// How the miner reseals blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResealStatus {
+    /// The current minimum period between parcel-inspired reseals.
+    pub period: Duration,
+    /// Time left until a parcel-inspired reseal is allowed.
+    pub next_allowed_reseal: Duration,
+    /// Whether the mem pool changed after the last reseal, waiting for the minimum period to pass.
+    pub deferred: bool,
+    /// Time left until a block is sealed even if it has no parcels.
+    pub next_mandatory_reseal: Duration,
+    /// How much the pending parcels fill the next block, in percent.
+    pub pending_fullness: u8,
+    /// Parcels paying at least this fee are sealed right away.
+    pub urgent_fee: Option<U256>,
+    /// The pending parcels filling this percentage of the next block are sealed right away.
+    pub urgent_fullness: Option<u8>,
+    /// Whether the minimum period grows while the mem pool is idle.
+    pub backoff: bool,
+}
+
+/// Status of a parcel known to the mem pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParcelPoolStatus {
+    /// The parcel is ready to be included in a block.
+    Pending,
+    /// The parcel waits for the parcels of lower nonces.
+    Future,
+    /// The parcel left the mem pool without being mined.
+    Dropped(DropReason),
+}
+
+/// Milliseconds since the UNIX epoch at which a parcel went through each step of its lifecycle.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ParcelTimestamps {
+    /// When the parcel is imported to the mem pool for the first time.
+    pub first_seen: Option<u64>,
+    /// When the parcel is propagated to peers for the first time.
+    pub broadcast: Option<u64>,
+    /// When a block including the parcel is imported to the canonical chain.
+    pub included: Option<u64>,
+    /// When the block including the parcel becomes final.
+    pub finalized: Option<u64>,
+    /// The correlation id of the request which submitted the parcel, if it came from an RPC.
+    pub span: Option<u64>,
+}
+
+/// Why a parcel left the mem pool without being mined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropReason {
+    /// Dropped because the mem pool is full.
+    Limit,
+    /// Replaced by the parcel of the hash, which has the same nonce and a higher fee.
+    Replaced(H256),
+    /// The parcel is invalid.
+    Invalid,
+    /// The parcel was canceled.
+    Canceled,
+    /// The parcel stayed in the mem pool longer than its time-to-live.
+    Expired,
+}
+
 /// Represents the result of importing parcel.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ParcelImportResult {
@@ -0,0 +1,181 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use linked_hash_map::LinkedHashMap;
+use primitives::H256;
+
+use super::ParcelTimestamps;
+
+/// Milliseconds since the UNIX epoch.
+pub fn now_millis() -> u64 {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+}
+
+/// Keeps the lifecycle timestamps of the recently seen parcels.
+pub struct TimestampedParcels {
+    limit: usize,
+    parcels: LinkedHashMap<H256, ParcelTimestamps>,
+}
+
+impl Default for TimestampedParcels {
+    fn default() -> Self {
+        Self::new(8192)
+    }
+}
+
+impl TimestampedParcels {
+    pub fn new(limit: usize) -> Self {
+        TimestampedParcels {
+            limit,
+            parcels: Default::default(),
+        }
+    }
+
+    pub fn mark_seen(&mut self, hash: H256, now: u64) {
+        let timestamps = self.entry(hash);
+        timestamps.first_seen = timestamps.first_seen.or(Some(now));
+    }
+
+    pub fn mark_span(&mut self, hash: H256, span: u64) {
+        let timestamps = self.entry(hash);
+        timestamps.span = timestamps.span.or(Some(span));
+    }
+
+    /// Returns the span of the parcel if it's broadcast for the first time.
+    pub fn mark_broadcast(&mut self, hash: H256, now: u64) -> Option<u64> {
+        let timestamps = self.entry(hash);
+        if timestamps.broadcast.is_some() {
+            return None
+        }
+        timestamps.broadcast = Some(now);
+        timestamps.span
+    }
+
+    /// Returns the span of the parcel if it's included for the first time.
+    pub fn mark_included(&mut self, hash: H256, now: u64) -> Option<u64> {
+        let timestamps = self.entry(hash);
+        if timestamps.included.is_some() {
+            return None
+        }
+        timestamps.included = Some(now);
+        timestamps.span
+    }
+
+    /// Only the parcels already included are marked, since a block is final only after it is included.
+    pub fn mark_finalized(&mut self, hash: &H256, now: u64) {
+        if let Some(timestamps) = self.parcels.get_mut(hash) {
+            if timestamps.included.is_some() {
+                timestamps.finalized = timestamps.finalized.or(Some(now));
+            }
+        }
+    }
+
+    /// Forgets the inclusion of the parcel whose block is retracted.
+    pub fn mark_retracted(&mut self, hash: &H256) {
+        if let Some(timestamps) = self.parcels.get_mut(hash) {
+            timestamps.included = None;
+            timestamps.finalized = None;
+        }
+    }
+
+    pub fn get(&self, hash: &H256) -> Option<ParcelTimestamps> {
+        self.parcels.get(hash).cloned()
+    }
+
+    fn entry(&mut self, hash: H256) -> &mut ParcelTimestamps {
+        if !self.parcels.contains_key(&hash) {
+            self.parcels.insert(hash, Default::default());
+            while self.parcels.len() > self.limit {
+                self.parcels.pop_front();
+            }
+        }
+        self.parcels.get_mut(&hash).expect("The entry is inserted above")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_first_timestamp() {
+        let mut parcels = TimestampedParcels::new(10);
+        parcels.mark_seen(1.into(), 100);
+        parcels.mark_seen(1.into(), 200);
+        parcels.mark_broadcast(1.into(), 300);
+        parcels.mark_included(1.into(), 400);
+        parcels.mark_finalized(&1.into(), 500);
+
+        assert_eq!(
+            Some(ParcelTimestamps {
+                first_seen: Some(100),
+                broadcast: Some(300),
+                included: Some(400),
+                finalized: Some(500),
+                span: None,
+            }),
+            parcels.get(&1.into())
+        );
+    }
+
+    #[test]
+    fn retraction_clears_inclusion() {
+        let mut parcels = TimestampedParcels::new(10);
+        parcels.mark_finalized(&1.into(), 100);
+        assert_eq!(None, parcels.get(&1.into()));
+
+        parcels.mark_seen(1.into(), 100);
+        parcels.mark_included(1.into(), 200);
+        parcels.mark_retracted(&1.into());
+        parcels.mark_finalized(&1.into(), 300);
+        assert_eq!(
+            Some(ParcelTimestamps {
+                first_seen: Some(100),
+                broadcast: None,
+                included: None,
+                finalized: None,
+                span: None,
+            }),
+            parcels.get(&1.into())
+        );
+    }
+
+    #[test]
+    fn span_is_returned_only_once() {
+        let mut parcels = TimestampedParcels::new(10);
+        parcels.mark_seen(1.into(), 100);
+        parcels.mark_span(1.into(), 7);
+        assert_eq!(Some(7), parcels.mark_broadcast(1.into(), 200));
+        assert_eq!(None, parcels.mark_broadcast(1.into(), 300));
+        assert_eq!(Some(7), parcels.mark_included(1.into(), 400));
+        assert_eq!(None, parcels.mark_included(1.into(), 500));
+
+        assert_eq!(None, parcels.mark_broadcast(2.into(), 200));
+    }
+
+    #[test]
+    fn forgets_the_oldest() {
+        let mut parcels = TimestampedParcels::new(1);
+        parcels.mark_seen(1.into(), 100);
+        parcels.mark_seen(2.into(), 200);
+        assert_eq!(None, parcels.get(&1.into()));
+        assert!(parcels.get(&2.into()).is_some());
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Remote signer which lets a separate daemon keep the key of the engine signer.
+//! It speaks the stratum protocol: the messages to sign are pushed as jobs and the signatures are submitted as seals.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ckey::{public_to_address, recover, Address, Signature};
+use cstratum::{Error as StratumServiceError, JobDispatcher, PushWorkHandler, Stratum as StratumService};
+use parking_lot::{Condvar, Mutex};
+use primitives::{Bytes, H256};
+use rlp::UntrustedRlp;
+
+use super::super::account_provider::SignError;
+use super::super::consensus::SignableMessage;
+
+/// The remote signer must submit the signature within this time.
+const SIGN_TIMEOUT_SECS: u64 = 3;
+
+/// A message waiting for the signature.
+struct Pending {
+    payload: String,
+    signature: Option<Signature>,
+}
+
+/// Collects the signatures submitted by the remote signer.
+struct SignatureCollector {
+    address: Address,
+    /// The messages waiting for the signatures, by their hashes.
+    signatures: Mutex<HashMap<H256, Pending>>,
+    submitted: Condvar,
+}
+
+impl SignatureCollector {
+    fn new(address: Address) -> Self {
+        Self {
+            address,
+            signatures: Mutex::new(HashMap::new()),
+            submitted: Condvar::new(),
+        }
+    }
+
+    fn expect(&self, hash: H256, payload: String) {
+        self.signatures.lock().insert(
+            hash,
+            Pending {
+                payload,
+                signature: None,
+            },
+        );
+    }
+
+    fn cancel(&self, hash: &H256) {
+        self.signatures.lock().remove(hash);
+    }
+
+    /// Waits for the signature of the hash until the timeout expires.
+    fn wait(&self, hash: &H256, timeout: Duration) -> Option<Signature> {
+        let deadline = Instant::now() + timeout;
+        let mut signatures = self.signatures.lock();
+        loop {
+            let signature = signatures.get(hash).and_then(|pending| pending.signature);
+            if signature.is_some() {
+                signatures.remove(hash);
+                return signature
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                signatures.remove(hash);
+                return None
+            }
+            self.submitted.wait_for(&mut signatures, deadline - now);
+        }
+    }
+}
+
+/// The hash to sign, the kind of the message and the RLP of the message,
+/// so that the remote signer can check what it signs before signing the hash.
+fn payload(message: &SignableMessage) -> String {
+    let rlp: String = message.rlp().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(r#"["0x{:x}","{}","0x{}"]"#, message.hash(), message.kind(), rlp)
+}
+
+impl JobDispatcher for SignatureCollector {
+    fn initial(&self) -> Option<String> {
+        // A signer which connects late gets a message waiting for the signature.
+        self.signatures
+            .lock()
+            .values()
+            .find(|pending| pending.signature.is_none())
+            .map(|pending| pending.payload.clone())
+    }
+
+    fn submit(&self, payload: (H256, Vec<Bytes>)) -> Result<(), StratumServiceError> {
+        let (hash, seal) = payload;
+        let signature = seal
+            .get(0)
+            .and_then(|bytes| UntrustedRlp::new(bytes).as_val::<Signature>().ok())
+            .ok_or(StratumServiceError::PowInvalid)?;
+        match recover(&signature, &hash) {
+            Ok(public) if public_to_address(&public) == self.address => {}
+            _ => {
+                cwarn!(MINER, "The remote signer submitted a signature of {} by another key", hash);
+                return Err(StratumServiceError::UnauthorizedWorker)
+            }
+        }
+
+        let mut signatures = self.signatures.lock();
+        match signatures.get_mut(&hash) {
+            Some(pending) => pending.signature = Some(signature),
+            None => return Err(StratumServiceError::PowHashInvalid),
+        }
+        self.submitted.notify_all();
+        Ok(())
+    }
+}
+
+/// Signs the consensus messages with the key kept by a remote signer.
+pub struct RemoteSigner {
+    collector: Arc<SignatureCollector>,
+    service: Arc<StratumService>,
+}
+
+impl RemoteSigner {
+    /// Listens for the remote signer which keeps the key of the address.
+    /// Only the signer which authorizes with the secret, whose hash is `secret_hash`, gets the messages.
+    pub fn start(addr: &SocketAddr, address: Address, secret_hash: H256) -> Result<Self, StratumServiceError> {
+        let collector = Arc::new(SignatureCollector::new(address));
+        let service = StratumService::start(addr, Arc::clone(&collector) as Arc<JobDispatcher>, Some(secret_hash))?;
+        Ok(Self {
+            collector,
+            service,
+        })
+    }
+
+    pub fn address(&self) -> Address {
+        self.collector.address
+    }
+
+    /// Sends the message to the remote signer and waits for the signature of its hash.
+    pub fn sign(&self, message: &SignableMessage) -> Result<Signature, SignError> {
+        let hash = message.hash();
+        let payload = payload(message);
+        self.collector.expect(hash, payload.clone());
+        if let Err(err) = self.service.push_work_all(payload) {
+            self.collector.cancel(&hash);
+            cwarn!(MINER, "Cannot send {} to the remote signer: {:?}", hash, err);
+            return Err(SignError::RemoteSignerUnavailable)
+        }
+        let timeout = Duration::from_secs(SIGN_TIMEOUT_SECS);
+        self.collector.wait(&hash, timeout).ok_or_else(|| {
+            cwarn!(MINER, "The remote signer didn't sign {} in {:?}", hash, timeout);
+            SignError::RemoteSignerUnavailable
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use ckey::{sign, Generator, Random};
+    use rlp::encode;
+
+    use super::*;
+
+    fn expect(collector: &SignatureCollector) -> H256 {
+        let message = SignableMessage::Vote(H256::random().to_vec());
+        let hash = message.hash();
+        collector.expect(hash, payload(&message));
+        hash
+    }
+
+    #[test]
+    fn payload_contains_the_message() {
+        let message = SignableMessage::Lease(vec![0xc2, 0x01, 0x02]);
+        assert_eq!(format!(r#"["0x{:x}","lease","0xc20102"]"#, message.hash()), payload(&message));
+    }
+
+    #[test]
+    fn collect_the_submitted_signature() {
+        let key_pair = Random.generate().unwrap();
+        let collector = Arc::new(SignatureCollector::new(key_pair.address()));
+        let hash = expect(&collector);
+
+        let signature = sign(key_pair.private(), &hash).unwrap();
+        let submitter = Arc::clone(&collector);
+        let seal = vec![encode(&signature).into_vec()];
+        thread::spawn(move || submitter.submit((hash, seal)).unwrap());
+
+        assert_eq!(Some(signature), collector.wait(&hash, Duration::from_secs(5)));
+        assert_eq!(None, collector.initial());
+    }
+
+    #[test]
+    fn reject_the_signature_of_another_key() {
+        let key_pair = Random.generate().unwrap();
+        let collector = SignatureCollector::new(key_pair.address());
+        let hash = expect(&collector);
+
+        let other = Random.generate().unwrap();
+        let signature = sign(other.private(), &hash).unwrap();
+        match collector.submit((hash, vec![encode(&signature).into_vec()])) {
+            Err(StratumServiceError::UnauthorizedWorker) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reject_the_signature_of_unknown_hash() {
+        let key_pair = Random.generate().unwrap();
+        let collector = SignatureCollector::new(key_pair.address());
+        let hash = H256::random();
+
+        let signature = sign(key_pair.private(), &hash).unwrap();
+        match collector.submit((hash, vec![encode(&signature).into_vec()])) {
+            Err(StratumServiceError::PowHashInvalid) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn wait_expires_without_signature() {
+        let collector = SignatureCollector::new(Address::random());
+        let message = SignableMessage::Vote(H256::random().to_vec());
+        let hash = message.hash();
+        collector.expect(hash, payload(&message));
+        assert_eq!(Some(payload(&message)), collector.initial());
+
+        assert_eq!(None, collector.wait(&hash, Duration::from_millis(10)));
+        assert_eq!(None, collector.initial());
+    }
+}
@@ -0,0 +1,372 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use ckey::{public_to_address, recover, Address, Password, Signature};
+use cnetwork::{Api, NetworkExtension, NodeId, TimerToken};
+use parking_lot::{Mutex, RwLock};
+use rlp::{Encodable, RlpStream, UntrustedRlp};
+use time;
+
+use super::super::account_provider::AccountProvider;
+use super::super::consensus::{EngineSigner, SignableMessage};
+use super::parcel_timestamps::now_millis;
+use super::remote_signer::RemoteSigner;
+
+const LEASE_TIMER_TOKEN: TimerToken = 0;
+
+/// Role of a node which shares its sealing key with another node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SealingRole {
+    /// Seals, and announces a lease every period so that the standby stays idle.
+    Primary,
+    /// Begins sealing after the primary misses `missed_slots` leases in a row,
+    /// and stops as soon as it observes a lease of the primary again.
+    /// It never seals before it observes a lease, since it can't tell a failed primary from an unreachable one.
+    Standby {
+        missed_slots: u32,
+    },
+}
+
+/// Lease announced by the primary, signed by the sealing key.
+#[derive(Debug, Clone, PartialEq, RlpEncodable, RlpDecodable)]
+struct Lease {
+    /// Milliseconds since the UNIX epoch.
+    timestamp: u64,
+    signature: Signature,
+}
+
+impl Lease {
+    fn message(author: &Address, timestamp: u64) -> SignableMessage {
+        let mut s = RlpStream::new_list(2);
+        s.append(author).append(&timestamp);
+        SignableMessage::Lease(s.out())
+    }
+}
+
+/// Counts the leases the primary missed.
+struct Watch {
+    missed_slots_limit: u32,
+    missed_slots: u32,
+    /// Whether any lease has been observed since the start.
+    has_observed_primary: bool,
+    is_lease_seen: bool,
+    is_sealing: bool,
+}
+
+impl Watch {
+    fn new(missed_slots_limit: u32) -> Self {
+        Self {
+            missed_slots_limit,
+            missed_slots: 0,
+            has_observed_primary: false,
+            is_lease_seen: false,
+            is_sealing: false,
+        }
+    }
+
+    /// Returns true if the standby stops sealing.
+    fn on_lease(&mut self) -> bool {
+        self.has_observed_primary = true;
+        self.is_lease_seen = true;
+        self.missed_slots = 0;
+        let was_sealing = self.is_sealing;
+        self.is_sealing = false;
+        was_sealing
+    }
+
+    /// Returns true if the standby begins sealing.
+    fn on_slot(&mut self) -> bool {
+        if !self.has_observed_primary {
+            return false
+        }
+        if self.is_lease_seen {
+            self.is_lease_seen = false;
+            return false
+        }
+        self.missed_slots = self.missed_slots.saturating_add(1);
+        if self.is_sealing || self.missed_slots < self.missed_slots_limit {
+            return false
+        }
+        self.is_sealing = true;
+        true
+    }
+}
+
+/// Coordinates a primary and a standby node which hold the same sealing key,
+/// so that only one of them seals at a time.
+///
+/// It only gates the miner. The engine still signs its consensus messages with the key.
+pub struct SealingLeaseExtension {
+    role: SealingRole,
+    period: Duration,
+    /// Signs the leases with the sealing key, in the same way as the engine does.
+    signer: RwLock<EngineSigner>,
+    /// Timestamp of the latest accepted lease, which keeps the leases from being replayed.
+    latest_lease: Mutex<u64>,
+    watch: Mutex<Watch>,
+    peers: RwLock<Vec<NodeId>>,
+    api: Mutex<Option<Arc<Api>>>,
+}
+
+impl SealingLeaseExtension {
+    pub fn new(role: SealingRole, period: Duration) -> Self {
+        let missed_slots_limit = match role {
+            SealingRole::Primary => 0,
+            SealingRole::Standby {
+                missed_slots,
+            } => missed_slots,
+        };
+        Self {
+            role,
+            period,
+            signer: RwLock::new(EngineSigner::default()),
+            latest_lease: Mutex::new(0),
+            watch: Mutex::new(Watch::new(missed_slots_limit)),
+            peers: RwLock::new(Vec::new()),
+            api: Mutex::new(None),
+        }
+    }
+
+    pub fn set_signer(&self, accounts: Arc<AccountProvider>, address: Address, password: Option<Password>) {
+        self.signer.write().set(accounts, address, password);
+    }
+
+    pub fn set_remote_signer(&self, remote: Arc<RemoteSigner>) {
+        self.signer.write().set_remote(remote);
+    }
+
+    /// Whether this node may seal now.
+    pub fn is_holding(&self) -> bool {
+        match self.role {
+            SealingRole::Primary => true,
+            SealingRole::Standby {
+                ..
+            } => self.watch.lock().is_sealing,
+        }
+    }
+
+    fn announce(&self) {
+        let timestamp = now_millis();
+        // The remote signer may take a while, so don't sign under the lock.
+        let signer = self.signer.read().clone();
+        let address = match signer.address() {
+            Some(address) => address,
+            None => return,
+        };
+        let signature = match signer.sign(&Lease::message(&address, timestamp)) {
+            Ok(signature) => signature,
+            Err(err) => {
+                cwarn!(MINER, "Cannot sign the sealing lease: {}", err);
+                return
+            }
+        };
+        let lease = Lease {
+            timestamp,
+            signature,
+        };
+        self.broadcast(&lease.rlp_bytes(), None);
+    }
+
+    /// Sends the lease to the peers except the one it came from.
+    fn broadcast(&self, lease: &[u8], from: Option<&NodeId>) {
+        if let Some(api) = self.api.lock().as_ref() {
+            for peer in self.peers.read().iter().filter(|peer| Some(*peer) != from) {
+                if let Err(err) = api.send(peer, lease) {
+                    cdebug!(MINER, "Cannot send the sealing lease to {}: {:?}", peer, err);
+                }
+            }
+        }
+    }
+
+    fn is_valid(&self, lease: &Lease) -> bool {
+        let author = match self.signer.read().address() {
+            Some(address) => address,
+            None => return false,
+        };
+        let message = Lease::message(&author, lease.timestamp);
+        match recover(&lease.signature, &message.hash()) {
+            Ok(public) if public_to_address(&public) == author => {}
+            _ => return false,
+        }
+        // A lease older than two periods is stale, so is a lease from too far in the future.
+        let tolerance = 2 * (self.period.as_secs() * 1000 + u64::from(self.period.subsec_millis()));
+        let now = now_millis();
+        if lease.timestamp + tolerance < now || now + tolerance < lease.timestamp {
+            return false
+        }
+        let mut latest_lease = self.latest_lease.lock();
+        if lease.timestamp <= *latest_lease {
+            return false
+        }
+        *latest_lease = lease.timestamp;
+        true
+    }
+}
+
+impl NetworkExtension for SealingLeaseExtension {
+    fn name(&self) -> &'static str {
+        "sealing-lease"
+    }
+
+    fn need_encryption(&self) -> bool {
+        false
+    }
+
+    fn versions(&self) -> &[u64] {
+        const VERSIONS: &'static [u64] = &[0];
+        &VERSIONS
+    }
+
+    fn on_initialize(&self, api: Arc<Api>) {
+        let period = time::Duration::from_std(self.period).expect("The lease period must be valid");
+        api.set_timer(LEASE_TIMER_TOKEN, period).expect("Timer set succeeds");
+        *self.api.lock() = Some(api);
+    }
+
+    fn on_node_added(&self, node: &NodeId, _version: u64) {
+        self.peers.write().push(*node);
+    }
+
+    fn on_node_removed(&self, node: &NodeId) {
+        self.peers.write().retain(|peer| peer != node);
+    }
+
+    fn on_message(&self, node: &NodeId, data: &[u8]) {
+        let lease: Lease = match UntrustedRlp::new(data).as_val() {
+            Ok(lease) => lease,
+            Err(_) => {
+                cinfo!(MINER, "Invalid sealing lease from {}", node);
+                return
+            }
+        };
+        if self.role == SealingRole::Primary {
+            cwarn!(MINER, "Another node announces the lease of the sealing key. It must be a standby");
+            return
+        }
+        if !self.is_valid(&lease) {
+            ctrace!(MINER, "Ignore the sealing lease from {}", node);
+            return
+        }
+        if self.watch.lock().on_lease() {
+            cinfo!(MINER, "The primary is back. Stop sealing");
+        }
+        // Relay the lease, so that a standby which isn't connected to the primary observes it too.
+        // A lease is accepted only once, so it doesn't go around.
+        self.broadcast(data, Some(node));
+    }
+
+    fn on_timeout(&self, timer: TimerToken) {
+        match timer {
+            LEASE_TIMER_TOKEN => match self.role {
+                SealingRole::Primary => self.announce(),
+                SealingRole::Standby {
+                    ..
+                } => {
+                    if self.watch.lock().on_slot() {
+                        cwarn!(MINER, "The primary missed its leases. Start sealing");
+                    }
+                }
+            },
+            _ => debug_assert!(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ccrypto::blake256;
+
+    use super::*;
+
+    #[test]
+    fn standby_seals_after_missed_slots() {
+        let mut watch = Watch::new(2);
+        assert!(!watch.on_lease());
+        assert!(!watch.on_slot());
+
+        assert!(!watch.on_slot());
+        assert!(!watch.is_sealing);
+        assert!(watch.on_slot());
+        assert!(watch.is_sealing);
+        assert!(!watch.on_slot());
+        assert!(watch.is_sealing);
+    }
+
+    #[test]
+    fn standby_stops_sealing_on_lease() {
+        let mut watch = Watch::new(1);
+        assert!(!watch.on_lease());
+        assert!(!watch.on_slot());
+        assert!(watch.on_slot());
+        assert!(watch.on_lease());
+        assert!(!watch.is_sealing);
+
+        // The slot in which the lease is seen is not missed.
+        assert!(!watch.on_slot());
+        assert!(watch.on_slot());
+    }
+
+    #[test]
+    fn standby_never_seals_before_observing_a_lease() {
+        let mut watch = Watch::new(1);
+        for _ in 0..10 {
+            assert!(!watch.on_slot());
+        }
+        assert!(!watch.is_sealing);
+    }
+
+    #[test]
+    fn primary_always_holds() {
+        let extension = SealingLeaseExtension::new(SealingRole::Primary, Duration::from_secs(1));
+        assert!(extension.is_holding());
+        let extension = SealingLeaseExtension::new(
+            SealingRole::Standby {
+                missed_slots: 1,
+            },
+            Duration::from_secs(1),
+        );
+        assert!(!extension.is_holding());
+    }
+
+    #[test]
+    fn lease_signed_by_the_key_is_accepted_once() {
+        let accounts = AccountProvider::transient_provider();
+        let address = accounts.insert_account(blake256("standby").into(), &"password".into()).unwrap();
+        let extension = SealingLeaseExtension::new(
+            SealingRole::Standby {
+                missed_slots: 1,
+            },
+            Duration::from_secs(1),
+        );
+        extension.set_signer(Arc::clone(&accounts), address, Some("password".into()));
+
+        let lease = |timestamp: u64| Lease {
+            timestamp,
+            signature: accounts
+                .sign(address, Some("password".into()), Lease::message(&address, timestamp).hash())
+                .unwrap(),
+        };
+        let now = now_millis();
+        assert!(extension.is_valid(&lease(now)));
+        assert!(!extension.is_valid(&lease(now)));
+        assert!(!extension.is_valid(&lease(now - 10_000)));
+        assert!(!extension.is_valid(&lease(now + 10_000)));
+    }
+}
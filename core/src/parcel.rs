@@ -14,10 +14,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::mem;
 use std::ops::Deref;
 
-use ccrypto::blake256;
-use ckey::{self, recover, sign, Private, Public, Signature};
+use ccrypto::{blake256, blake512, ed25519};
+use ckey::{
+    self, ed25519_public_to_public, recover, sign, verify_ed25519, Ed25519Public, Ed25519Signature, Private, Public,
+    Signature,
+};
 use ctypes::parcel::{Action, Error as ParcelError, Parcel};
 use ctypes::transaction::Transaction;
 use ctypes::BlockNumber;
@@ -27,13 +31,269 @@ use rlp::{self, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
 use super::scheme::CommonParams;
 
+/// Maximum length of a name in bytes.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Maximum number of the keys of a multi-signature account.
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+const MULTISIG_SCHEME: u8 = 1;
+const ED25519_SCHEME: u8 = 2;
+
+/// The signature of a parcel.
+///
+/// A single ECDSA signature is encoded as it is.
+/// The others are encoded as a list of the tag of the scheme and the signature.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParcelSignature {
+    Single(Signature),
+    Multi(MultiSig),
+    /// The Ed25519 signature of the hash of the parcel, with the public key of the signer.
+    Ed25519 {
+        public: Ed25519Public,
+        signature: Ed25519Signature,
+    },
+}
+
+impl From<Signature> for ParcelSignature {
+    fn from(sig: Signature) -> Self {
+        ParcelSignature::Single(sig)
+    }
+}
+
+impl ParcelSignature {
+    /// Number of the signatures, which the fee of the parcel depends on.
+    pub fn count(&self) -> usize {
+        match self {
+            ParcelSignature::Single(_) => 1,
+            ParcelSignature::Multi(multisig) => multisig.signatures.len(),
+            ParcelSignature::Ed25519 {
+                ..
+            } => 1,
+        }
+    }
+}
+
+impl HeapSizeOf for ParcelSignature {
+    fn heap_size_of_children(&self) -> usize {
+        match self {
+            ParcelSignature::Single(_) => 0,
+            ParcelSignature::Multi(multisig) => {
+                multisig.publics.heap_size_of_children()
+                    + multisig.signatures.capacity() * mem::size_of::<(u8, Signature)>()
+            }
+            ParcelSignature::Ed25519 {
+                ..
+            } => 0,
+        }
+    }
+}
+
+impl rlp::Encodable for ParcelSignature {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            ParcelSignature::Single(sig) => s.append(sig),
+            ParcelSignature::Multi(multisig) => s.begin_list(2).append(&MULTISIG_SCHEME).append(multisig),
+            ParcelSignature::Ed25519 {
+                public,
+                signature,
+            } => s.begin_list(3).append(&ED25519_SCHEME).append(public).append(signature),
+        };
+    }
+}
+
+impl rlp::Decodable for ParcelSignature {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if !d.is_list() {
+            return Ok(ParcelSignature::Single(d.as_val()?))
+        }
+        match d.val_at::<u8>(0)? {
+            MULTISIG_SCHEME => {
+                if d.item_count()? != 2 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                Ok(ParcelSignature::Multi(d.val_at(1)?))
+            }
+            ED25519_SCHEME => {
+                if d.item_count()? != 3 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                Ok(ParcelSignature::Ed25519 {
+                    public: d.val_at(1)?,
+                    signature: d.val_at(2)?,
+                })
+            }
+            _ => Err(DecoderError::Custom("Unknown signature scheme")),
+        }
+    }
+}
+
+/// The signatures of an m-of-n multi-signature account.
+///
+/// The account is identified by the threshold and the public keys, in that order.
+/// Its signer is `MultiSig::public`, so the fee payer of the parcel is the address of it, not one of the co-signers.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MultiSig {
+    threshold: u8,
+    publics: Vec<Public>,
+    /// Signatures paired with the index of their keys, in ascending order of the index.
+    signatures: Vec<(u8, Signature)>,
+}
+
+impl rlp::Encodable for MultiSig {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        s.append(&self.threshold);
+        s.append_list(&self.publics);
+        s.begin_list(self.signatures.len());
+        for (index, sig) in &self.signatures {
+            s.begin_list(2).append(index).append(sig);
+        }
+    }
+}
+
+impl rlp::Decodable for MultiSig {
+    fn decode(d: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if d.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen)
+        }
+        let signatures = d
+            .at(2)?
+            .iter()
+            .map(|item| {
+                if item.item_count()? != 2 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                Ok((item.val_at(0)?, item.val_at(1)?))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(MultiSig {
+            threshold: d.val_at(0)?,
+            publics: d.list_at(1)?,
+            signatures,
+        })
+    }
+}
+
+impl MultiSig {
+    /// Creates the multi-signature of the account without any signature.
+    pub fn new(threshold: u8, publics: Vec<Public>) -> Self {
+        MultiSig {
+            threshold,
+            publics,
+            signatures: Vec::new(),
+        }
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn publics(&self) -> &[Public] {
+        &self.publics
+    }
+
+    pub fn signatures(&self) -> &[(u8, Signature)] {
+        &self.signatures
+    }
+
+    /// The key identifying the account.
+    /// It is a hash rather than a point on the curve, so nobody has the secret of it.
+    pub fn public(&self) -> Public {
+        multisig_public(self.threshold, &self.publics)
+    }
+
+    /// The message which the co-signers sign instead of the hash of the parcel.
+    /// It contains the account, so that the signatures cannot be reused for another set of keys.
+    pub fn message(&self, parcel_hash: &H256) -> H256 {
+        let mut s = RlpStream::new_list(2);
+        s.append(parcel_hash);
+        s.append(&self.public());
+        blake256(s.out())
+    }
+
+    /// Adds the signature of the `index`th key, replacing the previous one of the key.
+    pub fn add_signature(&mut self, index: u8, sig: Signature) -> Result<(), ckey::Error> {
+        if index as usize >= self.publics.len() {
+            return Err(ckey::Error::InvalidPublic)
+        }
+        match self.signatures.binary_search_by_key(&index, |(i, _)| *i) {
+            Ok(position) => self.signatures[position].1 = sig,
+            Err(position) => self.signatures.insert(position, (index, sig)),
+        }
+        Ok(())
+    }
+
+    /// Adds the signatures of `other`, which must be the multi-signature of the same account.
+    pub fn merge(&mut self, other: &MultiSig) -> Result<(), ckey::Error> {
+        if self.threshold != other.threshold || self.publics != other.publics {
+            return Err(ckey::Error::Custom("The multi-signatures are of different accounts".to_string()))
+        }
+        for (index, sig) in &other.signatures {
+            self.add_signature(*index, *sig)?;
+        }
+        Ok(())
+    }
+
+    fn verify_structure(&self) -> Result<(), ckey::Error> {
+        let key_count = self.publics.len();
+        if key_count > MAX_MULTISIG_KEYS {
+            return Err(ckey::Error::Custom(format!("A multi-signature can have up to {} keys", MAX_MULTISIG_KEYS)))
+        }
+        if self.threshold == 0 || self.threshold as usize > key_count {
+            return Err(ckey::Error::Custom(format!("{} of {} is an invalid threshold", self.threshold, key_count)))
+        }
+        for (i, public) in self.publics.iter().enumerate() {
+            if self.publics[..i].contains(public) {
+                return Err(ckey::Error::Custom("A multi-signature has duplicated keys".to_string()))
+            }
+        }
+        let mut previous = None;
+        for (index, _) in &self.signatures {
+            if *index as usize >= key_count || previous.map_or(false, |previous| previous >= *index) {
+                return Err(ckey::Error::Custom("The signatures are not in ascending order of the keys".to_string()))
+            }
+            previous = Some(*index);
+        }
+        // Exactly `threshold` signatures, or the same parcel could be signed into several hashes.
+        if self.signatures.len() != self.threshold as usize {
+            return Err(ckey::Error::Custom(format!(
+                "{} signatures are given but {} are required",
+                self.signatures.len(),
+                self.threshold
+            )))
+        }
+        Ok(())
+    }
+
+    /// Checks the signatures of the parcel and returns the key identifying the account.
+    pub fn recover(&self, parcel_hash: &H256) -> Result<Public, ckey::Error> {
+        self.verify_structure()?;
+        let message = self.message(parcel_hash);
+        for (index, sig) in &self.signatures {
+            if recover(sig, &message)? != self.publics[*index as usize] {
+                return Err(ckey::Error::InvalidSignature)
+            }
+        }
+        Ok(self.public())
+    }
+}
+
+/// The key identifying the multi-signature account of the keys.
+pub fn multisig_public(threshold: u8, publics: &[Public]) -> Public {
+    let mut s = RlpStream::new_list(2);
+    s.append(&threshold);
+    s.append_list(publics);
+    blake512(s.out())
+}
+
 /// Signed parcel information without verified signature.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct UnverifiedParcel {
     /// Plain Parcel.
     unsigned: Parcel,
     /// Signature.
-    sig: Signature,
+    sig: ParcelSignature,
     /// Hash of the parcel
     hash: H256,
 }
@@ -80,6 +340,41 @@ impl UnverifiedParcel {
         }.compute_hash()
     }
 
+    /// Creates the parcel signed by the Ed25519 key.
+    pub fn new_ed25519(parcel: Parcel, public: Ed25519Public, signature: Ed25519Signature) -> Self {
+        UnverifiedParcel {
+            unsigned: parcel,
+            sig: ParcelSignature::Ed25519 {
+                public,
+                signature,
+            },
+            hash: 0.into(),
+        }.compute_hash()
+    }
+
+    /// Creates the parcel of a multi-signature account. The signatures can be added later.
+    pub fn new_multisig(parcel: Parcel, multisig: MultiSig) -> Self {
+        UnverifiedParcel {
+            unsigned: parcel,
+            sig: ParcelSignature::Multi(multisig),
+            hash: 0.into(),
+        }.compute_hash()
+    }
+
+    /// Applies `f` to the multi-signature of the parcel and recomputes the hash.
+    pub fn update_multisig<F>(mut self, f: F) -> Result<Self, ckey::Error>
+    where
+        F: FnOnce(&mut MultiSig) -> Result<(), ckey::Error>, {
+        match &mut self.sig {
+            ParcelSignature::Multi(multisig) => f(multisig)?,
+            ParcelSignature::Single(_)
+            | ParcelSignature::Ed25519 {
+                ..
+            } => return Err(ckey::Error::Custom("The parcel is not of a multi-signature account".to_string())),
+        }
+        Ok(self.compute_hash())
+    }
+
     /// Used to compute hash of created parcels
     fn compute_hash(mut self) -> UnverifiedParcel {
         let hash = blake256(&*self.rlp_bytes());
@@ -107,19 +402,41 @@ impl UnverifiedParcel {
         self.hash
     }
 
-    /// Construct a signature object from the sig.
-    pub fn signature(&self) -> Signature {
-        Signature::from(self.sig)
+    /// The signature of the parcel.
+    pub fn signature(&self) -> &ParcelSignature {
+        &self.sig
     }
 
     /// Recovers the public key of the signature.
+    /// The public key of a multi-signature parcel or an Ed25519 parcel is the one identifying the account.
     pub fn recover_public(&self) -> Result<Public, ckey::Error> {
-        Ok(recover(&self.signature(), &self.unsigned.hash())?)
+        match &self.sig {
+            ParcelSignature::Single(sig) => Ok(recover(sig, &self.unsigned.hash())?),
+            ParcelSignature::Multi(multisig) => multisig.recover(&self.unsigned.hash()),
+            ParcelSignature::Ed25519 {
+                public,
+                signature,
+            } => {
+                if !verify_ed25519(public, signature, &self.unsigned.hash()) {
+                    return Err(ckey::Error::InvalidSignature)
+                }
+                Ok(ed25519_public_to_public(public))
+            }
+        }
     }
 
-    /// Checks whether the signature has a low 's' value.
+    /// Checks whether the signatures have a low 's' value.
+    /// An Ed25519 signature is checked whether its 'S' is reduced, which is the same in effect.
     pub fn check_low_s(&self) -> Result<(), ckey::Error> {
-        if !self.signature().is_low_s() {
+        let is_low_s = match &self.sig {
+            ParcelSignature::Single(sig) => sig.is_low_s(),
+            ParcelSignature::Multi(multisig) => multisig.signatures.iter().all(|(_, sig)| sig.is_low_s()),
+            ParcelSignature::Ed25519 {
+                signature,
+                ..
+            } => ed25519::is_canonical(signature),
+        };
+        if !is_low_s {
             Err(ckey::Error::InvalidSignature.into())
         } else {
             Ok(())
@@ -176,12 +493,42 @@ impl UnverifiedParcel {
                     }
                 }
             }
+            Action::RegisterName {
+                name,
+                value,
+                ..
+            }
+            | Action::UpdateName {
+                name,
+                value,
+            } => {
+                verify_name(name)?;
+                if value.len() > params.max_metadata_size {
+                    return Err(ParcelError::MetadataTooBig)
+                }
+            }
+            Action::TransferName {
+                name,
+                ..
+            } => verify_name(name)?,
             _ => {}
         }
         Ok(())
     }
 }
 
+/// Names are kept in one form, so the names which look the same cannot be registered to different accounts.
+fn verify_name(name: &str) -> Result<(), ParcelError> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(ParcelError::InvalidName(name.to_string()))
+    }
+    let is_normalized = name.bytes().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-');
+    if !is_normalized || name.starts_with('-') || name.ends_with('-') {
+        return Err(ParcelError::InvalidName(name.to_string()))
+    }
+    Ok(())
+}
+
 /// A `UnverifiedParcel` with successfully recovered `signer`.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct SignedParcel {
@@ -191,7 +538,7 @@ pub struct SignedParcel {
 
 impl HeapSizeOf for SignedParcel {
     fn heap_size_of_children(&self) -> usize {
-        self.parcel.unsigned.heap_size_of_children()
+        self.parcel.unsigned.heap_size_of_children() + self.parcel.sig.heap_size_of_children()
     }
 }
 
@@ -224,6 +571,14 @@ impl SignedParcel {
         })
     }
 
+    /// Creates the parcel with the signer recovered before. `signer_public` must be recovered from the parcel.
+    pub(crate) fn new_with_signer(parcel: UnverifiedParcel, signer_public: Public) -> Self {
+        SignedParcel {
+            parcel,
+            signer_public,
+        }
+    }
+
     /// Signs the parcel as coming from `signer`.
     pub fn new_with_sign(parcel: Parcel, private: &Private) -> SignedParcel {
         let sig = sign(&private, &parcel.hash()).expect("data is valid and context has signing capabilities; qed");
@@ -280,12 +635,158 @@ impl Deref for LocalizedParcel {
 
 #[cfg(test)]
 mod tests {
-    use ckey::{Address, Public, Signature};
+    use ckey::{ed25519_public, sign_ed25519, Address, Generator, KeyPair, Public, Random, Signature};
     use ctypes::transaction::AssetMintOutput;
     use primitives::H256;
 
     use super::*;
 
+    fn payment_parcel() -> Parcel {
+        Parcel {
+            nonce: 0.into(),
+            fee: 10.into(),
+            network_id: "tc".into(),
+            action: Action::Payment {
+                receiver: Address::default(),
+                amount: 300.into(),
+            },
+        }
+    }
+
+    fn multisig_parcel(threshold: u8, keys: &[KeyPair], signers: &[usize]) -> UnverifiedParcel {
+        let parcel = payment_parcel();
+        let multisig = MultiSig::new(threshold, keys.iter().map(|key| *key.public()).collect());
+        let message = multisig.message(&parcel.hash());
+        let mut parcel = UnverifiedParcel::new_multisig(parcel, multisig);
+        for &index in signers {
+            let sig = sign(keys[index].private(), &message).unwrap();
+            parcel = parcel.update_multisig(|multisig| multisig.add_signature(index as u8, sig)).unwrap();
+        }
+        parcel
+    }
+
+    #[test]
+    fn only_normalized_names_are_valid() {
+        assert_eq!(Ok(()), verify_name("code-chain2"));
+        assert_eq!(Err(ParcelError::InvalidName("CodeChain".to_string())), verify_name("CodeChain"));
+        assert_eq!(Err(ParcelError::InvalidName("code chain".to_string())), verify_name("code chain"));
+        assert_eq!(Err(ParcelError::InvalidName("-codechain".to_string())), verify_name("-codechain"));
+        assert_eq!(Err(ParcelError::InvalidName("c\u{f6}dechain".to_string())), verify_name("c\u{f6}dechain"));
+        assert_eq!(Err(ParcelError::InvalidName("".to_string())), verify_name(""));
+    }
+
+    #[test]
+    fn multisig_parcel_rlp() {
+        let keys: Vec<KeyPair> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        rlp_encode_and_decode_test!(multisig_parcel(2, &keys, &[]));
+        rlp_encode_and_decode_test!(multisig_parcel(2, &keys, &[0, 2]));
+    }
+
+    #[test]
+    fn multisig_parcel_is_signed_by_the_account() {
+        let keys: Vec<KeyPair> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        let parcel = multisig_parcel(2, &keys, &[2, 0]);
+        let publics: Vec<_> = keys.iter().map(|key| *key.public()).collect();
+        assert_eq!(Ok(multisig_public(2, &publics)), parcel.recover_public());
+        assert_eq!(2, parcel.signature().count());
+
+        let signed = SignedParcel::new(parcel).unwrap();
+        assert_eq!(multisig_public(2, &publics), signed.signer_public());
+        assert_ne!(multisig_public(1, &publics), signed.signer_public());
+    }
+
+    #[test]
+    fn multisig_parcel_needs_threshold_signatures() {
+        let keys: Vec<KeyPair> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        assert!(multisig_parcel(2, &keys, &[1]).recover_public().is_err());
+        assert!(multisig_parcel(0, &keys, &[]).recover_public().is_err());
+        assert!(multisig_parcel(4, &keys, &[0, 1, 2]).recover_public().is_err());
+    }
+
+    #[test]
+    fn multisig_parcel_cannot_have_more_signatures_than_threshold() {
+        let keys: Vec<KeyPair> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        assert!(multisig_parcel(2, &keys, &[0, 1]).recover_public().is_ok());
+        assert!(multisig_parcel(2, &keys, &[0, 1, 2]).recover_public().is_err());
+    }
+
+    #[test]
+    fn multisig_signature_cannot_be_reused_for_another_account() {
+        let keys: Vec<KeyPair> = (0..2).map(|_| Random.generate().unwrap()).collect();
+        let parcel = multisig_parcel(1, &keys, &[0]);
+        let sig = match parcel.signature() {
+            ParcelSignature::Multi(multisig) => multisig.signatures()[0].1,
+            ParcelSignature::Single(_)
+            | ParcelSignature::Ed25519 {
+                ..
+            } => unreachable!(),
+        };
+        let other = MultiSig::new(1, vec![*keys[0].public()]);
+        let reused = UnverifiedParcel::new_multisig(parcel.as_unsigned().clone(), other)
+            .update_multisig(|multisig| multisig.add_signature(0, sig))
+            .unwrap();
+        assert_eq!(Err(ckey::Error::InvalidSignature), reused.recover_public());
+    }
+
+    #[test]
+    fn multisig_signatures_are_merged() {
+        let keys: Vec<KeyPair> = (0..3).map(|_| Random.generate().unwrap()).collect();
+        let first = multisig_parcel(2, &keys, &[0]);
+        let second = multisig_parcel(2, &keys, &[2]);
+        let second = match second.signature() {
+            ParcelSignature::Multi(multisig) => multisig.clone(),
+            ParcelSignature::Single(_)
+            | ParcelSignature::Ed25519 {
+                ..
+            } => unreachable!(),
+        };
+        let merged = first.update_multisig(|multisig| multisig.merge(&second)).unwrap();
+        assert!(merged.recover_public().is_ok());
+
+        let mut another_account = MultiSig::new(1, keys.iter().map(|key| *key.public()).collect());
+        assert!(another_account.merge(&second).is_err());
+    }
+
+    fn ed25519_parcel(key: &KeyPair) -> UnverifiedParcel {
+        let parcel = payment_parcel();
+        let signature = sign_ed25519(key.private(), &parcel.hash());
+        UnverifiedParcel::new_ed25519(parcel, ed25519_public(key.private()), signature)
+    }
+
+    #[test]
+    fn ed25519_parcel_rlp() {
+        let key: KeyPair = Random.generate().unwrap();
+        rlp_encode_and_decode_test!(ed25519_parcel(&key));
+    }
+
+    #[test]
+    fn ed25519_parcel_is_signed_by_the_account_of_the_key() {
+        let key: KeyPair = Random.generate().unwrap();
+        let parcel = ed25519_parcel(&key);
+        assert_eq!(Ok(()), parcel.check_low_s());
+
+        let signed = SignedParcel::new(parcel).unwrap();
+        assert_eq!(ed25519_public_to_public(&ed25519_public(key.private())), signed.signer_public());
+        assert_ne!(key.public(), &signed.signer_public());
+    }
+
+    #[test]
+    fn ed25519_parcel_of_another_key_is_invalid() {
+        let key: KeyPair = Random.generate().unwrap();
+        let another: KeyPair = Random.generate().unwrap();
+        let parcel = payment_parcel();
+        let signature = sign_ed25519(key.private(), &parcel.hash());
+        let parcel = UnverifiedParcel::new_ed25519(parcel, ed25519_public(another.private()), signature);
+        assert_eq!(Err(ckey::Error::InvalidSignature), parcel.recover_public());
+    }
+
+    #[test]
+    fn unknown_signature_scheme_is_rejected() {
+        let mut s = RlpStream::new_list(2);
+        s.append(&3u8).append(&H256::default());
+        assert!(UntrustedRlp::new(&s.out()).as_val::<ParcelSignature>().is_err());
+    }
+
     #[test]
     fn unverified_parcel_rlp() {
         rlp_encode_and_decode_test!(
@@ -296,7 +797,7 @@ mod tests {
                     action: Action::CreateShard,
                     network_id: "tc".into(),
                 },
-                sig: Signature::default(),
+                sig: Signature::default().into(),
                 hash: H256::default(),
             }.compute_hash()
         );
@@ -372,7 +873,7 @@ mod tests {
                         amount: 300.into(),
                     },
                 },
-                sig: Signature::default(),
+                sig: Signature::default().into(),
                 hash: H256::default(),
             }.compute_hash()
         );
@@ -390,7 +891,7 @@ mod tests {
                         key: Public::random(),
                     },
                 },
-                sig: Signature::default(),
+                sig: Signature::default().into(),
                 hash: H256::default(),
             }.compute_hash()
         );
@@ -406,7 +907,7 @@ mod tests {
                     network_id: "tc".into(),
                     action: Action::CreateShard,
                 },
-                sig: Signature::default(),
+                sig: Signature::default().into(),
                 hash: H256::default(),
             }.compute_hash()
         );
@@ -0,0 +1,88 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use ckey::Address;
+use cmerkle::verify_proof;
+use cstate::{Account, StateError};
+use primitives::Bytes;
+use rlp::UntrustedRlp;
+
+use super::error::Error;
+use super::header::Header;
+
+/// Verify the proof of account `address` made by `chain_getProof` against the state root of `header`.
+///
+/// Returns the account, or `None` if the proof shows that the account doesn't exist.
+/// The header itself must be verified separately, e.g. by following the chain of headers.
+pub fn verify_account_proof(header: &Header, address: &Address, proof: &[Bytes]) -> Result<Option<Account>, Error> {
+    let account = verify_proof(header.state_root(), address.as_ref(), proof).map_err(|err| StateError::from(*err))?;
+    match account {
+        Some(account) => Ok(Some(UntrustedRlp::new(&account).as_val()?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cmerkle::{TrieDB, TrieDBMut, TrieMut};
+    use memorydb::MemoryDB;
+    use primitives::{H256, U256};
+
+    use super::*;
+
+    fn header_and_proofs(accounts: &[(Address, Account)], targets: &[Address]) -> (Header, Vec<Vec<Bytes>>) {
+        let mut memdb = MemoryDB::new();
+        let mut root = H256::new();
+        {
+            let mut t = TrieDBMut::new(&mut memdb, &mut root);
+            for (address, account) in accounts {
+                t.insert(address.as_ref(), &::rlp::encode(account)).unwrap();
+            }
+        }
+        let t = TrieDB::new(&memdb, &root).unwrap();
+        let proofs = targets.iter().map(|address| t.get_proof(address.as_ref()).unwrap()).collect();
+
+        let mut header = Header::default();
+        header.set_state_root(root);
+        (header, proofs)
+    }
+
+    #[test]
+    fn verify_existing_and_missing_accounts() {
+        let alice = Address::random();
+        let bob = Address::random();
+        let carol = Address::random();
+        let accounts = [(alice, Account::new(100.into(), 1.into())), (bob, Account::new(200.into(), 2.into()))];
+        let (header, proofs) = header_and_proofs(&accounts, &[bob, carol]);
+
+        let account = verify_account_proof(&header, &bob, &proofs[0]).unwrap().unwrap();
+        assert_eq!(&U256::from(200), account.balance());
+        assert_eq!(&U256::from(2), account.nonce());
+        assert!(verify_account_proof(&header, &carol, &proofs[1]).unwrap().is_none());
+    }
+
+    #[test]
+    fn proof_for_another_state_root_is_rejected() {
+        let alice = Address::random();
+        let accounts = [(alice, Account::new(100.into(), 1.into()))];
+        let (_, proofs) = header_and_proofs(&accounts, &[alice]);
+
+        let mut header = Header::default();
+        header.set_state_root(H256::random());
+        assert!(verify_account_proof(&header, &alice, &proofs[0]).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::BTreeMap;
+
+use cjson;
+use ctypes::util::unexpected::Mismatch;
+use ctypes::BlockNumber;
+use primitives::H256;
+
+use super::super::error::BlockError;
+use super::super::header::Header;
+
+/// Hashes of the canon blocks which are fixed in the chain scheme.
+///
+/// A header at a checkpoint number must have the checkpoint hash, and a block which forks below a checkpoint
+/// that is already in the canon chain is rejected. Once the header of a checkpoint is in the canon chain, the seals
+/// of the blocks whose headers are its canon ancestors are not checked since the hash chain guarantees them.
+/// The headers themselves are always checked.
+#[derive(Clone, Debug, Default)]
+pub struct Checkpoints {
+    hashes: BTreeMap<BlockNumber, H256>,
+}
+
+impl Checkpoints {
+    pub fn new(hashes: BTreeMap<BlockNumber, H256>) -> Self {
+        Self {
+            hashes,
+        }
+    }
+
+    pub fn hash_at(&self, number: BlockNumber) -> Option<H256> {
+        self.hashes.get(&number).cloned()
+    }
+
+    /// Checks a header which is not in the chain yet.
+    /// `canon_hash` returns the hash of the canon block at the given number.
+    pub fn verify<F>(&self, header: &Header, canon_hash: F) -> Result<(), BlockError>
+    where
+        F: Fn(BlockNumber) -> Option<H256>, {
+        let number = header.number();
+        if let Some(expected) = self.hash_at(number) {
+            let found = header.hash();
+            if expected != found {
+                return Err(BlockError::CheckpointMismatch(Mismatch {
+                    expected,
+                    found,
+                }))
+            }
+        }
+
+        let passed = self.hashes.range(number..).find(|&(&checkpoint, &hash)| canon_hash(checkpoint) == Some(hash));
+        if let Some((&checkpoint, _)) = passed {
+            return Err(BlockError::ForkBelowCheckpoint(checkpoint))
+        }
+        Ok(())
+    }
+
+    /// Whether the header is a canon ancestor of a canon checkpoint, which guarantees its seal.
+    /// `canon_hash` returns the hash of the canon header at the given number.
+    pub fn guarantees<F>(&self, header: &Header, canon_hash: F) -> bool
+    where
+        F: Fn(BlockNumber) -> Option<H256>, {
+        let number = header.number();
+        if canon_hash(number) != Some(header.hash()) {
+            return false
+        }
+        self.hashes.range(number..).any(|(&checkpoint, &hash)| canon_hash(checkpoint) == Some(hash))
+    }
+}
+
+impl From<Vec<cjson::scheme::Checkpoint>> for Checkpoints {
+    fn from(checkpoints: Vec<cjson::scheme::Checkpoint>) -> Self {
+        let hashes = checkpoints.into_iter().map(|checkpoint| (checkpoint.number.into(), checkpoint.hash.into()));
+        Self::new(hashes.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(number: BlockNumber) -> Header {
+        let mut header = Header::default();
+        header.set_number(number);
+        header
+    }
+
+    #[test]
+    fn header_at_checkpoint_must_have_its_hash() {
+        let good = header(10);
+        let mut bad = header(10);
+        bad.set_timestamp(1);
+
+        let checkpoints = Checkpoints::new(vec![(10, good.hash())].into_iter().collect());
+        assert_eq!(Ok(()), checkpoints.verify(&good, |_| None));
+        assert_eq!(
+            Err(BlockError::CheckpointMismatch(Mismatch {
+                expected: good.hash(),
+                found: bad.hash(),
+            })),
+            checkpoints.verify(&bad, |_| None)
+        );
+    }
+
+    #[test]
+    fn fork_below_passed_checkpoint_is_rejected() {
+        let checkpoint = H256::from(10);
+        let checkpoints = Checkpoints::new(vec![(10, checkpoint)].into_iter().collect());
+
+        // The checkpoint is not in the chain yet.
+        assert_eq!(Ok(()), checkpoints.verify(&header(5), |_| None));
+
+        let canon_hash = |number| Some(checkpoint).filter(|_| number == 10);
+        assert_eq!(Err(BlockError::ForkBelowCheckpoint(10)), checkpoints.verify(&header(5), canon_hash));
+        assert_eq!(Ok(()), checkpoints.verify(&header(11), canon_hash));
+    }
+
+    #[test]
+    fn only_canon_ancestors_of_passed_checkpoint_are_guaranteed() {
+        let ancestor = header(5);
+        let mut fork = header(5);
+        fork.set_timestamp(1);
+        let checkpoint = H256::from(10);
+        let checkpoints = Checkpoints::new(vec![(10, checkpoint)].into_iter().collect());
+
+        // The checkpoint is not in the chain yet.
+        assert!(!checkpoints.guarantees(&ancestor, |number| Some(ancestor.hash()).filter(|_| number == 5)));
+
+        let canon_hash = |number| match number {
+            5 => Some(ancestor.hash()),
+            10 => Some(checkpoint),
+            _ => None,
+        };
+        assert!(checkpoints.guarantees(&ancestor, canon_hash));
+        assert!(!checkpoints.guarantees(&fork, canon_hash));
+        assert!(!checkpoints.guarantees(&header(11), canon_hash));
+    }
+}
@@ -15,18 +15,19 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::io::Read;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use ccrypto::{blake256, BLAKE_NULL_RLP};
 use cjson;
-use ckey::{Address, NetworkId};
+use ckey::{Address, NetworkId, Signature};
 use cmerkle::TrieFactory;
 use cstate::{
     ActionHandler, Backend, Metadata, MetadataAddress, Shard, ShardAddress, ShardMetadataAddress, StateDB, StateResult,
     WorldAddress,
 };
 use ctypes::transaction::Error as TransactionError;
-use ctypes::ShardId;
+use ctypes::{BlockNumber, ShardId};
 use hashdb::HashDB;
 use parking_lot::RwLock;
 use primitives::{Bytes, H256, U256};
@@ -35,14 +36,16 @@ use rlp::{Encodable, Rlp, RlpStream};
 use super::super::blockchain::HeaderProvider;
 
 use super::super::codechain_machine::CodeChainMachine;
-use super::super::consensus::{BlakePoW, CodeChainEngine, Cuckoo, NullEngine, SimplePoA, Solo, Tendermint};
+use super::super::consensus::{
+    BlakePoW, Clique, CodeChainEngine, Cuckoo, Multi, NullEngine, SimplePoA, Solo, Tendermint,
+};
 use super::super::error::{Error, SchemeError};
 use super::super::header::Header;
 use super::pod_state::{PodAccounts, PodShards};
 use super::seal::Generic as GenericSeal;
-use super::Genesis;
+use super::{Checkpoints, Genesis};
 
-#[derive(Debug, PartialEq, Default, RlpEncodable)]
+#[derive(Debug, PartialEq, Clone, Default, RlpEncodable)]
 pub struct CommonParams {
     /// Maximum size of extra data.
     pub max_extra_data_size: usize,
@@ -58,6 +61,12 @@ pub struct CommonParams {
     pub snapshot_period: u64,
     /// Flag whether to use shard validator.
     pub use_shard_validator: bool,
+    /// Minimum balance of an account. The balances below it are burnt, leaving only the nonces.
+    pub min_balance: U256,
+    /// Fee for keeping a name registered for a block. It is burned, not given to the block author.
+    pub name_fee_per_block: U256,
+    /// Fee for each signature of a multi-signature parcel beyond the first.
+    pub additional_signature_fee: U256,
 }
 
 impl From<cjson::scheme::Params> for CommonParams {
@@ -70,6 +79,31 @@ impl From<cjson::scheme::Params> for CommonParams {
             max_body_size: p.max_body_size.into(),
             snapshot_period: p.snapshot_period.into(),
             use_shard_validator: p.use_shard_validator.into(),
+            min_balance: p.min_balance.map(Into::into).unwrap_or_else(U256::zero),
+            name_fee_per_block: p.name_fee_per_block.map(Into::into).unwrap_or_else(U256::zero),
+            additional_signature_fee: p.additional_signature_fee.map(Into::into).unwrap_or_else(U256::zero),
+        }
+    }
+}
+
+/// Nodes allowed to connect in a fully permissioned network.
+#[derive(Clone, Debug)]
+pub struct NodeAllowList {
+    /// The account which signs the list.
+    pub authority: Address,
+    /// The version signed with the nodes.
+    pub version: u64,
+    pub nodes: Vec<SocketAddr>,
+    pub signature: Option<Signature>,
+}
+
+impl From<cjson::scheme::NodeAllowList> for NodeAllowList {
+    fn from(list: cjson::scheme::NodeAllowList) -> Self {
+        Self {
+            authority: list.authority.into_address(),
+            version: list.version.into(),
+            nodes: list.nodes,
+            signature: list.signature,
         }
     }
 }
@@ -86,6 +120,12 @@ pub struct Scheme {
 
     /// Known nodes on the network in enode format.
     pub nodes: Vec<String>,
+    /// Only these nodes can connect if it exists.
+    pub node_allow_list: Option<NodeAllowList>,
+    /// Hashes which the canon blocks at the numbers must have.
+    pub checkpoints: Checkpoints,
+    /// The blocks where the engine changes, in ascending order.
+    pub fork_blocks: Vec<BlockNumber>,
 
     /// The genesis block's parent hash field.
     pub parent_hash: H256,
@@ -106,6 +146,8 @@ pub struct Scheme {
 
     /// May be prepopulated if we know this in advance.
     state_root_memo: RwLock<H256>,
+    /// The state roots of the genesis shards, in the order of the shard ids.
+    shard_roots_memo: RwLock<Vec<H256>>,
 
     /// Genesis state as plain old data.
     genesis_accounts: PodAccounts,
@@ -137,17 +179,25 @@ impl Scheme {
 
     /// Convert engine scheme into a arc'd Engine of the right underlying type.
     /// TODO avoid this hard-coded nastiness - use dynamic-linked plugin framework instead.
-    fn engine(engine_scheme: cjson::scheme::Engine, params: CommonParams) -> Arc<CodeChainEngine> {
-        let machine = Self::machine(&engine_scheme, params);
+    fn engine(engine_scheme: cjson::scheme::Engine, params: CommonParams) -> Result<Arc<CodeChainEngine>, Error> {
+        let machine = Self::machine(&engine_scheme, params.clone());
 
-        match engine_scheme {
+        Ok(match engine_scheme {
             cjson::scheme::Engine::Null(null) => Arc::new(NullEngine::new(null.params.into(), machine)),
             cjson::scheme::Engine::Solo(solo) => Arc::new(Solo::new(solo.params.into(), machine)),
             cjson::scheme::Engine::SimplePoA(simple_poa) => Arc::new(SimplePoA::new(simple_poa.params.into(), machine)),
+            cjson::scheme::Engine::Clique(clique) => Arc::new(Clique::new(clique.params.into(), machine)),
             cjson::scheme::Engine::Tendermint(tendermint) => Tendermint::new(tendermint.params.into(), machine),
             cjson::scheme::Engine::Cuckoo(cuckoo) => Arc::new(Cuckoo::new(cuckoo.params.into(), machine)),
             cjson::scheme::Engine::BlakePoW(blake_pow) => Arc::new(BlakePoW::new(blake_pow.params.into(), machine)),
-        }
+            cjson::scheme::Engine::Multi(multi) => {
+                let mut engines = Vec::with_capacity(multi.params.engines.len());
+                for transition in multi.params.engines {
+                    engines.push((transition.block.into(), Self::engine(transition.engine, params.clone())?));
+                }
+                Arc::new(Multi::new(engines, machine).ok_or(SchemeError::InvalidEngineTransitions)?)
+            }
+        })
     }
 
     fn initialize_state<DB: Backend>(&self, db: DB) -> StateResult<DB> {
@@ -206,6 +256,7 @@ impl Scheme {
             let users = shard.users.clone();
             shards.push((ShardAddress::new(*shard_id), Shard::new(shard_root, owners, users)));
         }
+        *self.shard_roots_memo.write() = shards.iter().map(|(_, shard)| *shard.root()).collect();
 
         debug_assert_eq!(::std::mem::size_of::<u16>(), ::std::mem::size_of::<ShardId>());
         debug_assert!(shards.len() <= ::std::u16::MAX as usize, "{} <= {}", shards.len(), ::std::u16::MAX as usize);
@@ -312,6 +363,12 @@ impl Scheme {
         load_bundled!("simple_poa")
     }
 
+    /// Create a new Scheme with Clique consensus which does internal sealing (not requiring
+    /// work).
+    pub fn new_test_clique() -> Self {
+        load_bundled!("clique")
+    }
+
     /// Create a new Scheme with Tendermint consensus which does internal sealing (not requiring
     /// work).
     pub fn new_test_tendermint() -> Self {
@@ -341,6 +398,11 @@ impl Scheme {
         &self.engine.params()
     }
 
+    /// Get the addresses of the accounts in the genesis state.
+    pub fn genesis_addresses(&self) -> Vec<Address> {
+        self.genesis_accounts.keys().cloned().collect()
+    }
+
     /// Get the header of the genesis block.
     pub fn genesis_header(&self) -> Header {
         let mut header: Header = Default::default();
@@ -352,6 +414,7 @@ impl Scheme {
         header.set_extra_data(blake256(&self.params().rlp_bytes()).to_vec());
         header.set_state_root(self.state_root());
         header.set_invoices_root(self.invoices_root.clone());
+        header.set_shard_roots(self.shard_roots_memo.read().clone());
         header.set_score(self.score.clone());
         header.set_seal({
             let r = Rlp::new(&self.seal_rlp);
@@ -378,16 +441,27 @@ fn load_from(s: cjson::scheme::Scheme) -> Result<Scheme, Error> {
     let g = Genesis::from(s.genesis);
     let GenericSeal(seal_rlp) = g.seal.into();
     let params = CommonParams::from(s.params);
-    let engine = Scheme::engine(s.engine, params);
-    let custom_handlers = match &engine {
-        _ => vec![],
+    let fork_blocks = match &s.engine {
+        cjson::scheme::Engine::Multi(multi) => multi
+            .params
+            .engines
+            .iter()
+            .map(|transition| transition.block.into())
+            .filter(|block: &BlockNumber| *block != 0)
+            .collect(),
+        _ => Vec::new(),
     };
+    let engine = Scheme::engine(s.engine, params)?;
+    let custom_handlers = engine.action_handlers();
 
     let mut s = Scheme {
         name: s.name.clone().into(),
         engine,
         data_dir: s.data_dir.unwrap_or(s.name).into(),
         nodes: s.nodes.unwrap_or_else(Vec::new),
+        node_allow_list: s.node_allow_list.map(Into::into),
+        checkpoints: s.checkpoints.map(Into::into).unwrap_or_default(),
+        fork_blocks,
         parent_hash: g.parent_hash,
         parcels_root: g.parcels_root,
         invoices_root: g.invoices_root,
@@ -397,6 +471,7 @@ fn load_from(s: cjson::scheme::Scheme) -> Result<Scheme, Error> {
         extra_data: g.extra_data,
         seal_rlp,
         state_root_memo: RwLock::new(Default::default()), // will be overwritten right after.
+        shard_roots_memo: RwLock::new(Default::default()),
         genesis_accounts: s.accounts.into(),
         genesis_shards: s.shards.into(),
 
@@ -405,7 +480,12 @@ fn load_from(s: cjson::scheme::Scheme) -> Result<Scheme, Error> {
 
     // use memoized state root if provided.
     match g.state_root {
-        Some(root) => *s.state_root_memo.get_mut() = root,
+        Some(root) => {
+            *s.state_root_memo.get_mut() = root;
+            // The shard roots are not given, so the shards are initialized apart.
+            let db = StateDB::new_with_memorydb(0, s.custom_handlers.clone());
+            let _ = s.initialize_shards(db, BLAKE_NULL_RLP)?;
+        }
         None => {
             let db = StateDB::new_with_memorydb(0, s.custom_handlers.clone());
             let _ = s.initialize_state(db)?;
@@ -17,13 +17,16 @@
 use std::path::Path;
 use std::sync::Arc;
 
-use cio::{IoContext, IoHandler, IoHandlerResult, IoService};
+use cio::{IoContext, IoHandler, IoHandlerResult, IoService, TimerToken};
 use cnetwork::NodeId;
+use kvdb::KeyValueDB;
+use kvdb_memorydb;
 use kvdb_rocksdb::{Database, DatabaseConfig};
 use primitives::Bytes;
 
-use super::client::{Client, ClientConfig};
+use super::client::{Client, ClientConfig, DatabaseBackend};
 use super::error::Error;
+use super::migration::check_database_version;
 use super::miner::Miner;
 use super::scheme::Scheme;
 
@@ -42,16 +45,8 @@ impl ClientService {
     ) -> Result<ClientService, Error> {
         let io_service = IoService::<ClientIoMessage>::start()?;
 
-        let mut db_config = DatabaseConfig::with_columns(super::db::NUM_COLUMNS);
-
-        db_config.memory_budget = config.db_cache_size;
-        db_config.compaction = config.db_compaction.compaction_profile(client_path);
-        db_config.wal = config.db_wal;
-
-        let db = Arc::new(
-            Database::open(&db_config, &client_path.to_str().expect("DB path could not be converted to string."))
-                .map_err(::client::Error::Database)?,
-        );
+        let db = open_database(&config, client_path)?;
+        check_database_version(&*db)?;
 
         let client = Client::new(config, &scheme, db, miner, io_service.channel())?;
 
@@ -73,6 +68,25 @@ impl ClientService {
     }
 }
 
+/// Opens the client database at the given path, creating it if it does not exist and it's not read-only.
+/// The path is ignored if the database is kept in memory.
+pub fn open_database(config: &ClientConfig, client_path: &Path) -> Result<Arc<KeyValueDB>, Error> {
+    if config.db_backend == DatabaseBackend::Memory {
+        return Ok(Arc::new(kvdb_memorydb::create(super::db::NUM_COLUMNS.unwrap_or(0))))
+    }
+
+    let mut db_config = DatabaseConfig::with_columns(super::db::NUM_COLUMNS);
+
+    db_config.memory_budget = config.db_cache_size;
+    db_config.compaction = config.db_compaction.compaction_profile(client_path);
+    db_config.wal = config.db_wal;
+    db_config.read_only = config.db_read_only;
+
+    let db = Database::open(&db_config, &client_path.to_str().expect("DB path could not be converted to string."))
+        .map_err(::client::Error::Database)?;
+    Ok(Arc::new(db))
+}
+
 /// Message type for external and internal events
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum ClientIoMessage {
@@ -89,7 +103,27 @@ struct ClientIoHandler {
     client: Arc<Client>,
 }
 
+const DEFERRED_RESEAL_TIMER_TOKEN: TimerToken = 0;
+const DEFERRED_RESEAL_INTERVAL_MS: u64 = 100;
+const PARCEL_EXPIRY_TIMER_TOKEN: TimerToken = 1;
+const PARCEL_EXPIRY_INTERVAL_MS: u64 = 10_000;
+
 impl IoHandler<ClientIoMessage> for ClientIoHandler {
+    fn initialize(&self, io: &IoContext<ClientIoMessage>) -> IoHandlerResult<()> {
+        io.register_timer(DEFERRED_RESEAL_TIMER_TOKEN, DEFERRED_RESEAL_INTERVAL_MS)?;
+        io.register_timer(PARCEL_EXPIRY_TIMER_TOKEN, PARCEL_EXPIRY_INTERVAL_MS)?;
+        Ok(())
+    }
+
+    fn timeout(&self, _io: &IoContext<ClientIoMessage>, timer: TimerToken) -> IoHandlerResult<()> {
+        match timer {
+            DEFERRED_RESEAL_TIMER_TOKEN => self.client.update_deferred_sealing(),
+            PARCEL_EXPIRY_TIMER_TOKEN => self.client.remove_expired_parcels(),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
     fn message(&self, _io: &IoContext<ClientIoMessage>, net_message: &ClientIoMessage) -> IoHandlerResult<()> {
         match net_message {
             ClientIoMessage::BlockVerified => {
@@ -105,3 +139,34 @@ impl IoHandler<ClientIoMessage> for ClientIoHandler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::client::{BlockInfo, ChainInfo, EngineClient};
+    use super::super::miner::MinerOptions;
+    use super::*;
+
+    #[test]
+    fn client_runs_on_memory_database() {
+        let scheme = Scheme::new_test_solo();
+        let options = MinerOptions {
+            force_sealing: true,
+            ..Default::default()
+        };
+        let miner = Miner::new(options, &scheme, None);
+        let config = ClientConfig {
+            db_backend: DatabaseBackend::Memory,
+            ..Default::default()
+        };
+        let service = ClientService::start(config, &scheme, Path::new("/nonexistent"), miner).unwrap();
+        let client = service.client();
+        for _ in 0..3 {
+            client.update_sealing();
+        }
+
+        let chain_info = client.chain_info();
+        assert!(chain_info.best_block_number > 0);
+        assert_eq!((chain_info.best_block_number, chain_info.best_block_hash), client.best_block_number_and_hash());
+        assert_eq!(chain_info.best_block_hash, client.best_block_header().hash());
+    }
+}
@@ -128,7 +128,7 @@ impl NetworkExtension for ShardValidator {
     }
 
     fn on_message(&self, from: &NodeId, message: &[u8]) {
-        let message = match Message::decode(&UntrustedRlp::new(&message)) {
+        let message = match UntrustedRlp::new_checked(&message).and_then(|rlp| Message::decode(&rlp)) {
             Ok(message) => message,
             Err(err) => {
                 cwarn!(SHARD_VALIDATOR, "Invalid message from {:?}: {:?}", from, err);
@@ -161,7 +161,7 @@ impl NetworkExtension for ShardValidator {
 
                         let api = api.as_ref().expect("The extension must be initialized first");
                         for node in nodes.iter() {
-                            api.send(node, &message);
+                            send(api, node, &message);
                         }
                     }
                 }
@@ -171,7 +171,7 @@ impl NetworkExtension for ShardValidator {
 
                 let message = Message::Action(action).rlp_bytes();
                 for node in nodes.iter().filter(|node| node != &from) {
-                    api.send(node, &message);
+                    send(api, node, &message);
                 }
             }
             Message::Signatures {
@@ -191,7 +191,7 @@ impl NetworkExtension for ShardValidator {
                     }.rlp_bytes();
                     let api = api.as_ref().expect("The extension must be initialized first");
                     for node in nodes.iter().filter(|node| node != &from) {
-                        api.send(node, &message);
+                        send(api, node, &message);
                     }
                 }
             }
@@ -201,13 +201,18 @@ impl NetworkExtension for ShardValidator {
 
                 if let Some(action) = actions.get(&action_hash) {
                     let api = api.as_ref().expect("The extension must be initialized first");
-                    api.send(from, &Message::Action(action.clone()).rlp_bytes());
+                    send(api, from, &Message::Action(action.clone()).rlp_bytes());
                 }
             }
         }
     }
 }
 
+fn send(api: &Api, node: &NodeId, message: &[u8]) {
+    if let Err(err) = api.send(node, message) {
+        cwarn!(SHARD_VALIDATOR, "Cannot send a message to {}: {:?}", node, err);
+    }
+}
 
 fn insert_signatures(
     signatures_map: &mut HashMap<H256, HashSet<Signature>>,
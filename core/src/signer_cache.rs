@@ -0,0 +1,100 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ckey::{self, Public};
+use parking_lot::RwLock;
+use primitives::H256;
+
+use super::blockchain::{CacheStats, LruCache};
+use super::parcel::{SignedParcel, UnverifiedParcel};
+
+/// The number of the signers kept by default. A pooled parcel is usually included in one of the next blocks, so it
+/// only needs to cover the mem pool and the blocks being verified.
+pub const DEFAULT_SIGNER_CACHE_SIZE: usize = 32 * 1024;
+
+/// Maps the hash of a parcel to its recovered signer. It's shared by the mem pool and the block verifier, so a pooled
+/// parcel is not recovered again when it arrives in a block.
+///
+/// The hash covers the signature, so the parcels of the same hash always have the same signer.
+pub struct SignerCache {
+    signers: RwLock<LruCache<H256, Public>>,
+}
+
+impl SignerCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            signers: RwLock::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Recovers the signer of the parcel, unless it's cached. Only the valid signatures are cached.
+    pub fn recover(&self, parcel: UnverifiedParcel) -> Result<SignedParcel, ckey::Error> {
+        let hash = parcel.hash();
+        let cached = self.signers.read().get(&hash).cloned();
+        if let Some(signer_public) = cached {
+            return Ok(SignedParcel::new_with_signer(parcel, signer_public))
+        }
+        let signed = SignedParcel::new(parcel)?;
+        self.signers.write().insert(hash, signed.signer_public());
+        Ok(signed)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.signers.read().stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{Generator, NetworkId, Random, Signature};
+    use ctypes::parcel::{Action, Parcel};
+
+    use super::*;
+
+    fn parcel(nonce: u64) -> Parcel {
+        Parcel {
+            nonce: nonce.into(),
+            fee: 10.into(),
+            network_id: NetworkId::default(),
+            action: Action::Payment {
+                receiver: Random.generate().unwrap().address(),
+                amount: 1.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn recovered_signer_is_reused() {
+        let keypair = Random.generate().unwrap();
+        let parcel: UnverifiedParcel = SignedParcel::new_with_sign(parcel(0), keypair.private()).into();
+        let cache = SignerCache::new(10);
+
+        assert_eq!(*keypair.public(), cache.recover(parcel.clone()).unwrap().signer_public());
+        assert_eq!(0, cache.stats().hits);
+        assert_eq!(*keypair.public(), cache.recover(parcel).unwrap().signer_public());
+        assert_eq!(1, cache.stats().hits);
+        assert_eq!(1, cache.stats().len);
+    }
+
+    #[test]
+    fn invalid_signature_is_not_cached() {
+        let cache = SignerCache::new(10);
+        let parcel = UnverifiedParcel::new(parcel(0), Signature::default());
+        assert!(cache.recover(parcel.clone()).is_err());
+        assert!(cache.recover(parcel).is_err());
+        assert_eq!(0, cache.stats().len);
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use cstate::AssetSchemeAddress;
+use ctypes::parcel::{Action, Parcel};
+use ctypes::transaction::Transaction;
+use ctypes::BlockNumber;
+use primitives::{Bloom, BloomInput, H256};
+
+use super::BlockId;
+
+/// The topics of a parcel beyond it are neither accrued into the bloom nor returned in its log.
+pub const MAX_PARCEL_TOPICS: usize = 256;
+
+/// Topics of a parcel, which are accrued into the log bloom of the block.
+///
+/// Addresses are left-padded to 32 bytes.
+pub fn parcel_topics(parcel: &Parcel) -> Vec<H256> {
+    let mut topics = Vec::new();
+    match &parcel.action {
+        Action::AssetTransactionGroup {
+            transactions,
+            ..
+        } => {
+            for transaction in transactions {
+                topics.push(transaction.hash());
+                transaction_topics(transaction, &mut topics);
+            }
+        }
+        Action::Payment {
+            receiver,
+            ..
+        }
+        | Action::TransferName {
+            receiver,
+            ..
+        } => topics.push(H256::from(**receiver)),
+        Action::SetShardOwners {
+            owners: addresses,
+            ..
+        }
+        | Action::SetShardUsers {
+            users: addresses,
+            ..
+        } => topics.extend(addresses.iter().map(|address| H256::from(**address))),
+        Action::SetRegularKey {
+            ..
+        }
+        | Action::CreateShard
+        | Action::RegisterName {
+            ..
+        }
+        | Action::UpdateName {
+            ..
+        }
+        | Action::Custom(_) => {}
+    }
+    topics.truncate(MAX_PARCEL_TOPICS);
+    topics
+}
+
+fn transaction_topics(transaction: &Transaction, topics: &mut Vec<H256>) {
+    match transaction {
+        Transaction::AssetMint {
+            shard_id,
+            world_id,
+            output,
+            ..
+        } => {
+            let asset_type = AssetSchemeAddress::new(transaction.hash(), *shard_id, *world_id);
+            topics.push(asset_type.into());
+            topics.push(output.lock_script_hash);
+        }
+        Transaction::AssetTransfer {
+            burns,
+            inputs,
+            outputs,
+            ..
+        } => {
+            for input in burns.iter().chain(inputs.iter()) {
+                topics.push(input.prev_out.transaction_hash);
+            }
+            for output in outputs {
+                topics.push(output.asset_type);
+                topics.push(output.lock_script_hash);
+            }
+        }
+        Transaction::CreateWorld {
+            ..
+        }
+        | Transaction::SetWorldOwners {
+            ..
+        }
+        | Transaction::SetWorldUsers {
+            ..
+        } => {}
+    }
+}
+
+/// Bloom of the given topics.
+pub fn topics_bloom<'a, I>(topics: I) -> Bloom
+where
+    I: IntoIterator<Item = &'a H256>, {
+    let mut bloom = Bloom::default();
+    for topic in topics {
+        bloom.accrue(BloomInput::Raw(topic));
+    }
+    bloom
+}
+
+/// Parcel filter for `BlockChainClient::logs`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Filter {
+    /// Blockchain will be searched from this block.
+    pub from_block: BlockId,
+    /// Till this block.
+    pub to_block: BlockId,
+    /// Parcels having any of these topics match.
+    /// An empty list matches every parcel.
+    pub topics: Vec<H256>,
+    /// The number of the logs returned at most, from the earliest one.
+    pub limit: usize,
+}
+
+impl Filter {
+    /// Blooms that a block should contain at least one of to have a matching parcel.
+    pub fn bloom_possibilities(&self) -> Vec<Bloom> {
+        self.topics.iter().map(|topic| topics_bloom(::std::iter::once(topic))).collect()
+    }
+
+    /// Returns true if the parcel having `topics` matches the filter.
+    pub fn matches(&self, topics: &[H256]) -> bool {
+        self.topics.is_empty() || self.topics.iter().any(|topic| topics.contains(topic))
+    }
+}
+
+/// Parcel matched by a `Filter`, with its location in the canon chain.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LocalizedLog {
+    /// Topics of the parcel.
+    pub topics: Vec<H256>,
+    /// Block number.
+    pub block_number: BlockNumber,
+    /// Block hash.
+    pub block_hash: H256,
+    /// Parcel hash.
+    pub parcel_hash: H256,
+    /// Parcel index within the block.
+    pub parcel_index: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = Filter {
+            from_block: BlockId::Earliest,
+            to_block: BlockId::Latest,
+            topics: vec![],
+            limit: 10,
+        };
+        assert!(filter.matches(&[]));
+        assert!(filter.matches(&[H256::from(1)]));
+    }
+
+    #[test]
+    fn filter_matches_any_topic() {
+        let topic = H256::from(2);
+        let filter = Filter {
+            from_block: BlockId::Earliest,
+            to_block: BlockId::Latest,
+            topics: vec![H256::from(1), topic],
+            limit: 10,
+        };
+        assert!(filter.matches(&[topic]));
+        assert!(!filter.matches(&[H256::from(3)]));
+    }
+
+    #[test]
+    fn bloom_of_topics_contains_each_possibility() {
+        let topics = vec![H256::from(1), H256::from(2)];
+        let bloom = topics_bloom(&topics);
+        let filter = Filter {
+            from_block: BlockId::Earliest,
+            to_block: BlockId::Latest,
+            topics: topics.clone(),
+            limit: 10,
+        };
+        for possibility in filter.bloom_possibilities() {
+            assert!(bloom.contains_bloom(&possibility));
+        }
+    }
+
+    #[test]
+    fn topics_of_a_parcel_are_bounded() {
+        let parcel = Parcel {
+            fee: 0.into(),
+            action: Action::SetShardUsers {
+                shard_id: 0,
+                users: vec![Default::default(); MAX_PARCEL_TOPICS + 1],
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(MAX_PARCEL_TOPICS, parcel_topics(&parcel).len());
+    }
+}
@@ -16,8 +16,12 @@
 
 mod block_status;
 mod ids;
+mod log_entry;
+mod sync_progress;
 mod verification_queue_info;
 
 pub use self::block_status::BlockStatus;
 pub use self::ids::{BlockId, ParcelId, TransactionId};
+pub use self::log_entry::{parcel_topics, topics_bloom, Filter, LocalizedLog};
+pub use self::sync_progress::SyncProgress;
 pub use self::verification_queue_info::VerificationQueueInfo;
@@ -0,0 +1,28 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ctypes::BlockNumber;
+
+/// The progress of the block sync, reported by the sync extension.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncProgress {
+    /// The best block number when the sync started.
+    pub starting_block: BlockNumber,
+    /// The highest block number whose header is downloaded from the peers.
+    pub highest_block: BlockNumber,
+    /// The number of the blocks imported per second, measured over the recent seconds.
+    pub blocks_per_second: f64,
+}
@@ -34,6 +34,11 @@ pub trait BlockLike {
 
     /// Get the score of this item.
     fn score(&self) -> U256;
+
+    /// Whether a checkpoint guarantees the seal of this item, so that the seal is not checked.
+    fn is_seal_guaranteed(&self) -> bool {
+        false
+    }
 }
 
 /// Defines transitions between stages of verification.
@@ -126,6 +131,7 @@ pub mod headers {
 pub mod blocks {
     use heapsize::HeapSizeOf;
     use primitives::{Bytes, H256, U256};
+    use rlp::{DecoderError, UntrustedRlp};
 
     use super::super::super::super::consensus::CodeChainEngine;
     use super::super::super::super::error::Error;
@@ -172,18 +178,27 @@ pub mod blocks {
     pub struct Unverified {
         header: Header,
         bytes: Bytes,
+        seal_guaranteed: bool,
     }
 
     impl Unverified {
-        /// Create an `Unverified` from raw bytes.
-        pub fn new(bytes: Bytes) -> Self {
-            use views::BlockView;
-
-            let header = BlockView::new(&bytes).header();
-            Unverified {
+        /// Create an `Unverified` from raw bytes. The bytes come from the peers, so they may be malformed.
+        pub fn new(bytes: Bytes) -> Result<Self, DecoderError> {
+            let header = UntrustedRlp::new_checked(&bytes)?.val_at(0)?;
+            Ok(Unverified {
                 header,
                 bytes,
-            }
+                seal_guaranteed: false,
+            })
+        }
+
+        pub fn header(&self) -> &Header {
+            &self.header
+        }
+
+        /// Sets whether a checkpoint guarantees the seal of the block.
+        pub fn set_seal_guaranteed(&mut self, guaranteed: bool) {
+            self.seal_guaranteed = guaranteed;
         }
     }
 
@@ -205,6 +220,10 @@ pub mod blocks {
         fn score(&self) -> U256 {
             self.header.score().clone()
         }
+
+        fn is_seal_guaranteed(&self) -> bool {
+            self.seal_guaranteed
+        }
     }
 
     impl BlockLike for PreverifiedBlock {
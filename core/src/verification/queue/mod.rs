@@ -227,7 +227,8 @@ impl<K: Kind> VerificationQueue<K> {
             };
 
             let hash = item.hash();
-            let is_ready = match K::verify(item, &*engine, verification.check_seal) {
+            let check_seal = verification.check_seal && !item.is_seal_guaranteed();
+            let is_ready = match K::verify(item, &*engine, check_seal) {
                 Ok(verified) => {
                     let mut verifying = verification.verifying.lock();
                     let mut idx = None;
@@ -534,19 +535,25 @@ mod tests {
     #[test]
     fn import_blocks() {
         let queue = get_test_queue();
-        if let Err(e) = queue.import(Unverified::new(get_good_dummy_block())) {
+        if let Err(e) = queue.import(Unverified::new(get_good_dummy_block()).unwrap()) {
             panic!("error importing block that is valid by definition({:?})", e);
         }
     }
 
+    #[test]
+    fn malformed_block_is_an_error() {
+        assert!(Unverified::new(vec![0xc1, 0x80, 0x80]).is_err());
+        assert!(Unverified::new(vec![0xc0]).is_err());
+    }
+
     #[test]
     fn return_error_for_duplicates() {
         let queue = get_test_queue();
-        if let Err(e) = queue.import(Unverified::new(get_good_dummy_block())) {
+        if let Err(e) = queue.import(Unverified::new(get_good_dummy_block()).unwrap()) {
             panic!("error importing block that is valid by definition({:?})", e);
         }
 
-        let duplicate_import = queue.import(Unverified::new(get_good_dummy_block()));
+        let duplicate_import = queue.import(Unverified::new(get_good_dummy_block()).unwrap());
         match duplicate_import {
             Err(e) => match e {
                 Error::Import(ImportError::AlreadyQueued) => {}
@@ -21,6 +21,7 @@ use ctypes::util::unexpected::{Mismatch, OutOfBounds};
 use ctypes::BlockNumber;
 use heapsize::HeapSizeOf;
 use primitives::{Bytes, H256};
+use rayon::prelude::*;
 use rlp::UntrustedRlp;
 
 use super::super::blockchain::BlockProvider;
@@ -140,15 +141,14 @@ pub fn verify_block_unordered(
     if check_seal {
         engine.verify_block_unordered(&header)?;
     }
-    // Verify parcels.
-    let mut parcels = Vec::new();
-    {
-        let v = BlockView::new(&bytes);
-        for t in v.parcels() {
-            let signed_parcel = engine.verify_parcel_unordered(t, &header)?;
-            parcels.push(signed_parcel);
-        }
-    }
+    // Verify parcels. Recovering the signers dominates the import time, so they are recovered in parallel.
+    // The signers are kept in the `SignedParcel`s, so the execution doesn't recover them again.
+    // The header memoizes its hash in a `RefCell`, so each job gets its own copy.
+    let parcels = BlockView::new(&bytes)
+        .parcels()
+        .into_par_iter()
+        .map_with(header.clone(), |header, parcel| engine.verify_parcel_unordered(parcel, header))
+        .collect::<Result<Vec<_>, _>>()?;
     Ok(PreverifiedBlock {
         header,
         parcels,
@@ -196,6 +196,12 @@ pub fn verify_block_family<C: BlockInfo + TransactionInfo>(
     Ok(())
 }
 
+/// Check the header against its parent, for the clients which keep only the headers.
+pub fn verify_header_family(header: &Header, parent: &Header, engine: &CodeChainEngine) -> Result<(), Error> {
+    verify_parent(header, parent)?;
+    engine.verify_block_family(header, parent)
+}
+
 /// Check header parameters agains parent header.
 fn verify_parent(header: &Header, parent: &Header) -> Result<(), Error> {
     if !header.parent_hash().is_zero() && &parent.hash() != header.parent_hash() {
@@ -243,5 +249,17 @@ pub fn verify_block_final(expected: &Header, got: &Header) -> Result<(), Error>
             found: *got.invoices_root(),
         })))
     }
+    if expected.log_bloom() != got.log_bloom() {
+        return Err(From::from(BlockError::InvalidLogBloom(Mismatch {
+            expected: *expected.log_bloom(),
+            found: *got.log_bloom(),
+        })))
+    }
+    if expected.shard_roots() != got.shard_roots() {
+        return Err(From::from(BlockError::InvalidShardRoots(Mismatch {
+            expected: expected.shard_roots_hash(),
+            found: got.shard_roots_hash(),
+        })))
+    }
     Ok(())
 }
@@ -17,7 +17,7 @@
 use ccrypto::blake256;
 use ckey::Address;
 use ctypes::BlockNumber;
-use primitives::{Bytes, H256, U256};
+use primitives::{Bloom, Bytes, H256, U256};
 use rlp::{self, Rlp};
 
 /// View onto block header rlp.
@@ -95,10 +95,20 @@ impl<'a> HeaderView<'a> {
         self.rlp.val_at(8)
     }
 
+    /// Returns block log bloom.
+    pub fn log_bloom(&self) -> Bloom {
+        self.rlp.val_at(9)
+    }
+
+    /// Returns the state roots of the shards.
+    pub fn shard_roots(&self) -> Vec<H256> {
+        self.rlp.list_at(10)
+    }
+
     /// Returns a vector of post-RLP-encoded seal fields.
     pub fn seal(&self) -> Vec<Bytes> {
         let mut seal = vec![];
-        for i in 9..self.rlp.item_count() {
+        for i in 11..self.rlp.item_count() {
             seal.push(self.rlp.at(i).as_raw().to_vec());
         }
         seal
@@ -0,0 +1,151 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use primitives::{H256, H512};
+use ring::signature::{self, Ed25519KeyPair};
+use untrusted::Input;
+
+/// Derives the key pair from the 32-byte seed.
+/// The secret key is the seed followed by the public key, as in RFC 8032.
+pub fn keypair(seed: &H256) -> (H512, H256) {
+    let pair = Ed25519KeyPair::from_seed_unchecked(Input::from(seed)).expect("Any 32 bytes are a seed");
+    let public = H256::from_slice(pair.public_key_bytes());
+    let mut secret = H512::default();
+    secret[0..32].copy_from_slice(seed);
+    secret[32..64].copy_from_slice(&public);
+    (secret, public)
+}
+
+pub fn sign(message: &[u8], secret: &H512) -> H512 {
+    let pair = Ed25519KeyPair::from_seed_and_public_key(Input::from(&secret[0..32]), Input::from(&secret[32..64]))
+        .expect("The secret key is the seed followed by its public key");
+    H512::from_slice(pair.sign(message).as_ref())
+}
+
+/// The public key and R of the signature must be encoded canonically, and S must be reduced.
+/// Otherwise the same key or the same signature has several encodings.
+pub fn verify(message: &[u8], public: &H256, signature: &H512) -> bool {
+    if !is_canonical_point(public) || !is_canonical_point(&signature[0..32]) || !is_canonical(signature) {
+        return false
+    }
+    signature::verify(&signature::ED25519, Input::from(public), Input::from(message), Input::from(signature)).is_ok()
+}
+
+/// The order of the base point in little endian.
+const L: [u8; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+];
+
+/// The prime of the field, 2^255 - 19, in little endian.
+const P: [u8; 32] = [
+    0xed, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+];
+
+/// Checks whether S of the signature is less than the order of the base point.
+/// `verify` rejects the others, since S + L is also a valid S.
+pub fn is_canonical(signature: &H512) -> bool {
+    is_less_than(&signature[32..64], &L)
+}
+
+/// Checks whether y of the encoded point is less than the prime, and x is not encoded as -0.
+fn is_canonical_point(point: &[u8]) -> bool {
+    let mut y = [0u8; 32];
+    y.copy_from_slice(point);
+    let x_is_negative = y[31] & 0x80 != 0;
+    y[31] &= 0x7f;
+    if !is_less_than(&y, &P) {
+        return false
+    }
+    // x is 0 when y is 1 or -1.
+    let mut one = [0u8; 32];
+    one[0] = 1;
+    let mut minus_one = P;
+    minus_one[0] -= 1;
+    !x_is_negative || (y != one && y != minus_one)
+}
+
+/// Compares the numbers in little endian.
+fn is_less_than(a: &[u8], b: &[u8; 32]) -> bool {
+    for i in (0..32).rev() {
+        if a[i] != b[i] {
+            return a[i] < b[i]
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first test vector of RFC 8032
+    #[test]
+    fn sign_empty_message() {
+        let seed = H256::from("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+        let (secret, public) = keypair(&seed);
+        assert_eq!(H256::from("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"), public);
+
+        let signature = sign(&[], &secret);
+        assert_eq!(
+            H512::from(
+                "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+            ),
+            signature
+        );
+        assert!(verify(&[], &public, &signature));
+        assert!(!verify(&[0], &public, &signature));
+        assert!(is_canonical(&signature));
+    }
+
+    #[test]
+    fn order_is_not_canonical() {
+        let mut signature = H512::default();
+        signature[32..64].copy_from_slice(&L);
+        assert!(!is_canonical(&signature));
+        signature[32] -= 1;
+        assert!(is_canonical(&signature));
+    }
+
+    #[test]
+    fn points_are_encoded_canonically() {
+        let public = H256::from("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        assert!(is_canonical_point(&public));
+
+        let mut y = P;
+        assert!(!is_canonical_point(&y));
+        y[0] -= 1;
+        assert!(is_canonical_point(&y));
+        y[31] |= 0x80;
+        assert!(!is_canonical_point(&y));
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert!(is_canonical_point(&one));
+        one[31] |= 0x80;
+        assert!(!is_canonical_point(&one));
+    }
+
+    #[test]
+    fn signature_with_non_canonical_r_is_rejected() {
+        let (secret, public) = keypair(&H256::random());
+        let mut signature = sign(b"message", &secret);
+        assert!(verify(b"message", &public, &signature));
+        signature[0..32].copy_from_slice(&P);
+        assert!(!verify(b"message", &public, &signature));
+    }
+}
@@ -19,9 +19,11 @@ extern crate primitives;
 #[macro_use]
 extern crate quick_error;
 extern crate ring;
+extern crate untrusted;
 
 pub mod aes;
 mod blake;
+pub mod ed25519;
 pub mod error;
 mod hash;
 pub mod pbkdf2;
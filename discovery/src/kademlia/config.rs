@@ -17,6 +17,8 @@
 pub struct Config {
     pub bucket_size: u8,
     pub t_refresh: u32,
+    /// Shares only the nodes which are known to accept connections.
+    pub verified_only: bool,
 }
 
 use super::K;
@@ -30,6 +32,7 @@ impl Config {
         Self {
             bucket_size,
             t_refresh,
+            verified_only: false,
         }
     }
 }
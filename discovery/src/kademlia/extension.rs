@@ -73,7 +73,7 @@ impl NetworkExtension for Extension {
         let api = self.api.read();
         let mut nodes = self.nodes.write();
         nodes.insert(node.clone());
-        api.as_ref().map(|api| api.send(&node, &Message::FindNode(self.config.bucket_size).rlp_bytes()));
+        api.as_ref().map(|api| send(api, &node, &Message::FindNode(self.config.bucket_size).rlp_bytes()));
     }
 
     fn on_node_removed(&self, node: &NodeId) {
@@ -82,7 +82,7 @@ impl NetworkExtension for Extension {
     }
 
     fn on_message(&self, node: &NodeId, message: &[u8]) {
-        let message = match Message::decode(&UntrustedRlp::new(&message)) {
+        let message = match UntrustedRlp::new_checked(&message).and_then(|rlp| Message::decode(&rlp)) {
             Ok(message) => message,
             Err(err) => {
                 cwarn!(DISCOVERY, "Invalid message from {} : {:?}", node, err);
@@ -96,8 +96,13 @@ impl NetworkExtension for Extension {
                 match (&*api, &*routing_table) {
                     (Some(api), Some(routing_table)) => {
                         let datum = address_to_hash(&node.into_addr());
-                        let mut addresses = routing_table
-                            .reachable_addresses(&node.into_addr())
+                        let from = node.into_addr();
+                        let addresses = if self.config.verified_only {
+                            routing_table.verified_addresses(&from)
+                        } else {
+                            routing_table.reachable_addresses(&from)
+                        };
+                        let mut addresses = addresses
                             .into_iter()
                             .map(|address| KademliaId::new(address.clone(), &datum))
                             .collect::<Vec<_>>();
@@ -110,7 +115,7 @@ impl NetworkExtension for Extension {
                             .take(::std::cmp::min(self.config.bucket_size, len) as usize)
                             .collect();
                         let response = Message::Nodes(addresses).rlp_bytes();
-                        api.send(&node, &response);
+                        send(api, &node, &response);
                     }
                     _ => {}
                 }
@@ -131,17 +136,7 @@ impl NetworkExtension for Extension {
 
     fn on_timeout(&self, timer: TimerToken) {
         match timer {
-            REFRESH_TOKEN => {
-                let mut api = self.api.read();
-                let nodes = self.nodes.read();
-
-                api.as_ref().map(|api| {
-                    let request = Message::FindNode(self.config.bucket_size).rlp_bytes();
-                    for node in nodes.iter() {
-                        api.send(&node, &request);
-                    }
-                });
-            }
+            REFRESH_TOKEN => self.refresh(),
             _ => unreachable!(),
         }
     }
@@ -151,4 +146,22 @@ impl DiscoveryApi for Extension {
     fn set_routing_table(&self, routing_table: Arc<RoutingTable>) {
         *self.routing_table.write() = Some(routing_table);
     }
+
+    fn refresh(&self) {
+        let api = self.api.read();
+        let nodes = self.nodes.read();
+
+        api.as_ref().map(|api| {
+            let request = Message::FindNode(self.config.bucket_size).rlp_bytes();
+            for node in nodes.iter() {
+                send(api, &node, &request);
+            }
+        });
+    }
+}
+
+fn send(api: &Api, node: &NodeId, message: &[u8]) {
+    if let Err(err) = api.send(node, message) {
+        cdebug!(DISCOVERY, "Cannot send a message to {}: {:?}", node, err);
+    }
 }
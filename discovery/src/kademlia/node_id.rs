@@ -47,23 +47,21 @@ impl Into<SocketAddr> for KademliaId {
 pub fn address_to_hash(addr: &SocketAddr) -> H256 {
     let ip = addr.ip();
     let port = addr.port();
-    match ip {
-        IpAddr::V4(ip) => {
-            if ip.is_loopback() || ip.is_private() {
-                let mut octets = [0u8; 18];
-                octets[0..16].clone_from_slice(&ip.to_ipv6_compatible().octets());
-                octets[16] = (port >> 8) as u8;
-                octets[17] = (port & 0xFF) as u8;
-                return Blake::blake(&octets)
-            }
-            let octets: [u8; 16] = ip.to_ipv6_compatible().octets();
-            let mut hash = H256::blake(&octets);
-            hash[14] ^= (port >> 8) as u8;
-            hash[15] ^= (port & 0xFF) as u8;
-            hash
-        }
-        IpAddr::V6(_ip) => unimplemented!(),
+    let (octets, is_local): ([u8; 16], bool) = match ip {
+        IpAddr::V4(ip) => (ip.to_ipv6_compatible().octets(), ip.is_loopback() || ip.is_private()),
+        IpAddr::V6(ip) => (ip.octets(), !addr.is_global()),
+    };
+    if is_local {
+        let mut local_octets = [0u8; 18];
+        local_octets[0..16].clone_from_slice(&octets);
+        local_octets[16] = (port >> 8) as u8;
+        local_octets[17] = (port & 0xFF) as u8;
+        return Blake::blake(&local_octets)
     }
+    let mut hash = H256::blake(&octets);
+    hash[14] ^= (port >> 8) as u8;
+    hash[15] ^= (port & 0xFF) as u8;
+    hash
 }
 
 fn log2_distance(addr: &SocketAddr, datum: &H256) -> usize {
@@ -17,6 +17,8 @@
 pub struct Config {
     pub bucket_size: u8,
     pub t_refresh: u32,
+    /// Shares only the nodes which are known to accept connections.
+    pub verified_only: bool,
 }
 
 use super::K;
@@ -27,6 +29,7 @@ impl Config {
         Self {
             bucket_size: bucket_size.unwrap_or(K),
             t_refresh: t_refresh.unwrap_or(T_REFRESH),
+            verified_only: false,
         }
     }
 }
@@ -73,7 +73,7 @@ impl NetworkExtension for Extension {
         let api = self.api.read();
         let mut nodes = self.nodes.write();
         nodes.insert(node.clone());
-        api.as_ref().map(|api| api.send(&node, &Message::Request(self.config.bucket_size).rlp_bytes()));
+        api.as_ref().map(|api| send(api, &node, &Message::Request(self.config.bucket_size).rlp_bytes()));
     }
 
     fn on_node_removed(&self, node: &NodeId) {
@@ -82,7 +82,7 @@ impl NetworkExtension for Extension {
     }
 
     fn on_message(&self, node: &NodeId, message: &[u8]) {
-        let message = match Message::decode(&UntrustedRlp::new(&message)) {
+        let message = match UntrustedRlp::new_checked(&message).and_then(|rlp| Message::decode(&rlp)) {
             Ok(message) => message,
             Err(err) => {
                 cwarn!(DISCOVERY, "Invalid message from {} : {:?}", node, err);
@@ -95,15 +95,20 @@ impl NetworkExtension for Extension {
                 let api = self.api.read();
                 match (&*api, &*routing_table) {
                     (Some(api), Some(routing_table)) => {
-                        let mut addresses =
-                            routing_table.reachable_addresses(&node.into_addr()).into_iter().collect::<Vec<_>>();
+                        let from = node.into_addr();
+                        let addresses = if self.config.verified_only {
+                            routing_table.verified_addresses(&from)
+                        } else {
+                            routing_table.reachable_addresses(&from)
+                        };
+                        let mut addresses = addresses.into_iter().collect::<Vec<_>>();
                         thread_rng().shuffle(&mut addresses);
                         let addresses = addresses
                             .into_iter()
                             .take(::std::cmp::min(self.config.bucket_size, len) as usize)
                             .collect();
                         let response = Message::Response(addresses).rlp_bytes();
-                        api.send(&node, &response);
+                        send(api, &node, &response);
                     }
                     _ => {}
                 }
@@ -124,17 +129,7 @@ impl NetworkExtension for Extension {
 
     fn on_timeout(&self, timer: TimerToken) {
         match timer {
-            REFRESH_TOKEN => {
-                let mut api = self.api.read();
-                let nodes = self.nodes.read();
-
-                api.as_ref().map(|api| {
-                    let request = Message::Request(self.config.bucket_size).rlp_bytes();
-                    for node in nodes.iter() {
-                        api.send(&node, &request);
-                    }
-                });
-            }
+            REFRESH_TOKEN => self.refresh(),
             _ => unreachable!(),
         }
     }
@@ -144,4 +139,22 @@ impl DiscoveryApi for Extension {
     fn set_routing_table(&self, routing_table: Arc<RoutingTable>) {
         *self.routing_table.write() = Some(routing_table);
     }
+
+    fn refresh(&self) {
+        let api = self.api.read();
+        let nodes = self.nodes.read();
+
+        api.as_ref().map(|api| {
+            let request = Message::Request(self.config.bucket_size).rlp_bytes();
+            for node in nodes.iter() {
+                send(api, &node, &request);
+            }
+        });
+    }
+}
+
+fn send(api: &Api, node: &NodeId, message: &[u8]) {
+    if let Err(err) = api.send(node, message) {
+        cdebug!(DISCOVERY, "Cannot send a message to {}: {:?}", node, err);
+    }
 }
@@ -0,0 +1,50 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use super::super::hash::H256;
+use super::super::uint::Uint;
+
+/// A block hash which every node following the chain must have at the number.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// Block number.
+    pub number: Uint,
+    /// Block hash.
+    pub hash: H256,
+}
+
+#[cfg(test)]
+mod tests {
+    use primitives::H256 as CoreH256;
+    use serde_json;
+
+    use super::super::super::hash::H256;
+    use super::super::super::uint::Uint;
+    use super::Checkpoint;
+
+    #[test]
+    fn checkpoint_deserialization() {
+        let s = r#"{
+            "number": "0x400",
+            "hash": "0x0000000000000000000000000000000000000000000000000000000000000001"
+        }"#;
+        let deserialized: Checkpoint = serde_json::from_str(s).unwrap();
+        assert_eq!(deserialized.number, Uint(0x400.into()));
+        assert_eq!(deserialized.hash, H256(CoreH256::from(1)));
+    }
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::{BlakePoW, Cuckoo, NullEngine, SimplePoA, Solo, Tendermint};
+use super::{BlakePoW, Clique, Cuckoo, Multi, NullEngine, SimplePoA, Solo, Tendermint};
 
 /// Engine deserialization.
 #[derive(Debug, PartialEq, Deserialize)]
@@ -24,9 +24,13 @@ pub enum Engine {
     Null(NullEngine),
     Solo(Solo),
     SimplePoA(SimplePoA),
+    /// Round-robin proof of authority.
+    Clique(Clique),
     Tendermint(Tendermint),
     Cuckoo(Cuckoo),
     BlakePoW(BlakePoW),
+    /// Engines switched at the given blocks.
+    Multi(Multi),
 }
 
 #[cfg(test)]
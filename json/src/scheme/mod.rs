@@ -16,9 +16,13 @@
 
 mod account;
 mod blake_pow;
+mod checkpoint;
+mod clique;
 mod cuckoo;
 mod engine;
 mod genesis;
+mod multi;
+mod node_allow_list;
 mod null_engine;
 mod params;
 mod scheme;
@@ -32,9 +36,13 @@ mod world;
 
 pub use self::account::Account;
 pub use self::blake_pow::{BlakePoW, BlakePoWParams};
+pub use self::checkpoint::Checkpoint;
+pub use self::clique::{Clique, CliqueParams};
 pub use self::cuckoo::{Cuckoo, CuckooParams};
 pub use self::engine::Engine;
 pub use self::genesis::Genesis;
+pub use self::multi::{EngineTransition, Multi, MultiParams};
+pub use self::node_allow_list::NodeAllowList;
 pub use self::null_engine::{NullEngine, NullEngineParams};
 pub use self::params::Params;
 pub use self::scheme::Scheme;
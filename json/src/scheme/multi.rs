@@ -0,0 +1,91 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use super::super::uint::Uint;
+use super::Engine;
+
+/// An engine which takes over the chain from the given block.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineTransition {
+    /// The first block sealed by the engine.
+    pub block: Uint,
+    pub engine: Engine,
+}
+
+/// Multi engine params deserialization.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiParams {
+    /// Engines in the order of their transitions. The first one must start from the genesis block.
+    pub engines: Vec<EngineTransition>,
+}
+
+/// Multi engine deserialization.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Multi {
+    pub params: MultiParams,
+}
+
+#[cfg(test)]
+mod tests {
+    use primitives::U256;
+    use serde_json;
+
+    use super::super::super::uint::Uint;
+    use super::super::Engine;
+    use super::Multi;
+
+    #[test]
+    fn multi_deserialization() {
+        let s = r#"{
+            "params": {
+                "engines": [{
+                    "block": "0",
+                    "engine": {
+                        "solo": {
+                            "params": {}
+                        }
+                    }
+                }, {
+                    "block": "0x64",
+                    "engine": {
+                        "tendermint": {
+                            "params": {
+                                "validators": ["tccqzzpxln6w5zrhmfju3zc53w6w4y6s95mf5hw0n62"]
+                            }
+                        }
+                    }
+                }]
+            }
+        }"#;
+
+        let deserialized: Multi = serde_json::from_str(s).unwrap();
+        let engines = deserialized.params.engines;
+        assert_eq!(2, engines.len());
+        assert_eq!(Uint(U256::from(0)), engines[0].block);
+        match engines[0].engine {
+            Engine::Solo(_) => {}
+            _ => panic!(),
+        }
+        assert_eq!(Uint(U256::from(100)), engines[1].block);
+        match engines[1].engine {
+            Engine::Tendermint(_) => {}
+            _ => panic!(),
+        }
+    }
+}
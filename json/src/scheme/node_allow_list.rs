@@ -0,0 +1,61 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::net::SocketAddr;
+
+use ckey::{PlatformAddress, Signature};
+
+use super::super::uint::Uint;
+
+/// Nodes allowed to connect in a fully permissioned network.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeAllowList {
+    /// The account which signs the list.
+    pub authority: PlatformAddress,
+    /// The version signed with the nodes. 0 if it is omitted.
+    #[serde(default)]
+    pub version: Uint,
+    /// Allowed nodes, in `ip:port` format.
+    pub nodes: Vec<SocketAddr>,
+    /// Signature of the authority. No node is allowed until a signed list is given via RPC if it is omitted.
+    pub signature: Option<Signature>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn node_allow_list_deserialization() {
+        let s = r#"{
+            "authority": "tccqzzpxln6w5zrhmfju3zc53w6w4y6s95mf5hw0n62",
+            "nodes": ["192.168.0.1:3485", "192.168.0.2:3485"]
+        }"#;
+        let deserialized: NodeAllowList = serde_json::from_str(s).unwrap();
+        assert_eq!(
+            deserialized.authority,
+            PlatformAddress::from_str("tccqzzpxln6w5zrhmfju3zc53w6w4y6s95mf5hw0n62").unwrap()
+        );
+        assert_eq!(deserialized.nodes, vec!["192.168.0.1:3485".parse().unwrap(), "192.168.0.2:3485".parse().unwrap()]);
+        assert_eq!(deserialized.version, Uint::default());
+        assert_eq!(deserialized.signature, None);
+    }
+}
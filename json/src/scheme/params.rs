@@ -36,6 +36,12 @@ pub struct Params {
     /// Snapshot creation period in unit of block numbers.
     pub snapshot_period: Uint,
     pub use_shard_validator: bool,
+    /// The balances which fall below it are burnt. The nonces of the accounts are kept.
+    pub min_balance: Option<Uint>,
+    /// Fee burned per block when a name is registered.
+    pub name_fee_per_block: Option<Uint>,
+    /// Fee charged for each signature of a multi-signature parcel beyond the first.
+    pub additional_signature_fee: Option<Uint>,
 }
 
 #[cfg(test)]
@@ -66,5 +72,59 @@ mod tests {
         assert_eq!(deserialized.max_body_size, Uint(4194304.into()));
         assert_eq!(deserialized.snapshot_period, Uint(16384.into()));
         assert_eq!(deserialized.use_shard_validator, true);
+        assert_eq!(deserialized.min_balance, None);
+        assert_eq!(deserialized.name_fee_per_block, None);
+        assert_eq!(deserialized.additional_signature_fee, None);
+    }
+
+    #[test]
+    fn params_deserialization_with_min_balance() {
+        let s = r#"{
+            "maxExtraDataSize": "0x20",
+            "maxMetadataSize": "0x0400",
+            "networkID" : "tc",
+            "minParcelCost" : "10",
+            "maxBodySize" : 4194304,
+            "snapshotPeriod": 16384,
+            "useShardValidator": false,
+            "minBalance": "1000"
+        }"#;
+
+        let deserialized: Params = serde_json::from_str(s).unwrap();
+        assert_eq!(deserialized.min_balance, Some(Uint(U256::from(1000))));
+    }
+
+    #[test]
+    fn params_deserialization_with_name_fee_per_block() {
+        let s = r#"{
+            "maxExtraDataSize": "0x20",
+            "maxMetadataSize": "0x0400",
+            "networkID" : "tc",
+            "minParcelCost" : "10",
+            "maxBodySize" : 4194304,
+            "snapshotPeriod": 16384,
+            "useShardValidator": false,
+            "nameFeePerBlock": "5"
+        }"#;
+
+        let deserialized: Params = serde_json::from_str(s).unwrap();
+        assert_eq!(deserialized.name_fee_per_block, Some(Uint(U256::from(5))));
+    }
+
+    #[test]
+    fn params_deserialization_with_additional_signature_fee() {
+        let s = r#"{
+            "maxExtraDataSize": "0x20",
+            "maxMetadataSize": "0x0400",
+            "networkID" : "tc",
+            "minParcelCost" : "10",
+            "maxBodySize" : 4194304,
+            "snapshotPeriod": 16384,
+            "useShardValidator": false,
+            "additionalSignatureFee": "3"
+        }"#;
+
+        let deserialized: Params = serde_json::from_str(s).unwrap();
+        assert_eq!(deserialized.additional_signature_fee, Some(Uint(U256::from(3))));
     }
 }
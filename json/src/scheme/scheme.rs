@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::{Accounts, Engine, Genesis, Params, Shards};
+use super::{Accounts, Checkpoint, Engine, Genesis, NodeAllowList, Params, Shards};
 use serde_json;
 use serde_json::Error;
 use std::io::Read;
@@ -38,6 +38,10 @@ pub struct Scheme {
     pub shards: Shards,
     /// Boot nodes.
     pub nodes: Option<Vec<String>>,
+    /// Only these nodes can connect if it exists.
+    pub node_allow_list: Option<NodeAllowList>,
+    /// Hashes of the canon blocks at the given numbers.
+    pub checkpoints: Option<Vec<Checkpoint>>,
 }
 
 impl Scheme {
@@ -34,6 +34,10 @@ pub struct TendermintParams {
     pub timeout_commit: Option<Uint>,
     /// Reward per block.
     pub block_reward: Option<Uint>,
+    /// Number of blocks for which both keys of a validator are accepted after a key rotation.
+    pub key_rotation_grace_period: Option<Uint>,
+    /// Whether the validators can be replaced by the approval of the current validators.
+    pub changeable_validators: Option<bool>,
 }
 
 /// Tendermint engine deserialization.
@@ -0,0 +1,69 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crypto::{blake512_with_key, ed25519};
+use primitives::{H256, H512};
+
+use super::{Message, Private, Public};
+
+pub const ED25519_PUBLIC_LENGTH: usize = 32;
+pub const ED25519_SIGNATURE_LENGTH: usize = 64;
+
+pub type Ed25519Public = H256;
+pub type Ed25519Signature = H512;
+
+/// Derives the Ed25519 public key of the private key, which is used as the seed.
+pub fn ed25519_public(private: &Private) -> Ed25519Public {
+    ed25519::keypair(private).1
+}
+
+pub fn sign_ed25519(private: &Private, message: &Message) -> Ed25519Signature {
+    let (secret, _) = ed25519::keypair(private);
+    ed25519::sign(message, &secret)
+}
+
+pub fn verify_ed25519(public: &Ed25519Public, signature: &Ed25519Signature, message: &Message) -> bool {
+    ed25519::verify(message, public, signature)
+}
+
+/// The key identifying the account of the Ed25519 public key.
+/// It is a hash rather than a point on secp256k1, so it never collides with the public key of an ECDSA account.
+pub fn ed25519_public_to_public(public: &Ed25519Public) -> Public {
+    blake512_with_key(public, b"ed25519")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Generator, Message, Random};
+    use super::{ed25519_public, ed25519_public_to_public, sign_ed25519, verify_ed25519};
+
+    #[test]
+    fn sign_and_verify() {
+        let keypair = Random.generate().unwrap();
+        let public = ed25519_public(keypair.private());
+        let message = Message::default();
+        let signature = sign_ed25519(keypair.private(), &message);
+        assert!(verify_ed25519(&public, &signature, &message));
+        assert!(!verify_ed25519(&public, &signature, &Message::from(1)));
+    }
+
+    #[test]
+    fn public_is_different_from_ecdsa_one() {
+        let keypair = Random.generate().unwrap();
+        let public = ed25519_public(keypair.private());
+        assert_ne!(keypair.public(), &ed25519_public_to_public(&public));
+    }
+}
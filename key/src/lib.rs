@@ -33,6 +33,7 @@ extern crate serde_json;
 
 mod address;
 mod ecdsa;
+mod ed25519;
 mod error;
 mod exchange;
 mod keypair;
@@ -48,6 +49,10 @@ pub use ecdsa::{
     recover_ecdsa as recover, sign_ecdsa as sign, verify_ecdsa as verify, verify_ecdsa_address as verify_address,
     ECDSASignature as Signature, ECDSA_SIGNATURE_LENGTH as SIGNATURE_LENGTH,
 };
+pub use ed25519::{
+    ed25519_public, ed25519_public_to_public, sign_ed25519, verify_ed25519, Ed25519Public, Ed25519Signature,
+    ED25519_PUBLIC_LENGTH, ED25519_SIGNATURE_LENGTH,
+};
 pub use error::Error;
 pub use exchange::exchange;
 pub use keypair::{public_to_address, KeyPair};
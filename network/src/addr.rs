@@ -17,7 +17,7 @@
 use std::cmp::Ordering;
 use std::convert::{From, Into};
 use std::fmt;
-use std::net::{self, AddrParseError, IpAddr, Ipv4Addr};
+use std::net::{self, AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
 use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
@@ -38,8 +38,8 @@ impl SocketAddr {
         SocketAddr::new(IpAddr::V4(Ipv4Addr::new(a, b, c, d)), port)
     }
 
-    pub fn v6(_a: u16, _b: u16, _c: u16, _d: u16, _e: u16, _f: u16, _g: u16, _h: u16, _port: u16) -> Self {
-        unimplemented!();
+    pub fn v6(a: u16, b: u16, c: u16, d: u16, e: u16, f: u16, g: u16, h: u16, port: u16) -> Self {
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::new(a, b, c, d, e, f, g, h)), port)
     }
 
     pub fn ip(&self) -> IpAddr {
@@ -50,10 +50,21 @@ impl SocketAddr {
         self.addr.port()
     }
 
+    /// The address to send to through an IPv6 socket. The IPv4 addresses are stored as IPv4, so they are mapped
+    /// back to ::ffff:a.b.c.d, which a dual-stack socket sends over IPv4.
+    pub fn to_ipv6_mapped(&self) -> net::SocketAddr {
+        match self.addr {
+            net::SocketAddr::V4(v4) => net::SocketAddr::new(IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port()),
+            net::SocketAddr::V6(_) => self.addr,
+        }
+    }
+
     pub fn is_global(&self) -> bool {
         match self.ip() {
             net::IpAddr::V4(ip) => !ip.is_loopback() && !ip.is_private(),
-            net::IpAddr::V6(_ip) => unimplemented!(),
+            net::IpAddr::V6(ip) => {
+                !ip.is_loopback() && !ip.is_unspecified() && !is_unique_local(&ip) && !is_link_local(&ip)
+            }
         }
     }
 
@@ -82,11 +93,45 @@ impl SocketAddr {
                 }
                 true
             }
-            _ => unimplemented!(),
+            (net::IpAddr::V6(self_ip), net::IpAddr::V6(other_ip)) => {
+                debug_assert_eq!(false, other_ip.is_multicast());
+                debug_assert_eq!(false, other_ip.is_unspecified());
+                if self_ip.is_loopback() {
+                    return true
+                }
+                if other_ip.is_loopback() {
+                    return false
+                }
+                // The link-local addresses are meaningless without the interface of the receiver.
+                if is_link_local(&other_ip) {
+                    return false
+                }
+                if is_unique_local(&other_ip) {
+                    return is_unique_local(&self_ip) && is_same_site(&self_ip, &other_ip)
+                }
+                true
+            }
+            // A node may not have the connectivity of the other family.
+            _ => false,
         }
     }
 }
 
+/// fc00::/7
+fn is_unique_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xfe00 == 0xfc00
+}
+
+/// fe80::/10
+fn is_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// The unique local addresses in the same /48 prefix belong to the same site.
+fn is_same_site(ip1: &Ipv6Addr, ip2: &Ipv6Addr) -> bool {
+    ip1.segments()[0..3] == ip2.segments()[0..3]
+}
+
 fn is_same_private_subnet(ip1: &Ipv4Addr, ip2: &Ipv4Addr) -> bool {
     debug_assert_eq!(true, ip1.is_private());
     debug_assert_eq!(true, ip2.is_private());
@@ -123,11 +168,30 @@ impl From<net::SocketAddr> for SocketAddr {
             net::SocketAddr::V4(_) => Self {
                 addr,
             },
-            net::SocketAddr::V6(_) => unimplemented!(),
+            // A dual-stack socket sees the IPv4 peers as the IPv4-mapped addresses.
+            // They are stored as IPv4 to keep one node id per node.
+            net::SocketAddr::V6(v6) => match to_ipv4_mapped(v6.ip()) {
+                Some(ip) => Self {
+                    addr: net::SocketAddr::new(IpAddr::V4(ip), v6.port()),
+                },
+                None => Self {
+                    addr,
+                },
+            },
         }
     }
 }
 
+/// ::ffff:a.b.c.d
+fn to_ipv4_mapped(ip: &Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    if segments[0..6] != [0, 0, 0, 0, 0, 0xffff] {
+        return None
+    }
+    let (high, low) = (segments[6], segments[7]);
+    Some(Ipv4Addr::new((high >> 8) as u8, high as u8, (low >> 8) as u8, low as u8))
+}
+
 impl Into<net::SocketAddr> for SocketAddr {
     fn into(self) -> net::SocketAddr {
         self.addr
@@ -143,8 +207,8 @@ impl<'a> Into<&'a net::SocketAddr> for &'a SocketAddr {
 impl FromStr for SocketAddr {
     type Err = AddrParseError;
     fn from_str(addr: &str) -> Result<Self, Self::Err> {
-        let addr = net::SocketAddrV4::from_str(addr)?;
-        Ok(Self::from(net::SocketAddr::V4(addr)))
+        let addr = net::SocketAddr::from_str(addr)?;
+        Ok(Self::from(addr))
     }
 }
 
@@ -185,7 +249,15 @@ impl Encodable for SocketAddr {
                 }
                 s.append(&self.port());
             }
-            IpAddr::V6(ref _addr) => unimplemented!(),
+            IpAddr::V6(ref addr) => {
+                let octets = addr.octets();
+                assert_eq!(16, octets.len());
+                s.begin_list(octets.len() + 1);
+                for octet in octets.iter() {
+                    s.append(octet);
+                }
+                s.append(&self.port());
+            }
         }
     }
 }
@@ -201,7 +273,14 @@ impl Decodable for SocketAddr {
                 let port = rlp.val_at(4)?;
                 Ok(SocketAddr::v4(ip0, ip1, ip2, ip3, port))
             }
-            17 => unimplemented!(),
+            17 => {
+                let mut octets = [0u8; 16];
+                for (i, octet) in octets.iter_mut().enumerate() {
+                    *octet = rlp.val_at(i)?;
+                }
+                let port = rlp.val_at(16)?;
+                Ok(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+            }
             _ => Err(DecoderError::RlpIncorrectListLen),
         }
     }
@@ -256,6 +335,67 @@ mod tests {
         assert_eq!(true, a3.is_global());
     }
 
+    #[test]
+    fn is_global_for_ipv6() {
+        let loopback = SocketAddr::v6(0, 0, 0, 0, 0, 0, 0, 1, 3485);
+        let unique_local = SocketAddr::v6(0xfd12, 0x3456, 0x789a, 1, 0, 0, 0, 1, 3485);
+        let link_local = SocketAddr::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1, 3485);
+        let global = SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888, 3485);
+        assert_eq!(false, loopback.is_global());
+        assert_eq!(false, unique_local.is_global());
+        assert_eq!(false, link_local.is_global());
+        assert_eq!(true, global.is_global());
+    }
+
+    #[test]
+    fn is_reachable_for_ipv6() {
+        let loopback = SocketAddr::v6(0, 0, 0, 0, 0, 0, 0, 1, 3485);
+        let site_1 = SocketAddr::v6(0xfd12, 0x3456, 0x789a, 1, 0, 0, 0, 1, 3485);
+        let site_2 = SocketAddr::v6(0xfd12, 0x3456, 0x789a, 2, 0, 0, 0, 1, 3485);
+        let other_site = SocketAddr::v6(0xfd00, 0, 0, 1, 0, 0, 0, 1, 3485);
+        let link_local = SocketAddr::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1, 3485);
+        let global_1 = SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888, 3485);
+        let global_2 = SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8844, 3485);
+
+        assert_eq!(true, loopback.is_reachable(&site_1));
+        assert_eq!(true, site_1.is_reachable(&site_2));
+        assert_eq!(false, site_1.is_reachable(&other_site));
+        assert_eq!(false, global_1.is_reachable(&site_1));
+        assert_eq!(false, site_1.is_reachable(&link_local));
+        assert_eq!(true, site_1.is_reachable(&global_1));
+        assert_eq!(true, global_1.is_reachable(&global_2));
+        assert_eq!(false, global_1.is_reachable(&loopback));
+    }
+
+    #[test]
+    fn different_families_are_not_reachable() {
+        let v4 = SocketAddr::v4(1, 1, 1, 1, 3485);
+        let v6 = SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888, 3485);
+        assert_eq!(false, v4.is_reachable(&v6));
+        assert_eq!(false, v6.is_reachable(&v4));
+    }
+
+    #[test]
+    fn ipv4_mapped_address_is_stored_as_ipv4() {
+        let mapped: net::SocketAddr = "[::ffff:1.2.3.4]:3485".parse().unwrap();
+        assert_eq!(SocketAddr::v4(1, 2, 3, 4, 3485), SocketAddr::from(mapped));
+    }
+
+    #[test]
+    fn ipv4_address_is_mapped_back_to_send_through_ipv6_socket() {
+        let mapped: net::SocketAddr = "[::ffff:1.2.3.4]:3485".parse().unwrap();
+        assert_eq!(mapped, SocketAddr::from(mapped).to_ipv6_mapped());
+
+        let v6 = SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888, 3485);
+        assert_eq!(Into::<net::SocketAddr>::into(v6), v6.to_ipv6_mapped());
+    }
+
+    #[test]
+    fn parse_ipv6_address() {
+        let addr: SocketAddr = "[2001:4860:4860::8888]:3485".parse().unwrap();
+        assert_eq!(SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888, 3485), addr);
+    }
+
     #[test]
     fn is_reachable() {
         // Servers which have loopback addresses can connect to each other
@@ -308,4 +448,9 @@ mod tests {
     fn encode_and_decode_nonce() {
         rlp_encode_and_decode_test!(SocketAddr::v4(1, 2, 3, 4, 5678));
     }
+
+    #[test]
+    fn encode_and_decode_ipv6() {
+        rlp_encode_and_decode_test!(SocketAddr::v6(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888, 5678));
+    }
 }
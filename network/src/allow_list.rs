@@ -0,0 +1,197 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use ccrypto::blake256;
+use ckey::{public_to_address, recover, Address, Signature};
+use parking_lot::RwLock;
+use primitives::H256;
+use rlp::RlpStream;
+
+use super::NodeId;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    InvalidSignature,
+    NotSignedByAuthority,
+    /// The version of the list must be greater than the version of the list in use.
+    OutdatedVersion {
+        current: u64,
+        given: u64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidSignature => write!(f, "The signature of the allow-list is invalid"),
+            Error::NotSignedByAuthority => write!(f, "The allow-list is not signed by the authority"),
+            Error::OutdatedVersion {
+                current,
+                given,
+            } => write!(f, "The version of the allow-list must be greater than {}, but {} is given", current, given),
+        }
+    }
+}
+
+/// The message that the authority signs to distribute the allow-list.
+/// The version is signed together, so that an older list can't be given again.
+pub fn node_allow_list_hash(version: u64, nodes: &[NodeId]) -> H256 {
+    let mut s = RlpStream::new_list(2);
+    s.append(&version);
+    s.begin_list(nodes.len());
+    for node in nodes {
+        s.append(node);
+    }
+    blake256(s.out())
+}
+
+#[derive(Default)]
+struct AllowedNodes {
+    /// None until the first list is given.
+    version: Option<u64>,
+    nodes: HashSet<NodeId>,
+}
+
+/// Nodes which may connect in a fully permissioned network.
+///
+/// The list can be replaced only by a newer list signed by the authority.
+pub struct NodeAllowList {
+    authority: Address,
+    allowed: RwLock<AllowedNodes>,
+}
+
+impl NodeAllowList {
+    pub fn new(authority: Address) -> Self {
+        Self {
+            authority,
+            allowed: Default::default(),
+        }
+    }
+
+    pub fn authority(&self) -> &Address {
+        &self.authority
+    }
+
+    pub fn update(&self, version: u64, nodes: Vec<NodeId>, signature: &Signature) -> Result<(), Error> {
+        let message = node_allow_list_hash(version, &nodes);
+        let signer = recover(signature, &message).map_err(|_| Error::InvalidSignature)?;
+        if public_to_address(&signer) != self.authority {
+            return Err(Error::NotSignedByAuthority)
+        }
+        let mut allowed = self.allowed.write();
+        if let Some(current) = allowed.version {
+            if version <= current {
+                return Err(Error::OutdatedVersion {
+                    current,
+                    given: version,
+                })
+            }
+        }
+        cinfo!(NETWORK, "The node allow-list is updated to the version {}: {:?}", version, nodes);
+        *allowed = AllowedNodes {
+            version: Some(version),
+            nodes: nodes.into_iter().collect(),
+        };
+        Ok(())
+    }
+
+    pub fn is_allowed(&self, node: &NodeId) -> bool {
+        self.allowed.read().nodes.contains(node)
+    }
+
+    pub fn version(&self) -> Option<u64> {
+        self.allowed.read().version
+    }
+
+    pub fn nodes(&self) -> Vec<NodeId> {
+        let mut nodes: Vec<_> = self.allowed.read().nodes.iter().cloned().collect();
+        nodes.sort();
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{sign, Generator, Random};
+
+    use super::super::SocketAddr;
+    use super::*;
+
+    fn node(port: u16) -> NodeId {
+        SocketAddr::v4(127, 0, 0, 1, port).into()
+    }
+
+    #[test]
+    fn nothing_is_allowed_before_the_first_update() {
+        let authority = Random.generate().unwrap();
+        let allow_list = NodeAllowList::new(authority.address());
+        assert!(!allow_list.is_allowed(&node(3485)));
+    }
+
+    #[test]
+    fn update_with_the_authority_signature() {
+        let authority = Random.generate().unwrap();
+        let allow_list = NodeAllowList::new(authority.address());
+
+        let nodes = vec![node(3485), node(3486)];
+        let signature = sign(authority.private(), &node_allow_list_hash(1, &nodes)).unwrap();
+        assert_eq!(Ok(()), allow_list.update(1, nodes.clone(), &signature));
+        assert!(allow_list.is_allowed(&node(3485)));
+        assert!(!allow_list.is_allowed(&node(3487)));
+        assert_eq!(nodes, allow_list.nodes());
+        assert_eq!(Some(1), allow_list.version());
+    }
+
+    #[test]
+    fn reject_the_older_list() {
+        let authority = Random.generate().unwrap();
+        let allow_list = NodeAllowList::new(authority.address());
+
+        let old_nodes = vec![node(3485), node(3486)];
+        let old_signature = sign(authority.private(), &node_allow_list_hash(1, &old_nodes)).unwrap();
+        let new_nodes = vec![node(3485)];
+        let new_signature = sign(authority.private(), &node_allow_list_hash(2, &new_nodes)).unwrap();
+        assert_eq!(Ok(()), allow_list.update(1, old_nodes.clone(), &old_signature));
+        assert_eq!(Ok(()), allow_list.update(2, new_nodes.clone(), &new_signature));
+
+        let replayed = allow_list.update(1, old_nodes.clone(), &old_signature);
+        assert_eq!(
+            Err(Error::OutdatedVersion {
+                current: 2,
+                given: 1
+            }),
+            replayed
+        );
+        assert!(!allow_list.is_allowed(&node(3486)));
+        // The version can't be changed without the signature.
+        assert_eq!(Err(Error::NotSignedByAuthority), allow_list.update(3, old_nodes, &old_signature));
+    }
+
+    #[test]
+    fn reject_the_list_signed_by_others() {
+        let authority = Random.generate().unwrap();
+        let other = Random.generate().unwrap();
+        let allow_list = NodeAllowList::new(authority.address());
+
+        let nodes = vec![node(3485)];
+        let signature = sign(other.private(), &node_allow_list_hash(1, &nodes)).unwrap();
+        assert_eq!(Err(Error::NotSignedByAuthority), allow_list.update(1, nodes, &signature));
+        assert!(!allow_list.is_allowed(&node(3485)));
+    }
+}
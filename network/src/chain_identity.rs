@@ -0,0 +1,172 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::sync::Arc;
+
+use ccrypto::blake256;
+use ckey::NetworkId;
+use ctypes::BlockNumber;
+use primitives::H256;
+use rlp::RlpStream;
+
+/// Tells how far the node has followed its chain.
+pub trait ChainHead: Send + Sync {
+    fn best_block_number(&self) -> BlockNumber;
+}
+
+/// Identifies the forks which a node has passed. Nodes exchange it in the handshake.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, RlpEncodable, RlpDecodable)]
+pub struct ForkId {
+    pub network_id: NetworkId,
+    /// The hash of the genesis hash and the fork blocks which the node has passed.
+    pub hash: H256,
+    /// The first fork block which the node hasn't passed, or 0 if there is none.
+    pub next: BlockNumber,
+}
+
+/// The chain which a node follows: the network, the genesis block and the blocks where the consensus rules change.
+#[derive(Clone)]
+pub struct ChainIdentity {
+    network_id: NetworkId,
+    genesis_hash: H256,
+    /// In ascending order.
+    forks: Vec<BlockNumber>,
+    head: Arc<ChainHead>,
+}
+
+impl ChainIdentity {
+    pub fn new(network_id: NetworkId, genesis_hash: H256, mut forks: Vec<BlockNumber>, head: Arc<ChainHead>) -> Self {
+        forks.retain(|fork| *fork != 0);
+        forks.sort();
+        forks.dedup();
+        Self {
+            network_id,
+            genesis_hash,
+            forks,
+            head,
+        }
+    }
+
+    /// The fork id at the best block. The forks scheduled after it don't change the id.
+    pub fn fork_id(&self) -> ForkId {
+        self.fork_id_at(self.head.best_block_number())
+    }
+
+    /// Whether the remote node follows the same chain.
+    /// A node which hasn't passed a fork yet follows the same chain as a node which has, if both know the fork.
+    pub fn is_compatible(&self, remote: &ForkId) -> bool {
+        self.is_compatible_at(self.head.best_block_number(), remote)
+    }
+
+    fn fork_id_at(&self, number: BlockNumber) -> ForkId {
+        let passed = self.passed_forks(number);
+        ForkId {
+            network_id: self.network_id,
+            hash: self.fork_hash(passed),
+            next: self.forks.get(passed).cloned().unwrap_or(0),
+        }
+    }
+
+    fn is_compatible_at(&self, number: BlockNumber, remote: &ForkId) -> bool {
+        if remote.network_id != self.network_id {
+            return false
+        }
+        let passed = self.passed_forks(number);
+        if remote.hash == self.fork_hash(passed) {
+            // The remote node expects a fork which this node has passed without changing the rules.
+            return remote.next == 0 || remote.next > number
+        }
+        // The remote node is behind. The next fork it expects must be the first one it hasn't passed.
+        if let Some(behind) = (0..passed).find(|count| self.fork_hash(*count) == remote.hash) {
+            return remote.next == self.forks[behind]
+        }
+        // The remote node is ahead, and it has passed the forks which this node will pass.
+        (passed + 1..=self.forks.len()).any(|count| self.fork_hash(count) == remote.hash)
+    }
+
+    fn passed_forks(&self, number: BlockNumber) -> usize {
+        self.forks.iter().take_while(|fork| **fork <= number).count()
+    }
+
+    fn fork_hash(&self, passed: usize) -> H256 {
+        let mut s = RlpStream::new_list(passed + 1);
+        s.append(&self.genesis_hash);
+        for fork in &self.forks[..passed] {
+            s.append(fork);
+        }
+        blake256(s.as_raw())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Head(BlockNumber);
+
+    impl ChainHead for Head {
+        fn best_block_number(&self) -> BlockNumber {
+            self.0
+        }
+    }
+
+    fn chain(forks: Vec<BlockNumber>) -> ChainIdentity {
+        ChainIdentity::new("tc".into(), H256::zero(), forks, Arc::new(Head(0)))
+    }
+
+    #[test]
+    fn scheduled_forks_do_not_change_the_fork_id() {
+        let scheduled = chain(vec![10, 20]).fork_id_at(5);
+        let none = chain(vec![]).fork_id_at(5);
+        assert_eq!(none.hash, scheduled.hash);
+        assert_eq!(10, scheduled.next);
+        assert_eq!(0, none.next);
+    }
+
+    #[test]
+    fn nodes_at_different_heights_are_compatible() {
+        let chain = chain(vec![10, 20]);
+        assert!(chain.is_compatible_at(5, &chain.fork_id_at(5)));
+        assert!(chain.is_compatible_at(15, &chain.fork_id_at(5)));
+        assert!(chain.is_compatible_at(5, &chain.fork_id_at(15)));
+        assert!(chain.is_compatible_at(25, &chain.fork_id_at(5)));
+        assert!(chain.is_compatible_at(5, &chain.fork_id_at(25)));
+    }
+
+    #[test]
+    fn node_without_the_next_fork_is_compatible_until_the_fork() {
+        let upgraded = chain(vec![10]);
+        let outdated = chain(vec![]).fork_id_at(5);
+        assert!(upgraded.is_compatible_at(5, &outdated));
+        assert!(!upgraded.is_compatible_at(10, &chain(vec![]).fork_id_at(10)));
+        assert!(!chain(vec![]).is_compatible_at(10, &upgraded.fork_id_at(5)));
+    }
+
+    #[test]
+    fn node_behind_must_expect_the_passed_fork() {
+        let local = chain(vec![10, 20]);
+        let remote = chain(vec![15, 20]).fork_id_at(5);
+        assert!(!local.is_compatible_at(12, &remote));
+    }
+
+    #[test]
+    fn other_network_is_incompatible() {
+        let local = chain(vec![]);
+        let remote = ChainIdentity::new("tn".into(), H256::zero(), vec![], Arc::new(Head(0)));
+        assert!(!local.is_compatible_at(0, &remote.fork_id_at(0)));
+    }
+}
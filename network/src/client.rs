@@ -14,62 +14,134 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 
 use cio::IoChannel;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use rlp::Encodable;
 use time::Duration;
 
+use super::extension_stats::{ExtensionStats, ExtensionStatsTable};
+use super::outbound_queues::{OutboundQueues, Release, Reservation};
 use super::p2p::Message as P2pMessage;
-use super::timer::Message as TimerMessage;
-use super::{Api, IntoSocketAddr, NetworkExtension, NetworkExtensionError, NetworkExtensionResult, NodeId, TimerToken};
+use super::pending_messages::PendingMessages;
+use super::timer::{Message as TimerMessage, TimerInfo, FIRST_TIMER_TOKEN, MAX_TIMERS};
+use super::{
+    Api, IntoSocketAddr, NetworkExtension, NetworkExtensionError, NetworkExtensionResult, NodeId, TimerToken,
+    DEFAULT_MAX_MESSAGE_SIZE,
+};
+
+/// The versions of the extensions negotiated with each node.
+type NegotiatedVersions = RwLock<HashMap<NodeId, HashMap<&'static str, u64>>>;
 
 struct ClientApi {
     extension: Weak<NetworkExtension>,
+    negotiated_versions: Arc<NegotiatedVersions>,
+    outbound_queues: Arc<OutboundQueues<P2pMessage>>,
+    pending_messages: Option<Arc<PendingMessages>>,
+    stats: Arc<ExtensionStatsTable>,
     p2p_channel: IoChannel<P2pMessage>,
+    timer_info: Arc<Mutex<TimerInfo>>,
     timer_channel: IoChannel<TimerMessage>,
 }
 
+/// Hands the message to the p2p handler, which writes it when the connection to the node is writable.
+/// If too many messages to the node are not written yet, the message waits until they are.
+fn enqueue_message(
+    extension: &NetworkExtension,
+    id: &NodeId,
+    version: u64,
+    data: Vec<u8>,
+    outbound_queues: &OutboundQueues<P2pMessage>,
+    stats: &ExtensionStatsTable,
+    p2p_channel: &IoChannel<P2pMessage>,
+) -> NetworkExtensionResult<()> {
+    let bytes = data.len();
+    let message = P2pMessage::SendExtensionMessage {
+        node_id: *id,
+        extension_name: extension.name().to_string(),
+        version,
+        need_encryption: extension.need_encryption(),
+        data,
+    };
+    let message = match outbound_queues.try_reserve(id, extension.name(), message) {
+        Reservation::Send(message) => message,
+        Reservation::Deferred => {
+            cdebug!(NETAPI, "`{}` defers a message to {} until the queue has room", extension.name(), id.into_addr());
+            stats.on_sent(extension.name(), bytes);
+            return Ok(())
+        }
+        Reservation::Full => {
+            cdebug!(NETAPI, "`{}` cannot queue more messages to {}", extension.name(), id.into_addr());
+            stats.on_error(extension.name());
+            return Err(NetworkExtensionError::QueueFull)
+        }
+    };
+    if let Err(err) = p2p_channel.send(message) {
+        outbound_queues.cancel(id, extension.name());
+        stats.on_error(extension.name());
+        cerror!(NETAPI, "`{}` cannot send {} bytes message to {} : {:?}", extension.name(), bytes, id.into_addr(), err);
+        return Err(err.into())
+    }
+    cdebug!(NETAPI, "`{}` sends {} bytes to {}", extension.name(), bytes, id.into_addr());
+    stats.on_sent(extension.name(), bytes);
+    Ok(())
+}
+
+impl ClientApi {
+    fn count_error<T>(
+        &self,
+        extension_name: &'static str,
+        result: NetworkExtensionResult<T>,
+    ) -> NetworkExtensionResult<T> {
+        if result.is_err() {
+            self.stats.on_error(extension_name);
+        }
+        result
+    }
+}
+
 impl Api for ClientApi {
-    fn send(&self, id: &NodeId, message: &[u8]) {
+    fn send(&self, id: &NodeId, message: &[u8]) -> NetworkExtensionResult<()> {
         if let Some(extension) = self.extension.upgrade() {
-            let need_encryption = extension.need_encryption();
-            let extension_name = extension.name().to_string();
-            let node_id = *id;
-            let data = message.to_vec();
-            let bytes = data.len();
-            if let Err(err) = self.p2p_channel.send(P2pMessage::SendExtensionMessage {
-                node_id,
-                extension_name,
-                need_encryption,
-                data,
-            }) {
-                cerror!(
-                    NETAPI,
-                    "`{}` cannot send {} bytes message to {} : {:?}",
-                    extension.name(),
-                    bytes,
-                    id.into_addr(),
-                    err
-                );
-            } else {
-                cdebug!(NETAPI, "`{}` sends {} bytes to {}", extension.name(), bytes, id.into_addr());
-            }
+            let version = match self.negotiated_version(id) {
+                Some(version) => version,
+                None => {
+                    if let (Some(pending_messages), Some(ttl)) =
+                        (self.pending_messages.as_ref(), extension.pending_message_ttl())
+                    {
+                        cdebug!(NETAPI, "`{}` keeps a message until {} connects", extension.name(), id.into_addr());
+                        pending_messages.push(id, extension.name(), message.to_vec(), ttl);
+                        return Ok(())
+                    }
+                    Default::default()
+                }
+            };
+            enqueue_message(
+                &*extension,
+                id,
+                version,
+                message.to_vec(),
+                &self.outbound_queues,
+                &self.stats,
+                &self.p2p_channel,
+            )
         } else {
-            cwarn!(NETAPI, "The extension already dropped");
+            Err(NetworkExtensionError::ExtensionDropped)
         }
     }
 
     fn set_timer(&self, timer_id: usize, duration: Duration) -> NetworkExtensionResult<()> {
         if let Some(extension) = self.extension.upgrade() {
-            let extension_name = extension.name().to_string();
-            Ok(self.timer_channel.send_sync(TimerMessage::SetTimer {
-                extension_name,
-                timer_id,
-                duration,
-            })?)
+            let result = self.timer_info.lock().insert(extension.name().to_string(), timer_id, false).and_then(|token| {
+                Ok(self.timer_channel.send_sync(TimerMessage::SetTimer {
+                    token,
+                    duration,
+                })?)
+            });
+            self.count_error(extension.name(), result)
         } else {
             Err(NetworkExtensionError::ExtensionDropped)
         }
@@ -77,12 +149,27 @@ impl Api for ClientApi {
 
     fn set_timer_once(&self, timer_id: usize, duration: Duration) -> NetworkExtensionResult<()> {
         if let Some(extension) = self.extension.upgrade() {
-            let extension_name = extension.name().to_string();
-            Ok(self.timer_channel.send_sync(TimerMessage::SetTimerOnce {
-                extension_name,
-                timer_id,
-                duration,
-            })?)
+            let result = self.timer_info.lock().insert(extension.name().to_string(), timer_id, true).and_then(|token| {
+                Ok(self.timer_channel.send_sync(TimerMessage::SetTimerOnce {
+                    token,
+                    duration,
+                })?)
+            });
+            self.count_error(extension.name(), result)
+        } else {
+            Err(NetworkExtensionError::ExtensionDropped)
+        }
+    }
+
+    fn set_timer_at(&self, timer_id: usize, deadline: Instant) -> NetworkExtensionResult<()> {
+        if let Some(extension) = self.extension.upgrade() {
+            let result = self.timer_info.lock().insert(extension.name().to_string(), timer_id, true).and_then(|token| {
+                Ok(self.timer_channel.send_sync(TimerMessage::SetTimerAt {
+                    token,
+                    deadline,
+                })?)
+            });
+            self.count_error(extension.name(), result)
         } else {
             Err(NetworkExtensionError::ExtensionDropped)
         }
@@ -90,11 +177,12 @@ impl Api for ClientApi {
 
     fn clear_timer(&self, timer_id: usize) -> NetworkExtensionResult<()> {
         if let Some(extension) = self.extension.upgrade() {
-            let extension_name = extension.name().to_string();
-            Ok(self.timer_channel.send_sync(TimerMessage::ClearTimer {
-                extension_name,
-                timer_id,
-            })?)
+            let result = self.timer_info.lock().remove_by_info(extension.name(), timer_id).and_then(|token| {
+                Ok(self.timer_channel.send_sync(TimerMessage::ClearTimer {
+                    token,
+                })?)
+            });
+            self.count_error(extension.name(), result)
         } else {
             Err(NetworkExtensionError::ExtensionDropped)
         }
@@ -108,44 +196,55 @@ impl Api for ClientApi {
                 extension_name,
                 message,
             }) {
+                self.stats.on_error(extension.name());
                 cwarn!(NETAPI, "Cannot send local message: {:?}", err);
             }
         } else {
             cdebug!(NETAPI, "The extension already dropped");
         }
     }
+
+    fn negotiated_version(&self, id: &NodeId) -> Option<u64> {
+        let extension = self.extension.upgrade()?;
+        self.negotiated_versions.read().get(id).and_then(|versions| versions.get(extension.name()).cloned())
+    }
+
+    fn disconnect(&self, id: &NodeId) {
+        if let Some(extension) = self.extension.upgrade() {
+            cinfo!(NETAPI, "`{}` disconnects {}", extension.name(), id.into_addr());
+            if let Err(err) = self.p2p_channel.send(P2pMessage::Disconnect(id.into_addr())) {
+                self.stats.on_error(extension.name());
+                cwarn!(NETAPI, "Cannot disconnect {}: {:?}", id.into_addr(), err);
+            }
+        } else {
+            cdebug!(NETAPI, "The extension already dropped");
+        }
+    }
 }
 
 pub struct Client {
     extensions: RwLock<HashMap<&'static str, Arc<NetworkExtension>>>,
+    /// Nodes added to the extensions which are not initialized yet.
+    /// They are announced to each extension after it is initialized.
+    uninitialized: Mutex<HashMap<&'static str, Vec<(NodeId, u64)>>>,
+    negotiated_versions: Arc<NegotiatedVersions>,
+    outbound_queues: Arc<OutboundQueues<P2pMessage>>,
+    pending_messages: Option<Arc<PendingMessages>>,
+    stats: Arc<ExtensionStatsTable>,
     p2p_channel: IoChannel<P2pMessage>,
+    timer_info: Arc<Mutex<TimerInfo>>,
     timer_channel: IoChannel<TimerMessage>,
 }
 
-macro_rules! define_broadcast_method {
-    ($method_name: ident) => {
-        pub fn $method_name (&self) {
-            let extensions = self.extensions.read();
-            for (_, ref extension) in extensions.iter() {
-                extension.$method_name();
-            }
-        }
-    };
-    ($method_name: ident; $($var: ident, $t: ty);*) => {
-        pub fn $method_name (&self, $($var: $t), *) {
-            let extensions = self.extensions.read();
-            for (_, ref extension) in extensions.iter() {
-                extension.$method_name($($var),*);
-            }
-        }
-    };
-}
-
 macro_rules! define_method {
     ($method_name: ident; $($var: ident, $t: ty);*) => {
         pub fn $method_name (&self, name: &String, $($var: $t), *) {
             let extensions = self.extensions.read();
             if let Some(ref extension) = extensions.get(name.as_str()) {
+                if self.uninitialized.lock().contains_key(name.as_str()) {
+                    cdebug!(NETAPI, "{} is not initialized yet.", name);
+                    return
+                }
                 extension.$method_name($($var),*);
             } else {
                 cdebug!(NETAPI, "{} doesn't exist.", name);
@@ -162,52 +261,231 @@ impl Client {
             let name = extension.name();
             panic!("Duplicated extension name : {}", name);
         }
+        self.uninitialized.lock().insert(name, Vec::new());
+        self.stats.register(name);
+    }
+
+    /// Initializes the registered extensions, each after its dependencies.
+    pub fn initialize_extensions(&self) {
+        let names: Vec<_> = self.extensions.read().keys().cloned().collect();
+        for name in names {
+            self.initialize_extension(name);
+        }
     }
 
+    /// Initializes the extension after its dependencies.
+    /// The dependencies which are not registered are ignored.
     pub fn initialize_extension(&self, extension_name: &str) {
-        let extension = {
-            let mut extensions = self.extensions.read();
-            extensions.get(extension_name).map(Arc::clone)
+        self.initialize_with_dependencies(extension_name, &mut HashSet::new());
+    }
+
+    fn initialize_with_dependencies(&self, extension_name: &str, visiting: &mut HashSet<&'static str>) {
+        let extension = match self.extensions.read().get(extension_name) {
+            Some(extension) => Arc::clone(extension),
+            None => return,
         };
-        if let Some(extension) = extension {
-            let p2p_channel = self.p2p_channel.clone();
-            let timer_channel = self.timer_channel.clone();
-            let api: Arc<Api> = Arc::new(ClientApi {
-                extension: Arc::downgrade(&extension),
-                p2p_channel,
-                timer_channel,
-            });
-            extension.on_initialize(api);
+        let name = extension.name();
+        if !self.uninitialized.lock().contains_key(name) {
+            return
+        }
+        if !visiting.insert(name) {
+            panic!("Circular dependency of extension : {}", name);
+        }
+        for dependency in extension.dependencies() {
+            if self.extensions.read().contains_key(dependency) {
+                self.initialize_with_dependencies(dependency, visiting);
+            } else {
+                cdebug!(NETAPI, "`{}` depends on `{}`, which is not registered", name, dependency);
+            }
+        }
+        visiting.remove(name);
+
+        let p2p_channel = self.p2p_channel.clone();
+        let timer_channel = self.timer_channel.clone();
+        let api: Arc<Api> = Arc::new(ClientApi {
+            extension: Arc::downgrade(&extension),
+            negotiated_versions: Arc::clone(&self.negotiated_versions),
+            outbound_queues: Arc::clone(&self.outbound_queues),
+            pending_messages: self.pending_messages.clone(),
+            stats: Arc::clone(&self.stats),
+            p2p_channel,
+            timer_info: Arc::clone(&self.timer_info),
+            timer_channel,
+        });
+        extension.on_initialize(api);
+
+        let mut uninitialized = self.uninitialized.lock();
+        let nodes = uninitialized.remove(name).expect("Checked above");
+        for (id, version) in nodes {
+            self.send_pending_messages(&*extension, &id, version);
+            extension.on_node_added(&id, version);
         }
     }
 
-    pub fn new(p2p_channel: IoChannel<P2pMessage>, timer_channel: IoChannel<TimerMessage>) -> Arc<Self> {
+    pub fn new(
+        p2p_channel: IoChannel<P2pMessage>,
+        timer_channel: IoChannel<TimerMessage>,
+        pending_messages: Option<Arc<PendingMessages>>,
+    ) -> Arc<Self> {
         Arc::new(Self {
             extensions: RwLock::new(HashMap::new()),
+            uninitialized: Mutex::new(HashMap::new()),
+            negotiated_versions: Default::default(),
+            outbound_queues: Default::default(),
+            pending_messages,
+            stats: Default::default(),
             p2p_channel,
+            timer_info: Arc::new(Mutex::new(TimerInfo::new(FIRST_TIMER_TOKEN, MAX_TIMERS))),
             timer_channel,
         })
     }
 
+    pub fn timer_info(&self) -> Arc<Mutex<TimerInfo>> {
+        Arc::clone(&self.timer_info)
+    }
+
+    /// Frees the queue slot of a message which is sent or dropped by the p2p handler.
+    /// The message which waits for the slot is handed to the p2p handler.
+    pub fn on_message_dequeued(&self, name: &str, id: &NodeId) {
+        let extension = match self.extensions.read().get(name) {
+            Some(extension) => Arc::clone(extension),
+            None => return,
+        };
+        match self.outbound_queues.release(id, extension.name()) {
+            Release::Next(message) => {
+                if let Err(err) = self.p2p_channel.send(message) {
+                    self.outbound_queues.cancel(id, extension.name());
+                    self.stats.on_error(extension.name());
+                    cerror!(NETAPI, "`{}` cannot send a deferred message to {} : {:?}", name, id.into_addr(), err);
+                }
+            }
+            Release::Ready => {
+                ctrace!(NETAPI, "`{}` can send messages to {} again", name, id.into_addr());
+                extension.on_send_ready(id);
+            }
+            Release::Nothing => {}
+        }
+    }
+
+    /// Keeps the message which the p2p handler couldn't send because the node was disconnected.
+    pub fn on_message_undelivered(&self, name: &str, id: &NodeId, data: &[u8]) {
+        let pending_messages = match self.pending_messages.as_ref() {
+            Some(pending_messages) => pending_messages,
+            None => return,
+        };
+        let extensions = self.extensions.read();
+        if let Some(extension) = extensions.get(name) {
+            if let Some(ttl) = extension.pending_message_ttl() {
+                cdebug!(NETAPI, "`{}` keeps {} bytes until {} connects again", name, data.len(), id.into_addr());
+                pending_messages.push(id, extension.name(), data.to_vec(), ttl);
+            }
+        }
+    }
+
+    /// Sends the messages kept while the node was disconnected, before the extension is told the node is added.
+    fn send_pending_messages(&self, extension: &NetworkExtension, id: &NodeId, version: u64) {
+        let pending_messages = match self.pending_messages.as_ref() {
+            Some(pending_messages) => pending_messages,
+            None => return,
+        };
+        for data in pending_messages.take(id, extension.name()) {
+            if let Err(err) =
+                enqueue_message(extension, id, version, data, &self.outbound_queues, &self.stats, &self.p2p_channel)
+            {
+                cwarn!(NETAPI, "`{}` drops a pending message to {}: {:?}", extension.name(), id.into_addr(), err);
+            }
+        }
+    }
+
+    /// Returns the counters of the registered extensions by their names.
+    pub fn extension_stats(&self) -> HashMap<String, ExtensionStats> {
+        self.stats.snapshot()
+    }
+
     pub fn extension_versions(&self) -> Vec<(String, Vec<u64>)> {
         let extensions = self.extensions.read();
         extensions.iter().map(|(name, extension)| (name.to_string(), extension.versions().to_vec())).collect()
     }
 
-    define_method!(on_node_added; id, &NodeId; version, u64);
-    define_broadcast_method!(on_node_removed; id, &NodeId);
+    /// The largest message among the registered extensions.
+    /// The connections refuse the larger messages before decoding them.
+    pub fn max_message_size(&self) -> usize {
+        let extensions = self.extensions.read();
+        extensions.values().map(|extension| extension.max_message_size()).max().unwrap_or(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    pub fn max_message_size_of(&self, name: &str) -> Option<usize> {
+        let extensions = self.extensions.read();
+        extensions.get(name).map(|extension| extension.max_message_size())
+    }
+
+    /// Chooses the version of the extension to talk with the node.
+    /// It returns `None` if the extension doesn't exist or refuses the node.
+    pub fn select_version(&self, name: &str, id: &NodeId, versions: &[u64]) -> Option<u64> {
+        let extensions = self.extensions.read();
+        extensions.get(name).and_then(|extension| extension.select_version(id, versions))
+    }
+
+    pub fn on_node_added(&self, name: &String, id: &NodeId, version: u64) {
+        let extensions = self.extensions.read();
+        if let Some(ref extension) = extensions.get(name.as_str()) {
+            self.negotiated_versions.write().entry(*id).or_insert_with(HashMap::new).insert(extension.name(), version);
+            if let Some(nodes) = self.uninitialized.lock().get_mut(name.as_str()) {
+                cdebug!(NETAPI, "{} is announced to `{}` after it is initialized", id.into_addr(), name);
+                nodes.push((*id, version));
+                return
+            }
+            self.send_pending_messages(&***extension, id, version);
+            extension.on_node_added(id, version);
+        } else {
+            cdebug!(NETAPI, "{} doesn't exist.", name);
+        }
+    }
+
+    pub fn on_node_removed(&self, id: &NodeId) {
+        self.outbound_queues.remove_node(id);
+        self.negotiated_versions.write().remove(id);
+        let extensions = self.extensions.read();
+        let initialized: Vec<_> = {
+            let mut uninitialized = self.uninitialized.lock();
+            for nodes in uninitialized.values_mut() {
+                nodes.retain(|(node, _)| node != id);
+            }
+            extensions.iter().filter(|(name, _)| !uninitialized.contains_key(*name)).map(|(_, e)| e).collect()
+        };
+        for extension in initialized {
+            extension.on_node_removed(id);
+        }
+    }
 
     pub fn on_message(&self, name: &String, id: &NodeId, data: &[u8]) {
         let extensions = self.extensions.read();
         if let Some(ref extension) = extensions.get(name.as_str()) {
+            if self.uninitialized.lock().contains_key(name.as_str()) {
+                cdebug!(NETAPI, "`{}` is not initialized. Drops {} bytes from {}", name, data.len(), id.into_addr());
+                return
+            }
             cdebug!(NETAPI, "`{}` receives {} bytes from {}", name, data.len(), id.into_addr());
+            self.stats.on_received(extension.name(), data.len());
             extension.on_message(id, data);
         } else {
             cwarn!(NETAPI, "{} doesn't exist.", name);
         }
     }
 
-    define_method!(on_timeout; timer_id, TimerToken);
+    pub fn on_timeout(&self, name: &String, timer_id: TimerToken) {
+        let extensions = self.extensions.read();
+        if let Some(ref extension) = extensions.get(name.as_str()) {
+            if self.uninitialized.lock().contains_key(name.as_str()) {
+                cdebug!(NETAPI, "{} is not initialized yet.", name);
+                return
+            }
+            self.stats.on_timer_fired(extension.name());
+            extension.on_timeout(timer_id);
+        } else {
+            cdebug!(NETAPI, "{} doesn't exist.", name);
+        }
+    }
 
     define_method!(on_local_message; message, &[u8]);
 }
@@ -224,13 +502,13 @@ mod tests {
     use time::Duration;
 
     use super::super::SocketAddr;
-    use super::{Api, Client, NetworkExtension, NetworkExtensionResult, NodeId};
+    use super::{Api, Client, NetworkExtension, NetworkExtensionResult, NodeId, DEFAULT_MAX_MESSAGE_SIZE};
 
     #[allow(dead_code)]
     struct TestApi;
 
     impl Api for TestApi {
-        fn send(&self, _id: &NodeId, _message: &[u8]) {
+        fn send(&self, _id: &NodeId, _message: &[u8]) -> NetworkExtensionResult<()> {
             unimplemented!()
         }
 
@@ -242,6 +520,10 @@ mod tests {
             unimplemented!()
         }
 
+        fn set_timer_at(&self, _timer_id: usize, _deadline: Instant) -> NetworkExtensionResult<()> {
+            unimplemented!()
+        }
+
         fn clear_timer(&self, _timer_id: usize) -> NetworkExtensionResult<()> {
             unimplemented!()
         }
@@ -249,6 +531,14 @@ mod tests {
         fn send_local_message(&self, _message: &Encodable) {
             unimplemented!()
         }
+
+        fn negotiated_version(&self, _id: &NodeId) -> Option<u64> {
+            unimplemented!()
+        }
+
+        fn disconnect(&self, _id: &NodeId) {
+            unimplemented!()
+        }
     }
 
     #[derive(Debug, Eq, PartialEq)]
@@ -262,14 +552,28 @@ mod tests {
 
     struct TestExtension {
         name: &'static str,
+        dependencies: &'static [&'static str],
+        max_message_size: usize,
         callbacks: Mutex<Vec<Callback>>,
+        initialized: Arc<Mutex<Vec<&'static str>>>,
     }
 
     impl TestExtension {
         fn new(name: &'static str) -> Self {
+            Self::with_dependencies(name, &[], Default::default())
+        }
+
+        fn with_dependencies(
+            name: &'static str,
+            dependencies: &'static [&'static str],
+            initialized: Arc<Mutex<Vec<&'static str>>>,
+        ) -> Self {
             Self {
                 name,
+                dependencies,
+                max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
                 callbacks: Mutex::new(vec![]),
+                initialized,
             }
         }
     }
@@ -288,9 +592,18 @@ mod tests {
             &VERSIONS
         }
 
+        fn dependencies(&self) -> &[&'static str] {
+            self.dependencies
+        }
+
+        fn max_message_size(&self) -> usize {
+            self.max_message_size
+        }
+
         fn on_initialize(&self, _api: Arc<Api>) {
             let mut callbacks = self.callbacks.lock();
             callbacks.push(Callback::Initialize);
+            self.initialized.lock().push(self.name);
         }
 
         fn on_node_added(&self, _id: &NodeId, _version: u64) {
@@ -319,7 +632,7 @@ mod tests {
         let p2p_service = IoService::start().unwrap();
         let timer_service = IoService::start().unwrap();
 
-        let client = Client::new(p2p_service.channel(), timer_service.channel());
+        let client = Client::new(p2p_service.channel(), timer_service.channel(), None);
 
         let node_id1 = SocketAddr::v4(127, 0, 0, 1, 8081).into();
         let node_id5 = SocketAddr::v4(127, 0, 0, 1, 8085).into();
@@ -359,4 +672,81 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn dependencies_are_initialized_first() {
+        let p2p_service = IoService::start().unwrap();
+        let timer_service = IoService::start().unwrap();
+
+        let client = Client::new(p2p_service.channel(), timer_service.channel(), None);
+        let initialized = Arc::new(Mutex::new(vec![]));
+
+        let e3 = TestExtension::with_dependencies("e3", &["e2", "e4"], Arc::clone(&initialized));
+        client.register_extension(Arc::new(e3));
+        let e2 = TestExtension::with_dependencies("e2", &["e1"], Arc::clone(&initialized));
+        client.register_extension(Arc::new(e2));
+        let e1 = TestExtension::with_dependencies("e1", &[], Arc::clone(&initialized));
+        client.register_extension(Arc::new(e1));
+
+        client.initialize_extensions();
+        assert_eq!(initialized.lock().deref(), &vec!["e1", "e2", "e3"]);
+    }
+
+    #[test]
+    fn nodes_are_announced_after_initialization() {
+        let p2p_service = IoService::start().unwrap();
+        let timer_service = IoService::start().unwrap();
+
+        let client = Client::new(p2p_service.channel(), timer_service.channel(), None);
+
+        let node_id1 = SocketAddr::v4(127, 0, 0, 1, 8081).into();
+        let node_id5 = SocketAddr::v4(127, 0, 0, 1, 8085).into();
+
+        let e1 = Arc::new(TestExtension::new("e1"));
+        client.register_extension(Arc::clone(&e1) as Arc<NetworkExtension>);
+        client.on_node_added(&"e1".to_string(), &node_id1, 0);
+        client.on_node_added(&"e1".to_string(), &node_id5, 0);
+        client.on_node_removed(&node_id5);
+        client.on_message(&"e1".to_string(), &node_id1, &vec![]);
+        assert!(e1.callbacks.lock().is_empty());
+
+        client.initialize_extensions();
+        assert_eq!(e1.callbacks.lock().deref(), &vec![Callback::Initialize, Callback::NodeAdded]);
+    }
+
+    #[test]
+    fn select_the_highest_common_version() {
+        let p2p_service = IoService::start().unwrap();
+        let timer_service = IoService::start().unwrap();
+
+        let client = Client::new(p2p_service.channel(), timer_service.channel(), None);
+
+        let node_id = SocketAddr::v4(127, 0, 0, 1, 8081).into();
+
+        client.register_extension(Arc::new(TestExtension::new("e1")));
+        assert_eq!(Some(0), client.select_version("e1", &node_id, &[0, 1]));
+        assert_eq!(None, client.select_version("e1", &node_id, &[1, 2]));
+        assert_eq!(None, client.select_version("e2", &node_id, &[0]));
+    }
+
+    #[test]
+    fn max_message_size_is_the_largest_among_extensions() {
+        let p2p_service = IoService::start().unwrap();
+        let timer_service = IoService::start().unwrap();
+
+        let client = Client::new(p2p_service.channel(), timer_service.channel(), None);
+        assert_eq!(DEFAULT_MAX_MESSAGE_SIZE, client.max_message_size());
+
+        let mut e1 = TestExtension::new("e1");
+        e1.max_message_size = 100;
+        client.register_extension(Arc::new(e1));
+        assert_eq!(100, client.max_message_size());
+
+        let mut e2 = TestExtension::new("e2");
+        e2.max_message_size = 3 * DEFAULT_MAX_MESSAGE_SIZE;
+        client.register_extension(Arc::new(e2));
+        assert_eq!(3 * DEFAULT_MAX_MESSAGE_SIZE, client.max_message_size());
+        assert_eq!(Some(100), client.max_message_size_of("e1"));
+        assert_eq!(None, client.max_message_size_of("e3"));
+    }
 }
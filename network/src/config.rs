@@ -15,14 +15,35 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use super::SocketAddr;
+use ckey::Private;
 use std::net::IpAddr;
+use std::path::PathBuf;
 
 pub struct Config {
     pub address: String,
     pub port: u16,
     pub bootstrap_addresses: Vec<SocketAddr>,
     pub min_peers: usize,
-    pub max_peers: usize,
-    pub whitelist: Vec<IpAddr>,
-    pub blacklist: Vec<IpAddr>,
+    pub max_inbound_peers: usize,
+    pub max_outbound_peers: usize,
+    pub trusted_nodes: Vec<IpAddr>,
+    /// The files of the whitelist and the blacklist. The changes made at runtime are saved to them.
+    pub whitelist_path: Option<PathBuf>,
+    pub blacklist_path: Option<PathBuf>,
+    /// The directory where the messages to the disconnected nodes are kept. They are dropped if it's `None`.
+    pub pending_messages_path: Option<PathBuf>,
+    /// The node key given on the command line. The key file is not used if it exists.
+    pub node_key: Option<Private>,
+    /// The file which keeps the node key. A new key is generated if it doesn't exist.
+    pub node_key_file: PathBuf,
+    /// The SOCKS5 proxy through which the outbound connections are dialed.
+    pub proxy: Option<SocketAddr>,
+    /// Refuses the inbound connections, and neither initiates nor answers the sessions over UDP.
+    pub hide_address: bool,
+    /// Whether the sockets bound to an IPv6 interface also accept IPv4.
+    pub dual_stack: bool,
+    /// Dials the addresses advertised by the inbound peers to verify that they accept connections.
+    pub probe_inbound: bool,
+    /// The number of the threads which run the handlers of the extensions.
+    pub io_workers: usize,
 }
@@ -14,12 +14,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::net::IpAddr;
+use std::collections::HashMap;
 use std::result::Result;
+use std::time::Duration;
 
+use ckey::{Public, Signature};
 use primitives::H256;
 
 use super::addr::SocketAddr;
+use super::allow_list::Error as NodeAllowListError;
+use super::extension_stats::ExtensionStats;
+use super::filters::FilterEntry;
 
 pub trait Control: Send + Sync {
     fn register_secret(&self, secret: H256, addr: SocketAddr) -> Result<(), Error>;
@@ -27,14 +32,17 @@ pub trait Control: Send + Sync {
     fn disconnect(&self, addr: SocketAddr) -> Result<(), Error>;
     fn is_connected(&self, addr: &SocketAddr) -> Result<bool, Error>;
     fn get_port(&self) -> Result<u16, Error>;
+    fn local_info(&self) -> Result<LocalInfo, Error>;
     fn get_peer_count(&self) -> Result<usize, Error>;
     fn established_peers(&self) -> Result<Vec<SocketAddr>, Error>;
+    /// The round-trip times of the established peers, measured by the keep-alive pings.
+    fn peer_rtts(&self) -> Result<HashMap<SocketAddr, Duration>, Error>;
 
-    fn add_to_whitelist(&self, addr: IpAddr) -> Result<(), Error>;
-    fn remove_from_whitelist(&self, addr: &IpAddr) -> Result<(), Error>;
+    fn add_to_whitelist(&self, entry: FilterEntry) -> Result<(), Error>;
+    fn remove_from_whitelist(&self, entry: &FilterEntry) -> Result<(), Error>;
 
-    fn add_to_blacklist(&self, addr: IpAddr) -> Result<(), Error>;
-    fn remove_from_blacklist(&self, addr: &IpAddr) -> Result<(), Error>;
+    fn add_to_blacklist(&self, entry: FilterEntry) -> Result<(), Error>;
+    fn remove_from_blacklist(&self, entry: &FilterEntry) -> Result<(), Error>;
 
     fn enable_whitelist(&self) -> Result<(), Error>;
     fn disable_whitelist(&self) -> Result<(), Error>;
@@ -42,12 +50,42 @@ pub trait Control: Send + Sync {
     fn enable_blacklist(&self) -> Result<(), Error>;
     fn disable_blacklist(&self) -> Result<(), Error>;
 
-    fn get_whitelist(&self) -> Result<(Vec<IpAddr>, bool), Error>;
-    fn get_blacklist(&self) -> Result<(Vec<IpAddr>, bool), Error>;
+    fn get_whitelist(&self) -> Result<(Vec<FilterEntry>, bool), Error>;
+    fn get_blacklist(&self) -> Result<(Vec<FilterEntry>, bool), Error>;
+
+    fn set_node_allow_list(&self, version: u64, nodes: Vec<SocketAddr>, signature: Signature) -> Result<(), Error>;
+    /// The version of the list in use, which is None until the first list is given, and the nodes.
+    fn get_node_allow_list(&self) -> Result<(Option<u64>, Vec<SocketAddr>), Error>;
+
+    fn extension_stats(&self) -> Result<HashMap<String, ExtensionStats>, Error>;
+
+    /// Starts a new discovery round immediately. Fails with `Disabled` if the node runs without discovery.
+    fn refresh_discovery(&self) -> Result<(), Error>;
+
+    /// Changes the peer limits at runtime. The peers beyond the new maximums are not disconnected.
+    fn set_peer_limits(
+        &self,
+        min_peers: usize,
+        max_inbound_peers: usize,
+        max_outbound_peers: usize,
+    ) -> Result<(), Error>;
+}
+
+/// How the other nodes see this node.
+#[derive(Clone, Debug)]
+pub struct LocalInfo {
+    /// The address on which the node listens for peers.
+    pub address: SocketAddr,
+    /// The public key of the node key.
+    pub node_key: Public,
 }
 
 #[derive(Clone, Debug)]
 pub enum Error {
     Disabled,
     NotConnected,
+    /// The network is not in the allow-list mode.
+    NoNodeAllowList,
+    InvalidNodeAllowList(NodeAllowListError),
+    InvalidPeerLimits(String),
 }
@@ -20,4 +20,6 @@ use super::RoutingTable;
 
 pub trait Api: Send + Sync {
     fn set_routing_table(&self, routing_table: Arc<RoutingTable>);
+    /// Starts a new discovery round without waiting for the refresh timer.
+    fn refresh(&self);
 }
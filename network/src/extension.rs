@@ -16,6 +16,7 @@
 
 use std::result;
 use std::sync::Arc;
+use std::time::Instant;
 
 use cio::IoError;
 use rlp::Encodable;
@@ -24,11 +25,19 @@ use time::Duration;
 use super::NodeId;
 pub use cio::TimerToken;
 
+/// The largest message an extension accepts unless it overrides `Extension::max_message_size`.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1 << 20;
+
 #[derive(Debug)]
 pub enum Error {
     ExtensionDropped,
     DuplicatedTimerId,
     NoMoreTimerToken,
+    /// The timer is not set or already expired.
+    UnknownTimerId,
+    /// Too many messages to the node are not sent yet, and too many wait for them.
+    /// `Extension::on_send_ready` is called when it can send again.
+    QueueFull,
     IoError(IoError),
 }
 
@@ -41,19 +50,47 @@ impl From<IoError> for Error {
 pub type Result<T> = result::Result<T, Error>;
 
 pub trait Api: Send + Sync {
-    fn send(&self, node: &NodeId, message: &[u8]);
+    /// Queues the message to the node.
+    fn send(&self, node: &NodeId, message: &[u8]) -> Result<()>;
 
     fn set_timer(&self, timer: TimerToken, d: Duration) -> Result<()>;
     fn set_timer_once(&self, timer: TimerToken, d: Duration) -> Result<()>;
+    /// Sets the timer which expires once at the deadline. It expires immediately if the deadline is already passed.
+    fn set_timer_at(&self, timer: TimerToken, deadline: Instant) -> Result<()>;
     fn clear_timer(&self, timer: TimerToken) -> Result<()>;
 
     fn send_local_message(&self, message: &Encodable);
+
+    /// The version of the extension agreed with the node. It is `None` until the node is added.
+    fn negotiated_version(&self, node: &NodeId) -> Option<u64>;
+
+    /// Closes the connection to the misbehaving node and keeps it from being dialed again.
+    fn disconnect(&self, node: &NodeId);
 }
 
 pub trait Extension: Send + Sync {
     fn name(&self) -> &'static str;
     fn need_encryption(&self) -> bool;
     fn versions(&self) -> &[u64];
+    /// Chooses the version to talk with the node among the versions the node supports.
+    /// The node is refused if it returns `None`.
+    fn select_version(&self, _node: &NodeId, versions: &[u64]) -> Option<u64> {
+        self.versions().iter().filter(|version| versions.contains(version)).max().cloned()
+    }
+    /// Names of the extensions which must be initialized before this extension.
+    fn dependencies(&self) -> &[&'static str] {
+        &[]
+    }
+    /// The largest message, in bytes, the extension accepts.
+    /// A node sending a larger message is disconnected before the message is decoded.
+    fn max_message_size(&self) -> usize {
+        DEFAULT_MAX_MESSAGE_SIZE
+    }
+    /// How long the messages to a disconnected node are kept to be sent when it connects again.
+    /// They are dropped if it returns `None` or the pending message queue is disabled.
+    fn pending_message_ttl(&self) -> Option<Duration> {
+        None
+    }
 
     fn on_initialize(&self, api: Arc<Api>);
 
@@ -61,6 +98,8 @@ pub trait Extension: Send + Sync {
     fn on_node_removed(&self, _node: &NodeId) {}
 
     fn on_message(&self, _node: &NodeId, _message: &[u8]) {}
+    /// Called when the queue to the node, which was full, has room again.
+    fn on_send_ready(&self, _node: &NodeId) {}
 
     fn on_timeout(&self, _timer: TimerToken) {}
 
@@ -0,0 +1,109 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+
+/// Counters of an extension since the node started.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ExtensionStats {
+    pub received_messages: u64,
+    pub received_bytes: u64,
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+    pub timer_fires: u64,
+    /// Errors returned to the extension by its `Api`.
+    pub errors: u64,
+}
+
+/// Counters of every extension, so an operator can see an extension which has gone silent.
+#[derive(Default)]
+pub struct ExtensionStatsTable {
+    table: Mutex<HashMap<&'static str, ExtensionStats>>,
+}
+
+impl ExtensionStatsTable {
+    pub fn on_received(&self, extension_name: &'static str, bytes: usize) {
+        let mut table = self.table.lock();
+        let stats = table.entry(extension_name).or_insert_with(Default::default);
+        stats.received_messages += 1;
+        stats.received_bytes += bytes as u64;
+    }
+
+    pub fn on_sent(&self, extension_name: &'static str, bytes: usize) {
+        let mut table = self.table.lock();
+        let stats = table.entry(extension_name).or_insert_with(Default::default);
+        stats.sent_messages += 1;
+        stats.sent_bytes += bytes as u64;
+    }
+
+    pub fn on_timer_fired(&self, extension_name: &'static str) {
+        self.table.lock().entry(extension_name).or_insert_with(Default::default).timer_fires += 1;
+    }
+
+    pub fn on_error(&self, extension_name: &'static str) {
+        self.table.lock().entry(extension_name).or_insert_with(Default::default).errors += 1;
+    }
+
+    /// Adds an empty entry, so the registered extensions are listed before they do anything.
+    pub fn register(&self, extension_name: &'static str) {
+        self.table.lock().entry(extension_name).or_insert_with(Default::default);
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, ExtensionStats> {
+        self.table.lock().iter().map(|(name, stats)| (name.to_string(), *stats)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_per_extension() {
+        let table = ExtensionStatsTable::default();
+        table.register("a");
+        table.register("b");
+        table.on_received("a", 10);
+        table.on_received("a", 5);
+        table.on_sent("a", 3);
+        table.on_timer_fired("b");
+        table.on_error("b");
+
+        let snapshot = table.snapshot();
+        assert_eq!(
+            ExtensionStats {
+                received_messages: 2,
+                received_bytes: 15,
+                sent_messages: 1,
+                sent_bytes: 3,
+                timer_fires: 0,
+                errors: 0,
+            },
+            snapshot["a"]
+        );
+        assert_eq!(
+            ExtensionStats {
+                timer_fires: 1,
+                errors: 1,
+                ..Default::default()
+            },
+            snapshot["b"]
+        );
+    }
+}
@@ -16,20 +16,25 @@
 
 use std::net::IpAddr;
 
+use super::super::SocketAddr;
+use super::filter::FilterEntry;
+
 pub trait Control: Send + Sync {
-    fn add_to_whitelist(&self, addr: IpAddr);
-    fn remove_from_whitelist(&self, addr: &IpAddr);
+    fn add_to_whitelist(&self, entry: FilterEntry);
+    fn remove_from_whitelist(&self, entry: &FilterEntry);
 
-    fn add_to_blacklist(&self, addr: IpAddr);
-    fn remove_from_blacklist(&self, addr: &IpAddr);
+    fn add_to_blacklist(&self, entry: FilterEntry);
+    fn remove_from_blacklist(&self, entry: &FilterEntry);
 
     fn enable_whitelist(&self);
     fn disable_whitelist(&self);
     fn enable_blacklist(&self);
     fn disable_blacklist(&self);
 
-    fn get_whitelist(&self) -> (Vec<IpAddr>, bool);
-    fn get_blacklist(&self) -> (Vec<IpAddr>, bool);
+    fn get_whitelist(&self) -> (Vec<FilterEntry>, bool);
+    fn get_blacklist(&self) -> (Vec<FilterEntry>, bool);
 
+    /// Whether the address may connect. The nodes in the lists can't be told before their ports are known.
     fn is_allowed(&self, addr: &IpAddr) -> bool;
+    fn is_allowed_node(&self, node: &SocketAddr) -> bool;
 }
@@ -15,11 +15,98 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::collections::HashSet;
+use std::fmt;
 use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::super::SocketAddr;
+
+/// An entry of the whitelist or the blacklist.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum FilterEntry {
+    /// The addresses in a CIDR block.
+    Network {
+        ip: IpAddr,
+        prefix_len: u8,
+    },
+    /// The node listening on the address.
+    Node(SocketAddr),
+}
+
+impl FilterEntry {
+    /// The entry matching only the given address.
+    pub fn host(ip: IpAddr) -> Self {
+        FilterEntry::Network {
+            ip,
+            prefix_len: max_prefix_len(&ip),
+        }
+    }
+}
+
+/// A bare IPv4 address with trailing zero octets matches the addresses sharing the other octets.
+/// e.g. 1.2.0.0 is the same as 1.2.0.0/16.
+impl From<IpAddr> for FilterEntry {
+    fn from(ip: IpAddr) -> Self {
+        let prefix_len = match ip {
+            IpAddr::V4(ipv4) => 8 * (4 - ipv4.octets().iter().rev().take_while(|octet| **octet == 0).count() as u8),
+            IpAddr::V6(_) => 128,
+        };
+        FilterEntry::Network {
+            ip,
+            prefix_len,
+        }
+    }
+}
+
+impl FromStr for FilterEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(index) = s.find('/') {
+            let ip: IpAddr = s[..index].parse().map_err(|_| format!("Invalid CIDR block {:?}", s))?;
+            let prefix_len: u8 = s[index + 1..].parse().map_err(|_| format!("Invalid CIDR block {:?}", s))?;
+            if prefix_len > max_prefix_len(&ip) {
+                return Err(format!("Invalid prefix length of {:?}", s))
+            }
+            return Ok(FilterEntry::Network {
+                ip,
+                prefix_len,
+            })
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(ip.into())
+        }
+        let node = s.parse().map_err(|_| format!("{:?} is neither an IP address, a CIDR block nor a node", s))?;
+        Ok(FilterEntry::Node(node))
+    }
+}
+
+impl fmt::Display for FilterEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterEntry::Network {
+                ip,
+                ..
+            } if FilterEntry::from(*ip) == *self => write!(f, "{}", ip),
+            FilterEntry::Network {
+                ip,
+                prefix_len,
+            } => write!(f, "{}/{}", ip, prefix_len),
+            FilterEntry::Node(node) => write!(f, "{}", node),
+        }
+    }
+}
+
+fn max_prefix_len(ip: &IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
 
 pub struct Filter {
     enabled: bool,
-    list: HashSet<IpAddr>,
+    list: HashSet<FilterEntry>,
 }
 
 impl Default for Filter {
@@ -32,19 +119,19 @@ impl Default for Filter {
 }
 
 impl Filter {
-    pub fn new(input_vector: Vec<IpAddr>) -> Self {
+    pub fn new(input_vector: Vec<FilterEntry>) -> Self {
         Self {
             enabled: !input_vector.is_empty(),
             list: input_vector.into_iter().collect(),
         }
     }
 
-    pub fn add(&mut self, addr: IpAddr) {
-        self.list.insert(addr);
+    pub fn add(&mut self, entry: FilterEntry) {
+        self.list.insert(entry);
     }
 
-    pub fn remove(&mut self, addr: &IpAddr) {
-        self.list.remove(&addr);
+    pub fn remove(&mut self, entry: &FilterEntry) {
+        self.list.remove(entry);
     }
 
     pub fn enable(&mut self) {
@@ -55,8 +142,8 @@ impl Filter {
         self.enabled = false;
     }
 
-    pub fn status(&self) -> (Vec<IpAddr>, bool) {
-        let mut list: Vec<_> = self.list.iter().map(|a| *a).collect();
+    pub fn status(&self) -> (Vec<FilterEntry>, bool) {
+        let mut list: Vec<_> = self.list.iter().cloned().collect();
         list.sort();
         (list, self.enabled)
     }
@@ -65,29 +152,61 @@ impl Filter {
         self.enabled
     }
 
+    /// Whether a CIDR block of the list contains the address.
     pub fn contains(&self, addr: &IpAddr) -> bool {
         debug_assert!(self.enabled);
         debug_assert!(!addr.is_unspecified(), "{:?}", addr);
         self.list.iter().any(|filter| is_filtered(addr, filter))
     }
+
+    /// Whether the list has a node listening on the address.
+    pub fn has_node_on(&self, addr: &IpAddr) -> bool {
+        debug_assert!(self.enabled);
+        self.list.iter().any(|filter| match filter {
+            FilterEntry::Node(node) => node.ip() == *addr,
+            FilterEntry::Network {
+                ..
+            } => false,
+        })
+    }
+
+    /// Whether the node or a CIDR block containing its address is in the list.
+    pub fn contains_node(&self, node: &SocketAddr) -> bool {
+        debug_assert!(self.enabled);
+        self.contains(&node.ip()) || self.list.contains(&FilterEntry::Node(*node))
+    }
 }
 
-fn is_filtered(target: &IpAddr, filter: &IpAddr) -> bool {
-    match (target, filter) {
-        (IpAddr::V4(target), IpAddr::V4(filter)) => {
+fn is_filtered(target: &IpAddr, filter: &FilterEntry) -> bool {
+    let (ip, prefix_len) = match filter {
+        FilterEntry::Network {
+            ip,
+            prefix_len,
+        } => (ip, *prefix_len),
+        FilterEntry::Node(_) => return false,
+    };
+    match (target, ip) {
+        (IpAddr::V4(target), IpAddr::V4(ip)) => {
             debug_assert!(!target.is_unspecified(), "{:?}", target);
             debug_assert!(!target.is_broadcast(), "{:?}", target);
-            match (target.octets(), filter.octets()) {
-                (_, [0, 0, 0, 0]) => true,
-                ([a0, _, _, _], [f0, 0, 0, 0]) => a0 == f0,
-                ([a0, a1, _, _], [f0, f1, 0, 0]) => a0 == f0 && a1 == f1,
-                ([a0, a1, a2, _], [f0, f1, f2, 0]) => a0 == f0 && a1 == f1 && a2 == f2,
-                ([a0, a1, a2, a3], [f0, f1, f2, f3]) => a0 == f0 && a1 == f1 && a2 == f2 && a3 == f3,
-            }
+            has_same_prefix(&target.octets(), &ip.octets(), prefix_len)
         }
-        (IpAddr::V6(_), _) => unreachable!(),
-        (_, IpAddr::V6(_)) => unreachable!(),
+        (IpAddr::V6(target), IpAddr::V6(ip)) => has_same_prefix(&target.octets(), &ip.octets(), prefix_len),
+        _ => false,
+    }
+}
+
+fn has_same_prefix(lhs: &[u8], rhs: &[u8], prefix_len: u8) -> bool {
+    let bytes = prefix_len as usize / 8;
+    let bits = prefix_len % 8;
+    if lhs[..bytes] != rhs[..bytes] {
+        return false
+    }
+    if bits == 0 {
+        return true
     }
+    let mask = !0u8 << (8 - bits);
+    lhs[bytes] & mask == rhs[bytes] & mask
 }
 
 #[cfg(test)]
@@ -100,7 +219,7 @@ mod tests_is_filtered {
     fn same_ip_is_filtered() {
         let ip = IpAddr::from_str("1.2.3.4").unwrap();
         let filter = IpAddr::from_str("1.2.3.4").unwrap();
-        assert!(is_filtered(&ip, &filter));
+        assert!(is_filtered(&ip, &filter.into()));
     }
 
     #[test]
@@ -111,11 +230,11 @@ mod tests_is_filtered {
         let ip3 = IpAddr::from_str("1.2.7.4").unwrap();
         let ip4 = IpAddr::from_str("1.2.8.9").unwrap();
         let filter = IpAddr::from_str("1.2.0.0").unwrap();
-        assert!(is_filtered(&ip0, &filter));
-        assert!(is_filtered(&ip1, &filter));
-        assert!(is_filtered(&ip2, &filter));
-        assert!(is_filtered(&ip3, &filter));
-        assert!(is_filtered(&ip4, &filter));
+        assert!(is_filtered(&ip0, &filter.into()));
+        assert!(is_filtered(&ip1, &filter.into()));
+        assert!(is_filtered(&ip2, &filter.into()));
+        assert!(is_filtered(&ip3, &filter.into()));
+        assert!(is_filtered(&ip4, &filter.into()));
     }
 
     #[test]
@@ -126,11 +245,11 @@ mod tests_is_filtered {
         let ip3 = IpAddr::from_str("100.2.7.4").unwrap();
         let ip4 = IpAddr::from_str("1.21.8.9").unwrap();
         let filter = IpAddr::from_str("1.2.0.0").unwrap();
-        assert!(!is_filtered(&ip0, &filter));
-        assert!(!is_filtered(&ip1, &filter));
-        assert!(!is_filtered(&ip2, &filter));
-        assert!(!is_filtered(&ip3, &filter));
-        assert!(!is_filtered(&ip4, &filter));
+        assert!(!is_filtered(&ip0, &filter.into()));
+        assert!(!is_filtered(&ip1, &filter.into()));
+        assert!(!is_filtered(&ip2, &filter.into()));
+        assert!(!is_filtered(&ip3, &filter.into()));
+        assert!(!is_filtered(&ip4, &filter.into()));
     }
 }
 
@@ -147,7 +266,7 @@ mod tests {
         filter.enable();
         assert!(filter.is_enabled());
 
-        filter.add(IpAddr::from_str("100.2.7.4").unwrap());
+        filter.add(IpAddr::from_str("100.2.7.4").unwrap().into());
 
         assert!(filter.contains(&IpAddr::from_str("100.2.7.4").unwrap()));
         assert!(!filter.contains(&IpAddr::from_str("100.2.7.3").unwrap()));
@@ -160,11 +279,42 @@ mod tests {
         filter.enable();
         assert!(filter.is_enabled());
 
-        filter.add(IpAddr::from_str("100.2.7.4").unwrap());
+        filter.add(IpAddr::from_str("100.2.7.4").unwrap().into());
 
         assert!(filter.contains(&IpAddr::from_str("100.2.7.4").unwrap()));
 
-        filter.remove(&IpAddr::from_str("100.2.7.4").unwrap());
+        filter.remove(&IpAddr::from_str("100.2.7.4").unwrap().into());
         assert!(!filter.contains(&IpAddr::from_str("100.2.7.4").unwrap()));
     }
+
+    #[test]
+    fn cidr_block() {
+        let entry: FilterEntry = "10.1.128.0/17".parse().unwrap();
+        assert!(is_filtered(&IpAddr::from_str("10.1.200.3").unwrap(), &entry));
+        assert!(!is_filtered(&IpAddr::from_str("10.1.127.3").unwrap(), &entry));
+        assert!(!is_filtered(&IpAddr::from_str("::1").unwrap(), &entry));
+        assert_eq!("10.1.128.0/17", entry.to_string());
+
+        let entry: FilterEntry = "2001:db8::/32".parse().unwrap();
+        assert!(is_filtered(&IpAddr::from_str("2001:db8:1::7").unwrap(), &entry));
+        assert!(!is_filtered(&IpAddr::from_str("2001:db9::7").unwrap(), &entry));
+
+        assert!(FilterEntry::from_str("10.1.128.0/33").is_err());
+    }
+
+    #[test]
+    fn bare_address_keeps_its_form() {
+        assert_eq!("1.2.0.0", FilterEntry::from_str("1.2.0.0").unwrap().to_string());
+        assert_eq!("1.2.0.0/32", FilterEntry::host(IpAddr::from_str("1.2.0.0").unwrap()).to_string());
+    }
+
+    #[test]
+    fn node_entry_matches_only_the_node() {
+        let filter = Filter::new(vec!["1.2.3.4:3485".parse().unwrap()]);
+        assert!(filter.contains_node(&SocketAddr::v4(1, 2, 3, 4, 3485)));
+        assert!(!filter.contains_node(&SocketAddr::v4(1, 2, 3, 4, 3486)));
+        assert!(!filter.contains(&IpAddr::from_str("1.2.3.4").unwrap()));
+        assert!(filter.has_node_on(&IpAddr::from_str("1.2.3.4").unwrap()));
+        assert!(!filter.has_node_on(&IpAddr::from_str("1.2.3.5").unwrap()));
+    }
 }
@@ -14,29 +14,115 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::fs;
+use std::io;
 use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use parking_lot::RwLock;
 
+use super::super::SocketAddr;
 use super::control::Control;
-use super::filter::Filter;
+use super::filter::{Filter, FilterEntry};
 
 pub struct Filters {
     whitelist: RwLock<Filter>,
     blacklist: RwLock<Filter>,
+    /// The files where the changes of the lists are saved.
+    whitelist_path: Option<PathBuf>,
+    blacklist_path: Option<PathBuf>,
 }
 
 impl Filters {
-    pub fn new(whitelist_vector: Vec<IpAddr>, blacklist_vector: Vec<IpAddr>) -> Arc<Self> {
+    pub fn new(whitelist_vector: Vec<FilterEntry>, blacklist_vector: Vec<FilterEntry>) -> Arc<Self> {
         let whitelist = Filter::new(whitelist_vector);
         let blacklist = Filter::new(blacklist_vector);
 
         Arc::new(Self {
             whitelist: RwLock::new(whitelist),
             blacklist: RwLock::new(blacklist),
+            whitelist_path: None,
+            blacklist_path: None,
         })
     }
+
+    /// Reads the lists from the files, which keep the changes made at runtime.
+    /// A list is empty and disabled if its file doesn't exist yet.
+    pub fn load(whitelist_path: Option<&Path>, blacklist_path: Option<&Path>) -> Result<Arc<Self>, String> {
+        let whitelist = read_list(whitelist_path, "white")?;
+        let blacklist = read_list(blacklist_path, "black")?;
+
+        Ok(Arc::new(Self {
+            whitelist: RwLock::new(whitelist),
+            blacklist: RwLock::new(blacklist),
+            whitelist_path: whitelist_path.map(Path::to_path_buf),
+            blacklist_path: blacklist_path.map(Path::to_path_buf),
+        }))
+    }
+}
+
+const ENABLED: &str = "enabled";
+const DISABLED: &str = "disabled";
+
+fn read_list(path: Option<&Path>, list_name: &str) -> Result<Filter, String> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(Filter::default()),
+    };
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Filter::default()),
+        Err(err) => return Err(format!("Cannot open the {}list file {:?}: {:?}", list_name, path, err)),
+    };
+    parse_list(&contents)
+}
+
+/// The saved lists start with whether they are enabled.
+/// A list written by hand may omit it, and then it is enabled if it isn't empty.
+fn parse_list(contents: &str) -> Result<Filter, String> {
+    let mut words = contents.split_whitespace().peekable();
+    let enabled = match words.peek() {
+        Some(&ENABLED) => Some(true),
+        Some(&DISABLED) => Some(false),
+        _ => None,
+    };
+    if enabled.is_some() {
+        words.next();
+    }
+    let mut filter = Filter::new(words.map(str::parse).collect::<Result<Vec<_>, _>>()?);
+    match enabled {
+        Some(true) => filter.enable(),
+        Some(false) => filter.disable(),
+        None => {}
+    }
+    Ok(filter)
+}
+
+fn format_list(filter: &Filter) -> String {
+    let (list, enabled) = filter.status();
+    let header = if enabled {
+        ENABLED
+    } else {
+        DISABLED
+    };
+    let mut contents = format!("{}\n", header);
+    for entry in list {
+        contents.push_str(&format!("{}\n", entry));
+    }
+    contents
+}
+
+/// Writes a temporary file and renames it, so the list file is never left half written.
+fn save_list(filter: &Filter, path: Option<&PathBuf>, list_name: &str) {
+    let path = match path {
+        Some(path) => path,
+        None => return,
+    };
+    let temp_path = path.with_extension("tmp");
+    if let Err(err) = fs::write(&temp_path, format_list(filter)).and_then(|_| fs::rename(&temp_path, path)) {
+        cwarn!(NETFILTER, "Cannot save the {}list to {:?}: {:?}", list_name, path, err);
+    }
 }
 
 impl Default for Filters {
@@ -44,65 +130,75 @@ impl Default for Filters {
         Self {
             whitelist: RwLock::new(Default::default()),
             blacklist: RwLock::new(Default::default()),
+            whitelist_path: None,
+            blacklist_path: None,
         }
     }
 }
 
 impl Control for Filters {
-    fn add_to_whitelist(&self, addr: IpAddr) {
+    fn add_to_whitelist(&self, entry: FilterEntry) {
         let mut whitelist = self.whitelist.write();
-        whitelist.add(addr);
-        cinfo!(NETFILTER, "{:?} is added to the whitelist", addr);
+        whitelist.add(entry);
+        save_list(&whitelist, self.whitelist_path.as_ref(), "white");
+        cinfo!(NETFILTER, "{} is added to the whitelist", entry);
     }
 
-    fn remove_from_whitelist(&self, addr: &IpAddr) {
+    fn remove_from_whitelist(&self, entry: &FilterEntry) {
         let mut whitelist = self.whitelist.write();
-        whitelist.remove(&addr);
-        cinfo!(NETFILTER, "{:?} is removed from the whitelist", addr);
+        whitelist.remove(entry);
+        save_list(&whitelist, self.whitelist_path.as_ref(), "white");
+        cinfo!(NETFILTER, "{} is removed from the whitelist", entry);
     }
 
-    fn add_to_blacklist(&self, addr: IpAddr) {
+    fn add_to_blacklist(&self, entry: FilterEntry) {
         let mut blacklist = self.blacklist.write();
-        blacklist.add(addr);
-        cinfo!(NETFILTER, "{:?} is added to the blacklist", addr);
+        blacklist.add(entry);
+        save_list(&blacklist, self.blacklist_path.as_ref(), "black");
+        cinfo!(NETFILTER, "{} is added to the blacklist", entry);
     }
 
-    fn remove_from_blacklist(&self, addr: &IpAddr) {
+    fn remove_from_blacklist(&self, entry: &FilterEntry) {
         let mut blacklist = self.blacklist.write();
-        blacklist.remove(&addr);
-        cinfo!(NETFILTER, "{:?} is removed from the blacklist", addr);
+        blacklist.remove(entry);
+        save_list(&blacklist, self.blacklist_path.as_ref(), "black");
+        cinfo!(NETFILTER, "{} is removed from the blacklist", entry);
     }
 
     fn enable_whitelist(&self) {
         let mut whitelist = self.whitelist.write();
         whitelist.enable();
+        save_list(&whitelist, self.whitelist_path.as_ref(), "white");
         cinfo!(NETFILTER, "The whitelist is enabled");
     }
 
     fn disable_whitelist(&self) {
         let mut whitelist = self.whitelist.write();
         whitelist.disable();
+        save_list(&whitelist, self.whitelist_path.as_ref(), "white");
         cinfo!(NETFILTER, "The whitelist is disabled");
     }
 
     fn enable_blacklist(&self) {
         let mut blacklist = self.blacklist.write();
         blacklist.enable();
+        save_list(&blacklist, self.blacklist_path.as_ref(), "black");
         cinfo!(NETFILTER, "The blacklist is enabled");
     }
 
     fn disable_blacklist(&self) {
         let mut blacklist = self.blacklist.write();
         blacklist.disable();
+        save_list(&blacklist, self.blacklist_path.as_ref(), "black");
         cinfo!(NETFILTER, "The blacklist is disabled");
     }
 
-    fn get_whitelist(&self) -> (Vec<IpAddr>, bool) {
+    fn get_whitelist(&self) -> (Vec<FilterEntry>, bool) {
         let whitelist = self.whitelist.read();
         whitelist.status()
     }
 
-    fn get_blacklist(&self) -> (Vec<IpAddr>, bool) {
+    fn get_blacklist(&self) -> (Vec<FilterEntry>, bool) {
         let blacklist = self.blacklist.read();
         blacklist.status()
     }
@@ -112,7 +208,7 @@ impl Control for Filters {
         let blacklist = self.blacklist.read();
 
         if whitelist.is_enabled() {
-            if !whitelist.contains(addr) {
+            if !whitelist.contains(addr) && !whitelist.has_node_on(addr) {
                 return false
             }
         }
@@ -124,4 +220,47 @@ impl Control for Filters {
         }
         true
     }
+
+    fn is_allowed_node(&self, node: &SocketAddr) -> bool {
+        let whitelist = self.whitelist.read();
+        let blacklist = self.blacklist.read();
+
+        if whitelist.is_enabled() {
+            if !whitelist.contains_node(node) {
+                return false
+            }
+        }
+
+        if blacklist.is_enabled() {
+            if blacklist.contains_node(node) {
+                return false
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_flag_is_kept() {
+        let mut filter = Filter::new(vec!["1.2.3.4".parse().unwrap()]);
+        filter.disable();
+        let loaded = parse_list(&format_list(&filter)).unwrap();
+        assert_eq!(filter.status(), loaded.status());
+
+        let mut filter = Filter::default();
+        filter.enable();
+        let loaded = parse_list(&format_list(&filter)).unwrap();
+        assert_eq!((vec![], true), loaded.status());
+    }
+
+    #[test]
+    fn list_without_the_flag_is_enabled_if_not_empty() {
+        assert_eq!((vec!["10.0.0.0/8".parse().unwrap()], true), parse_list("10.0.0.0/8\n").unwrap().status());
+        assert_eq!((vec![], false), parse_list("").unwrap().status());
+        assert!(parse_list("enabled\nnot-an-address\n").is_err());
+    }
 }
@@ -19,6 +19,7 @@
 #[macro_use]
 extern crate log;
 extern crate mio;
+extern crate net2;
 extern crate parking_lot;
 extern crate primitives;
 extern crate rand;
@@ -31,22 +32,26 @@ extern crate codechain_crypto as ccrypto;
 extern crate codechain_finally as cfinally;
 extern crate codechain_io as cio;
 extern crate codechain_key as ckey;
-extern crate codechain_limited_table as climited_table;
 extern crate codechain_token_generator as ctoken_generator;
 extern crate codechain_types as ctypes;
-extern crate table as ctable;
 extern crate time;
 
 #[macro_use]
 extern crate codechain_logger as clogger;
 
 mod addr;
+mod allow_list;
+mod chain_identity;
 mod client;
 mod config;
 mod discovery;
 mod extension;
+mod extension_stats;
 mod filters;
 mod node_id;
+mod node_key;
+mod outbound_queues;
+mod pending_messages;
 mod routing_table;
 mod service;
 mod session_initiator;
@@ -56,17 +61,25 @@ mod timer;
 pub mod control;
 mod p2p;
 pub mod session;
+pub mod test_harness;
 
 pub use self::addr::SocketAddr;
+pub use self::allow_list::{node_allow_list_hash, Error as NodeAllowListError, NodeAllowList};
+pub use self::chain_identity::{ChainHead, ChainIdentity, ForkId};
 pub use self::config::Config as NetworkConfig;
-pub use self::control::{Control as NetworkControl, Error as NetworkControlError};
+pub use self::control::{Control as NetworkControl, Error as NetworkControlError, LocalInfo};
 pub use self::discovery::Api as DiscoveryApi;
 pub use self::extension::{
     Api, Error as NetworkExtensionError, Extension as NetworkExtension, Result as NetworkExtensionResult, TimerToken,
+    DEFAULT_MAX_MESSAGE_SIZE,
 };
+pub use self::extension_stats::ExtensionStats;
 pub use self::node_id::{IntoSocketAddr, NodeId};
+pub use self::node_key::load_node_key;
+pub use self::p2p::check_peer_limits;
+pub use self::pending_messages::PendingMessages;
 pub use self::service::{Error as NetworkServiceError, Service as NetworkService};
 pub use self::test::{Call as TestNetworkCall, TestClient as TestNetworkClient};
 
-pub use self::filters::{Filters, FiltersControl};
+pub use self::filters::{FilterEntry, Filters, FiltersControl};
 pub use self::routing_table::RoutingTable;
@@ -0,0 +1,114 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use ckey::hex::ToHex;
+use ckey::{Generator, KeyPair, Private, Random};
+
+/// Reads the node key from the file. A new key is generated and saved to the file if it doesn't exist yet.
+///
+/// The file holds the hex of the private key. The key is rotated by removing the file before the node starts.
+pub fn load_node_key(path: &Path) -> Result<KeyPair, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            check_permissions(path)?;
+            let private: Private =
+                contents.trim().parse().map_err(|err| format!("Invalid node key in {:?}: {}", path, err))?;
+            KeyPair::from_private(private).map_err(|err| format!("Invalid node key in {:?}: {}", path, err))
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            let key_pair = Random.generate().map_err(|err| format!("Cannot generate a node key: {}", err))?;
+            save_node_key(path, &key_pair).map_err(|err| format!("Cannot save the node key to {:?}: {}", path, err))?;
+            cinfo!(NETWORK, "A new node key is saved to {:?}", path);
+            Ok(key_pair)
+        }
+        Err(err) => Err(format!("Cannot read the node key {:?}: {}", path, err)),
+    }
+}
+
+fn save_node_key(path: &Path, key_pair: &KeyPair) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = owner_only(OpenOptions::new().write(true).create_new(true)).open(path)?;
+    writeln!(file, "{}", key_pair.private().to_hex())
+}
+
+#[cfg(unix)]
+fn owner_only(options: &mut OpenOptions) -> &mut OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600)
+}
+
+#[cfg(not(unix))]
+fn owner_only(options: &mut OpenOptions) -> &mut OpenOptions {
+    options
+}
+
+/// Refuses the key file which others can read, like ssh does.
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = fs::metadata(path).map_err(|err| format!("Cannot read the node key {:?}: {}", path, err))?;
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(format!("The node key {:?} is accessible by others ({:o}). Run `chmod 600` on it", path, mode))
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn the_generated_key_is_loaded_again() {
+        let path = env::temp_dir().join(format!("codechain-node-key-{}", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let generated = load_node_key(&path).unwrap();
+        let loaded = load_node_key(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Ok(generated), loaded);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_key_readable_by_others_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = env::temp_dir().join(format!("codechain-open-node-key-{}", process::id()));
+        let _ = fs::remove_file(&path);
+
+        load_node_key(&path).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let loaded = load_node_key(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+}
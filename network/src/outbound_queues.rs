@@ -0,0 +1,211 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+
+use super::NodeId;
+
+/// Messages that an extension can queue for a node before they are written to the socket.
+const MAX_QUEUED_MESSAGES: usize = 1024;
+/// Messages that wait for a slot of the full queue. They are queued as the written messages free the slots.
+const MAX_DEFERRED_MESSAGES: usize = 1024;
+
+pub enum Reservation<M> {
+    /// The message took a slot and can be sent now.
+    Send(M),
+    /// The queue is full. The message is sent when a slot is freed.
+    Deferred,
+    /// The queue and the deferred messages are full. The message is dropped.
+    Full,
+}
+
+pub enum Release<M> {
+    /// The deferred message which takes the freed slot.
+    Next(M),
+    /// The extension can send messages again after it was refused.
+    Ready,
+    Nothing,
+}
+
+struct Queue<M> {
+    queued: usize,
+    deferred: VecDeque<M>,
+    /// Whether a message was refused since the queue became full.
+    refused: bool,
+}
+
+/// Numbers of the extension messages which are queued but not written to the sockets yet.
+///
+/// An extension cannot queue more messages for a node once its queue is full,
+/// so a slow peer cannot make the messages pile up in memory.
+pub struct OutboundQueues<M> {
+    limit: usize,
+    deferred_limit: usize,
+    queues: Mutex<HashMap<(NodeId, &'static str), Queue<M>>>,
+}
+
+impl<M> Default for OutboundQueues<M> {
+    fn default() -> Self {
+        Self::new(MAX_QUEUED_MESSAGES, MAX_DEFERRED_MESSAGES)
+    }
+}
+
+impl<M> OutboundQueues<M> {
+    pub fn new(limit: usize, deferred_limit: usize) -> Self {
+        Self {
+            limit,
+            deferred_limit,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a slot of the queue, or defers the message if the queue is full.
+    pub fn try_reserve(&self, node: &NodeId, extension_name: &'static str, message: M) -> Reservation<M> {
+        let mut queues = self.queues.lock();
+        let queue = queues.entry((*node, extension_name)).or_insert_with(|| Queue {
+            queued: 0,
+            deferred: VecDeque::new(),
+            refused: false,
+        });
+        if queue.queued < self.limit {
+            queue.queued += 1;
+            return Reservation::Send(message)
+        }
+        if queue.deferred.len() < self.deferred_limit {
+            queue.deferred.push_back(message);
+            return Reservation::Deferred
+        }
+        queue.refused = true;
+        Reservation::Full
+    }
+
+    /// Frees the slot of a message which is written or dropped.
+    /// The first deferred message takes the slot if there is one.
+    pub fn release(&self, node: &NodeId, extension_name: &'static str) -> Release<M> {
+        let mut queues = self.queues.lock();
+        let key = (*node, extension_name);
+        let release = match queues.get_mut(&key) {
+            Some(queue) => {
+                if let Some(message) = queue.deferred.pop_front() {
+                    return Release::Next(message)
+                }
+                queue.queued -= 1;
+                if queue.refused {
+                    queue.refused = false;
+                    Release::Ready
+                } else {
+                    Release::Nothing
+                }
+            }
+            None => return Release::Nothing,
+        };
+        if queues[&key].queued == 0 {
+            queues.remove(&key);
+        }
+        release
+    }
+
+    /// Frees the slot of a message which couldn't be handed to the p2p handler.
+    pub fn cancel(&self, node: &NodeId, extension_name: &'static str) {
+        let mut queues = self.queues.lock();
+        let key = (*node, extension_name);
+        let empty = match queues.get_mut(&key) {
+            Some(queue) => {
+                queue.queued -= 1;
+                queue.queued == 0 && queue.deferred.is_empty()
+            }
+            None => return,
+        };
+        if empty {
+            queues.remove(&key);
+        }
+    }
+
+    /// Frees all the slots and drops the deferred messages of the node which is disconnected.
+    pub fn remove_node(&self, node: &NodeId) {
+        self.queues.lock().retain(|(queued_node, _), _| queued_node != node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SocketAddr;
+    use super::*;
+
+    fn is_sent(reservation: Reservation<u32>, expected: u32) -> bool {
+        match reservation {
+            Reservation::Send(message) => message == expected,
+            _ => false,
+        }
+    }
+
+    fn is_deferred(reservation: Reservation<u32>) -> bool {
+        match reservation {
+            Reservation::Deferred => true,
+            _ => false,
+        }
+    }
+
+    fn is_full(reservation: Reservation<u32>) -> bool {
+        match reservation {
+            Reservation::Full => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn full_queue_defers_until_released() {
+        let queues = OutboundQueues::new(2, 1);
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+
+        assert!(is_sent(queues.try_reserve(&node, "a", 1), 1));
+        assert!(is_sent(queues.try_reserve(&node, "a", 2), 2));
+        assert!(is_deferred(queues.try_reserve(&node, "a", 3)));
+        assert!(is_full(queues.try_reserve(&node, "a", 4)));
+        assert!(is_sent(queues.try_reserve(&node, "b", 5), 5));
+
+        match queues.release(&node, "a") {
+            Release::Next(3) => {}
+            _ => panic!("The deferred message must take the freed slot"),
+        }
+        match queues.release(&node, "a") {
+            Release::Ready => {}
+            _ => panic!("The refused extension must be told"),
+        }
+        match queues.release(&node, "a") {
+            Release::Nothing => {}
+            _ => panic!("The extension is told only once"),
+        }
+        assert!(is_sent(queues.try_reserve(&node, "a", 6), 6));
+    }
+
+    #[test]
+    fn removed_node_starts_over() {
+        let queues = OutboundQueues::new(1, 1);
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+
+        assert!(is_sent(queues.try_reserve(&node, "a", 1), 1));
+        assert!(is_deferred(queues.try_reserve(&node, "a", 2)));
+        queues.remove_node(&node);
+        match queues.release(&node, "a") {
+            Release::Nothing => {}
+            _ => panic!("The deferred messages of the removed node must be dropped"),
+        }
+        assert!(is_sent(queues.try_reserve(&node, "a", 3), 3));
+    }
+}
@@ -19,6 +19,7 @@ use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::io;
 use std::result;
+use std::time::{Duration, Instant};
 
 use cio::IoManager;
 use mio::deprecated::EventLoop;
@@ -28,10 +29,13 @@ use parking_lot::Mutex;
 use rlp::{DecoderError, UntrustedRlp};
 
 use super::super::session::Session;
-use super::super::{NodeId, SocketAddr};
-use super::message::{HandshakeMessage, Message, Seq, SignedMessage, Version};
+use super::super::{ForkId, NodeId, SocketAddr};
+use super::message::{HandshakeMessage, Message, Seq, SignedMessage, Version, KEEP_ALIVE_VERSION};
 use super::stream::{Error as StreamError, SignedStream, Stream};
-use super::{ExtensionMessage, NegotiationMessage};
+use super::{ExtensionMessage, KeepAliveMessage, NegotiationMessage};
+
+/// Handshake messages carry only the port, the node id and the fork id.
+const MAX_HANDSHAKE_MESSAGE_SIZE: usize = 1024;
 
 struct EstablishedConnection {
     stream: SignedStream,
@@ -39,6 +43,15 @@ struct EstablishedConnection {
     next_negotiation_seq: Seq,
     requested_negotiation: HashMap<Seq, String>,
     remote_node_id: NodeId,
+    /// Extensions of the messages which are written or dropped since the last `take_dequeued_extension_messages`.
+    dequeued_extension_messages: Vec<String>,
+    /// Whether the node knows the keep-alive messages.
+    answers_pings: bool,
+    next_ping_seq: Seq,
+    /// The last ping which is not answered yet and when it was queued.
+    unanswered_ping: Option<(Seq, Instant)>,
+    missed_pings: usize,
+    rtt: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -73,13 +86,19 @@ impl From<StreamError> for Error {
 pub type Result<T> = result::Result<T, Error>;
 
 impl EstablishedConnection {
-    fn new(stream: SignedStream, remote_node_id: NodeId) -> Self {
+    fn new(stream: SignedStream, remote_node_id: NodeId, remote_version: Version) -> Self {
         Self {
             stream,
             send_queue: VecDeque::new(),
             next_negotiation_seq: 0,
             requested_negotiation: HashMap::new(),
             remote_node_id,
+            dequeued_extension_messages: Vec::new(),
+            answers_pings: remote_version >= KEEP_ALIVE_VERSION,
+            next_ping_seq: 0,
+            unanswered_ping: None,
+            missed_pings: 0,
+            rtt: None,
         }
     }
 
@@ -108,51 +127,109 @@ impl EstablishedConnection {
         self.enqueue(Message::Negotiation(NegotiationMessage::allowed(seq, version)));
     }
 
-    fn enqueue_extension_message(&mut self, extension_name: String, need_encryption: bool, message: &[u8]) {
-        const VERSION: u64 = 0;
+    fn enqueue_negotiation_denied(&mut self, seq: Seq) {
+        self.enqueue(Message::Negotiation(NegotiationMessage::denied(seq)));
+    }
+
+    fn enqueue_extension_message(
+        &mut self,
+        extension_name: String,
+        version: Version,
+        need_encryption: bool,
+        message: &[u8],
+    ) {
         let message = if need_encryption {
             match ExtensionMessage::encrypted_from_unencrypted_data(
-                extension_name,
-                VERSION,
+                extension_name.clone(),
+                version,
                 message,
                 self.stream.session(),
             ) {
                 Ok(message) => message,
                 Err(err) => {
                     cdebug!(NETWORK, "Cannot encrypt message : {:?}", err);
+                    self.dequeued_extension_messages.push(extension_name);
                     return
                 }
             }
         } else {
-            ExtensionMessage::unencrypted(extension_name, VERSION, &message)
+            ExtensionMessage::unencrypted(extension_name, version, &message)
         };
         self.enqueue(Message::Extension(message));
     }
 
+    fn take_dequeued_extension_messages(&mut self) -> Vec<String> {
+        ::std::mem::replace(&mut self.dequeued_extension_messages, Vec::new())
+    }
+
+    /// Queues a new ping, returning how many pings in a row the node hasn't answered.
+    /// Returns `None` without queueing if the node doesn't know the pings.
+    fn enqueue_ping(&mut self) -> Option<usize> {
+        if !self.answers_pings {
+            return None
+        }
+        if self.unanswered_ping.is_some() {
+            self.missed_pings += 1;
+        }
+        let seq = self.next_ping_seq;
+        self.next_ping_seq += 1;
+        self.unanswered_ping = Some((seq, Instant::now()));
+        self.enqueue(Message::KeepAlive(KeepAliveMessage::ping(seq)));
+        Some(self.missed_pings)
+    }
+
+    fn enqueue_pong(&mut self, seq: Seq) {
+        self.enqueue(Message::KeepAlive(KeepAliveMessage::pong(seq)));
+    }
+
+    /// Any answer shows the node is alive, but only the answer to the last ping measures the round-trip time.
+    fn on_pong(&mut self, seq: Seq) {
+        if seq >= self.next_ping_seq {
+            return
+        }
+        self.missed_pings = 0;
+        if let Some((unanswered_seq, sent_at)) = self.unanswered_ping {
+            if unanswered_seq == seq {
+                self.rtt = Some(sent_at.elapsed());
+                self.unanswered_ping = None;
+            }
+        }
+    }
+
+    fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
     fn stream(&self) -> &SignedStream {
         &self.stream
     }
 
     fn interest(&self) -> Ready {
-        if self.send_queue.is_empty() {
+        if self.send_queue.is_empty() && !self.stream.is_writing() {
             Ready::readable() | UnixReady::hup()
         } else {
             Ready::writable() | Ready::readable() | UnixReady::hup()
         }
     }
 
+    /// Writes the queued messages until the socket stops taking them.
     fn send(&mut self) -> Result<bool> {
-        if let Some(message) = self.send_queue.pop_front() {
+        self.stream.flush()?;
+        while !self.stream.is_writing() {
+            let message = match self.send_queue.pop_front() {
+                Some(message) => message,
+                None => break,
+            };
             self.stream.write(&message)?;
-            Ok(false)
-        } else {
-            self.stream.flush()?;
-            Ok(false)
+            if let Message::Extension(extension_message) = &message {
+                self.dequeued_extension_messages.push(extension_message.extension_name().clone());
+            }
         }
+        Ok(false)
     }
 
-    fn receive(&mut self) -> Result<Option<Message>> {
-        Ok(self.stream.read()?)
+    fn receive(&mut self, max_size: usize) -> Result<Option<Message>> {
+        Ok(self.stream.read(max_size)?)
     }
 
     fn remote_node_id(&self) -> Option<NodeId> {
@@ -193,23 +270,28 @@ struct WaitSyncConnection {
     stream: Stream,
     session: Option<Session>,
     remote_node_id: Option<NodeId>,
+    remote_version: Version,
+    fork_id: ForkId,
     state: WaitState,
 }
 
 impl WaitSyncConnection {
-    fn new(stream: Stream) -> Self {
+    fn new(stream: Stream, fork_id: ForkId) -> Self {
         Self {
             stream,
             session: None,
             remote_node_id: None,
+            remote_version: 0,
+            fork_id,
             state: WaitState::Created,
         }
     }
 
-    fn ready_session(&mut self, remote_node_id: NodeId, session: Session) {
+    fn ready_session(&mut self, remote_node_id: NodeId, session: Session, remote_version: Version) {
         debug_assert_eq!(self.state, WaitState::Created);
         self.remote_node_id = Some(remote_node_id);
         self.session = Some(session);
+        self.remote_version = remote_version;
         self.state = WaitState::Received;
     }
 
@@ -217,7 +299,7 @@ impl WaitSyncConnection {
         debug_assert_eq!(self.state, WaitState::Sent);
         let session = self.session.as_ref().expect("Session must exist");
         let remote_node_id = self.remote_node_id.expect("Sync message set peer node id");
-        EstablishedConnection::new(SignedStream::new(self.stream, *session), remote_node_id)
+        EstablishedConnection::new(SignedStream::new(self.stream, *session), remote_node_id, self.remote_version)
     }
 
     fn disconnect(self) -> DisconnectingConnection {
@@ -246,7 +328,7 @@ impl WaitSyncConnection {
         }
 
         let session = self.session.as_ref().expect("Session must exist");
-        let message = Message::Handshake(HandshakeMessage::ack());
+        let message = Message::Handshake(HandshakeMessage::ack(self.fork_id));
         let signed_message = SignedMessage::new(&message, session);
 
         self.stream.write(&signed_message)?;
@@ -258,9 +340,9 @@ impl WaitSyncConnection {
         if self.state != WaitState::Created {
             return Ok(None)
         }
-        if let Some(signed_message) = self.stream.read::<SignedMessage>()? {
+        if let Some(signed_message) = self.stream.read::<SignedMessage>(MAX_HANDSHAKE_MESSAGE_SIZE)? {
             let message = {
-                let rlp = UntrustedRlp::new(&signed_message.message);
+                let rlp = UntrustedRlp::new_checked(&signed_message.message)?;
                 rlp.as_val::<Message>()?
             };
 
@@ -303,16 +385,27 @@ struct WaitAckConnection {
     port: u16,
     local_node_id: NodeId,
     remote_node_id: NodeId,
+    remote_version: Version,
+    fork_id: ForkId,
     state: WaitState,
 }
 
 impl WaitAckConnection {
-    fn new(stream: Stream, session: Session, port: u16, local_node_id: NodeId, remote_node_id: NodeId) -> Self {
+    fn new(
+        stream: Stream,
+        session: Session,
+        port: u16,
+        local_node_id: NodeId,
+        remote_node_id: NodeId,
+        fork_id: ForkId,
+    ) -> Self {
         Self {
             stream: SignedStream::new(stream, session),
             port,
             local_node_id,
             remote_node_id,
+            remote_version: 0,
+            fork_id,
             state: WaitState::Created,
         }
     }
@@ -320,7 +413,7 @@ impl WaitAckConnection {
     fn establish(self) -> EstablishedConnection {
         debug_assert_eq!(WaitState::Received, self.state);
         let remote_node_id = self.remote_node_id;
-        EstablishedConnection::new(self.stream, remote_node_id)
+        EstablishedConnection::new(self.stream, remote_node_id, self.remote_version)
     }
 
     fn disconnect(self) -> DisconnectingConnection {
@@ -344,7 +437,7 @@ impl WaitAckConnection {
             return Ok(false)
         }
 
-        self.stream.write(&Message::Handshake(HandshakeMessage::sync(self.port, self.local_node_id)))?;
+        self.stream.write(&Message::Handshake(HandshakeMessage::sync(self.port, self.local_node_id, self.fork_id)))?;
         self.state = WaitState::Sent;
         Ok(false)
     }
@@ -353,11 +446,12 @@ impl WaitAckConnection {
         if self.state != WaitState::Sent {
             return Ok(None)
         }
-        if let Some(message) = self.stream.read()? {
+        if let Some(message) = self.stream.read(MAX_HANDSHAKE_MESSAGE_SIZE)? {
             match message {
-                Message::Handshake(HandshakeMessage::Ack(version)) => {
+                Message::Handshake(HandshakeMessage::Ack(version, fork_id)) => {
+                    self.remote_version = version;
                     self.state = WaitState::Received;
-                    Ok(Some(HandshakeMessage::Ack(version)))
+                    Ok(Some(HandshakeMessage::Ack(version, fork_id)))
                 }
                 _ => Err(Error::UnreadySession),
             }
@@ -442,15 +536,16 @@ impl Connection {
         local_port: u16,
         local_node_id: NodeId,
         remote_node_id: NodeId,
+        fork_id: ForkId,
     ) -> Self {
-        let connection = WaitAckConnection::new(stream, session, local_port, local_node_id, remote_node_id);
+        let connection = WaitAckConnection::new(stream, session, local_port, local_node_id, remote_node_id, fork_id);
         Self {
             state: Mutex::new(Cell::new(State::WaitAck(connection))),
         }
     }
 
-    pub fn accept(stream: Stream) -> Self {
-        let connection = WaitSyncConnection::new(stream);
+    pub fn accept(stream: Stream, fork_id: ForkId) -> Self {
+        let connection = WaitSyncConnection::new(stream, fork_id);
         Self {
             state: Mutex::new(Cell::new(State::WaitSync(connection))),
         }
@@ -462,7 +557,9 @@ impl Connection {
             State::WaitAck(connection) => connection.stream.shutdown(),
             State::WaitSync(connection) => connection.stream.shutdown(),
             State::Established(connection) => connection.stream.shutdown(),
-            _ => unreachable!(),
+            // The connection is already being closed.
+            State::Disconnecting(_) => Ok(()),
+            State::Intermediate => unreachable!(),
         }
     }
 
@@ -602,19 +699,22 @@ impl Connection {
         }
     }
 
-    pub fn receive(&self) -> Result<Option<ReceivedMessage>> {
+    /// `max_size` limits the messages of the established connection.
+    pub fn receive(&self, max_size: usize) -> Result<Option<ReceivedMessage>> {
         let mut state = self.state.lock();
         match state.get_mut() {
             State::WaitAck(connection) => Ok(connection.receive()?.map(|message| match message {
-                HandshakeMessage::Ack(version) => ReceivedMessage::Ack {
+                HandshakeMessage::Ack(version, fork_id) => ReceivedMessage::Ack {
                     version,
+                    fork_id,
                 },
                 _ => unreachable!(),
             })),
             State::WaitSync(connection) => Ok(connection.receive()?.map(ReceivedMessage::Sync)),
-            State::Established(connection) => Ok(connection.receive()?.map(|message| match message {
+            State::Established(connection) => Ok(connection.receive(max_size)?.map(|message| match message {
                 Message::Negotiation(msg) => ReceivedMessage::Negotiation(msg),
                 Message::Extension(msg) => ReceivedMessage::Extension(msg),
+                Message::KeepAlive(msg) => ReceivedMessage::KeepAlive(msg),
                 _ => unreachable!(),
             })),
             State::Disconnecting(_) => Ok(None),
@@ -622,12 +722,12 @@ impl Connection {
         }
     }
 
-    pub fn ready_session(&self, remote_node_id: NodeId, session: Session) -> bool {
+    pub fn ready_session(&self, remote_node_id: NodeId, session: Session, remote_version: Version) -> bool {
         let mut state = self.state.lock();
         match state.get_mut() {
             State::WaitAck(_) => false,
             State::WaitSync(connection) => {
-                connection.ready_session(remote_node_id, session);
+                connection.ready_session(remote_node_id, session, remote_version);
                 true
             }
             State::Established(_) => false,
@@ -661,19 +761,83 @@ impl Connection {
         }
     }
 
-    pub fn enqueue_extension_message(&self, extension_name: &String, need_encryption: bool, data: &[u8]) -> bool {
+    pub fn enqueue_negotiation_denied(&self, seq: u64) -> bool {
+        let mut state = self.state.lock();
+        match state.get_mut() {
+            State::WaitAck(_) => false,
+            State::WaitSync(_) => false,
+            State::Established(connection) => {
+                connection.enqueue_negotiation_denied(seq);
+                true
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn enqueue_extension_message(
+        &self,
+        extension_name: &String,
+        version: Version,
+        need_encryption: bool,
+        data: &[u8],
+    ) -> bool {
         let mut state = self.state.lock();
         match state.get_mut() {
             State::WaitAck(_) => false,
             State::WaitSync(_) => false,
             State::Established(connection) => {
-                connection.enqueue_extension_message(extension_name.clone(), need_encryption, &data);
+                connection.enqueue_extension_message(extension_name.clone(), version, need_encryption, &data);
                 true
             }
             _ => unreachable!(),
         }
     }
 
+    pub fn take_dequeued_extension_messages(&self) -> Vec<String> {
+        let mut state = self.state.lock();
+        match state.get_mut() {
+            State::Established(connection) => connection.take_dequeued_extension_messages(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns how many pings in a row the node hasn't answered.
+    /// Returns `None` if the connection is not established or the node doesn't know the pings.
+    pub fn enqueue_ping(&self) -> Option<usize> {
+        let mut state = self.state.lock();
+        match state.get_mut() {
+            State::Established(connection) => connection.enqueue_ping(),
+            _ => None,
+        }
+    }
+
+    pub fn enqueue_pong(&self, seq: Seq) -> bool {
+        let mut state = self.state.lock();
+        match state.get_mut() {
+            State::Established(connection) => {
+                connection.enqueue_pong(seq);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn on_pong(&self, seq: Seq) {
+        let mut state = self.state.lock();
+        if let State::Established(connection) = state.get_mut() {
+            connection.on_pong(seq);
+        }
+    }
+
+    /// The round-trip time measured by the last answered ping.
+    pub fn rtt(&self) -> Option<Duration> {
+        let mut state = self.state.lock();
+        match state.get_mut() {
+            State::Established(connection) => connection.rtt(),
+            _ => None,
+        }
+    }
+
     pub fn remove_requested_negotiation(&self, seq: &u64) -> Option<String> {
         let mut state = self.state.lock();
         match state.get_mut() {
@@ -727,8 +891,74 @@ impl Connection {
 pub enum ReceivedMessage {
     Ack {
         version: u64,
+        fork_id: ForkId,
     },
     Sync(SignedMessage),
     Extension(ExtensionMessage),
     Negotiation(NegotiationMessage),
+    KeepAlive(KeepAliveMessage),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use mio::net::TcpStream;
+    use primitives::H256;
+
+    use super::*;
+
+    fn established(listener: &TcpListener, remote_version: Version) -> EstablishedConnection {
+        let stream = TcpStream::connect(&listener.local_addr().unwrap()).unwrap();
+        let session = Session::new_with_zero_nonce(H256::random());
+        let remote_node_id = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+        EstablishedConnection::new(SignedStream::new(stream.into(), session), remote_node_id, remote_version)
+    }
+
+    #[test]
+    fn node_of_old_version_is_not_pinged() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut connection = established(&listener, KEEP_ALIVE_VERSION - 1);
+        assert_eq!(None, connection.enqueue_ping());
+        assert!(connection.send_queue.is_empty());
+    }
+
+    #[test]
+    fn missed_pings_are_counted_until_pong() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut connection = established(&listener, KEEP_ALIVE_VERSION);
+        assert_eq!(Some(0), connection.enqueue_ping());
+        assert_eq!(Some(1), connection.enqueue_ping());
+        assert_eq!(Some(2), connection.enqueue_ping());
+
+        connection.on_pong(2);
+        assert!(connection.rtt().is_some());
+        assert_eq!(Some(0), connection.enqueue_ping());
+    }
+
+    #[test]
+    fn writable_connection_drains_the_queue() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut connection = established(&listener, KEEP_ALIVE_VERSION);
+        let _remote = listener.accept().unwrap();
+        for _ in 0..3 {
+            connection.enqueue_extension_message("a".to_string(), 0, false, &[0; 16]);
+        }
+
+        connection.send().unwrap();
+        assert!(connection.send_queue.is_empty());
+        assert_eq!(3, connection.take_dequeued_extension_messages().len());
+        assert_eq!(Ready::readable() | UnixReady::hup(), connection.interest());
+    }
+
+    #[test]
+    fn shutting_down_disconnecting_connection_is_noop() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connection = Connection {
+            state: Mutex::new(Cell::new(State::Established(established(&listener, KEEP_ALIVE_VERSION)))),
+        };
+        connection.set_disconnecting();
+        assert!(connection.is_disconnecting());
+        assert!(connection.shutdown().is_ok());
+    }
 }
@@ -16,6 +16,7 @@
 
 use std::collections::HashMap;
 use std::io;
+use std::time::Duration;
 
 use cio::{IoManager, StreamToken};
 use mio::deprecated::EventLoop;
@@ -24,13 +25,16 @@ use parking_lot::RwLock;
 
 use super::super::node_id::IntoSocketAddr;
 use super::super::session::Session;
-use super::super::{FiltersControl, NodeId, SocketAddr};
+use super::super::{ChainIdentity, NodeId, SocketAddr};
 use super::connection::{Connection, Result};
+use super::message::{Seq, Version};
 use super::stream::Stream;
 
 pub use super::connection::{ConnectionType, ReceivedMessage};
 
 pub struct Connections {
+    chain: ChainIdentity,
+
     // stream token => established connection
     connections: RwLock<HashMap<StreamToken, Connection>>,
 
@@ -39,8 +43,10 @@ pub struct Connections {
 }
 
 impl Connections {
-    pub fn new() -> Self {
+    pub fn new(chain: ChainIdentity) -> Self {
         Self {
+            chain,
+
             connections: RwLock::new(HashMap::new()),
 
             connected_nodes: RwLock::new(HashMap::new()),
@@ -50,7 +56,7 @@ impl Connections {
 
     pub fn accept(&self, token: StreamToken, stream: Stream) {
         let mut connections = self.connections.write();
-        let t = connections.insert(token, Connection::accept(stream));
+        let t = connections.insert(token, Connection::accept(stream, self.chain.fork_id()));
         debug_assert!(t.is_none());
     }
 
@@ -73,7 +79,8 @@ impl Connections {
             return false
         }
 
-        let connection = Connection::connect(stream, session, local_port, local_node_id, remote_node_id);
+        let fork_id = self.chain.fork_id();
+        let connection = Connection::connect(stream, session, local_port, local_node_id, remote_node_id, fork_id);
         let t = connections.insert(token, connection);
         debug_assert!(t.is_none());
         let t = connected_nodes.insert(remote_node_id, token);
@@ -99,6 +106,18 @@ impl Connections {
         connection.shutdown()
     }
 
+    // Shutdown connection will cause hup event on stream
+    pub fn shutdown_token(&self, token: &StreamToken) -> io::Result<()> {
+        let connections = self.connections.read();
+        match connections.get(token) {
+            Some(connection) => connection.shutdown(),
+            None => {
+                cwarn!(NETWORK, "The connection to disconnect is not exist");
+                Ok(())
+            }
+        }
+    }
+
     pub fn set_disconnecting(&self, token: &StreamToken) {
         let connections = self.connections.read();
         let connection = connections.get(token).unwrap();
@@ -233,11 +252,11 @@ impl Connections {
         }
     }
 
-    pub fn receive(&self, token: &StreamToken) -> Result<Option<ReceivedMessage>> {
+    pub fn receive(&self, token: &StreamToken, max_size: usize) -> Result<Option<ReceivedMessage>> {
         let connections = self.connections.read();
 
         if let Some(connection) = connections.get(token) {
-            Ok(connection.receive()?)
+            Ok(connection.receive(max_size)?)
         } else {
             Ok(None)
         }
@@ -261,21 +280,68 @@ impl Connections {
         }
     }
 
+    pub fn enqueue_negotiation_denied(&self, token: &StreamToken, seq: u64) -> bool {
+        let connections = self.connections.read();
+        if let Some(connection) = connections.get(token) {
+            connection.enqueue_negotiation_denied(seq)
+        } else {
+            false
+        }
+    }
+
     pub fn enqueue_extension_message(
         &self,
         token: &StreamToken,
         extension_name: &String,
+        version: u64,
         need_encryption: bool,
         data: &[u8],
     ) -> bool {
         let connections = self.connections.read();
         if let Some(connection) = connections.get(token) {
-            connection.enqueue_extension_message(extension_name, need_encryption, &data)
+            connection.enqueue_extension_message(extension_name, version, need_encryption, &data)
         } else {
             false
         }
     }
 
+    pub fn take_dequeued_extension_messages(&self, token: &StreamToken) -> Vec<String> {
+        let connections = self.connections.read();
+        connections.get(token).map(|connection| connection.take_dequeued_extension_messages()).unwrap_or_default()
+    }
+
+    /// Pings the established connections, returning how many pings in a row each node hasn't answered.
+    pub fn ping_established(&self) -> Vec<(StreamToken, usize)> {
+        let connections = self.connections.read();
+        connections
+            .iter()
+            .filter_map(|(token, connection)| connection.enqueue_ping().map(|missed_pings| (*token, missed_pings)))
+            .collect()
+    }
+
+    pub fn enqueue_pong(&self, token: &StreamToken, seq: Seq) -> bool {
+        let connections = self.connections.read();
+        connections.get(token).map_or(false, |connection| connection.enqueue_pong(seq))
+    }
+
+    pub fn on_pong(&self, token: &StreamToken, seq: Seq) {
+        let connections = self.connections.read();
+        if let Some(connection) = connections.get(token) {
+            connection.on_pong(seq);
+        }
+    }
+
+    pub fn established_rtts(&self) -> HashMap<SocketAddr, Duration> {
+        let connections = self.connections.read();
+        connections
+            .values()
+            .filter_map(|con| {
+                let rtt = con.rtt()?;
+                Some((con.remote_node_id()?.into_addr(), rtt))
+            })
+            .collect()
+    }
+
     pub fn remove_requested_negotiation(&self, token: &StreamToken, seq: &u64) -> Option<String> {
         let connections = self.connections.read();
         connections.get(token).and_then(|connection| connection.remove_requested_negotiation(seq))
@@ -286,9 +352,18 @@ impl Connections {
         connections.get(token).and_then(|connection| connection.remote_addr_of_waiting_sync())
     }
 
-    pub fn ready_session(&self, token: &StreamToken, remote_node_id: NodeId, session: Session) -> bool {
+    pub fn ready_session(
+        &self,
+        token: &StreamToken,
+        remote_node_id: NodeId,
+        session: Session,
+        remote_version: Version,
+    ) -> bool {
         let connections = self.connections.read();
-        connections.get(token).map(|connection| connection.ready_session(remote_node_id, session)).is_some()
+        connections
+            .get(token)
+            .map(|connection| connection.ready_session(remote_node_id, session, remote_version))
+            .is_some()
     }
 
     pub fn stream_token(&self, node: &NodeId) -> Option<StreamToken> {
@@ -325,12 +400,10 @@ impl Connections {
             .collect()
     }
 
-    pub fn get_filtered_address(&self, filters: &FiltersControl) -> Vec<SocketAddr> {
+    pub fn get_filtered_address<F>(&self, is_allowed: F) -> Vec<SocketAddr>
+    where
+        F: Fn(&SocketAddr) -> bool, {
         let connected_nodes = self.connected_nodes.read();
-        connected_nodes
-            .keys()
-            .map(|node_id| node_id.into_addr())
-            .filter(|addr| !filters.is_allowed(&addr.ip()))
-            .collect()
+        connected_nodes.keys().map(|node_id| node_id.into_addr()).filter(|addr| !is_allowed(addr)).collect()
     }
 }
@@ -14,8 +14,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::net::{self, IpAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use ccrypto::aes::SymmetricCipherError;
 use cfinally::finally;
@@ -29,12 +33,19 @@ use rlp::UntrustedRlp;
 
 use super::super::addr::convert_to_node_id;
 use super::super::client::Client;
-use super::super::{FiltersControl, IntoSocketAddr, NodeId, RoutingTable, SocketAddr};
+use super::super::{
+    ChainIdentity, FiltersControl, ForkId, IntoSocketAddr, NodeAllowList, NodeId, RoutingTable, SocketAddr,
+};
+use super::connection::Error as ConnectionError;
 use super::connections::{ConnectionType, Connections, ReceivedMessage};
 use super::listener::Listener;
 use super::message::{HandshakeMessage, Message as NetworkMessage, Version};
-use super::stream::Stream;
-use super::NegotiationBody;
+use super::prober::Prober;
+use super::rate_limiter::RateLimiter;
+use super::slots::{Admission, Slots};
+use super::socks5;
+use super::stream::{Error as StreamError, Stream};
+use super::{KeepAliveMessage, NegotiationBody, SignedMessage};
 
 pub const MAX_CONNECTIONS: usize = 200;
 
@@ -45,6 +56,20 @@ const LAST_CONNECTION_TOKEN: TimerToken = FIRST_CONNECTION_TOKEN + MAX_CONNECTIO
 
 const CREATE_CONNECTIONS_TOKEN: TimerToken = 0;
 const PULL_CONNECTIONS_MS: u64 = 10 * 1000;
+const PING_TOKEN: TimerToken = CREATE_CONNECTIONS_TOKEN + 1;
+const PING_INTERVAL_MS: u64 = 15 * 1000;
+
+/// A peer is considered dead and disconnected once it misses this many pings in a row.
+/// It catches the connections which are broken silently, without closing the socket.
+const MAX_MISSED_PINGS: usize = 3;
+
+/// Room for the signature, the extension name and the version around the payload of an extension message.
+const MAX_FRAME_OVERHEAD: usize = 1024;
+/// The payload of an encrypted message grows by the padding of the cipher.
+const MAX_ENCRYPTION_PADDING: usize = 16;
+
+/// The proxy must connect to the peer within this time.
+const PROXY_CONNECT_TIMEOUT_SECS: u64 = 5;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum IgnoreConnectionLimit {
@@ -62,11 +87,16 @@ pub enum Message {
     SendExtensionMessage {
         node_id: NodeId,
         extension_name: String,
+        version: Version,
         need_encryption: bool,
         data: Vec<u8>,
     },
     Disconnect(SocketAddr),
     ApplyFilters,
+    /// Starts pulling the connections again, after the minimum number of peers is raised.
+    PullConnections,
+    /// The proxy has connected to the peer. The stream is kept in the proxied streams.
+    ProxyConnected(SocketAddr),
 }
 
 #[derive(Debug)]
@@ -75,6 +105,9 @@ enum Error {
     InvalidNode(NodeId),
     InvalidSign,
     UnexpectedNodeId(Mismatch<NodeId>),
+    NotAllowedNode(NodeId),
+    ChainMismatch(Mismatch<ForkId>),
+    TooLargeMessage(usize),
     SymmetricCipherError(SymmetricCipherError),
     General(&'static str),
 }
@@ -86,6 +119,9 @@ impl ::std::fmt::Display for Error {
             Error::InvalidNode(_) => ::std::fmt::Debug::fmt(self, f),
             Error::InvalidSign => ::std::fmt::Debug::fmt(&self, f),
             Error::UnexpectedNodeId(_) => ::std::fmt::Debug::fmt(&self, f),
+            Error::NotAllowedNode(_) => ::std::fmt::Debug::fmt(&self, f),
+            Error::ChainMismatch(_) => ::std::fmt::Debug::fmt(&self, f),
+            Error::TooLargeMessage(_) => ::std::fmt::Debug::fmt(&self, f),
             Error::SymmetricCipherError(err) => ::std::fmt::Debug::fmt(&err, f),
             Error::General(_) => ::std::fmt::Debug::fmt(self, f),
         }
@@ -101,12 +137,26 @@ pub struct Handler {
 
     routing_table: Arc<RoutingTable>,
     filters: Arc<FiltersControl>,
+    /// Only the nodes in the list can connect if it exists.
+    allow_list: Option<Arc<NodeAllowList>>,
+    chain: ChainIdentity,
     connections: Connections,
+    slots: Mutex<Slots>,
+    rate_limiter: Mutex<RateLimiter>,
 
     client: Arc<Client>,
 
-    min_peers: usize,
-    max_peers: usize,
+    min_peers: AtomicUsize,
+
+    /// The outbound connections are dialed through this SOCKS5 proxy if it exists.
+    proxy: Option<SocketAddr>,
+    /// The streams which the proxy has connected, until the IO thread takes them.
+    /// The proxy is dialed on another thread not to block the IO thread.
+    proxied_streams: Arc<Mutex<HashMap<SocketAddr, net::TcpStream>>>,
+    /// Refuses the inbound connections to keep the local address unknown.
+    hide_address: bool,
+    /// Dials the addresses advertised by the inbound peers to verify that they accept connections.
+    prober: Option<Prober>,
 }
 
 impl Handler {
@@ -115,30 +165,69 @@ impl Handler {
         client: Arc<Client>,
         routing_table: Arc<RoutingTable>,
         filters: Arc<FiltersControl>,
+        allow_list: Option<Arc<NodeAllowList>>,
+        chain: ChainIdentity,
         min_peers: usize,
-        max_peers: usize,
+        max_inbound_peers: usize,
+        max_outbound_peers: usize,
+        trusted_nodes: Vec<IpAddr>,
+        proxy: Option<SocketAddr>,
+        hide_address: bool,
+        dual_stack: bool,
+        probe_inbound: bool,
     ) -> ::std::result::Result<Self, String> {
-        if MAX_CONNECTIONS < max_peers {
-            return Err(format!("Max peers must be less than {}", MAX_CONNECTIONS))
-        }
-        debug_assert!(max_peers < MAX_CONNECTIONS);
+        check_peer_limits(max_inbound_peers, max_outbound_peers)?;
+        // The peers behind the proxy cannot be dialed directly.
+        let prober = if probe_inbound && proxy.is_none() {
+            Some(Prober::new(Arc::clone(&routing_table)))
+        } else {
+            None
+        };
         Ok(Self {
             socket_address,
-            listener: Listener::bind(&socket_address).expect("Cannot listen TCP port"),
+            listener: Listener::bind(&socket_address, dual_stack).expect("Cannot listen TCP port"),
 
             tokens: Mutex::new(TokenGenerator::new(FIRST_CONNECTION_TOKEN, LAST_CONNECTION_TOKEN)),
 
             routing_table,
             filters,
-            connections: Connections::new(),
+            allow_list,
+            connections: Connections::new(chain.clone()),
+            chain,
+            slots: Mutex::new(Slots::new(max_inbound_peers, max_outbound_peers, trusted_nodes)),
+            rate_limiter: Mutex::new(RateLimiter::default()),
 
             client,
 
-            min_peers,
-            max_peers,
+            min_peers: AtomicUsize::new(min_peers),
+
+            proxy,
+            proxied_streams: Default::default(),
+            hide_address,
+            prober,
         })
     }
 
+    /// Changes the peer limits. The peers beyond the new limits are not disconnected.
+    pub fn set_peer_limits(
+        &self,
+        min_peers: usize,
+        max_inbound_peers: usize,
+        max_outbound_peers: usize,
+    ) -> ::std::result::Result<(), String> {
+        check_peer_limits(max_inbound_peers, max_outbound_peers)?;
+        self.slots.lock().set_limits(max_inbound_peers, max_outbound_peers);
+        self.min_peers.store(min_peers, Ordering::SeqCst);
+        cinfo!(
+            NETWORK,
+            "Peer limits are changed: min {}, max inbound {}, max outbound {}",
+            min_peers,
+            max_inbound_peers,
+            max_outbound_peers
+        );
+        Ok(())
+    }
+
     pub fn get_port(&self) -> u16 {
         self.socket_address.port()
     }
@@ -151,13 +240,81 @@ impl Handler {
         self.connections.established_peers()
     }
 
+    /// The round-trip times of the established peers which answered a ping.
+    pub fn peer_rtts(&self) -> HashMap<SocketAddr, Duration> {
+        self.connections.established_rtts()
+    }
+
+    fn is_allowed_node(&self, node_id: &NodeId) -> bool {
+        self.filters.is_allowed_node(&node_id.into_addr())
+            && self.allow_list.as_ref().map_or(true, |allow_list| allow_list.is_allowed(node_id))
+    }
+
+    /// Disconnects a node that follows another network, genesis or fork.
+    /// It's not banned, since it may be upgraded to follow the same fork later.
+    fn reject_other_chain(&self, stream: &StreamToken, node_id: &NodeId, fork_id: ForkId) -> IoHandlerResult<Error> {
+        cinfo!(NETWORK, "{} is disconnected: It follows another chain {:?}", node_id, fork_id);
+        self.connections.shutdown_token(stream)?;
+        Ok(Error::ChainMismatch(Mismatch {
+            expected: self.chain.fork_id(),
+            found: fork_id,
+        }))
+    }
+
+    /// A node behind a proxy connects from the address of the proxy.
+    /// It's accepted only if it proves that it has the session initiated with the address it claims.
+    fn is_proxied(&self, node_id: &NodeId, port: u16, signed_message: &SignedMessage) -> bool {
+        let claimed_addr = node_id.into_addr();
+        if claimed_addr.port() != port {
+            return false
+        }
+        self.routing_table
+            .unestablished_session(&claimed_addr)
+            .map_or(false, |session| signed_message.is_valid(&session))
+    }
+
+    fn reject_too_large_message(&self, stream: &StreamToken, size: usize) -> IoHandlerResult<Error> {
+        match self.connections.node_id(stream) {
+            Some(node_id) => {
+                cinfo!(NETWORK, "{} is disconnected and banned: It sent a message of {} bytes", node_id, size);
+                self.routing_table.ban(&node_id.into_addr());
+            }
+            None => cinfo!(NETWORK, "Connection {} is closed: It sent a message of {} bytes", stream, size),
+        }
+        self.connections.shutdown_token(stream)?;
+        Ok(Error::TooLargeMessage(size))
+    }
+
     fn accept(&self) -> IoHandlerResult<Option<(StreamToken, SocketAddr)>> {
         match self.listener.accept()? {
             Some((stream, socket_address)) => {
                 let ip = socket_address.ip();
+                if self.hide_address {
+                    cinfo!(NETWORK, "P2P connection request from {} is refused to hide the local address", ip);
+                    return Ok(None)
+                }
                 if self.filters.is_allowed(&ip) {
+                    let mut slots = self.slots.lock();
+                    match slots.admit_inbound(&ip, |token| self.connections.is_established(token)) {
+                        Admission::Accept => {}
+                        Admission::Evict(evicted) => {
+                            cinfo!(NETWORK, "Connection {} is closed to make room for trusted node {}", evicted, ip);
+                            self.connections.shutdown_token(&evicted)?;
+                            slots.release(&evicted);
+                        }
+                        Admission::Reject => {
+                            cinfo!(
+                                NETWORK,
+                                "P2P connection request from {} is received. But all {} inbound slots are taken",
+                                ip,
+                                slots.inbound_count()
+                            );
+                            return Ok(None)
+                        }
+                    }
                     let token = self.tokens.lock().gen().ok_or(Error::General("TooManyConnections"))?;
                     self.connections.accept(token, stream);
+                    slots.add_inbound(token, ip);
                     Ok(Some((token, socket_address)))
                 } else {
                     cinfo!(NETWORK, "P2P connection request from {} is received. But it's not allowed", ip);
@@ -168,37 +325,80 @@ impl Handler {
         }
     }
 
-    fn connect(&self, socket_address: &SocketAddr) -> IoHandlerResult<Option<StreamToken>> {
+    fn can_connect(&self, socket_address: &SocketAddr) -> bool {
         let ip = socket_address.ip();
         if !self.filters.is_allowed(&ip) {
             cinfo!(NETWORK, "P2P connection from {} is received. But it's not allowed", ip);
+            return false
+        }
+        if !self.is_allowed_node(&socket_address.into()) {
+            cinfo!(NETWORK, "{} is not in the node allow-list", socket_address);
+            return false
+        }
+        true
+    }
+
+    fn connect(&self, socket_address: &SocketAddr) -> IoHandlerResult<Option<StreamToken>> {
+        if !self.can_connect(socket_address) {
             return Ok(None)
         }
+        match Stream::connect(socket_address)? {
+            Some(stream) => self.add_outbound(stream, socket_address),
+            None => Ok(None),
+        }
+    }
 
-        Ok(match Stream::connect(socket_address)? {
-            Some(stream) => {
-                let remote_node_id = socket_address.into();
-
-                let local_node_id =
-                    self.routing_table.local_node_id(&remote_node_id).ok_or(Error::General("Not handshaked"))?;
-                let session = self
-                    .routing_table
-                    .unestablished_session(&socket_address)
-                    .ok_or(Error::General("Session doesn't exist"))?;
-
-                let mut tokens = self.tokens.lock();
-                let token = tokens.gen().ok_or(Error::General("TooManyConnections"))?;
-                if self.connections.connect(token, stream, local_node_id, session, socket_address, self.get_port()) {
-                    self.routing_table.establish(socket_address);
-                    Some(token)
-                } else {
-                    cwarn!(NETWORK, "Cannot create connection to {}", socket_address);
-                    tokens.restore(token);
-                    None
+    /// Dials the peer through the proxy on another thread.
+    /// The IO thread is notified by `Message::ProxyConnected` when the proxy has connected to the peer.
+    fn connect_through_proxy(&self, proxy: SocketAddr, socket_address: SocketAddr, io: &IoContext<Message>) {
+        if !self.can_connect(&socket_address) {
+            return
+        }
+        let proxied_streams = Arc::clone(&self.proxied_streams);
+        let channel = io.channel();
+        let dial = move || {
+            let timeout = Duration::from_secs(PROXY_CONNECT_TIMEOUT_SECS);
+            let stream = match socks5::connect(&proxy.into(), &socket_address.into(), timeout) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    cinfo!(NETWORK, "Cannot connect to {} through the proxy: {}", socket_address, err);
+                    return
                 }
+            };
+            proxied_streams.lock().insert(socket_address, stream);
+            if let Err(err) = channel.send(Message::ProxyConnected(socket_address)) {
+                cwarn!(NETWORK, "Cannot notify the connection to {}: {:?}", socket_address, err);
+                proxied_streams.lock().remove(&socket_address);
             }
-            None => None,
-        })
+        };
+        if let Err(err) = thread::Builder::new().name("socks5".to_string()).spawn(dial) {
+            cwarn!(NETWORK, "Cannot dial {} through the proxy: {:?}", socket_address, err);
+        }
+    }
+
+    fn add_outbound(&self, stream: Stream, socket_address: &SocketAddr) -> IoHandlerResult<Option<StreamToken>> {
+        let ip = socket_address.ip();
+        let remote_node_id = socket_address.into();
+
+        let local_node_id = self.routing_table.local_node_id(&remote_node_id).ok_or(Error::General("Not handshaked"))?;
+        let session = self
+            .routing_table
+            .unestablished_session(&socket_address)
+            .ok_or(Error::General("Session doesn't exist"))?;
+
+        let mut tokens = self.tokens.lock();
+        let token = tokens.gen().ok_or(Error::General("TooManyConnections"))?;
+        if self.connections.connect(token, stream, local_node_id, session, socket_address, self.get_port()) {
+            self.slots.lock().add_outbound(token, ip);
+            self.routing_table.establish(socket_address);
+            // This node has just dialed it.
+            self.routing_table.verify(socket_address);
+            Ok(Some(token))
+        } else {
+            cwarn!(NETWORK, "Cannot create connection to {}", socket_address);
+            tokens.restore(token);
+            Ok(None)
+        }
     }
 
     fn register_stream(
@@ -228,16 +428,31 @@ impl Handler {
     ) -> IoHandlerResult<()> {
         self.connections.deregister(&token, event_loop)?;
         self.connections.remove(&token);
+        self.slots.lock().release(&token);
+        if let Some(traffic) = self.rate_limiter.lock().remove(&token) {
+            ctrace!(NETWORK, "Traffic from {}: {:?}", token, traffic);
+        }
         Ok(())
     }
 
     // Return false if there is no message
     fn receive(&self, stream: &StreamToken, client: &Client, io: &IoContext<Message>) -> IoHandlerResult<bool> {
-        Ok(match self.connections.receive(stream)? {
+        let received = match self.connections.receive(stream, client.max_message_size() + MAX_FRAME_OVERHEAD) {
+            Err(ConnectionError::StreamError(StreamError::TooLargeMessage(size))) => {
+                return Err(self.reject_too_large_message(stream, size)?.into())
+            }
+            received => received?,
+        };
+        Ok(match received {
             None => false,
             Some(ReceivedMessage::Ack {
+                fork_id,
                 ..
             }) => {
+                if !self.chain.is_compatible(&fork_id) {
+                    let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
+                    return Err(self.reject_other_chain(stream, &node_id, fork_id)?.into())
+                }
                 if !self.connections.establish_wait_ack_connection(stream) {
                     return Err(Error::InvalidStream(*stream).into())
                 }
@@ -248,29 +463,42 @@ impl Handler {
                 true
             }
             Some(ReceivedMessage::Sync(signed_message)) => {
-                let rlp = UntrustedRlp::new(&signed_message.message);
+                let rlp = UntrustedRlp::new_checked(&signed_message.message)?;
                 let message = rlp.as_val::<NetworkMessage>()?;
 
                 match message {
                     NetworkMessage::Handshake(HandshakeMessage::Sync {
+                        version,
                         port,
                         node_id,
-                        ..
+                        fork_id,
                     }) => {
                         let remote_addr = self
                             .connections
                             .remote_addr_of_waiting_sync(stream)
                             .ok_or(Error::General("Cannot find remote address"))?;
-                        let remote_node_id = convert_to_node_id(remote_addr.ip(), port);
-
-                        if remote_node_id != node_id {
+                        let expected_node_id = convert_to_node_id(remote_addr.ip(), port);
+                        let remote_node_id = if expected_node_id == node_id {
+                            node_id
+                        } else if self.is_proxied(&node_id, port, &signed_message) {
+                            cdebug!(NETWORK, "{} connects through a proxy at {}", node_id, remote_addr);
+                            node_id
+                        } else {
                             return Err(Error::UnexpectedNodeId(Mismatch {
-                                expected: remote_node_id,
+                                expected: expected_node_id,
                                 found: node_id,
                             }).into())
+                        };
+                        if !self.is_allowed_node(&remote_node_id) {
+                            cinfo!(NETWORK, "{} is disconnected: Not in the node allow-list", remote_node_id);
+                            self.connections.shutdown_token(stream)?;
+                            return Err(Error::NotAllowedNode(remote_node_id).into())
+                        }
+                        if !self.chain.is_compatible(&fork_id) {
+                            return Err(self.reject_other_chain(stream, &remote_node_id, fork_id)?.into())
                         }
 
-                        let remote_addr = SocketAddr::new(remote_addr.ip(), port);
+                        let remote_addr = remote_node_id.into_addr();
                         let session = self
                             .routing_table
                             .unestablished_session(&remote_addr)
@@ -280,49 +508,107 @@ impl Handler {
                         }
 
                         self.routing_table.establish(&remote_addr);
-                        self.connections.ready_session(stream, remote_node_id, session);
+                        if let Some(prober) = &self.prober {
+                            if !self.routing_table.is_verified(&remote_addr) {
+                                prober.probe(remote_addr);
+                            }
+                        }
+                        self.connections.ready_session(stream, remote_node_id, session, version);
                         true
                     }
                     _ => unreachable!(),
                 }
             }
             Some(ReceivedMessage::Extension(msg)) => {
+                if let Some(max_size) = client.max_message_size_of(msg.extension_name()) {
+                    if msg.data().len() > max_size + MAX_ENCRYPTION_PADDING {
+                        return Err(self.reject_too_large_message(stream, msg.data().len())?.into())
+                    }
+                }
                 let session = self.connections.established_session(stream).ok_or(Error::General("Invalid stream"))?;
                 // FIXME: check version of extension
                 let message = msg.unencrypted_data(&session).map_err(Error::from)?;
                 let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
+                let mut rate_limiter = self.rate_limiter.lock();
+                if !rate_limiter.allow(*stream, msg.extension_name(), message.len(), Instant::now()) {
+                    if !rate_limiter.is_flooding(stream) {
+                        ctrace!(NETWORK, "Rate limit drops a {} message from {}", msg.extension_name(), node_id);
+                        return Ok(true)
+                    }
+                    cwarn!(NETWORK, "{} is disconnected and banned because it floods messages", node_id);
+                    self.routing_table.ban(&node_id.into_addr());
+                    self.connections.shutdown_token(stream)?;
+                    return Ok(false)
+                }
+                drop(rate_limiter);
                 client.on_message(msg.extension_name(), &node_id, &message);
                 true
             }
+            Some(ReceivedMessage::KeepAlive(KeepAliveMessage::Ping(_, seq))) => {
+                // The pong is written when the registration is updated after reading
+                if !self.connections.enqueue_pong(stream, seq) {
+                    return Err(Error::InvalidStream(*stream).into())
+                }
+                true
+            }
+            Some(ReceivedMessage::KeepAlive(KeepAliveMessage::Pong(_, seq))) => {
+                self.connections.on_pong(stream, seq);
+                true
+            }
             Some(ReceivedMessage::Negotiation(msg)) => {
                 match msg.body() {
                     NegotiationBody::Request {
                         ref extension_name,
-                        ..
+                        ref extension_versions,
                     } => {
                         let seq = msg.seq();
-                        // FIXME: version negotiation
-                        const VERSION: Version = 0;
-                        if self.connections.enqueue_negotiation_allowed(stream, seq, VERSION) {
-                            let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
-                            client.on_node_added(&extension_name, &node_id, VERSION);
-                        } else {
-                            cwarn!(NETWORK, "Cannot enqueue negotiation message for {}", stream);
+                        let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
+                        match client.select_version(extension_name, &node_id, extension_versions) {
+                            Some(version) => {
+                                if self.connections.enqueue_negotiation_allowed(stream, seq, version) {
+                                    client.on_node_added(&extension_name, &node_id, version);
+                                } else {
+                                    cwarn!(NETWORK, "Cannot enqueue negotiation message for {}", stream);
+                                }
+                            }
+                            None => {
+                                cdebug!(
+                                    NETWORK,
+                                    "`{}` refuses {}, which supports the versions {:?}",
+                                    extension_name,
+                                    node_id,
+                                    extension_versions
+                                );
+                                if !self.connections.enqueue_negotiation_denied(stream, seq) {
+                                    cwarn!(NETWORK, "Cannot enqueue negotiation message for {}", stream);
+                                }
+                            }
                         }
                     }
                     NegotiationBody::Allowed(extension_version) => {
                         let seq = msg.seq();
                         if let Some(name) = self.connections.remove_requested_negotiation(stream, &seq) {
                             let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
-                            client.on_node_added(&name, &node_id, *extension_version);
+                            if client.select_version(&name, &node_id, &[*extension_version]).is_some() {
+                                client.on_node_added(&name, &node_id, *extension_version);
+                            } else {
+                                cdebug!(
+                                    NETWORK,
+                                    "`{}` refuses {}, which allowed the version {}",
+                                    name,
+                                    node_id,
+                                    extension_version
+                                );
+                            }
                         } else {
                             ctrace!(NETWORK, "Negotiation::Allowed message received from non requested seq");
                         }
                     }
                     NegotiationBody::Denied => {
                         let seq = msg.seq();
-                        if let Some(_) = self.connections.remove_requested_negotiation(stream, &seq) {
-                            self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
+                        if let Some(name) = self.connections.remove_requested_negotiation(stream, &seq) {
+                            let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
+                            cdebug!(NETWORK, "{} refuses `{}`", node_id, name);
                         } else {
                             ctrace!(NETWORK, "Negotiation::Denied message received from non requested seq");
                         }
@@ -351,18 +637,35 @@ impl Handler {
                 }
                 Ok(())
             }
-            ConnectionType::Established => Ok(()),
+            ConnectionType::Established => {
+                let dequeued = self.connections.take_dequeued_extension_messages(stream);
+                if !dequeued.is_empty() {
+                    let node_id = self.connections.node_id(&stream).ok_or(Error::InvalidStream(*stream))?;
+                    for extension_name in dequeued {
+                        self.client.on_message_dequeued(&extension_name, &node_id);
+                    }
+                }
+                Ok(())
+            }
             ConnectionType::Disconnecting => Err(Error::InvalidStream(*stream).into()),
         }
     }
 }
 
+pub fn check_peer_limits(max_inbound_peers: usize, max_outbound_peers: usize) -> ::std::result::Result<(), String> {
+    if MAX_CONNECTIONS < max_inbound_peers + max_outbound_peers {
+        return Err(format!("The sum of max inbound and outbound peers must be less than {}", MAX_CONNECTIONS))
+    }
+    Ok(())
+}
+
 
 impl IoHandler<Message> for Handler {
     fn initialize(&self, io: &IoContext<Message>) -> IoHandlerResult<()> {
         io.register_stream(ACCEPT_TOKEN)?;
         io.register_timer_once(CREATE_CONNECTIONS_TOKEN, PULL_CONNECTIONS_MS)
             .expect("Pull connections must be registered");
+        io.register_timer(PING_TOKEN, PING_INTERVAL_MS).expect("Ping timer must be registered");
         Ok(())
     }
 
@@ -377,9 +680,10 @@ impl IoHandler<Message> for Handler {
                     }
                 });
                 let number_of_connections = self.connections.len();
-                if number_of_connections < self.min_peers {
+                let min_peers = self.min_peers.load(Ordering::SeqCst);
+                if number_of_connections < min_peers {
                     register_new_timer.store(true, Ordering::SeqCst);
-                    let count = (self.min_peers - number_of_connections + 1) / 2;
+                    let count = (min_peers - number_of_connections + 1) / 2;
                     let addresses = self.routing_table.unestablished_addresses(count);
                     for address in addresses {
                         io.message(Message::RequestConnection(address, IgnoreConnectionLimit::Not))?;
@@ -387,6 +691,29 @@ impl IoHandler<Message> for Handler {
                 }
                 Ok(())
             }
+            PING_TOKEN => {
+                for (stream, missed_pings) in self.connections.ping_established() {
+                    if missed_pings >= MAX_MISSED_PINGS {
+                        match self.connections.node_id(&stream) {
+                            Some(node_id) => {
+                                cinfo!(NETWORK, "{} is disconnected: It missed {} pings", node_id, missed_pings)
+                            }
+                            None => {
+                                cinfo!(NETWORK, "Connection {} is closed: It missed {} pings", stream, missed_pings)
+                            }
+                        }
+                        // The hup event removes the node from the extensions
+                        if let Err(err) = self.connections.shutdown_token(&stream) {
+                            cwarn!(NETWORK, "Cannot close the connection {}: {:?}", stream, err);
+                        }
+                        continue
+                    }
+                    if let Err(err) = io.update_registration(stream) {
+                        cwarn!(NETWORK, "Cannot update registration to ping {}: {:?}", stream, err);
+                    }
+                }
+                Ok(())
+            }
             _ => unreachable!(),
         }
     }
@@ -395,13 +722,19 @@ impl IoHandler<Message> for Handler {
         match message {
             Message::RequestConnection(socket_address, ignore_connection_limit) => {
                 if ignore_connection_limit == &IgnoreConnectionLimit::Not {
-                    let number_of_connections = self.connections.len();
-                    if self.max_peers <= number_of_connections {
-                        ctrace!(NETWORK, "Already has maximum peers({})", number_of_connections);
+                    let slots = self.slots.lock();
+                    if !slots.has_outbound_slot(&socket_address.ip()) {
+                        ctrace!(NETWORK, "Already has maximum outbound peers({})", slots.outbound_count());
                         return Ok(())
                     }
                 }
 
+                if let Some(proxy) = self.proxy {
+                    ctrace!(NETWORK, "Connecting to {} through the proxy", socket_address);
+                    self.connect_through_proxy(proxy, *socket_address, io);
+                    return Ok(())
+                }
+
                 ctrace!(NETWORK, "Connecting to {}", socket_address);
                 let token = self.connect(&socket_address)?.ok_or(Error::General("Cannot create connection"))?;
                 cinfo!(NETWORK, "New connection to {}({})", socket_address, token);
@@ -424,11 +757,23 @@ impl IoHandler<Message> for Handler {
             Message::SendExtensionMessage {
                 node_id,
                 extension_name,
+                version,
                 need_encryption,
                 data,
             } => {
-                let token = self.connections.stream_token(node_id).ok_or(Error::InvalidNode(*node_id))?;
-                if !self.connections.enqueue_extension_message(&token, extension_name, *need_encryption, data) {
+                let token = match self.connections.stream_token(node_id) {
+                    Some(token) => token,
+                    None => {
+                        self.client.on_message_undelivered(extension_name, node_id, data);
+                        self.client.on_message_dequeued(extension_name, node_id);
+                        return Err(Error::InvalidNode(*node_id).into())
+                    }
+                };
+                if !self
+                    .connections
+                    .enqueue_extension_message(&token, extension_name, *version, *need_encryption, data)
+                {
+                    self.client.on_message_dequeued(extension_name, node_id);
                     return Err(Error::InvalidStream(token).into())
                 }
                 io.update_registration(token)?;
@@ -440,7 +785,9 @@ impl IoHandler<Message> for Handler {
                 Ok(())
             }
             Message::ApplyFilters => {
-                let addresses = self.connections.get_filtered_address(&*self.filters);
+                let addresses = self.connections.get_filtered_address(|address| {
+                    self.filters.is_allowed(&address.ip()) && self.is_allowed_node(&address.into())
+                });
                 cinfo!(NETWORK, "Connections to the following addresses will be closed: {:?}", addresses);
                 for address in addresses.iter() {
                     let _ = self.connections.shutdown(address).map_err(|err| {
@@ -449,6 +796,23 @@ impl IoHandler<Message> for Handler {
                 }
                 Ok(())
             }
+            Message::PullConnections => {
+                io.register_timer_once(CREATE_CONNECTIONS_TOKEN, 0)?;
+                Ok(())
+            }
+            Message::ProxyConnected(socket_address) => {
+                let stream = self
+                    .proxied_streams
+                    .lock()
+                    .remove(socket_address)
+                    .ok_or(Error::General("Cannot find the proxied stream"))?;
+                let stream = Stream::from_proxied(stream)?;
+                let token =
+                    self.add_outbound(stream, socket_address)?.ok_or(Error::General("Cannot create connection"))?;
+                cinfo!(NETWORK, "New connection to {}({}) through the proxy", socket_address, token);
+                io.register_stream(token)?;
+                Ok(())
+            }
         }
     }
 
@@ -468,7 +832,7 @@ impl IoHandler<Message> for Handler {
                             .expect("Pull connections must be registered");
                     }
                 });
-                if self.connections.len() < self.min_peers {
+                if self.connections.len() < self.min_peers.load(Ordering::SeqCst) {
                     register_new_timer.store(true, Ordering::SeqCst);
                 }
                 let was_established = self.connections.is_established(&stream);
@@ -15,10 +15,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::io;
+use std::net::{self, IpAddr};
 
 use mio::event::Evented;
 use mio::net::TcpListener;
 use mio::{Poll, PollOpt, Ready, Token};
+use net2::TcpBuilder;
 
 use super::super::SocketAddr;
 use super::stream::Stream;
@@ -28,9 +30,24 @@ pub struct Listener {
 }
 
 impl Listener {
-    pub fn bind(socket_address: &SocketAddr) -> io::Result<Self> {
+    /// An IPv6 listener also accepts the IPv4 connections if `dual_stack` is true.
+    pub fn bind(socket_address: &SocketAddr, dual_stack: bool) -> io::Result<Self> {
+        let listener = match socket_address.ip() {
+            IpAddr::V4(_) => TcpListener::bind(socket_address.into())?,
+            IpAddr::V6(_) => {
+                let builder = TcpBuilder::new_v6()?;
+                builder.only_v6(!dual_stack)?;
+                // Same as mio::net::TcpListener::bind
+                if cfg!(unix) {
+                    builder.reuse_address(true)?;
+                }
+                let address: &net::SocketAddr = socket_address.into();
+                builder.bind(address)?;
+                TcpListener::from_std(builder.listen(1024)?)?
+            }
+        };
         Ok(Self {
-            listener: TcpListener::bind(socket_address.into())?,
+            listener,
         })
     }
 
@@ -20,9 +20,10 @@ use super::ProtocolId;
 use super::Version;
 
 use super::ACK_ID;
+use super::HANDSHAKE_VERSION;
 use super::SYNC_ID;
 
-use super::super::super::NodeId;
+use super::super::super::{ForkId, NodeId};
 
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Message {
@@ -30,21 +31,23 @@ pub enum Message {
         version: Version,
         port: u16,
         node_id: NodeId,
+        fork_id: ForkId,
     },
-    Ack(Version),
+    Ack(Version, ForkId),
 }
 
 impl Message {
-    pub fn sync(port: u16, node_id: NodeId) -> Self {
+    pub fn sync(port: u16, node_id: NodeId, fork_id: ForkId) -> Self {
         Message::Sync {
-            version: 0,
+            version: HANDSHAKE_VERSION,
             port,
             node_id,
+            fork_id,
         }
     }
 
-    pub fn ack() -> Self {
-        Message::Ack(0)
+    pub fn ack(fork_id: ForkId) -> Self {
+        Message::Ack(HANDSHAKE_VERSION, fork_id)
     }
 
     #[allow(dead_code)]
@@ -54,7 +57,7 @@ impl Message {
                 version,
                 ..
             } => version,
-            Message::Ack(version) => version,
+            Message::Ack(version, _) => version,
         }
     }
 
@@ -63,7 +66,7 @@ impl Message {
             Message::Sync {
                 ..
             } => SYNC_ID,
-            Message::Ack(_) => ACK_ID,
+            Message::Ack(..) => ACK_ID,
         }
     }
 }
@@ -76,11 +79,17 @@ impl Encodable for Message {
                 version,
                 port,
                 node_id,
+                fork_id,
             } => {
-                s.begin_list(4).append(version).append(&self.protocol_id()).append(port).append(node_id);
+                s.begin_list(5)
+                    .append(version)
+                    .append(&self.protocol_id())
+                    .append(port)
+                    .append(node_id)
+                    .append(fork_id);
             }
-            Message::Ack(version) => {
-                s.begin_list(2).append(version).append(&self.protocol_id());
+            Message::Ack(version, fork_id) => {
+                s.begin_list(3).append(version).append(&self.protocol_id()).append(fork_id);
             }
         }
     }
@@ -92,20 +101,21 @@ impl Decodable for Message {
         let protocol_id: ProtocolId = rlp.val_at(1)?;
         match protocol_id {
             SYNC_ID => {
-                if rlp.item_count()? != 4 {
+                if rlp.item_count()? != 5 {
                     return Err(DecoderError::RlpIncorrectListLen)
                 }
                 Ok(Message::Sync {
                     version,
                     port: rlp.val_at(2)?,
                     node_id: rlp.val_at(3)?,
+                    fork_id: rlp.val_at(4)?,
                 })
             }
             ACK_ID => {
-                if rlp.item_count()? != 2 {
+                if rlp.item_count()? != 3 {
                     return Err(DecoderError::RlpIncorrectListLen)
                 }
-                Ok(Message::Ack(version))
+                Ok(Message::Ack(version, rlp.val_at(2)?))
             }
             _ => Err(DecoderError::Custom("invalid protocol id")),
         }
@@ -114,30 +124,54 @@ impl Decodable for Message {
 
 #[cfg(test)]
 mod tests {
+    use primitives::H256;
+
     use super::super::super::super::SocketAddr;
     use super::*;
 
+    fn fork_id() -> ForkId {
+        ForkId {
+            network_id: "tc".into(),
+            hash: H256::random(),
+            next: 10,
+        }
+    }
+
     #[test]
     fn protocol_id_of_sync_is_0() {
         const PORT: u16 = 1234;
         let node_id = SocketAddr::v4(127, 0, 0, 1, 8080).into();
-        assert_eq!(0x00, Message::sync(PORT, node_id).protocol_id());
+        assert_eq!(0x00, Message::sync(PORT, node_id, fork_id()).protocol_id());
     }
 
     #[test]
     fn protocol_id_of_ack_is_1() {
-        assert_eq!(0x01, Message::ack().protocol_id());
+        assert_eq!(0x01, Message::ack(fork_id()).protocol_id());
     }
 
     #[test]
     fn encode_and_decode_sync() {
         const PORT: u16 = 1234;
         let node_id = SocketAddr::v4(127, 0, 0, 1, 8080).into();
-        rlp_encode_and_decode_test!(Message::sync(PORT, node_id));
+        rlp_encode_and_decode_test!(Message::sync(PORT, node_id, fork_id()));
     }
 
     #[test]
     fn encode_and_decode_ack() {
-        rlp_encode_and_decode_test!(Message::ack());
+        rlp_encode_and_decode_test!(Message::ack(fork_id()));
+    }
+
+    #[test]
+    fn sync_of_old_version_is_decoded() {
+        let node_id = SocketAddr::v4(127, 0, 0, 1, 8080).into();
+        let fork_id = fork_id();
+        let old = Message::Sync {
+            version: 0,
+            port: 1234,
+            node_id,
+            fork_id,
+        };
+        let decoded: Message = ::rlp::decode(&::rlp::encode(&old));
+        assert_eq!(0, *decoded.version());
     }
 }
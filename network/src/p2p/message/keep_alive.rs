@@ -0,0 +1,100 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+use super::ProtocolId;
+use super::Seq;
+use super::Version;
+
+use super::PING_ID;
+use super::PONG_ID;
+
+/// The messages that check whether the established connection is still alive.
+/// A pong carries the seq of the ping it answers.
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Message {
+    Ping(Version, Seq),
+    Pong(Version, Seq),
+}
+
+impl Message {
+    pub fn ping(seq: Seq) -> Self {
+        Message::Ping(0, seq)
+    }
+
+    pub fn pong(seq: Seq) -> Self {
+        Message::Pong(0, seq)
+    }
+
+    fn protocol_id(&self) -> ProtocolId {
+        match self {
+            Message::Ping(..) => PING_ID,
+            Message::Pong(..) => PONG_ID,
+        }
+    }
+}
+
+impl Encodable for Message {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let (version, seq) = match self {
+            Message::Ping(version, seq) => (version, seq),
+            Message::Pong(version, seq) => (version, seq),
+        };
+        s.begin_list(3).append(version).append(&self.protocol_id()).append(seq);
+    }
+}
+
+impl Decodable for Message {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen)
+        }
+        let version: Version = rlp.val_at(0)?;
+        let protocol_id: ProtocolId = rlp.val_at(1)?;
+        let seq: Seq = rlp.val_at(2)?;
+        match protocol_id {
+            PING_ID => Ok(Message::Ping(version, seq)),
+            PONG_ID => Ok(Message::Pong(version, seq)),
+            _ => Err(DecoderError::Custom("invalid protocol id")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_id_of_ping_is_7() {
+        assert_eq!(0x07, Message::ping(Default::default()).protocol_id());
+    }
+
+    #[test]
+    fn protocol_id_of_pong_is_8() {
+        assert_eq!(0x08, Message::pong(Default::default()).protocol_id());
+    }
+
+    #[test]
+    fn encode_and_decode_ping() {
+        rlp_encode_and_decode_test!(Message::ping(0x1234));
+    }
+
+    #[test]
+    fn encode_and_decode_pong() {
+        rlp_encode_and_decode_test!(Message::pong(0x5678));
+    }
+}
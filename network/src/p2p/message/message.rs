@@ -18,6 +18,7 @@ use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
 use super::ExtensionMessage;
 use super::HandshakeMessage;
+use super::KeepAliveMessage;
 use super::NegotiationMessage;
 
 #[derive(Debug)]
@@ -25,6 +26,7 @@ pub enum Message {
     Extension(ExtensionMessage),
     Handshake(HandshakeMessage),
     Negotiation(NegotiationMessage),
+    KeepAlive(KeepAliveMessage),
 }
 
 impl Message {
@@ -43,6 +45,8 @@ use super::ACK_ID;
 use super::ALLOWED_ID;
 use super::DENIED_ID;
 use super::ENCRYPTED_ID;
+use super::PING_ID;
+use super::PONG_ID;
 use super::REQUEST_ID;
 use super::SYNC_ID;
 use super::UNENCRYPTED_ID;
@@ -53,6 +57,7 @@ impl Encodable for Message {
             Message::Extension(message) => message.rlp_append(s),
             Message::Handshake(message) => message.rlp_append(s),
             Message::Negotiation(message) => message.rlp_append(s),
+            Message::KeepAlive(message) => message.rlp_append(s),
         }
     }
 }
@@ -68,6 +73,8 @@ impl Decodable for Message {
             DENIED_ID => Ok(Message::Negotiation(NegotiationMessage::decode(rlp)?)),
             ENCRYPTED_ID => Ok(Message::Extension(ExtensionMessage::decode(rlp)?)),
             UNENCRYPTED_ID => Ok(Message::Extension(ExtensionMessage::decode(rlp)?)),
+            PING_ID => Ok(Message::KeepAlive(KeepAliveMessage::decode(rlp)?)),
+            PONG_ID => Ok(Message::KeepAlive(KeepAliveMessage::decode(rlp)?)),
             _ => Err(DecoderError::Custom("unexpected protocol id")),
         }
     }
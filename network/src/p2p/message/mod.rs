@@ -16,6 +16,7 @@
 
 mod extension;
 mod handshake;
+mod keep_alive;
 mod message;
 mod negotiation;
 mod signed_message;
@@ -24,6 +25,7 @@ use primitives::H256;
 
 pub use self::extension::Message as ExtensionMessage;
 pub use self::handshake::Message as HandshakeMessage;
+pub use self::keep_alive::Message as KeepAliveMessage;
 pub use self::message::Message;
 pub use self::negotiation::{Body as NegotiationBody, Message as NegotiationMessage};
 pub use self::signed_message::SignedMessage;
@@ -34,6 +36,12 @@ pub type ProtocolId = u64;
 pub type Seq = u64;
 pub type Signature = H256;
 
+/// The version of the handshake messages this node sends.
+/// The handshake messages carry the fork id from version 2.
+pub const HANDSHAKE_VERSION: Version = 2;
+/// The nodes which sent a lower handshake version don't know the keep-alive messages, so they are not pinged.
+pub const KEEP_ALIVE_VERSION: Version = 1;
+
 pub const SYNC_ID: ProtocolId = 0x00;
 pub const ACK_ID: ProtocolId = 0x01;
 pub const REQUEST_ID: ProtocolId = 0x02;
@@ -41,6 +49,8 @@ pub const ALLOWED_ID: ProtocolId = 0x03;
 pub const DENIED_ID: ProtocolId = 0x04;
 pub const ENCRYPTED_ID: ProtocolId = 0x05;
 pub const UNENCRYPTED_ID: ProtocolId = 0x06;
+pub const PING_ID: ProtocolId = 0x07;
+pub const PONG_ID: ProtocolId = 0x08;
 
 #[cfg(test)]
 mod tests {
@@ -48,6 +58,8 @@ mod tests {
     use super::ALLOWED_ID;
     use super::DENIED_ID;
     use super::ENCRYPTED_ID;
+    use super::PING_ID;
+    use super::PONG_ID;
     use super::REQUEST_ID;
     use super::SYNC_ID;
     use super::UNENCRYPTED_ID;
@@ -121,4 +133,28 @@ mod tests {
         assert_ne!(UNENCRYPTED_ID, DENIED_ID);
         assert_ne!(UNENCRYPTED_ID, ENCRYPTED_ID);
     }
+
+    #[test]
+    fn ping_id_is_a_unique() {
+        assert_ne!(PING_ID, SYNC_ID);
+        assert_ne!(PING_ID, ACK_ID);
+        assert_ne!(PING_ID, REQUEST_ID);
+        assert_ne!(PING_ID, ALLOWED_ID);
+        assert_ne!(PING_ID, DENIED_ID);
+        assert_ne!(PING_ID, ENCRYPTED_ID);
+        assert_ne!(PING_ID, UNENCRYPTED_ID);
+        assert_ne!(PING_ID, PONG_ID);
+    }
+
+    #[test]
+    fn pong_id_is_a_unique() {
+        assert_ne!(PONG_ID, SYNC_ID);
+        assert_ne!(PONG_ID, ACK_ID);
+        assert_ne!(PONG_ID, REQUEST_ID);
+        assert_ne!(PONG_ID, ALLOWED_ID);
+        assert_ne!(PONG_ID, DENIED_ID);
+        assert_ne!(PONG_ID, ENCRYPTED_ID);
+        assert_ne!(PONG_ID, UNENCRYPTED_ID);
+        assert_ne!(PONG_ID, PING_ID);
+    }
 }
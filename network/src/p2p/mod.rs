@@ -19,10 +19,15 @@ mod connections;
 mod handler;
 mod listener;
 mod message;
+mod prober;
+mod rate_limiter;
+mod slots;
+mod socks5;
 mod stream;
 
-pub use self::handler::{Handler, IgnoreConnectionLimit, Message};
+pub use self::handler::{check_peer_limits, Handler, IgnoreConnectionLimit, Message};
 use self::message::ExtensionMessage;
+use self::message::KeepAliveMessage;
 use self::message::NegotiationBody;
 use self::message::NegotiationMessage;
 use self::message::SignedMessage;
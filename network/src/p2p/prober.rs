@@ -0,0 +1,190 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::net;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use super::super::{RoutingTable, SocketAddr};
+
+/// At most this many addresses are dialed at once.
+const PROBE_THREADS: usize = 4;
+/// The addresses beyond this many waiting ones are not probed.
+/// They are probed again when the peers connect next time.
+const MAX_PENDING_PROBES: usize = 64;
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+type Dial = Fn(&SocketAddr) -> bool + Send + Sync;
+
+/// Dials the addresses which the inbound peers advertised on a fixed number of threads not to block the IO thread.
+/// The connection is closed right away. The address is verified if it's accepted.
+pub struct Prober {
+    sender: SyncSender<SocketAddr>,
+    /// The addresses which are waiting or being dialed, not to dial an address twice at once.
+    probing: Arc<Mutex<HashSet<SocketAddr>>>,
+}
+
+impl Prober {
+    pub fn new(routing_table: Arc<RoutingTable>) -> Self {
+        Self::with_dial(routing_table, PROBE_THREADS, MAX_PENDING_PROBES, Arc::new(dial))
+    }
+
+    fn with_dial(routing_table: Arc<RoutingTable>, threads: usize, max_pending: usize, dial: Arc<Dial>) -> Self {
+        let (sender, receiver) = sync_channel(max_pending);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let probing: Arc<Mutex<HashSet<SocketAddr>>> = Default::default();
+        for _ in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            let routing_table = Arc::clone(&routing_table);
+            let probing = Arc::clone(&probing);
+            let dial = Arc::clone(&dial);
+            let worker = move || run(&receiver, &routing_table, &probing, &*dial);
+            if let Err(err) = thread::Builder::new().name("probe".to_string()).spawn(worker) {
+                cwarn!(NETWORK, "Cannot start a probe thread: {:?}", err);
+            }
+        }
+        Self {
+            sender,
+            probing,
+        }
+    }
+
+    /// Returns false if the address is not going to be probed, because too many addresses are waiting.
+    pub fn probe(&self, address: SocketAddr) -> bool {
+        let mut probing = self.probing.lock();
+        if probing.contains(&address) {
+            return true
+        }
+        if self.sender.try_send(address).is_err() {
+            cdebug!(NETWORK, "Too many addresses are waiting to be probed. {} is not probed", address);
+            return false
+        }
+        probing.insert(address);
+        true
+    }
+}
+
+fn run(
+    receiver: &Mutex<Receiver<SocketAddr>>,
+    routing_table: &RoutingTable,
+    probing: &Mutex<HashSet<SocketAddr>>,
+    dial: &Dial,
+) {
+    loop {
+        // The sender is dropped with the prober.
+        let address = match receiver.lock().recv() {
+            Ok(address) => address,
+            Err(_) => return,
+        };
+        if dial(&address) {
+            routing_table.verify(&address);
+        }
+        probing.lock().remove(&address);
+    }
+}
+
+fn dial(address: &SocketAddr) -> bool {
+    let timeout = Duration::from_secs(PROBE_TIMEOUT_SECS);
+    match net::TcpStream::connect_timeout(address.into(), timeout) {
+        Ok(_) => true,
+        Err(err) => {
+            cdebug!(NETWORK, "{} doesn't accept connections at the advertised address: {}", address, err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+    use std::time::Instant;
+
+    use super::*;
+
+    fn wait_until<F: Fn() -> bool>(condition: F) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "Timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn addresses_beyond_the_pending_limit_are_not_probed() {
+        let table = Arc::new(RoutingTable::new());
+        let dialing = Arc::new(AtomicUsize::new(0));
+        let (release, released) = channel::<()>();
+        let released = Mutex::new(released);
+        let dial = {
+            let dialing = Arc::clone(&dialing);
+            move |_: &SocketAddr| {
+                dialing.fetch_add(1, Ordering::SeqCst);
+                released.lock().recv().is_ok()
+            }
+        };
+        let prober = Prober::with_dial(Arc::clone(&table), 1, 1, Arc::new(dial));
+
+        let first = SocketAddr::v4(127, 0, 0, 1, 3485);
+        let second = SocketAddr::v4(127, 0, 0, 2, 3485);
+        let third = SocketAddr::v4(127, 0, 0, 3, 3485);
+        assert!(prober.probe(first));
+        wait_until(|| dialing.load(Ordering::SeqCst) == 1);
+        assert!(prober.probe(second));
+        assert!(prober.probe(second), "An address which is waiting is not queued twice");
+        assert!(!prober.probe(third));
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        wait_until(|| table.is_verified(&first) && table.is_verified(&second));
+        assert_eq!(2, dialing.load(Ordering::SeqCst));
+        assert!(!table.is_verified(&third));
+    }
+
+    #[test]
+    fn probes_run_on_the_limited_threads() {
+        let table = Arc::new(RoutingTable::new());
+        let dialing = Arc::new(AtomicUsize::new(0));
+        let (release, released) = channel::<()>();
+        let released = Mutex::new(released);
+        let dial = {
+            let dialing = Arc::clone(&dialing);
+            move |_: &SocketAddr| {
+                dialing.fetch_add(1, Ordering::SeqCst);
+                released.lock().recv().is_ok()
+            }
+        };
+        let prober = Prober::with_dial(Arc::clone(&table), 2, 8, Arc::new(dial));
+
+        let addresses: Vec<_> = (1..6).map(|i| SocketAddr::v4(127, 0, 0, i, 3485)).collect();
+        for address in &addresses {
+            assert!(prober.probe(*address));
+        }
+        wait_until(|| dialing.load(Ordering::SeqCst) == 2);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(2, dialing.load(Ordering::SeqCst));
+
+        for _ in &addresses {
+            release.send(()).unwrap();
+        }
+        wait_until(|| addresses.iter().all(|address| table.is_verified(address)));
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use cio::StreamToken;
+
+/// Extension messages that a peer can send to each extension per second.
+const MESSAGES_PER_SEC: f64 = 100.0;
+const MESSAGE_BURST: f64 = 200.0;
+/// Bytes of extension messages that a peer can send per second.
+const BYTES_PER_SEC: f64 = (8 * 1024 * 1024) as f64;
+const BYTE_BURST: f64 = (16 * 1024 * 1024) as f64;
+/// A peer is flooding once this many of its messages are dropped, less the ones forgiven over time.
+/// So a long-lived peer isn't banned for the drops spread over its whole lifetime.
+const MAX_RECENT_DROPS: f64 = 1000.0;
+const FORGIVEN_DROPS_PER_SEC: f64 = 1.0;
+
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, capacity: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now > self.last_refill {
+            let elapsed = now.duration_since(self.last_refill);
+            let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+            self.last_refill = now;
+        }
+    }
+
+    fn has(&self, amount: f64) -> bool {
+        self.tokens >= amount
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.tokens -= amount;
+    }
+}
+
+/// Extension message traffic from a peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Traffic {
+    pub received_messages: u64,
+    pub received_bytes: u64,
+    pub dropped_messages: u64,
+}
+
+struct Peer {
+    traffic: Traffic,
+    bytes: TokenBucket,
+    messages: HashMap<String, TokenBucket>,
+    /// Each dropped message takes a token. The peer is flooding once it's empty.
+    drops: TokenBucket,
+}
+
+/// Token bucket rate limits on the extension messages from each peer.
+///
+/// The number of messages is limited for each extension, and the size of messages is limited for all extensions.
+pub struct RateLimiter {
+    messages_per_sec: f64,
+    message_burst: f64,
+    bytes_per_sec: f64,
+    byte_burst: f64,
+    forgiven_drops_per_sec: f64,
+    max_recent_drops: f64,
+    peers: HashMap<StreamToken, Peer>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(MESSAGES_PER_SEC, MESSAGE_BURST, BYTES_PER_SEC, BYTE_BURST)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(messages_per_sec: f64, message_burst: f64, bytes_per_sec: f64, byte_burst: f64) -> Self {
+        Self {
+            messages_per_sec,
+            message_burst,
+            bytes_per_sec,
+            byte_burst,
+            forgiven_drops_per_sec: FORGIVEN_DROPS_PER_SEC,
+            max_recent_drops: MAX_RECENT_DROPS,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Accounts a message of `size` bytes, returning false if it exceeds the limits and must be dropped.
+    pub fn allow(&mut self, token: StreamToken, extension_name: &str, size: usize, now: Instant) -> bool {
+        let (bytes_per_sec, byte_burst) = (self.bytes_per_sec, self.byte_burst);
+        let (messages_per_sec, message_burst) = (self.messages_per_sec, self.message_burst);
+        let (forgiven_drops_per_sec, max_recent_drops) = (self.forgiven_drops_per_sec, self.max_recent_drops);
+        let peer = self.peers.entry(token).or_insert_with(|| Peer {
+            traffic: Traffic::default(),
+            bytes: TokenBucket::new(bytes_per_sec, byte_burst, now),
+            messages: HashMap::new(),
+            drops: TokenBucket::new(forgiven_drops_per_sec, max_recent_drops, now),
+        });
+        peer.traffic.received_messages += 1;
+        peer.traffic.received_bytes += size as u64;
+
+        let messages = peer
+            .messages
+            .entry(extension_name.to_string())
+            .or_insert_with(|| TokenBucket::new(messages_per_sec, message_burst, now));
+        messages.refill(now);
+        peer.bytes.refill(now);
+        // A message larger than the burst would never pass, so it only has to drain the bucket.
+        let size = (size as f64).min(peer.bytes.capacity);
+        if !messages.has(1.0) || !peer.bytes.has(size) {
+            peer.traffic.dropped_messages += 1;
+            peer.drops.refill(now);
+            if peer.drops.has(1.0) {
+                peer.drops.take(1.0);
+            }
+            return false
+        }
+        messages.take(1.0);
+        peer.bytes.take(size);
+        true
+    }
+
+    /// Whether so many messages of the peer are dropped recently that it must be banned.
+    pub fn is_flooding(&self, token: &StreamToken) -> bool {
+        self.peers.get(token).map_or(false, |peer| !peer.drops.has(1.0))
+    }
+
+    pub fn traffic(&self, token: &StreamToken) -> Option<Traffic> {
+        self.peers.get(token).map(|peer| peer.traffic)
+    }
+
+    pub fn remove(&mut self, token: &StreamToken) -> Option<Traffic> {
+        self.peers.remove(token).map(|peer| peer.traffic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn messages_are_limited_per_extension() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1.0, 2.0, 1000.0, 1000.0);
+        assert!(limiter.allow(1, "a", 1, now));
+        assert!(limiter.allow(1, "a", 1, now));
+        assert!(!limiter.allow(1, "a", 1, now));
+        assert!(limiter.allow(1, "b", 1, now));
+        assert!(limiter.allow(2, "a", 1, now));
+
+        assert!(limiter.allow(1, "a", 1, now + Duration::from_secs(1)));
+        assert_eq!(
+            Some(Traffic {
+                received_messages: 5,
+                received_bytes: 5,
+                dropped_messages: 1,
+            }),
+            limiter.traffic(&1)
+        );
+    }
+
+    #[test]
+    fn bytes_are_limited_per_peer() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(100.0, 100.0, 10.0, 10.0);
+        assert!(limiter.allow(1, "a", 6, now));
+        assert!(!limiter.allow(1, "b", 6, now));
+        assert!(limiter.allow(1, "b", 6, now + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn removed_peer_starts_over() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1.0, 1.0, 1000.0, 1000.0);
+        assert!(limiter.allow(1, "a", 1, now));
+        assert!(!limiter.allow(1, "a", 1, now));
+        assert_eq!(1, limiter.remove(&1).unwrap().dropped_messages);
+        assert!(limiter.allow(1, "a", 1, now));
+    }
+
+    #[test]
+    fn dropped_messages_are_forgiven_over_time() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter {
+            forgiven_drops_per_sec: 1.0,
+            max_recent_drops: 2.0,
+            ..RateLimiter::new(1.0, 1.0, 1000.0, 1000.0)
+        };
+        assert!(limiter.allow(1, "a", 1, now));
+        assert!(!limiter.allow(1, "a", 1, now));
+        assert!(!limiter.is_flooding(&1));
+
+        // The drops spread over time don't add up.
+        let later = now + Duration::from_secs(10);
+        assert!(limiter.allow(1, "a", 1, later));
+        assert!(!limiter.allow(1, "a", 1, later));
+        assert!(!limiter.is_flooding(&1));
+
+        assert!(!limiter.allow(1, "a", 1, later));
+        assert!(limiter.is_flooding(&1));
+        assert_eq!(3, limiter.traffic(&1).unwrap().dropped_messages);
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+use cio::StreamToken;
+
+struct Slot {
+    ip: IpAddr,
+    /// Order in which the connection has taken the slot.
+    seq: u64,
+}
+
+/// Connection slots of the p2p handler.
+///
+/// The inbound and the outbound connections are limited separately.
+/// The trusted nodes always have a slot: an outbound connection to them is not limited,
+/// and an inbound connection from them evicts another inbound peer when there is no free slot.
+pub struct Slots {
+    max_inbound: usize,
+    max_outbound: usize,
+    trusted: HashSet<IpAddr>,
+
+    inbound: HashMap<StreamToken, Slot>,
+    outbound: HashMap<StreamToken, Slot>,
+    next_seq: u64,
+}
+
+pub enum Admission {
+    Accept,
+    /// Accept after evicting the peer of the token.
+    Evict(StreamToken),
+    Reject,
+}
+
+impl Slots {
+    pub fn new(max_inbound: usize, max_outbound: usize, trusted: Vec<IpAddr>) -> Self {
+        Self {
+            max_inbound,
+            max_outbound,
+            trusted: trusted.into_iter().collect(),
+
+            inbound: HashMap::new(),
+            outbound: HashMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Changes the limits. The peers beyond the new limits are kept until they disconnect.
+    pub fn set_limits(&mut self, max_inbound: usize, max_outbound: usize) {
+        self.max_inbound = max_inbound;
+        self.max_outbound = max_outbound;
+    }
+
+    pub fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted.contains(ip)
+    }
+
+    /// Decides whether an inbound connection from `ip` can take a slot.
+    ///
+    /// Only the peers for which `is_evictable` returns true are evicted. Among them,
+    /// the most recently connected one is the lowest-value peer, since it has served the least.
+    pub fn admit_inbound<F>(&self, ip: &IpAddr, is_evictable: F) -> Admission
+    where
+        F: Fn(&StreamToken) -> bool, {
+        if self.inbound.len() < self.max_inbound {
+            return Admission::Accept
+        }
+        if !self.is_trusted(ip) {
+            return Admission::Reject
+        }
+        self.inbound
+            .iter()
+            .filter(|(token, slot)| !self.is_trusted(&slot.ip) && is_evictable(token))
+            .max_by_key(|(_, slot)| slot.seq)
+            .map(|(token, _)| Admission::Evict(*token))
+            .unwrap_or(Admission::Reject)
+    }
+
+    pub fn has_outbound_slot(&self, ip: &IpAddr) -> bool {
+        self.outbound.len() < self.max_outbound || self.is_trusted(ip)
+    }
+
+    pub fn add_inbound(&mut self, token: StreamToken, ip: IpAddr) {
+        let slot = self.new_slot(ip);
+        let t = self.inbound.insert(token, slot);
+        debug_assert!(t.is_none());
+    }
+
+    pub fn add_outbound(&mut self, token: StreamToken, ip: IpAddr) {
+        let slot = self.new_slot(ip);
+        let t = self.outbound.insert(token, slot);
+        debug_assert!(t.is_none());
+    }
+
+    pub fn release(&mut self, token: &StreamToken) {
+        self.inbound.remove(token);
+        self.outbound.remove(token);
+    }
+
+    pub fn inbound_count(&self) -> usize {
+        self.inbound.len()
+    }
+
+    pub fn outbound_count(&self) -> usize {
+        self.outbound.len()
+    }
+
+    fn new_slot(&mut self, ip: IpAddr) -> Slot {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        Slot {
+            ip,
+            seq,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn inbound_and_outbound_are_limited_separately() {
+        let mut slots = Slots::new(1, 1, vec![]);
+        slots.add_outbound(1, ip(1));
+        assert!(!slots.has_outbound_slot(&ip(2)));
+        match slots.admit_inbound(&ip(2), |_| true) {
+            Admission::Accept => {}
+            _ => panic!(),
+        }
+        slots.add_inbound(2, ip(2));
+        match slots.admit_inbound(&ip(3), |_| true) {
+            Admission::Reject => {}
+            _ => panic!(),
+        }
+
+        slots.release(&1);
+        assert!(slots.has_outbound_slot(&ip(2)));
+        assert_eq!(1, slots.inbound_count());
+        assert_eq!(0, slots.outbound_count());
+    }
+
+    #[test]
+    fn trusted_nodes_are_not_limited_by_outbound_slots() {
+        let mut slots = Slots::new(1, 1, vec![ip(9)]);
+        slots.add_outbound(1, ip(1));
+        assert!(!slots.has_outbound_slot(&ip(2)));
+        assert!(slots.has_outbound_slot(&ip(9)));
+    }
+
+    #[test]
+    fn lowered_limits_keep_existing_peers() {
+        let mut slots = Slots::new(2, 2, vec![]);
+        slots.add_inbound(1, ip(1));
+        slots.add_inbound(2, ip(2));
+        slots.add_outbound(3, ip(3));
+
+        slots.set_limits(1, 1);
+        assert_eq!(2, slots.inbound_count());
+        assert!(!slots.has_outbound_slot(&ip(4)));
+        match slots.admit_inbound(&ip(4), |_| true) {
+            Admission::Reject => {}
+            _ => panic!(),
+        }
+
+        slots.set_limits(3, 2);
+        assert!(slots.has_outbound_slot(&ip(4)));
+        match slots.admit_inbound(&ip(4), |_| true) {
+            Admission::Accept => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn trusted_inbound_evicts_newest_untrusted_peer() {
+        let mut slots = Slots::new(3, 1, vec![ip(8), ip(9)]);
+        slots.add_inbound(1, ip(1));
+        slots.add_inbound(2, ip(8));
+        slots.add_inbound(3, ip(3));
+
+        match slots.admit_inbound(&ip(9), |_| true) {
+            Admission::Evict(3) => {}
+            _ => panic!(),
+        }
+        match slots.admit_inbound(&ip(9), |token| *token != 3) {
+            Admission::Evict(1) => {}
+            _ => panic!(),
+        }
+        match slots.admit_inbound(&ip(9), |_| false) {
+            Admission::Reject => {}
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+const VERSION: u8 = 0x05;
+const NO_AUTHENTICATION: u8 = 0x00;
+const CONNECT: u8 = 0x01;
+const RESERVED: u8 = 0x00;
+const SUCCEEDED: u8 = 0x00;
+
+const IPV4: u8 = 0x01;
+const DOMAIN_NAME: u8 = 0x03;
+const IPV6: u8 = 0x04;
+
+/// Opens a TCP connection to the target through the SOCKS5 proxy, without authentication. See RFC 1928.
+/// It blocks until the proxy connects to the target or the timeout expires.
+pub fn connect(proxy: &SocketAddr, target: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect_timeout(proxy, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    stream.write_all(&[VERSION, 1, NO_AUTHENTICATION])?;
+    let mut method = [0u8; 2];
+    stream.read_exact(&mut method)?;
+    if method != [VERSION, NO_AUTHENTICATION] {
+        return Err(invalid_data("The proxy doesn't allow the connection without authentication"))
+    }
+
+    let mut request = vec![VERSION, CONNECT, RESERVED];
+    match target.ip() {
+        IpAddr::V4(ip) => {
+            request.push(IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            request.push(IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+    }
+    request.push((target.port() >> 8) as u8);
+    request.push(target.port() as u8);
+    stream.write_all(&request)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != VERSION {
+        return Err(invalid_data("The proxy doesn't speak SOCKS5"))
+    }
+    if reply[1] != SUCCEEDED {
+        let message = format!("The proxy cannot connect to {}: reply {}", target, reply[1]);
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, message))
+    }
+    // The address which the proxy binds to is not used, but it must be read out of the stream.
+    let address_len = match reply[3] {
+        IPV4 => 4,
+        IPV6 => 16,
+        DOMAIN_NAME => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        _ => return Err(invalid_data("The proxy replies an unknown address type")),
+    };
+    let mut bound_address = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound_address)?;
+
+    stream.set_read_timeout(None)?;
+    stream.set_write_timeout(None)?;
+    Ok(stream)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// Accepts a connection and answers the SOCKS5 requests with the reply code.
+    fn proxy(reply: u8) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            assert_eq!([VERSION, 1, NO_AUTHENTICATION], greeting);
+            stream.write_all(&[VERSION, NO_AUTHENTICATION]).unwrap();
+
+            let mut request = [0u8; 10];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!([VERSION, CONNECT, RESERVED, IPV4, 10, 0, 0, 1, 0x0d, 0x9d], request);
+            stream.write_all(&[VERSION, reply, RESERVED, IPV4, 127, 0, 0, 1, 0, 0]).unwrap();
+            stream.write_all(b"hello").unwrap();
+        });
+        address
+    }
+
+    #[test]
+    fn connect_through_the_proxy() {
+        let proxy = proxy(SUCCEEDED);
+        let target = "10.0.0.1:3485".parse().unwrap();
+
+        let mut stream = connect(&proxy, &target, Duration::from_secs(5)).unwrap();
+        let mut hello = [0u8; 5];
+        stream.read_exact(&mut hello).unwrap();
+        assert_eq!(b"hello", &hello);
+    }
+
+    #[test]
+    fn fail_if_the_proxy_cannot_connect() {
+        const HOST_UNREACHABLE: u8 = 0x04;
+        let proxy = proxy(HOST_UNREACHABLE);
+        let target = "10.0.0.1:3485".parse().unwrap();
+
+        let err = connect(&proxy, &target, Duration::from_secs(5)).unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+    }
+}
@@ -34,6 +34,8 @@ pub enum Error {
     IoError(io::Error),
     DecoderError(DecoderError),
     InvalidSign,
+    /// The length prefix of the message exceeds the limit. The payload is not read.
+    TooLargeMessage(usize),
 }
 
 impl fmt::Display for Error {
@@ -42,6 +44,7 @@ impl fmt::Display for Error {
             Error::IoError(err) => err.fmt(f),
             Error::DecoderError(err) => err.fmt(f),
             Error::InvalidSign => fmt::Debug::fmt(&self, f),
+            Error::TooLargeMessage(_) => fmt::Debug::fmt(&self, f),
         }
     }
 }
@@ -131,7 +134,7 @@ impl TryStream {
         }
     }
 
-    fn read_bytes(&mut self) -> Result<Option<Vec<u8>>> {
+    fn read_bytes(&mut self, max_size: usize) -> Result<Option<Vec<u8>>> {
         let from_socket = self.peer_addr()?;
 
         let (mut total_length, mut result) = {
@@ -155,6 +158,16 @@ impl TryStream {
             }
         };
 
+        if total_length > max_size {
+            cdebug!(
+                NETWORK,
+                "{} sends a message of {} bytes, which exceeds {} bytes",
+                from_socket,
+                total_length,
+                max_size
+            );
+            return Err(Error::TooLargeMessage(total_length))
+        }
         if total_length == 0 {
             return Ok(Some(result))
         }
@@ -247,14 +260,22 @@ impl Stream {
         })
     }
 
-    pub fn read<M>(&mut self) -> Result<Option<M>>
+    /// Wraps the connection which the SOCKS5 proxy has established to the peer.
+    pub fn from_proxied(stream: net::TcpStream) -> Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Self::from(TcpStream::from_stream(stream)?))
+    }
+
+    /// Reads a message whose payload is at most `max_size` bytes.
+    /// A larger message is refused by its length prefix before the payload is read.
+    pub fn read<M>(&mut self, max_size: usize) -> Result<Option<M>>
     where
         M: ?Sized + Decodable, {
-        match self.read_bytes()? {
+        match self.read_bytes(max_size)? {
             None => Ok(None),
             Some(ref bytes) if bytes.is_empty() => Ok(None),
             Some(bytes) => {
-                let rlp = UntrustedRlp::new(&bytes);
+                let rlp = UntrustedRlp::new_checked(&bytes)?;
                 Ok(Some(rlp.as_val::<M>()?))
             }
         }
@@ -277,8 +298,13 @@ impl Stream {
         self.try_stream.write.clear();
     }
 
-    fn read_bytes(&mut self) -> Result<Option<Vec<u8>>> {
-        self.try_stream.read_bytes()
+    /// Whether the socket didn't take all the written bytes yet.
+    pub fn is_writing(&self) -> bool {
+        !self.try_stream.write.is_empty()
+    }
+
+    fn read_bytes(&mut self, max_size: usize) -> Result<Option<Vec<u8>>> {
+        self.try_stream.read_bytes(max_size)
     }
 
     pub fn peer_addr(&self) -> Result<SocketAddr> {
@@ -303,14 +329,14 @@ impl SignedStream {
         }
     }
 
-    pub fn read<M>(&mut self) -> Result<Option<M>>
+    pub fn read<M>(&mut self, max_size: usize) -> Result<Option<M>>
     where
         M: ?Sized + Decodable, {
-        if let Some(signed) = self.stream.read::<SignedMessage>()? {
+        if let Some(signed) = self.stream.read::<SignedMessage>(max_size)? {
             if !signed.is_valid(&self.session) {
                 return Err(Error::InvalidSign)
             }
-            let rlp = UntrustedRlp::new(&signed.message);
+            let rlp = UntrustedRlp::new_checked(&signed.message)?;
             Ok(Some(rlp.as_val::<M>()?))
         } else {
             Ok(None)
@@ -329,6 +355,10 @@ impl SignedStream {
         Ok(())
     }
 
+    pub fn is_writing(&self) -> bool {
+        self.stream.is_writing()
+    }
+
     pub fn session(&self) -> &Session {
         &self.session
     }
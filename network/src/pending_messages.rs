@@ -0,0 +1,550 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ccrypto::aes;
+use parking_lot::Mutex;
+use primitives::{H128, H256};
+use rand::{OsRng, Rng};
+use rlp::{DecoderError, RlpStream, UntrustedRlp};
+use time::Duration;
+
+use super::{IntoSocketAddr, NodeId};
+
+/// Messages kept for a node. The oldest one is dropped when a new one comes to the full queue.
+const MAX_PENDING_MESSAGES: usize = 256;
+/// The total size of the messages kept for a node. The oldest ones are dropped to make room for a new one.
+const MAX_PENDING_BYTES: usize = 1 << 20;
+/// The messages to the other nodes are dropped while this many nodes have pending messages.
+const MAX_PENDING_NODES: usize = 128;
+
+/// The file in the directory which keeps the key encrypting the saved messages.
+const KEY_FILE_NAME: &str = "key";
+const QUEUE_FILE_EXTENSION: &str = "rlp";
+
+struct Limits {
+    messages: usize,
+    bytes: usize,
+    nodes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            messages: MAX_PENDING_MESSAGES,
+            bytes: MAX_PENDING_BYTES,
+            nodes: MAX_PENDING_NODES,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, RlpEncodable, RlpDecodable)]
+struct PendingMessage {
+    extension_name: String,
+    data: Vec<u8>,
+    /// The message is dropped after this time, in seconds since the Unix epoch.
+    expires_at: u64,
+}
+
+impl PendingMessage {
+    fn size(&self) -> usize {
+        self.extension_name.len() + self.data.len()
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<PendingMessage>,
+    /// The sum of the sizes of the messages.
+    bytes: usize,
+}
+
+impl Queue {
+    fn new(messages: VecDeque<PendingMessage>) -> Self {
+        let bytes = messages.iter().map(PendingMessage::size).sum();
+        Self {
+            messages,
+            bytes,
+        }
+    }
+
+    fn push(&mut self, message: PendingMessage) {
+        self.bytes += message.size();
+        self.messages.push_back(message);
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(message) = self.messages.pop_front() {
+            self.bytes -= message.size();
+        }
+    }
+
+    fn drop_expired(&mut self, now: u64) {
+        self.messages.retain(|message| message.expires_at > now);
+        self.bytes = self.messages.iter().map(PendingMessage::size).sum();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// The queues and the files keeping them. The files are written on the saver thread, not under the lock of the queues.
+struct Store {
+    path: PathBuf,
+    key: H256,
+    queues: Mutex<HashMap<NodeId, Queue>>,
+    /// The nodes whose queues have changed since they were saved.
+    dirty: Mutex<HashSet<NodeId>>,
+}
+
+/// The extension messages to the nodes which are not connected.
+///
+/// They are sent when the node connects again before they expire, so a short disconnection doesn't lose them.
+/// The queue of each node is saved to its own file in the directory, so the messages survive a restart.
+/// The files are encrypted with the key kept in the directory.
+pub struct PendingMessages {
+    store: Arc<Store>,
+    limits: Limits,
+    saver: Mutex<Option<Sender<NodeId>>>,
+    saver_thread: Option<JoinHandle<()>>,
+}
+
+impl PendingMessages {
+    /// Reads the queues saved in the directory. It creates the directory if it doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Self::load_with_limits(path, Limits::default())
+    }
+
+    fn load_with_limits(path: &Path, limits: Limits) -> Result<Self, String> {
+        fs::create_dir_all(path)
+            .map_err(|err| format!("Cannot create the pending message directory {:?}: {:?}", path, err))?;
+        let key = load_key(&path.join(KEY_FILE_NAME))?;
+        let entries = fs::read_dir(path)
+            .map_err(|err| format!("Cannot read the pending message directory {:?}: {:?}", path, err))?;
+
+        let now = now();
+        let mut queues = HashMap::new();
+        for entry in entries {
+            let file = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => return Err(format!("Cannot read the pending message directory {:?}: {:?}", path, err)),
+            };
+            let is_queue = file.extension().and_then(|extension| extension.to_str()) == Some(QUEUE_FILE_EXTENSION);
+            if !file.is_file() || !is_queue {
+                continue
+            }
+            let bytes =
+                fs::read(&file).map_err(|err| format!("Cannot read the pending messages {:?}: {:?}", file, err))?;
+            match decrypt(&key, &bytes).and_then(|bytes| decode_queue(&bytes).map_err(|err| format!("{:?}", err))) {
+                Ok((node, messages)) => {
+                    let mut queue = Queue::new(messages);
+                    queue.drop_expired(now);
+                    if !queue.is_empty() && queues.len() < limits.nodes {
+                        queues.insert(node, queue);
+                    }
+                }
+                Err(err) => {
+                    cwarn!(NETAPI, "Drops the broken pending messages {:?}: {}", file, err);
+                }
+            }
+            if let Err(err) = fs::remove_file(&file) {
+                cwarn!(NETAPI, "Cannot remove the pending messages {:?}: {:?}", file, err);
+            }
+        }
+
+        let nodes: Vec<_> = queues.keys().cloned().collect();
+        let store = Arc::new(Store {
+            path: path.to_path_buf(),
+            key,
+            queues: Mutex::new(queues),
+            dirty: Default::default(),
+        });
+        for node in nodes {
+            store.save(&node);
+        }
+
+        let (saver, saved_nodes) = channel::<NodeId>();
+        let saver_store = Arc::clone(&store);
+        let saver_thread = thread::Builder::new()
+            .name("pending messages".to_string())
+            .spawn(move || {
+                for node in saved_nodes {
+                    saver_store.dirty.lock().remove(&node);
+                    saver_store.save(&node);
+                }
+            })
+            .map_err(|err| format!("Cannot start the pending message saver: {:?}", err))?;
+
+        Ok(Self {
+            store,
+            limits,
+            saver: Mutex::new(Some(saver)),
+            saver_thread: Some(saver_thread),
+        })
+    }
+
+    /// Keeps the message until the node connects again or the message expires.
+    pub fn push(&self, node: &NodeId, extension_name: &str, data: Vec<u8>, ttl: Duration) {
+        let now = now();
+        let message = PendingMessage {
+            extension_name: extension_name.to_string(),
+            data,
+            expires_at: now + ttl.num_seconds().max(0) as u64,
+        };
+        if message.size() > self.limits.bytes {
+            cdebug!(NETAPI, "The message to {} is too large to keep", node.into_addr());
+            return
+        }
+        {
+            let mut queues = self.store.queues.lock();
+            if !queues.contains_key(node) && queues.len() >= self.limits.nodes {
+                queues.retain(|_, queue| {
+                    queue.drop_expired(now);
+                    !queue.is_empty()
+                });
+                if queues.len() >= self.limits.nodes {
+                    cdebug!(NETAPI, "Too many nodes have pending messages. Drops the message to {}", node.into_addr());
+                    return
+                }
+            }
+            let queue = queues.entry(*node).or_insert_with(Queue::default);
+            queue.drop_expired(now);
+            while queue.messages.len() >= self.limits.messages || queue.bytes + message.size() > self.limits.bytes {
+                cdebug!(NETAPI, "The pending messages to {} are full. Drops the oldest one", node.into_addr());
+                queue.pop_front();
+            }
+            queue.push(message);
+        }
+        self.save_later(node);
+    }
+
+    /// Takes the unexpired messages of the extension to the node, in the order they were pushed.
+    pub fn take(&self, node: &NodeId, extension_name: &str) -> Vec<Vec<u8>> {
+        let now = now();
+        let messages = {
+            let mut queues = self.store.queues.lock();
+            let (taken, kept): (VecDeque<_>, VecDeque<_>) = match queues.get_mut(node) {
+                Some(queue) => queue
+                    .messages
+                    .drain(..)
+                    .filter(|message| message.expires_at > now)
+                    .partition(|message| message.extension_name == extension_name),
+                None => return Vec::new(),
+            };
+            if kept.is_empty() {
+                queues.remove(node);
+            } else {
+                queues.insert(*node, Queue::new(kept));
+            }
+            taken.into_iter().map(|message| message.data).collect()
+        };
+        self.save_later(node);
+        messages
+    }
+
+    fn save_later(&self, node: &NodeId) {
+        if !self.store.dirty.lock().insert(*node) {
+            return
+        }
+        if let Some(saver) = self.saver.lock().as_ref() {
+            if saver.send(*node).is_err() {
+                cwarn!(NETAPI, "Cannot save the pending messages to {}", node.into_addr());
+            }
+        }
+    }
+}
+
+/// Waits for the saver thread to write the changes.
+impl Drop for PendingMessages {
+    fn drop(&mut self) {
+        self.saver.lock().take();
+        if let Some(saver_thread) = self.saver_thread.take() {
+            if saver_thread.join().is_err() {
+                cwarn!(NETAPI, "The pending message saver has panicked");
+            }
+        }
+    }
+}
+
+impl Store {
+    fn file_path(&self, node: &NodeId) -> PathBuf {
+        let addr = node.into_addr();
+        // The colons of IPv6 addresses are not allowed in file names on some platforms.
+        let name = format!("{}-{}.{}", addr.ip(), addr.port(), QUEUE_FILE_EXTENSION).replace(':', "_");
+        self.path.join(name)
+    }
+
+    /// Writes the current queue of the node. The queue is encoded under the lock, which is bounded by the limits,
+    /// and the file is written after the lock is released.
+    fn save(&self, node: &NodeId) {
+        let encoded = {
+            let queues = self.queues.lock();
+            queues.get(node).map(|queue| encode_queue(node, &queue.messages))
+        };
+        let path = self.file_path(node);
+        let result = match encoded {
+            None => match fs::remove_file(&path) {
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result.map_err(|err| format!("{:?}", err)),
+            },
+            Some(encoded) => encrypt(&self.key, &encoded).and_then(|encrypted| {
+                // The file is replaced at once, so a crash doesn't leave a half written queue.
+                let temp_path = path.with_extension("tmp");
+                fs::write(&temp_path, encrypted)
+                    .and_then(|_| fs::rename(&temp_path, &path))
+                    .map_err(|err| format!("{:?}", err))
+            }),
+        };
+        if let Err(err) = result {
+            cwarn!(NETAPI, "Cannot save the pending messages to {:?}: {}", path, err);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("The current time is after the Unix epoch").as_secs()
+}
+
+/// Reads the key, or generates it if the file doesn't exist yet. Only the owner can read the file.
+fn load_key(path: &Path) -> Result<H256, String> {
+    match fs::read(path) {
+        Ok(ref bytes) if bytes.len() == 32 => return Ok(H256::from_slice(bytes)),
+        Ok(_) => return Err(format!("The pending message key {:?} is broken", path)),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(format!("Cannot read the pending message key {:?}: {:?}", path, err)),
+    }
+    let mut rng = OsRng::new().map_err(|err| format!("Cannot generate the pending message key: {:?}", err))?;
+    let mut key = [0u8; 32];
+    rng.fill(&mut key);
+    fs::write(path, &key).map_err(|err| format!("Cannot write the pending message key {:?}: {:?}", path, err))?;
+    restrict_permissions(path)?;
+    Ok(H256::from_slice(&key))
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .map_err(|err| format!("Cannot restrict the permissions of {:?}: {:?}", path, err))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// A random IV followed by the encrypted data.
+fn encrypt(key: &H256, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut rng = OsRng::new().map_err(|err| format!("{:?}", err))?;
+    let mut iv = [0u8; 16];
+    rng.fill(&mut iv);
+    let encrypted = aes::encrypt(data, key, &H128::from_slice(&iv)).map_err(|err| format!("{:?}", err))?;
+    let mut result = iv.to_vec();
+    result.extend_from_slice(&encrypted);
+    Ok(result)
+}
+
+fn decrypt(key: &H256, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() < 16 {
+        return Err("Too short".to_string())
+    }
+    let iv = H128::from_slice(&bytes[..16]);
+    aes::decrypt(&bytes[16..], key, &iv).map_err(|err| format!("{:?}", err))
+}
+
+fn encode_queue(node: &NodeId, queue: &VecDeque<PendingMessage>) -> Vec<u8> {
+    let mut s = RlpStream::new_list(2);
+    s.append(node);
+    s.begin_list(queue.len());
+    for message in queue {
+        s.append(message);
+    }
+    s.out()
+}
+
+fn decode_queue(bytes: &[u8]) -> Result<(NodeId, VecDeque<PendingMessage>), DecoderError> {
+    let rlp = UntrustedRlp::new_checked(bytes)?;
+    if rlp.item_count()? != 2 {
+        return Err(DecoderError::RlpIncorrectListLen)
+    }
+    let node = rlp.val_at(0)?;
+    let queue = rlp.list_at(1)?;
+    Ok((node, queue.into_iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::super::SocketAddr;
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("codechain-pending-messages-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn queue_files(dir: &TempDir) -> Vec<Vec<u8>> {
+        fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().and_then(|extension| extension.to_str()) == Some(QUEUE_FILE_EXTENSION))
+            .map(|path| fs::read(path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn take_only_the_messages_of_the_extension() {
+        let dir = TempDir::new("take");
+        let pending = PendingMessages::load(&dir.0).unwrap();
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+
+        pending.push(&node, "a", vec![1], Duration::seconds(30));
+        pending.push(&node, "b", vec![2], Duration::seconds(30));
+        pending.push(&node, "a", vec![3], Duration::seconds(30));
+
+        assert_eq!(vec![vec![1], vec![3]], pending.take(&node, "a"));
+        assert!(pending.take(&node, "a").is_empty());
+        assert_eq!(vec![vec![2]], pending.take(&node, "b"));
+        drop(pending);
+        assert!(queue_files(&dir).is_empty());
+    }
+
+    #[test]
+    fn expired_messages_are_dropped() {
+        let dir = TempDir::new("expired");
+        let pending = PendingMessages::load(&dir.0).unwrap();
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+
+        pending.push(&node, "a", vec![1], Duration::seconds(0));
+        pending.push(&node, "a", vec![2], Duration::seconds(30));
+
+        assert_eq!(vec![vec![2]], pending.take(&node, "a"));
+    }
+
+    #[test]
+    fn the_oldest_message_is_dropped_from_the_full_queue() {
+        let dir = TempDir::new("full");
+        let limits = Limits {
+            messages: 2,
+            ..Default::default()
+        };
+        let pending = PendingMessages::load_with_limits(&dir.0, limits).unwrap();
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+
+        pending.push(&node, "a", vec![1], Duration::seconds(30));
+        pending.push(&node, "a", vec![2], Duration::seconds(30));
+        pending.push(&node, "a", vec![3], Duration::seconds(30));
+
+        assert_eq!(vec![vec![2], vec![3]], pending.take(&node, "a"));
+    }
+
+    #[test]
+    fn messages_survive_reload() {
+        let dir = TempDir::new("reload");
+        let node1: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+        let node2: NodeId = SocketAddr::v4(10, 0, 0, 2, 3485).into();
+        {
+            let pending = PendingMessages::load(&dir.0).unwrap();
+            pending.push(&node1, "a", vec![1], Duration::seconds(30));
+            pending.push(&node2, "a", vec![2], Duration::seconds(0));
+        }
+
+        let pending = PendingMessages::load(&dir.0).unwrap();
+        assert_eq!(vec![vec![1]], pending.take(&node1, "a"));
+        assert!(pending.take(&node2, "a").is_empty());
+        drop(pending);
+        assert!(queue_files(&dir).is_empty());
+    }
+
+    #[test]
+    fn the_oldest_messages_are_dropped_to_keep_the_size() {
+        let dir = TempDir::new("size");
+        let limits = Limits {
+            bytes: 8,
+            ..Default::default()
+        };
+        let pending = PendingMessages::load_with_limits(&dir.0, limits).unwrap();
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+
+        pending.push(&node, "a", vec![1, 1, 1], Duration::seconds(30));
+        pending.push(&node, "a", vec![2, 2, 2], Duration::seconds(30));
+        pending.push(&node, "a", vec![3, 3, 3], Duration::seconds(30));
+        pending.push(&node, "a", vec![4; 8], Duration::seconds(30));
+
+        assert_eq!(vec![vec![2, 2, 2], vec![3, 3, 3]], pending.take(&node, "a"));
+    }
+
+    #[test]
+    fn messages_to_too_many_nodes_are_dropped() {
+        let dir = TempDir::new("nodes");
+        let limits = Limits {
+            nodes: 1,
+            ..Default::default()
+        };
+        let pending = PendingMessages::load_with_limits(&dir.0, limits).unwrap();
+        let node1: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+        let node2: NodeId = SocketAddr::v4(10, 0, 0, 2, 3485).into();
+
+        pending.push(&node1, "a", vec![1], Duration::seconds(30));
+        pending.push(&node2, "a", vec![2], Duration::seconds(30));
+        assert!(pending.take(&node2, "a").is_empty());
+
+        assert_eq!(vec![vec![1]], pending.take(&node1, "a"));
+        pending.push(&node2, "a", vec![2], Duration::seconds(30));
+        assert_eq!(vec![vec![2]], pending.take(&node2, "a"));
+    }
+
+    #[test]
+    fn saved_messages_are_encrypted() {
+        let dir = TempDir::new("encrypted");
+        let node: NodeId = SocketAddr::v4(127, 0, 0, 1, 3485).into();
+        let secret = b"the secret payload".to_vec();
+        {
+            let pending = PendingMessages::load(&dir.0).unwrap();
+            pending.push(&node, "a", secret.clone(), Duration::seconds(30));
+        }
+
+        let files = queue_files(&dir);
+        assert_eq!(1, files.len());
+        assert!(!files[0].windows(secret.len()).any(|window| window == &secret[..]));
+
+        fs::remove_file(dir.0.join(KEY_FILE_NAME)).unwrap();
+        let pending = PendingMessages::load(&dir.0).unwrap();
+        assert!(pending.take(&node, "a").is_empty());
+    }
+}
@@ -49,9 +49,17 @@ enum State {
     Banned,
 }
 
+/// The number of the verified nodes remembered after they are disconnected.
+const MAX_VERIFIED_NODES: usize = 8192;
+
 pub struct RoutingTable {
     entries: RwLock<HashMap<NodeId, Mutex<Cell<State>>>>,
 
+    // The nodes which are known to accept connections at their addresses and are not banned:
+    // the ones this node has dialed, and the inbound ones whose advertised addresses are probed.
+    // They are kept after the connections are closed.
+    verified: RwLock<HashSet<NodeId>>,
+
     // remote node id => local node id
     // One node can have multiple node ids because the machine can has a multiple ip addresses
     // This field represents the local node id that remote node thinks.
@@ -64,6 +72,7 @@ impl RoutingTable {
     pub fn new() -> Arc<Self> {
         Arc::new(Self {
             entries: RwLock::new(HashMap::new()),
+            verified: RwLock::new(HashSet::new()),
             remote_to_local_node_ids: RwLock::new(HashMap::new()),
             rng: Mutex::new(OsRng::new().unwrap()),
         })
@@ -120,6 +129,37 @@ impl RoutingTable {
         entries.keys().map(|node_id| node_id.into_addr()).filter(|addr| from.is_reachable(addr)).collect()
     }
 
+    /// The addresses of the nodes which are known to accept connections.
+    pub fn verified_addresses(&self, from: &SocketAddr) -> HashSet<SocketAddr> {
+        let verified = self.verified.read();
+        verified.iter().map(|node_id| node_id.into_addr()).filter(|addr| from.is_reachable(addr)).collect()
+    }
+
+    pub fn is_verified(&self, remote_address: &SocketAddr) -> bool {
+        self.verified.read().contains(&remote_address.into())
+    }
+
+    /// Marks the node as accepting connections at the address, unless it's banned.
+    pub fn verify(&self, remote_address: &SocketAddr) {
+        let node_id = remote_address.into();
+        let entries = self.entries.read();
+        if let Some(entry) = entries.get(&node_id) {
+            let entry = entry.lock();
+            let state = entry.replace(State::Intermediate);
+            let is_banned = state == State::Banned;
+            entry.set(state);
+            if is_banned {
+                return
+            }
+        }
+        let mut verified = self.verified.write();
+        if verified.len() >= MAX_VERIFIED_NODES && !verified.contains(&node_id) {
+            let evicted = *verified.iter().next().expect("MAX_VERIFIED_NODES is not zero; qed");
+            verified.remove(&evicted);
+        }
+        verified.insert(node_id);
+    }
+
     pub fn is_connected(&self, addr: &SocketAddr) -> bool {
         let entries = self.entries.read();
         if let Some(entry) = entries.get(&addr.into()) {
@@ -413,6 +453,7 @@ impl RoutingTable {
     pub fn ban(&self, remote_address: &SocketAddr) -> bool {
         let entries = self.entries.read();
         let remote_node_id = remote_address.into();
+        self.verified.write().remove(&remote_node_id);
         if let Some(entry) = entries.get(&remote_node_id) {
             let entry = entry.lock();
             entry.set(State::Banned);
@@ -533,3 +574,23 @@ fn encode_and_encrypt_nonce(session: &Session, nonce: &Nonce) -> Option<Vec<u8>>
         })
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banned_node_is_not_verified() {
+        let table = RoutingTable::new();
+        let address = SocketAddr::v4(127, 0, 0, 1, 3485);
+        table.verify(&address);
+        assert!(table.is_verified(&address));
+        assert!(table.verified_addresses(&SocketAddr::v4(127, 0, 0, 1, 3486)).contains(&address));
+
+        table.add_candidate(address);
+        table.ban(&address);
+        assert!(!table.is_verified(&address));
+        table.verify(&address);
+        assert!(!table.is_verified(&address), "A probe which ends after the ban doesn't verify the node");
+    }
+}
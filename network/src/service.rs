@@ -15,23 +15,32 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 
+use std::collections::HashMap;
 use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use cio::{IoError, IoService};
+use ckey::{KeyPair, Signature};
+use parking_lot::RwLock;
 use primitives::H256;
 
 use super::client::Client;
-use super::control::{Control, Error as ControlError};
-use super::filters::FiltersControl;
+use super::control::{Control, Error as ControlError, LocalInfo};
+use super::extension_stats::ExtensionStats;
+use super::filters::{FilterEntry, FiltersControl};
+use super::node_id::IntoSocketAddr;
 use super::p2p;
+use super::pending_messages::PendingMessages;
 use super::routing_table::RoutingTable;
 use super::session_initiator;
 use super::timer;
 use super::DiscoveryApi;
-use super::{NetworkExtension, SocketAddr};
+use super::{ChainIdentity, NetworkExtension, NodeAllowList, SocketAddr};
 
 pub struct Service {
+    address: SocketAddr,
+    node_key: KeyPair,
     session_initiator: IoService<session_initiator::Message>,
     p2p: IoService<p2p::Message>,
     #[allow(dead_code)]
@@ -40,30 +49,52 @@ pub struct Service {
     routing_table: Arc<RoutingTable>,
     p2p_handler: Arc<p2p::Handler>,
     filters_control: Arc<FiltersControl>,
+    allow_list: Option<Arc<NodeAllowList>>,
+    discovery: RwLock<Option<Arc<DiscoveryApi>>>,
 }
 
 impl Service {
     pub fn start(
         address: SocketAddr,
         min_peers: usize,
-        max_peers: usize,
+        max_inbound_peers: usize,
+        max_outbound_peers: usize,
+        trusted_nodes: Vec<IpAddr>,
         filters_control: Arc<FiltersControl>,
+        allow_list: Option<Arc<NodeAllowList>>,
+        pending_messages: Option<Arc<PendingMessages>>,
+        node_key: KeyPair,
+        proxy: Option<SocketAddr>,
+        hide_address: bool,
+        dual_stack: bool,
+        probe_inbound: bool,
+        io_workers: usize,
+        chain: ChainIdentity,
     ) -> Result<Arc<Self>, Error> {
-        let p2p = IoService::start()?;
+        // The handlers of the extensions run on the workers of the p2p service.
+        let p2p = IoService::with_workers(io_workers)?;
         let timer = IoService::start()?;
         let session_initiator = IoService::start()?;
 
         let routing_table = RoutingTable::new();
 
-        let client = Client::new(p2p.channel(), timer.channel());
+        let client = Client::new(p2p.channel(), timer.channel(), pending_messages);
 
         let p2p_handler = Arc::new(p2p::Handler::try_new(
             address,
             Arc::clone(&client),
             Arc::clone(&routing_table),
             Arc::clone(&filters_control),
+            allow_list.clone(),
+            chain,
             min_peers,
-            max_peers,
+            max_inbound_peers,
+            max_outbound_peers,
+            trusted_nodes,
+            proxy,
+            hide_address,
+            dual_stack,
+            probe_inbound,
         )?);
         p2p.register_handler(p2p_handler.clone())?;
 
@@ -74,10 +105,14 @@ impl Service {
             Arc::clone(&routing_table),
             p2p.channel(),
             Arc::clone(&filters_control),
+            hide_address,
+            dual_stack,
         ));
         session_initiator.register_handler(session_initiator_handler)?;
 
         Ok(Arc::new(Self {
+            address,
+            node_key,
             session_initiator,
             p2p,
             timer,
@@ -85,13 +120,19 @@ impl Service {
             routing_table,
             p2p_handler,
             filters_control,
+            allow_list,
+            discovery: RwLock::new(None),
         }))
     }
 
+    /// Registers the extension. It is not initialized until `initialize_extensions` is called.
     pub fn register_extension(&self, extension: Arc<NetworkExtension>) {
-        let extension_name = extension.name();
         self.client.register_extension(extension);
-        self.client.initialize_extension(extension_name);
+    }
+
+    /// Initializes the registered extensions in the order of their dependencies.
+    pub fn initialize_extensions(&self) {
+        self.client.initialize_extensions();
     }
 
     pub fn connect_to(&self, address: SocketAddr) -> Result<(), String> {
@@ -102,8 +143,9 @@ impl Service {
         }
     }
 
-    pub fn set_routing_table(&self, disc: &DiscoveryApi) {
+    pub fn set_discovery(&self, disc: Arc<DiscoveryApi>) {
         disc.set_routing_table(Arc::clone(&self.routing_table));
+        *self.discovery.write() = Some(disc);
     }
 }
 
@@ -142,6 +184,13 @@ impl Control for Service {
         Ok(self.p2p_handler.get_port())
     }
 
+    fn local_info(&self) -> Result<LocalInfo, ControlError> {
+        Ok(LocalInfo {
+            address: self.address,
+            node_key: *self.node_key.public(),
+        })
+    }
+
     fn get_peer_count(&self) -> Result<usize, ControlError> {
         Ok(self.p2p_handler.get_peer_count())
     }
@@ -150,29 +199,33 @@ impl Control for Service {
         Ok(self.p2p_handler.established_peers())
     }
 
-    fn add_to_whitelist(&self, addr: IpAddr) -> Result<(), ControlError> {
-        self.filters_control.add_to_whitelist(addr);
+    fn peer_rtts(&self) -> Result<HashMap<SocketAddr, Duration>, ControlError> {
+        Ok(self.p2p_handler.peer_rtts())
+    }
+
+    fn add_to_whitelist(&self, entry: FilterEntry) -> Result<(), ControlError> {
+        self.filters_control.add_to_whitelist(entry);
         Ok(())
     }
 
-    fn remove_from_whitelist(&self, addr: &IpAddr) -> Result<(), ControlError> {
-        self.filters_control.remove_from_whitelist(addr);
+    fn remove_from_whitelist(&self, entry: &FilterEntry) -> Result<(), ControlError> {
+        self.filters_control.remove_from_whitelist(entry);
         if let Err(err) = self.p2p.send_message(p2p::Message::ApplyFilters) {
             cerror!(NETWORK, "Error occurred while apply filters: {:?}", err);
         }
         Ok(())
     }
 
-    fn add_to_blacklist(&self, addr: IpAddr) -> Result<(), ControlError> {
-        self.filters_control.add_to_blacklist(addr);
+    fn add_to_blacklist(&self, entry: FilterEntry) -> Result<(), ControlError> {
+        self.filters_control.add_to_blacklist(entry);
         if let Err(err) = self.p2p.send_message(p2p::Message::ApplyFilters) {
             cerror!(NETWORK, "Error occurred while apply filters: {:?}", err);
         }
         Ok(())
     }
 
-    fn remove_from_blacklist(&self, addr: &IpAddr) -> Result<(), ControlError> {
-        self.filters_control.remove_from_blacklist(addr);
+    fn remove_from_blacklist(&self, entry: &FilterEntry) -> Result<(), ControlError> {
+        self.filters_control.remove_from_blacklist(entry);
         Ok(())
     }
 
@@ -202,13 +255,58 @@ impl Control for Service {
         Ok(())
     }
 
-    fn get_whitelist(&self) -> Result<(Vec<IpAddr>, bool), ControlError> {
+    fn get_whitelist(&self) -> Result<(Vec<FilterEntry>, bool), ControlError> {
         Ok(self.filters_control.get_whitelist())
     }
 
-    fn get_blacklist(&self) -> Result<(Vec<IpAddr>, bool), ControlError> {
+    fn get_blacklist(&self) -> Result<(Vec<FilterEntry>, bool), ControlError> {
         Ok(self.filters_control.get_blacklist())
     }
+
+    fn set_node_allow_list(
+        &self,
+        version: u64,
+        nodes: Vec<SocketAddr>,
+        signature: Signature,
+    ) -> Result<(), ControlError> {
+        let allow_list = self.allow_list.as_ref().ok_or(ControlError::NoNodeAllowList)?;
+        let nodes = nodes.into_iter().map(Into::into).collect();
+        allow_list.update(version, nodes, &signature).map_err(ControlError::InvalidNodeAllowList)?;
+        if let Err(err) = self.p2p.send_message(p2p::Message::ApplyFilters) {
+            cerror!(NETWORK, "Error occurred while apply filters: {:?}", err);
+        }
+        Ok(())
+    }
+
+    fn get_node_allow_list(&self) -> Result<(Option<u64>, Vec<SocketAddr>), ControlError> {
+        let allow_list = self.allow_list.as_ref().ok_or(ControlError::NoNodeAllowList)?;
+        Ok((allow_list.version(), allow_list.nodes().into_iter().map(IntoSocketAddr::into_addr).collect()))
+    }
+
+    fn extension_stats(&self) -> Result<HashMap<String, ExtensionStats>, ControlError> {
+        Ok(self.client.extension_stats())
+    }
+
+    fn refresh_discovery(&self) -> Result<(), ControlError> {
+        let discovery = self.discovery.read();
+        discovery.as_ref().ok_or(ControlError::Disabled)?.refresh();
+        Ok(())
+    }
+
+    fn set_peer_limits(
+        &self,
+        min_peers: usize,
+        max_inbound_peers: usize,
+        max_outbound_peers: usize,
+    ) -> Result<(), ControlError> {
+        self.p2p_handler
+            .set_peer_limits(min_peers, max_inbound_peers, max_outbound_peers)
+            .map_err(ControlError::InvalidPeerLimits)?;
+        if let Err(err) = self.p2p.send_message(p2p::Message::PullConnections) {
+            cerror!(NETWORK, "Error occurred while pulling connections: {:?}", err);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -90,6 +90,8 @@ struct SessionInitiator {
     requests: Requests,
     channel_to_p2p: IoChannel<p2p::Message>,
     filters: Arc<FiltersControl>,
+    /// Neither sends nor answers the UDP messages to keep the local address unknown.
+    hide_address: bool,
 }
 
 #[derive(Debug)]
@@ -196,14 +198,17 @@ impl SessionInitiator {
         routing_table: Arc<RoutingTable>,
         channel_to_p2p: IoChannel<p2p::Message>,
         filters: Arc<FiltersControl>,
+        hide_address: bool,
+        dual_stack: bool,
     ) -> Result<Self> {
-        let server = Server::bind(socket_address)?;
+        let server = Server::bind(socket_address, dual_stack)?;
         Ok(Self {
             server,
             routing_table,
             requests: Requests::new(),
             channel_to_p2p,
             filters,
+            hide_address,
         })
     }
 
@@ -234,6 +239,10 @@ impl SessionInitiator {
     }
 
     fn create_new_connection(&mut self, target: &SocketAddr, io: &IoContext<Message>) -> Result<()> {
+        if self.hide_address {
+            ctrace!(NETWORK, "The session to {} is not initiated to hide the local address", target);
+            return Ok(())
+        }
         let seq = self.requests.gen(*target)?;
         io.register_timer_once(seq, MESSAGE_TIMEOUT_MS)?;
         let message = message::Message::node_id_request(seq as u64, target.into());
@@ -242,6 +251,10 @@ impl SessionInitiator {
     }
 
     fn on_packet(&mut self, message: &message::Message, from: &SocketAddr, io: &IoContext<Message>) -> Result<()> {
+        if self.hide_address {
+            ctrace!(NETWORK, "The message from {} is ignored to hide the local address", from);
+            return Ok(())
+        }
         match message.body() {
             message::Body::NodeIdRequest(responder_node_id) => {
                 if !self.routing_table.add_node(from, *responder_node_id) {
@@ -407,9 +420,11 @@ impl Handler {
         routing_table: Arc<RoutingTable>,
         channel_to_p2p: IoChannel<p2p::Message>,
         filters: Arc<FiltersControl>,
+        hide_address: bool,
+        dual_stack: bool,
     ) -> Self {
         let session_initiator = RwLock::new(
-            SessionInitiator::bind(&socket_address, routing_table, channel_to_p2p, filters)
+            SessionInitiator::bind(&socket_address, routing_table, channel_to_p2p, filters, hide_address, dual_stack)
                 .expect("Cannot bind UDP port"),
         );
         Self {
@@ -466,7 +481,6 @@ impl IoHandler<Message> for Handler {
             }
             Message::ManuallyConnectTo(socket_address) => {
                 let mut session_initiator = self.session_initiator.write();
-                session_initiator.filters.add_to_whitelist(socket_address.ip());
                 session_initiator.routing_table.unban(&socket_address);
                 session_initiator.routing_table.add_candidate(*socket_address);
                 session_initiator.requests.manually_connected_address.insert(*socket_address);
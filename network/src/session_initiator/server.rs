@@ -82,8 +82,8 @@ pub struct Server {
 }
 
 impl Server {
-    pub fn bind(socket_address: &SocketAddr) -> Result<Server> {
-        let socket = Socket::bind(socket_address)?;
+    pub fn bind(socket_address: &SocketAddr, dual_stack: bool) -> Result<Server> {
+        let socket = Socket::bind(socket_address, dual_stack)?;
         Ok(Self {
             socket,
             queue: VecDeque::new(),
@@ -17,10 +17,12 @@
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
+use std::net::{self, IpAddr};
 
 use mio::event::Evented;
 use mio::net::UdpSocket;
 use mio::{Poll, PollOpt, Ready, Token};
+use net2::UdpBuilder;
 use rlp::{Decodable, DecoderError, Encodable, UntrustedRlp};
 
 use super::super::SocketAddr;
@@ -39,6 +41,8 @@ pub type Result<T> = ::std::result::Result<T, Error>;
 
 pub struct Socket {
     socket: UdpSocket,
+    /// An IPv6 socket can't send to an IPv4 address; it sends to the IPv4-mapped address instead.
+    is_v6: bool,
 }
 
 impl fmt::Display for Error {
@@ -87,14 +91,27 @@ impl From<DecoderError> for Error {
 }
 const MAX_PACKET_SIZE: usize = 1024;
 impl Socket {
-    pub fn bind(socket_address: &SocketAddr) -> Result<Self> {
-        let socket = UdpSocket::bind(socket_address.into())?;
+    /// An IPv6 socket also receives the IPv4 packets if `dual_stack` is true.
+    pub fn bind(socket_address: &SocketAddr, dual_stack: bool) -> Result<Self> {
+        let (socket, is_v6) = match socket_address.ip() {
+            IpAddr::V4(_) => (UdpSocket::bind(socket_address.into())?, false),
+            IpAddr::V6(_) => {
+                let builder = UdpBuilder::new_v6()?;
+                builder.only_v6(!dual_stack)?;
+                let address: &net::SocketAddr = socket_address.into();
+                (UdpSocket::from_socket(builder.bind(address)?)?, true)
+            }
+        };
         Ok(Self {
             socket,
+            is_v6,
         })
     }
 
     fn write_bytes(&self, message: &[u8], target: &SocketAddr) -> Result<usize> {
+        if self.is_v6 {
+            return Ok(self.socket.send_to(&message, &target.to_ipv6_mapped())?)
+        }
         Ok(self.socket.send_to(&message, target.into())?)
     }
 
@@ -140,7 +157,7 @@ impl Socket {
         match result {
             None => Ok(None),
             Some((bytes, target)) => {
-                let rlp = UntrustedRlp::new(&bytes);
+                let rlp = UntrustedRlp::new_checked(&bytes)?;
                 Ok(Some((rlp.as_val::<M>()?, target)))
             }
         }
@@ -17,6 +17,7 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::ops::Deref;
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 
 use parking_lot::Mutex;
 use rlp::Encodable;
@@ -37,14 +38,19 @@ pub enum Call {
         token: TimerToken,
         duration: Duration,
     },
+    SetTimerAt {
+        token: TimerToken,
+        deadline: Instant,
+    },
     ClearTimer(TimerToken),
     SendLocalMessage(Vec<u8>),
+    Disconnect(NodeId),
 }
 
 struct TestApi {
     extension: Weak<Extension>,
 
-    connections: Mutex<HashSet<NodeId>>,
+    connections: Mutex<HashMap<NodeId, u64>>,
     timers: Mutex<HashMap<TimerToken, (Duration, bool)>>,
 
     calls: Mutex<VecDeque<Call>>,
@@ -55,7 +61,7 @@ impl TestApi {
         Arc::new(Self {
             extension,
 
-            connections: Mutex::new(HashSet::new()),
+            connections: Mutex::new(HashMap::new()),
             timers: Mutex::new(HashMap::new()),
 
             calls: Mutex::new(VecDeque::new()),
@@ -68,8 +74,9 @@ impl TestApi {
 }
 
 impl Api for TestApi {
-    fn send(&self, node: &NodeId, message: &[u8]) {
+    fn send(&self, node: &NodeId, message: &[u8]) -> Result<()> {
         self.calls.lock().push_back(Call::Send(*node, message.to_vec()));
+        Ok(())
     }
 
     fn set_timer(&self, token: TimerToken, duration: Duration) -> Result<()> {
@@ -98,6 +105,25 @@ impl Api for TestApi {
         Ok(())
     }
 
+    fn set_timer_at(&self, token: TimerToken, deadline: Instant) -> Result<()> {
+        let mut timers = self.timers.lock();
+        if timers.contains_key(&token) {
+            panic!("Tried to set timer with token #{} twice", token);
+        }
+        let now = Instant::now();
+        let duration = if deadline > now {
+            Duration::from_std(deadline - now).expect("The deadline is not too far")
+        } else {
+            Duration::zero()
+        };
+        timers.insert(token, (duration, true));
+        self.calls.lock().push_back(Call::SetTimerAt {
+            token,
+            deadline,
+        });
+        Ok(())
+    }
+
     fn clear_timer(&self, token: TimerToken) -> Result<()> {
         let mut timers = self.timers.lock();
         if timers.contains_key(&token) {
@@ -111,11 +137,19 @@ impl Api for TestApi {
         let message = message.rlp_bytes().into_vec();
         self.calls.lock().push_back(Call::SendLocalMessage(message));
     }
+
+    fn negotiated_version(&self, node: &NodeId) -> Option<u64> {
+        self.connections.lock().get(node).cloned()
+    }
+
+    fn disconnect(&self, node: &NodeId) {
+        self.calls.lock().push_back(Call::Disconnect(*node));
+    }
 }
 
 impl TestApi {
     fn remove_node(&self, node: NodeId) {
-        if !self.connections.lock().remove(&node) {
+        if self.connections.lock().remove(&node).is_none() {
             panic!("Tried to remove unregistered node #{}", node);
         }
         self.extension().on_node_removed(&node);
@@ -123,16 +157,17 @@ impl TestApi {
 
     fn add_node(&self, node: NodeId) {
         let mut connections = self.connections.lock();
-        if connections.contains(&node) {
+        if connections.contains_key(&node) {
             panic!("Duplicated connection detected for node #{}", node);
         }
-        connections.insert(node);
         let version = *self.extension().versions().iter().max().unwrap();
+        connections.insert(node, version);
+        drop(connections);
         self.extension().on_node_added(&node, version);
     }
 
     fn send_message(&self, from: NodeId, message: &[u8]) {
-        if !self.connections.lock().contains(&from) {
+        if !self.connections.lock().contains_key(&from) {
             panic!("Tried to inject message from unconnected node #{}", from);
         }
         self.extension().on_message(&from, &message.to_vec());
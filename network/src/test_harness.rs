@@ -0,0 +1,505 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A deterministic simulation of nodes which run network extensions.
+//!
+//! The nodes share a virtual clock and exchange messages through an in-memory router.
+//! Nothing runs until the test delivers the messages or advances the clock,
+//! so the same sequence of calls always makes the same callbacks in the same order.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
+
+use parking_lot::Mutex;
+use rlp::Encodable;
+use time::Duration;
+
+use super::extension::{Api, Error, Extension, Result, TimerToken};
+use super::NodeId;
+
+/// The simulation panics if this many callbacks run without reaching a quiescent state.
+const MAX_STEPS: usize = 100_000;
+
+enum Event {
+    Message {
+        from: NodeId,
+        to: NodeId,
+        extension: &'static str,
+        data: Vec<u8>,
+    },
+    Local {
+        node: NodeId,
+        extension: &'static str,
+        data: Vec<u8>,
+    },
+    Disconnect {
+        a: NodeId,
+        b: NodeId,
+    },
+}
+
+struct Timer {
+    deadline: Instant,
+    period: Option<StdDuration>,
+    seq: u64,
+}
+
+type TimerKey = (NodeId, &'static str, TimerToken);
+
+struct Shared {
+    now: Mutex<Instant>,
+    timers: Mutex<HashMap<TimerKey, Timer>>,
+    next_timer_seq: Mutex<u64>,
+    events: Mutex<VecDeque<Event>>,
+    /// (local node, extension, remote node) => the negotiated version
+    versions: Mutex<HashMap<(NodeId, &'static str, NodeId), u64>>,
+}
+
+impl Shared {
+    fn now(&self) -> Instant {
+        *self.now.lock()
+    }
+
+    fn insert_timer(&self, key: TimerKey, deadline: Instant, period: Option<StdDuration>) -> Result<()> {
+        let mut timers = self.timers.lock();
+        if timers.contains_key(&key) {
+            return Err(Error::DuplicatedTimerId)
+        }
+        let seq = self.take_timer_seq();
+        timers.insert(
+            key,
+            Timer {
+                deadline,
+                period,
+                seq,
+            },
+        );
+        Ok(())
+    }
+
+    fn take_timer_seq(&self) -> u64 {
+        let mut next_timer_seq = self.next_timer_seq.lock();
+        let seq = *next_timer_seq;
+        *next_timer_seq += 1;
+        seq
+    }
+
+    fn is_connected(&self, node: &NodeId, extension: &'static str, remote: &NodeId) -> bool {
+        self.versions.lock().contains_key(&(*node, extension, *remote))
+    }
+}
+
+struct SimulatedApi {
+    node: NodeId,
+    extension: &'static str,
+    shared: Arc<Shared>,
+}
+
+impl Api for SimulatedApi {
+    fn send(&self, node: &NodeId, message: &[u8]) -> Result<()> {
+        if self.shared.is_connected(&self.node, self.extension, node) {
+            self.shared.events.lock().push_back(Event::Message {
+                from: self.node,
+                to: *node,
+                extension: self.extension,
+                data: message.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    fn set_timer(&self, timer: TimerToken, d: Duration) -> Result<()> {
+        let period = to_std(d);
+        let deadline = self.shared.now() + period;
+        self.shared.insert_timer((self.node, self.extension, timer), deadline, Some(period))
+    }
+
+    fn set_timer_once(&self, timer: TimerToken, d: Duration) -> Result<()> {
+        let deadline = self.shared.now() + to_std(d);
+        self.shared.insert_timer((self.node, self.extension, timer), deadline, None)
+    }
+
+    fn set_timer_at(&self, timer: TimerToken, deadline: Instant) -> Result<()> {
+        let deadline = ::std::cmp::max(deadline, self.shared.now());
+        self.shared.insert_timer((self.node, self.extension, timer), deadline, None)
+    }
+
+    fn clear_timer(&self, timer: TimerToken) -> Result<()> {
+        match self.shared.timers.lock().remove(&(self.node, self.extension, timer)) {
+            Some(_) => Ok(()),
+            None => Err(Error::UnknownTimerId),
+        }
+    }
+
+    fn send_local_message(&self, message: &Encodable) {
+        self.shared.events.lock().push_back(Event::Local {
+            node: self.node,
+            extension: self.extension,
+            data: message.rlp_bytes().into_vec(),
+        });
+    }
+
+    fn negotiated_version(&self, node: &NodeId) -> Option<u64> {
+        self.shared.versions.lock().get(&(self.node, self.extension, *node)).cloned()
+    }
+
+    fn disconnect(&self, node: &NodeId) {
+        self.shared.events.lock().push_back(Event::Disconnect {
+            a: self.node,
+            b: *node,
+        });
+    }
+}
+
+fn to_std(d: Duration) -> StdDuration {
+    d.to_std().unwrap_or_else(|_| StdDuration::from_secs(0))
+}
+
+/// Runs the extensions of multiple nodes on a virtual clock.
+///
+/// Messages are queued when an extension sends them and are delivered by `step` or `run_until_idle`.
+/// Timers expire only when `advance` moves the clock past their deadlines.
+pub struct Simulation {
+    shared: Arc<Shared>,
+    nodes: BTreeMap<NodeId, Vec<Arc<Extension>>>,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                now: Mutex::new(Instant::now()),
+                timers: Mutex::new(HashMap::new()),
+                next_timer_seq: Mutex::new(0),
+                events: Mutex::new(VecDeque::new()),
+                versions: Mutex::new(HashMap::new()),
+            }),
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    /// The current time of the virtual clock.
+    pub fn now(&self) -> Instant {
+        self.shared.now()
+    }
+
+    /// Adds a node and initializes its extensions in the given order.
+    pub fn add_node(&mut self, node: NodeId, extensions: Vec<Arc<Extension>>) {
+        assert!(!self.nodes.contains_key(&node), "{} is already added", node);
+        for extension in &extensions {
+            extension.on_initialize(Arc::new(SimulatedApi {
+                node,
+                extension: extension.name(),
+                shared: Arc::clone(&self.shared),
+            }));
+        }
+        self.nodes.insert(node, extensions);
+    }
+
+    pub fn extension(&self, node: &NodeId, name: &str) -> Option<Arc<Extension>> {
+        self.nodes.get(node)?.iter().find(|extension| extension.name() == name).cloned()
+    }
+
+    /// Connects two nodes. Each extension which both nodes run negotiates the version as the p2p layer does.
+    pub fn connect(&self, a: &NodeId, b: &NodeId) {
+        assert_ne!(a, b);
+        for extension_a in &self.nodes[a] {
+            let name = extension_a.name();
+            let extension_b = match self.extension(b, name) {
+                Some(extension_b) => extension_b,
+                None => continue,
+            };
+            let version = match extension_a.select_version(b, extension_b.versions()) {
+                Some(version) => version,
+                None => continue,
+            };
+            if extension_b.select_version(a, &[version]).is_none() {
+                continue
+            }
+            {
+                let mut versions = self.shared.versions.lock();
+                versions.insert((*a, name, *b), version);
+                versions.insert((*b, name, *a), version);
+            }
+            extension_a.on_node_added(b, version);
+            extension_b.on_node_added(a, version);
+        }
+    }
+
+    /// Disconnects two nodes. The messages between them which are not delivered yet are dropped.
+    pub fn disconnect(&self, a: &NodeId, b: &NodeId) {
+        self.shared.events.lock().retain(|event| match event {
+            Event::Message {
+                from,
+                to,
+                ..
+            } => !((from == a && to == b) || (from == b && to == a)),
+            Event::Local {
+                ..
+            } => true,
+            Event::Disconnect {
+                ..
+            } => true,
+        });
+        for extension_a in &self.nodes[a] {
+            let name = extension_a.name();
+            let was_connected = {
+                let mut versions = self.shared.versions.lock();
+                versions.remove(&(*b, name, *a));
+                versions.remove(&(*a, name, *b)).is_some()
+            };
+            if was_connected {
+                extension_a.on_node_removed(b);
+                if let Some(extension_b) = self.extension(b, name) {
+                    extension_b.on_node_removed(a);
+                }
+            }
+        }
+    }
+
+    /// The number of the queued messages, including the local ones.
+    pub fn pending_messages(&self) -> usize {
+        self.shared.events.lock().len()
+    }
+
+    /// Delivers the oldest queued message. Returns false if there is no message.
+    pub fn step(&self) -> bool {
+        let event = match self.shared.events.lock().pop_front() {
+            Some(event) => event,
+            None => return false,
+        };
+        match event {
+            Event::Message {
+                from,
+                to,
+                extension,
+                data,
+            } => {
+                if self.shared.is_connected(&to, extension, &from) {
+                    if let Some(extension) = self.extension(&to, extension) {
+                        extension.on_message(&from, &data);
+                    }
+                }
+            }
+            Event::Local {
+                node,
+                extension,
+                data,
+            } => {
+                if let Some(extension) = self.extension(&node, extension) {
+                    extension.on_local_message(&data);
+                }
+            }
+            Event::Disconnect {
+                a,
+                b,
+            } => self.disconnect(&a, &b),
+        }
+        true
+    }
+
+    /// Delivers the messages until no message remains. Returns the number of the delivered messages.
+    pub fn run_until_idle(&self) -> usize {
+        let mut delivered = 0;
+        while self.step() {
+            delivered += 1;
+            assert!(delivered < MAX_STEPS, "The extensions keep sending messages");
+        }
+        delivered
+    }
+
+    /// Advances the clock, expiring the timers in the order of their deadlines.
+    /// The messages are delivered after each expiration.
+    pub fn advance(&self, d: Duration) {
+        self.run_until_idle();
+        let target = self.now() + to_std(d);
+        let mut expired = 0;
+        while let Some((node, name, token)) = self.expire_next_timer(target) {
+            if let Some(extension) = self.extension(&node, name) {
+                extension.on_timeout(token);
+            }
+            self.run_until_idle();
+            expired += 1;
+            assert!(expired < MAX_STEPS, "The timers keep expiring");
+        }
+        *self.shared.now.lock() = target;
+    }
+
+    fn expire_next_timer(&self, until: Instant) -> Option<TimerKey> {
+        let mut timers = self.shared.timers.lock();
+        let key = *timers
+            .iter()
+            .filter(|(_, timer)| timer.deadline <= until)
+            .min_by_key(|(_, timer)| (timer.deadline, timer.seq))
+            .map(|(key, _)| key)?;
+        let deadline = timers[&key].deadline;
+        *self.shared.now.lock() = deadline;
+        match timers[&key].period {
+            Some(period) => {
+                let seq = self.shared.take_timer_seq();
+                let timer = timers.get_mut(&key).expect("The key is found above");
+                timer.deadline = deadline + ::std::cmp::max(period, StdDuration::from_millis(1));
+                timer.seq = seq;
+            }
+            None => {
+                timers.remove(&key);
+            }
+        }
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::RwLock;
+
+    use super::super::SocketAddr;
+    use super::*;
+
+    const PING_TIMER: TimerToken = 0;
+
+    struct PingPong {
+        versions: Vec<u64>,
+        api: RwLock<Option<Arc<Api>>>,
+        peers: Mutex<Vec<NodeId>>,
+        received: Mutex<Vec<(NodeId, Vec<u8>)>>,
+    }
+
+    impl PingPong {
+        fn new(versions: Vec<u64>) -> Arc<Self> {
+            Arc::new(Self {
+                versions,
+                api: RwLock::new(None),
+                peers: Mutex::new(vec![]),
+                received: Mutex::new(vec![]),
+            })
+        }
+
+        fn api(&self) -> Arc<Api> {
+            Arc::clone(self.api.read().as_ref().unwrap())
+        }
+    }
+
+    impl Extension for PingPong {
+        fn name(&self) -> &'static str {
+            "ping-pong"
+        }
+
+        fn need_encryption(&self) -> bool {
+            false
+        }
+
+        fn versions(&self) -> &[u64] {
+            &self.versions
+        }
+
+        fn on_initialize(&self, api: Arc<Api>) {
+            api.set_timer(PING_TIMER, Duration::seconds(1)).unwrap();
+            *self.api.write() = Some(api);
+        }
+
+        fn on_node_added(&self, node: &NodeId, _version: u64) {
+            self.peers.lock().push(*node);
+        }
+
+        fn on_node_removed(&self, node: &NodeId) {
+            self.peers.lock().retain(|peer| peer != node);
+        }
+
+        fn on_message(&self, node: &NodeId, message: &[u8]) {
+            self.received.lock().push((*node, message.to_vec()));
+            if message == &b"ping"[..] {
+                self.api().send(node, b"pong").unwrap();
+            }
+        }
+
+        fn on_timeout(&self, timer: TimerToken) {
+            assert_eq!(PING_TIMER, timer);
+            let api = self.api();
+            for peer in self.peers.lock().iter() {
+                api.send(peer, b"ping").unwrap();
+            }
+        }
+    }
+
+    fn node(port: u16) -> NodeId {
+        SocketAddr::v4(127, 0, 0, 1, port).into()
+    }
+
+    #[test]
+    fn timers_expire_as_the_clock_advances() {
+        let (a, b) = (node(3485), node(3486));
+        let (ext_a, ext_b) = (PingPong::new(vec![0]), PingPong::new(vec![0]));
+        let mut simulation = Simulation::new();
+        simulation.add_node(a, vec![Arc::clone(&ext_a) as Arc<Extension>]);
+        simulation.add_node(b, vec![Arc::clone(&ext_b) as Arc<Extension>]);
+        simulation.connect(&a, &b);
+
+        let started_at = simulation.now();
+        simulation.advance(Duration::milliseconds(999));
+        assert!(ext_a.received.lock().is_empty());
+
+        simulation.advance(Duration::milliseconds(2001));
+        assert_eq!(StdDuration::from_secs(3), simulation.now() - started_at);
+        // Each node sends 3 pings and answers the 3 pings of the other.
+        assert_eq!(6, ext_a.received.lock().len());
+        // The timer of a expires first, so a gets the answer to its ping before the ping of b.
+        assert_eq!(vec![(b, b"pong".to_vec()), (b, b"ping".to_vec())], ext_a.received.lock()[..2].to_vec());
+    }
+
+    #[test]
+    fn messages_are_not_delivered_after_disconnection() {
+        let (a, b) = (node(3485), node(3486));
+        let (ext_a, ext_b) = (PingPong::new(vec![0]), PingPong::new(vec![0]));
+        let mut simulation = Simulation::new();
+        simulation.add_node(a, vec![Arc::clone(&ext_a) as Arc<Extension>]);
+        simulation.add_node(b, vec![Arc::clone(&ext_b) as Arc<Extension>]);
+        simulation.connect(&a, &b);
+
+        ext_a.api().send(&b, b"ping").unwrap();
+        assert_eq!(1, simulation.pending_messages());
+        simulation.disconnect(&a, &b);
+        assert_eq!(0, simulation.pending_messages());
+        assert!(ext_a.peers.lock().is_empty());
+        assert!(ext_b.peers.lock().is_empty());
+
+        simulation.advance(Duration::seconds(5));
+        assert!(ext_b.received.lock().is_empty());
+    }
+
+    #[test]
+    fn nodes_without_a_common_version_are_not_added() {
+        let (a, b, c) = (node(3485), node(3486), node(3487));
+        let (ext_a, ext_b, ext_c) = (PingPong::new(vec![0, 1]), PingPong::new(vec![1, 2]), PingPong::new(vec![2]));
+        let mut simulation = Simulation::new();
+        simulation.add_node(a, vec![Arc::clone(&ext_a) as Arc<Extension>]);
+        simulation.add_node(b, vec![Arc::clone(&ext_b) as Arc<Extension>]);
+        simulation.add_node(c, vec![Arc::clone(&ext_c) as Arc<Extension>]);
+        simulation.connect(&a, &b);
+        simulation.connect(&a, &c);
+
+        assert_eq!(Some(1), ext_a.api().negotiated_version(&b));
+        assert_eq!(None, ext_a.api().negotiated_version(&c));
+        assert_eq!(vec![b], *ext_a.peers.lock());
+    }
+}
@@ -16,31 +16,32 @@
 
 use std::fmt;
 use std::sync::Arc;
+use std::time::Instant;
 
-use cio::{IoContext, IoHandler, IoHandlerResult, TimerToken};
+use cio::{IoContext, IoHandler, IoHandlerResult, TimerToken, TOKENS_PER_HANDLER};
 use parking_lot::Mutex;
 use time::Duration;
 
 use super::super::client::Client;
 use super::timer_info::TimerInfo;
 
-type TimerId = usize;
-
+/// The tokens are allocated in `TimerInfo` by the caller, so the errors are returned to the extensions.
 #[derive(Clone, Debug, PartialOrd, PartialEq)]
 pub enum Message {
     SetTimer {
-        extension_name: String,
-        timer_id: TimerId,
+        token: TimerToken,
         duration: Duration,
     },
     SetTimerOnce {
-        extension_name: String,
-        timer_id: TimerId,
+        token: TimerToken,
         duration: Duration,
     },
+    SetTimerAt {
+        token: TimerToken,
+        deadline: Instant,
+    },
     ClearTimer {
-        extension_name: String,
-        timer_id: TimerId,
+        token: TimerToken,
     },
     LocalMessage {
         extension_name: String,
@@ -63,18 +64,19 @@ impl fmt::Display for Error {
 
 pub struct Handler {
     client: Arc<Client>,
-    timer: Mutex<TimerInfo>,
+    timer: Arc<Mutex<TimerInfo>>,
 }
 
-const FIRST_TIMER_TOKEN: TimerToken = 0;
-const MAX_TIMERS: usize = 100;
-const LAST_TIMER_TOKEN: TimerToken = FIRST_TIMER_TOKEN + MAX_TIMERS;
+pub const FIRST_TIMER_TOKEN: TimerToken = 0;
+pub const MAX_TIMERS: usize = TOKENS_PER_HANDLER;
+const LAST_TIMER_TOKEN: TimerToken = FIRST_TIMER_TOKEN + MAX_TIMERS - 1;
 
 impl Handler {
     pub fn new(client: Arc<Client>) -> Self {
+        let timer = client.timer_info();
         Self {
             client,
-            timer: Mutex::new(TimerInfo::new(FIRST_TIMER_TOKEN, MAX_TIMERS)),
+            timer,
         }
     }
 }
@@ -101,33 +103,33 @@ impl IoHandler<Message> for Handler {
     fn message(&self, io: &IoContext<Message>, message: &Message) -> IoHandlerResult<()> {
         match message {
             Message::SetTimer {
-                extension_name,
-                timer_id,
+                token,
                 duration,
             } => {
-                let mut timer = self.timer.lock();
-                let token = timer.insert(extension_name.clone(), *timer_id, false)?;
-                io.register_timer(token, duration.num_milliseconds() as u64)?;
+                io.register_timer(*token, duration.num_milliseconds() as u64)?;
                 Ok(())
             }
             Message::SetTimerOnce {
-                extension_name,
-                timer_id,
+                token,
                 duration,
             } => {
-                let mut timer = self.timer.lock();
-                let token = timer.insert(extension_name.clone(), *timer_id, true)?;
-                io.register_timer_once(token, duration.num_milliseconds() as u64)?;
+                io.register_timer_once(*token, duration.num_milliseconds() as u64)?;
+                Ok(())
+            }
+            Message::SetTimerAt {
+                token,
+                deadline,
+            } => {
+                io.register_timer_once(*token, millis_until(*deadline, Instant::now()))?;
                 Ok(())
             }
             Message::ClearTimer {
-                extension_name,
-                timer_id,
+                token,
             } => {
-                let mut timer = self.timer.lock();
-                if let Some(token) = timer.remove_by_info(extension_name.clone(), *timer_id) {
-                    io.clear_timer(token)?;
-                }
+                let result = io.clear_timer(*token);
+                // The token is reused only after the timer is cleared, so the new timer isn't cleared instead.
+                self.timer.lock().release(*token);
+                result?;
                 Ok(())
             }
             Message::LocalMessage {
@@ -140,3 +142,33 @@ impl IoHandler<Message> for Handler {
         }
     }
 }
+
+/// Milliseconds from `now` to the deadline, rounded up so that the timer doesn't expire early.
+fn millis_until(deadline: Instant, now: Instant) -> u64 {
+    if deadline <= now {
+        return 0
+    }
+    let remaining = deadline - now;
+    remaining.as_secs() * 1000 + u64::from((remaining.subsec_nanos() + 999_999) / 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use super::*;
+
+    #[test]
+    fn passed_deadline_expires_immediately() {
+        let now = Instant::now();
+        assert_eq!(0, millis_until(now, now + StdDuration::from_millis(10)));
+        assert_eq!(0, millis_until(now, now));
+    }
+
+    #[test]
+    fn remaining_time_is_rounded_up() {
+        let now = Instant::now();
+        assert_eq!(1500, millis_until(now + StdDuration::from_millis(1500), now));
+        assert_eq!(2, millis_until(now + StdDuration::from_micros(1001), now));
+    }
+}
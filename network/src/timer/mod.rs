@@ -18,4 +18,5 @@ mod handler;
 mod timer_info;
 
 
-pub use self::handler::{Handler, Message};
+pub use self::handler::{Handler, Message, FIRST_TIMER_TOKEN, MAX_TIMERS};
+pub use self::timer_info::TimerInfo;
@@ -14,16 +14,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
 use std::result;
 use std::string::ToString;
 
-use climited_table::{Key as TimerToken, LimitedTable};
-use ctable::Table;
+use cio::TimerToken;
+
+use super::super::extension::Error as ExtensionError;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Error {
     DuplicatedTimerId,
     NoSpace,
+    UnknownTimerId,
 }
 
 impl ToString for Error {
@@ -31,6 +34,17 @@ impl ToString for Error {
         match self {
             Error::DuplicatedTimerId => "Duplicated timer id".to_string(),
             Error::NoSpace => "No space".to_string(),
+            Error::UnknownTimerId => "Unknown timer id".to_string(),
+        }
+    }
+}
+
+impl From<Error> for ExtensionError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::DuplicatedTimerId => ExtensionError::DuplicatedTimerId,
+            Error::NoSpace => ExtensionError::NoMoreTimerToken,
+            Error::UnknownTimerId => ExtensionError::UnknownTimerId,
         }
     }
 }
@@ -46,38 +60,48 @@ pub struct TimerItem {
     pub once: bool,
 }
 
+/// Maps the timer ids of the extensions to the IO timer tokens.
+///
+/// Each extension has its own namespace of timer ids. The tokens are allocated
+/// on demand and reused after the timers are cleared, up to `limit` tokens.
 pub struct TimerInfo {
-    tokens: LimitedTable<TimerItem>,
-    reversed: Table<String, TimerId, TimerToken>,
+    next_token: TimerToken,
+    end: TimerToken,
+    free_tokens: Vec<TimerToken>,
+    tokens: HashMap<TimerToken, TimerItem>,
+    namespaces: HashMap<String, HashMap<TimerId, TimerToken>>,
 }
 
 impl TimerInfo {
     pub fn new(begin: TimerToken, limit: usize) -> Self {
         Self {
-            tokens: LimitedTable::new(begin, limit),
-            reversed: Table::new(),
+            next_token: begin,
+            end: begin + limit,
+            free_tokens: Vec::new(),
+            tokens: HashMap::new(),
+            namespaces: HashMap::new(),
         }
     }
 
     pub fn insert(&mut self, name: String, timer_id: TimerId, once: bool) -> Result<TimerToken> {
-        if self.reversed.get(&name, &timer_id).is_some() {
+        if self.namespaces.get(&name).map_or(false, |timers| timers.contains_key(&timer_id)) {
             return Err(Error::DuplicatedTimerId)
         }
-        self.tokens
-            .insert(TimerItem {
-                name: name.clone(),
+        let token = self.allocate_token().ok_or(Error::NoSpace)?;
+        self.namespaces.entry(name.clone()).or_insert_with(HashMap::new).insert(timer_id, token);
+        self.tokens.insert(
+            token,
+            TimerItem {
+                name,
                 timer_id,
                 once,
-            })
-            .map(|token| {
-                self.reversed.insert(name.clone(), timer_id, token);
-                token
-            })
-            .ok_or(Error::NoSpace)
+            },
+        );
+        Ok(token)
     }
 
     pub fn get_info(&self, token: TimerToken) -> Option<TimerItem> {
-        self.tokens.get(token).cloned()
+        self.tokens.get(&token).cloned()
     }
 
     pub fn remove_by_token(&mut self, token: TimerToken) {
@@ -85,17 +109,48 @@ impl TimerInfo {
             name,
             timer_id,
             ..
-        }) = self.tokens.remove(token)
+        }) = self.tokens.remove(&token)
         {
-            self.reversed.remove(&name, &timer_id);
+            self.remove_from_namespace(&name, timer_id);
+            self.free_tokens.push(token);
+        }
+    }
+
+    /// The token isn't reused until it's released, because the timer is still registered in the IO loop.
+    pub fn remove_by_info(&mut self, name: &str, timer_id: TimerId) -> Result<TimerToken> {
+        let token = self.remove_from_namespace(name, timer_id).ok_or(Error::UnknownTimerId)?;
+        self.tokens.remove(&token);
+        Ok(token)
+    }
+
+    /// Called after the timer removed by `remove_by_info` is cleared in the IO loop.
+    pub fn release(&mut self, token: TimerToken) {
+        debug_assert!(!self.tokens.contains_key(&token));
+        self.free_tokens.push(token);
+    }
+
+    fn allocate_token(&mut self) -> Option<TimerToken> {
+        if let Some(token) = self.free_tokens.pop() {
+            return Some(token)
+        }
+        if self.next_token == self.end {
+            return None
         }
+        let token = self.next_token;
+        self.next_token += 1;
+        Some(token)
     }
 
-    pub fn remove_by_info(&mut self, name: String, timer_id: TimerId) -> Option<TimerToken> {
-        self.reversed.remove(&name, &timer_id).map(|token| {
-            self.tokens.remove(token);
-            token
-        })
+    fn remove_from_namespace(&mut self, name: &str, timer_id: TimerId) -> Option<TimerToken> {
+        let (token, is_empty) = {
+            let timers = self.namespaces.get_mut(name)?;
+            let token = timers.remove(&timer_id)?;
+            (token, timers.is_empty())
+        };
+        if is_empty {
+            self.namespaces.remove(name);
+        }
+        Some(token)
     }
 }
 
@@ -124,4 +179,28 @@ mod tests {
         assert_eq!(Ok(0), timer.insert("a".to_string(), 1, false));
         assert_eq!(Ok(1), timer.insert("b".to_string(), 1, false));
     }
+
+    #[test]
+    fn cleared_token_is_reused() {
+        let mut timer = TimerInfo::new(0, 2);
+        assert_eq!(Ok(0), timer.insert("a".to_string(), 1, false));
+        assert_eq!(Ok(1), timer.insert("b".to_string(), 1, false));
+        assert_eq!(Err(Error::NoSpace), timer.insert("c".to_string(), 1, false));
+
+        assert_eq!(Ok(0), timer.remove_by_info("a", 1));
+        assert_eq!(Err(Error::NoSpace), timer.insert("c".to_string(), 1, false));
+
+        timer.release(0);
+        assert_eq!(Ok(0), timer.insert("c".to_string(), 1, false));
+    }
+
+    #[test]
+    fn clearing_unknown_timer_is_an_error() {
+        let mut timer = TimerInfo::new(0, 4);
+        assert_eq!(Ok(0), timer.insert("a".to_string(), 1, true));
+        assert_eq!(Err(Error::UnknownTimerId), timer.remove_by_info("b", 1));
+
+        timer.remove_by_token(0);
+        assert_eq!(Err(Error::UnknownTimerId), timer.remove_by_info("a", 1));
+    }
 }
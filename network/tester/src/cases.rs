@@ -0,0 +1,293 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::net::SocketAddr;
+
+use primitives::H128;
+
+use super::codec::{
+    negotiation_request, signed_frame, sync, unencrypted_extension_message, Session, SignedMessage, ALLOWED_ID,
+    DENIED_ID, NONCE_ALLOWED, P2P_VERSION,
+};
+use super::connection::{Connection, Peer, Received};
+use super::Config;
+
+pub enum Outcome {
+    Pass,
+    /// The node is not broken, but doesn't behave as strictly as the protocol requires.
+    Warn(String),
+    Fail(String),
+}
+
+pub struct Case {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub run: fn(&Config) -> Result<Outcome, String>,
+}
+
+pub const CASES: &[Case] = &[
+    Case {
+        name: "handshake",
+        description: "A valid Sync is acknowledged with a signed Ack",
+        run: handshake,
+    },
+    Case {
+        name: "negotiation",
+        description: "A negotiation request is answered with Allowed or Denied of the same seq",
+        run: negotiation,
+    },
+    Case {
+        name: "truncated_frame",
+        description: "A frame split across writes is processed only after its last byte arrives",
+        run: truncated_frame,
+    },
+    Case {
+        name: "abandoned_frame",
+        description: "A connection closed in the middle of a frame doesn't affect the node",
+        run: abandoned_frame,
+    },
+    Case {
+        name: "non_list_frame",
+        description: "A frame which is not an RLP list closes the connection",
+        run: non_list_frame,
+    },
+    Case {
+        name: "message_before_sync",
+        description: "A message sent before Sync is rejected",
+        run: message_before_sync,
+    },
+    Case {
+        name: "invalid_signature",
+        description: "A Sync with an invalid signature is rejected and the connection is closed",
+        run: invalid_signature,
+    },
+    Case {
+        name: "unexpected_node_id",
+        description: "A Sync whose node id doesn't match the address of the peer is rejected",
+        run: unexpected_node_id,
+    },
+    Case {
+        name: "wrong_version",
+        description: "A Sync of an unknown version is rejected or acknowledged in the version of the node",
+        run: wrong_version,
+    },
+    Case {
+        name: "reused_session",
+        description: "A session can't be used by a second connection",
+        run: reused_session,
+    },
+    Case {
+        name: "replayed_nonce",
+        description: "A replayed nonce request doesn't create a new session",
+        run: replayed_nonce,
+    },
+    Case {
+        name: "oversized_frame",
+        description: "A frame larger than the limit closes the connection and doesn't affect the node",
+        run: oversized_frame,
+    },
+    Case {
+        name: "unnegotiated_extension",
+        description: "An extension message before the negotiation doesn't break the connection",
+        run: unnegotiated_extension,
+    },
+];
+
+const TESTER_EXTENSION: &str = "p2p-tester";
+
+fn handshake(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    peer.handshake(config.target, config.timeout)?;
+    Ok(Outcome::Pass)
+}
+
+fn negotiation(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.handshake(config.target, config.timeout)?;
+    connection.send(&negotiation_request(1, TESTER_EXTENSION, &[0]))?;
+    match connection.wait_for(is_negotiation_response(1))? {
+        Some(_) => Ok(Outcome::Pass),
+        None => Ok(Outcome::Fail("The node didn't answer the negotiation request".to_string())),
+    }
+}
+
+fn truncated_frame(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    let frame = signed_frame(&peer.sync(&connection)?, peer.session());
+    let (head, tail) = frame.split_at(frame.len() / 2);
+
+    connection.send_raw(head)?;
+    match connection.receive()? {
+        Received::Timeout => {}
+        Received::Closed => return Ok(Outcome::Fail("The node closed the connection on a partial frame".into())),
+        Received::Frame(_) => return Ok(Outcome::Fail("The node responded to a partial frame".into())),
+    }
+    connection.send_raw(tail)?;
+    connection.expect_ack()?;
+    Ok(Outcome::Pass)
+}
+
+fn abandoned_frame(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    let frame = signed_frame(&peer.sync(&connection)?, peer.session());
+    connection.send_raw(&frame[..frame.len() / 2])?;
+    connection.close();
+
+    still_alive(config)
+}
+
+fn non_list_frame(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    connection.send_raw(&[0x83, b'a', b'b', b'c'])?;
+    rejected(&mut connection, "a frame which is not a list", true)
+}
+
+fn message_before_sync(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    connection.send(&negotiation_request(1, TESTER_EXTENSION, &[0]))?;
+    rejected(&mut connection, "a negotiation request before Sync", false)
+}
+
+fn invalid_signature(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    let wrong_session = Session {
+        secret: peer.session().secret,
+        nonce: H128::random(),
+    };
+    connection.send_raw(&signed_frame(&peer.sync(&connection)?, &wrong_session))?;
+    rejected(&mut connection, "a Sync with an invalid signature", true)
+}
+
+fn unexpected_node_id(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    let port = peer.port()?;
+    let other = SocketAddr::new(connection.local_addr()?.ip(), port.wrapping_add(1));
+    connection.send(&sync(P2P_VERSION, port, &other))?;
+    rejected(&mut connection, "a Sync with another node id", false)
+}
+
+fn wrong_version(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    let port = peer.port()?;
+    let node_id = SocketAddr::new(connection.local_addr()?.ip(), port);
+    connection.send(&sync(P2P_VERSION + 1, port, &node_id))?;
+    match connection.wait_for(|_| true) {
+        Ok(None) | Err(_) => Ok(Outcome::Pass),
+        Ok(Some(ref message)) if message.version() == Ok(P2P_VERSION) => Ok(Outcome::Pass),
+        Ok(Some(message)) => Ok(Outcome::Fail(format!(
+            "The node responded in version {:?} which it doesn't speak",
+            message.version()
+        ))),
+    }
+}
+
+fn reused_session(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let _first = peer.handshake(config.target, config.timeout)?;
+    let mut second = peer.connect(config.target, config.timeout)?;
+    let sync = peer.sync(&second)?;
+    second.send(&sync)?;
+    rejected(&mut second, "a Sync reusing an established session", false)
+}
+
+fn replayed_nonce(config: &Config) -> Result<Outcome, String> {
+    let mut peer = Peer::new(config.target, config.timeout)?;
+    let request = peer.nonce_request().to_vec();
+    match peer.initiator().send_and_receive(&request)? {
+        Some(ref response) if response.protocol_id == NONCE_ALLOWED => {
+            Ok(Outcome::Fail("The node allowed a replayed nonce request".to_string()))
+        }
+        _ => Ok(Outcome::Pass),
+    }
+}
+
+fn oversized_frame(config: &Config) -> Result<Outcome, String> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+    const MAX_BYTES_TO_SEND: usize = 16 * CHUNK_SIZE;
+
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.connect(config.target, config.timeout)?;
+    let frame_size = config.max_frame_size + 1;
+    connection.send_list_header(frame_size)?;
+
+    let chunk = vec![0u8; CHUNK_SIZE];
+    let mut sent = 0;
+    while sent < ::std::cmp::min(frame_size, MAX_BYTES_TO_SEND) {
+        if connection.send_raw(&chunk).is_err() {
+            // The node closed the connection as it should.
+            return still_alive(config)
+        }
+        sent += chunk.len();
+    }
+    let outcome = rejected(&mut connection, &format!("a frame of {} bytes", frame_size), true)?;
+    connection.close();
+    match still_alive(config)? {
+        Outcome::Pass => Ok(outcome),
+        failure => Ok(failure),
+    }
+}
+
+fn unnegotiated_extension(config: &Config) -> Result<Outcome, String> {
+    let peer = Peer::new(config.target, config.timeout)?;
+    let mut connection = peer.handshake(config.target, config.timeout)?;
+    connection.send(&unencrypted_extension_message(TESTER_EXTENSION, 0, b"not negotiated"))?;
+    connection.send(&negotiation_request(1, TESTER_EXTENSION, &[0]))?;
+    match connection.wait_for(is_negotiation_response(1)) {
+        Ok(Some(_)) => Ok(Outcome::Pass),
+        Ok(None) => Ok(Outcome::Fail("The node stopped answering after an unnegotiated message".to_string())),
+        // Dropping the peer is a valid way to reject the message.
+        Err(_) => Ok(Outcome::Pass),
+    }
+}
+
+fn is_negotiation_response(seq: u64) -> impl Fn(&SignedMessage) -> bool {
+    move |message| {
+        let protocol_id = message.protocol_id();
+        (protocol_id == Ok(ALLOWED_ID) || protocol_id == Ok(DENIED_ID)) && message.seq() == Ok(seq)
+    }
+}
+
+/// Checks that the node neither acknowledged nor answered what was sent.
+///
+/// The protocol requires closing the connection in some cases; a node that
+/// just ignores the message gets a warning for them.
+fn rejected(connection: &mut Connection, what: &str, must_close: bool) -> Result<Outcome, String> {
+    match connection.receive() {
+        Ok(Received::Closed) => Ok(Outcome::Pass),
+        Ok(Received::Timeout) if must_close => {
+            Ok(Outcome::Warn(format!("The node ignored {} but kept the connection open", what)))
+        }
+        Ok(Received::Timeout) => Ok(Outcome::Pass),
+        Ok(Received::Frame(_)) => Ok(Outcome::Fail(format!("The node responded to {}", what))),
+        Err(err) => Ok(Outcome::Fail(err)),
+    }
+}
+
+/// Checks that a new peer can still finish the handshake.
+fn still_alive(config: &Config) -> Result<Outcome, String> {
+    match handshake(config) {
+        Ok(outcome) => Ok(outcome),
+        Err(err) => Ok(Outcome::Fail(format!("The node doesn't accept new peers anymore: {}", err))),
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! A minimal encoder/decoder of the wire messages. It is written against the
+//! protocol, not against the `codechain-network` crate, so that the tester
+//! doesn't share bugs with the implementation it checks.
+
+use std::net::{IpAddr, SocketAddr};
+
+use ccrypto::Blake;
+use primitives::{H128, H256};
+use rlp::{DecoderError, RlpStream, UntrustedRlp};
+
+pub const P2P_VERSION: u64 = 0;
+
+pub const SYNC_ID: u64 = 0x00;
+pub const ACK_ID: u64 = 0x01;
+pub const REQUEST_ID: u64 = 0x02;
+pub const ALLOWED_ID: u64 = 0x03;
+pub const DENIED_ID: u64 = 0x04;
+pub const UNENCRYPTED_ID: u64 = 0x06;
+
+pub const INITIATION_VERSION: u32 = 0;
+
+pub const NODE_ID_REQUEST: u8 = 0x01;
+pub const NODE_ID_RESPONSE: u8 = 0x02;
+pub const SECRET_REQUEST: u8 = 0x03;
+pub const SECRET_ALLOWED: u8 = 0x04;
+pub const SECRET_DENIED: u8 = 0x05;
+pub const NONCE_REQUEST: u8 = 0x06;
+pub const NONCE_ALLOWED: u8 = 0x07;
+pub const NONCE_DENIED: u8 = 0x08;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Session {
+    pub secret: H256,
+    pub nonce: H128,
+}
+
+impl Session {
+    pub fn sign(&self, data: &[u8]) -> H256 {
+        H256::blake_with_key(data, &self.nonce)
+    }
+}
+
+pub fn append_node_id(s: &mut RlpStream, addr: &SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            s.begin_list(5);
+            for octet in ip.octets().iter() {
+                s.append(octet);
+            }
+            s.append(&addr.port());
+        }
+        IpAddr::V6(_) => panic!("IPv6 addresses are not supported by the protocol yet"),
+    }
+}
+
+pub fn initiation_message<F>(version: u32, seq: u64, protocol_id: u8, append_body: F) -> Vec<u8>
+where
+    F: FnOnce(&mut RlpStream), {
+    let mut s = RlpStream::new_list(4);
+    s.append(&version).append(&seq).append(&protocol_id);
+    append_body(&mut s);
+    s.out()
+}
+
+pub struct InitiationMessage {
+    pub seq: u64,
+    pub protocol_id: u8,
+    pub body: Vec<u8>,
+}
+
+pub fn decode_initiation_message(bytes: &[u8]) -> Result<InitiationMessage, DecoderError> {
+    let rlp = UntrustedRlp::new(bytes);
+    if rlp.item_count()? != 4 {
+        return Err(DecoderError::RlpIncorrectListLen)
+    }
+    Ok(InitiationMessage {
+        seq: rlp.val_at(1)?,
+        protocol_id: rlp.val_at(2)?,
+        body: rlp.at(3)?.as_raw().to_vec(),
+    })
+}
+
+pub fn sync(version: u64, port: u16, node_id: &SocketAddr) -> Vec<u8> {
+    let mut s = RlpStream::new_list(4);
+    s.append(&version).append(&SYNC_ID).append(&port);
+    append_node_id(&mut s, node_id);
+    s.out()
+}
+
+pub fn negotiation_request(seq: u64, extension_name: &str, extension_versions: &[u64]) -> Vec<u8> {
+    let mut s = RlpStream::new_list(5);
+    s.append(&P2P_VERSION).append(&REQUEST_ID).append(&seq).append(&extension_name).append_list(extension_versions);
+    s.out()
+}
+
+pub fn unencrypted_extension_message(extension_name: &str, extension_version: u64, data: &[u8]) -> Vec<u8> {
+    let mut s = RlpStream::new_list(5);
+    s.append(&P2P_VERSION)
+        .append(&UNENCRYPTED_ID)
+        .append(&extension_name)
+        .append(&extension_version)
+        .append(&data.to_vec());
+    s.out()
+}
+
+/// Wraps an encoded message into a frame signed with the session.
+pub fn signed_frame(message: &[u8], session: &Session) -> Vec<u8> {
+    let mut s = RlpStream::new_list(2);
+    s.append(&message.to_vec()).append(&session.sign(message));
+    s.out()
+}
+
+pub struct SignedMessage {
+    pub message: Vec<u8>,
+    pub signature: H256,
+}
+
+impl SignedMessage {
+    pub fn decode(frame: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = UntrustedRlp::new(frame);
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen)
+        }
+        Ok(SignedMessage {
+            message: rlp.val_at(0)?,
+            signature: rlp.val_at(1)?,
+        })
+    }
+
+    pub fn is_valid(&self, session: &Session) -> bool {
+        session.sign(&self.message) == self.signature
+    }
+
+    pub fn version(&self) -> Result<u64, DecoderError> {
+        UntrustedRlp::new(&self.message).val_at(0)
+    }
+
+    pub fn protocol_id(&self) -> Result<u64, DecoderError> {
+        UntrustedRlp::new(&self.message).val_at(1)
+    }
+
+    /// The sequence number of a negotiation message.
+    pub fn seq(&self) -> Result<u64, DecoderError> {
+        UntrustedRlp::new(&self.message).val_at(2)
+    }
+}
+
+/// Returns the header of a list whose payload is `len` bytes long.
+pub fn list_header(len: usize) -> Vec<u8> {
+    if len < 56 {
+        return vec![0xc0 + len as u8]
+    }
+    let bytes: Vec<u8> = (0..8).rev().map(|i| (len >> (i * 8)) as u8).skip_while(|byte| *byte == 0).collect();
+    let mut header = vec![0xf7 + bytes.len() as u8];
+    header.extend(bytes);
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_header_of_short_list() {
+        assert_eq!(vec![0xc0], list_header(0));
+        assert_eq!(vec![0xf7], list_header(55));
+    }
+
+    #[test]
+    fn list_header_of_long_list() {
+        assert_eq!(vec![0xf8, 56], list_header(56));
+        assert_eq!(vec![0xfa, 0x01, 0x00, 0x00], list_header(0x10000));
+    }
+
+    #[test]
+    fn list_header_matches_rlp_stream() {
+        let mut s = RlpStream::new_list(1);
+        s.append(&vec![0u8; 100]);
+        let out = s.out();
+        assert_eq!(&out[..2], &list_header(out.len() - 2)[..]);
+    }
+
+    #[test]
+    fn signed_frame_is_valid_for_the_same_session() {
+        let session = Session {
+            secret: H256::random(),
+            nonce: H128::random(),
+        };
+        let frame = signed_frame(&negotiation_request(1, "ext", &[0]), &session);
+        let signed = SignedMessage::decode(&frame).unwrap();
+        assert!(signed.is_valid(&session));
+        assert_eq!(Ok(REQUEST_ID), signed.protocol_id());
+        assert_eq!(Ok(1), signed.seq());
+
+        let other = Session {
+            secret: session.secret,
+            nonce: H128::random(),
+        };
+        assert!(!signed.is_valid(&other));
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use super::codec::{list_header, signed_frame, sync, Session, SignedMessage, ACK_ID, P2P_VERSION};
+use super::initiator::{is_timeout, Initiator};
+
+pub enum Received {
+    Frame(Vec<u8>),
+    Closed,
+    Timeout,
+}
+
+/// A peer that has finished the session initiation and can open TCP connections.
+pub struct Peer {
+    // The node looks up the session by the address of this socket.
+    initiator: Initiator,
+    session: Session,
+    nonce_request: Vec<u8>,
+}
+
+impl Peer {
+    pub fn new(target: SocketAddr, timeout: Duration) -> Result<Self, String> {
+        let mut initiator = Initiator::bind(target, timeout).map_err(|err| format!("Cannot bind UDP: {}", err))?;
+        let (session, nonce_request) = initiator.initiate()?;
+        Ok(Self {
+            initiator,
+            session,
+            nonce_request,
+        })
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn initiator(&mut self) -> &mut Initiator {
+        &mut self.initiator
+    }
+
+    pub fn nonce_request(&self) -> &[u8] {
+        &self.nonce_request
+    }
+
+    pub fn port(&self) -> Result<u16, String> {
+        self.initiator.port().map_err(|err| format!("Cannot get the UDP port: {}", err))
+    }
+
+    pub fn connect(&self, target: SocketAddr, timeout: Duration) -> Result<Connection, String> {
+        let stream = TcpStream::connect_timeout(&target, timeout)
+            .map_err(|err| format!("Cannot connect to {}: {}", target, err))?;
+        stream.set_read_timeout(Some(timeout)).map_err(|err| err.to_string())?;
+        stream.set_nodelay(true).map_err(|err| err.to_string())?;
+        Ok(Connection {
+            stream,
+            session: self.session,
+        })
+    }
+
+    /// The Sync message that the node expects from this peer on the given connection.
+    pub fn sync(&self, connection: &Connection) -> Result<Vec<u8>, String> {
+        let port = self.port()?;
+        let local = connection.local_addr()?;
+        Ok(sync(P2P_VERSION, port, &SocketAddr::new(local.ip(), port)))
+    }
+
+    /// Opens a connection and finishes the handshake on it.
+    pub fn handshake(&self, target: SocketAddr, timeout: Duration) -> Result<Connection, String> {
+        let mut connection = self.connect(target, timeout)?;
+        let sync = self.sync(&connection)?;
+        connection.send(&sync)?;
+        connection.expect_ack()?;
+        Ok(connection)
+    }
+}
+
+pub struct Connection {
+    stream: TcpStream,
+    session: Session,
+}
+
+impl Connection {
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.stream.local_addr().map_err(|err| format!("Cannot get the local address: {}", err))
+    }
+
+    pub fn send_raw(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.stream.write_all(bytes).and_then(|_| self.stream.flush()).map_err(|err| format!("Cannot send: {}", err))
+    }
+
+    /// Signs the message with the session of this connection and sends it.
+    pub fn send(&mut self, message: &[u8]) -> Result<(), String> {
+        let frame = signed_frame(message, &self.session);
+        self.send_raw(&frame)
+    }
+
+    pub fn close(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+
+    /// Reads a frame. A frame is an RLP list, whose length is known from its header.
+    pub fn receive(&mut self) -> Result<Received, String> {
+        let mut header = [0u8; 1];
+        if let Some(received) = self.read_exact(&mut header)? {
+            return Ok(received)
+        }
+        if header[0] < 0xc0 {
+            return Err(format!("The node sent a frame which is not a list (0x{:02x})", header[0]))
+        }
+        let mut frame = header.to_vec();
+        let payload_len = if header[0] < 0xf8 {
+            (header[0] - 0xc0) as usize
+        } else {
+            let mut len_of_len = vec![0u8; (header[0] - 0xf7) as usize];
+            if self.read_exact(&mut len_of_len)?.is_some() {
+                return Err("The node sent a truncated frame header".to_string())
+            }
+            frame.extend_from_slice(&len_of_len);
+            len_of_len.iter().fold(0usize, |len, byte| (len << 8) | *byte as usize)
+        };
+        let mut payload = vec![0u8; payload_len];
+        if self.read_exact(&mut payload)?.is_some() {
+            return Err("The node sent a truncated frame".to_string())
+        }
+        frame.extend(payload);
+        Ok(Received::Frame(frame))
+    }
+
+    /// Returns what happened instead if the buffer couldn't be filled.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<Option<Received>, String> {
+        match self.stream.read_exact(buf) {
+            Ok(()) => Ok(None),
+            Err(ref err) if is_timeout(err) => Ok(Some(Received::Timeout)),
+            Err(ref err) if is_closed(err) => Ok(Some(Received::Closed)),
+            Err(err) => Err(format!("Cannot read: {}", err)),
+        }
+    }
+
+    /// Waits for a signed message which satisfies the predicate, skipping the others.
+    ///
+    /// The node may send its own negotiation requests at any time after the
+    /// handshake, so unrelated messages are not errors.
+    pub fn wait_for<F>(&mut self, predicate: F) -> Result<Option<SignedMessage>, String>
+    where
+        F: Fn(&SignedMessage) -> bool, {
+        loop {
+            let frame = match self.receive()? {
+                Received::Frame(frame) => frame,
+                Received::Closed => return Err("The node closed the connection".to_string()),
+                Received::Timeout => return Ok(None),
+            };
+            let message = SignedMessage::decode(&frame).map_err(|err| format!("Invalid signed message: {}", err))?;
+            if !message.is_valid(&self.session) {
+                return Err("The node sent a message with an invalid signature".to_string())
+            }
+            if predicate(&message) {
+                return Ok(Some(message))
+            }
+        }
+    }
+
+    pub fn expect_ack(&mut self) -> Result<(), String> {
+        let ack = self.wait_for(|message| message.protocol_id() == Ok(ACK_ID))?;
+        let ack = ack.ok_or_else(|| "The node didn't acknowledge the Sync message".to_string())?;
+        match ack.version() {
+            Ok(P2P_VERSION) => Ok(()),
+            Ok(version) => Err(format!("The node acknowledged in version {}", version)),
+            Err(err) => Err(format!("Invalid Ack message: {}", err)),
+        }
+    }
+
+    /// Sends a frame header announcing a payload of `len` bytes.
+    pub fn send_list_header(&mut self, len: usize) -> Result<(), String> {
+        let header = list_header(len);
+        self.send_raw(&header)
+    }
+}
+
+fn is_closed(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe => true,
+        _ => false,
+    }
+}
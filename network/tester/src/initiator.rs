@@ -0,0 +1,158 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use ccrypto::aes;
+use ckey::{exchange, Generator, Public, Random};
+use primitives::H128;
+use rlp::{self, UntrustedRlp};
+
+use super::codec::{
+    append_node_id, decode_initiation_message, initiation_message, InitiationMessage, Session, INITIATION_VERSION,
+    NODE_ID_REQUEST, NODE_ID_RESPONSE, NONCE_ALLOWED, NONCE_DENIED, NONCE_REQUEST, SECRET_ALLOWED, SECRET_DENIED,
+    SECRET_REQUEST,
+};
+
+/// Runs the session initiation protocol over UDP as an initiator.
+///
+/// The socket is kept open as long as the initiator lives, because the node
+/// identifies the session by the address of this socket.
+pub struct Initiator {
+    socket: UdpSocket,
+    target: SocketAddr,
+    timeout: Duration,
+    seq: u64,
+}
+
+impl Initiator {
+    pub fn bind(target: SocketAddr, timeout: Duration) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(target)?;
+        socket.set_read_timeout(Some(timeout))?;
+        Ok(Self {
+            socket,
+            target,
+            timeout,
+            seq: 0,
+        })
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        Ok(self.socket.local_addr()?.port())
+    }
+
+    /// Shares a secret and a nonce with the node.
+    ///
+    /// It also returns the encoded nonce request, so that a case can replay it.
+    pub fn initiate(&mut self) -> Result<(Session, Vec<u8>), String> {
+        let target = self.target;
+        let response = self.request(NODE_ID_REQUEST, |s| append_node_id(s, &target))?;
+        expect_protocol_id(&response, NODE_ID_RESPONSE)?;
+
+        let key_pair = Random.generate().map_err(|err| format!("Cannot generate a key pair: {}", err))?;
+        let response = self.request(SECRET_REQUEST, |s| {
+            s.append(key_pair.public());
+        })?;
+        if response.protocol_id == SECRET_DENIED {
+            return Err("The node denied the secret request".to_string())
+        }
+        expect_protocol_id(&response, SECRET_ALLOWED)?;
+        let node_public: Public = decode_body(&response)?;
+        let secret = exchange(&node_public, key_pair.private()).map_err(|err| format!("ECDH failed: {}", err))?;
+
+        let temporary_nonce = H128::random();
+        let encrypted = aes::encrypt(&rlp::encode(&temporary_nonce), &secret, &H128::zero())
+            .map_err(|err| format!("Cannot encrypt the temporary nonce: {:?}", err))?;
+        self.seq += 1;
+        let request = initiation_message(INITIATION_VERSION, self.seq, NONCE_REQUEST, |s| {
+            s.append(&encrypted);
+        });
+        let response = self.send_and_receive(&request)?.ok_or_else(|| no_response(NONCE_REQUEST))?;
+        if response.protocol_id == NONCE_DENIED {
+            return Err("The node denied the nonce request".to_string())
+        }
+        expect_protocol_id(&response, NONCE_ALLOWED)?;
+        let encrypted_nonce: Vec<u8> = decode_body(&response)?;
+        let nonce = aes::decrypt(&encrypted_nonce, &secret, &temporary_nonce)
+            .map_err(|err| format!("Cannot decrypt the nonce: {:?}", err))?;
+        let nonce: H128 = UntrustedRlp::new(&nonce).as_val().map_err(|err| format!("Invalid nonce: {}", err))?;
+
+        Ok((
+            Session {
+                secret,
+                nonce,
+            },
+            request,
+        ))
+    }
+
+    fn request<F>(&mut self, protocol_id: u8, append_body: F) -> Result<InitiationMessage, String>
+    where
+        F: FnOnce(&mut rlp::RlpStream), {
+        self.seq += 1;
+        let message = initiation_message(INITIATION_VERSION, self.seq, protocol_id, append_body);
+        self.send_and_receive(&message)?.ok_or_else(|| no_response(protocol_id))
+    }
+
+    /// Sends an encoded message and waits for the response of the same seq.
+    ///
+    /// Returns `None` if the node doesn't respond in time.
+    pub fn send_and_receive(&mut self, message: &[u8]) -> Result<Option<InitiationMessage>, String> {
+        let seq = UntrustedRlp::new(message).val_at::<u64>(1).map_err(|err| format!("Invalid request: {}", err))?;
+        self.socket.send(message).map_err(|err| format!("Cannot send a datagram: {}", err))?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0u8; 2048];
+        while Instant::now() < deadline {
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(ref err) if is_timeout(err) => break,
+                Err(err) => return Err(format!("Cannot receive a datagram: {}", err)),
+            };
+            match decode_initiation_message(&buf[..len]) {
+                Ok(ref response) if response.seq != seq => continue,
+                Ok(response) => return Ok(Some(response)),
+                Err(err) => return Err(format!("The node sent an invalid datagram: {}", err)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub fn is_timeout(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut
+}
+
+fn no_response(protocol_id: u8) -> String {
+    format!("No response to the message 0x{:02x}", protocol_id)
+}
+
+fn expect_protocol_id(message: &InitiationMessage, expected: u8) -> Result<(), String> {
+    if message.protocol_id != expected {
+        return Err(format!("Expected a message 0x{:02x} but got 0x{:02x}", expected, message.protocol_id))
+    }
+    Ok(())
+}
+
+fn decode_body<T: rlp::Decodable>(message: &InitiationMessage) -> Result<T, String> {
+    UntrustedRlp::new(&message.body)
+        .as_val()
+        .map_err(|err| format!("Invalid body of 0x{:02x}: {}", message.protocol_id, err))
+}
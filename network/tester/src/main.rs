@@ -0,0 +1,140 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+//! Checks whether a node speaks the CodeChain P2P protocol, including the
+//! edge cases of the handshake and the framing that a well-behaved peer never
+//! exercises.
+
+extern crate clap;
+extern crate codechain_crypto as ccrypto;
+extern crate codechain_key as ckey;
+extern crate primitives;
+extern crate rlp;
+
+mod cases;
+mod codec;
+mod connection;
+mod initiator;
+
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::process;
+use std::time::Duration;
+
+use clap::{App, Arg};
+
+use cases::{Outcome, CASES};
+
+pub struct Config {
+    pub target: SocketAddr,
+    pub timeout: Duration,
+    pub max_frame_size: usize,
+}
+
+fn main() {
+    let matches = App::new("codechain-p2p-tester")
+        .about("Checks the conformance of a node to the CodeChain P2P protocol")
+        .arg(
+            Arg::with_name("target")
+                .help("The address of the P2P port of the node, e.g. 127.0.0.1:3485")
+                .required_unless("list")
+                .index(1),
+        ).arg(
+            Arg::with_name("case")
+                .long("case")
+                .help("Runs only the given cases")
+                .takes_value(true)
+                .multiple(true),
+        ).arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .help("Milliseconds to wait for a response")
+                .takes_value(true)
+                .default_value("3000"),
+        ).arg(
+            Arg::with_name("max-frame-size")
+                .long("max-frame-size")
+                .help("The largest frame in bytes that the node is expected to accept")
+                .takes_value(true)
+                .default_value("16777216"),
+        ).arg(Arg::with_name("list").long("list").help("Lists the cases"))
+        .get_matches();
+
+    if matches.is_present("list") {
+        for case in CASES {
+            println!("{:24} {}", case.name, case.description);
+        }
+        return
+    }
+
+    let config = match parse_config(&matches) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+    let selected: Option<Vec<&str>> = matches.values_of("case").map(|cases| cases.collect());
+    if let Some(ref selected) = selected {
+        if let Some(unknown) = selected.iter().find(|name| CASES.iter().all(|case| &case.name != *name)) {
+            eprintln!("Unknown case: {}", unknown);
+            process::exit(2);
+        }
+    }
+
+    let mut failures = 0;
+    let mut warnings = 0;
+    for case in CASES {
+        if let Some(ref selected) = selected {
+            if !selected.contains(&case.name) {
+                continue
+            }
+        }
+        match (case.run)(&config) {
+            Ok(Outcome::Pass) => println!("PASS {}", case.name),
+            Ok(Outcome::Warn(reason)) => {
+                warnings += 1;
+                println!("WARN {}: {}", case.name, reason);
+            }
+            Ok(Outcome::Fail(reason)) | Err(reason) => {
+                failures += 1;
+                println!("FAIL {}: {}", case.name, reason);
+            }
+        }
+    }
+    println!("{} failure(s), {} warning(s)", failures, warnings);
+    if failures != 0 {
+        process::exit(1);
+    }
+}
+
+fn parse_config(matches: &clap::ArgMatches) -> Result<Config, String> {
+    let target = matches.value_of("target").expect("target is required");
+    let target = target
+        .to_socket_addrs()
+        .map_err(|err| format!("Invalid target {}: {}", target, err))?
+        .find(SocketAddr::is_ipv4)
+        .ok_or_else(|| format!("{} has no IPv4 address", target))?;
+    let timeout = matches.value_of("timeout").expect("timeout has a default value");
+    let timeout = timeout.parse().map_err(|_| format!("Invalid timeout {}", timeout))?;
+    let max_frame_size = matches.value_of("max-frame-size").expect("max-frame-size has a default value");
+    let max_frame_size = max_frame_size.parse().map_err(|_| format!("Invalid frame size {}", max_frame_size))?;
+    Ok(Config {
+        target,
+        timeout: Duration::from_millis(timeout),
+        max_frame_size,
+    })
+}
@@ -0,0 +1,208 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use clogger;
+use futures_cpupool::{Builder as CpuPoolBuilder, CpuPool};
+use jsonrpc_core::futures::future::{self, Either};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{Call, Failure, Id, MetaIoHandler, Metadata, Middleware, Output, Request, Response, Version};
+
+use super::methods::{access, Access};
+use super::v1::errors;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchConfig {
+    /// The number of the threads executing the read-only calls of the batches.
+    pub threads: usize,
+    /// The maximum number of the calls in a batch. Not limited if it's None.
+    pub max_batch_size: Option<usize>,
+}
+
+/// The worker pool of the batches, shared by all the RPC servers.
+pub struct BatchExecutor {
+    pool: CpuPool,
+    max_batch_size: Option<usize>,
+}
+
+impl BatchExecutor {
+    pub fn new(config: BatchConfig) -> Self {
+        Self {
+            pool: CpuPoolBuilder::new().pool_size(config.threads.max(1)).name_prefix("rpc-batch-").create(),
+            max_batch_size: config.max_batch_size,
+        }
+    }
+}
+
+/// Dispatches the requests to `handler`, which has the methods.
+///
+/// The methods are sync, so the calls of a batch are executed one by one if they are given to the handler at once.
+/// The calls of a batch having only the read-only calls are given to the handler separately on the worker pool,
+/// and their outputs are collected in the order of the calls. A batch having another call is executed in order,
+/// since its calls may depend on each other.
+pub struct BatchMiddleware<M: Metadata, S: Middleware<M>> {
+    handler: Arc<MetaIoHandler<M, S>>,
+    executor: Arc<BatchExecutor>,
+}
+
+impl<M: Metadata, S: Middleware<M>> BatchMiddleware<M, S> {
+    pub fn new(handler: MetaIoHandler<M, S>, executor: Arc<BatchExecutor>) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            executor,
+        }
+    }
+}
+
+impl<M: Metadata, S: Middleware<M>> Middleware<M> for BatchMiddleware<M, S> {
+    type Future = Box<Future<Item = Option<Response>, Error = ()> + Send>;
+
+    fn on_request<F, X>(&self, request: Request, meta: M, _next: F) -> Either<Self::Future, X>
+    where
+        F: FnOnce(Request, M) -> X + Send,
+        X: Future<Item = Option<Response>, Error = ()> + Send + 'static, {
+        let calls = match request {
+            Request::Batch(calls) => calls,
+            single => return Either::A(Box::new(self.handler.handle_rpc_request(single, meta))),
+        };
+        if let Some(max_batch_size) = self.executor.max_batch_size {
+            if calls.len() > max_batch_size {
+                let response = Response::Single(Output::Failure(Failure {
+                    jsonrpc: Some(Version::V2),
+                    error: errors::batch_too_large(max_batch_size),
+                    id: Id::Null,
+                }));
+                return Either::A(Box::new(future::ok(Some(response))))
+            }
+        }
+        if calls.len() < 2 || !calls.iter().all(is_read_only) {
+            return Either::A(Box::new(self.handler.handle_rpc_request(Request::Batch(calls), meta)))
+        }
+
+        let span = clogger::current_span();
+        let responses: Vec<_> = calls
+            .into_iter()
+            .map(|call| {
+                let handler = Arc::clone(&self.handler);
+                let meta = meta.clone();
+                self.executor.pool.spawn_fn(move || {
+                    // The logs of the calls are tagged with the span of the batch.
+                    let _span = span.map(clogger::enter_span);
+                    handler.handle_rpc_request(Request::Single(call), meta).wait()
+                })
+            })
+            .collect();
+        let response = future::join_all(responses).map(|responses| {
+            let outputs: Vec<_> = responses
+                .into_iter()
+                .filter_map(|response| match response {
+                    Some(Response::Single(output)) => Some(output),
+                    _ => None,
+                })
+                .collect();
+            if outputs.is_empty() {
+                None
+            } else {
+                Some(Response::Batch(outputs))
+            }
+        });
+        Either::A(Box::new(response))
+    }
+}
+
+fn is_read_only(call: &Call) -> bool {
+    match call {
+        Call::MethodCall(call) => access(&call.method) == Access::Read,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use jsonrpc_core::middleware::Noop;
+    use jsonrpc_core::{Compatibility, Params, Value};
+
+    use super::*;
+
+    fn handler(max_batch_size: Option<usize>) -> MetaIoHandler<(), BatchMiddleware<(), Noop>> {
+        // The methods are named after the read-only methods, so that the batches of them are executed on the pool.
+        let mut methods = MetaIoHandler::default();
+        methods.add_method("chain_getBlockHash", |params: Params| {
+            let (value,): (u64,) = params.parse()?;
+            Ok(Value::from(value))
+        });
+        methods.add_method("chain_getBestBlockNumber", |_params: Params| {
+            Ok(Value::from(thread::current().name().unwrap_or_default().to_string()))
+        });
+        methods.add_method("chain_sendValue", |_params: Params| Ok(Value::Bool(true)));
+        let executor = Arc::new(BatchExecutor::new(BatchConfig {
+            threads: 2,
+            max_batch_size,
+        }));
+        MetaIoHandler::new(Compatibility::V2, BatchMiddleware::new(methods, executor))
+    }
+
+    fn batch(methods: &[(&str, u64)]) -> String {
+        let calls: Vec<_> = methods
+            .iter()
+            .enumerate()
+            .map(|(id, (method, param))| {
+                format!(r#"{{"jsonrpc":"2.0","method":"{}","params":[{}],"id":{}}}"#, method, param, id)
+            })
+            .collect();
+        format!("[{}]", calls.join(","))
+    }
+
+    #[test]
+    fn outputs_are_in_order() {
+        let handler = handler(None);
+        let calls: Vec<_> = (0..20).map(|value| ("chain_getBlockHash", value)).collect();
+        let response = handler.handle_request_sync(&batch(&calls), ()).unwrap();
+        let response: Vec<Value> = ::serde_json::from_str(&response).unwrap();
+        for (id, output) in response.into_iter().enumerate() {
+            assert_eq!(Value::from(id), output["id"]);
+            assert_eq!(Value::from(id), output["result"]);
+        }
+    }
+
+    #[test]
+    fn only_read_only_batch_is_executed_on_pool() {
+        let handler = handler(None);
+        let read_only = batch(&[("chain_getBestBlockNumber", 0), ("chain_getBestBlockNumber", 0)]);
+        let response = handler.handle_request_sync(&read_only, ()).unwrap();
+        assert!(response.contains("rpc-batch-"), response);
+
+        let mixed = batch(&[("chain_getBestBlockNumber", 0), ("chain_sendValue", 0)]);
+        let response = handler.handle_request_sync(&mixed, ()).unwrap();
+        assert!(!response.contains("rpc-batch-"), response);
+    }
+
+    #[test]
+    fn large_batch_is_rejected() {
+        let handler = handler(Some(2));
+        let calls = [("chain_getBlockHash", 1), ("chain_getBlockHash", 2)];
+        let response = handler.handle_request_sync(&batch(&calls), ()).unwrap();
+        assert!(response.contains("result"), response);
+
+        let calls = [("chain_getBlockHash", 1), ("chain_getBlockHash", 2), ("chain_getBlockHash", 3)];
+        let response = handler.handle_request_sync(&batch(&calls), ()).unwrap();
+        assert!(response.contains("-32600"), response);
+        assert!(!response.contains("result"), response);
+    }
+}
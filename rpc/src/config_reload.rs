@@ -0,0 +1,29 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// The keys of a configuration reload, in the form of `section.key`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReloadReport {
+    /// The changed keys which are applied to the running node.
+    pub applied: Vec<String>,
+    /// The changed keys which take effect only after a restart.
+    pub requires_restart: Vec<String>,
+}
+
+/// Reloads the configuration of the running node, for `config_reload`.
+pub trait ConfigReload: Send + Sync {
+    fn reload(&self) -> Result<ReloadReport, String>;
+}
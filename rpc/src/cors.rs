@@ -0,0 +1,117 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use jsonrpc_http_server::hyper::header::Origin;
+use jsonrpc_http_server::hyper::{Request, Response, StatusCode};
+use jsonrpc_http_server::{cors_header, AccessControlAllowOrigin, CorsHeader, RequestMiddlewareAction};
+use parking_lot::RwLock;
+
+/// The origins from which a browser can call the RPC. The list can be changed at runtime.
+///
+/// The requests from the other origins, including the preflight requests, are rejected before they reach the
+/// handler. The requests without `Origin`, and the requests from the same host, are not restricted.
+pub struct Cors {
+    domains: RwLock<Option<Vec<AccessControlAllowOrigin>>>,
+}
+
+impl Cors {
+    /// Every origin is allowed if `domains` is None.
+    pub fn new(domains: Option<Vec<String>>) -> Self {
+        Self {
+            domains: RwLock::new(parse_domains(domains)),
+        }
+    }
+
+    pub fn set(&self, domains: Option<Vec<String>>) {
+        *self.domains.write() = parse_domains(domains);
+    }
+
+    pub fn on_request(&self, request: Request) -> RequestMiddlewareAction {
+        if let CorsHeader::Invalid = cors_header(&request, &self.domains.read()) {
+            let origin = request.headers().get::<Origin>().map(ToString::to_string).unwrap_or_default();
+            cdebug!(RPC, "The request from {} is rejected by the CORS policy", origin);
+            return Response::new().with_status(StatusCode::Forbidden).with_body("Origin is not allowed").into()
+        }
+        request.into()
+    }
+}
+
+fn parse_domains(domains: Option<Vec<String>>) -> Option<Vec<AccessControlAllowOrigin>> {
+    domains.map(|domains| {
+        domains
+            .into_iter()
+            .map(|domain| match domain.as_str() {
+                "*" => AccessControlAllowOrigin::Any,
+                "null" => AccessControlAllowOrigin::Null,
+                domain => AccessControlAllowOrigin::Value(domain.into()),
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_http_server::hyper::Method;
+
+    use super::*;
+
+    fn request(method: Method, origin: Option<&'static str>) -> Request {
+        let mut request = Request::new(method, "/".parse().unwrap());
+        request.headers_mut().set_raw("host", "localhost:8080");
+        if let Some(origin) = origin {
+            request.headers_mut().set_raw("origin", origin);
+        }
+        request
+    }
+
+    fn is_rejected(action: RequestMiddlewareAction) -> bool {
+        match action {
+            RequestMiddlewareAction::Respond {
+                ..
+            } => true,
+            RequestMiddlewareAction::Proceed {
+                ..
+            } => false,
+        }
+    }
+
+    #[test]
+    fn only_allowed_origins_pass() {
+        let cors = Cors::new(Some(vec!["http://dapp.local".to_string()]));
+        assert!(!is_rejected(cors.on_request(request(Method::Post, Some("http://dapp.local")))));
+        assert!(is_rejected(cors.on_request(request(Method::Post, Some("http://evil.com")))));
+        assert!(is_rejected(cors.on_request(request(Method::Options, Some("http://evil.com")))));
+    }
+
+    #[test]
+    fn requests_without_cross_origin_are_not_restricted() {
+        let cors = Cors::new(Some(vec![]));
+        assert!(!is_rejected(cors.on_request(request(Method::Post, None))));
+        assert!(!is_rejected(cors.on_request(request(Method::Post, Some("http://localhost:8080")))));
+        assert!(is_rejected(cors.on_request(request(Method::Post, Some("http://dapp.local")))));
+    }
+
+    #[test]
+    fn domains_can_be_changed() {
+        let cors = Cors::new(Some(vec![]));
+        cors.set(Some(vec!["*".to_string()]));
+        assert!(!is_rejected(cors.on_request(request(Method::Post, Some("http://dapp.local")))));
+        cors.set(None);
+        assert!(!is_rejected(cors.on_request(request(Method::Options, Some("http://dapp.local")))));
+        cors.set(Some(vec!["null".to_string()]));
+        assert!(is_rejected(cors.on_request(request(Method::Post, Some("http://dapp.local")))));
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ccore::{BlockChainClient, ChainInfo, Client, DatabaseClient, SyncProgress, COL_HEADERS};
+use cnetwork::NetworkControl;
+use ctypes::BlockNumber;
+use futures_cpupool::{Builder as CpuPoolBuilder, CpuPool};
+use jsonrpc_http_server::hyper::header::ContentType;
+use jsonrpc_http_server::hyper::{Error as HyperError, Method, Request, Response, StatusCode};
+use jsonrpc_http_server::RequestMiddlewareAction;
+use serde_json;
+
+use super::v1::types::NodeHealth;
+
+/// The thresholds of a node which is ready to serve.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HealthConfig {
+    /// Maximum seconds since the best block was created, for the chains which produce blocks steadily.
+    /// Not checked if it's None.
+    pub max_block_age: Option<u64>,
+    /// Minimum number of the connected peers. Not checked if the network is disabled.
+    pub min_peers: usize,
+}
+
+/// Checks the status of the node, for `net_health` and the `/health` and `/ready` HTTP endpoints.
+///
+/// A node is healthy if its database can be read, and ready if it's also synced, has a recent enough best block and
+/// has enough peers.
+pub struct HealthCheck {
+    client: Arc<Client>,
+    network_control: Arc<NetworkControl>,
+    config: HealthConfig,
+    /// The checks of the HTTP endpoints read the database, so they run on the pool rather than the event loop.
+    pool: CpuPool,
+}
+
+impl HealthCheck {
+    pub fn new(client: Arc<Client>, network_control: Arc<NetworkControl>, config: HealthConfig) -> Self {
+        Self {
+            client,
+            network_control,
+            config,
+            pool: CpuPoolBuilder::new().pool_size(1).name_prefix("rpc-health-").create(),
+        }
+    }
+
+    pub fn check(&self) -> NodeHealth {
+        check(&self.client, &*self.network_control, &self.config)
+    }
+
+    /// Answers `GET /health` and `GET /ready` with 200 or 503, and passes the other requests to the RPC handler.
+    pub fn on_request(&self, request: Request) -> RequestMiddlewareAction {
+        if *request.method() != Method::Get {
+            return request.into()
+        }
+        let is_readiness = match request.path() {
+            "/health" => false,
+            "/ready" => true,
+            _ => return request.into(),
+        };
+
+        let client = Arc::clone(&self.client);
+        let network_control = Arc::clone(&self.network_control);
+        let config = self.config.clone();
+        let future = self.pool.spawn_fn(move || -> Result<Response, HyperError> {
+            Ok(response(&check(&client, &*network_control, &config), is_readiness))
+        });
+        RequestMiddlewareAction::Respond {
+            should_validate_hosts: true,
+            response: Box::new(future),
+        }
+    }
+}
+
+fn check(client: &Client, network_control: &NetworkControl, config: &HealthConfig) -> NodeHealth {
+    let chain_info = client.chain_info();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+    let last_block_age = now.saturating_sub(chain_info.best_block_timestamp);
+    let peer_count = network_control.get_peer_count().ok();
+    let db_healthy = match client.database().get(COL_HEADERS, &chain_info.best_block_hash) {
+        Ok(header) => header.is_some(),
+        Err(err) => {
+            cwarn!(RPC, "Cannot read the best block header: {}", err);
+            false
+        }
+    };
+    let synced = is_synced(client.sync_progress(), chain_info.best_block_number);
+    evaluate(config, db_healthy, synced, last_block_age, peer_count)
+}
+
+/// The node is synced once it imported the highest block the peers have. A node without the sync extension is
+/// regarded as synced.
+fn is_synced(progress: Option<SyncProgress>, best_block_number: BlockNumber) -> bool {
+    progress.map_or(true, |progress| progress.highest_block <= best_block_number)
+}
+
+fn evaluate(
+    config: &HealthConfig,
+    db_healthy: bool,
+    synced: bool,
+    last_block_age: u64,
+    peer_count: Option<usize>,
+) -> NodeHealth {
+    let is_recent = config.max_block_age.map_or(true, |max_block_age| last_block_age <= max_block_age);
+    let has_enough_peers = peer_count.map_or(true, |peer_count| peer_count >= config.min_peers);
+    NodeHealth {
+        healthy: db_healthy,
+        ready: db_healthy && synced && is_recent && has_enough_peers,
+        synced,
+        peer_count,
+        last_block_age,
+        db_healthy,
+    }
+}
+
+fn response(health: &NodeHealth, is_readiness: bool) -> Response {
+    let status = if (is_readiness && health.ready) || (!is_readiness && health.healthy) {
+        StatusCode::Ok
+    } else {
+        StatusCode::ServiceUnavailable
+    };
+    let body = serde_json::to_string(health).expect("NodeHealth is always serializable");
+    Response::new().with_status(status).with_header(ContentType::json()).with_body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(highest_block: BlockNumber) -> SyncProgress {
+        SyncProgress {
+            highest_block,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn node_behind_the_peers_is_not_synced() {
+        assert!(is_synced(None, 0));
+        assert!(is_synced(Some(progress(10)), 10));
+        assert!(!is_synced(Some(progress(11)), 10));
+    }
+
+    #[test]
+    fn idle_chain_is_ready_without_the_block_age_limit() {
+        let config = HealthConfig {
+            max_block_age: None,
+            min_peers: 1,
+        };
+        assert!(evaluate(&config, true, true, 3600, Some(1)).ready);
+        assert!(!evaluate(&config, true, false, 0, Some(1)).ready);
+        assert!(!evaluate(&config, true, true, 0, Some(0)).ready);
+        assert!(evaluate(&config, true, true, 0, None).ready, "The peers are not counted without the network");
+
+        let config = HealthConfig {
+            max_block_age: Some(60),
+            min_peers: 0,
+        };
+        assert!(!evaluate(&config, true, true, 61, Some(0)).ready);
+    }
+
+    #[test]
+    fn unready_node_is_healthy() {
+        let config = HealthConfig::default();
+        let health = evaluate(&config, true, false, 0, None);
+        assert_eq!(StatusCode::Ok, response(&health, false).status());
+        assert_eq!(StatusCode::ServiceUnavailable, response(&health, true).status());
+
+        let health = evaluate(&config, false, true, 0, None);
+        assert_eq!(StatusCode::ServiceUnavailable, response(&health, false).status());
+    }
+}
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 extern crate codechain_core as ccore;
+extern crate codechain_crypto as ccrypto;
 #[macro_use]
 extern crate codechain_logger as clogger;
 extern crate codechain_key as ckey;
@@ -22,6 +23,7 @@ extern crate codechain_keystore as ckeystore;
 extern crate codechain_network as cnetwork;
 extern crate codechain_state as cstate;
 extern crate codechain_types as ctypes;
+extern crate futures_cpupool;
 extern crate jsonrpc_core;
 extern crate jsonrpc_http_server;
 extern crate jsonrpc_ipc_server;
@@ -29,6 +31,7 @@ extern crate kvdb;
 extern crate kvdb_rocksdb as rocksdb;
 #[macro_use]
 extern crate log;
+extern crate parking_lot;
 extern crate primitives;
 extern crate rlp;
 extern crate rustc_hex;
@@ -42,12 +45,29 @@ extern crate tokio_core;
 #[macro_use]
 extern crate jsonrpc_macros;
 
+mod batch;
+mod config_reload;
+mod cors;
+mod health;
+mod limits;
+mod maintenance;
+mod methods;
 pub mod rpc_server;
+mod signing;
+mod tracing;
 pub mod v1;
 
 pub use rustc_serialize::hex;
 
+pub use batch::{BatchConfig, BatchExecutor, BatchMiddleware};
+pub use config_reload::{ConfigReload, ReloadReport};
+pub use cors::Cors;
+pub use health::{HealthCheck, HealthConfig};
 pub use jsonrpc_core::{Compatibility, Error, MetaIoHandler, Params, Value};
+pub use limits::{LimitMiddleware, Limits, LimitsConfig, RateLimit};
+pub use maintenance::{Maintenance, MaintenanceMiddleware};
+pub use signing::{ResponseSigner, SigningMiddleware};
+pub use tracing::TracingMiddleware;
 pub use jsonrpc_http_server::tokio_core::reactor::Remote;
 
 pub use jsonrpc_http_server::Server as HttpServer;
@@ -0,0 +1,437 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use jsonrpc_core::futures::future::{self, Either};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{Call, Error, Metadata, Middleware, Request, Response};
+use jsonrpc_http_server::hyper::header::{ContentLength, RetryAfter};
+use jsonrpc_http_server::hyper::{self, Method, StatusCode};
+use jsonrpc_http_server::RequestMiddlewareAction;
+use parking_lot::Mutex;
+use serde_json;
+
+use super::maintenance::reject;
+use super::v1::errors;
+
+/// The methods which carry a raw parcel. A request only calling them can be as large as `max_parcel_payload`.
+const PARCEL_METHODS: &[&str] = &["chain_sendSignedParcel"];
+
+/// The full buckets are dropped once this many IPs are tracked.
+const MAX_TRACKED_IPS: usize = 10_000;
+/// The full buckets are dropped at most once in this interval.
+const SWEEP_INTERVAL_SECS: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// The requests allowed from an IP per second.
+    pub per_second: u32,
+    /// The requests which an idle IP can send at once.
+    pub burst: u32,
+}
+
+/// The limits protecting a public RPC endpoint from abuse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitsConfig {
+    /// The requests from an IP are not limited if it's None. Only the HTTP requests are limited.
+    pub rate_limit: Option<RateLimit>,
+    /// The maximum size of a request in bytes.
+    pub max_payload: usize,
+    /// The maximum size of a request which only sends parcels.
+    pub max_parcel_payload: usize,
+    /// The maximum number of the requests handled at the same time. Not limited if it's None.
+    pub max_concurrent_requests: Option<usize>,
+}
+
+/// The token bucket of an IP.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(rate_limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(rate_limit.burst),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate_limit: RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+        self.tokens = (self.tokens + elapsed * f64::from(rate_limit.per_second)).min(f64::from(rate_limit.burst));
+        self.updated = now;
+    }
+
+    fn take(&mut self, rate_limit: RateLimit, now: Instant) -> bool {
+        self.refill(rate_limit, now);
+        if self.tokens < 1.0 {
+            return false
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Enforces the `LimitsConfig`, shared by all the RPC servers.
+///
+/// The size and the rate of the HTTP requests are checked before their bodies are read. The size of a request which
+/// calls a method other than the parcel methods, and the number of the concurrent requests, are checked by
+/// `LimitMiddleware`.
+pub struct Limits {
+    config: LimitsConfig,
+    buckets: Mutex<Buckets>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+struct Buckets {
+    /// Keyed by the IPv4 address, or by the /64 prefix of the IPv6 address, which a single host usually owns.
+    tracked: HashMap<IpAddr, Bucket>,
+    /// Shared by the IPs which are not tracked because too many IPs are.
+    overflow: Option<Bucket>,
+    swept: Option<Instant>,
+}
+
+impl Limits {
+    pub fn new(config: LimitsConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(Buckets {
+                tracked: HashMap::new(),
+                overflow: None,
+                swept: None,
+            }),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The server stops reading a body larger than this, which may come without Content-Length.
+    pub fn max_request_body_size(&self) -> usize {
+        self.config.max_payload.max(self.config.max_parcel_payload)
+    }
+
+    pub fn on_http_request(&self, request: hyper::Request) -> RequestMiddlewareAction {
+        // The server doesn't give the remote address in another way.
+        #[allow(deprecated)]
+        let remote_ip = request.remote_addr().map(|addr| addr.ip());
+        if let Some(ip) = remote_ip {
+            if !self.acquire(ip, Instant::now()) {
+                cdebug!(RPC, "The request from {} exceeds the rate limit", ip);
+                return hyper::Response::new()
+                    .with_status(StatusCode::TooManyRequests)
+                    .with_header(RetryAfter::Delay(Duration::from_secs(1)))
+                    .with_body("Too many requests")
+                    .into()
+            }
+        }
+
+        if *request.method() == Method::Post {
+            let max_payload = self.max_request_body_size();
+            // A chunked body is limited by the server while it's read.
+            if let Some(ContentLength(length)) = request.headers().get::<ContentLength>() {
+                if *length > max_payload as u64 {
+                    return hyper::Response::new()
+                        .with_status(StatusCode::PayloadTooLarge)
+                        .with_body(format!("The request is larger than {} bytes", max_payload))
+                        .into()
+                }
+            }
+        }
+        request.into()
+    }
+
+    fn acquire(&self, ip: IpAddr, now: Instant) -> bool {
+        let rate_limit = match self.config.rate_limit {
+            Some(rate_limit) => rate_limit,
+            None => return true,
+        };
+        let key = bucket_key(ip);
+        let mut buckets = self.buckets.lock();
+        if let Some(bucket) = buckets.tracked.get_mut(&key) {
+            return bucket.take(rate_limit, now)
+        }
+        if buckets.tracked.len() >= MAX_TRACKED_IPS {
+            let sweep_interval = Duration::from_secs(SWEEP_INTERVAL_SECS);
+            let can_sweep = buckets.swept.map_or(true, |swept| now.duration_since(swept) >= sweep_interval);
+            if can_sweep {
+                buckets.tracked.retain(|_, bucket| {
+                    bucket.refill(rate_limit, now);
+                    bucket.tokens < f64::from(rate_limit.burst)
+                });
+                buckets.swept = Some(now);
+            }
+        }
+        if buckets.tracked.len() >= MAX_TRACKED_IPS {
+            return buckets.overflow.get_or_insert_with(|| Bucket::full(rate_limit, now)).take(rate_limit, now)
+        }
+        buckets.tracked.entry(key).or_insert_with(|| Bucket::full(rate_limit, now)).take(rate_limit, now)
+    }
+
+    fn payload_rejection(&self, request: &Request) -> Option<Error> {
+        let max_payload = if calls(request).iter().all(is_parcel_call) {
+            self.config.max_parcel_payload
+        } else {
+            self.config.max_payload
+        };
+        if payload_size(request) > max_payload {
+            return Some(errors::payload_too_large(max_payload))
+        }
+        None
+    }
+
+    fn enter(&self) -> Option<InFlight> {
+        let max_concurrent_requests = self.config.max_concurrent_requests?;
+        if self.in_flight.fetch_add(1, Ordering::SeqCst) >= max_concurrent_requests {
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            return None
+        }
+        Some(InFlight(Arc::clone(&self.in_flight)))
+    }
+}
+
+/// A request being handled. It's counted until dropped.
+struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Rejects the requests which are too large, or which exceed the number of the concurrent requests.
+pub struct LimitMiddleware<T> {
+    inner: T,
+    limits: Arc<Limits>,
+}
+
+impl<T> LimitMiddleware<T> {
+    pub fn new(inner: T, limits: Arc<Limits>) -> Self {
+        Self {
+            inner,
+            limits,
+        }
+    }
+}
+
+impl<M: Metadata, T: Middleware<M>> Middleware<M> for LimitMiddleware<T> {
+    type Future = Either<T::Future, Box<Future<Item = Option<Response>, Error = ()> + Send>>;
+
+    fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X>
+    where
+        F: FnOnce(Request, M) -> X + Send,
+        X: Future<Item = Option<Response>, Error = ()> + Send + 'static, {
+        if let Some(error) = self.limits.payload_rejection(&request) {
+            return Either::A(Either::B(Box::new(future::ok(reject_request(request, error)))))
+        }
+        if self.limits.config.max_concurrent_requests.is_none() {
+            return match self.inner.on_request(request, meta, next) {
+                Either::A(a) => Either::A(Either::A(a)),
+                Either::B(x) => Either::B(x),
+            }
+        }
+        let in_flight = match self.limits.enter() {
+            Some(in_flight) => in_flight,
+            None => {
+                let response = reject_request(request, errors::too_many_requests());
+                return Either::A(Either::B(Box::new(future::ok(response))))
+            }
+        };
+        let response: Box<Future<Item = Option<Response>, Error = ()> + Send> =
+            match self.inner.on_request(request, meta, next) {
+                Either::A(a) => Box::new(a.then(move |response| {
+                    drop(in_flight);
+                    response
+                })),
+                Either::B(x) => Box::new(x.then(move |response| {
+                    drop(in_flight);
+                    response
+                })),
+            };
+        Either::A(Either::B(response))
+    }
+}
+
+fn bucket_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ipv6) => {
+            let segments = ipv6.segments();
+            IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], segments[2], segments[3], 0, 0, 0, 0))
+        }
+    }
+}
+
+fn calls(request: &Request) -> &[Call] {
+    match request {
+        Request::Single(call) => ::std::slice::from_ref(call),
+        Request::Batch(calls) => calls.as_slice(),
+    }
+}
+
+/// The invalid calls aren't counted since they can't be serialized again.
+fn payload_size(request: &Request) -> usize {
+    calls(request).iter().filter_map(|call| serde_json::to_vec(call).ok()).map(|bytes| bytes.len()).sum()
+}
+
+fn is_parcel_call(call: &Call) -> bool {
+    match call {
+        Call::MethodCall(call) => PARCEL_METHODS.contains(&call.method.as_str()),
+        Call::Notification(notification) => PARCEL_METHODS.contains(&notification.method.as_str()),
+        _ => false,
+    }
+}
+
+fn reject_request(request: Request, error: Error) -> Option<Response> {
+    match request {
+        Request::Single(call) => reject(call, error).map(Response::Single),
+        Request::Batch(calls) => {
+            let outputs: Vec<_> = calls.into_iter().filter_map(|call| reject(call, error.clone())).collect();
+            if outputs.is_empty() {
+                None
+            } else {
+                Some(Response::Batch(outputs))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LimitsConfig {
+        LimitsConfig {
+            rate_limit: Some(RateLimit {
+                per_second: 2,
+                burst: 3,
+            }),
+            max_payload: 200,
+            max_parcel_payload: 1000,
+            max_concurrent_requests: Some(1),
+        }
+    }
+
+    fn call(method: &str, param: &str) -> Call {
+        let call = format!(r#"{{"jsonrpc":"2.0","method":"{}","params":["{}"],"id":1}}"#, method, param);
+        serde_json::from_str(&call).unwrap()
+    }
+
+    #[test]
+    fn rate_is_limited_per_ip() {
+        let limits = Limits::new(config());
+        let now = Instant::now();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        assert!(limits.acquire(ip, now));
+        assert!(limits.acquire(ip, now));
+        assert!(limits.acquire(ip, now));
+        assert!(!limits.acquire(ip, now));
+        assert!(limits.acquire(other, now));
+
+        // Two tokens per second, up to the burst.
+        assert!(limits.acquire(ip, now + Duration::from_millis(500)));
+        assert!(!limits.acquire(ip, now + Duration::from_millis(500)));
+        let later = now + Duration::from_secs(10);
+        assert!(limits.acquire(ip, later));
+        assert!(limits.acquire(ip, later));
+        assert!(limits.acquire(ip, later));
+        assert!(!limits.acquire(ip, later));
+    }
+
+    #[test]
+    fn ipv6_is_limited_per_64_prefix() {
+        let limits = Limits::new(config());
+        let now = Instant::now();
+        for suffix in 1..4 {
+            let ip: IpAddr = format!("2001:db8:1:2::{}", suffix).parse().unwrap();
+            assert!(limits.acquire(ip, now));
+        }
+        assert!(!limits.acquire("2001:db8:1:2:ffff::1".parse().unwrap(), now));
+        assert!(limits.acquire("2001:db8:1:3::1".parse().unwrap(), now));
+    }
+
+    #[test]
+    fn untracked_ips_share_a_bucket() {
+        let limits = Limits::new(config());
+        let now = Instant::now();
+        for index in 0..MAX_TRACKED_IPS {
+            let ip = IpAddr::V4((0x0a00_0000 + index as u32).into());
+            assert!(limits.acquire(ip, now));
+        }
+        assert!(limits.acquire("192.168.0.1".parse().unwrap(), now));
+        assert!(limits.acquire("192.168.0.2".parse().unwrap(), now));
+        assert!(limits.acquire("192.168.0.3".parse().unwrap(), now));
+        assert!(!limits.acquire("192.168.0.4".parse().unwrap(), now));
+
+        // The full buckets are dropped after a while.
+        let later = now + Duration::from_secs(10);
+        assert!(limits.acquire("192.168.0.4".parse().unwrap(), later));
+        assert_eq!(1, limits.buckets.lock().tracked.len());
+    }
+
+    #[test]
+    fn parcel_methods_can_be_larger() {
+        let limits = Limits::new(config());
+        let large = "0".repeat(500);
+        assert_eq!(None, limits.payload_rejection(&Request::Single(call("chain_getBlockByNumber", "0x1"))));
+        assert!(limits.payload_rejection(&Request::Single(call("chain_getBlockByNumber", &large))).is_some());
+        assert_eq!(None, limits.payload_rejection(&Request::Single(call("chain_sendSignedParcel", &large))));
+
+        let mixed = Request::Batch(vec![call("chain_sendSignedParcel", &large), call("chain_getBestBlockNumber", "")]);
+        assert!(limits.payload_rejection(&mixed).is_some());
+        let too_large = "0".repeat(1000);
+        assert!(limits.payload_rejection(&Request::Single(call("chain_sendSignedParcel", &too_large))).is_some());
+    }
+
+    #[test]
+    fn concurrent_requests_are_limited() {
+        let limits = Limits::new(config());
+        let in_flight = limits.enter().unwrap();
+        assert!(limits.enter().is_none());
+        drop(in_flight);
+        assert!(limits.enter().is_some());
+    }
+
+    #[test]
+    fn http_request_is_limited_by_content_length() {
+        let limits = Limits::new(config());
+        let is_rejected = |request: hyper::Request| match limits.on_http_request(request) {
+            RequestMiddlewareAction::Respond {
+                ..
+            } => true,
+            RequestMiddlewareAction::Proceed {
+                ..
+            } => false,
+        };
+        let post = |length: Option<u64>| {
+            let mut request = hyper::Request::new(Method::Post, "/".parse().unwrap());
+            if let Some(length) = length {
+                request.headers_mut().set(ContentLength(length));
+            }
+            request
+        };
+        assert!(!is_rejected(post(None)));
+        assert!(!is_rejected(post(Some(1000))));
+        assert!(is_rejected(post(Some(1001))));
+        assert!(!is_rejected(hyper::Request::new(Method::Get, "/health".parse().unwrap())));
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use jsonrpc_core::futures::future::{self, Either, FutureResult};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{Call, Error, Failure, MethodCall, Metadata, Middleware, Output, Request, Response};
+
+use super::methods::{access, Access};
+use super::v1::errors;
+
+/// Seconds that the clients are advised to wait before they retry.
+const RETRY_AFTER_SECS: u64 = 30;
+
+/// The switch of the maintenance mode, shared by all the RPC servers.
+#[derive(Default)]
+pub struct Maintenance {
+    enabled: AtomicBool,
+    read_only: bool,
+}
+
+impl Maintenance {
+    /// The switch of a node which only reads the chain. The requests modifying it are always rejected.
+    pub fn read_only() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            read_only: true,
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        if enabled {
+            cinfo!(RPC, "The maintenance mode is on. Only the admin requests are handled");
+        } else {
+            cinfo!(RPC, "The maintenance mode is off");
+        }
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+}
+
+/// Rejects the new non-admin requests during the maintenance mode, and the write requests to a read-only node.
+///
+/// The requests already accepted are not affected, so they are finished as usual.
+/// Only the rejected calls of a batch fail, and the other calls of it are handled.
+pub struct MaintenanceMiddleware {
+    maintenance: Arc<Maintenance>,
+}
+
+impl MaintenanceMiddleware {
+    pub fn new(maintenance: Arc<Maintenance>) -> Self {
+        Self {
+            maintenance,
+        }
+    }
+
+    fn rejection(&self, call: &Call) -> Option<Error> {
+        if self.maintenance.is_enabled() && !is_admin(call) {
+            return Some(errors::maintenance(RETRY_AFTER_SECS))
+        }
+        if self.maintenance.is_read_only() && is_write(call) {
+            return Some(errors::read_only())
+        }
+        None
+    }
+}
+
+impl<M: Metadata> Middleware<M> for MaintenanceMiddleware {
+    type Future = Either<FutureResult<Option<Response>, ()>, Box<Future<Item = Option<Response>, Error = ()> + Send>>;
+
+    fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X>
+    where
+        F: FnOnce(Request, M) -> X + Send,
+        X: Future<Item = Option<Response>, Error = ()> + Send + 'static, {
+        if !self.maintenance.is_enabled() && !self.maintenance.is_read_only() {
+            return Either::B(next(request, meta))
+        }
+        let calls = match request {
+            Request::Single(call) => {
+                return match self.rejection(&call) {
+                    None => Either::B(next(Request::Single(call), meta)),
+                    Some(error) => Either::A(Either::A(future::ok(reject(call, error).map(Response::Single)))),
+                }
+            }
+            Request::Batch(calls) => calls,
+        };
+        if calls.iter().all(|call| self.rejection(call).is_none()) {
+            return Either::B(next(Request::Batch(calls), meta))
+        }
+
+        // Only the rejected calls of a batch fail. The others are handled, and the outputs are kept in order.
+        let mut slots = Vec::with_capacity(calls.len());
+        let mut forwarded = Vec::new();
+        for call in calls {
+            match self.rejection(&call) {
+                Some(error) => slots.extend(reject(call, error).map(Slot::Rejected)),
+                None => {
+                    if !is_notification(&call) {
+                        slots.push(Slot::Forwarded);
+                    }
+                    forwarded.push(call);
+                }
+            }
+        }
+        if forwarded.is_empty() {
+            return Either::A(Either::A(future::ok(merge(slots, None))))
+        }
+        let response = next(Request::Batch(forwarded), meta);
+        Either::A(Either::B(Box::new(response.map(move |response| merge(slots, response)))))
+    }
+}
+
+/// The place of an output in the response to a batch.
+enum Slot {
+    Rejected(Output),
+    Forwarded,
+}
+
+fn merge(slots: Vec<Slot>, response: Option<Response>) -> Option<Response> {
+    let mut forwarded = match response {
+        Some(Response::Batch(outputs)) => outputs,
+        Some(Response::Single(output)) => vec![output],
+        None => vec![],
+    }
+    .into_iter();
+    let mut outputs = Vec::with_capacity(slots.len());
+    for slot in slots {
+        match slot {
+            Slot::Rejected(output) => outputs.push(output),
+            Slot::Forwarded => outputs.extend(forwarded.next()),
+        }
+    }
+    outputs.extend(forwarded);
+    if outputs.is_empty() {
+        None
+    } else {
+        Some(Response::Batch(outputs))
+    }
+}
+
+fn is_notification(call: &Call) -> bool {
+    match call {
+        Call::Notification(_) => true,
+        _ => false,
+    }
+}
+
+fn is_admin(call: &Call) -> bool {
+    match call {
+        Call::MethodCall(call) => call.method.starts_with("admin_"),
+        Call::Notification(notification) => notification.method.starts_with("admin_"),
+        _ => false,
+    }
+}
+
+fn is_write(call: &Call) -> bool {
+    match call {
+        Call::MethodCall(call) => access(&call.method) == Access::Write,
+        Call::Notification(notification) => access(&notification.method) == Access::Write,
+        _ => false,
+    }
+}
+
+pub(crate) fn reject(call: Call, error: Error) -> Option<Output> {
+    match call {
+        Call::MethodCall(MethodCall {
+            jsonrpc,
+            id,
+            ..
+        }) => Some(Output::Failure(Failure {
+            jsonrpc,
+            error,
+            id,
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::{Compatibility, MetaIoHandler, Params, Value};
+
+    use super::*;
+
+    fn handler(maintenance: &Arc<Maintenance>) -> MetaIoHandler<(), MaintenanceMiddleware> {
+        let mut handler = MetaIoHandler::new(Compatibility::V2, MaintenanceMiddleware::new(Arc::clone(maintenance)));
+        handler.add_method("chain_getValue", |_params: Params| Ok(Value::from(1)));
+        handler.add_method("chain_sendSignedParcel", |_params: Params| Ok(Value::Bool(true)));
+        handler.add_method("admin_getMaintenanceMode", |_params: Params| Ok(Value::Bool(true)));
+        handler
+    }
+
+    fn outputs(handler: &MetaIoHandler<(), MaintenanceMiddleware>, request: &str) -> Vec<Value> {
+        let response = handler.handle_request_sync(request, ()).unwrap();
+        ::serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn only_admin_calls_of_batch_are_handled_in_maintenance_mode() {
+        let maintenance = Arc::new(Maintenance::default());
+        maintenance.set(true);
+        let handler = handler(&maintenance);
+        let request = r#"[
+            {"jsonrpc":"2.0","method":"chain_getValue","params":[],"id":0},
+            {"jsonrpc":"2.0","method":"admin_getMaintenanceMode","params":[],"id":1},
+            {"jsonrpc":"2.0","method":"chain_getValue","params":[]},
+            {"jsonrpc":"2.0","method":"chain_getValue","params":[],"id":2}
+        ]"#;
+        let outputs = outputs(&handler, request);
+        assert_eq!(3, outputs.len());
+        assert_eq!(Value::from(0), outputs[0]["id"]);
+        assert_eq!(Value::from(errors::codes::MAINTENANCE_MODE), outputs[0]["error"]["code"]);
+        assert_eq!(Value::from(1), outputs[1]["id"]);
+        assert_eq!(Value::Bool(true), outputs[1]["result"]);
+        assert_eq!(Value::from(2), outputs[2]["id"]);
+        assert_eq!(Value::from(errors::codes::MAINTENANCE_MODE), outputs[2]["error"]["code"]);
+    }
+
+    #[test]
+    fn only_write_calls_of_batch_fail_on_read_only_node() {
+        let maintenance = Arc::new(Maintenance::read_only());
+        let handler = handler(&maintenance);
+        let request = r#"[
+            {"jsonrpc":"2.0","method":"chain_sendSignedParcel","params":[],"id":0},
+            {"jsonrpc":"2.0","method":"chain_getValue","params":[],"id":1}
+        ]"#;
+        let outputs = outputs(&handler, request);
+        assert_eq!(2, outputs.len());
+        assert_eq!(Value::from(errors::codes::READ_ONLY), outputs[0]["error"]["code"]);
+        assert_eq!(Value::from(1), outputs[1]["id"]);
+        assert_eq!(Value::from(1), outputs[1]["result"]);
+    }
+}
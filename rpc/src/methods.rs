@@ -0,0 +1,121 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// How a method uses the node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    /// Only reads the node. The calls of a batch are executed concurrently only if all of them read.
+    Read,
+    /// Modifies the chain, the pool or the miner. It's rejected by a read-only node.
+    Write,
+    /// Neither of them, e.g. the filters, whose changes depend on the previous polls.
+    Other,
+}
+
+/// The methods which read or write. The others are `Access::Other`.
+const METHODS: &[(&str, Access)] = &[
+    ("admin_getMaintenanceMode", Access::Read),
+    ("chain_containsParcel", Access::Read),
+    ("chain_getAccounts", Access::Read),
+    ("chain_getAsset", Access::Read),
+    ("chain_getAssetSchemeByHash", Access::Read),
+    ("chain_getAssetSchemeByType", Access::Read),
+    ("chain_getBalance", Access::Read),
+    ("chain_getBalances", Access::Read),
+    ("chain_getBestBlockId", Access::Read),
+    ("chain_getBestBlockNumber", Access::Read),
+    ("chain_getBlockByHash", Access::Read),
+    ("chain_getBlockByNumber", Access::Read),
+    ("chain_getBlockHash", Access::Read),
+    ("chain_getCacheStats", Access::Read),
+    ("chain_getChainStats", Access::Read),
+    ("chain_getCoinbase", Access::Read),
+    ("chain_getFinalizedBlock", Access::Read),
+    ("chain_getLogs", Access::Read),
+    ("chain_getMultisigAddress", Access::Read),
+    ("chain_getNetworkId", Access::Read),
+    ("chain_getNonce", Access::Read),
+    ("chain_getNumberOfShards", Access::Read),
+    ("chain_getParcel", Access::Read),
+    ("chain_getParcelInvoice", Access::Read),
+    ("chain_getParcelsByAddress", Access::Read),
+    ("chain_getPendingBlock", Access::Read),
+    ("chain_getPendingParcels", Access::Read),
+    ("chain_getProof", Access::Read),
+    ("chain_getRecommendedNonce", Access::Read),
+    ("chain_getRegularKey", Access::Read),
+    ("chain_getRegularKeyOwner", Access::Read),
+    ("chain_getShardRoot", Access::Read),
+    ("chain_getTotalScore", Access::Read),
+    ("chain_getTransaction", Access::Read),
+    ("chain_getTransactionInvoice", Access::Read),
+    ("chain_getValidators", Access::Read),
+    ("chain_isAssetSpent", Access::Read),
+    ("chain_resolveName", Access::Read),
+    ("chain_sendSignedParcel", Access::Write),
+    ("chain_syncing", Access::Read),
+    ("config_reload", Access::Write),
+    ("devel_mineBlock", Access::Write),
+    ("devel_revertState", Access::Write),
+    ("devel_setTime", Access::Write),
+    ("devel_snapshotState", Access::Write),
+    ("devel_startSealing", Access::Write),
+    ("devel_stopSealing", Access::Write),
+    ("mempool_cancelParcel", Access::Write),
+    ("miner_getResealStatus", Access::Read),
+    ("miner_prioritizeParcel", Access::Write),
+    ("miner_removeParcel", Access::Write),
+    ("miner_setAuthor", Access::Write),
+    ("miner_setOptions", Access::Write),
+    ("miner_submitWork", Access::Write),
+    ("net_extensionStats", Access::Read),
+    ("net_getBlacklist", Access::Read),
+    ("net_getEstablishedPeers", Access::Read),
+    ("net_getNodeAllowList", Access::Read),
+    ("net_getPeerCount", Access::Read),
+    ("net_getPeerRtts", Access::Read),
+    ("net_getPort", Access::Read),
+    ("net_getWhitelist", Access::Read),
+    ("net_health", Access::Read),
+    ("net_isConnected", Access::Read),
+    ("net_localInfo", Access::Read),
+    ("shardValidator_registerAction", Access::Write),
+];
+
+pub fn access(method: &str) -> Access {
+    METHODS.iter().find(|(name, _)| *name == method).map_or(Access::Other, |(_, access)| *access)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn each_method_is_listed_once() {
+        let names: HashSet<_> = METHODS.iter().map(|(name, _)| name).collect();
+        assert_eq!(METHODS.len(), names.len());
+    }
+
+    #[test]
+    fn polling_filter_is_not_read_only() {
+        assert_eq!(Access::Read, access("chain_getBlockByNumber"));
+        assert_eq!(Access::Other, access("chain_getFilterChanges"));
+        assert_eq!(Access::Write, access("chain_sendSignedParcel"));
+        assert_eq!(Access::Other, access("chain_unknownMethod"));
+    }
+}
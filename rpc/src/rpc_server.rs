@@ -16,42 +16,59 @@
 
 // TODO: panic handler
 use jsonrpc_core;
-use jsonrpc_http_server::{self, Host, Server as HttpServer, ServerBuilder as HttpServerBuilder};
+use jsonrpc_http_server::{
+    DomainsValidation, Host, RequestMiddlewareAction, Server as HttpServer, ServerBuilder as HttpServerBuilder,
+};
 use jsonrpc_ipc_server::{Server as IpcServer, ServerBuilder as IpcServerBuilder};
 use std::default::Default;
 use std::io;
 use std::net::SocketAddr;
+use std::sync::Arc;
+
+use super::{Cors, HealthCheck, Limits};
 
 /// Start http server asynchronously and returns result with `Server` handle on success or an error.
-pub fn start_http<M: jsonrpc_core::Metadata>(
+/// The requests from the origins not allowed by `cors`, or exceeding the rate and the size in `limits`, are rejected.
+/// The health endpoints are served if `health` is given.
+pub fn start_http<M: jsonrpc_core::Metadata, S: jsonrpc_core::Middleware<M>>(
     addr: &SocketAddr,
-    cors_domains: Option<Vec<String>>,
+    cors: Arc<Cors>,
+    limits: Arc<Limits>,
     allowed_hosts: Option<Vec<String>>,
-    handler: jsonrpc_core::MetaIoHandler<M>,
+    health: Option<Arc<HealthCheck>>,
+    handler: jsonrpc_core::MetaIoHandler<M, S>,
 ) -> Result<HttpServer, io::Error>
 where
     M: Default, {
-    let cors_domains = cors_domains.map(|domains| {
-        domains
-            .into_iter()
-            .map(|v| match v.as_str() {
-                "*" => jsonrpc_http_server::AccessControlAllowOrigin::Any,
-                "null" => jsonrpc_http_server::AccessControlAllowOrigin::Null,
-                v => jsonrpc_http_server::AccessControlAllowOrigin::Value(v.into()),
-            })
-            .collect()
-    });
-
+    let max_request_body_size = limits.max_request_body_size();
+    // The origins are checked by the middleware, since the server can't change its CORS domains at runtime.
     HttpServerBuilder::new(handler)
-        .cors(cors_domains.into())
+        .cors(DomainsValidation::Disabled)
+        .max_request_body_size(max_request_body_size)
         .allowed_hosts(allowed_hosts.map(|hosts| hosts.into_iter().map(Host::from).collect()).into())
+        .request_middleware(move |request| match cors.on_request(request) {
+            RequestMiddlewareAction::Proceed {
+                request,
+                ..
+            } => match limits.on_http_request(request) {
+                RequestMiddlewareAction::Proceed {
+                    request,
+                    ..
+                } => match &health {
+                    Some(health) => health.on_request(request),
+                    None => request.into(),
+                },
+                rejected => rejected,
+            },
+            rejected => rejected,
+        })
         .start_http(addr)
 }
 
 /// Start ipc server asynchronously and returns result with `Server` handle on success or an error.
-pub fn start_ipc<M: jsonrpc_core::Metadata>(
+pub fn start_ipc<M: jsonrpc_core::Metadata, S: jsonrpc_core::Middleware<M>>(
     addr: &str,
-    handler: jsonrpc_core::MetaIoHandler<M>,
+    handler: jsonrpc_core::MetaIoHandler<M, S>,
 ) -> Result<IpcServer, io::Error>
 where
     M: Default, {
@@ -0,0 +1,359 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashSet;
+use std::mem;
+use std::sync::Arc;
+
+use ccore::{AccountProvider, AccountProviderError};
+use ccrypto::blake256;
+use ckey::{PlatformAddress, Signature};
+use jsonrpc_core::futures::future::Either;
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{
+    Call, Failure, Id, MethodCall, Metadata, Middleware, Output, Params, Request, Response, Success, Value,
+};
+use primitives::H256;
+use serde_json;
+
+/// The prefix of the method names which ask for the signed results of the methods.
+pub const SIGNED_METHOD_PREFIX: &str = "signed_";
+
+/// Signs the results of the selected methods with the engine signer of the node,
+/// so that the consumers of a shared gateway can prove which node served the data.
+///
+/// The members of a response are fixed by the JSON-RPC server, so a signature can't be added next to the result.
+/// Instead, a client asks for the signed result by prefixing the method with `SIGNED_METHOD_PREFIX`,
+/// e.g. `signed_chain_getBestBlockNumber`. The calls without the prefix get their results as they are.
+pub struct ResponseSigner {
+    account_provider: Arc<AccountProvider>,
+    signer: PlatformAddress,
+    methods: HashSet<String>,
+}
+
+/// A call whose result is signed.
+/// It's executed with an id which no other call of the request has, so that its output is found even if the client
+/// reused the id in a batch.
+struct SignedCall {
+    id: Id,
+    original_id: Id,
+    method: String,
+    params: Params,
+}
+
+/// The hashed payload. The method and the params are included so that a signed result can't be presented as the
+/// answer to another request.
+#[derive(Serialize)]
+struct Payload<'a> {
+    method: &'a str,
+    params: &'a Params,
+    result: &'a Value,
+}
+
+/// The result of a signed call.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignedResult {
+    value: Value,
+    hash: H256,
+    signer: PlatformAddress,
+    signature: Signature,
+}
+
+impl ResponseSigner {
+    /// Fails if the account can't sign without a password.
+    pub fn new(
+        account_provider: Arc<AccountProvider>,
+        signer: PlatformAddress,
+        methods: Vec<String>,
+    ) -> Result<Self, AccountProviderError> {
+        account_provider.sign(signer.into_address(), None, H256::zero())?;
+        Ok(Self {
+            account_provider,
+            signer,
+            methods: methods.into_iter().collect(),
+        })
+    }
+
+    /// Strips the prefix from the calls asking for the signed results, and gives them unique ids.
+    fn prepare(&self, request: Request) -> (Request, Vec<SignedCall>) {
+        let mut used_ids: Vec<Id> = {
+            let calls = match &request {
+                Request::Single(call) => ::std::slice::from_ref(call),
+                Request::Batch(calls) => calls.as_slice(),
+            };
+            calls
+                .iter()
+                .filter_map(|call| match call {
+                    Call::MethodCall(call) => Some(call.id.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let mut signed_calls = Vec::new();
+        let request = match request {
+            Request::Single(call) => Request::Single(self.prepare_call(call, &mut used_ids, &mut signed_calls)),
+            Request::Batch(calls) => Request::Batch(
+                calls.into_iter().map(|call| self.prepare_call(call, &mut used_ids, &mut signed_calls)).collect(),
+            ),
+        };
+        (request, signed_calls)
+    }
+
+    fn prepare_call(&self, call: Call, used_ids: &mut Vec<Id>, signed_calls: &mut Vec<SignedCall>) -> Call {
+        let mut call = match call {
+            Call::MethodCall(call) => call,
+            other => return other,
+        };
+        let method = match self.signed_method(&call.method) {
+            Some(method) => method.to_string(),
+            None => return Call::MethodCall(call),
+        };
+        let id = (0u64..)
+            .map(|n| Id::Str(format!("signed-{}", n)))
+            .find(|id| !used_ids.contains(id))
+            .expect("There are infinitely many ids");
+        used_ids.push(id.clone());
+        let original_id = mem::replace(&mut call.id, id.clone());
+        call.method = method.clone();
+        signed_calls.push(SignedCall {
+            id,
+            original_id,
+            method,
+            params: call.params.clone(),
+        });
+        Call::MethodCall(call)
+    }
+
+    /// The selected method which the call of `method` asks to sign.
+    fn signed_method<'a>(&self, method: &'a str) -> Option<&'a str> {
+        if !method.starts_with(SIGNED_METHOD_PREFIX) {
+            return None
+        }
+        let method = &method[SIGNED_METHOD_PREFIX.len()..];
+        if self.methods.contains(method) {
+            Some(method)
+        } else {
+            None
+        }
+    }
+
+    fn sign_response(&self, response: Response, calls: &[SignedCall]) -> Response {
+        match response {
+            Response::Single(output) => Response::Single(self.sign_output(output, calls)),
+            Response::Batch(outputs) => {
+                Response::Batch(outputs.into_iter().map(|output| self.sign_output(output, calls)).collect())
+            }
+        }
+    }
+
+    fn sign_output(&self, output: Output, calls: &[SignedCall]) -> Output {
+        match output {
+            Output::Success(Success {
+                jsonrpc,
+                result,
+                id,
+            }) => {
+                let call = calls.iter().find(|call| call.id == id);
+                match call {
+                    Some(call) => Output::Success(Success {
+                        jsonrpc,
+                        result: self.sign(&call.method, &call.params, result),
+                        id: call.original_id.clone(),
+                    }),
+                    None => Output::Success(Success {
+                        jsonrpc,
+                        result,
+                        id,
+                    }),
+                }
+            }
+            Output::Failure(Failure {
+                jsonrpc,
+                error,
+                id,
+            }) => {
+                let call = calls.iter().find(|call| call.id == id);
+                Output::Failure(Failure {
+                    jsonrpc,
+                    error,
+                    id: call.map_or(id, |call| call.original_id.clone()),
+                })
+            }
+        }
+    }
+
+    fn sign(&self, method: &str, params: &Params, result: Value) -> Value {
+        let hash = {
+            let payload = Payload {
+                method,
+                params,
+                result: &result,
+            };
+            blake256(serde_json::to_vec(&payload).expect("The payload is always serializable"))
+        };
+        match self.account_provider.sign(self.signer.into_address(), None, hash) {
+            Ok(signature) => {
+                let signed = SignedResult {
+                    value: result,
+                    hash,
+                    signer: self.signer,
+                    signature,
+                };
+                serde_json::to_value(signed).expect("The signed result is always serializable")
+            }
+            Err(err) => {
+                cwarn!(RPC, "Cannot sign the result of {}: {}", method, err);
+                result
+            }
+        }
+    }
+}
+
+/// Signs the results of the methods selected by the `ResponseSigner`, after the inner middleware.
+pub struct SigningMiddleware<T> {
+    inner: T,
+    signer: Option<Arc<ResponseSigner>>,
+}
+
+impl<T> SigningMiddleware<T> {
+    pub fn new(inner: T, signer: Option<Arc<ResponseSigner>>) -> Self {
+        Self {
+            inner,
+            signer,
+        }
+    }
+}
+
+impl<M: Metadata, T: Middleware<M>> Middleware<M> for SigningMiddleware<T> {
+    type Future = Either<T::Future, Box<Future<Item = Option<Response>, Error = ()> + Send>>;
+
+    fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X>
+    where
+        F: FnOnce(Request, M) -> X + Send,
+        X: Future<Item = Option<Response>, Error = ()> + Send + 'static, {
+        let signer = match &self.signer {
+            Some(signer) => Arc::clone(signer),
+            None => return forward(self.inner.on_request(request, meta, next)),
+        };
+        let (request, calls) = signer.prepare(request);
+        if calls.is_empty() {
+            return forward(self.inner.on_request(request, meta, next))
+        }
+        let response = self.inner.on_request(request, meta, next);
+        Either::A(Either::B(Box::new(
+            response.map(move |response| response.map(|response| signer.sign_response(response, &calls))),
+        )))
+    }
+}
+
+fn forward<A, B, X>(response: Either<A, X>) -> Either<Either<A, B>, X> {
+    match response {
+        Either::A(a) => Either::A(Either::A(a)),
+        Either::B(x) => Either::B(x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckey::{verify_address, Generator, NetworkId, Random};
+    use jsonrpc_core::middleware::Noop;
+    use jsonrpc_core::{Compatibility, MetaIoHandler};
+
+    use super::*;
+
+    type Handler = MetaIoHandler<(), SigningMiddleware<Noop>>;
+
+    fn handler() -> (Handler, PlatformAddress) {
+        let account_provider = AccountProvider::transient_provider();
+        let key_pair = Random.generate().unwrap();
+        let address = account_provider.insert_account(key_pair.private().clone(), &"test".into()).unwrap();
+        account_provider.unlock_account_permanently(address, "test".into()).unwrap();
+        let signer = PlatformAddress::create(0, NetworkId::default(), address);
+
+        let methods = vec!["chain_getValue".to_string()];
+        let response_signer = Arc::new(ResponseSigner::new(account_provider, signer, methods).unwrap());
+        let mut handler = MetaIoHandler::new(Compatibility::V2, SigningMiddleware::new(Noop, Some(response_signer)));
+        handler.add_method("chain_getValue", |_params: Params| Ok(Value::from(1)));
+        handler.add_method("chain_getOther", |_params: Params| Ok(Value::from(2)));
+        (handler, signer)
+    }
+
+    fn outputs(handler: &Handler, request: &str) -> Vec<Value> {
+        let response = handler.handle_request_sync(request, ()).unwrap();
+        ::serde_json::from_str(&response).unwrap()
+    }
+
+    fn assert_signed(signer: &PlatformAddress, params: &str, result: &Value) {
+        let hash: H256 = ::serde_json::from_value(result["hash"].clone()).unwrap();
+        let signature: Signature = ::serde_json::from_value(result["signature"].clone()).unwrap();
+        let payload = Payload {
+            method: "chain_getValue",
+            params: &::serde_json::from_str(params).unwrap(),
+            result: &Value::from(1),
+        };
+        assert_eq!(blake256(::serde_json::to_vec(&payload).unwrap()), hash);
+        assert_eq!(Value::String(signer.to_string()), result["signer"]);
+        assert!(verify_address(&signer.into_address(), &signature, &hash).unwrap());
+    }
+
+    #[test]
+    fn result_without_the_prefix_is_not_signed() {
+        let (handler, _) = handler();
+        let request = r#"[{"jsonrpc":"2.0","method":"chain_getValue","params":[],"id":0}]"#;
+        let outputs = outputs(&handler, request);
+        assert_eq!(Value::from(0), outputs[0]["id"]);
+        assert_eq!(Value::from(1), outputs[0]["result"]);
+    }
+
+    #[test]
+    fn result_with_the_prefix_is_signed() {
+        let (handler, signer) = handler();
+        let request = r#"[{"jsonrpc":"2.0","method":"signed_chain_getValue","params":[3],"id":0}]"#;
+        let outputs = outputs(&handler, request);
+        assert_eq!(Value::from(0), outputs[0]["id"]);
+        assert_eq!(Value::from(1), outputs[0]["result"]["value"]);
+        assert_signed(&signer, "[3]", &outputs[0]["result"]);
+    }
+
+    #[test]
+    fn calls_of_batch_with_the_same_id_are_paired_with_their_outputs() {
+        let (handler, signer) = handler();
+        let request = r#"[
+            {"jsonrpc":"2.0","method":"chain_getValue","params":[],"id":0},
+            {"jsonrpc":"2.0","method":"signed_chain_getValue","params":[],"id":0},
+            {"jsonrpc":"2.0","method":"chain_getOther","params":[],"id":"signed-0"}
+        ]"#;
+        let outputs = outputs(&handler, request);
+        assert_eq!(3, outputs.len());
+        assert_eq!(Value::from(0), outputs[0]["id"]);
+        assert_eq!(Value::from(1), outputs[0]["result"]);
+        assert_eq!(Value::from(0), outputs[1]["id"]);
+        assert_signed(&signer, "[]", &outputs[1]["result"]);
+        assert_eq!(Value::from("signed-0"), outputs[2]["id"]);
+        assert_eq!(Value::from(2), outputs[2]["result"]);
+    }
+
+    #[test]
+    fn method_which_is_not_selected_cannot_be_signed() {
+        let (handler, _) = handler();
+        let request = r#"[{"jsonrpc":"2.0","method":"signed_chain_getOther","params":[],"id":0}]"#;
+        let outputs = outputs(&handler, request);
+        assert_eq!(Value::from(0), outputs[0]["id"]);
+        assert_eq!(Value::from(-32601), outputs[0]["error"]["code"]);
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use clogger;
+use jsonrpc_core::futures::future::Either;
+use jsonrpc_core::futures::{Future, Poll};
+use jsonrpc_core::{Call, Failure, Metadata, Middleware, Output, Request, Response, Value};
+use serde_json::Map;
+
+/// Tags each request with a new correlation id, so that the logs of the miner and the network about a parcel
+/// submitted by the request can be followed with it.
+///
+/// The span covers the methods both while the inner middleware is called and while the response is polled, so the
+/// asynchronous methods are tagged as well. The correlation id is returned in the `data` of the errors.
+pub struct TracingMiddleware<T> {
+    inner: T,
+}
+
+impl<T> TracingMiddleware<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+        }
+    }
+}
+
+impl<M: Metadata, T: Middleware<M>> Middleware<M> for TracingMiddleware<T> {
+    type Future = Box<Future<Item = Option<Response>, Error = ()> + Send>;
+
+    fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X>
+    where
+        F: FnOnce(Request, M) -> X + Send,
+        X: Future<Item = Option<Response>, Error = ()> + Send + 'static, {
+        let span = clogger::new_span();
+        let response = {
+            let _span = clogger::enter_span(span);
+            cdebug!(RPC, "Request: {:?}", methods(&request));
+            self.inner.on_request(request, meta, next)
+        };
+        let response = Spanned {
+            span,
+            inner: response,
+        };
+        Either::A(Box::new(response.map(move |response| response.map(|response| with_correlation_id(response, span)))))
+    }
+}
+
+/// A future which is polled in the span.
+struct Spanned<F> {
+    span: u64,
+    inner: F,
+}
+
+impl<F: Future> Future for Spanned<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        let _span = clogger::enter_span(self.span);
+        self.inner.poll()
+    }
+}
+
+fn with_correlation_id(response: Response, span: u64) -> Response {
+    let tag = |output: Output| match output {
+        Output::Failure(Failure {
+            jsonrpc,
+            mut error,
+            id,
+        }) => {
+            error.data = match error.data.take() {
+                None => Some(correlation_id(Map::new(), span)),
+                Some(Value::Object(data)) => Some(correlation_id(data, span)),
+                // The errors which carry a message in the data keep it as it is.
+                data => data,
+            };
+            Output::Failure(Failure {
+                jsonrpc,
+                error,
+                id,
+            })
+        }
+        success @ Output::Success(_) => success,
+    };
+    match response {
+        Response::Single(output) => Response::Single(tag(output)),
+        Response::Batch(outputs) => Response::Batch(outputs.into_iter().map(tag).collect()),
+    }
+}
+
+fn correlation_id(mut data: Map<String, Value>, span: u64) -> Value {
+    data.insert("correlationId".to_string(), Value::from(span));
+    Value::Object(data)
+}
+
+fn methods(request: &Request) -> Vec<&str> {
+    let calls = match request {
+        Request::Single(call) => ::std::slice::from_ref(call),
+        Request::Batch(calls) => calls.as_slice(),
+    };
+    calls
+        .iter()
+        .filter_map(|call| match call {
+            Call::MethodCall(call) => Some(call.method.as_str()),
+            Call::Notification(notification) => Some(notification.method.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::futures::future;
+    use jsonrpc_core::middleware::Noop;
+    use jsonrpc_core::{Compatibility, Error, MetaIoHandler, Params};
+    use serde_json;
+
+    use super::*;
+
+    fn handler() -> MetaIoHandler<(), TracingMiddleware<Noop>> {
+        let mut handler = MetaIoHandler::new(Compatibility::V2, TracingMiddleware::new(Noop));
+        // The span is read when the future is polled, after the method is called.
+        handler.add_method("span", |_params: Params| future::lazy(|| Ok(Value::from(clogger::current_span()))));
+        handler.add_method("fail", |_params: Params| Err::<Value, _>(Error::internal_error()));
+        handler
+    }
+
+    fn output(handler: &MetaIoHandler<(), TracingMiddleware<Noop>>, method: &str) -> Value {
+        let request = format!(r#"{{"jsonrpc": "2.0", "method": "{}", "params": [], "id": 1}}"#, method);
+        serde_json::from_str(&handler.handle_request_sync(&request, ()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn asynchronous_method_is_in_the_span() {
+        let handler = handler();
+        assert!(output(&handler, "span")["result"].is_u64());
+        assert_eq!(None, clogger::current_span());
+    }
+
+    #[test]
+    fn correlation_id_is_returned_with_the_error() {
+        let handler = handler();
+        let first = output(&handler, "fail")["error"]["data"]["correlationId"].as_u64().unwrap();
+        let second = output(&handler, "fail")["error"]["data"]["correlationId"].as_u64().unwrap();
+        assert_ne!(first, second);
+    }
+}
@@ -25,10 +25,11 @@ use cstate::StateError;
 use ctypes::parcel::Error as ParcelError;
 use kvdb::Error as KVDBError;
 use rlp::DecoderError;
+use serde_json::Map;
 
 use jsonrpc_core::{Error, ErrorCode, Value};
 
-mod codes {
+pub(crate) mod codes {
     pub const NO_AUTHOR: i64 = -32002;
     pub const NO_WORK_REQUIRED: i64 = -32004;
     pub const RLP_ERROR: i64 = -32009;
@@ -38,6 +39,18 @@ mod codes {
     pub const NETWORK_DISABLED: i64 = -32014;
     pub const NETWORK_CANNOT_DISCONNECT_NOT_CONNECTED_ERROR: i64 = -32015;
     pub const ACCOUNT_PROVIDER_ERROR: i64 = -32016;
+    pub const FILTER_NOT_FOUND: i64 = -32018;
+    pub const NO_NODE_ALLOW_LIST: i64 = -32019;
+    pub const INVALID_NODE_ALLOW_LIST: i64 = -32020;
+    pub const MAINTENANCE_MODE: i64 = -32021;
+    pub const CANNOT_SEAL: i64 = -32022;
+    pub const SNAPSHOT_NOT_FOUND: i64 = -32023;
+    pub const PARCEL_NOT_FOUND: i64 = -32024;
+    pub const IDEMPOTENCY_KEY_REUSED: i64 = -32025;
+    pub const READ_ONLY: i64 = -32026;
+    pub const CONFIG_RELOAD_FAILED: i64 = -32027;
+    pub const TOO_MANY_REQUESTS: i64 = -32028;
+    pub const PAYLOAD_TOO_LARGE: i64 = -32029;
     pub const VERIFICATION_FAILED: i64 = -32030;
     pub const ALREADY_IMPORTED: i64 = -32031;
     pub const NOT_ENOUGH_BALANCE: i64 = -32032;
@@ -45,12 +58,16 @@ mod codes {
     pub const TOO_CHEAP_TO_REPLACE: i64 = -32034;
     pub const INVALID_NONCE: i64 = -32035;
     pub const INVALID_NETWORK_ID: i64 = -32036;
+    pub const POOL_FULL: i64 = -32037;
+    pub const SENDER_LIMIT_REACHED: i64 = -32038;
+    pub const FUTURE_LIMIT_REACHED: i64 = -32039;
     pub const KEYSTORE_ERROR: i64 = -32040;
     pub const KEY_ERROR: i64 = -32041;
     pub const ALREADY_EXISTS: i64 = -32042;
     pub const WRONG_PASSWORD: i64 = -32043;
     pub const NO_SUCH_ACCOUNT: i64 = -32044;
     pub const NOT_UNLOCKED: i64 = -32045;
+    pub const SUBMISSION_IN_PROGRESS: i64 = -32046;
     pub const UNKNOWN_ERROR: i64 = -32099;
 }
 
@@ -66,11 +83,7 @@ pub fn core<T: Into<CoreError>>(error: T) -> Error {
 pub fn parcel_state<T: Into<StateError>>(error: T) -> Error {
     let error = error.into();
     if let StateError::Parcel(e) = error {
-        Error {
-            code: ErrorCode::ServerError(codes::PARCEL_ERROR),
-            message: format!("{}", e),
-            data: None,
-        }
+        parcel(e)
     } else {
         Error {
             code: ErrorCode::ServerError(codes::UNKNOWN_ERROR),
@@ -82,73 +95,71 @@ pub fn parcel_state<T: Into<StateError>>(error: T) -> Error {
 
 pub fn parcel_core<T: Into<CoreError>>(error: T) -> Error {
     let error = error.into();
-    let unknown_error = Error {
-        code: ErrorCode::ServerError(codes::UNKNOWN_ERROR),
-        message: "Unknown error when sending parcel.".into(),
-        data: Some(Value::String(format!("{:?}", error))),
-    };
     match error {
-        CoreError::Key(error) => match error {
-            KeyError::InvalidSignature => Error {
-                code: ErrorCode::ServerError(codes::VERIFICATION_FAILED),
-                message: "Verification Failed".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            KeyError::InvalidNetworkId(_) => Error {
-                code: ErrorCode::ServerError(codes::INVALID_NETWORK_ID),
-                message: "Invalid NetworkId".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            _ => unknown_error,
+        CoreError::Key(KeyError::InvalidSignature) => Error {
+            code: ErrorCode::ServerError(codes::VERIFICATION_FAILED),
+            message: "Verification Failed".into(),
+            data: Some(reason("InvalidSignature")),
         },
-        CoreError::State(StateError::Parcel(error)) => match error {
-            ParcelError::InvalidSignature(_) => Error {
-                code: ErrorCode::ServerError(codes::VERIFICATION_FAILED),
-                message: "Verification Failed".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            ParcelError::InvalidNetworkId(_) => Error {
-                code: ErrorCode::ServerError(codes::INVALID_NETWORK_ID),
-                message: "Invalid NetworkId".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            ParcelError::ParcelAlreadyImported => Error {
-                code: ErrorCode::ServerError(codes::ALREADY_IMPORTED),
-                message: "Already Imported".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            ParcelError::InsufficientBalance {
-                ..
-            } => Error {
-                code: ErrorCode::ServerError(codes::NOT_ENOUGH_BALANCE),
-                message: "Not Enough Balance".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            ParcelError::InsufficientFee {
-                ..
-            } => Error {
-                code: ErrorCode::ServerError(codes::TOO_LOW_FEE),
-                message: "Too Low Fee".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            ParcelError::TooCheapToReplace => Error {
-                code: ErrorCode::ServerError(codes::TOO_CHEAP_TO_REPLACE),
-                message: "Too Cheap to Replace".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            ParcelError::Old {
-                ..
-            } => Error {
-                code: ErrorCode::ServerError(codes::INVALID_NONCE),
-                message: "Invalid Nonce".into(),
-                data: Some(Value::String(format!("{:?}", error))),
-            },
-            _ => unknown_error,
+        CoreError::Key(KeyError::InvalidNetworkId(network_id)) => Error {
+            code: ErrorCode::ServerError(codes::INVALID_NETWORK_ID),
+            message: "Invalid NetworkId".into(),
+            data: Some(reason_with_content("InvalidNetworkId", Value::String(network_id.to_string()))),
         },
-        _ => unknown_error,
+        CoreError::State(StateError::Parcel(error)) => parcel(error),
+        CoreError::AccountProvider(error) => account_provider(error),
+        _ => Error {
+            code: ErrorCode::ServerError(codes::UNKNOWN_ERROR),
+            message: "Unknown error when sending parcel.".into(),
+            data: Some(Value::String(format!("{:?}", error))),
+        },
+    }
+}
+
+/// Maps the parcel error to its code. The `data` is the error itself, as `{ "type": <reason>, "content": <details> }`.
+fn parcel(error: ParcelError) -> Error {
+    let (code, message) = match error {
+        ParcelError::InvalidSignature(_) => (codes::VERIFICATION_FAILED, "Verification Failed"),
+        ParcelError::InvalidNetworkId(_) => (codes::INVALID_NETWORK_ID, "Invalid NetworkId"),
+        ParcelError::ParcelAlreadyImported | ParcelError::TransactionAlreadyImported => {
+            (codes::ALREADY_IMPORTED, "Already Imported")
+        }
+        ParcelError::InsufficientBalance {
+            ..
+        } => (codes::NOT_ENOUGH_BALANCE, "Not Enough Balance"),
+        ParcelError::InsufficientFee {
+            ..
+        } => (codes::TOO_LOW_FEE, "Too Low Fee"),
+        ParcelError::TooCheapToReplace => (codes::TOO_CHEAP_TO_REPLACE, "Too Cheap to Replace"),
+        ParcelError::Old
+        | ParcelError::InvalidNonce {
+            ..
+        } => (codes::INVALID_NONCE, "Invalid Nonce"),
+        ParcelError::LimitReached => (codes::POOL_FULL, "Pool Full"),
+        ParcelError::SenderLimitReached => (codes::SENDER_LIMIT_REACHED, "Too Many Parcels From Sender"),
+        ParcelError::FutureLimitReached => (codes::FUTURE_LIMIT_REACHED, "Too Many Future Parcels"),
+        _ => (codes::PARCEL_ERROR, "Invalid Parcel"),
+    };
+    Error {
+        code: ErrorCode::ServerError(code),
+        message: message.into(),
+        data: Some(::serde_json::to_value(&error).expect("Parcel errors are always serializable")),
     }
 }
 
+fn reason(name: &str) -> Value {
+    let mut data = Map::new();
+    data.insert("type".to_string(), Value::String(name.to_string()));
+    Value::Object(data)
+}
+
+fn reason_with_content(name: &str, content: Value) -> Value {
+    let mut data = Map::new();
+    data.insert("type".to_string(), Value::String(name.to_string()));
+    data.insert("content".to_string(), content);
+    Value::Object(data)
+}
+
 pub fn kvdb(error: KVDBError) -> Error {
     Error {
         code: ErrorCode::ServerError(codes::KVDB_ERROR),
@@ -158,10 +169,14 @@ pub fn kvdb(error: KVDBError) -> Error {
 }
 
 pub fn rlp(error: DecoderError) -> Error {
+    let data = match error {
+        DecoderError::Custom(message) => reason_with_content("Custom", Value::String(message.to_string())),
+        _ => reason(&format!("{:?}", error)),
+    };
     Error {
         code: ErrorCode::ServerError(codes::RLP_ERROR),
         message: "Invalid RLP.".into(),
-        data: Some(Value::String(format!("{:?}", error))),
+        data: Some(data),
     }
 }
 
@@ -215,6 +230,54 @@ pub fn no_author() -> Error {
     }
 }
 
+pub fn filter_not_found() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::FILTER_NOT_FOUND),
+        message: "Filter not found".into(),
+        data: None,
+    }
+}
+
+pub fn cannot_seal() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::CANNOT_SEAL),
+        message: "The engine cannot seal a block by itself.".into(),
+        data: None,
+    }
+}
+
+pub fn snapshot_not_found() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::SNAPSHOT_NOT_FOUND),
+        message: "Snapshot not found".into(),
+        data: None,
+    }
+}
+
+pub fn parcel_not_found() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::PARCEL_NOT_FOUND),
+        message: "The parcel is not a pending parcel of a local account.".into(),
+        data: None,
+    }
+}
+
+pub fn idempotency_key_reused() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::IDEMPOTENCY_KEY_REUSED),
+        message: "The idempotency key is already used for a different parcel.".into(),
+        data: None,
+    }
+}
+
+pub fn submission_in_progress() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::SUBMISSION_IN_PROGRESS),
+        message: "The parcel with the idempotency key is being submitted. Retry later.".into(),
+        data: None,
+    }
+}
+
 pub fn no_work_required() -> Error {
     Error {
         code: ErrorCode::ServerError(codes::NO_WORK_REQUIRED),
@@ -235,9 +298,76 @@ pub fn network_control(error: NetworkControlError) -> Error {
             message: "Network is diabled.".into(),
             data: None,
         },
+        NetworkControlError::NoNodeAllowList => Error {
+            code: ErrorCode::ServerError(codes::NO_NODE_ALLOW_LIST),
+            message: "The network is not in the node allow-list mode.".into(),
+            data: None,
+        },
+        NetworkControlError::InvalidNodeAllowList(err) => Error {
+            code: ErrorCode::ServerError(codes::INVALID_NODE_ALLOW_LIST),
+            message: format!("{}", err),
+            data: None,
+        },
+        NetworkControlError::InvalidPeerLimits(err) => Error::invalid_params(err),
+    }
+}
+
+pub fn maintenance(retry_after: u64) -> Error {
+    let mut data = Map::new();
+    data.insert("retryAfter".to_string(), Value::from(retry_after));
+    Error {
+        code: ErrorCode::ServerError(codes::MAINTENANCE_MODE),
+        message: "The node is under maintenance. Retry later.".into(),
+        data: Some(Value::Object(data)),
+    }
+}
+
+pub fn read_only() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::READ_ONLY),
+        message: "The node is read-only.".into(),
+        data: None,
+    }
+}
+
+pub fn config_reload(error: String) -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::CONFIG_RELOAD_FAILED),
+        message: format!("Cannot reload the configuration: {}", error),
+        data: None,
+    }
+}
+
+pub fn too_many_requests() -> Error {
+    Error {
+        code: ErrorCode::ServerError(codes::TOO_MANY_REQUESTS),
+        message: "Too many requests are being handled. Retry later.".into(),
+        data: None,
+    }
+}
+
+pub fn payload_too_large(max_payload: usize) -> Error {
+    let mut data = Map::new();
+    data.insert("maxPayload".to_string(), Value::from(max_payload));
+    Error {
+        code: ErrorCode::ServerError(codes::PAYLOAD_TOO_LARGE),
+        message: format!("The request is larger than {} bytes.", max_payload),
+        data: Some(Value::Object(data)),
+    }
+}
+
+pub fn batch_too_large(max_batch_size: usize) -> Error {
+    Error {
+        code: ErrorCode::InvalidRequest,
+        message: format!("The batch has more than {} calls.", max_batch_size),
+        data: None,
     }
 }
 
+pub fn invalid_params<T: fmt::Display>(error: T) -> Error {
+    Error::invalid_params(format!("{}", error))
+}
+
 /// Internal error signifying a logic error in code.
 /// Should not be used when function can just fail
 /// because of invalid parameters or incomplete node state.
@@ -248,3 +378,46 @@ pub fn internal<T: fmt::Debug>(error: &str, data: T) -> Error {
         data: Some(Value::String(format!("{:?}", data))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use primitives::U256;
+
+    use super::*;
+
+    #[test]
+    fn parcel_error_has_its_code_and_data() {
+        let error = parcel_core(StateError::Parcel(ParcelError::InsufficientFee {
+            minimal: U256::from(10),
+            got: U256::from(1),
+        }));
+        assert_eq!(ErrorCode::ServerError(codes::TOO_LOW_FEE), error.code);
+        let data = error.data.unwrap();
+        assert_eq!(Value::String("InsufficientFee".to_string()), data["type"]);
+        assert_eq!(Value::String("0xa".to_string()), data["content"]["minimal"]);
+    }
+
+    #[test]
+    fn full_pool_is_distinguished() {
+        let error = parcel_core(StateError::Parcel(ParcelError::LimitReached));
+        assert_eq!(ErrorCode::ServerError(codes::POOL_FULL), error.code);
+        assert_eq!(Some(reason("LimitReached")), error.data);
+    }
+
+    #[test]
+    fn pool_limits_of_sender_and_future_have_their_codes() {
+        let error = parcel_core(StateError::Parcel(ParcelError::SenderLimitReached));
+        assert_eq!(ErrorCode::ServerError(codes::SENDER_LIMIT_REACHED), error.code);
+        assert_eq!(Some(reason("SenderLimitReached")), error.data);
+
+        let error = parcel_core(StateError::Parcel(ParcelError::FutureLimitReached));
+        assert_eq!(ErrorCode::ServerError(codes::FUTURE_LIMIT_REACHED), error.code);
+        assert_eq!(Some(reason("FutureLimitReached")), error.data);
+    }
+
+    #[test]
+    fn rlp_error_has_its_reason() {
+        let error = rlp(DecoderError::RlpIsTooShort);
+        assert_eq!(Some(reason("RlpIsTooShort")), error.data);
+    }
+}
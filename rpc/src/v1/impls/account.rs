@@ -17,13 +17,16 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use ccore::AccountProvider;
-use ckey::{NetworkId, Password, PlatformAddress, Signature};
+use ccore::{AccountProvider, KeyRotationAction, ParcelSignature, UnverifiedParcel};
+use ckey::{public_to_address, NetworkId, Password, PlatformAddress, Signature};
+use cnetwork::{node_allow_list_hash, SocketAddr};
 use jsonrpc_core::Result;
 use primitives::H256;
+use rlp::{Encodable, UntrustedRlp};
 
 use super::super::errors::{self, account_provider};
 use super::super::traits::Account;
+use super::super::types::Bytes;
 
 pub struct AccountClient {
     account_provider: Arc<AccountProvider>,
@@ -73,6 +76,74 @@ impl Account for AccountClient {
             .map_err(account_provider)
     }
 
+    fn sign_key_rotation(
+        &self,
+        validator: PlatformAddress,
+        new_key: PlatformAddress,
+        activation: u64,
+        seq: u64,
+        signer: Option<PlatformAddress>,
+        passphrase: Option<Password>,
+    ) -> Result<Bytes> {
+        let validator = validator.try_into_address().map_err(errors::core)?;
+        let new_key = new_key.try_into_address().map_err(errors::core)?;
+        let signer = match signer {
+            Some(signer) => signer.try_into_address().map_err(errors::core)?,
+            None => validator,
+        };
+        let message = KeyRotationAction::message(&validator, &new_key, activation, seq);
+        let signature = self.account_provider.sign(signer, passphrase, message).map_err(account_provider)?;
+        let action = KeyRotationAction {
+            validator,
+            new_key,
+            activation,
+            seq,
+            signature,
+        };
+        Ok(action.rlp_bytes().into_vec().into())
+    }
+
+    fn sign_node_allow_list(
+        &self,
+        version: u64,
+        nodes: Vec<::std::net::SocketAddr>,
+        authority: PlatformAddress,
+        passphrase: Option<Password>,
+    ) -> Result<Signature> {
+        let authority = authority.try_into_address().map_err(errors::core)?;
+        let nodes: Vec<_> = nodes.into_iter().map(|address| SocketAddr::from(address).into()).collect();
+        let message = node_allow_list_hash(version, &nodes);
+        self.account_provider.sign(authority, passphrase, message).map_err(account_provider)
+    }
+
+    fn sign_multisig_parcel(
+        &self,
+        parcel: Bytes,
+        signer: PlatformAddress,
+        passphrase: Option<Password>,
+    ) -> Result<Bytes> {
+        let signer = signer.try_into_address().map_err(errors::core)?;
+        let parcel: UnverifiedParcel = UntrustedRlp::new(&parcel.into_vec()).as_val().map_err(errors::rlp)?;
+        let (index, message) = match parcel.signature() {
+            ParcelSignature::Multi(multisig) => {
+                let index = multisig
+                    .publics()
+                    .iter()
+                    .position(|public| public_to_address(public) == signer)
+                    .ok_or_else(|| errors::invalid_params("The account is not a co-signer of the parcel"))?;
+                (index as u8, multisig.message(&parcel.as_unsigned().hash()))
+            }
+            ParcelSignature::Single(_)
+            | ParcelSignature::Ed25519 {
+                ..
+            } => return Err(errors::invalid_params("The parcel is not of a multi-signature account")),
+        };
+        let signature = self.account_provider.sign(signer, passphrase, message).map_err(account_provider)?;
+        let parcel =
+            parcel.update_multisig(|multisig| multisig.add_signature(index, signature)).map_err(errors::core)?;
+        Ok(parcel.rlp_bytes().into_vec().into())
+    }
+
     fn unlock(&self, address: PlatformAddress, password: Password, duration: Option<u64>) -> Result<()> {
         const DEFAULT_DURATION: u64 = 300;
         match duration {
@@ -0,0 +1,45 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use jsonrpc_core::Result;
+
+use super::super::super::Maintenance;
+use super::super::traits::Admin;
+
+pub struct AdminClient {
+    maintenance: Arc<Maintenance>,
+}
+
+impl AdminClient {
+    pub fn new(maintenance: &Arc<Maintenance>) -> Self {
+        Self {
+            maintenance: Arc::clone(maintenance),
+        }
+    }
+}
+
+impl Admin for AdminClient {
+    fn set_maintenance_mode(&self, enabled: bool) -> Result<()> {
+        self.maintenance.set(enabled);
+        Ok(())
+    }
+
+    fn get_maintenance_mode(&self) -> Result<bool> {
+        Ok(self.maintenance.is_enabled())
+    }
+}
@@ -14,45 +14,236 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use ccore::{
-    AssetClient, BlockId, EngineInfo, ExecuteClient, MinerService, MiningBlockChainClient, RegularKey, RegularKeyOwner,
-    Shard, SignedParcel, UnverifiedParcel,
+    multisig_public, AssetClient, BlockId, ChainStatistics, EngineInfo, ExecuteClient, MinerService,
+    MiningBlockChainClient, ParcelSignature, RegularKey, RegularKeyOwner, Shard, SignedParcel, StateInfo, TraceClient,
+    UnverifiedParcel,
 };
-use ckey::{NetworkId, PlatformAddress, Public};
-use cstate::{AssetScheme, AssetSchemeAddress, OwnedAsset};
+use ccrypto::blake256;
+use ckey::{public_to_address, Address, NetworkId, PlatformAddress, Public};
+use cstate::{AssetScheme, AssetSchemeAddress, OwnedAsset, TopStateInfo};
 use ctypes::invoice::{ParcelInvoice, TransactionInvoice};
 use ctypes::parcel::Action;
 use ctypes::{BlockNumber, ShardId, WorldId};
+use parking_lot::Mutex;
 use primitives::{H256, U256};
-use rlp::{DecoderError, UntrustedRlp};
+use rlp::{DecoderError, Encodable, UntrustedRlp};
 
 use jsonrpc_core::Result;
+use jsonrpc_macros::Trailing;
 
 use super::super::errors;
 use super::super::traits::Chain;
-use super::super::types::{Block, BlockNumberAndHash, Bytes, Parcel, ShardChange, Transaction};
+use super::super::types::{
+    Account, AccountsSnapshot, Block, BlockNumberAndHash, Bytes, CacheStats, ChainStats, FinalizedBlock, Log,
+    LogFilter, MaybeProven, NameRecord, Parcel, ParcelCall, ParcelLocation, Proven, ShardChange, SyncStatus,
+    Transaction, MAX_LOGS,
+};
+
+/// The results of the submissions with idempotency keys are remembered for this long.
+const IDEMPOTENCY_KEY_LIFETIME_SECS: u64 = 10 * 60;
+/// The number of idempotency keys remembered at once.
+const MAX_IDEMPOTENCY_KEYS: usize = 64 * 1024;
+/// The longest idempotency key in bytes.
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 64;
+
+struct Submission {
+    payload_hash: H256,
+    /// `None` while the payload is being submitted.
+    result: Option<H256>,
+    submitted_at: Instant,
+}
+
+/// The results of the submissions indexed by their idempotency keys, which expire after the lifetime.
+struct IdempotencyKeys {
+    lifetime: Duration,
+    capacity: usize,
+    submissions: HashMap<String, Submission>,
+    /// The keys in the order they are taken, with the time they are taken.
+    expirations: VecDeque<(Instant, String)>,
+}
+
+impl IdempotencyKeys {
+    fn new(lifetime: Duration, capacity: usize) -> Self {
+        Self {
+            lifetime,
+            capacity,
+            submissions: HashMap::new(),
+            expirations: VecDeque::new(),
+        }
+    }
+
+    /// Takes the key for the payload before it's submitted, or returns the result of the earlier submission.
+    /// It's an error to reuse the key for a different payload, or while the earlier submission is not finished.
+    fn begin(&mut self, key: &str, payload_hash: &H256, now: Instant) -> Result<Option<H256>> {
+        self.prune(now);
+        match self.submissions.get(key) {
+            Some(submission) if submission.payload_hash != *payload_hash => {
+                return Err(errors::idempotency_key_reused())
+            }
+            Some(submission) => return submission.result.map(Some).ok_or_else(errors::submission_in_progress),
+            None => {}
+        }
+        if self.submissions.len() >= self.capacity {
+            return Err(errors::too_many_requests())
+        }
+        self.submissions.insert(
+            key.to_string(),
+            Submission {
+                payload_hash: *payload_hash,
+                result: None,
+                submitted_at: now,
+            },
+        );
+        self.expirations.push_back((now, key.to_string()));
+        Ok(None)
+    }
+
+    /// Remembers the result of the submission. The key is freed if it failed, so it can be retried.
+    fn finish(&mut self, key: &str, result: &Result<H256>) {
+        match result {
+            Ok(result) => {
+                if let Some(submission) = self.submissions.get_mut(key) {
+                    submission.result = Some(*result);
+                }
+            }
+            Err(_) => {
+                self.submissions.remove(key);
+            }
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((taken_at, key)) = self.expirations.pop_front() {
+            if now.duration_since(taken_at) < self.lifetime {
+                self.expirations.push_front((taken_at, key));
+                break
+            }
+            // The key may be taken again after a failed submission freed it.
+            if self.submissions.get(&key).map_or(false, |submission| submission.submitted_at == taken_at) {
+                self.submissions.remove(&key);
+            }
+        }
+    }
+}
 
 pub struct ChainClient<C, M>
 where
-    C: AssetClient + MiningBlockChainClient + Shard + RegularKey + RegularKeyOwner + ExecuteClient + EngineInfo,
+    C: AssetClient
+        + MiningBlockChainClient
+        + Shard
+        + RegularKey
+        + RegularKeyOwner
+        + StateInfo
+        + ExecuteClient
+        + TraceClient
+        + EngineInfo,
     M: MinerService, {
     client: Arc<C>,
     miner: Arc<M>,
+    chain_stats: Arc<ChainStatistics>,
+    idempotency_keys: Mutex<IdempotencyKeys>,
 }
 
 impl<C, M> ChainClient<C, M>
 where
-    C: AssetClient + MiningBlockChainClient + Shard + RegularKey + RegularKeyOwner + ExecuteClient + EngineInfo,
+    C: AssetClient
+        + MiningBlockChainClient
+        + Shard
+        + RegularKey
+        + RegularKeyOwner
+        + StateInfo
+        + ExecuteClient
+        + TraceClient
+        + EngineInfo,
     M: MinerService,
 {
-    pub fn new(client: &Arc<C>, miner: &Arc<M>) -> Self {
+    pub fn new(client: &Arc<C>, miner: &Arc<M>, chain_stats: &Arc<ChainStatistics>) -> Self {
         ChainClient {
             client: client.clone(),
             miner: miner.clone(),
+            chain_stats: chain_stats.clone(),
+            idempotency_keys: Mutex::new(IdempotencyKeys::new(
+                Duration::from_secs(IDEMPOTENCY_KEY_LIFETIME_SECS),
+                MAX_IDEMPOTENCY_KEYS,
+            )),
         }
     }
+
+    /// Submits the payload once per idempotency key, returning the result of the first successful submission on
+    /// retries. Failed submissions are not remembered so that they can be retried with the same key.
+    fn submit_idempotently<F>(&self, key: Option<String>, payload: &[u8], submit: F) -> Result<H256>
+    where
+        F: FnOnce() -> Result<H256>, {
+        let key = match key {
+            Some(key) => key,
+            None => return submit(),
+        };
+        if key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(errors::invalid_params(format!(
+                "The idempotency key cannot be longer than {} bytes",
+                MAX_IDEMPOTENCY_KEY_LENGTH
+            )))
+        }
+        let payload_hash = blake256(payload);
+        if let Some(result) = self.idempotency_keys.lock().begin(&key, &payload_hash, Instant::now())? {
+            return Ok(result)
+        }
+        let result = submit();
+        self.idempotency_keys.lock().finish(&key, &result);
+        result
+    }
+
+    fn import_signed_parcel(&self, raw: &[u8]) -> Result<H256> {
+        self.decode_signed_parcel(raw)
+            .and_then(|signed| {
+                let hash = signed.hash();
+                self.miner.import_own_parcel(&*self.client, signed).map_err(errors::parcel_core).map(|_| hash)
+            })
+            .map(Into::into)
+    }
+
+    fn decode_signed_parcel(&self, raw: &[u8]) -> Result<SignedParcel> {
+        UntrustedRlp::new_checked(raw)
+            .and_then(|rlp| rlp.as_val())
+            .map_err(errors::rlp)
+            .and_then(|parcel: UnverifiedParcel| {
+                match &parcel.as_unsigned().action {
+                    Action::Custom(bytes) => {
+                        if !self.client.custom_handlers().iter().any(|c| c.is_target(bytes)) {
+                            return Err(errors::rlp(DecoderError::Custom("Invalid custom action!")))
+                        }
+                    }
+                    _ => {}
+                }
+                Ok(parcel)
+            })
+            .and_then(|parcel| SignedParcel::new(parcel).map_err(errors::parcel_core))
+    }
+
+    /// Reads a value of the account with the nodes of the state trie which prove the account.
+    fn read_with_account_proof<T, F>(&self, address: &Address, block_id: BlockId, read: F) -> Option<Proven<T>>
+    where
+        F: FnOnce(&TopStateInfo) -> Option<T>, {
+        // The header is resolved first so that the proof is anchored to the block whose state it read.
+        let header = self.client.block_header(block_id)?;
+        let state = self.client.state_info(BlockId::Hash(header.hash()))?;
+        let value = read(&*state)?;
+        let proof = state.account_proof(address).ok()?;
+        Some(Proven {
+            value,
+            header: header.into_inner().into(),
+            proof: proof.into_iter().map(Bytes::from).collect(),
+        })
+    }
+}
+
+fn try_addresses(addresses: &[PlatformAddress]) -> Result<Vec<Address>> {
+    addresses.iter().map(|address| address.try_address().map(|address| *address).map_err(errors::core)).collect()
 }
 
 impl<C, M> Chain for ChainClient<C, M>
@@ -62,32 +253,46 @@ where
         + Shard
         + RegularKey
         + RegularKeyOwner
+        + StateInfo
         + ExecuteClient
+        + TraceClient
         + EngineInfo
         + 'static,
     M: MinerService + 'static,
 {
-    fn send_signed_parcel(&self, raw: Bytes) -> Result<H256> {
-        UntrustedRlp::new(&raw.into_vec())
-            .as_val()
-            .map_err(errors::rlp)
-            .and_then(|parcel: UnverifiedParcel| {
-                match &parcel.as_unsigned().action {
-                    Action::Custom(bytes) => {
-                        if !self.client.custom_handlers().iter().any(|c| c.is_target(bytes)) {
-                            return Err(errors::rlp(DecoderError::Custom("Invalid custom action!")))
-                        }
-                    }
-                    _ => {}
+    fn send_signed_parcel(&self, raw: Bytes, idempotency_key: Trailing<String>) -> Result<H256> {
+        let raw = raw.into_vec();
+        self.submit_idempotently(idempotency_key.into(), &raw, || self.import_signed_parcel(&raw))
+    }
+
+    fn get_multisig_address(&self, threshold: u8, publics: Vec<Public>) -> Result<PlatformAddress> {
+        const VERSION: u8 = 0;
+        let network_id = self.client.common_params().network_id;
+        let address = public_to_address(&multisig_public(threshold, &publics));
+        Ok(PlatformAddress::create(VERSION, network_id, address))
+    }
+
+    fn combine_multisig_parcels(&self, parcels: Vec<Bytes>) -> Result<Bytes> {
+        let mut parcels = parcels.into_iter().map(|raw| -> Result<UnverifiedParcel> {
+            UntrustedRlp::new(&raw.into_vec()).as_val().map_err(errors::rlp)
+        });
+        let mut combined = parcels.next().unwrap_or_else(|| Err(errors::invalid_params("No parcel is given")))?;
+        for parcel in parcels {
+            let parcel = parcel?;
+            if parcel.as_unsigned() != combined.as_unsigned() {
+                return Err(errors::invalid_params("The parcels are different"))
+            }
+            combined = match parcel.signature() {
+                ParcelSignature::Multi(multisig) => {
+                    combined.update_multisig(|combined| combined.merge(multisig)).map_err(errors::core)?
                 }
-                Ok(parcel)
-            })
-            .and_then(|parcel| SignedParcel::new(parcel).map_err(errors::parcel_core))
-            .and_then(|signed| {
-                let hash = signed.hash();
-                self.miner.import_own_parcel(&*self.client, signed).map_err(errors::parcel_core).map(|_| hash)
-            })
-            .map(Into::into)
+                ParcelSignature::Single(_)
+                | ParcelSignature::Ed25519 {
+                    ..
+                } => return Err(errors::invalid_params("The parcel is not of a multi-signature account")),
+            };
+        }
+        Ok(combined.rlp_bytes().into_vec().into())
     }
 
     fn get_parcel(&self, parcel_hash: H256) -> Result<Option<Parcel>> {
@@ -97,6 +302,39 @@ where
         }
     }
 
+    fn get_parcels_by_address(
+        &self,
+        address: PlatformAddress,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Parcel>> {
+        const MAX_LIMIT: usize = 100;
+        let limit = limit.unwrap_or(MAX_LIMIT);
+        if limit > MAX_LIMIT {
+            return Err(errors::invalid_params(format!("The limit cannot exceed {}", MAX_LIMIT)))
+        }
+        let address = address.try_address().map_err(errors::core)?;
+        Ok(self
+            .client
+            .sender_parcel_addresses(address, offset.unwrap_or(0), limit)
+            .into_iter()
+            .filter_map(|parcel_address| self.client.parcel(parcel_address.into()))
+            .map(Into::into)
+            .collect())
+    }
+
+    fn contains_parcel(&self, parcel_hash: H256) -> Result<Option<ParcelLocation>> {
+        let timestamps = self.miner.parcel_timestamps(&parcel_hash);
+        if let Some(parcel) = self.client.parcel(parcel_hash.into()) {
+            let location = ParcelLocation::mined(parcel.block_number, parcel.block_hash, parcel.parcel_index);
+            return Ok(Some(location.with_timestamps(timestamps)))
+        }
+        Ok(self
+            .miner
+            .parcel_pool_status(&parcel_hash)
+            .map(|status| ParcelLocation::from(status).with_timestamps(timestamps)))
+    }
+
     fn get_parcel_invoice(&self, parcel_hash: H256) -> Result<Option<ParcelInvoice>> {
         Ok(self.client.parcel_invoice(parcel_hash.into()))
     }
@@ -142,16 +380,39 @@ where
         self.client.is_asset_spent(transaction_hash, index, shard_id, block_id).map_err(errors::parcel_state)
     }
 
-    fn get_nonce(&self, address: PlatformAddress, block_number: Option<u64>) -> Result<Option<U256>> {
+    fn get_nonce(
+        &self,
+        address: PlatformAddress,
+        block_number: Option<u64>,
+        with_proof: Trailing<bool>,
+    ) -> Result<Option<MaybeProven<U256>>> {
         let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
         let address = address.try_address().map_err(errors::core)?;
-        Ok(self.client.nonce(address, block_id))
+        if with_proof.unwrap_or_default() {
+            let proven = self.read_with_account_proof(address, block_id, |state| state.nonce(address).ok());
+            return Ok(proven.map(MaybeProven::Proven))
+        }
+        Ok(self.client.nonce(address, block_id).map(MaybeProven::Value))
     }
 
-    fn get_balance(&self, address: PlatformAddress, block_number: Option<u64>) -> Result<Option<U256>> {
+    fn get_recommended_nonce(&self, address: PlatformAddress) -> Result<U256> {
+        let address = address.try_address().map_err(errors::core)?;
+        Ok(self.miner.next_nonce(&*self.client, address))
+    }
+
+    fn get_balance(
+        &self,
+        address: PlatformAddress,
+        block_number: Option<u64>,
+        with_proof: Trailing<bool>,
+    ) -> Result<Option<MaybeProven<U256>>> {
         let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
         let address = address.try_address().map_err(errors::core)?;
-        Ok(self.client.balance(address, block_id.into()))
+        if with_proof.unwrap_or_default() {
+            let proven = self.read_with_account_proof(address, block_id, |state| state.balance(address).ok());
+            return Ok(proven.map(MaybeProven::Proven))
+        }
+        Ok(self.client.balance(address, block_id.into()).map(MaybeProven::Value))
     }
 
     fn get_regular_key(&self, address: PlatformAddress, block_number: Option<u64>) -> Result<Option<Public>> {
@@ -160,6 +421,72 @@ where
         Ok(self.client.regular_key(address, block_id.into()))
     }
 
+    fn get_balances(&self, addresses: Vec<PlatformAddress>, block_number: Option<u64>) -> Result<Option<Vec<U256>>> {
+        let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
+        let addresses = try_addresses(&addresses)?;
+        let state = match self.client.state_info(block_id) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        let balances = addresses
+            .iter()
+            .map(|address| state.balance(address).map_err(errors::parcel_state))
+            .collect::<Result<_>>()?;
+        Ok(Some(balances))
+    }
+
+    fn get_accounts(
+        &self,
+        platform_addresses: Vec<PlatformAddress>,
+        block_number: Option<u64>,
+    ) -> Result<Option<AccountsSnapshot>> {
+        let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
+        let addresses = try_addresses(&platform_addresses)?;
+        // Resolve the block first so that the snapshot reports the block whose state it read.
+        let header = match self.client.block_header(block_id) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let state = match self.client.state_info(BlockId::Hash(header.hash())) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        let accounts = platform_addresses
+            .into_iter()
+            .zip(addresses.iter())
+            .map(|(platform_address, address)| {
+                Ok(Account {
+                    address: platform_address,
+                    balance: state.balance(address).map_err(errors::parcel_state)?,
+                    nonce: state.nonce(address).map_err(errors::parcel_state)?,
+                    regular_key: state.regular_key(address).map_err(errors::parcel_state)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Some(AccountsSnapshot {
+            block_number: header.number(),
+            block_hash: header.hash(),
+            accounts,
+        }))
+    }
+
+    fn get_proof(
+        &self,
+        platform_address: PlatformAddress,
+        block_number: Option<u64>,
+    ) -> Result<Option<Proven<Account>>> {
+        let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
+        let address = *platform_address.try_address().map_err(errors::core)?;
+        Ok(self.read_with_account_proof(&address, block_id, |state| {
+            Some(Account {
+                address: platform_address,
+                balance: state.balance(&address).ok()?,
+                nonce: state.nonce(&address).ok()?,
+                regular_key: state.regular_key(&address).ok()?,
+            })
+        }))
+    }
+
     fn get_regular_key_owner(&self, public: Public, block_number: Option<u64>) -> Result<Option<PlatformAddress>> {
         let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
         const VERSION: u8 = 0;
@@ -170,6 +497,31 @@ where
             .and_then(|address| Some(PlatformAddress::create(VERSION, network_id, address))))
     }
 
+    fn resolve_name(&self, name: String, block_number: Option<u64>) -> Result<Option<NameRecord>> {
+        let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
+        const VERSION: u8 = 0;
+        let network_id = self.client.common_params().network_id;
+        // The names are registered in lowercase.
+        let name = name.to_ascii_lowercase();
+        let (number, state) = match (self.client.block_number(block_id), self.client.state_info(block_id)) {
+            (Some(number), Some(state)) => (number, state),
+            _ => return Ok(None),
+        };
+        let record = match state.name_record(&name).map_err(errors::parcel_state)? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        if record.is_expired(number) {
+            return Ok(None)
+        }
+        Ok(Some(NameRecord {
+            owner: PlatformAddress::create(VERSION, network_id, *record.owner()),
+            value: record.value().clone().into(),
+            expiration: record.expiration(),
+            name,
+        }))
+    }
+
     fn get_number_of_shards(&self, block_number: Option<u64>) -> Result<Option<ShardId>> {
         let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
         Ok(self.client.number_of_shards(block_id.into()))
@@ -181,14 +533,24 @@ where
     }
 
     fn get_best_block_number(&self) -> Result<BlockNumber> {
-        Ok(self.client.chain_info().best_block_number)
+        Ok(self.client.best_block_number_and_hash().0)
     }
 
     fn get_best_block_id(&self) -> Result<BlockNumberAndHash> {
-        let chain_info = self.client.chain_info();
+        let (number, hash) = self.client.best_block_number_and_hash();
         Ok(BlockNumberAndHash {
-            number: chain_info.best_block_number,
-            hash: chain_info.best_block_hash,
+            number,
+            hash,
+        })
+    }
+
+    fn get_finalized_block(&self) -> Result<FinalizedBlock> {
+        let hash = self.client.finalized_block_hash();
+        let number = self.client.block_number(BlockId::Hash(hash)).expect("The finalized block is in the chain");
+        Ok(FinalizedBlock {
+            number,
+            hash,
+            proof: self.client.finality_proof(&hash).map(Bytes::new),
         })
     }
 
@@ -210,6 +572,24 @@ where
             .map(|block| Block::from_core(block.decode(), self.client.common_params().network_id)))
     }
 
+    fn get_pending_block(&self) -> Result<Option<Block>> {
+        Ok(self
+            .miner
+            .pending_block(&*self.client)
+            .map(|block| Block::from_core(block, self.client.common_params().network_id)))
+    }
+
+    fn get_total_score(&self, block_hash: H256) -> Result<Option<U256>> {
+        Ok(self.client.block_total_score(BlockId::Hash(block_hash)))
+    }
+
+    fn get_logs(&self, filter: LogFilter) -> Result<Vec<Log>> {
+        if filter.limit.map_or(false, |limit| limit > MAX_LOGS) {
+            return Err(errors::invalid_params(format!("The limit cannot exceed {}", MAX_LOGS)))
+        }
+        Ok(self.client.logs(filter.into()).into_iter().map(Into::into).collect())
+    }
+
     fn get_pending_parcels(&self) -> Result<Vec<Parcel>> {
         Ok(self.client.ready_parcels().into_iter().map(|signed| signed.into()).collect())
     }
@@ -244,4 +624,115 @@ where
             .map(From::from)
             .collect())
     }
+
+    fn call(&self, raw: Bytes, block_number: Option<u64>) -> Result<Option<ParcelCall>> {
+        let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
+        let parcel = self.decode_signed_parcel(&raw.into_vec())?;
+        let call = self.client.call_parcel(&parcel, block_id).map_err(errors::parcel_core)?;
+        Ok(call.map(|(invoice, traces)| ParcelCall {
+            invoice,
+            traces,
+        }))
+    }
+
+    fn get_validators(&self, block_number: Option<u64>) -> Result<Option<Vec<PlatformAddress>>> {
+        const VERSION: u8 = 0;
+        let block_id = block_number.map(BlockId::Number).unwrap_or(BlockId::Latest);
+        let network_id = self.client.common_params().network_id;
+        Ok(self.client.validators(block_id).map(|validators| {
+            validators.into_iter().map(|address| PlatformAddress::create(VERSION, network_id, address)).collect()
+        }))
+    }
+
+    fn get_cache_stats(&self) -> Result<HashMap<String, CacheStats>> {
+        let stats = self.client.cache_stats();
+        let mut result = HashMap::new();
+        result.insert("headers".to_string(), stats.headers.into());
+        result.insert("bodies".to_string(), stats.bodies.into());
+        result.insert("blockDetails".to_string(), stats.block_details.into());
+        result.insert("transactionAddresses".to_string(), stats.transaction_addresses.into());
+        Ok(result)
+    }
+
+    fn syncing(&self) -> Result<Option<SyncStatus>> {
+        let current_block = self.client.chain_info().best_block_number;
+        Ok(match self.client.sync_progress() {
+            Some(progress) if progress.highest_block > current_block => Some(SyncStatus::new(progress, current_block)),
+            _ => None,
+        })
+    }
+
+    fn get_chain_stats(&self, from: u64, to: u64) -> Result<Option<ChainStats>> {
+        if from > to {
+            return Err(errors::invalid_params("The start of the range is after its end"))
+        }
+        Ok(self.chain_stats.stats(from, to).map(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_return_the_first_result() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 16);
+        let now = Instant::now();
+        let payload_hash = blake256(b"parcel");
+        assert_eq!(None, keys.begin("key", &payload_hash, now).unwrap());
+
+        let result = H256::random();
+        keys.finish("key", &Ok(result));
+        assert_eq!(Some(result), keys.begin("key", &payload_hash, now + Duration::from_secs(59)).unwrap());
+        assert!(keys.begin("key", &blake256(b"another parcel"), now).is_err());
+    }
+
+    #[test]
+    fn concurrent_retry_does_not_submit_again() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 16);
+        let now = Instant::now();
+        let payload_hash = blake256(b"parcel");
+        assert_eq!(None, keys.begin("key", &payload_hash, now).unwrap());
+        assert!(keys.begin("key", &payload_hash, now).is_err());
+    }
+
+    #[test]
+    fn failed_submission_frees_the_key() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 16);
+        let now = Instant::now();
+        let payload_hash = blake256(b"parcel");
+        assert_eq!(None, keys.begin("key", &payload_hash, now).unwrap());
+        keys.finish("key", &Err(errors::parcel_not_found()));
+
+        assert_eq!(None, keys.begin("key", &payload_hash, now + Duration::from_secs(30)).unwrap());
+        keys.finish("key", &Ok(H256::random()));
+        // The key taken first expires, but the one taken again is kept for its own lifetime.
+        assert!(keys.begin("key", &payload_hash, now + Duration::from_secs(60)).unwrap().is_some());
+    }
+
+    #[test]
+    fn keys_expire() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 16);
+        let now = Instant::now();
+        let payload_hash = blake256(b"parcel");
+        keys.begin("key", &payload_hash, now).unwrap();
+        keys.finish("key", &Ok(H256::random()));
+
+        assert_eq!(None, keys.begin("key", &blake256(b"another parcel"), now + Duration::from_secs(60)).unwrap());
+        assert_eq!(1, keys.submissions.len());
+        assert_eq!(1, keys.expirations.len());
+    }
+
+    #[test]
+    fn keys_over_the_capacity_are_refused() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 1);
+        let now = Instant::now();
+        let payload_hash = blake256(b"parcel");
+        keys.begin("first", &payload_hash, now).unwrap();
+        keys.finish("first", &Ok(H256::random()));
+
+        assert!(keys.begin("second", &payload_hash, now).is_err());
+        assert!(keys.begin("first", &payload_hash, now).unwrap().is_some());
+        assert_eq!(None, keys.begin("second", &payload_hash, now + Duration::from_secs(60)).unwrap());
+    }
 }
@@ -0,0 +1,43 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use jsonrpc_core::Result;
+
+use super::super::super::ConfigReload;
+use super::super::errors;
+use super::super::traits::Config;
+use super::super::types::ConfigReloadReport;
+
+pub struct ConfigClient {
+    config_reload: Arc<ConfigReload>,
+}
+
+impl ConfigClient {
+    pub fn new(config_reload: &Arc<ConfigReload>) -> Self {
+        Self {
+            config_reload: Arc::clone(config_reload),
+        }
+    }
+}
+
+impl Config for ConfigClient {
+    fn reload(&self) -> Result<ConfigReloadReport> {
+        let report = self.config_reload.reload().map_err(errors::config_reload)?;
+        Ok(report.into())
+    }
+}
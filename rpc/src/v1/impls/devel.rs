@@ -18,7 +18,11 @@ use std::ops::Deref;
 use std::sync::Arc;
 use std::vec::Vec;
 
-use ccore::{DatabaseClient, MinerService, MiningBlockChainClient, COL_STATE};
+use ccore::{
+    BlockId, ChainInfo, DatabaseClient, MinerService, MiningBlockChainClient, RevertChain, TraceClient, COL_STATE,
+};
+use clogger::{self, LevelFilter};
+use cstate::Trace;
 use jsonrpc_core::Result;
 use kvdb::KeyValueDB;
 use primitives::H256;
@@ -30,7 +34,7 @@ use super::super::types::Bytes;
 
 pub struct DevelClient<C, M>
 where
-    C: DatabaseClient + MiningBlockChainClient,
+    C: DatabaseClient + MiningBlockChainClient + RevertChain + TraceClient,
     M: MinerService, {
     client: Arc<C>,
     db: Arc<KeyValueDB>,
@@ -39,7 +43,7 @@ where
 
 impl<C, M> DevelClient<C, M>
 where
-    C: DatabaseClient + MiningBlockChainClient,
+    C: DatabaseClient + MiningBlockChainClient + RevertChain + TraceClient,
     M: MinerService,
 {
     pub fn new(client: &Arc<C>, miner: &Arc<M>) -> Self {
@@ -53,10 +57,11 @@ where
 
 impl<C, M> Devel for DevelClient<C, M>
 where
-    C: DatabaseClient + MiningBlockChainClient + 'static,
+    C: DatabaseClient + MiningBlockChainClient + RevertChain + TraceClient + 'static,
     M: MinerService + 'static,
 {
     fn get_state_trie_keys(&self, offset: usize, limit: usize) -> Result<Vec<H256>> {
+        self.client.flush_database().map_err(errors::kvdb)?;
         let iter = self.db.iter(COL_STATE);
         Ok(iter.skip(offset).take(limit).map(|val| H256::from(val.0.deref())).collect())
     }
@@ -72,6 +77,10 @@ where
         }
     }
 
+    fn backup_database(&self, path: String) -> Result<()> {
+        self.client.backup_database(&path).map_err(errors::kvdb)
+    }
+
     fn start_sealing(&self) -> Result<()> {
         self.miner.start_sealing(&*self.client);
         Ok(())
@@ -81,4 +90,45 @@ where
         self.miner.stop_sealing();
         Ok(())
     }
+
+    fn mine_block(&self) -> Result<H256> {
+        self.miner.mine_block(&*self.client).ok_or_else(errors::cannot_seal)
+    }
+
+    fn set_time(&self, timestamp: u64) -> Result<()> {
+        self.miner.set_block_time(timestamp);
+        Ok(())
+    }
+
+    fn snapshot_state(&self) -> Result<H256> {
+        Ok(self.client.chain_info().best_block_hash)
+    }
+
+    fn revert_state(&self, snapshot: H256) -> Result<()> {
+        if self.client.revert_to(&snapshot) {
+            Ok(())
+        } else {
+            Err(errors::snapshot_not_found())
+        }
+    }
+
+    fn trace_parcel(&self, hash: H256) -> Result<Option<Vec<Trace>>> {
+        self.client.trace_parcel(hash).map_err(errors::core)
+    }
+
+    fn replay_block(&self, block_number: u64) -> Result<Option<Vec<Vec<Trace>>>> {
+        self.client.replay_block(BlockId::Number(block_number)).map_err(errors::core)
+    }
+
+    fn set_log_level(&self, target: String, level: String) -> Result<()> {
+        let level: LevelFilter =
+            level.parse().map_err(|_| errors::invalid_params(format!("Invalid log level: {}", level)))?;
+        clogger::set_level(&target, level);
+        Ok(())
+    }
+
+    fn reset_log_levels(&self) -> Result<()> {
+        clogger::reset_levels();
+        Ok(())
+    }
 }
@@ -0,0 +1,227 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ccore::{BlockId, MinerService, MiningBlockChainClient};
+use ctypes::BlockNumber;
+use jsonrpc_core::Result;
+use parking_lot::Mutex;
+use primitives::H256;
+
+use super::super::errors;
+use super::super::traits::Filter;
+
+/// Filters unused for this long are removed.
+const FILTER_LIFETIME_SECS: u64 = 5 * 60;
+
+enum PollFilter {
+    Block {
+        last_block_number: BlockNumber,
+    },
+    PendingParcel {
+        known: HashSet<H256>,
+    },
+    DroppedParcel {
+        known: HashSet<H256>,
+    },
+}
+
+struct Poll {
+    filter: PollFilter,
+    last_used: Instant,
+}
+
+/// Filters indexed by their ids, which expire when they are not polled.
+struct PollManager {
+    lifetime: Duration,
+    next_id: usize,
+    polls: HashMap<usize, Poll>,
+}
+
+impl PollManager {
+    fn new(lifetime: Duration) -> Self {
+        Self {
+            lifetime,
+            next_id: 0,
+            polls: HashMap::new(),
+        }
+    }
+
+    fn create(&mut self, filter: PollFilter, now: Instant) -> usize {
+        self.prune(now);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.polls.insert(
+            id,
+            Poll {
+                filter,
+                last_used: now,
+            },
+        );
+        id
+    }
+
+    fn poll_mut(&mut self, id: usize, now: Instant) -> Option<&mut PollFilter> {
+        self.prune(now);
+        self.polls.get_mut(&id).map(|poll| {
+            poll.last_used = now;
+            &mut poll.filter
+        })
+    }
+
+    fn remove(&mut self, id: usize) -> bool {
+        self.polls.remove(&id).is_some()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let lifetime = self.lifetime;
+        self.polls.retain(|_, poll| now.duration_since(poll.last_used) < lifetime);
+    }
+}
+
+pub struct FilterClient<C, M>
+where
+    C: MiningBlockChainClient,
+    M: MinerService, {
+    client: Arc<C>,
+    miner: Arc<M>,
+    polls: Mutex<PollManager>,
+}
+
+impl<C, M> FilterClient<C, M>
+where
+    C: MiningBlockChainClient,
+    M: MinerService,
+{
+    pub fn new(client: &Arc<C>, miner: &Arc<M>) -> Self {
+        Self {
+            client: client.clone(),
+            miner: miner.clone(),
+            polls: Mutex::new(PollManager::new(Duration::from_secs(FILTER_LIFETIME_SECS))),
+        }
+    }
+
+    fn pending_parcel_hashes(&self) -> HashSet<H256> {
+        self.miner.ready_parcels().iter().map(|parcel| parcel.hash()).collect()
+    }
+
+    fn dropped_parcel_hashes(&self) -> HashSet<H256> {
+        self.miner.dropped_parcels().into_iter().collect()
+    }
+}
+
+impl<C, M> Filter for FilterClient<C, M>
+where
+    C: MiningBlockChainClient + 'static,
+    M: MinerService + 'static,
+{
+    fn new_block_filter(&self) -> Result<usize> {
+        let filter = PollFilter::Block {
+            last_block_number: self.client.chain_info().best_block_number,
+        };
+        Ok(self.polls.lock().create(filter, Instant::now()))
+    }
+
+    fn new_pending_parcel_filter(&self) -> Result<usize> {
+        let filter = PollFilter::PendingParcel {
+            known: self.pending_parcel_hashes(),
+        };
+        Ok(self.polls.lock().create(filter, Instant::now()))
+    }
+
+    fn new_dropped_parcel_filter(&self) -> Result<usize> {
+        let filter = PollFilter::DroppedParcel {
+            known: self.dropped_parcel_hashes(),
+        };
+        Ok(self.polls.lock().create(filter, Instant::now()))
+    }
+
+    fn get_filter_changes(&self, id: usize) -> Result<Vec<H256>> {
+        let mut polls = self.polls.lock();
+        match polls.poll_mut(id, Instant::now()) {
+            Some(PollFilter::Block {
+                last_block_number,
+            }) => {
+                let best_block_number = self.client.chain_info().best_block_number;
+                let hashes = (*last_block_number + 1..=best_block_number)
+                    .filter_map(|number| self.client.block_hash(BlockId::Number(number)))
+                    .collect();
+                *last_block_number = best_block_number;
+                Ok(hashes)
+            }
+            Some(PollFilter::PendingParcel {
+                known,
+            }) => {
+                let current = self.pending_parcel_hashes();
+                let hashes = current.difference(known).cloned().collect();
+                *known = current;
+                Ok(hashes)
+            }
+            Some(PollFilter::DroppedParcel {
+                known,
+            }) => {
+                let current = self.miner.dropped_parcels();
+                let hashes = current.iter().filter(|hash| !known.contains(hash)).cloned().collect();
+                *known = current.into_iter().collect();
+                Ok(hashes)
+            }
+            None => Err(errors::filter_not_found()),
+        }
+    }
+
+    fn uninstall_filter(&self, id: usize) -> Result<bool> {
+        Ok(self.polls.lock().remove(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_filter() -> PollFilter {
+        PollFilter::Block {
+            last_block_number: 0,
+        }
+    }
+
+    #[test]
+    fn unused_filters_expire() {
+        let mut polls = PollManager::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let id = polls.create(block_filter(), now);
+
+        assert!(polls.poll_mut(id, now + Duration::from_secs(59)).is_some());
+        assert!(polls.poll_mut(id, now + Duration::from_secs(118)).is_some());
+        assert!(polls.poll_mut(id, now + Duration::from_secs(178)).is_none());
+    }
+
+    #[test]
+    fn removed_filter_is_not_found() {
+        let mut polls = PollManager::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let first = polls.create(block_filter(), now);
+        let second = polls.create(block_filter(), now);
+        assert_ne!(first, second);
+
+        assert!(polls.remove(first));
+        assert!(!polls.remove(first));
+        assert!(polls.poll_mut(first, now).is_none());
+        assert!(polls.poll_mut(second, now).is_some());
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::sync::Arc;
+
+use ccore::{MinerService, MiningBlockChainClient};
+use jsonrpc_core::Result;
+use primitives::H256;
+
+use super::super::errors;
+use super::super::traits::Mempool;
+
+pub struct MempoolClient<C, M>
+where
+    C: MiningBlockChainClient,
+    M: MinerService, {
+    client: Arc<C>,
+    miner: Arc<M>,
+}
+
+impl<C, M> MempoolClient<C, M>
+where
+    C: MiningBlockChainClient,
+    M: MinerService,
+{
+    pub fn new(client: &Arc<C>, miner: &Arc<M>) -> Self {
+        Self {
+            client: client.clone(),
+            miner: miner.clone(),
+        }
+    }
+}
+
+impl<C, M> Mempool for MempoolClient<C, M>
+where
+    C: MiningBlockChainClient + 'static,
+    M: MinerService + 'static,
+{
+    fn cancel_parcel(&self, hash: H256) -> Result<H256> {
+        match self.miner.cancel_parcel(&*self.client, &hash).map_err(errors::parcel_core)? {
+            Some(replacement) => Ok(replacement),
+            None => Err(errors::parcel_not_found()),
+        }
+    }
+}
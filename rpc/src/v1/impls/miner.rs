@@ -15,15 +15,17 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use ccore::block::IsBlock;
 use ccore::{EngineClient, MinerService, MiningBlockChainClient};
+use ckey::{Password, PlatformAddress};
 use jsonrpc_core::Result;
 use primitives::H256;
 
 use super::super::errors;
-use super::super::traits::Miner;
-use super::super::types::{Bytes, Work};
+use super::super::traits::{Miner, MinerAdmin};
+use super::super::types::{Bytes, MinerOptions, ResealStatus, Work};
 
 pub struct MinerClient<C, M>
 where
@@ -81,4 +83,79 @@ where
         let seal = seal.iter().cloned().map(Into::into).collect();
         Ok(self.miner.submit_seal(&*self.client, pow_hash, seal).is_ok())
     }
+
+    fn get_reseal_status(&self) -> Result<ResealStatus> {
+        Ok(self.miner.reseal_status().into())
+    }
+}
+
+pub struct MinerAdminClient<C, M>
+where
+    C: MiningBlockChainClient,
+    M: MinerService, {
+    client: Arc<C>,
+    miner: Arc<M>,
+}
+
+impl<C, M> MinerAdminClient<C, M>
+where
+    C: MiningBlockChainClient,
+    M: MinerService,
+{
+    pub fn new(client: &Arc<C>, miner: &Arc<M>) -> Self {
+        Self {
+            client: client.clone(),
+            miner: miner.clone(),
+        }
+    }
+}
+
+impl<C, M> MinerAdmin for MinerAdminClient<C, M>
+where
+    C: MiningBlockChainClient + 'static,
+    M: MinerService + 'static,
+{
+    fn set_author(&self, author: PlatformAddress, password: Option<Password>) -> Result<()> {
+        let author = author.try_into_address().map_err(errors::core)?;
+        self.miner.set_author(author, password).map_err(errors::account_provider)
+    }
+
+    fn remove_parcel(&self, hash: H256) -> Result<bool> {
+        Ok(self.miner.remove_parcel(&*self.client, &hash))
+    }
+
+    fn set_options(&self, options: MinerOptions) -> Result<()> {
+        if let Some(size) = options.max_body_size {
+            self.miner.set_max_body_size(size);
+        }
+        if let Some(count) = options.max_parcels_per_block {
+            self.miner.set_max_parcels_per_block(count);
+        }
+        if let Some(fee) = options.min_fee {
+            self.miner.set_minimal_fee(fee);
+        }
+        if let Some(period) = options.reseal_min_period {
+            self.miner.set_reseal_min_period(Duration::from_millis(period));
+        }
+        if let Some(period) = options.reseal_max_period {
+            self.miner.set_reseal_max_period(Duration::from_millis(period));
+        }
+        if let Some(fee) = options.urgent_reseal_fee {
+            self.miner.set_urgent_reseal_fee(Some(fee).filter(|fee| !fee.is_zero()));
+        }
+        if let Some(fullness) = options.urgent_reseal_fullness {
+            self.miner.set_urgent_reseal_fullness(Some(fullness).filter(|fullness| *fullness != 0));
+        }
+        if let Some(backoff) = options.reseal_backoff {
+            self.miner.set_reseal_backoff(backoff);
+        }
+        if let Some(fee_bump) = options.replacement_fee_bump {
+            self.miner.set_replacement_fee_bump(fee_bump);
+        }
+        Ok(())
+    }
+
+    fn prioritize_parcel(&self, hash: H256) -> Result<bool> {
+        Ok(self.miner.prioritize_parcel(&hash))
+    }
 }
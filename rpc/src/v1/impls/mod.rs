@@ -15,15 +15,23 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod account;
+mod admin;
 mod chain;
+mod config;
 mod devel;
+mod filter;
+mod mempool;
 mod miner;
 mod net;
 mod shard_validator;
 
 pub use self::account::AccountClient;
+pub use self::admin::AdminClient;
 pub use self::chain::ChainClient;
+pub use self::config::ConfigClient;
 pub use self::devel::DevelClient;
-pub use self::miner::MinerClient;
+pub use self::filter::FilterClient;
+pub use self::mempool::MempoolClient;
+pub use self::miner::{MinerAdminClient, MinerClient};
 pub use self::net::NetClient;
 pub use self::shard_validator::ShardValidatorClient;
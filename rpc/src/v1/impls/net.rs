@@ -14,24 +14,29 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use ckey::Signature;
 use cnetwork::{NetworkControl, SocketAddr};
 use jsonrpc_core::Result;
 use primitives::H256;
 
+use super::super::super::HealthCheck;
 use super::super::errors;
 use super::super::traits::Net;
-use super::super::types::FilterStatus;
+use super::super::types::{ExtensionStats, FilterStatus, LocalInfo, NodeAllowList, NodeHealth};
 
 pub struct NetClient {
     network_control: Arc<NetworkControl>,
+    health: Arc<HealthCheck>,
 }
 
 impl NetClient {
-    pub fn new(network_control: &Arc<NetworkControl>) -> Self {
+    pub fn new(network_control: &Arc<NetworkControl>, health: &Arc<HealthCheck>) -> Self {
         Self {
             network_control: network_control.clone(),
+            health: Arc::clone(health),
         }
     }
 }
@@ -60,6 +65,10 @@ impl Net for NetClient {
         Ok(self.network_control.get_port().map_err(errors::network_control)?)
     }
 
+    fn local_info(&self) -> Result<LocalInfo> {
+        Ok(self.network_control.local_info().map_err(errors::network_control)?.into())
+    }
+
     fn get_peer_count(&self) -> Result<usize> {
         Ok(self.network_control.get_peer_count().map_err(errors::network_control)?)
     }
@@ -69,20 +78,35 @@ impl Net for NetClient {
         Ok(peers.into_iter().map(Into::into).collect())
     }
 
-    fn add_to_whitelist(&self, addr: ::std::net::IpAddr) -> Result<()> {
-        self.network_control.add_to_whitelist(addr).map_err(errors::network_control)
+    fn get_peer_rtts(&self) -> Result<HashMap<String, u64>> {
+        let rtts = self.network_control.peer_rtts().map_err(errors::network_control)?;
+        Ok(rtts
+            .into_iter()
+            .map(|(address, rtt)| {
+                let address: ::std::net::SocketAddr = address.into();
+                (address.to_string(), rtt.as_secs() * 1000 + u64::from(rtt.subsec_millis()))
+            })
+            .collect())
+    }
+
+    fn add_to_whitelist(&self, entry: String) -> Result<()> {
+        let entry = entry.parse().map_err(errors::invalid_params)?;
+        self.network_control.add_to_whitelist(entry).map_err(errors::network_control)
     }
 
-    fn remove_from_whitelist(&self, addr: ::std::net::IpAddr) -> Result<()> {
-        self.network_control.remove_from_whitelist(&addr).map_err(errors::network_control)
+    fn remove_from_whitelist(&self, entry: String) -> Result<()> {
+        let entry = entry.parse().map_err(errors::invalid_params)?;
+        self.network_control.remove_from_whitelist(&entry).map_err(errors::network_control)
     }
 
-    fn add_to_blacklist(&self, addr: ::std::net::IpAddr) -> Result<()> {
-        self.network_control.add_to_blacklist(addr).map_err(errors::network_control)
+    fn add_to_blacklist(&self, entry: String) -> Result<()> {
+        let entry = entry.parse().map_err(errors::invalid_params)?;
+        self.network_control.add_to_blacklist(entry).map_err(errors::network_control)
     }
 
-    fn remove_from_blacklist(&self, addr: ::std::net::IpAddr) -> Result<()> {
-        self.network_control.remove_from_blacklist(&addr).map_err(errors::network_control)
+    fn remove_from_blacklist(&self, entry: String) -> Result<()> {
+        let entry = entry.parse().map_err(errors::invalid_params)?;
+        self.network_control.remove_from_blacklist(&entry).map_err(errors::network_control)
     }
 
     fn enable_whitelist(&self) -> Result<()> {
@@ -104,7 +128,7 @@ impl Net for NetClient {
     fn get_whitelist(&self) -> Result<FilterStatus> {
         let (list, enabled) = self.network_control.get_whitelist().map_err(errors::network_control)?;
         Ok(FilterStatus {
-            list: list.into_iter().map(Into::into).collect(),
+            list: list.iter().map(ToString::to_string).collect(),
             enabled,
         })
     }
@@ -112,8 +136,35 @@ impl Net for NetClient {
     fn get_blacklist(&self) -> Result<FilterStatus> {
         let (list, enabled) = self.network_control.get_blacklist().map_err(errors::network_control)?;
         Ok(FilterStatus {
-            list: list.into_iter().map(Into::into).collect(),
+            list: list.iter().map(ToString::to_string).collect(),
             enabled,
         })
     }
+
+    fn set_node_allow_list(
+        &self,
+        version: u64,
+        nodes: Vec<::std::net::SocketAddr>,
+        signature: Signature,
+    ) -> Result<()> {
+        let nodes = nodes.into_iter().map(Into::into).collect();
+        self.network_control.set_node_allow_list(version, nodes, signature).map_err(errors::network_control)
+    }
+
+    fn get_node_allow_list(&self) -> Result<NodeAllowList> {
+        let (version, nodes) = self.network_control.get_node_allow_list().map_err(errors::network_control)?;
+        Ok(NodeAllowList {
+            version,
+            nodes: nodes.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    fn extension_stats(&self) -> Result<HashMap<String, ExtensionStats>> {
+        let stats = self.network_control.extension_stats().map_err(errors::network_control)?;
+        Ok(stats.into_iter().map(|(name, stats)| (name, stats.into())).collect())
+    }
+
+    fn health(&self) -> Result<NodeHealth> {
+        Ok(self.health.check())
+    }
 }
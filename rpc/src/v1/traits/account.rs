@@ -18,6 +18,8 @@ use ckey::{Password, PlatformAddress, Signature};
 use jsonrpc_core::Result;
 use primitives::H256;
 
+use super::super::types::Bytes;
+
 build_rpc_trait! {
     pub trait Account {
         /// Gets a list of accounts
@@ -43,5 +45,17 @@ build_rpc_trait! {
         /// Changes the account's password
         # [rpc(name = "account_changePassword")]
         fn change_password(&self, PlatformAddress, Password, Password) -> Result<()>;
+
+        /// Signs the custom action which rotates the sealing key of a validator
+        # [rpc(name = "account_signKeyRotation")]
+        fn sign_key_rotation(&self, PlatformAddress, PlatformAddress, u64, u64, Option<PlatformAddress>, Option<Password>) -> Result<Bytes>;
+
+        /// Signs the list of the nodes allowed to connect in a permissioned network
+        # [rpc(name = "account_signNodeAllowList")]
+        fn sign_node_allow_list(&self, u64, Vec<::std::net::SocketAddr>, PlatformAddress, Option<Password>) -> Result<Signature>;
+
+        /// Adds the signature of the account to the parcel of a multi-signature account
+        # [rpc(name = "account_signMultisigParcel")]
+        fn sign_multisig_parcel(&self, Bytes, PlatformAddress, Option<Password>) -> Result<Bytes>;
     }
 }
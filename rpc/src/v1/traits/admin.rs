@@ -0,0 +1,29 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use jsonrpc_core::Result;
+
+build_rpc_trait! {
+    pub trait Admin {
+        /// Rejects the new non-admin requests until the maintenance mode is turned off
+        # [rpc(name = "admin_setMaintenanceMode")]
+        fn set_maintenance_mode(&self, bool) -> Result<()>;
+
+        /// Returns true if the node is in the maintenance mode
+        # [rpc(name = "admin_getMaintenanceMode")]
+        fn get_maintenance_mode(&self) -> Result<bool>;
+    }
+}
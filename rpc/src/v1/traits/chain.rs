@@ -14,6 +14,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
+
 use ckey::{NetworkId, PlatformAddress, Public};
 use cstate::{AssetScheme, OwnedAsset};
 use ctypes::invoice::{ParcelInvoice, TransactionInvoice};
@@ -21,19 +23,41 @@ use ctypes::{BlockNumber, ShardId, WorldId};
 use primitives::{H256, U256};
 
 use jsonrpc_core::Result;
+use jsonrpc_macros::Trailing;
 
-use super::super::types::{Block, BlockNumberAndHash, Bytes, Parcel, ShardChange, Transaction};
+use super::super::types::{
+    Account, AccountsSnapshot, Block, BlockNumberAndHash, Bytes, CacheStats, ChainStats, FinalizedBlock, Log,
+    LogFilter, MaybeProven, NameRecord, Parcel, ParcelCall, ParcelLocation, Proven, ShardChange, SyncStatus,
+    Transaction,
+};
 
 build_rpc_trait! {
     pub trait Chain {
         /// Sends signed parcel, returning its hash.
+        /// Retries with the same idempotency key return the hash of the first successful submission.
         # [rpc(name = "chain_sendSignedParcel")]
-        fn send_signed_parcel(&self, Bytes) -> Result<H256>;
+        fn send_signed_parcel(&self, Bytes, Trailing<String>) -> Result<H256>;
+
+        /// Gets the address of the multi-signature account of the threshold and the keys.
+        # [rpc(name = "chain_getMultisigAddress")]
+        fn get_multisig_address(&self, u8, Vec<Public>) -> Result<PlatformAddress>;
+
+        /// Combines the signatures of the copies of a multi-signature parcel, which are signed by different co-signers.
+        # [rpc(name = "chain_combineMultisigParcels")]
+        fn combine_multisig_parcels(&self, Vec<Bytes>) -> Result<Bytes>;
 
         /// Gets parcel with given hash.
         # [rpc(name = "chain_getParcel")]
         fn get_parcel(&self, H256) -> Result<Option<Parcel>>;
 
+        /// Gets the parcels sent by the account, in the order they appear in the canon chain.
+        # [rpc(name = "chain_getParcelsByAddress")]
+        fn get_parcels_by_address(&self, PlatformAddress, Option<usize>, Option<usize>) -> Result<Vec<Parcel>>;
+
+        /// Gets where the parcel with given hash is known: the chain, the mem pool, or the recently dropped parcels.
+        # [rpc(name = "chain_containsParcel")]
+        fn contains_parcel(&self, H256) -> Result<Option<ParcelLocation>>;
+
         /// Gets parcel invoices with given hash.
         # [rpc(name = "chain_getParcelInvoice")]
         fn get_parcel_invoice(&self, H256) -> Result<Option<ParcelInvoice>>;
@@ -62,22 +86,42 @@ build_rpc_trait! {
         # [rpc(name = "chain_isAssetSpent")]
         fn is_asset_spent(&self, H256, usize, ShardId, Option<u64>) -> Result<Option<bool>>;
 
-        /// Gets nonce with given account.
+        /// Gets nonce with given account, optionally with the proof of it.
         # [rpc(name = "chain_getNonce")]
-        fn get_nonce(&self, PlatformAddress, Option<u64>) -> Result<Option<U256>>;
+        fn get_nonce(&self, PlatformAddress, Option<u64>, Trailing<bool>) -> Result<Option<MaybeProven<U256>>>;
 
-        /// Gets balance with given account.
+        /// Gets the nonce which the next parcel of given account should use, counting its pending parcels.
+        # [rpc(name = "chain_getRecommendedNonce")]
+        fn get_recommended_nonce(&self, PlatformAddress) -> Result<U256>;
+
+        /// Gets balance with given account, optionally with the proof of it.
         # [rpc(name = "chain_getBalance")]
-        fn get_balance(&self, PlatformAddress, Option<u64>) -> Result<Option<U256>>;
+        fn get_balance(&self, PlatformAddress, Option<u64>, Trailing<bool>) -> Result<Option<MaybeProven<U256>>>;
 
         /// Gets regular key with given account
         # [rpc(name = "chain_getRegularKey")]
         fn get_regular_key(&self, PlatformAddress, Option<u64>) -> Result<Option<Public>>;
 
+        /// Gets balances of given accounts at the same block.
+        # [rpc(name = "chain_getBalances")]
+        fn get_balances(&self, Vec<PlatformAddress>, Option<u64>) -> Result<Option<Vec<U256>>>;
+
+        /// Gets balances, nonces and regular keys of given accounts at the same block.
+        # [rpc(name = "chain_getAccounts")]
+        fn get_accounts(&self, Vec<PlatformAddress>, Option<u64>) -> Result<Option<AccountsSnapshot>>;
+
+        /// Gets the account with the nodes of the state trie which prove it.
+        # [rpc(name = "chain_getProof")]
+        fn get_proof(&self, PlatformAddress, Option<u64>) -> Result<Option<Proven<Account>>>;
+
         /// Gets the owner of given regular key.
         # [rpc(name = "chain_getRegularKeyOwner")]
         fn get_regular_key_owner(&self, Public, Option<u64>) -> Result<Option<PlatformAddress>>;
 
+        /// Gets the record of the name if it is registered and not expired.
+        # [rpc(name = "chain_resolveName")]
+        fn resolve_name(&self, String, Option<u64>) -> Result<Option<NameRecord>>;
+
         /// Gets the number of shards
         # [rpc(name = "chain_getNumberOfShards")]
         fn get_number_of_shards(&self, Option<u64>) -> Result<Option<ShardId>>;
@@ -94,6 +138,10 @@ build_rpc_trait! {
         # [rpc(name = "chain_getBestBlockId")]
         fn get_best_block_id(&self) -> Result<BlockNumberAndHash>;
 
+        /// Gets the number and the hash of the latest finalized block, with its finality proof.
+        # [rpc(name = "chain_getFinalizedBlock")]
+        fn get_finalized_block(&self) -> Result<FinalizedBlock>;
+
         /// Gets the hash of the block with given number.
         # [rpc(name = "chain_getBlockHash")]
         fn get_block_hash(&self, u64) -> Result<Option<H256>>;
@@ -106,6 +154,18 @@ build_rpc_trait! {
         # [rpc(name = "chain_getBlockByHash")]
         fn get_block_by_hash(&self, H256) -> Result<Option<Block>>;
 
+        /// Gets the block which would be sealed next from the pending parcels.
+        # [rpc(name = "chain_getPendingBlock")]
+        fn get_pending_block(&self) -> Result<Option<Block>>;
+
+        /// Gets the total score of the chain ending at the block with given hash.
+        # [rpc(name = "chain_getTotalScore")]
+        fn get_total_score(&self, H256) -> Result<Option<U256>>;
+
+        /// Gets parcels matching given filter.
+        # [rpc(name = "chain_getLogs")]
+        fn get_logs(&self, LogFilter) -> Result<Vec<Log>>;
+
         /// Gets parcels in the current mem pool.
         # [rpc(name = "chain_getPendingParcels")]
         fn get_pending_parcels(&self) -> Result<Vec<Parcel>>;
@@ -121,5 +181,27 @@ build_rpc_trait! {
         /// Execute Transactions
         # [rpc(name = "chain_executeTransactions")]
         fn execute_change_shard_state(&self, Vec<Transaction>, PlatformAddress) -> Result<Vec<ShardChange>>;
+
+        /// Executes the signed parcel on the state of the block without importing it, returning the invoice and the
+        /// effects.
+        # [rpc(name = "chain_call")]
+        fn call(&self, Bytes, Option<u64>) -> Result<Option<ParcelCall>>;
+
+        /// Gets the validators which seal the block after the given block.
+        # [rpc(name = "chain_getValidators")]
+        fn get_validators(&self, Option<u64>) -> Result<Option<Vec<PlatformAddress>>>;
+
+        /// Gets the hit/miss counters of the blockchain caches.
+        # [rpc(name = "chain_getCacheStats")]
+        fn get_cache_stats(&self) -> Result<HashMap<String, CacheStats>>;
+
+        /// Gets the progress of the block sync. None if the node is not syncing.
+        # [rpc(name = "chain_syncing")]
+        fn syncing(&self) -> Result<Option<SyncStatus>>;
+
+        /// Gets the block interval, parcel and fee statistics of the blocks from `from` to `to`, inclusive.
+        /// None if the range is older than the statistics kept by the node.
+        # [rpc(name = "chain_getChainStats")]
+        fn get_chain_stats(&self, u64, u64) -> Result<Option<ChainStats>>;
     }
 }
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use cstate::Trace;
 use primitives::H256;
 
 use jsonrpc_core::Result;
@@ -28,10 +29,37 @@ build_rpc_trait! {
         # [rpc(name = "devel_getStateTrieValue")]
         fn get_state_trie_value(&self, H256) -> Result<Vec<Bytes>>;
 
+        # [rpc(name = "devel_backupDatabase")]
+        fn backup_database(&self, String) -> Result<()>;
+
         # [rpc(name = "devel_startSealing")]
         fn start_sealing(&self) -> Result<()>;
 
         # [rpc(name = "devel_stopSealing")]
         fn stop_sealing(&self) -> Result<()>;
+
+        # [rpc(name = "devel_mineBlock")]
+        fn mine_block(&self) -> Result<H256>;
+
+        # [rpc(name = "devel_setTime")]
+        fn set_time(&self, u64) -> Result<()>;
+
+        # [rpc(name = "devel_snapshotState")]
+        fn snapshot_state(&self) -> Result<H256>;
+
+        # [rpc(name = "devel_revertState")]
+        fn revert_state(&self, H256) -> Result<()>;
+
+        # [rpc(name = "devel_traceParcel")]
+        fn trace_parcel(&self, H256) -> Result<Option<Vec<Trace>>>;
+
+        # [rpc(name = "devel_replayBlock")]
+        fn replay_block(&self, u64) -> Result<Option<Vec<Vec<Trace>>>>;
+
+        # [rpc(name = "devel_setLogLevel")]
+        fn set_log_level(&self, String, String) -> Result<()>;
+
+        # [rpc(name = "devel_resetLogLevels")]
+        fn reset_log_levels(&self) -> Result<()>;
     }
 }
@@ -0,0 +1,43 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use primitives::H256;
+
+use jsonrpc_core::Result;
+
+build_rpc_trait! {
+    pub trait Filter {
+        /// Creates a filter of new blocks, returning its id.
+        # [rpc(name = "chain_newBlockFilter")]
+        fn new_block_filter(&self) -> Result<usize>;
+
+        /// Creates a filter of new pending parcels, returning its id.
+        # [rpc(name = "chain_newPendingParcelFilter")]
+        fn new_pending_parcel_filter(&self) -> Result<usize>;
+
+        /// Creates a filter of the parcels dropped from the mem pool, returning its id.
+        # [rpc(name = "chain_newDroppedParcelFilter")]
+        fn new_dropped_parcel_filter(&self) -> Result<usize>;
+
+        /// Gets the hashes added since the last poll of the filter.
+        # [rpc(name = "chain_getFilterChanges")]
+        fn get_filter_changes(&self, usize) -> Result<Vec<H256>>;
+
+        /// Removes the filter.
+        # [rpc(name = "chain_uninstallFilter")]
+        fn uninstall_filter(&self, usize) -> Result<bool>;
+    }
+}
@@ -14,10 +14,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use ckey::{Password, PlatformAddress};
 use jsonrpc_core::Result;
 use primitives::H256;
 
-use super::super::types::{Bytes, Work};
+use super::super::types::{Bytes, MinerOptions, ResealStatus, Work};
 
 build_rpc_trait! {
     pub trait Miner {
@@ -26,5 +27,31 @@ build_rpc_trait! {
 
         # [rpc(name = "miner_submitWork")]
         fn submit_work(&self, H256, Vec<Bytes>) -> Result<bool>;
+
+        /// Returns how the miner currently reseals blocks.
+        # [rpc(name = "miner_getResealStatus")]
+        fn get_reseal_status(&self) -> Result<ResealStatus>;
+    }
+}
+
+build_rpc_trait! {
+    pub trait MinerAdmin {
+        /// Changes the address which the block rewards are paid to. It's only served by the admin API.
+        # [rpc(name = "miner_setAuthor")]
+        fn set_author(&self, PlatformAddress, Option<Password>) -> Result<()>;
+
+        /// Removes the parcel from the pool. It's only served by the admin API.
+        # [rpc(name = "miner_removeParcel")]
+        fn remove_parcel(&self, H256) -> Result<bool>;
+
+        /// Changes the options for assembling blocks. The options which are not given are kept.
+        /// It's only served by the admin API.
+        # [rpc(name = "miner_setOptions")]
+        fn set_options(&self, MinerOptions) -> Result<()>;
+
+        /// Pins the parcel in the pool to the top of the next blocks regardless of its fee.
+        /// It's only served by the admin API.
+        # [rpc(name = "miner_prioritizeParcel")]
+        fn prioritize_parcel(&self, H256) -> Result<bool>;
     }
 }
@@ -15,15 +15,23 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod account;
+mod admin;
 mod chain;
+mod config;
 mod devel;
+mod filter;
+mod mempool;
 mod miner;
 mod net;
 mod shard_validator;
 
 pub use self::account::Account;
+pub use self::admin::Admin;
 pub use self::chain::Chain;
+pub use self::config::Config;
 pub use self::devel::Devel;
-pub use self::miner::Miner;
+pub use self::filter::Filter;
+pub use self::mempool::Mempool;
+pub use self::miner::{Miner, MinerAdmin};
 pub use self::net::Net;
 pub use self::shard_validator::ShardValidator;
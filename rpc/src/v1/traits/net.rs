@@ -14,10 +14,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
+
+use ckey::Signature;
 use jsonrpc_core::Result;
 use primitives::H256;
 
-use super::super::types::FilterStatus;
+use super::super::types::{ExtensionStats, FilterStatus, LocalInfo, NodeAllowList, NodeHealth};
 
 build_rpc_trait! {
     pub trait Net {
@@ -36,23 +39,32 @@ build_rpc_trait! {
         # [rpc(name = "net_getPort")]
         fn get_port(&self) -> Result<u16>;
 
+        #[rpc(name = "net_localInfo")]
+        fn local_info(&self) -> Result<LocalInfo>;
+
         # [rpc(name = "net_getPeerCount")]
         fn get_peer_count(&self) -> Result<usize>;
 
         # [rpc(name = "net_getEstablishedPeers")]
         fn get_established_peers(&self) -> Result<Vec<::std::net::SocketAddr>>;
 
+        /// The round-trip times of the established peers in milliseconds.
+        #[rpc(name = "net_getPeerRtts")]
+        fn get_peer_rtts(&self) -> Result<HashMap<String, u64>>;
+
+        /// Takes an IP address, a CIDR block or the IP:PORT of a node.
         #[rpc(name = "net_addToWhitelist")]
-        fn add_to_whitelist(&self, ::std::net::IpAddr) -> Result<()>;
+        fn add_to_whitelist(&self, String) -> Result<()>;
 
         #[rpc(name = "net_removeFromWhitelist")]
-        fn remove_from_whitelist(&self, ::std::net::IpAddr) -> Result<()>;
+        fn remove_from_whitelist(&self, String) -> Result<()>;
 
+        /// Takes an IP address, a CIDR block or the IP:PORT of a node.
         #[rpc(name = "net_addToBlacklist")]
-        fn add_to_blacklist(&self, ::std::net::IpAddr) -> Result<()>;
+        fn add_to_blacklist(&self, String) -> Result<()>;
 
         #[rpc(name = "net_removeFromBlacklist")]
-        fn remove_from_blacklist(&self, ::std::net::IpAddr) -> Result<()>;
+        fn remove_from_blacklist(&self, String) -> Result<()>;
 
         #[rpc(name = "net_enableWhitelist")]
         fn enable_whitelist(&self) -> Result<()>;
@@ -71,5 +83,17 @@ build_rpc_trait! {
 
         #[rpc(name = "net_getBlacklist")]
         fn get_blacklist(&self) -> Result<FilterStatus>;
+
+        #[rpc(name = "net_setNodeAllowList")]
+        fn set_node_allow_list(&self, u64, Vec<::std::net::SocketAddr>, Signature) -> Result<()>;
+
+        #[rpc(name = "net_getNodeAllowList")]
+        fn get_node_allow_list(&self) -> Result<NodeAllowList>;
+
+        #[rpc(name = "net_extensionStats")]
+        fn extension_stats(&self) -> Result<HashMap<String, ExtensionStats>>;
+
+        #[rpc(name = "net_health")]
+        fn health(&self) -> Result<NodeHealth>;
     }
 }
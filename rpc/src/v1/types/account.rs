@@ -0,0 +1,37 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ckey::{PlatformAddress, Public};
+use ctypes::BlockNumber;
+use primitives::{H256, U256};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub address: PlatformAddress,
+    pub balance: U256,
+    pub nonce: U256,
+    pub regular_key: Option<Public>,
+}
+
+/// Accounts read from the state of one block.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountsSnapshot {
+    pub block_number: BlockNumber,
+    pub block_hash: H256,
+    pub accounts: Vec<Account>,
+}
@@ -16,7 +16,7 @@
 
 use ckey::{Error as KeyError, NetworkId, PlatformAddress, Public, Signature};
 use ctypes::parcel::{Action as ActionType, ShardChange as ShardChangeType};
-use ctypes::ShardId;
+use ctypes::{BlockNumber, ShardId};
 use primitives::{Bytes, H256, U256};
 
 use super::Transaction;
@@ -55,6 +55,19 @@ pub enum Action {
         shard_id: ShardId,
         users: Vec<PlatformAddress>,
     },
+    RegisterName {
+        name: String,
+        value: Bytes,
+        duration: BlockNumber,
+    },
+    UpdateName {
+        name: String,
+        value: Bytes,
+    },
+    TransferName {
+        name: String,
+        receiver: PlatformAddress,
+    },
     Custom(Bytes),
 }
 
@@ -108,6 +121,29 @@ impl Action {
                 shard_id,
                 users: users.into_iter().map(|user| PlatformAddress::create(VERSION, network_id, user)).collect(),
             },
+            ActionType::RegisterName {
+                name,
+                value,
+                duration,
+            } => Action::RegisterName {
+                name,
+                value,
+                duration,
+            },
+            ActionType::UpdateName {
+                name,
+                value,
+            } => Action::UpdateName {
+                name,
+                value,
+            },
+            ActionType::TransferName {
+                name,
+                receiver,
+            } => Action::TransferName {
+                name,
+                receiver: PlatformAddress::create(VERSION, network_id, receiver),
+            },
             ActionType::Custom(bytes) => Action::Custom(bytes),
         }
     }
@@ -172,6 +208,29 @@ impl From<Action> for Result<ActionType, KeyError> {
                     users: users?,
                 }
             }
+            Action::RegisterName {
+                name,
+                value,
+                duration,
+            } => ActionType::RegisterName {
+                name,
+                value,
+                duration,
+            },
+            Action::UpdateName {
+                name,
+                value,
+            } => ActionType::UpdateName {
+                name,
+                value,
+            },
+            Action::TransferName {
+                name,
+                receiver,
+            } => ActionType::TransferName {
+                name,
+                receiver: receiver.try_into_address()?,
+            },
             Action::Custom(bytes) => ActionType::Custom(bytes),
         })
     }
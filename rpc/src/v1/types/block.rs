@@ -19,7 +19,8 @@ use ckey::{NetworkId, PlatformAddress};
 use ctypes::BlockNumber;
 use primitives::{H256, U256};
 
-use super::{Action, Parcel};
+use super::parcel::signature_fields;
+use super::{Action, Bytes, Parcel};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -68,7 +69,7 @@ impl Block {
                 .into_iter()
                 .enumerate()
                 .map(|(i, unverified)| {
-                    let sig = unverified.signature();
+                    let (sig, multisig, ed25519) = signature_fields(unverified.signature());
                     let network_id = unverified.as_unsigned().network_id;
                     Parcel {
                         block_number: Some(block_number),
@@ -79,7 +80,9 @@ impl Block {
                         network_id,
                         action: Action::from_core(unverified.as_unsigned().action.clone(), network_id),
                         hash: unverified.hash(),
-                        sig: sig.into(),
+                        sig,
+                        multisig,
+                        ed25519,
                     }
                 })
                 .collect(),
@@ -93,3 +96,12 @@ pub struct BlockNumberAndHash {
     pub number: BlockNumber,
     pub hash: H256,
 }
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizedBlock {
+    pub number: BlockNumber,
+    pub hash: H256,
+    /// The proof given by the engine. None if the block is final by its depth.
+    pub proof: Option<Bytes>,
+}
@@ -0,0 +1,38 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use ccore::CacheStats as CoreCacheStats;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub size: usize,
+    pub capacity: usize,
+}
+
+impl From<CoreCacheStats> for CacheStats {
+    fn from(stats: CoreCacheStats) -> Self {
+        Self {
+            hits: stats.hits,
+            misses: stats.misses,
+            size: stats.len,
+            capacity: stats.capacity,
+        }
+    }
+}
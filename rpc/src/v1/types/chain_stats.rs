@@ -0,0 +1,43 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ccore::ChainStats as CoreChainStats;
+use ctypes::BlockNumber;
+use primitives::U256;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainStats {
+    pub from: BlockNumber,
+    pub to: BlockNumber,
+    pub average_block_interval: Option<f64>,
+    pub parcels_per_block: f64,
+    pub transactions_per_block: f64,
+    pub total_fees: U256,
+}
+
+impl From<CoreChainStats> for ChainStats {
+    fn from(stats: CoreChainStats) -> Self {
+        Self {
+            from: stats.from,
+            to: stats.to,
+            average_block_interval: stats.average_block_interval,
+            parcels_per_block: stats.parcels_per_block,
+            transactions_per_block: stats.transactions_per_block,
+            total_fees: stats.total_fees,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use cnetwork::ExtensionStats as NetworkExtensionStats;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionStats {
+    pub received_messages: u64,
+    pub received_bytes: u64,
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+    pub timer_fires: u64,
+    pub errors: u64,
+}
+
+impl From<NetworkExtensionStats> for ExtensionStats {
+    fn from(stats: NetworkExtensionStats) -> Self {
+        Self {
+            received_messages: stats.received_messages,
+            received_bytes: stats.received_bytes,
+            sent_messages: stats.sent_messages,
+            sent_bytes: stats.sent_bytes,
+            timer_fires: stats.timer_fires,
+            errors: stats.errors,
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::net::SocketAddr;
+
+use cnetwork::LocalInfo as NetworkLocalInfo;
+use primitives::H512;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalInfo {
+    /// The address on which the node listens for peers.
+    pub address: SocketAddr,
+    /// The public key of the node key, which identifies the node.
+    pub node_id: H512,
+}
+
+impl From<NetworkLocalInfo> for LocalInfo {
+    fn from(info: NetworkLocalInfo) -> Self {
+        Self {
+            address: info.address.into(),
+            node_id: info.node_key,
+        }
+    }
+}
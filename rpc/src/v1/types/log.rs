@@ -0,0 +1,67 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ccore::{BlockId, Filter, LocalizedLog};
+use ctypes::BlockNumber;
+use primitives::H256;
+
+#[derive(Debug, Deserialize)]
+pub struct LogFilter {
+    /// Defaults to the genesis block.
+    pub from: Option<BlockNumber>,
+    /// Defaults to the best block.
+    pub to: Option<BlockNumber>,
+    #[serde(default)]
+    pub topics: Vec<H256>,
+    /// Defaults to `MAX_LOGS`.
+    pub limit: Option<usize>,
+}
+
+/// The number of the logs returned at most by a request.
+pub const MAX_LOGS: usize = 1000;
+
+impl From<LogFilter> for Filter {
+    fn from(filter: LogFilter) -> Self {
+        Filter {
+            from_block: filter.from.map(BlockId::Number).unwrap_or(BlockId::Earliest),
+            to_block: filter.to.map(BlockId::Number).unwrap_or(BlockId::Latest),
+            topics: filter.topics,
+            limit: filter.limit.unwrap_or(MAX_LOGS),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+    pub block_number: BlockNumber,
+    pub block_hash: H256,
+    pub parcel_hash: H256,
+    pub parcel_index: usize,
+    pub topics: Vec<H256>,
+}
+
+impl From<LocalizedLog> for Log {
+    fn from(log: LocalizedLog) -> Self {
+        Log {
+            block_number: log.block_number,
+            block_hash: log.block_hash,
+            parcel_hash: log.parcel_hash,
+            parcel_index: log.parcel_index,
+            topics: log.topics,
+        }
+    }
+}
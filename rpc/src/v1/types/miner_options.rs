@@ -0,0 +1,37 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use primitives::U256;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinerOptions {
+    pub max_body_size: Option<usize>,
+    pub max_parcels_per_block: Option<usize>,
+    pub min_fee: Option<U256>,
+    /// In milliseconds.
+    pub reseal_min_period: Option<u64>,
+    /// In milliseconds.
+    pub reseal_max_period: Option<u64>,
+    /// Zero disables the urgent reseals by the fee.
+    pub urgent_reseal_fee: Option<U256>,
+    /// In percent. Zero disables the urgent reseals by the fullness.
+    pub urgent_reseal_fullness: Option<u8>,
+    pub reseal_backoff: Option<bool>,
+    /// In percent.
+    pub replacement_fee_bump: Option<u8>,
+}
@@ -14,23 +14,56 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod account;
 mod action;
 mod block;
 mod bytes;
+mod cache_stats;
+mod chain_stats;
+mod config_reload;
+mod extension_stats;
+mod health;
+mod local_info;
+mod log;
+mod miner_options;
+mod name_record;
 mod parcel;
+mod proof;
+mod reseal_status;
+mod sync_status;
 mod transaction;
 mod work;
 
+pub use self::account::{Account, AccountsSnapshot};
 pub use self::action::{Action, ShardChange};
 pub use self::block::Block;
-pub use self::block::BlockNumberAndHash;
+pub use self::block::{BlockNumberAndHash, FinalizedBlock};
 pub use self::bytes::Bytes;
-pub use self::parcel::Parcel;
+pub use self::cache_stats::CacheStats;
+pub use self::chain_stats::ChainStats;
+pub use self::config_reload::ConfigReloadReport;
+pub use self::extension_stats::ExtensionStats;
+pub use self::health::NodeHealth;
+pub use self::local_info::LocalInfo;
+pub use self::log::{Log, LogFilter, MAX_LOGS};
+pub use self::miner_options::MinerOptions;
+pub use self::name_record::NameRecord;
+pub use self::parcel::{Parcel, ParcelCall, ParcelLocation};
+pub use self::proof::{MaybeProven, Proven};
+pub use self::reseal_status::ResealStatus;
+pub use self::sync_status::SyncStatus;
 pub use self::transaction::Transaction;
 pub use self::work::Work;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FilterStatus {
-    pub list: Vec<::std::net::IpAddr>,
+    pub list: Vec<String>,
     pub enabled: bool,
 }
+
+#[derive(Debug, Serialize)]
+pub struct NodeAllowList {
+    /// None until the first signed list is given.
+    pub version: Option<u64>,
+    pub nodes: Vec<::std::net::SocketAddr>,
+}
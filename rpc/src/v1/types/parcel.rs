@@ -14,8 +14,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use ccore::{LocalizedParcel, SignedParcel};
-use ckey::{NetworkId, Signature};
+use ccore::{
+    DropReason, LocalizedParcel, MultiSig as CoreMultiSig, ParcelPoolStatus, ParcelSignature, ParcelTimestamps,
+    SignedParcel,
+};
+use ckey::{Ed25519Public, Ed25519Signature, NetworkId, Public, Signature};
+use cstate::Trace;
+use ctypes::invoice::ParcelInvoice;
 use primitives::{H256, U256};
 
 use super::Action;
@@ -31,12 +36,74 @@ pub struct Parcel {
     pub network_id: NetworkId,
     pub action: Action,
     pub hash: H256,
+    /// The ECDSA signature of a single key. It is null if the parcel is signed by another scheme.
+    pub sig: Option<Signature>,
+    pub multisig: Option<MultiSig>,
+    pub ed25519: Option<Ed25519Sig>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ed25519Sig {
+    pub public: Ed25519Public,
+    pub sig: Ed25519Signature,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiSig {
+    pub threshold: u8,
+    pub publics: Vec<Public>,
+    pub signatures: Vec<MultiSigSignature>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiSigSignature {
+    /// Index of the key in `publics`.
+    pub index: u8,
     pub sig: Signature,
 }
 
+impl<'a> From<&'a CoreMultiSig> for MultiSig {
+    fn from(multisig: &'a CoreMultiSig) -> Self {
+        Self {
+            threshold: multisig.threshold(),
+            publics: multisig.publics().to_vec(),
+            signatures: multisig
+                .signatures()
+                .iter()
+                .map(|(index, sig)| MultiSigSignature {
+                    index: *index,
+                    sig: *sig,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Splits the signature of the parcel into the fields of `Parcel`.
+pub fn signature_fields(signature: &ParcelSignature) -> (Option<Signature>, Option<MultiSig>, Option<Ed25519Sig>) {
+    match signature {
+        ParcelSignature::Single(sig) => (Some(*sig), None, None),
+        ParcelSignature::Multi(multisig) => (None, Some(multisig.into()), None),
+        ParcelSignature::Ed25519 {
+            public,
+            signature,
+        } => (
+            None,
+            None,
+            Some(Ed25519Sig {
+                public: *public,
+                sig: *signature,
+            }),
+        ),
+    }
+}
+
 impl From<LocalizedParcel> for Parcel {
     fn from(p: LocalizedParcel) -> Self {
-        let sig = p.signature();
+        let (sig, multisig, ed25519) = signature_fields(p.signature());
         Self {
             block_number: Some(p.block_number),
             block_hash: Some(p.block_hash),
@@ -46,14 +113,97 @@ impl From<LocalizedParcel> for Parcel {
             network_id: p.network_id,
             action: Action::from_core(p.as_unsigned().action.clone(), p.network_id),
             hash: p.hash(),
-            sig: sig.into(),
+            sig,
+            multisig,
+            ed25519,
+        }
+    }
+}
+
+/// The outcome of a parcel executed without being imported.
+#[derive(Debug, Serialize)]
+pub struct ParcelCall {
+    pub invoice: ParcelInvoice,
+    /// The effects on the state, which are dropped after the call
+    pub traces: Vec<Trace>,
+}
+
+/// Where a parcel is known: the canonical chain, the mem pool, or the parcels recently dropped from the mem pool.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParcelLocation {
+    /// One of "mined", "pending", "future" and "dropped".
+    pub status: &'static str,
+    pub block_number: Option<u64>,
+    pub block_hash: Option<H256>,
+    pub parcel_index: Option<usize>,
+    /// One of "limit", "replaced", "invalid", "canceled" and "expired" if the parcel is dropped.
+    pub reason: Option<&'static str>,
+    /// Hash of the parcel which replaced the dropped one.
+    pub replaced_by: Option<H256>,
+    pub first_seen: Option<u64>,
+    pub broadcast: Option<u64>,
+    pub included: Option<u64>,
+    pub finalized: Option<u64>,
+}
+
+impl ParcelLocation {
+    pub fn mined(block_number: u64, block_hash: H256, parcel_index: usize) -> Self {
+        Self {
+            status: "mined",
+            block_number: Some(block_number),
+            block_hash: Some(block_hash),
+            parcel_index: Some(parcel_index),
+            reason: None,
+            replaced_by: None,
+            first_seen: None,
+            broadcast: None,
+            included: None,
+            finalized: None,
+        }
+    }
+
+    pub fn with_timestamps(self, timestamps: Option<ParcelTimestamps>) -> Self {
+        let timestamps = timestamps.unwrap_or_default();
+        Self {
+            first_seen: timestamps.first_seen,
+            broadcast: timestamps.broadcast,
+            included: timestamps.included,
+            finalized: timestamps.finalized,
+            ..self
+        }
+    }
+}
+
+impl From<ParcelPoolStatus> for ParcelLocation {
+    fn from(status: ParcelPoolStatus) -> Self {
+        let (status, reason, replaced_by) = match status {
+            ParcelPoolStatus::Pending => ("pending", None, None),
+            ParcelPoolStatus::Future => ("future", None, None),
+            ParcelPoolStatus::Dropped(DropReason::Limit) => ("dropped", Some("limit"), None),
+            ParcelPoolStatus::Dropped(DropReason::Replaced(hash)) => ("dropped", Some("replaced"), Some(hash)),
+            ParcelPoolStatus::Dropped(DropReason::Invalid) => ("dropped", Some("invalid"), None),
+            ParcelPoolStatus::Dropped(DropReason::Canceled) => ("dropped", Some("canceled"), None),
+            ParcelPoolStatus::Dropped(DropReason::Expired) => ("dropped", Some("expired"), None),
+        };
+        Self {
+            status,
+            block_number: None,
+            block_hash: None,
+            parcel_index: None,
+            reason,
+            replaced_by,
+            first_seen: None,
+            broadcast: None,
+            included: None,
+            finalized: None,
         }
     }
 }
 
 impl From<SignedParcel> for Parcel {
     fn from(p: SignedParcel) -> Self {
-        let sig = p.signature();
+        let (sig, multisig, ed25519) = signature_fields(p.signature());
         Self {
             block_number: None,
             block_hash: None,
@@ -63,7 +213,9 @@ impl From<SignedParcel> for Parcel {
             network_id: p.network_id,
             action: Action::from_core(p.as_unsigned().action.clone(), p.network_id),
             hash: p.hash(),
-            sig: sig.into(),
+            sig,
+            multisig,
+            ed25519,
         }
     }
 }
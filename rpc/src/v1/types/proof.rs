@@ -0,0 +1,35 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use super::Bytes;
+
+/// A value read from the state, with or without its proof.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum MaybeProven<T> {
+    Value(T),
+    Proven(Proven<T>),
+}
+
+/// A value with the nodes of the state trie which prove it.
+/// The proof is anchored to the state root of `header`, which is the RLP encoded block header.
+#[derive(Debug, Serialize)]
+pub struct Proven<T> {
+    pub value: T,
+    pub header: Bytes,
+    pub proof: Vec<Bytes>,
+}
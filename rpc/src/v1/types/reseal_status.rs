@@ -0,0 +1,56 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use ccore::ResealStatus as CoreResealStatus;
+use primitives::U256;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResealStatus {
+    /// In milliseconds.
+    pub period: u64,
+    /// In milliseconds.
+    pub next_allowed_reseal: u64,
+    pub reseal_deferred: bool,
+    /// In milliseconds.
+    pub next_mandatory_reseal: u64,
+    /// In percent.
+    pub pending_fullness: u8,
+    pub urgent_reseal_fee: Option<U256>,
+    pub urgent_reseal_fullness: Option<u8>,
+    pub reseal_backoff: bool,
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+impl From<CoreResealStatus> for ResealStatus {
+    fn from(status: CoreResealStatus) -> Self {
+        Self {
+            period: as_millis(status.period),
+            next_allowed_reseal: as_millis(status.next_allowed_reseal),
+            reseal_deferred: status.deferred,
+            next_mandatory_reseal: as_millis(status.next_mandatory_reseal),
+            pending_fullness: status.pending_fullness,
+            urgent_reseal_fee: status.urgent_fee,
+            urgent_reseal_fullness: status.urgent_fullness,
+            reseal_backoff: status.backoff,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ccore::SyncProgress;
+use ctypes::BlockNumber;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub starting_block: BlockNumber,
+    pub current_block: BlockNumber,
+    pub highest_block: BlockNumber,
+    pub blocks_per_second: f64,
+    /// None if no block is imported recently.
+    pub remaining_seconds: Option<u64>,
+}
+
+impl SyncStatus {
+    pub fn new(progress: SyncProgress, current_block: BlockNumber) -> Self {
+        let remaining_blocks = progress.highest_block.saturating_sub(current_block);
+        let remaining_seconds = if progress.blocks_per_second > 0f64 {
+            Some((remaining_blocks as f64 / progress.blocks_per_second).ceil() as u64)
+        } else {
+            None
+        };
+        Self {
+            starting_block: progress.starting_block,
+            current_block,
+            highest_block: progress.highest_block,
+            blocks_per_second: progress.blocks_per_second,
+            remaining_seconds,
+        }
+    }
+}
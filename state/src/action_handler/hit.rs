@@ -17,6 +17,7 @@
 use ccrypto::blake256;
 use cmerkle::TrieMut;
 use ctypes::invoice::ParcelInvoice;
+use ctypes::BlockNumber;
 use primitives::{Bytes, H256};
 use rlp::{self, Decodable, DecoderError, Encodable, UntrustedRlp};
 
@@ -71,7 +72,12 @@ impl ActionHandler for HitHandler {
     }
 
     /// `bytes` must be valid encoding of HitAction
-    fn execute(&self, bytes: &Bytes, state: &mut TopLevelState) -> Option<StateResult<ParcelInvoice>> {
+    fn execute(
+        &self,
+        bytes: &Bytes,
+        state: &mut TopLevelState,
+        _block_number: BlockNumber,
+    ) -> Option<StateResult<ParcelInvoice>> {
         HitAction::decode(&UntrustedRlp::new(bytes)).ok().map(|action| {
             let prev_counter: u32 = rlp::decode(&state.action_data(&self.address())?);
             let increase = action.increase as u32;
@@ -18,6 +18,7 @@ mod hit;
 
 use cmerkle::TrieMut;
 use ctypes::invoice::ParcelInvoice;
+use ctypes::BlockNumber;
 use primitives::Bytes;
 
 use super::{StateResult, TopLevelState};
@@ -25,7 +26,13 @@ use super::{StateResult, TopLevelState};
 pub trait ActionHandler: Send + Sync {
     fn init(&self, state: &mut TrieMut) -> StateResult<()>;
     fn is_target(&self, bytes: &Bytes) -> bool;
-    fn execute(&self, bytes: &Bytes, state: &mut TopLevelState) -> Option<StateResult<ParcelInvoice>>;
+    /// `block_number` is the number of the block which includes the parcel.
+    fn execute(
+        &self,
+        bytes: &Bytes,
+        state: &mut TopLevelState,
+        block_number: BlockNumber,
+    ) -> Option<StateResult<ParcelInvoice>>;
 }
 
 pub use self::hit::HitHandler;
@@ -54,6 +54,16 @@ impl<Item: CacheableItem> GlobalCacheBuffer<Item> {
         self.queue.push(QueuedItem::new(addr, item, modified));
     }
 
+    /// The addresses of the items which were modified, not only read.
+    pub fn modified_addresses(&self) -> Vec<Item::Address> {
+        self.queue.iter().filter(|item| item.is_modified()).map(|item| item.address.clone()).collect()
+    }
+
+    /// Moves the items of `other` after the items of this buffer.
+    pub fn append(&mut self, other: &mut Self) {
+        self.queue.append(&mut other.queue);
+    }
+
     pub fn sync_cache(
         &mut self,
         cache: &mut GlobalCache<Item>,
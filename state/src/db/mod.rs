@@ -17,6 +17,8 @@
 mod block_changes;
 mod global_cache;
 mod global_cache_buffer;
+mod shard_overlay_db;
 mod state_db;
 
+pub use self::shard_overlay_db::{ShardChanges, ShardOverlayDB};
 pub use self::state_db::StateDB;
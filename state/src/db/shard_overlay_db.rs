@@ -0,0 +1,250 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use hashdb::{DBValue, HashDB};
+use memorydb::MemoryDB;
+use primitives::H256;
+
+use super::super::{
+    AssetScheme, AssetSchemeAddress, Backend, OwnedAsset, OwnedAssetAddress, ShardBackend, ShardMetadata,
+    ShardMetadataAddress, World, WorldAddress,
+};
+use super::global_cache_buffer::GlobalCacheBuffer;
+use super::StateDB;
+
+/// A database to execute a shard in parallel with the other shards.
+///
+/// It reads through to the state database, and keeps the trie nodes and the cache entries written by the shard
+/// apart from it. They are merged into the state database by `StateDB::merge_shard_changes`.
+pub struct ShardOverlayDB<'a> {
+    base: &'a StateDB,
+    /// The trie nodes written by the earlier executions which are not merged into `base` yet.
+    earlier: Option<&'a MemoryDB>,
+    changes: ShardChanges,
+}
+
+/// The trie nodes and the cache entries written on a `ShardOverlayDB`.
+pub struct ShardChanges {
+    pub overlay: MemoryDB,
+    pub shard_metadata_cache_buffer: GlobalCacheBuffer<ShardMetadata>,
+    pub world_cache_buffer: GlobalCacheBuffer<World>,
+    pub asset_scheme_cache_buffer: GlobalCacheBuffer<AssetScheme>,
+    pub asset_cache_buffer: GlobalCacheBuffer<OwnedAsset>,
+}
+
+impl<'a> ShardOverlayDB<'a> {
+    pub fn new(base: &'a StateDB) -> Self {
+        Self {
+            base,
+            earlier: None,
+            changes: ShardChanges::default(),
+        }
+    }
+
+    /// Reads the changes of the earlier executions before the state database.
+    /// The changes of this one are kept apart, so they can be dropped if the execution fails.
+    pub fn on_top_of(base: &'a StateDB, earlier: &'a ShardChanges) -> Self {
+        Self {
+            base,
+            earlier: Some(&earlier.overlay),
+            changes: ShardChanges::default(),
+        }
+    }
+
+    pub fn into_changes(self) -> ShardChanges {
+        self.changes
+    }
+}
+
+impl Default for ShardChanges {
+    fn default() -> Self {
+        ShardChanges {
+            overlay: MemoryDB::new(),
+            shard_metadata_cache_buffer: GlobalCacheBuffer::new(),
+            world_cache_buffer: GlobalCacheBuffer::new(),
+            asset_scheme_cache_buffer: GlobalCacheBuffer::new(),
+            asset_cache_buffer: GlobalCacheBuffer::new(),
+        }
+    }
+}
+
+impl ShardChanges {
+    /// Moves the changes of `later` after these changes.
+    pub fn append(&mut self, mut later: ShardChanges) {
+        self.overlay.consolidate(later.overlay);
+        self.shard_metadata_cache_buffer.append(&mut later.shard_metadata_cache_buffer);
+        self.world_cache_buffer.append(&mut later.world_cache_buffer);
+        self.asset_scheme_cache_buffer.append(&mut later.asset_scheme_cache_buffer);
+        self.asset_cache_buffer.append(&mut later.asset_cache_buffer);
+    }
+}
+
+impl<'a> HashDB for ShardOverlayDB<'a> {
+    fn keys(&self) -> HashMap<H256, i32> {
+        let mut keys = self.base.as_hashdb().keys();
+        for overlay in self.earlier.into_iter().chain(Some(&self.changes.overlay)) {
+            for (key, refs) in overlay.keys() {
+                *keys.entry(key).or_insert(0) += refs;
+            }
+        }
+        keys
+    }
+
+    fn get(&self, key: &H256) -> Option<DBValue> {
+        if let Some((value, refs)) = self.changes.overlay.raw(key) {
+            if refs > 0 {
+                return Some(value)
+            }
+        }
+        if let Some((value, refs)) = self.earlier.and_then(|earlier| earlier.raw(key)) {
+            if refs > 0 {
+                return Some(value)
+            }
+        }
+        self.base.as_hashdb().get(key)
+    }
+
+    fn contains(&self, key: &H256) -> bool {
+        self.get(key).is_some()
+    }
+
+    fn insert(&mut self, value: &[u8]) -> H256 {
+        self.changes.overlay.insert(value)
+    }
+
+    fn emplace(&mut self, key: H256, value: DBValue) {
+        self.changes.overlay.emplace(key, value)
+    }
+
+    fn remove(&mut self, key: &H256) {
+        self.changes.overlay.remove(key)
+    }
+}
+
+impl<'a> Backend for ShardOverlayDB<'a> {
+    fn as_hashdb(&self) -> &HashDB {
+        self
+    }
+
+    fn as_hashdb_mut(&mut self) -> &mut HashDB {
+        self
+    }
+}
+
+impl<'a> ShardBackend for ShardOverlayDB<'a> {
+    fn add_to_shard_metadata_cache(
+        &mut self,
+        address: ShardMetadataAddress,
+        item: Option<ShardMetadata>,
+        modified: bool,
+    ) {
+        self.changes.shard_metadata_cache_buffer.push(address, item, modified);
+    }
+
+    fn add_to_world_cache(&mut self, address: WorldAddress, item: Option<World>, modified: bool) {
+        self.changes.world_cache_buffer.push(address, item, modified);
+    }
+
+    fn add_to_asset_scheme_cache(&mut self, addr: AssetSchemeAddress, item: Option<AssetScheme>, modified: bool) {
+        self.changes.asset_scheme_cache_buffer.push(addr, item, modified);
+    }
+
+    fn add_to_asset_cache(&mut self, addr: OwnedAssetAddress, item: Option<OwnedAsset>, modified: bool) {
+        self.changes.asset_cache_buffer.push(addr, item, modified);
+    }
+
+    fn get_cached_shard_metadata(&self, addr: &ShardMetadataAddress) -> Option<Option<ShardMetadata>> {
+        self.base.get_cached_shard_metadata(addr)
+    }
+
+    fn get_cached_world(&self, hash: &WorldAddress) -> Option<Option<World>> {
+        self.base.get_cached_world(hash)
+    }
+
+    fn get_cached_asset_scheme(&self, hash: &AssetSchemeAddress) -> Option<Option<AssetScheme>> {
+        self.base.get_cached_asset_scheme(hash)
+    }
+
+    fn get_cached_asset(&self, hash: &OwnedAssetAddress) -> Option<Option<OwnedAsset>> {
+        self.base.get_cached_asset(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::tests::helpers::get_temp_state_db;
+    use super::*;
+
+    #[test]
+    fn reads_through_to_the_state_db() {
+        let mut state_db = get_temp_state_db();
+        let base_key = state_db.as_hashdb_mut().insert(b"base");
+
+        let (overlay_key, changes) = {
+            let mut db = ShardOverlayDB::new(&state_db);
+            assert_eq!(Some(DBValue::from_slice(b"base")), db.get(&base_key));
+
+            let overlay_key = db.insert(b"overlay");
+            assert!(db.contains(&overlay_key));
+            (overlay_key, db.into_changes())
+        };
+        assert!(!state_db.as_hashdb().contains(&overlay_key));
+
+        state_db.merge_shard_changes(changes);
+        assert_eq!(Some(DBValue::from_slice(b"overlay")), state_db.as_hashdb().get(&overlay_key));
+    }
+
+    #[test]
+    fn reads_the_earlier_changes() {
+        let mut state_db = get_temp_state_db();
+        let (earlier_key, earlier) = {
+            let mut db = ShardOverlayDB::new(&state_db);
+            let key = db.insert(b"earlier");
+            (key, db.into_changes())
+        };
+
+        let (later_key, later) = {
+            let mut db = ShardOverlayDB::on_top_of(&state_db, &earlier);
+            assert_eq!(Some(DBValue::from_slice(b"earlier")), db.get(&earlier_key));
+            (db.insert(b"later"), db.into_changes())
+        };
+        assert!(!earlier.overlay.contains(&later_key));
+
+        let mut changes = earlier;
+        changes.append(later);
+        state_db.merge_shard_changes(changes);
+        assert!(state_db.as_hashdb().contains(&earlier_key));
+        assert!(state_db.as_hashdb().contains(&later_key));
+    }
+
+    #[test]
+    fn removal_is_kept_in_the_overlay() {
+        let mut state_db = get_temp_state_db();
+        let key = state_db.as_hashdb_mut().insert(b"value");
+
+        let changes = {
+            let mut db = ShardOverlayDB::new(&state_db);
+            db.remove(&key);
+            db.into_changes()
+        };
+        assert!(state_db.as_hashdb().contains(&key));
+
+        state_db.merge_shard_changes(changes);
+        assert!(!state_db.as_hashdb().contains(&key));
+    }
+}
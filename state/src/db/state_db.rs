@@ -44,6 +44,7 @@ use util_error::UtilError;
 
 use super::global_cache::GlobalCache;
 use super::global_cache_buffer::GlobalCacheBuffer;
+use super::shard_overlay_db::ShardChanges;
 
 use super::super::{
     Account, ActionData, ActionHandler, AssetScheme, AssetSchemeAddress, Backend, CacheableItem, Metadata,
@@ -354,6 +355,11 @@ impl StateDB {
         }
     }
 
+    /// The addresses of the accounts modified by the committing block. It must be called before `sync_cache`.
+    pub fn modified_accounts(&self) -> Vec<Address> {
+        self.account_cache_buffer.modified_addresses()
+    }
+
     /// Conversion method to interpret self as `HashDB` reference
     pub fn as_hashdb(&self) -> &HashDB {
         self.db.as_hashdb()
@@ -486,6 +492,22 @@ impl StateDB {
         &*self.db
     }
 
+    /// Merges the trie nodes and the cache entries of a shard executed on a `ShardOverlayDB`.
+    pub fn merge_shard_changes(&mut self, changes: ShardChanges) {
+        let ShardChanges {
+            overlay,
+            mut shard_metadata_cache_buffer,
+            mut world_cache_buffer,
+            mut asset_scheme_cache_buffer,
+            mut asset_cache_buffer,
+        } = changes;
+        self.db.consolidate(overlay);
+        self.shard_metadata_cache_buffer.append(&mut shard_metadata_cache_buffer);
+        self.world_cache_buffer.append(&mut world_cache_buffer);
+        self.asset_scheme_cache_buffer.append(&mut asset_scheme_cache_buffer);
+        self.asset_cache_buffer.append(&mut asset_cache_buffer);
+    }
+
     fn get_cached<Item>(&self, addr: &Item::Address, cache: &Mutex<GlobalCache<Item>>) -> Option<Option<Item>>
     where
         Item: CacheableItem, {
@@ -36,6 +36,7 @@
 //! or rolled back.
 
 use std::cell::RefMut;
+use std::collections::HashMap;
 use std::fmt;
 
 use ccrypto::BLAKE_NULL_RLP;
@@ -45,18 +46,24 @@ use ctypes::invoice::{ParcelInvoice, TransactionInvoice};
 use ctypes::parcel::{Action, Error as ParcelError, Parcel, ShardChange};
 use ctypes::transaction::Transaction;
 use ctypes::util::unexpected::Mismatch;
-use ctypes::{ShardId, WorldId};
+use ctypes::{BlockNumber, ShardId, WorldId};
 use primitives::{Bytes, H256, U256};
+use rayon::prelude::*;
+use rlp::{self, Encodable, UntrustedRlp, NULL_RLP};
 
 use super::super::backend::TopBackend;
 use super::super::checkpoint::{CheckpointId, StateWithCheckpoint};
+use super::super::db::{ShardChanges, ShardOverlayDB};
 use super::super::item::local_cache::{CacheableItem, LocalCache};
+use super::super::item::name_record::MAX_NAME_DURATION;
+use super::super::item::{ADDRESS_PREFIX, OWNED_ASSET_PREFIX};
 use super::super::traits::{ShardState, ShardStateInfo, StateWithCache, TopState, TopStateInfo};
 use super::super::{
-    Account, ActionData, AssetScheme, AssetSchemeAddress, Metadata, MetadataAddress, OwnedAsset, OwnedAssetAddress,
-    RegularAccount, RegularAccountAddress, Shard, ShardAddress, ShardLevelState, ShardMetadata, World,
+    Account, ActionData, AssetScheme, AssetSchemeAddress, Metadata, MetadataAddress, NameRecord, NameRecordAddress,
+    OwnedAsset, OwnedAssetAddress, RegularAccount, RegularAccountAddress, Shard, ShardAddress, ShardLevelState,
+    ShardMetadata, World,
 };
-use super::super::{StateDB, StateError, StateResult};
+use super::super::{StateDB, StateError, StateResult, Trace};
 
 /// Representation of the entire state of all accounts in the system.
 ///
@@ -111,6 +118,7 @@ pub struct TopLevelState {
     shard: LocalCache<Shard>,
     action_data: LocalCache<ActionData>,
     id_of_checkpoints: Vec<CheckpointId>,
+    trace: Option<Vec<Trace>>,
 }
 
 impl TopStateInfo for TopLevelState {
@@ -195,6 +203,44 @@ impl TopStateInfo for TopLevelState {
         let action_data = self.get_action_data_mut(key)?;
         Ok(action_data.clone().into())
     }
+
+    fn name_record(&self, name: &str) -> TrieResult<Option<NameRecord>> {
+        let address = NameRecordAddress::new(name);
+        let data = self.get_action_data(&address.into())?;
+        Ok(data.filter(|data| data.as_slice() != &NULL_RLP[..]).map(|data| rlp::decode(&data)))
+    }
+
+    fn account_proof(&self, a: &Address) -> TrieResult<Vec<Bytes>> {
+        let trie = TrieFactory::readonly(self.db.as_hashdb(), &self.root)?;
+        trie.get_proof(a.as_ref())
+    }
+
+    fn for_each_account(&self, f: &mut FnMut(&H256, Account)) -> TrieResult<()> {
+        let trie = TrieFactory::readonly(self.db.as_hashdb(), &self.root)?;
+        trie.for_each_leaf(|path, value| {
+            if has_prefix(value, ADDRESS_PREFIX) {
+                f(path, rlp::decode(value));
+            }
+        })
+    }
+
+    fn for_each_asset(&self, shard_id: ShardId, f: &mut FnMut(&H256, OwnedAsset)) -> TrieResult<()> {
+        let shard_root = match self.shard_root(shard_id)? {
+            Some(shard_root) => shard_root,
+            None => return Ok(()),
+        };
+        let trie = TrieFactory::readonly(self.db.as_hashdb(), &shard_root)?;
+        trie.for_each_leaf(|path, value| {
+            if has_prefix(value, OWNED_ASSET_PREFIX) {
+                f(path, rlp::decode(value));
+            }
+        })
+    }
+}
+
+/// Every item in the state tries is a list that starts with the prefix of its type.
+fn has_prefix(value: &[u8], prefix: u8) -> bool {
+    UntrustedRlp::new(value).val_at::<u8>(0).map(|item_prefix| item_prefix == prefix).unwrap_or(false)
 }
 
 const PARCEL_FEE_CHECKPOINT: CheckpointId = 123;
@@ -294,6 +340,7 @@ impl TopLevelState {
             shard: LocalCache::new(),
             action_data: LocalCache::new(),
             id_of_checkpoints: Default::default(),
+            trace: None,
         }
     }
 
@@ -312,6 +359,7 @@ impl TopLevelState {
             shard: LocalCache::new(),
             action_data: LocalCache::new(),
             id_of_checkpoints: Default::default(),
+            trace: None,
         };
 
         Ok(state)
@@ -321,6 +369,26 @@ impl TopLevelState {
         &self.root
     }
 
+    /// Starts recording the effects of the parcels applied to the state.
+    pub fn start_tracing(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(Vec::new());
+        }
+    }
+
+    /// Returns the effects recorded since the last call, leaving the tracing on.
+    pub fn take_trace(&mut self) -> Vec<Trace> {
+        self.trace.as_mut().map(|trace| trace.drain(..).collect()).unwrap_or_default()
+    }
+
+    fn trace<F>(&mut self, step: F)
+    where
+        F: FnOnce() -> Trace, {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(step());
+        }
+    }
+
     /// Destroy the current object and return root and database.
     pub fn drop(mut self) -> (H256, StateDB) {
         self.propagate_to_global_cache();
@@ -329,7 +397,80 @@ impl TopLevelState {
 
     /// Execute a given parcel, charging parcel fee.
     /// This will change the state accordingly.
-    pub fn apply(&mut self, parcel: &Parcel, signer_public: &Public) -> StateResult<ParcelInvoice> {
+    /// The fee payer is reaped if its balance falls below `min_balance`.
+    /// Registering a name burns `name_fee_per_block` for each block it is registered.
+    /// `block_number` is the number of the block which includes the parcel.
+    pub fn apply(
+        &mut self,
+        parcel: &Parcel,
+        signer_public: &Public,
+        min_balance: &U256,
+        name_fee_per_block: &U256,
+        block_number: BlockNumber,
+    ) -> StateResult<ParcelInvoice> {
+        let (_, invoice) =
+            self.apply_with(parcel, signer_public, min_balance, name_fee_per_block, block_number, false)?;
+        Ok(invoice)
+    }
+
+    /// Execute the parcels of a block as `apply` does one by one. It fails if any of them fails.
+    /// Returns the fee payer and the invoice of each parcel.
+    ///
+    /// The shards of the asset transaction groups are executed on the thread pool after the fees are paid.
+    /// The groups which don't change the same shards run in parallel. The parcels which change the shards themselves
+    /// are applied after the groups before them are executed.
+    pub fn apply_parcels(
+        &mut self,
+        parcels: &[(&Parcel, Public)],
+        min_balance: &U256,
+        name_fee_per_block: &U256,
+        block_number: BlockNumber,
+    ) -> StateResult<Vec<(Address, ParcelInvoice)>> {
+        let mut results = Vec::with_capacity(parcels.len());
+        let mut deferred = Vec::new();
+        for (parcel, signer_public) in parcels {
+            // The traces are in the order of the parcels only if they are applied one by one.
+            let group = if self.trace.is_none() {
+                deferred_group(&parcel.action)
+            } else {
+                None
+            };
+            if group.is_none() && changes_shards(&parcel.action) {
+                self.apply_deferred(&mut deferred, &mut results)?;
+            }
+            let (fee_payer, invoice) = self.apply_with(
+                parcel,
+                signer_public,
+                min_balance,
+                name_fee_per_block,
+                block_number,
+                group.is_some(),
+            )?;
+            if let Some((transactions, changes)) = group {
+                deferred.push(DeferredGroup {
+                    index: results.len(),
+                    sender: fee_payer,
+                    transactions,
+                    changes,
+                });
+            }
+            results.push((fee_payer, invoice));
+        }
+        self.apply_deferred(&mut deferred, &mut results)?;
+        Ok(results)
+    }
+
+    /// Returns the fee payer with the invoice.
+    /// If `defer_shards` is set, the shards of the parcel are not executed, and the invoice is empty.
+    fn apply_with(
+        &mut self,
+        parcel: &Parcel,
+        signer_public: &Public,
+        min_balance: &U256,
+        name_fee_per_block: &U256,
+        block_number: BlockNumber,
+        defer_shards: bool,
+    ) -> StateResult<(Address, ParcelInvoice)> {
         // Change the public to an owner address if it is a regular key.
         let fee_payer = if self.regular_account_exists_and_not_null(signer_public)? {
             let regular_account = self.get_regular_account_mut(signer_public)?;
@@ -340,7 +481,15 @@ impl TopLevelState {
 
         self.create_checkpoint(PARCEL_FEE_CHECKPOINT);
 
-        match self.apply_internal(parcel, &fee_payer, signer_public) {
+        match self.apply_internal(
+            parcel,
+            &fee_payer,
+            signer_public,
+            min_balance,
+            name_fee_per_block,
+            block_number,
+            defer_shards,
+        ) {
             Err(StateError::Transaction(err)) => unreachable!("{:?}", err),
             Err(err) => {
                 self.revert_to_checkpoint(PARCEL_FEE_CHECKPOINT);
@@ -349,7 +498,7 @@ impl TopLevelState {
             Ok(invoice) => {
                 self.discard_checkpoint(PARCEL_FEE_CHECKPOINT);
                 self.commit()?; // FIXME: Remove early commit.
-                Ok(invoice)
+                Ok((fee_payer, invoice))
             }
         }
     }
@@ -359,6 +508,10 @@ impl TopLevelState {
         parcel: &Parcel,
         fee_payer: &Address,
         signer_public: &Public,
+        min_balance: &U256,
+        name_fee_per_block: &U256,
+        block_number: BlockNumber,
+        defer_shards: bool,
     ) -> StateResult<ParcelInvoice> {
         let nonce = self.nonce(fee_payer)?;
 
@@ -381,11 +534,30 @@ impl TopLevelState {
 
         self.inc_nonce(fee_payer)?;
         self.sub_balance(fee_payer, &fee)?;
+        self.trace(|| Trace::PayFee {
+            payer: *fee_payer,
+            fee,
+            nonce: nonce + U256::one(),
+        });
+
+        if defer_shards {
+            // The group doesn't change the balances, so the payer can be reaped before it's executed.
+            self.reap_if_below_min_balance(fee_payer, min_balance)?;
+            return Ok(ParcelInvoice::Multiple(vec![]))
+        }
 
         // The failed parcel also must pay the fee and increase nonce.
         self.create_checkpoint(PARCEL_ACTION_CHECKPOINT);
 
-        match self.apply_action(&parcel.action, &parcel.network_id, fee_payer, signer_public) {
+        let invoice = match self.apply_action(
+            &parcel.action,
+            &parcel.network_id,
+            fee_payer,
+            signer_public,
+            min_balance,
+            name_fee_per_block,
+            block_number,
+        ) {
             Ok(invoice) => {
                 self.discard_checkpoint(PARCEL_ACTION_CHECKPOINT);
                 Ok(invoice)
@@ -410,13 +582,18 @@ impl TopLevelState {
             }
             Err(StateError::Parcel(err)) => {
                 self.revert_to_checkpoint(PARCEL_ACTION_CHECKPOINT);
+                self.trace(|| Trace::RevertAction {
+                    error: err.clone(),
+                });
                 Ok(ParcelInvoice::SingleFail(err))
             }
             Err(err) => {
                 self.revert_to_checkpoint(PARCEL_ACTION_CHECKPOINT);
                 Err(err)
             }
-        }
+        }?;
+        self.reap_if_below_min_balance(fee_payer, min_balance)?;
+        Ok(invoice)
     }
 
     fn apply_action(
@@ -425,6 +602,9 @@ impl TopLevelState {
         network_id: &NetworkId,
         fee_payer: &Address,
         signer_public: &Public,
+        min_balance: &U256,
+        name_fee_per_block: &U256,
+        block_number: BlockNumber,
     ) -> StateResult<ParcelInvoice> {
         match action {
             Action::AssetTransactionGroup {
@@ -442,6 +622,10 @@ impl TopLevelState {
 
                 debug_assert!(transactions.iter().all(|t| &t.network_id() == network_id));
 
+                if can_apply_in_parallel(changes) {
+                    return self.apply_transactions_in_parallel(&transactions, &changes, fee_payer)
+                }
+
                 let first_result = self.apply_transactions_with_check(&transactions, &changes[0], fee_payer)?;
 
                 for change in changes.iter().skip(1) {
@@ -455,10 +639,24 @@ impl TopLevelState {
             Action::Payment {
                 receiver,
                 amount,
-            } => match self.transfer_balance(fee_payer, receiver, amount) {
-                Ok(()) => Ok(ParcelInvoice::SingleSuccess),
-                Err(err) => Err(err.into()),
-            },
+            } => {
+                self.transfer_balance(fee_payer, receiver, amount)?;
+                self.trace(|| Trace::Transfer {
+                    sender: *fee_payer,
+                    receiver: *receiver,
+                    amount: *amount,
+                });
+                // Payments can't leave the receiver below the minimum balance, so dust accounts aren't created.
+                let balance = self.balance(receiver)?;
+                if &balance < min_balance {
+                    return Err(ParcelError::BelowMinBalance {
+                        address: *receiver,
+                        balance,
+                        min_balance: *min_balance,
+                    }.into())
+                }
+                Ok(ParcelInvoice::SingleSuccess)
+            }
             Action::SetRegularKey {
                 key,
             } => match self.set_regular_key(signer_public, key) {
@@ -480,6 +678,10 @@ impl TopLevelState {
                 owners,
             } => {
                 self.change_shard_owners(*shard_id, owners, fee_payer)?;
+                self.trace(|| Trace::SetShardOwners {
+                    shard: *shard_id,
+                    owners: owners.clone(),
+                });
                 Ok(ParcelInvoice::SingleSuccess)
             }
             Action::SetShardUsers {
@@ -487,12 +689,39 @@ impl TopLevelState {
                 users,
             } => {
                 self.change_shard_users(*shard_id, users, fee_payer)?;
+                self.trace(|| Trace::SetShardUsers {
+                    shard: *shard_id,
+                    users: users.clone(),
+                });
+                Ok(ParcelInvoice::SingleSuccess)
+            }
+            Action::RegisterName {
+                name,
+                value,
+                duration,
+            } => {
+                self.register_name(name, value, *duration, name_fee_per_block, fee_payer, block_number)?;
+                Ok(ParcelInvoice::SingleSuccess)
+            }
+            Action::UpdateName {
+                name,
+                value,
+            } => {
+                self.update_name(name, value, fee_payer, block_number)?;
+                Ok(ParcelInvoice::SingleSuccess)
+            }
+            Action::TransferName {
+                name,
+                receiver,
+            } => {
+                self.transfer_name(name, receiver, fee_payer, block_number)?;
                 Ok(ParcelInvoice::SingleSuccess)
             }
             Action::Custom(bytes) => {
+                self.trace(|| Trace::Custom);
                 let handlers = self.db.custom_handlers().to_vec();
                 for h in handlers {
-                    if let Some(result) = h.execute(bytes, self) {
+                    if let Some(result) = h.execute(bytes, self, block_number) {
                         return result
                     }
                 }
@@ -508,7 +737,133 @@ impl TopLevelState {
         sender: &Address,
     ) -> StateResult<Vec<TransactionInvoice>> {
         let shard_id = change.shard_id;
+        let shard_root = self.pre_root_of(change)?;
+
+        let (new_shard_root, db, results) =
+            self.apply_transactions_internal(transactions, shard_id, shard_root, sender)?;
+        check_post_root(change, &new_shard_root)?;
 
+        self.db = db;
+
+        self.set_shard_root(shard_id, &shard_root, &new_shard_root)?;
+        self.trace(|| Trace::ApplyTransactions {
+            shard: shard_id,
+            root: new_shard_root,
+            invoices: results.clone(),
+        });
+        Ok(results)
+    }
+
+    /// Applies the transactions to the distinct shards of the changes at the same time.
+    /// Each shard is executed on its own thread, and the results are checked in the order of the changes,
+    /// so that the parcel fails with the same error as when the shards are executed one by one.
+    fn apply_transactions_in_parallel(
+        &mut self,
+        transactions: &[Transaction],
+        changes: &[ShardChange],
+        sender: &Address,
+    ) -> StateResult<ParcelInvoice> {
+        let jobs: Vec<_> = changes
+            .iter()
+            .map(|change| -> StateResult<_> {
+                let shard_root = self.pre_root_of(change)?;
+                let shard_users = self.shard_users_with_owners(change.shard_id)?;
+                Ok((change.shard_id, shard_root, shard_users))
+            })
+            .collect();
+
+        let outcomes: Vec<_> = {
+            let db = &self.db;
+            jobs.into_par_iter()
+                .map(|job| -> StateResult<_> {
+                    let (shard_id, shard_root, shard_users) = job?;
+                    let mut shard_level_state =
+                        ShardLevelState::from_existing(shard_id, ShardOverlayDB::new(db), shard_root)?;
+                    let mut results = Vec::with_capacity(transactions.len());
+                    for t in transactions {
+                        results.push(shard_level_state.apply(shard_id, t, sender, &shard_users)?);
+                    }
+                    let (new_shard_root, db) = shard_level_state.drop();
+                    Ok((shard_root, new_shard_root, db.into_changes(), results))
+                })
+                .collect()
+        };
+
+        let mut first_result = None;
+        for (change, outcome) in changes.iter().zip(outcomes) {
+            let (shard_root, new_shard_root, shard_changes, results) = outcome?;
+            check_post_root(change, &new_shard_root)?;
+
+            self.db.merge_shard_changes(shard_changes);
+
+            self.set_shard_root(change.shard_id, &shard_root, &new_shard_root)?;
+            self.trace(|| Trace::ApplyTransactions {
+                shard: change.shard_id,
+                root: new_shard_root,
+                invoices: results.clone(),
+            });
+            if let Some(first_result) = &first_result {
+                if *first_result != results {
+                    return Err(ParcelError::InconsistentShardOutcomes.into())
+                }
+                continue
+            }
+            first_result = Some(results);
+        }
+        Ok(ParcelInvoice::Multiple(first_result.expect("There are at least two changes")))
+    }
+
+    /// Executes the shards of the deferred groups, and fills in their invoices.
+    fn apply_deferred(
+        &mut self,
+        groups: &mut Vec<DeferredGroup>,
+        results: &mut [(Address, ParcelInvoice)],
+    ) -> StateResult<()> {
+        if groups.is_empty() {
+            return Ok(())
+        }
+
+        let mut shards = HashMap::new();
+        for change in groups.iter().flat_map(|group| group.changes) {
+            if shards.contains_key(&change.shard_id) {
+                continue
+            }
+            // The groups of the shards which don't exist fail when they are executed.
+            if let Some(root) = self.shard_root(change.shard_id)? {
+                let users = self.shard_users_with_owners(change.shard_id)?;
+                shards.insert(change.shard_id, (root, users));
+            }
+        }
+
+        let outcomes: Vec<_> = {
+            let db = &self.db;
+            let groups = &groups[..];
+            let shards = &shards;
+            connected_groups(groups)
+                .into_par_iter()
+                .map(|members| apply_connected_groups(db, groups, &members, shards))
+                .collect()
+        };
+
+        for outcome in outcomes {
+            let (changes, roots, invoices) = outcome?;
+            self.db.merge_shard_changes(changes);
+            for (shard_id, new_shard_root) in roots {
+                let shard_root = shards[&shard_id].0;
+                self.set_shard_root(shard_id, &shard_root, &new_shard_root)?;
+            }
+            for (index, invoice) in invoices {
+                results[index].1 = invoice;
+            }
+        }
+        groups.clear();
+        self.commit()?;
+        Ok(())
+    }
+
+    /// Returns the root of the shard, which must match the pre-root of the change if it's given.
+    fn pre_root_of(&self, change: &ShardChange) -> StateResult<H256> {
+        let shard_id = change.shard_id;
         let shard_root = self.shard_root(shard_id)?.ok_or_else(|| ParcelError::InvalidShardId(shard_id))?;
 
         if !change.pre_root.is_zero() && shard_root != change.pre_root {
@@ -517,20 +872,14 @@ impl TopLevelState {
                 found: change.pre_root,
             }).into())
         }
+        Ok(shard_root)
+    }
 
-        let (new_shard_root, db, results) =
-            self.apply_transactions_internal(transactions, shard_id, shard_root, sender)?;
-        if !change.post_root.is_zero() && change.post_root != new_shard_root {
-            return Err(ParcelError::InvalidShardRoot(Mismatch {
-                expected: new_shard_root,
-                found: change.post_root,
-            }).into())
-        }
-
-        self.db = db;
-
-        self.set_shard_root(shard_id, &shard_root, &new_shard_root)?;
-        Ok(results)
+    fn shard_users_with_owners(&self, shard_id: ShardId) -> StateResult<Vec<Address>> {
+        let mut shard_owners = self.shard_owners(shard_id)?.expect("Shard must have the owner");
+        let mut shard_users = self.shard_users(shard_id)?.expect("Shard must exist");
+        shard_users.append(&mut shard_owners);
+        Ok(shard_users)
     }
 
     pub fn apply_transactions(
@@ -555,9 +904,7 @@ impl TopLevelState {
         shard_root: H256,
         sender: &Address,
     ) -> StateResult<(H256, StateDB, Vec<TransactionInvoice>)> {
-        let mut shard_owners = self.shard_owners(shard_id)?.expect("Shard must have the owner");
-        let mut shard_users = self.shard_users(shard_id)?.expect("Shard must exist");
-        shard_users.append(&mut shard_owners);
+        let shard_users = self.shard_users_with_owners(shard_id)?;
 
         // FIXME: Make it mutable borrow db instead of cloning.
         let mut shard_level_state =
@@ -573,6 +920,25 @@ impl TopLevelState {
         Ok((new_root, db, results))
     }
 
+    /// Burns the balance of the account if it's below `min_balance`.
+    /// Only the nonce is left, so that the parcels signed before can't be replayed when the account is funded again.
+    /// Accounts with a regular key are kept, since the key is registered to them.
+    fn reap_if_below_min_balance(&mut self, a: &Address, min_balance: &U256) -> TrieResult<()> {
+        let balance = match self.get_account(a)? {
+            Some(ref account) if account.balance() < min_balance && account.regular_key().is_none() => {
+                *account.balance()
+            }
+            _ => return Ok(()),
+        };
+        cinfo!(STATE, "Reaping account {} with the balance {}", a, balance);
+        self.sub_balance(a, &balance)?;
+        self.trace(|| Trace::Reap {
+            address: *a,
+            balance,
+        });
+        Ok(())
+    }
+
     fn create_shard_level_state(&mut self, owners: Vec<Address>, users: Vec<Address>) -> StateResult<()> {
         let (shard_id, shard_root, db) = {
             let mut metadata = self.get_metadata_mut()?;
@@ -592,6 +958,10 @@ impl TopLevelState {
         ctrace!(STATE, "shard created({}, {:?})\nowners: {:?}, users: {:?}", shard_id, shard_root, owners, users);
 
         self.set_shard_root(shard_id, &BLAKE_NULL_RLP, &shard_root)?;
+        self.trace(|| Trace::CreateShard {
+            shard: shard_id,
+            owners: owners.clone(),
+        });
         self.set_shard_owners(shard_id, owners)?;
         self.set_shard_users(shard_id, users)?;
         Ok(())
@@ -664,7 +1034,6 @@ impl TopLevelState {
         self.shard.get_mut(&shard_address, db, from_global_cache)
     }
 
-    #[allow(dead_code)]
     fn get_action_data(&self, key: &H256) -> TrieResult<Option<ActionData>> {
         let db = TrieFactory::readonly(self.db.as_hashdb(), &self.root)?;
         let from_global_cache = || self.db.get_cached_action_data(key);
@@ -676,6 +1045,229 @@ impl TopLevelState {
         let from_global_cache = || self.db.get_cached_action_data(key);
         self.action_data.get_mut(key, db, from_global_cache)
     }
+
+    /// Returns the record of the name if it is registered to `sender` and not expired.
+    fn owned_name_record(&self, name: &str, sender: &Address, block_number: BlockNumber) -> StateResult<NameRecord> {
+        match self.name_record(name)? {
+            Some(ref record) if record.is_expired(block_number) => {
+                Err(ParcelError::NameNotRegistered(name.to_string()).into())
+            }
+            Some(ref record) if record.owner() != sender => Err(ParcelError::InsufficientPermission.into()),
+            Some(record) => Ok(record),
+            None => Err(ParcelError::NameNotRegistered(name.to_string()).into()),
+        }
+    }
+
+    fn set_name_record(&mut self, name: &str, record: NameRecord) -> StateResult<()> {
+        self.trace(|| Trace::SetName {
+            name: name.to_string(),
+            owner: *record.owner(),
+            expiration: record.expiration(),
+        });
+        let address = NameRecordAddress::new(name);
+        self.update_action_data(&address.into(), record.rlp_bytes().into_vec())
+    }
+}
+
+/// Whether the changes are of two or more different shards, which don't affect each other.
+fn can_apply_in_parallel(changes: &[ShardChange]) -> bool {
+    changes.len() > 1
+        && changes
+            .iter()
+            .enumerate()
+            .all(|(i, change)| changes[..i].iter().all(|previous| previous.shard_id != change.shard_id))
+}
+
+/// The asset transaction group of a parcel whose shards are executed by `apply_deferred`.
+struct DeferredGroup<'a> {
+    /// The index of the parcel in the block.
+    index: usize,
+    sender: Address,
+    transactions: &'a [Transaction],
+    changes: &'a [ShardChange],
+}
+
+/// The transactions and the changes of the group, if the action is a group which changes shards.
+fn deferred_group(action: &Action) -> Option<(&[Transaction], &[ShardChange])> {
+    match action {
+        Action::AssetTransactionGroup {
+            transactions,
+            changes,
+            ..
+        } if !changes.is_empty() => Some((&transactions[..], &changes[..])),
+        _ => None,
+    }
+}
+
+/// Whether the action changes the shards outside of the groups, or may read them.
+/// The deferred groups must be executed before it.
+fn changes_shards(action: &Action) -> bool {
+    match action {
+        Action::CreateShard
+        | Action::SetShardOwners {
+            ..
+        }
+        | Action::SetShardUsers {
+            ..
+        }
+        | Action::Custom(_) => true,
+        Action::AssetTransactionGroup {
+            ..
+        }
+        | Action::Payment {
+            ..
+        }
+        | Action::SetRegularKey {
+            ..
+        }
+        | Action::RegisterName {
+            ..
+        }
+        | Action::UpdateName {
+            ..
+        }
+        | Action::TransferName {
+            ..
+        } => false,
+    }
+}
+
+/// Partitions the groups into the sets whose groups change the same shards, directly or through other groups.
+/// The groups of each set are in the order of the parcels.
+fn connected_groups(groups: &[DeferredGroup]) -> Vec<Vec<usize>> {
+    fn find(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    let mut parents: Vec<usize> = (0..groups.len()).collect();
+    let mut last_group_of_shard = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        for change in group.changes {
+            if let Some(previous) = last_group_of_shard.insert(change.shard_id, index) {
+                let previous = find(&mut parents, previous);
+                let current = find(&mut parents, index);
+                parents[current] = previous;
+            }
+        }
+    }
+
+    let mut sets: Vec<Vec<usize>> = Vec::new();
+    let mut set_of_root = HashMap::new();
+    for index in 0..groups.len() {
+        let root = find(&mut parents, index);
+        let set = *set_of_root.entry(root).or_insert_with(|| {
+            sets.push(vec![]);
+            sets.len() - 1
+        });
+        sets[set].push(index);
+    }
+    sets
+}
+
+/// The changes, the new shard roots and the invoices of the groups in a set of `connected_groups`.
+type ConnectedOutcome = (ShardChanges, Vec<(ShardId, H256)>, Vec<(usize, ParcelInvoice)>);
+
+/// Executes the groups one by one on the state database, as `apply_action` does.
+/// The changes of the groups which fail are dropped.
+fn apply_connected_groups(
+    db: &StateDB,
+    groups: &[DeferredGroup],
+    members: &[usize],
+    shards: &HashMap<ShardId, (H256, Vec<Address>)>,
+) -> StateResult<ConnectedOutcome> {
+    let mut changes = ShardChanges::default();
+    let mut roots = HashMap::new();
+    let mut invoices = Vec::with_capacity(members.len());
+    for &member in members {
+        let group = &groups[member];
+        let outcome = apply_group(db, &changes, group, &roots, shards)?;
+        let invoice = match outcome {
+            Ok((results, group_changes, group_roots)) => {
+                changes.append(group_changes);
+                roots.extend(group_roots);
+                ParcelInvoice::Multiple(results)
+            }
+            Err(err) => ParcelInvoice::SingleFail(err),
+        };
+        invoices.push((group.index, invoice));
+    }
+    let mut roots: Vec<_> = roots.into_iter().collect();
+    roots.sort();
+    Ok((changes, roots, invoices))
+}
+
+/// Executes the transactions of the group on each shard of its changes.
+/// Returns the parcel error if the group fails.
+fn apply_group(
+    db: &StateDB,
+    earlier: &ShardChanges,
+    group: &DeferredGroup,
+    roots: &HashMap<ShardId, H256>,
+    shards: &HashMap<ShardId, (H256, Vec<Address>)>,
+) -> StateResult<Result<(Vec<TransactionInvoice>, ShardChanges, Vec<(ShardId, H256)>), ParcelError>> {
+    let mut overlay = ShardOverlayDB::on_top_of(db, earlier);
+    let mut new_roots: Vec<(ShardId, H256)> = Vec::with_capacity(group.changes.len());
+    let mut first_result = None;
+    for change in group.changes {
+        let shard_id = change.shard_id;
+        let (shard_root, shard_users) = match shards.get(&shard_id) {
+            Some((root, users)) => {
+                // The shard may be changed by the earlier groups, or by the earlier changes of this group.
+                let latest = new_roots.iter().rev().find(|(id, _)| *id == shard_id).map(|(_, new_root)| new_root);
+                (*latest.or_else(|| roots.get(&shard_id)).unwrap_or(root), users)
+            }
+            None => return Ok(Err(ParcelError::InvalidShardId(shard_id))),
+        };
+        if !change.pre_root.is_zero() && shard_root != change.pre_root {
+            return Ok(Err(ParcelError::InvalidShardRoot(Mismatch {
+                expected: shard_root,
+                found: change.pre_root,
+            })))
+        }
+
+        let mut shard_level_state = ShardLevelState::from_existing(shard_id, overlay, shard_root)?;
+        let mut results = Vec::with_capacity(group.transactions.len());
+        for t in group.transactions {
+            match shard_level_state.apply(shard_id, t, &group.sender, shard_users) {
+                Ok(result) => results.push(result),
+                Err(StateError::Parcel(err)) => return Ok(Err(err)),
+                Err(err) => return Err(err),
+            }
+        }
+        let (new_shard_root, db) = shard_level_state.drop();
+        overlay = db;
+        match check_post_root(change, &new_shard_root) {
+            Ok(()) => {}
+            Err(StateError::Parcel(err)) => return Ok(Err(err)),
+            Err(err) => return Err(err),
+        }
+        new_roots.push((shard_id, new_shard_root));
+
+        if let Some(first_result) = &first_result {
+            if *first_result != results {
+                return Ok(Err(ParcelError::InconsistentShardOutcomes))
+            }
+            continue
+        }
+        first_result = Some(results);
+    }
+    let results = first_result.expect("The group has at least one change");
+    Ok(Ok((results, overlay.into_changes(), new_roots)))
+}
+
+/// The root of the shard after the transactions must match the post-root of the change if it's given.
+fn check_post_root(change: &ShardChange, new_shard_root: &H256) -> StateResult<()> {
+    if !change.post_root.is_zero() && change.post_root != *new_shard_root {
+        return Err(ParcelError::InvalidShardRoot(Mismatch {
+            expected: *new_shard_root,
+            found: change.post_root,
+        }).into())
+    }
+    Ok(())
 }
 
 impl fmt::Debug for TopLevelState {
@@ -702,6 +1294,7 @@ impl Clone for TopLevelState {
             metadata: self.metadata.clone(),
             shard: self.shard.clone(),
             action_data: self.action_data.clone(),
+            trace: self.trace.clone(),
         }
     }
 }
@@ -806,9 +1399,15 @@ impl TopState<StateDB> for TopLevelState {
             self.kill_regular_account(&prev_regular_key);
         }
 
-        let mut owner_account = self.get_account_mut(&owner_address)?;
-        owner_account.set_regular_key(regular_key);
-        self.get_regular_account_mut(&regular_key)?.set_owner_public(&owner_public);
+        {
+            let mut owner_account = self.get_account_mut(&owner_address)?;
+            owner_account.set_regular_key(regular_key);
+            self.get_regular_account_mut(&regular_key)?.set_owner_public(&owner_public);
+        }
+        self.trace(|| Trace::SetRegularKey {
+            owner: owner_public,
+            key: *regular_key,
+        });
         Ok(())
     }
 
@@ -872,11 +1471,81 @@ impl TopState<StateDB> for TopLevelState {
         *action_data = data.into();
         Ok(())
     }
+
+    /// The fee is burned, so the block author cannot register names for free.
+    fn register_name(
+        &mut self,
+        name: &str,
+        value: &Bytes,
+        duration: BlockNumber,
+        name_fee_per_block: &U256,
+        sender: &Address,
+        block_number: BlockNumber,
+    ) -> StateResult<()> {
+        let expiration = match self.name_record(name)? {
+            Some(ref record) if !record.is_expired(block_number) => {
+                if record.owner() != sender {
+                    return Err(ParcelError::NameAlreadyRegistered(name.to_string()).into())
+                }
+                record.expiration().saturating_add(duration)
+            }
+            _ => block_number.saturating_add(duration),
+        };
+        // A name must be extended before it expires, so nobody can hold it forever with a single parcel.
+        let remaining = expiration - block_number;
+        if remaining > MAX_NAME_DURATION {
+            return Err(ParcelError::NameDurationTooLong {
+                max: MAX_NAME_DURATION,
+                got: remaining,
+            }.into())
+        }
+
+        let (cost, overflow) = name_fee_per_block.overflowing_mul(duration.into());
+        let balance = self.balance(sender)?;
+        if overflow || balance < cost {
+            return Err(ParcelError::InsufficientBalance {
+                address: *sender,
+                cost: if overflow {
+                    U256::max_value()
+                } else {
+                    cost
+                },
+                balance,
+            }.into())
+        }
+        self.sub_balance(sender, &cost)?;
+
+        self.set_name_record(name, NameRecord::new(*sender, value.clone(), expiration))
+    }
+
+    fn update_name(
+        &mut self,
+        name: &str,
+        value: &Bytes,
+        sender: &Address,
+        block_number: BlockNumber,
+    ) -> StateResult<()> {
+        let mut record = self.owned_name_record(name, sender, block_number)?;
+        record.set_value(value.clone());
+        self.set_name_record(name, record)
+    }
+
+    fn transfer_name(
+        &mut self,
+        name: &str,
+        receiver: &Address,
+        sender: &Address,
+        block_number: BlockNumber,
+    ) -> StateResult<()> {
+        let mut record = self.owned_name_record(name, sender, block_number)?;
+        record.set_owner(*receiver);
+        self.set_name_record(name, record)
+    }
 }
 
 #[cfg(test)]
 mod tests_state {
-    use ccrypto::BLAKE_NULL_RLP;
+    use ccrypto::{blake256, BLAKE_NULL_RLP};
     use ckey::Address;
     use primitives::U256;
 
@@ -933,6 +1602,31 @@ mod tests_state {
         assert_eq!(Ok(1.into()), state.nonce(&a));
     }
 
+    #[test]
+    fn for_each_account_in_database() {
+        let a = Address::random();
+        let b = Address::random();
+        let (root, db) = {
+            let mut state = get_temp_state();
+            assert_eq!(Ok(()), state.add_balance(&a, &U256::from(69u64)));
+            assert_eq!(Ok(()), state.inc_nonce(&b));
+            assert_eq!(Ok(()), state.commit());
+            state.drop()
+        };
+
+        let state = TopLevelState::from_existing(db, root).unwrap();
+        let mut accounts = Vec::new();
+        assert_eq!(
+            Ok(()),
+            state.for_each_account(&mut |hash, account| accounts.push((*hash, *account.balance(), *account.nonce())))
+        );
+        accounts.sort();
+        let mut expected: Vec<(H256, U256, U256)> =
+            vec![(blake256(&a), 69.into(), 0.into()), (blake256(&b), 0.into(), 1.into())];
+        expected.sort();
+        assert_eq!(expected, accounts);
+    }
+
     #[test]
     fn remove() {
         let a = Address::default();
@@ -1143,7 +1837,7 @@ mod tests_parcel {
 
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
 
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
 
         assert_eq!(Ok(ParcelInvoice::Multiple(vec![])), result);
         assert_eq!(Ok(15.into()), state.balance(&sender));
@@ -1189,7 +1883,7 @@ mod tests_parcel {
             },
         };
 
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
 
         assert_eq!(Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success])), result);
         assert_eq!(Ok(15.into()), state.balance(&sender));
@@ -1204,63 +1898,272 @@ mod tests_parcel {
     }
 
     #[test]
-    fn create_world_with_owners() {
+    fn create_world_in_two_shards_at_once() {
         let (sender, sender_public) = address();
 
         let network_id = "tc".into();
-        let shard_id = 0;
 
         let mut state = get_temp_state();
-        let users = vec![];
-        assert_eq!(Ok(()), state.create_shard_level_state(vec![sender], users.clone()));
+        assert_eq!(Ok(()), state.create_shard_level_state(vec![sender], vec![]));
+        assert_eq!(Ok(()), state.create_shard_level_state(vec![sender], vec![]));
         assert_eq!(Ok(()), state.commit());
 
-        let nonce = 0;
-        let owners = vec![Address::random(), Address::random()];
-
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
 
+        let owners = vec![Address::random()];
         let transaction = Transaction::CreateWorld {
             network_id,
-            shard_id,
-            nonce,
+            shard_id: 0,
+            nonce: 0,
             owners: owners.clone(),
         };
 
+        let changes: Vec<_> = (0..2)
+            .map(|shard_id| ShardChange {
+                shard_id,
+                pre_root: H256::zero(),
+                post_root: H256::zero(),
+            })
+            .collect();
+        assert!(can_apply_in_parallel(&changes));
+
         let parcel = Parcel {
             fee: 5.into(),
             nonce: 0.into(),
             network_id,
             action: Action::AssetTransactionGroup {
                 transactions: vec![transaction],
-                changes: vec![ShardChange {
-                    shard_id,
-                    pre_root: H256::zero(),
-                    post_root: H256::zero(),
-                }],
+                changes,
                 signatures: vec![],
             },
         };
 
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
 
         assert_eq!(Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success])), result);
-        assert_eq!(Ok(15.into()), state.balance(&sender));
-        assert_eq!(Ok(1.into()), state.nonce(&sender));
-
-        let metadata = state.shard_metadata(shard_id);
-        assert_eq!(Ok(Some(ShardMetadata::new_with_nonce(1, 1))), metadata);
-
-        let world_id = 0;
-        let world = state.world(shard_id, world_id);
-        assert_eq!(Ok(Some(World::new(owners, users))), world);
+        for shard_id in 0..2 {
+            assert_eq!(Ok(Some(ShardMetadata::new_with_nonce(1, 1))), state.shard_metadata(shard_id));
+            assert_eq!(Ok(Some(World::new(owners.clone(), vec![]))), state.world(shard_id, 0));
+        }
+        assert_eq!(Ok(()), state.commit());
+        assert_eq!(Ok(Some(World::new(owners, vec![]))), state.world(1, 0));
     }
 
     #[test]
-    fn apply_error_for_invalid_nonce() {
-        let mut state = get_temp_state();
-
-        let parcel = Parcel {
+    fn changes_of_the_same_shard_are_applied_in_order() {
+        let changes = vec![
+            ShardChange {
+                shard_id: 0,
+                pre_root: H256::zero(),
+                post_root: H256::zero(),
+            },
+            ShardChange {
+                shard_id: 0,
+                pre_root: H256::zero(),
+                post_root: H256::zero(),
+            },
+        ];
+        assert!(!can_apply_in_parallel(&changes));
+        assert!(!can_apply_in_parallel(&changes[..1]));
+    }
+
+    fn create_world(sender_nonce: u64, shard_id: ShardId, pre_root: H256) -> Parcel {
+        let network_id = "tc".into();
+        Parcel {
+            fee: 5.into(),
+            nonce: sender_nonce.into(),
+            network_id,
+            action: Action::AssetTransactionGroup {
+                transactions: vec![Transaction::CreateWorld {
+                    network_id,
+                    shard_id,
+                    nonce: 0,
+                    owners: vec![Address::random()],
+                }],
+                changes: vec![ShardChange {
+                    shard_id,
+                    pre_root,
+                    post_root: H256::zero(),
+                }],
+                signatures: vec![],
+            },
+        }
+    }
+
+    /// Applies the parcels one by one and at once on the same state, and compares the outcomes.
+    fn apply_at_once(senders: &[Address], parcels: &[(Parcel, Public)]) -> Vec<ParcelInvoice> {
+        let prepare = || {
+            let mut state = get_temp_state();
+            assert_eq!(Ok(()), state.create_shard_level_state(senders.to_vec(), vec![]));
+            assert_eq!(Ok(()), state.create_shard_level_state(senders.to_vec(), vec![]));
+            for sender in senders {
+                assert_eq!(Ok(()), state.add_balance(sender, &100.into()));
+            }
+            assert_eq!(Ok(()), state.commit());
+            state
+        };
+
+        let mut one_by_one = prepare();
+        let expected: Vec<_> = parcels
+            .iter()
+            .map(|(parcel, public)| one_by_one.apply(parcel, public, &0.into(), &0.into(), 0).unwrap())
+            .collect();
+
+        let mut at_once = prepare();
+        let parcels: Vec<_> = parcels.iter().map(|(parcel, public)| (parcel, *public)).collect();
+        let outcomes = at_once.apply_parcels(&parcels, &0.into(), &0.into(), 0).unwrap();
+        let invoices: Vec<_> = outcomes.into_iter().map(|(_, invoice)| invoice).collect();
+        assert_eq!(expected, invoices);
+
+        assert_eq!(Ok(()), one_by_one.commit());
+        assert_eq!(Ok(()), at_once.commit());
+        assert_eq!(one_by_one.root(), at_once.root());
+        invoices
+    }
+
+    #[test]
+    fn parcels_on_shards_are_applied_at_once_as_one_by_one() {
+        let (first, first_public) = address();
+        let (second, second_public) = address();
+
+        let parcels = vec![
+            (create_world(0, 0, H256::zero()), first_public),
+            (create_world(0, 1, H256::zero()), second_public),
+            (create_world(1, 0, H256::zero()), first_public),
+            (create_world(1, 1, H256::random()), second_public),
+            (
+                Parcel {
+                    fee: 5.into(),
+                    nonce: 2.into(),
+                    network_id: "tc".into(),
+                    action: Action::Payment {
+                        receiver: second,
+                        amount: 10.into(),
+                    },
+                },
+                first_public,
+            ),
+        ];
+        let invoices = apply_at_once(&[first, second], &parcels);
+        assert_eq!(ParcelInvoice::Multiple(vec![TransactionInvoice::Success]), invoices[0]);
+        assert_eq!(ParcelInvoice::Multiple(vec![TransactionInvoice::Success]), invoices[1]);
+        match &invoices[3] {
+            ParcelInvoice::SingleFail(ParcelError::InvalidShardRoot(_)) => {}
+            invoice => panic!("Unexpected invoice {:?}", invoice),
+        }
+        assert_eq!(ParcelInvoice::SingleSuccess, invoices[4]);
+    }
+
+    #[test]
+    fn groups_after_shard_creation_see_the_new_shard() {
+        let (sender, sender_public) = address();
+
+        let parcels = vec![
+            (create_world(0, 2, H256::zero()), sender_public),
+            (
+                Parcel {
+                    fee: 5.into(),
+                    nonce: 1.into(),
+                    network_id: "tc".into(),
+                    action: Action::CreateShard,
+                },
+                sender_public,
+            ),
+            (create_world(2, 2, H256::zero()), sender_public),
+            (create_world(3, 0, H256::zero()), sender_public),
+        ];
+        let invoices = apply_at_once(&[sender], &parcels);
+        assert_eq!(ParcelInvoice::SingleFail(ParcelError::InvalidShardId(2)), invoices[0]);
+        assert_eq!(ParcelInvoice::Multiple(vec![TransactionInvoice::Success]), invoices[2]);
+    }
+
+    #[test]
+    fn groups_are_connected_through_shared_shards() {
+        let groups: Vec<_> = [vec![0], vec![1], vec![2, 0], vec![3], vec![1, 3]]
+            .iter()
+            .enumerate()
+            .map(|(index, shards)| {
+                let changes = shards
+                    .iter()
+                    .map(|shard_id| ShardChange {
+                        shard_id: *shard_id,
+                        pre_root: H256::zero(),
+                        post_root: H256::zero(),
+                    })
+                    .collect::<Vec<_>>();
+                (index, changes)
+            })
+            .collect();
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|(index, changes)| DeferredGroup {
+                index: *index,
+                sender: Address::default(),
+                transactions: &[],
+                changes,
+            })
+            .collect();
+        assert_eq!(vec![vec![0, 2], vec![1, 3, 4]], connected_groups(&groups));
+    }
+
+    #[test]
+    fn create_world_with_owners() {
+        let (sender, sender_public) = address();
+
+        let network_id = "tc".into();
+        let shard_id = 0;
+
+        let mut state = get_temp_state();
+        let users = vec![];
+        assert_eq!(Ok(()), state.create_shard_level_state(vec![sender], users.clone()));
+        assert_eq!(Ok(()), state.commit());
+
+        let nonce = 0;
+        let owners = vec![Address::random(), Address::random()];
+
+        assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
+
+        let transaction = Transaction::CreateWorld {
+            network_id,
+            shard_id,
+            nonce,
+            owners: owners.clone(),
+        };
+
+        let parcel = Parcel {
+            fee: 5.into(),
+            nonce: 0.into(),
+            network_id,
+            action: Action::AssetTransactionGroup {
+                transactions: vec![transaction],
+                changes: vec![ShardChange {
+                    shard_id,
+                    pre_root: H256::zero(),
+                    post_root: H256::zero(),
+                }],
+                signatures: vec![],
+            },
+        };
+
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
+
+        assert_eq!(Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success])), result);
+        assert_eq!(Ok(15.into()), state.balance(&sender));
+        assert_eq!(Ok(1.into()), state.nonce(&sender));
+
+        let metadata = state.shard_metadata(shard_id);
+        assert_eq!(Ok(Some(ShardMetadata::new_with_nonce(1, 1))), metadata);
+
+        let world_id = 0;
+        let world = state.world(shard_id, world_id);
+        assert_eq!(Ok(Some(World::new(owners, users))), world);
+    }
+
+    #[test]
+    fn apply_error_for_invalid_nonce() {
+        let mut state = get_temp_state();
+
+        let parcel = Parcel {
             nonce: 2.into(),
             fee: 5.into(),
             network_id: "tc".into(),
@@ -1273,7 +2176,7 @@ mod tests_parcel {
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
 
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(
             Err(StateError::Parcel(ParcelError::InvalidNonce {
                 expected: 0.into(),
@@ -1302,7 +2205,7 @@ mod tests_parcel {
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &4.into()));
 
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(
             Err(StateError::Parcel(ParcelError::InsufficientBalance {
                 address: sender,
@@ -1332,13 +2235,352 @@ mod tests_parcel {
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
 
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
 
         assert_eq!(Ok(10.into()), state.balance(&receiver));
         assert_eq!(Ok(5.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
     }
 
+    #[test]
+    fn trace_payment() {
+        let mut state = get_temp_state();
+        state.start_tracing();
+        let receiver = 1u64.into();
+
+        let parcel = Parcel {
+            fee: 5.into(),
+            action: Action::Payment {
+                receiver,
+                amount: 10.into(),
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
+
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
+        assert_eq!(
+            vec![
+                Trace::PayFee {
+                    payer: sender,
+                    fee: 5.into(),
+                    nonce: 1.into(),
+                },
+                Trace::Transfer {
+                    sender,
+                    receiver,
+                    amount: 10.into(),
+                },
+            ],
+            state.take_trace()
+        );
+        assert_eq!(Vec::<Trace>::new(), state.take_trace());
+    }
+
+    #[test]
+    fn trace_failed_payment() {
+        let mut state = get_temp_state();
+        state.start_tracing();
+        let receiver = 1u64.into();
+
+        let parcel = Parcel {
+            fee: 5.into(),
+            action: Action::Payment {
+                receiver,
+                amount: 10.into(),
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &100.into()));
+
+        let error = ParcelError::BelowMinBalance {
+            address: receiver,
+            balance: 10.into(),
+            min_balance: 20.into(),
+        };
+        assert_eq!(
+            Ok(ParcelInvoice::SingleFail(error.clone())),
+            state.apply(&parcel, &sender_public, &20.into(), &0.into(), 0)
+        );
+        assert_eq!(
+            vec![
+                Trace::PayFee {
+                    payer: sender,
+                    fee: 5.into(),
+                    nonce: 1.into(),
+                },
+                Trace::Transfer {
+                    sender,
+                    receiver,
+                    amount: 10.into(),
+                },
+                Trace::RevertAction {
+                    error,
+                },
+            ],
+            state.take_trace()
+        );
+    }
+
+    #[test]
+    fn nothing_is_traced_unless_started() {
+        let mut state = get_temp_state();
+        let parcel = Parcel {
+            fee: 5.into(),
+            action: Action::Payment {
+                receiver: 1u64.into(),
+                amount: 10.into(),
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
+
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
+        assert_eq!(Vec::<Trace>::new(), state.take_trace());
+    }
+
+    #[test]
+    fn payment_cannot_create_account_below_min_balance() {
+        let mut state = get_temp_state();
+        let receiver = 1u64.into();
+
+        let parcel = Parcel {
+            fee: 5.into(),
+            action: Action::Payment {
+                receiver,
+                amount: 10.into(),
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &100.into()));
+
+        assert_eq!(
+            Ok(ParcelInvoice::SingleFail(ParcelError::BelowMinBalance {
+                address: receiver,
+                balance: 10.into(),
+                min_balance: 20.into(),
+            })),
+            state.apply(&parcel, &sender_public, &20.into(), &0.into(), 0)
+        );
+
+        assert_eq!(Ok(false), state.account_exists(&receiver));
+        assert_eq!(Ok(95.into()), state.balance(&sender));
+        assert_eq!(Ok(1.into()), state.nonce(&sender));
+    }
+
+    #[test]
+    fn fee_payer_below_min_balance_is_reaped() {
+        let mut state = get_temp_state();
+        let receiver = 1u64.into();
+
+        let parcel = Parcel {
+            fee: 5.into(),
+            action: Action::Payment {
+                receiver,
+                amount: 30.into(),
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &50.into()));
+
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &20.into(), &0.into(), 0));
+
+        assert_eq!(Ok(30.into()), state.balance(&receiver));
+        assert_eq!(Ok(0.into()), state.balance(&sender));
+        assert_eq!(Ok(1.into()), state.nonce(&sender));
+    }
+
+    #[test]
+    fn parcel_of_reaped_account_cannot_be_replayed() {
+        let mut state = get_temp_state();
+        let receiver = 1u64.into();
+
+        let parcel = Parcel {
+            fee: 5.into(),
+            action: Action::Payment {
+                receiver,
+                amount: 30.into(),
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &50.into()));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &20.into(), &0.into(), 0));
+
+        assert_eq!(Ok(()), state.add_balance(&sender, &50.into()));
+        assert_eq!(
+            Err(StateError::Parcel(ParcelError::InvalidNonce {
+                expected: 1.into(),
+                got: 0.into(),
+            })),
+            state.apply(&parcel, &sender_public, &20.into(), &0.into(), 0)
+        );
+        assert_eq!(Ok(50.into()), state.balance(&sender));
+    }
+
+    #[test]
+    fn register_update_and_transfer_name() {
+        let mut state = get_temp_state();
+        let (sender, sender_public) = address();
+        let (receiver, receiver_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
+        assert_eq!(Ok(()), state.add_balance(&receiver, &20.into()));
+
+        let register = Parcel {
+            fee: 5.into(),
+            action: Action::RegisterName {
+                name: "codechain".to_string(),
+                value: vec![1],
+                duration: 10,
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&register, &sender_public, &0.into(), &0.into(), 5));
+        assert_eq!(Ok(Some(NameRecord::new(sender, vec![1], 15))), state.name_record("codechain"));
+
+        let update = Parcel {
+            fee: 5.into(),
+            action: Action::UpdateName {
+                name: "codechain".to_string(),
+                value: vec![2],
+            },
+            nonce: 0.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(
+            Ok(ParcelInvoice::SingleFail(ParcelError::InsufficientPermission)),
+            state.apply(&update, &receiver_public, &0.into(), &0.into(), 6)
+        );
+
+        let transfer = Parcel {
+            fee: 5.into(),
+            action: Action::TransferName {
+                name: "codechain".to_string(),
+                receiver,
+            },
+            nonce: 1.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&transfer, &sender_public, &0.into(), &0.into(), 7));
+
+        let update = Parcel {
+            nonce: 1.into(),
+            ..update
+        };
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&update, &receiver_public, &0.into(), &0.into(), 8));
+        assert_eq!(Ok(Some(NameRecord::new(receiver, vec![2], 15))), state.name_record("codechain"));
+    }
+
+    #[test]
+    fn expired_name_can_be_registered_by_another_account() {
+        let mut state = get_temp_state();
+        let (sender, sender_public) = address();
+        let (other, other_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
+        assert_eq!(Ok(()), state.add_balance(&other, &20.into()));
+
+        let register = |nonce: u64| Parcel {
+            fee: 5.into(),
+            action: Action::RegisterName {
+                name: "codechain".to_string(),
+                value: vec![],
+                duration: 10,
+            },
+            nonce: nonce.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(
+            Ok(ParcelInvoice::SingleSuccess),
+            state.apply(&register(0), &sender_public, &0.into(), &0.into(), 0)
+        );
+        assert_eq!(
+            Ok(ParcelInvoice::SingleFail(ParcelError::NameAlreadyRegistered("codechain".to_string()))),
+            state.apply(&register(0), &other_public, &0.into(), &0.into(), 9)
+        );
+        assert_eq!(
+            Ok(ParcelInvoice::SingleSuccess),
+            state.apply(&register(1), &other_public, &0.into(), &0.into(), 10)
+        );
+        assert_eq!(Ok(Some(NameRecord::new(other, vec![], 20))), state.name_record("codechain"));
+    }
+
+    #[test]
+    fn name_fee_is_burned() {
+        let mut state = get_temp_state();
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &50.into()));
+
+        let register = |nonce: u64, duration: BlockNumber| Parcel {
+            fee: 5.into(),
+            action: Action::RegisterName {
+                name: "codechain".to_string(),
+                value: vec![],
+                duration,
+            },
+            nonce: nonce.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(
+            Ok(ParcelInvoice::SingleSuccess),
+            state.apply(&register(0, 10), &sender_public, &0.into(), &2.into(), 0)
+        );
+        assert_eq!(Ok(25.into()), state.balance(&sender));
+        assert_eq!(
+            Ok(ParcelInvoice::SingleFail(ParcelError::InsufficientBalance {
+                address: sender,
+                cost: 40.into(),
+                balance: 20.into(),
+            })),
+            state.apply(&register(1, 20), &sender_public, &0.into(), &2.into(), 0)
+        );
+        assert_eq!(Ok(Some(NameRecord::new(sender, vec![], 10))), state.name_record("codechain"));
+    }
+
+    #[test]
+    fn name_cannot_be_registered_too_long() {
+        let mut state = get_temp_state();
+        let (sender, sender_public) = address();
+        assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
+
+        let register = |nonce: u64, duration: BlockNumber| Parcel {
+            fee: 5.into(),
+            action: Action::RegisterName {
+                name: "codechain".to_string(),
+                value: vec![],
+                duration,
+            },
+            nonce: nonce.into(),
+            network_id: "tc".into(),
+        };
+        assert_eq!(
+            Ok(ParcelInvoice::SingleSuccess),
+            state.apply(&register(0, MAX_NAME_DURATION), &sender_public, &0.into(), &0.into(), 0)
+        );
+        assert_eq!(
+            Ok(ParcelInvoice::SingleFail(ParcelError::NameDurationTooLong {
+                max: MAX_NAME_DURATION,
+                got: MAX_NAME_DURATION + 1,
+            })),
+            state.apply(&register(1, 2), &sender_public, &0.into(), &0.into(), 1)
+        );
+        assert_eq!(
+            Ok(ParcelInvoice::SingleSuccess),
+            state.apply(&register(2, 1), &sender_public, &0.into(), &0.into(), 1)
+        );
+    }
+
     #[test]
     fn apply_set_regular_key() {
         let mut state = get_temp_state();
@@ -1356,7 +2598,7 @@ mod tests_parcel {
         assert_eq!(Ok(()), state.add_balance(&sender, &5.into()));
 
         assert_eq!(state.regular_key(&sender), Ok(None));
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
         assert_eq!(Ok(Some(key)), state.regular_key(&sender));
     }
 
@@ -1378,7 +2620,7 @@ mod tests_parcel {
         assert_eq!(Ok(()), state.add_balance(&sender, &15.into()));
 
         assert_eq!(state.regular_key(&sender), Ok(None));
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
         assert_eq!(Ok(Some(*key)), state.regular_key(&sender));
 
         let parcel = Parcel {
@@ -1388,7 +2630,10 @@ mod tests_parcel {
             network_id: "tc".into(),
         };
 
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, regular_keypair.public()));
+        assert_eq!(
+            Ok(ParcelInvoice::SingleSuccess),
+            state.apply(&parcel, regular_keypair.public(), &0.into(), &0.into(), 0)
+        );
         assert_eq!(Ok(4.into()), state.balance(&sender));
         assert_eq!(Ok(Some(vec![sender])), state.shard_owners(0));
     }
@@ -1411,7 +2656,7 @@ mod tests_parcel {
         assert_eq!(Ok(()), state.add_balance(&sender, &15.into()));
 
         assert_eq!(state.regular_key(&sender), Ok(None));
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
         assert_eq!(Ok(Some(*key)), state.regular_key(&sender));
 
         let parcel = Parcel {
@@ -1425,7 +2670,7 @@ mod tests_parcel {
         let (sender2, sender_public2) = address();
         assert_eq!(Ok(()), state.add_balance(&sender2, &15.into()));
 
-        let result = state.apply(&parcel, &sender_public2);
+        let result = state.apply(&parcel, &sender_public2, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleFail(ParcelError::RegularKeyAlreadyInUse)), result);
         assert_eq!(Ok(10.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -1451,7 +2696,7 @@ mod tests_parcel {
             network_id: "tc".into(),
         };
 
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleFail(ParcelError::RegularKeyAlreadyInUseAsPlatformAccount)), result);
         assert_eq!(Ok(15.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -1479,7 +2724,7 @@ mod tests_parcel {
 
         assert_eq!(Some(regular_public), state.regular_key(&sender).unwrap());
         assert_eq!(Ok(true), state.regular_account_exists_and_not_null(&regular_public));
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &regular_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &regular_public, &0.into(), &0.into(), 0));
         assert_eq!(Ok(false), state.regular_account_exists_and_not_null(&regular_public));
         assert_eq!(Some(regular_public2), state.regular_key(&sender).unwrap());
     }
@@ -1569,7 +2814,7 @@ mod tests_parcel {
                 TransactionInvoice::Success,
                 TransactionInvoice::Success,
             ])),
-            state.apply(&parcel, &regular_public)
+            state.apply(&parcel, &regular_public, &0.into(), &0.into(), 0)
         );
     }
 
@@ -1594,7 +2839,7 @@ mod tests_parcel {
             nonce: 0.into(),
             network_id: "tc".into(),
         };
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &regular_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &regular_public, &0.into(), &0.into(), 0));
         assert_eq!(Ok(14.into()), state.balance(&regular_address));
         assert_eq!(Ok(20.into()), state.balance(&sender));
         assert_eq!(Ok(Some(vec![regular_address])), state.shard_owners(0));
@@ -1619,7 +2864,7 @@ mod tests_parcel {
             nonce: 0.into(),
             network_id: "tc".into(),
         };
-        let result = state.apply(&parcel, &sender_public);
+        let result = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleFail(ParcelError::InvalidTransferDestination)), result);
         assert_eq!(Ok(15.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -1648,7 +2893,7 @@ mod tests_parcel {
                 balance: 15.into(),
                 cost: 30.into(),
             })),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(0.into()), state.balance(&receiver));
@@ -1713,7 +2958,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success, TransactionInvoice::Success])),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(state.balance(&sender), Ok(58.into()));
@@ -1784,7 +3029,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success, TransactionInvoice::Success])),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(state.balance(&sender), Ok(64.into()));
@@ -1906,7 +3151,7 @@ mod tests_parcel {
                 TransactionInvoice::Success,
                 TransactionInvoice::Success,
             ]),
-            state.apply(&parcel, &sender_public).unwrap()
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0).unwrap()
         );
 
         assert_eq!(state.balance(&sender), Ok(100.into()));
@@ -1988,7 +3233,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success, TransactionInvoice::Success])),
-            state.apply(&mint_parcel, &sender_public)
+            state.apply(&mint_parcel, &sender_public, &0.into(), &0.into(), 0)
         );
         assert_eq!(state.balance(&sender), Ok(100.into()));
         assert_eq!(state.nonce(&sender), Ok(1.into()));
@@ -2057,7 +3302,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success])),
-            state.apply(&transfer_parcel, &sender_public)
+            state.apply(&transfer_parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(state.balance(&sender), Ok(70.into()));
@@ -2123,7 +3368,7 @@ mod tests_parcel {
         };
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
-        let res = state.apply(&parcel, &sender_public);
+        let res = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleSuccess), res);
         assert_eq!(Ok(14.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2144,7 +3389,7 @@ mod tests_parcel {
         };
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
-        let res = state.apply(&parcel, &sender_public);
+        let res = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleSuccess), res);
         assert_eq!(Ok(14.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2166,7 +3411,7 @@ mod tests_parcel {
         };
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &20.into()));
-        let res = state.apply(&parcel, &sender_public);
+        let res = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleSuccess), res);
         assert_eq!(Ok(14.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2223,7 +3468,7 @@ mod tests_parcel {
 
         assert_eq!(Ok(()), state.add_balance(&sender, &U256::from(69u64)));
 
-        let res = state.apply(&parcel, &sender_public);
+        let res = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleFail(ParcelError::InvalidShardId(0))), res);
         assert_eq!(Ok(58.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2292,7 +3537,7 @@ mod tests_parcel {
         let (sender, sender_public) = address();
         assert_eq!(Ok(()), state.add_balance(&sender, &U256::from(120)));
 
-        let res = state.apply(&parcel, &sender_public);
+        let res = state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0);
         assert_eq!(Ok(ParcelInvoice::SingleFail(ParcelError::InvalidShardId(100))), res);
         assert_eq!(Ok(90.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2348,7 +3593,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success, TransactionInvoice::Success])),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(100.into()), state.balance(&sender));
@@ -2400,7 +3645,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success])),
-            state.apply(&parcel0, &sender_public)
+            state.apply(&parcel0, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(100.into()), state.balance(&sender));
@@ -2432,7 +3677,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::Multiple(vec![TransactionInvoice::Success])),
-            state.apply(&parcel1, &sender_public)
+            state.apply(&parcel1, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(70.into()), state.balance(&sender));
@@ -2465,7 +3710,7 @@ mod tests_parcel {
 
         assert_eq!(Ok(Some(vec![sender])), state.shard_owners(shard_id));
 
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2513,7 +3758,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::SingleFail(ParcelError::NewOwnersMustContainSender)),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
@@ -2564,7 +3809,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::SingleFail(ParcelError::InsufficientPermission)),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
@@ -2602,7 +3847,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::SingleFail(ParcelError::InvalidShardId(shard_id))),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
@@ -2654,7 +3899,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::SingleFail(ParcelError::InsufficientPermission)),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
@@ -2724,7 +3969,7 @@ mod tests_parcel {
 
         assert_eq!(
             ParcelInvoice::Multiple(vec![TransactionInvoice::Success, TransactionInvoice::Success]),
-            state.apply(&parcel, &sender_public).unwrap()
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0).unwrap()
         );
 
         assert_eq!(Ok(0x31.into()), state.balance(&sender));
@@ -2766,7 +4011,7 @@ mod tests_parcel {
             network_id,
         };
 
-        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public));
+        assert_eq!(Ok(ParcelInvoice::SingleSuccess), state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0));
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
         assert_eq!(Ok(1.into()), state.nonce(&sender));
@@ -2806,7 +4051,7 @@ mod tests_parcel {
 
         assert_eq!(
             Ok(ParcelInvoice::SingleFail(ParcelError::InsufficientPermission)),
-            state.apply(&parcel, &sender_public)
+            state.apply(&parcel, &sender_public, &0.into(), &0.into(), 0)
         );
 
         assert_eq!(Ok(64.into()), state.balance(&sender));
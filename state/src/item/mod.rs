@@ -23,16 +23,18 @@ pub mod asset;
 pub mod asset_scheme;
 pub mod local_cache;
 pub mod metadata;
+pub mod name_record;
 pub mod regular_account;
 pub mod shard;
 pub mod shard_metadata;
 pub mod world;
 
-const OWNED_ASSET_PREFIX: u8 = 'A' as u8;
-const ADDRESS_PREFIX: u8 = 'C' as u8;
+pub(crate) const OWNED_ASSET_PREFIX: u8 = 'A' as u8;
+pub(crate) const ADDRESS_PREFIX: u8 = 'C' as u8;
 const SHARD_METADATA_PREFIX: u8 = 'E' as u8;
 const SHARD_PREFIX: u8 = 'H' as u8;
 const METADATA_PREFIX: u8 = 'M' as u8;
+const NAME_RECORD_PREFIX: u8 = 'N' as u8;
 const REGULAR_ACCOUNT_PREFIX: u8 = 'R' as u8;
 const ASSET_SCHEME_PREFIX: u8 = 'S' as u8;
 const WORLD_PREFIX: u8 = 'W' as u8;
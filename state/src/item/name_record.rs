@@ -0,0 +1,136 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ccrypto::blake256;
+use ckey::Address;
+use ctypes::BlockNumber;
+use primitives::{Bytes, H256};
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+/// The longest a name can be registered for from the current block.
+/// About a year of 15-second blocks.
+pub const MAX_NAME_DURATION: BlockNumber = 2_102_400;
+
+/// The value registered to a name. The name is free again after it expires.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NameRecord {
+    owner: Address,
+    value: Bytes,
+    expiration: BlockNumber,
+}
+
+impl NameRecord {
+    pub fn new(owner: Address, value: Bytes, expiration: BlockNumber) -> Self {
+        Self {
+            owner,
+            value,
+            expiration,
+        }
+    }
+
+    pub fn owner(&self) -> &Address {
+        &self.owner
+    }
+
+    pub fn set_owner(&mut self, owner: Address) {
+        self.owner = owner;
+    }
+
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    pub fn set_value(&mut self, value: Bytes) {
+        self.value = value;
+    }
+
+    /// The first block at which the name is not registered anymore.
+    pub fn expiration(&self) -> BlockNumber {
+        self.expiration
+    }
+
+    pub fn set_expiration(&mut self, expiration: BlockNumber) {
+        self.expiration = expiration;
+    }
+
+    pub fn is_expired(&self, block_number: BlockNumber) -> bool {
+        self.expiration <= block_number
+    }
+}
+
+const PREFIX: u8 = super::NAME_RECORD_PREFIX;
+
+impl Encodable for NameRecord {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4).append(&PREFIX).append(&self.owner).append(&self.value).append(&self.expiration);
+    }
+}
+
+impl Decodable for NameRecord {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpInvalidLength)
+        }
+        let prefix = rlp.val_at::<u8>(0)?;
+        if PREFIX != prefix {
+            cdebug!(STATE, "{} is not an expected prefix for name record", prefix);
+            return Err(DecoderError::Custom("Unexpected prefix"))
+        }
+        Ok(Self {
+            owner: rlp.val_at(1)?,
+            value: rlp.val_at(2)?,
+            expiration: rlp.val_at(3)?,
+        })
+    }
+}
+
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NameRecordAddress(H256);
+
+impl_address!(TOP, NameRecordAddress, PREFIX);
+
+impl NameRecordAddress {
+    pub fn new(name: &str) -> Self {
+        Self::from_transaction_hash(blake256(name.as_bytes()), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn different_names_make_different_addresses() {
+        let address1 = NameRecordAddress::new("codechain");
+        let address2 = NameRecordAddress::new("codechain2");
+        assert_ne!(address1, address2);
+        assert_eq!(address1[0], PREFIX);
+        assert_eq!(address2[0], PREFIX);
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let record = NameRecord::new(Address::random(), vec![1, 2, 3], 100);
+        assert_eq!(record, ::rlp::decode(&record.rlp_bytes()));
+    }
+
+    #[test]
+    fn expires_at_the_expiration() {
+        let record = NameRecord::new(Address::random(), vec![], 100);
+        assert!(!record.is_expired(99));
+        assert!(record.is_expired(100));
+    }
+}
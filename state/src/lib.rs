@@ -31,8 +31,10 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate lru_cache;
+extern crate memorydb;
 extern crate parking_lot;
 extern crate primitives;
+extern crate rayon;
 extern crate rlp;
 #[cfg(test)]
 extern crate rustc_hex;
@@ -47,6 +49,7 @@ mod db;
 mod error;
 mod impls;
 mod item;
+mod trace;
 mod traits;
 
 #[cfg(test)]
@@ -64,10 +67,12 @@ pub use item::asset::{OwnedAsset, OwnedAssetAddress};
 pub use item::asset_scheme::{AssetScheme, AssetSchemeAddress};
 pub use item::local_cache::{CacheableItem, LocalCache};
 pub use item::metadata::{Metadata, MetadataAddress};
+pub use item::name_record::{NameRecord, NameRecordAddress};
 pub use item::regular_account::{RegularAccount, RegularAccountAddress};
 pub use item::shard::{Shard, ShardAddress};
 pub use item::shard_metadata::{ShardMetadata, ShardMetadataAddress};
 pub use item::world::{World, WorldAddress};
+pub use trace::Trace;
 pub use traits::{ShardState, ShardStateInfo, StateWithCache, TopState, TopStateInfo};
 
 pub type StateResult<T> = Result<T, StateError>;
@@ -0,0 +1,78 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use ckey::{Address, Public};
+use ctypes::invoice::TransactionInvoice;
+use ctypes::parcel::Error as ParcelError;
+use ctypes::{BlockNumber, ShardId};
+use primitives::{H256, U256};
+
+/// An effect of a parcel on the state, recorded while the parcel is executed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", content = "content")]
+pub enum Trace {
+    /// The fee is charged and the nonce of the payer is increased to `nonce`.
+    PayFee {
+        payer: Address,
+        fee: U256,
+        nonce: U256,
+    },
+    Transfer {
+        sender: Address,
+        receiver: Address,
+        amount: U256,
+    },
+    SetRegularKey {
+        owner: Public,
+        key: Public,
+    },
+    CreateShard {
+        shard: ShardId,
+        owners: Vec<Address>,
+    },
+    SetShardOwners {
+        shard: ShardId,
+        owners: Vec<Address>,
+    },
+    SetShardUsers {
+        shard: ShardId,
+        users: Vec<Address>,
+    },
+    /// The transactions are applied to the shard, whose root becomes `root`.
+    ApplyTransactions {
+        shard: ShardId,
+        root: H256,
+        invoices: Vec<TransactionInvoice>,
+    },
+    /// The name is registered to `owner` until `expiration`.
+    SetName {
+        name: String,
+        owner: Address,
+        expiration: BlockNumber,
+    },
+    /// The action is handled by a custom handler.
+    Custom,
+    /// The action failed. The effects recorded after `PayFee` stay in the trace, but they are undone in the state.
+    RevertAction {
+        error: ParcelError,
+    },
+    /// The balance of the account is burnt because it fell below the minimum. The nonce is kept.
+    Reap {
+        address: Address,
+        balance: U256,
+    },
+}
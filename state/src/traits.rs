@@ -18,11 +18,14 @@ use ckey::{Address, Public};
 use cmerkle::Result as TrieResult;
 use ctypes::invoice::TransactionInvoice;
 use ctypes::transaction::Transaction;
-use ctypes::{ShardId, WorldId};
+use ctypes::{BlockNumber, ShardId, WorldId};
 use primitives::{Bytes, H256, U256};
 
 use super::backend::{ShardBackend, TopBackend};
-use super::{AssetScheme, AssetSchemeAddress, OwnedAsset, OwnedAssetAddress, ShardMetadata, StateResult, World};
+use super::{
+    Account, AssetScheme, AssetSchemeAddress, NameRecord, OwnedAsset, OwnedAssetAddress, ShardMetadata, StateResult,
+    World,
+};
 
 
 pub trait TopStateInfo {
@@ -52,6 +55,20 @@ pub trait TopStateInfo {
     fn asset(&self, shard_id: ShardId, a: &OwnedAssetAddress) -> TrieResult<Option<OwnedAsset>>;
 
     fn action_data(&self, key: &H256) -> TrieResult<Bytes>;
+
+    /// Get the record of the name. It may be expired.
+    fn name_record(&self, name: &str) -> TrieResult<Option<NameRecord>>;
+
+    /// Get the nodes of the state trie on the path to account `a`.
+    /// They prove the account, or its absence, against the state root.
+    fn account_proof(&self, a: &Address) -> TrieResult<Vec<Bytes>>;
+
+    /// Calls `f` with every account in the state trie.
+    /// Only the hash of the address is in the trie, so the account is given with the hash instead of the address.
+    fn for_each_account(&self, f: &mut FnMut(&H256, Account)) -> TrieResult<()>;
+
+    /// Calls `f` with every asset in the shard, given with the hash of its address.
+    fn for_each_asset(&self, shard_id: ShardId, f: &mut FnMut(&H256, OwnedAsset)) -> TrieResult<()>;
 }
 
 pub trait ShardStateInfo {
@@ -115,6 +132,30 @@ where
     fn set_shard_users(&mut self, shard_id: ShardId, new_users: Vec<Address>) -> StateResult<()>;
 
     fn update_action_data(&mut self, key: &H256, data: Bytes) -> StateResult<()>;
+
+    /// Register the name to `sender`, or extend the registration if `sender` already owns it.
+    fn register_name(
+        &mut self,
+        name: &str,
+        value: &Bytes,
+        duration: BlockNumber,
+        sender: &Address,
+        block_number: BlockNumber,
+    ) -> StateResult<()>;
+    fn update_name(
+        &mut self,
+        name: &str,
+        value: &Bytes,
+        sender: &Address,
+        block_number: BlockNumber,
+    ) -> StateResult<()>;
+    fn transfer_name(
+        &mut self,
+        name: &str,
+        receiver: &Address,
+        sender: &Address,
+        block_number: BlockNumber,
+    ) -> StateResult<()>;
 }
 
 pub trait StateWithCache {
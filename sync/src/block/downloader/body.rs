@@ -17,6 +17,7 @@
 use std::collections::{HashMap, HashSet};
 
 use ccore::{Header, UnverifiedParcel};
+use ctypes::BlockNumber;
 use primitives::H256;
 
 use super::super::message::RequestMessage;
@@ -24,6 +25,7 @@ use super::super::message::RequestMessage;
 #[derive(Clone)]
 struct Target {
     hash: H256,
+    number: BlockNumber,
     parent_hash: H256,
     parcels_root: H256,
     parent_root: H256,
@@ -44,10 +46,14 @@ impl BodyDownloader {
         }
     }
 
-    pub fn create_request(&mut self) -> Option<RequestMessage> {
+    /// Creates a request for the bodies which the peer keeps, which are the ones from `earliest_body_number`.
+    pub fn create_request(&mut self, earliest_body_number: BlockNumber) -> Option<RequestMessage> {
         const MAX_BODY_REQEUST_LENGTH: usize = 128;
         let mut hashes = Vec::new();
         for t in &self.targets {
+            if t.number < earliest_body_number {
+                continue
+            }
             if !self.downloading.contains(&t.hash) && !self.downloaded.contains_key(&t.hash) {
                 hashes.push(t.hash);
             }
@@ -81,6 +87,7 @@ impl BodyDownloader {
         ctrace!(SYNC, "Add download target: {}", header.hash());
         self.targets.push(Target {
             hash: header.hash(),
+            number: header.number(),
             parent_hash: parent.hash(),
             parcels_root: *header.parcels_root(),
             parent_root: *parent.parcels_root(),
@@ -120,3 +127,27 @@ impl BodyDownloader {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_pruned_by_peer_are_not_requested() {
+        let mut headers = vec![Header::new()];
+        for number in 1..4 {
+            let mut header = Header::new();
+            header.set_parent_hash(headers[number - 1].hash());
+            header.set_number(number as BlockNumber);
+            headers.push(header);
+        }
+        let mut downloader = BodyDownloader::new();
+        for neighbors in headers.windows(2) {
+            downloader.add_target(&neighbors[1], &neighbors[0]);
+        }
+
+        let expected = RequestMessage::Bodies(vec![headers[2].hash(), headers[3].hash()]);
+        assert_eq!(Some(expected), downloader.create_request(2));
+        assert_eq!(None, downloader.create_request(2));
+    }
+}
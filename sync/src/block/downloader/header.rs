@@ -20,6 +20,7 @@ use std::time::Instant;
 
 use ccore::encoded::Header;
 use ccore::{BlockChainClient, BlockId};
+use ctypes::BlockNumber;
 use primitives::{H256, U256};
 
 use super::super::message::RequestMessage;
@@ -31,6 +32,7 @@ const MAX_WAIT: u64 = 15;
 #[derive(Clone)]
 struct Pivot {
     hash: H256,
+    number: BlockNumber,
     total_score: U256,
 }
 
@@ -53,8 +55,13 @@ impl HeaderDownloader {
         self.total_score
     }
 
+    /// The number of the highest header downloaded from the peer.
+    pub fn best_number(&self) -> BlockNumber {
+        self.pivot.number
+    }
+
     pub fn new(client: Arc<BlockChainClient>, total_score: U256, best_hash: H256) -> Self {
-        let best_header_hash = client.best_block_header().hash();
+        let best_header = client.best_block_header();
         let best_score = client.block_total_score(BlockId::Latest).expect("Best block always exist");
 
         Self {
@@ -64,7 +71,8 @@ impl HeaderDownloader {
             best_hash,
 
             pivot: Pivot {
-                hash: best_header_hash,
+                hash: best_header.hash(),
+                number: best_header.number(),
                 total_score: best_score,
             },
             request_time: None,
@@ -127,14 +135,17 @@ impl HeaderDownloader {
 
             // FIXME: skip known headers
             let new_scores = headers[1..].iter().fold(U256::zero(), |acc, header| acc + header.score());
+            let last = headers.last().expect("Last downloaded header must exist");
             self.pivot = Pivot {
-                hash: headers.last().expect("Last downloaded header must exist").hash(),
+                hash: last.hash(),
+                number: last.number(),
                 total_score: self.pivot.total_score + new_scores,
             }
         } else {
             let pivot_header = self.pivot_header();
             self.pivot = Pivot {
                 hash: pivot_header.parent_hash(),
+                number: pivot_header.number().saturating_sub(1),
                 total_score: self.pivot.total_score - pivot_header.score(),
             }
         }
@@ -153,3 +164,29 @@ impl HeaderDownloader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ccore::{Header as BlockHeader, TestBlockChainClient};
+    use rlp::Encodable;
+
+    use super::*;
+
+    #[test]
+    fn best_number_follows_the_downloaded_headers() {
+        let client = Arc::new(TestBlockChainClient::new());
+        let mut headers: Vec<BlockHeader> = vec![client.best_block_header().decode()];
+        for number in 1..4 {
+            let mut header = BlockHeader::new();
+            header.set_parent_hash(headers[number - 1].hash());
+            header.set_number(number as BlockNumber);
+            header.set_score(1.into());
+            headers.push(header);
+        }
+        let mut downloader = HeaderDownloader::new(client, 10.into(), headers[3].hash());
+        assert_eq!(0, downloader.best_number());
+
+        downloader.import_headers(headers.iter().map(|header| Header::new(header.rlp_bytes().to_vec())).collect());
+        assert_eq!(3, downloader.best_number());
+    }
+}
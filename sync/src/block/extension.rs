@@ -14,14 +14,16 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use ccore::encoded::Header as EncodedHeader;
 use ccore::{
-    Block, BlockChainClient, BlockId, BlockImportError, BlockInfo, ChainInfo, ChainNotify, Client, Header, ImportBlock,
-    ImportError, Seal, UnverifiedParcel,
+    Block, BlockChainClient, BlockError, BlockId, BlockImportError, BlockInfo, ChainInfo, ChainNotify, Client, Header,
+    ImportBlock, ImportError, Seal, SyncProgress, UnverifiedParcel,
 };
 use cnetwork::{Api, NetworkExtension, NodeId, TimerToken};
 use ctoken_generator::TokenGenerator;
@@ -42,10 +44,22 @@ const SYNC_EXPIRE_TOKEN_LIMIT: usize = 1000;
 const SYNC_EXPIRE_TOKEN_END: TimerToken = SYNC_EXPIRE_TOKEN_BEGIN + SYNC_EXPIRE_TOKEN_LIMIT;
 
 const SYNC_TIMER_INTERVAL: i64 = 1000;
+
+/// The version from which the status has the earliest body number.
+const EARLIEST_BODY_VERSION: u64 = 1;
+/// The version from which the headers have the log bloom and the shard roots.
+/// The headers of the older versions cannot be decoded.
+const SHARD_ROOTS_VERSION: u64 = 3;
 const SYNC_EXPIRE_REQUEST_INTERVAL: i64 = 15000;
 
 const SNAPSHOT_PERIOD: u64 = (1 << 14);
 
+/// The import speed is measured over this many ticks of the sync timer.
+const MAX_PROGRESS_SAMPLES: usize = 30;
+
+/// A bodies response carries up to 128 block bodies.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
 #[derive(Debug, PartialEq)]
 pub struct TokenInfo {
     node_id: NodeId,
@@ -55,6 +69,10 @@ pub struct TokenInfo {
 pub struct Extension {
     requests: RwLock<HashMap<NodeId, Vec<(u64, RequestMessage)>>>,
     header_downloaders: RwLock<HashMap<NodeId, HeaderDownloader>>,
+    /// Peers which sent headers that contradict the checkpoints of the chain scheme or revert the finality.
+    rejected_peers: RwLock<HashSet<NodeId>>,
+    /// The number of the earliest block whose body each peer keeps.
+    earliest_body_numbers: RwLock<HashMap<NodeId, BlockNumber>>,
     body_downloader: Mutex<BodyDownloader>,
     tokens: RwLock<HashMap<NodeId, TimerToken>>,
     tokens_info: RwLock<HashMap<TimerToken, TokenInfo>>,
@@ -62,13 +80,20 @@ pub struct Extension {
     client: Arc<Client>,
     api: RwLock<Option<Arc<Api>>>,
     last_request: AtomicUsize,
+    /// The best block number when the extension started
+    starting_block: BlockNumber,
+    /// The best block numbers at the recent ticks of the sync timer
+    progress_samples: Mutex<VecDeque<(Instant, BlockNumber)>>,
 }
 
 impl Extension {
     pub fn new(client: Arc<Client>) -> Arc<Self> {
+        let starting_block = client.chain_info().best_block_number;
         Arc::new(Self {
             requests: RwLock::new(HashMap::new()),
             header_downloaders: RwLock::new(HashMap::new()),
+            rejected_peers: RwLock::new(HashSet::new()),
+            earliest_body_numbers: RwLock::new(HashMap::new()),
             body_downloader: Mutex::new(BodyDownloader::new()),
             tokens: RwLock::new(HashMap::new()),
             tokens_info: RwLock::new(HashMap::new()),
@@ -76,12 +101,37 @@ impl Extension {
             client,
             api: RwLock::new(None),
             last_request: AtomicUsize::new(0),
+            starting_block,
+            progress_samples: Mutex::new(VecDeque::new()),
         })
     }
 
+    /// Returns the connected peers in ascending order of their total score. Peers which have not sent a valid status
+    /// yet are regarded as having zero score.
+    pub fn peers_by_score(&self) -> Vec<(NodeId, U256)> {
+        let requests = self.requests.read();
+        let header_downloaders = self.header_downloaders.read();
+        let mut peers: Vec<_> = requests
+            .keys()
+            .map(|id| (*id, header_downloaders.get(id).map(|peer| peer.total_score()).unwrap_or_else(U256::zero)))
+            .collect();
+        peers.sort_by_key(|(_, total_score)| *total_score);
+        peers
+    }
+
     fn send_message(&self, id: &NodeId, message: Message) {
         let api = self.api.read();
-        api.as_ref().expect("Api must exist").send(id, &message.rlp_bytes().to_vec());
+        if let Err(err) = api.as_ref().expect("Api must exist").send(id, &message.rlp_bytes().to_vec()) {
+            cdebug!(SYNC, "Cannot send a message to {}: {:?}", id, err);
+        }
+    }
+
+    /// Stops syncing from the peer which follows a chain this node never accepts, and disconnects it.
+    fn reject_peer(&self, id: &NodeId) {
+        self.header_downloaders.write().remove(id);
+        self.rejected_peers.write().insert(*id);
+        let api = self.api.read();
+        api.as_ref().expect("Api must exist").disconnect(id);
     }
 
     fn dismiss_request(&self, id: &NodeId, request_id: u64) {
@@ -110,7 +160,8 @@ impl Extension {
                 return
             }
 
-            if let Some(request) = self.body_downloader.lock().create_request() {
+            let earliest_body_number = self.earliest_body_numbers.read().get(id).cloned().unwrap_or(0);
+            if let Some(request) = self.body_downloader.lock().create_request(earliest_body_number) {
                 let request_id = self.last_request.fetch_add(1, Ordering::Relaxed) as u64;
                 requests.push((request_id, request.clone()));
                 self.send_message(id, Message::Request(request_id, request));
@@ -145,10 +196,19 @@ impl NetworkExtension for Extension {
     }
 
     fn versions(&self) -> &[u64] {
-        const VERSIONS: &'static [u64] = &[0];
+        const VERSIONS: &'static [u64] = &[SHARD_ROOTS_VERSION];
         &VERSIONS
     }
 
+    fn dependencies(&self) -> &[&'static str] {
+        const DEPENDENCIES: &'static [&'static str] = &["unstructured-discovery"];
+        &DEPENDENCIES
+    }
+
+    fn max_message_size(&self) -> usize {
+        MAX_MESSAGE_SIZE
+    }
+
     fn on_initialize(&self, api: Arc<Api>) {
         let mut api_lock = self.api.write();
         api.set_timer(SYNC_TIMER_TOKEN, Duration::milliseconds(SYNC_TIMER_INTERVAL)).expect("Timer set succeeds");
@@ -172,22 +232,14 @@ impl NetworkExtension for Extension {
         cinfo!(SYNC, "Sync extension initialized");
     }
 
-    fn on_node_added(&self, id: &NodeId, _version: u64) {
+    fn on_node_added(&self, id: &NodeId, version: u64) {
         let mut requests = self.requests.write();
         let mut tokens = self.tokens.write();
         let mut tokens_info = self.tokens_info.write();
         let mut token_generator = self.token_generator.lock();
 
         cinfo!(SYNC, "New peer detected #{}", id);
-        let chain_info = self.client.chain_info();
-        self.send_message(
-            id,
-            Message::Status {
-                total_score: chain_info.total_score,
-                best_hash: chain_info.best_block_hash,
-                genesis_hash: chain_info.genesis_hash,
-            },
-        );
+        self.send_message(id, self.status(version));
 
         let token = token_generator.gen().expect("Token generator is full");
         let token_info = TokenInfo {
@@ -213,6 +265,8 @@ impl NetworkExtension for Extension {
 
         cinfo!(SYNC, "Peer removed #{}", id);
         header_downloaders.remove(id);
+        self.rejected_peers.write().remove(id);
+        self.earliest_body_numbers.write().remove(id);
 
         let t = requests.remove(id);
         debug_assert_ne!(None, t);
@@ -226,13 +280,14 @@ impl NetworkExtension for Extension {
     }
 
     fn on_message(&self, id: &NodeId, data: &[u8]) {
-        if let Ok(received_message) = UntrustedRlp::new(data).as_val() {
+        if let Ok(received_message) = UntrustedRlp::new_checked(data).and_then(|rlp| rlp.as_val()) {
             match received_message {
                 Message::Status {
                     total_score,
                     best_hash,
                     genesis_hash,
-                } => self.on_peer_status(id, total_score, best_hash, genesis_hash),
+                    earliest_body_number,
+                } => self.on_peer_status(id, total_score, best_hash, genesis_hash, earliest_body_number.unwrap_or(0)),
                 Message::Request(request_id, request) => self.on_peer_request(id, request_id, request),
                 Message::Response(request_id, response) => self.on_peer_response(id, request_id, response),
             }
@@ -244,6 +299,7 @@ impl NetworkExtension for Extension {
     fn on_timeout(&self, token: TimerToken) {
         match token {
             SYNC_TIMER_TOKEN => {
+                self.report_progress();
                 let total_score = self.client.chain_info().total_score;
                 let mut peer_ids: Vec<_> = self.header_downloaders.read().keys().cloned().collect();
                 thread_rng().shuffle(&mut peer_ids);
@@ -350,30 +406,84 @@ impl ChainNotify for Extension {
         self.body_downloader.lock().remove_target(&invalid);
 
 
-        let chain_info = self.client.chain_info();
         let peer_ids = self.header_downloaders.read();
         for id in peer_ids.keys() {
-            self.send_message(
-                id,
-                Message::Status {
-                    total_score: chain_info.total_score,
-                    best_hash: chain_info.best_block_hash,
-                    genesis_hash: chain_info.genesis_hash,
-                },
-            );
+            let version = self.api.read().as_ref().expect("Api must exist").negotiated_version(id).unwrap_or(0);
+            self.send_message(id, self.status(version));
         }
     }
 }
 
 impl Extension {
-    fn on_peer_status(&self, from: &NodeId, total_score: U256, best_hash: H256, genesis_hash: H256) {
+    fn status(&self, version: u64) -> Message {
+        let chain_info = self.client.chain_info();
+        let earliest_body_number = if version >= EARLIEST_BODY_VERSION {
+            Some(self.client.earliest_body_number())
+        } else {
+            None
+        };
+        Message::Status {
+            total_score: chain_info.total_score,
+            best_hash: chain_info.best_block_hash,
+            genesis_hash: chain_info.genesis_hash,
+            earliest_body_number,
+        }
+    }
+
+    /// Exports the progress to the client, which the headers downloaded from the peers lead.
+    fn report_progress(&self) {
+        let now = Instant::now();
+        let current_block = self.client.chain_info().best_block_number;
+        let highest_block =
+            self.header_downloaders.read().values().map(HeaderDownloader::best_number).fold(current_block, cmp::max);
+
+        let mut samples = self.progress_samples.lock();
+        samples.push_back((now, current_block));
+        if samples.len() > MAX_PROGRESS_SAMPLES {
+            samples.pop_front();
+        }
+        let (first_time, first_block) = *samples.front().expect("A sample is pushed above");
+        let elapsed = now.duration_since(first_time);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000f64;
+        let blocks_per_second = if elapsed > 0f64 {
+            current_block.saturating_sub(first_block) as f64 / elapsed
+        } else {
+            0f64
+        };
+
+        self.client.set_sync_progress(SyncProgress {
+            starting_block: self.starting_block,
+            highest_block,
+            blocks_per_second,
+        });
+    }
+
+    fn on_peer_status(
+        &self,
+        from: &NodeId,
+        total_score: U256,
+        best_hash: H256,
+        genesis_hash: H256,
+        earliest_body_number: BlockNumber,
+    ) {
         // Validity check
         if genesis_hash != self.client.chain_info().genesis_hash {
             cinfo!(SYNC, "Genesis hash mismatch with peer {}", from);
             return
         }
+        if self.rejected_peers.read().contains(from) {
+            return
+        }
 
-        cinfo!(SYNC, "Peer #{} status update: total_score: {}, best_hash: {}", from, total_score, best_hash);
+        cinfo!(
+            SYNC,
+            "Peer #{} status update: total_score: {}, best_hash: {}, earliest_body_number: {}",
+            from,
+            total_score,
+            best_hash,
+            earliest_body_number
+        );
+        self.earliest_body_numbers.write().insert(*from, earliest_body_number);
 
         let mut peers = self.header_downloaders.write();
         if peers.contains_key(from) {
@@ -436,9 +546,9 @@ impl Extension {
 
     fn create_headers_response(&self, start_number: BlockNumber, max_count: u64) -> ResponseMessage {
         let headers = (0..max_count)
-            .map(|number| self.client.block(BlockId::Number(start_number + number)))
-            .take_while(|block| block.is_some())
-            .map(|block| block.expect("take_while guarantees existance of item").header().decode())
+            .map(|number| self.client.block_header(BlockId::Number(start_number + number)))
+            .take_while(|header| header.is_some())
+            .map(|header| header.expect("take_while guarantees existance of item").decode())
             .collect();
         ResponseMessage::Headers(headers)
     }
@@ -491,7 +601,9 @@ impl Extension {
                                 return
                             }
                             let api = self.api.read();
-                            api.as_ref().expect("Api must exist").clear_timer(*token).expect("Timer clear succeed");
+                            if let Err(err) = api.as_ref().expect("Api must exist").clear_timer(*token) {
+                                cdebug!(SYNC, "Cannot clear the request timer of {}: {:?}", from, err);
+                            }
                             token_info.request_id = None;
                         }
                     }
@@ -565,6 +677,24 @@ impl Extension {
         for header in completed {
             match self.client.import_header(header.clone().into_inner()) {
                 Err(BlockImportError::Import(ImportError::AlreadyInChain)) => exists.push(header.hash()),
+                Err(BlockImportError::Block(BlockError::CheckpointMismatch(_)))
+                | Err(BlockImportError::Block(BlockError::ForkBelowCheckpoint(_))) => {
+                    // The peer follows a chain which can never be canon, so stop downloading from it.
+                    cwarn!(SYNC, "Peer #{} sent a header({}) off the checkpoints", from, header.hash());
+                    self.reject_peer(from);
+                    return
+                }
+                Err(BlockImportError::Block(BlockError::ForkBelowFinalized(_)))
+                | Err(BlockImportError::Block(BlockError::ReorgTooDeep(_))) => {
+                    cwarn!(
+                        SYNC,
+                        "Peer #{} sent a header({}) which reorganizes the chain too deeply",
+                        from,
+                        header.hash()
+                    );
+                    self.reject_peer(from);
+                    return
+                }
                 // FIXME: handle import errors
                 Err(err) => {
                     cwarn!(SYNC, "Cannot import header({}): {:?}", header.hash(), err);
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use ctypes::BlockNumber;
 use primitives::{H256, U256};
 use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
@@ -39,6 +40,8 @@ pub enum Message {
         total_score: U256,
         best_hash: H256,
         genesis_hash: H256,
+        /// Sent to the peers of version 1 or later. The peers of version 0 keep every body.
+        earliest_body_number: Option<BlockNumber>,
     },
     Request(u64, RequestMessage),
     Response(u64, ResponseMessage),
@@ -51,14 +54,26 @@ impl Encodable for Message {
                 total_score,
                 best_hash,
                 genesis_hash,
+                earliest_body_number,
             } => {
                 s.begin_list(2);
                 s.append(&MESSAGE_ID_STATUS);
 
-                s.begin_list(3);
-                s.append(total_score);
-                s.append(best_hash);
-                s.append(genesis_hash);
+                match earliest_body_number {
+                    Some(earliest_body_number) => {
+                        s.begin_list(4);
+                        s.append(total_score);
+                        s.append(best_hash);
+                        s.append(genesis_hash);
+                        s.append(earliest_body_number);
+                    }
+                    None => {
+                        s.begin_list(3);
+                        s.append(total_score);
+                        s.append(best_hash);
+                        s.append(genesis_hash);
+                    }
+                }
             }
             Message::Request(request_id, request) => {
                 s.begin_list(3);
@@ -85,14 +100,17 @@ impl Decodable for Message {
             }
             let message = rlp.at(1)?;
 
-            if message.item_count()? != 3 {
-                return Err(DecoderError::RlpIncorrectListLen)
-            }
+            let earliest_body_number = match message.item_count()? {
+                3 => None,
+                4 => Some(message.val_at(3)?),
+                _ => return Err(DecoderError::RlpIncorrectListLen),
+            };
 
             Ok(Message::Status {
                 total_score: message.val_at(0)?,
                 best_hash: message.val_at(1)?,
                 genesis_hash: message.val_at(2)?,
+                earliest_body_number,
             })
         } else {
             if rlp.item_count()? != 3 {
@@ -124,9 +142,41 @@ mod tests {
             total_score: U256::default(),
             best_hash: H256::default(),
             genesis_hash: H256::default(),
+            earliest_body_number: Some(10),
         });
     }
 
+    #[test]
+    fn status_message_rlp_of_version_0() {
+        rlp_encode_and_decode_test!(Message::Status {
+            total_score: U256::default(),
+            best_hash: H256::default(),
+            genesis_hash: H256::default(),
+            earliest_body_number: None,
+        });
+    }
+
+    #[test]
+    fn status_message_without_earliest_body_number() {
+        let mut s = RlpStream::new_list(2);
+        s.append(&MESSAGE_ID_STATUS);
+        s.begin_list(3);
+        s.append(&U256::default());
+        s.append(&H256::default());
+        s.append(&H256::default());
+
+        let decoded: Message = ::rlp::decode(&s.out());
+        assert_eq!(
+            Message::Status {
+                total_score: U256::default(),
+                best_hash: H256::default(),
+                genesis_hash: H256::default(),
+                earliest_body_number: None,
+            },
+            decoded
+        );
+    }
+
     #[test]
     fn request_bodies_message_rlp() {
         let request_id = 10;
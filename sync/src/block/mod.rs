@@ -17,5 +17,7 @@
 mod downloader;
 mod extension;
 mod message;
+mod watchdog;
 
 pub use self::extension::Extension as BlockSyncExtension;
+pub use self::watchdog::HeadLagWatchdog;
@@ -0,0 +1,142 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::cmp::max;
+use std::sync::{Arc, Weak};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use ccore::{ChainInfo, Client};
+use cnetwork::{IntoSocketAddr, NetworkControl, NetworkControlError, NodeId, SocketAddr};
+use parking_lot::Mutex;
+use primitives::{H256, U256};
+
+use super::extension::Extension;
+
+const WATCHDOG_INTERVAL_MS: u64 = 1000;
+
+/// Refreshes the peer set when the best block stops advancing while the peers are ahead of the node.
+pub struct HeadLagWatchdog {
+    client: Arc<Client>,
+    network: Arc<NetworkControl>,
+    sync: Arc<Extension>,
+    bootstrap_addresses: Vec<SocketAddr>,
+    /// How long the best block can stay the same before the peer set is refreshed
+    timeout: Duration,
+    /// The best block hash, and since when the peers have been ahead of it
+    last_progress: Mutex<(H256, Instant)>,
+}
+
+impl HeadLagWatchdog {
+    pub fn start(
+        client: Arc<Client>,
+        network: Arc<NetworkControl>,
+        sync: Arc<Extension>,
+        bootstrap_addresses: Vec<SocketAddr>,
+        timeout: Duration,
+    ) -> Arc<Self> {
+        let best_block_hash = client.chain_info().best_block_hash;
+        let watchdog = Arc::new(Self {
+            client,
+            network,
+            sync,
+            bootstrap_addresses,
+            timeout,
+            last_progress: Mutex::new((best_block_hash, Instant::now())),
+        });
+        let weak = Arc::downgrade(&watchdog);
+        spawn(move || run(&weak));
+        watchdog
+    }
+
+    fn check(&self) {
+        let chain_info = self.client.chain_info();
+        let now = Instant::now();
+        let mut last_progress = self.last_progress.lock();
+        if last_progress.0 != chain_info.best_block_hash {
+            *last_progress = (chain_info.best_block_hash, now);
+            return
+        }
+
+        // An idle chain which produces no blocks doesn't stall. Only the peers with a higher total score tell the
+        // node lags behind. Without peers there is nothing to refresh; the network reconnects to the bootstrap nodes
+        // by itself.
+        let peers = self.sync.peers_by_score();
+        let is_behind = peers.last().map_or(false, |(_, total_score)| *total_score > chain_info.total_score);
+        if !is_behind {
+            last_progress.1 = now;
+            return
+        }
+
+        let stalled_for = now.duration_since(last_progress.1);
+        if stalled_for < self.timeout {
+            return
+        }
+        let peer_count = self.network.get_peer_count().unwrap_or(0);
+        if peer_count == 0 {
+            return
+        }
+
+        cwarn!(
+            SYNC,
+            "The best block #{} ({}) has not changed for {} seconds behind {} peers. Refreshing the peer set",
+            chain_info.best_block_number,
+            chain_info.best_block_hash,
+            stalled_for.as_secs(),
+            peer_count
+        );
+        self.refresh_peers(peers, peer_count);
+        // Give the new peers a whole timeout before refreshing again.
+        last_progress.1 = now;
+    }
+
+    /// `peers` are in ascending order of their total score.
+    fn refresh_peers(&self, peers: Vec<(NodeId, U256)>, peer_count: usize) {
+        // Drops at most a half of the peers, so that the node is not isolated by the refresh.
+        let limit = max(1, peer_count / 2);
+        for (id, total_score) in peers.into_iter().take(limit) {
+            cinfo!(SYNC, "Dropping peer #{} (total_score: {})", id, total_score);
+            if let Err(err) = self.network.disconnect(id.into_addr()) {
+                cdebug!(SYNC, "Cannot disconnect peer #{}: {:?}", id, err);
+            }
+        }
+
+        for address in &self.bootstrap_addresses {
+            if let Ok(false) = self.network.is_connected(address) {
+                if let Err(err) = self.network.connect(*address) {
+                    cdebug!(SYNC, "Cannot connect to bootstrap node {}: {:?}", address, err);
+                }
+            }
+        }
+
+        match self.network.refresh_discovery() {
+            Ok(()) => {}
+            Err(NetworkControlError::Disabled) => cdebug!(SYNC, "Discovery is disabled"),
+            Err(err) => cwarn!(SYNC, "Cannot refresh discovery: {:?}", err),
+        }
+    }
+}
+
+fn run(watchdog: &Weak<HeadLagWatchdog>) {
+    loop {
+        sleep(Duration::from_millis(WATCHDOG_INTERVAL_MS));
+        match watchdog.upgrade() {
+            Some(watchdog) => watchdog.check(),
+            None => return,
+        }
+    }
+}
@@ -17,6 +17,7 @@
 extern crate parking_lot;
 
 extern crate codechain_core as ccore;
+extern crate codechain_key as ckey;
 extern crate codechain_merkle as cmerkle;
 #[macro_use]
 extern crate codechain_logger as clogger;
@@ -25,8 +26,10 @@ extern crate codechain_token_generator as ctoken_generator;
 extern crate codechain_types as ctypes;
 
 extern crate kvdb;
+extern crate linked_hash_map;
 #[macro_use]
 extern crate log;
+extern crate memorydb;
 extern crate primitives;
 extern crate rand;
 #[cfg_attr(test, macro_use)]
@@ -35,12 +38,11 @@ extern crate snap;
 extern crate time;
 
 mod block;
+mod light;
 mod parcel;
 mod snapshot;
 
-pub use self::block::BlockSyncExtension;
+pub use self::block::{BlockSyncExtension, HeadLagWatchdog};
+pub use self::light::{cht_number, verify_header_proof, Cht, LightSyncExtension, CHT_SIZE};
 pub use self::parcel::ParcelSyncExtension;
 pub use self::snapshot::SnapshotService;
-
-#[cfg(test)]
-extern crate codechain_key as ckey;
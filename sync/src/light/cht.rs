@@ -0,0 +1,113 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use cmerkle::{verify_proof, TrieDB, TrieDBMut, TrieMut};
+use ctypes::BlockNumber;
+use memorydb::MemoryDB;
+use primitives::{Bytes, H256};
+use rlp::{self, UntrustedRlp};
+
+/// The number of blocks in a CHT.
+///
+/// A CHT (canonical hash trie) maps the numbers of the blocks in a range to the hashes of the canonical blocks.
+/// A light client which trusts the root of a CHT can verify any header in it with a proof from a full node.
+pub const CHT_SIZE: BlockNumber = 2048;
+
+/// Returns the number of the CHT which has the block.
+pub fn cht_number(block_number: BlockNumber) -> u64 {
+    block_number / CHT_SIZE
+}
+
+pub struct Cht {
+    number: u64,
+    root: H256,
+    db: MemoryDB,
+}
+
+impl Cht {
+    /// Builds the CHT from the canonical block hashes.
+    /// Returns `None` if any block of the CHT is not in the canonical chain.
+    pub fn build<F>(number: u64, block_hash: F) -> Option<Self>
+    where
+        F: Fn(BlockNumber) -> Option<H256>, {
+        let mut db = MemoryDB::new();
+        let mut root = H256::new();
+        {
+            let mut trie = TrieDBMut::new(&mut db, &mut root);
+            for block_number in number * CHT_SIZE..(number + 1) * CHT_SIZE {
+                let hash = block_hash(block_number)?;
+                trie.insert(&rlp::encode(&block_number), &rlp::encode(&hash))
+                    .expect("Inserting to the memory db never fails");
+            }
+        }
+        Some(Self {
+            number,
+            root,
+            db,
+        })
+    }
+
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn root(&self) -> &H256 {
+        &self.root
+    }
+
+    /// Makes the proof of the hash of the block. It is empty if the block is not in this CHT.
+    pub fn prove(&self, block_number: BlockNumber) -> Vec<Bytes> {
+        if cht_number(block_number) != self.number {
+            return Vec::new()
+        }
+        TrieDB::new(&self.db, &self.root)
+            .and_then(|trie| trie.get_proof(&rlp::encode(&block_number)))
+            .expect("All the nodes of the trie are in the memory db")
+    }
+}
+
+/// Verify the proof made by `Cht::prove` against the root of the CHT.
+/// Returns the hash of the canonical block, or `None` if the proof is not valid.
+pub fn verify_header_proof(root: &H256, block_number: BlockNumber, proof: &[Bytes]) -> Option<H256> {
+    let hash = verify_proof(root, &rlp::encode(&block_number), proof).ok()??;
+    UntrustedRlp::new(&hash).as_val().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_of(block_number: BlockNumber) -> Option<H256> {
+        Some(H256::from(block_number + 1))
+    }
+
+    #[test]
+    fn verify_proofs_of_the_cht() {
+        let cht = Cht::build(1, hash_of).unwrap();
+        let block_number = CHT_SIZE + 5;
+        let proof = cht.prove(block_number);
+        assert_eq!(hash_of(block_number), verify_header_proof(cht.root(), block_number, &proof));
+        assert_eq!(None, verify_header_proof(cht.root(), block_number + 1, &proof));
+        assert_eq!(None, verify_header_proof(&H256::random(), block_number, &proof));
+        assert!(cht.prove(5).is_empty());
+    }
+
+    #[test]
+    fn cht_is_not_built_before_its_blocks() {
+        assert!(Cht::build(0, |block_number| if block_number < 100 { hash_of(block_number) } else { None }).is_none());
+    }
+}
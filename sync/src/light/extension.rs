@@ -0,0 +1,391 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
+
+use ccore::{BlockChainClient, BlockId, BlockImportError, BlockInfo, ChainInfo, Client, LightClient, StateInfo};
+use ckey::Address;
+use cnetwork::{Api, NetworkExtension, NodeId, TimerToken};
+use ctypes::BlockNumber;
+use linked_hash_map::LinkedHashMap;
+use parking_lot::{Mutex, RwLock};
+use primitives::{Bytes, H256, U256};
+use rand::{thread_rng, Rng};
+use rlp::{Encodable, UntrustedRlp};
+use time::Duration;
+
+use super::cht::{cht_number, Cht, CHT_SIZE};
+use super::message::{Message, RequestMessage, ResponseMessage};
+
+const SYNC_TIMER_TOKEN: TimerToken = 0;
+const SYNC_TIMER_INTERVAL: i64 = 1000;
+
+/// The maximum number of headers in a response.
+const MAX_HEADERS_PER_RESPONSE: u64 = 128;
+/// The requests which are not answered for this long are dropped.
+const REQUEST_TIMEOUT_SECS: u64 = 15;
+/// The number of the CHTs which are kept after they are built for the header proofs.
+const MAX_CACHED_CHTS: usize = 16;
+
+/// What the extension does for the light clients.
+enum Role {
+    /// A full node serves the headers and the proofs.
+    Server(Arc<Client>),
+    /// A light client syncs the headers, and reads the accounts through the proofs.
+    Client(Arc<LightClient>),
+}
+
+struct Peer {
+    total_score: U256,
+    best_number: BlockNumber,
+    requests: HashMap<u64, (RequestMessage, Instant)>,
+}
+
+pub struct Extension {
+    role: Role,
+    /// The peers which sent a valid status.
+    peers: RwLock<HashMap<NodeId, Peer>>,
+    api: RwLock<Option<Arc<Api>>>,
+    last_request: AtomicUsize,
+    /// The recently built CHTs by their numbers. A served CHT doesn't change, so it's built once while it's asked.
+    chts: Mutex<LinkedHashMap<u64, Cht>>,
+}
+
+impl Extension {
+    /// Creates the extension of a full node, which serves the light clients.
+    pub fn server(client: Arc<Client>) -> Arc<Self> {
+        Self::new(Role::Server(client))
+    }
+
+    /// Creates the extension of a light client, which syncs the headers from the full nodes.
+    pub fn client(client: Arc<LightClient>) -> Arc<Self> {
+        Self::new(Role::Client(client))
+    }
+
+    fn new(role: Role) -> Arc<Self> {
+        Arc::new(Self {
+            role,
+            peers: RwLock::new(HashMap::new()),
+            api: RwLock::new(None),
+            last_request: AtomicUsize::new(0),
+            chts: Mutex::new(LinkedHashMap::new()),
+        })
+    }
+
+    /// Asks a peer for the proof of the account at the block. Returns false if there is no peer to ask.
+    /// The account is available from `LightClient::account` once the proof is received and verified.
+    pub fn request_account(&self, block_hash: H256, address: Address) -> bool {
+        let peer = {
+            let peers = self.peers.read();
+            let ids: Vec<_> = peers.keys().cloned().collect();
+            match thread_rng().choose(&ids) {
+                Some(id) => *id,
+                None => return false,
+            }
+        };
+        self.send_request(
+            &peer,
+            RequestMessage::AccountProof {
+                block_hash,
+                address,
+            },
+        );
+        true
+    }
+
+    fn genesis_hash(&self) -> H256 {
+        match &self.role {
+            Role::Server(client) => client.chain_info().genesis_hash,
+            Role::Client(client) => client.genesis_hash(),
+        }
+    }
+
+    fn status(&self) -> Message {
+        match &self.role {
+            Role::Server(client) => {
+                let chain_info = client.chain_info();
+                Message::Status {
+                    total_score: chain_info.total_score,
+                    best_number: chain_info.best_block_number,
+                    genesis_hash: chain_info.genesis_hash,
+                }
+            }
+            Role::Client(client) => {
+                let (best_header, total_score) = client.best_header();
+                Message::Status {
+                    total_score,
+                    best_number: best_header.number(),
+                    genesis_hash: client.genesis_hash(),
+                }
+            }
+        }
+    }
+
+    fn send_message(&self, id: &NodeId, message: Message) {
+        let api = self.api.read();
+        if let Err(err) = api.as_ref().expect("Api must exist").send(id, &message.rlp_bytes().to_vec()) {
+            cdebug!(SYNC, "Cannot send a message to {}: {:?}", id, err);
+        }
+    }
+
+    fn send_request(&self, id: &NodeId, request: RequestMessage) {
+        if let Some(peer) = self.peers.write().get_mut(id) {
+            let request_id = self.last_request.fetch_add(1, Ordering::Relaxed) as u64;
+            peer.requests.insert(request_id, (request.clone(), Instant::now()));
+            self.send_message(id, Message::Request(request_id, request));
+        }
+    }
+
+    /// Asks the best peer for the headers after the best header, unless the headers are being downloaded already.
+    fn request_headers(&self) {
+        let client = match &self.role {
+            Role::Client(client) => client,
+            Role::Server(_) => return,
+        };
+        let (best_header, total_score) = client.best_header();
+        let target = {
+            let peers = self.peers.read();
+            let downloading = peers.values().flat_map(|peer| peer.requests.values()).any(|(request, _)| match request {
+                RequestMessage::Headers {
+                    ..
+                } => true,
+                _ => false,
+            });
+            if downloading {
+                return
+            }
+            peers
+                .iter()
+                .filter(|(_, peer)| peer.total_score > total_score && peer.best_number > best_header.number())
+                .max_by_key(|(_, peer)| peer.total_score)
+                .map(|(id, _)| *id)
+        };
+        if let Some(id) = target {
+            self.send_request(
+                &id,
+                RequestMessage::Headers {
+                    start_number: best_header.number() + 1,
+                    max_count: MAX_HEADERS_PER_RESPONSE,
+                },
+            );
+        }
+    }
+}
+
+impl NetworkExtension for Extension {
+    fn name(&self) -> &'static str {
+        "light-sync"
+    }
+    fn need_encryption(&self) -> bool {
+        false
+    }
+
+    fn versions(&self) -> &[u64] {
+        const VERSIONS: &'static [u64] = &[0];
+        &VERSIONS
+    }
+
+    fn on_initialize(&self, api: Arc<Api>) {
+        let mut api_lock = self.api.write();
+        api.set_timer(SYNC_TIMER_TOKEN, Duration::milliseconds(SYNC_TIMER_INTERVAL)).expect("Timer set succeeds");
+        *api_lock = Some(api);
+        cinfo!(SYNC, "Light sync extension initialized");
+    }
+
+    fn on_node_added(&self, id: &NodeId, _version: u64) {
+        let status = self.status();
+        self.send_message(id, status);
+    }
+
+    fn on_node_removed(&self, id: &NodeId) {
+        self.peers.write().remove(id);
+    }
+
+    fn on_message(&self, id: &NodeId, data: &[u8]) {
+        if let Ok(received_message) = UntrustedRlp::new_checked(data).and_then(|rlp| rlp.as_val()) {
+            match received_message {
+                Message::Status {
+                    total_score,
+                    best_number,
+                    genesis_hash,
+                } => self.on_peer_status(id, total_score, best_number, genesis_hash),
+                Message::Request(request_id, request) => self.on_peer_request(id, request_id, request),
+                Message::Response(request_id, response) => self.on_peer_response(id, request_id, response),
+            }
+        } else {
+            cinfo!(SYNC, "Invalid light sync message from peer {}", id);
+        }
+    }
+
+    fn on_timeout(&self, token: TimerToken) {
+        match token {
+            SYNC_TIMER_TOKEN => {
+                let timeout = StdDuration::from_secs(REQUEST_TIMEOUT_SECS);
+                for peer in self.peers.write().values_mut() {
+                    peer.requests.retain(|_, (_, requested_at)| requested_at.elapsed() < timeout);
+                }
+                self.request_headers();
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Extension {
+    fn on_peer_status(&self, from: &NodeId, total_score: U256, best_number: BlockNumber, genesis_hash: H256) {
+        if genesis_hash != self.genesis_hash() {
+            cinfo!(SYNC, "Genesis hash mismatch with light sync peer {}", from);
+            return
+        }
+        cdebug!(
+            SYNC,
+            "Light sync peer #{} status update: total_score: {}, best_number: {}",
+            from,
+            total_score,
+            best_number
+        );
+        {
+            let mut peers = self.peers.write();
+            let peer = peers.entry(*from).or_insert_with(|| Peer {
+                total_score,
+                best_number,
+                requests: HashMap::new(),
+            });
+            peer.total_score = total_score;
+            peer.best_number = best_number;
+        }
+        self.request_headers();
+    }
+
+    fn on_peer_request(&self, from: &NodeId, id: u64, request: RequestMessage) {
+        let client = match &self.role {
+            Role::Server(client) => client,
+            Role::Client(_) => return,
+        };
+        if !self.peers.read().contains_key(from) {
+            cinfo!(SYNC, "Light sync request from invalid peer #{} received", from);
+            return
+        }
+
+        let response = match request {
+            RequestMessage::Headers {
+                start_number,
+                max_count,
+            } => {
+                let headers = (start_number..start_number + max_count.min(MAX_HEADERS_PER_RESPONSE))
+                    .map(|number| client.block_header(BlockId::Number(number)))
+                    .take_while(Option::is_some)
+                    .map(|header| header.expect("take_while guarantees existance of item").decode())
+                    .collect();
+                ResponseMessage::Headers(headers)
+            }
+            RequestMessage::HeaderProof(number) => ResponseMessage::HeaderProof(self.prove_header(client, number)),
+            RequestMessage::AccountProof {
+                block_hash,
+                address,
+            } => {
+                let proof = client
+                    .state_info(BlockId::Hash(block_hash))
+                    .and_then(|state| state.account_proof(&address).ok())
+                    .unwrap_or_default();
+                ResponseMessage::AccountProof(proof)
+            }
+        };
+        self.send_message(from, Message::Response(id, response));
+    }
+
+    /// A CHT is served once the chain is a whole CHT ahead of it, so that it isn't changed by reorganizations.
+    fn prove_header(&self, client: &Client, number: BlockNumber) -> Vec<Bytes> {
+        let cht_number = cht_number(number);
+        if (cht_number + 2) * CHT_SIZE > client.chain_info().best_block_number + 1 {
+            return Vec::new()
+        }
+        let mut chts = self.chts.lock();
+        if let Some(cht) = chts.get_refresh(&cht_number) {
+            return cht.prove(number)
+        }
+        let cht = match Cht::build(cht_number, |number| client.block_hash(BlockId::Number(number))) {
+            Some(cht) => cht,
+            None => return Vec::new(),
+        };
+        let proof = cht.prove(number);
+        chts.insert(cht_number, cht);
+        if chts.len() > MAX_CACHED_CHTS {
+            chts.pop_front();
+        }
+        proof
+    }
+
+    fn on_peer_response(&self, from: &NodeId, id: u64, response: ResponseMessage) {
+        let client = match &self.role {
+            Role::Client(client) => client,
+            Role::Server(_) => return,
+        };
+        let request = match self.peers.write().get_mut(from).and_then(|peer| peer.requests.remove(&id)) {
+            Some((request, _)) => request,
+            None => return,
+        };
+
+        match (request, response) {
+            (
+                RequestMessage::Headers {
+                    start_number,
+                    ..
+                },
+                ResponseMessage::Headers(headers),
+            ) => {
+                for header in headers {
+                    match client.import_header(&header.rlp_bytes()) {
+                        Ok(_) | Err(BlockImportError::Import(_)) => {}
+                        Err(err) => {
+                            cwarn!(SYNC, "Cannot import header({}) from {}: {:?}", header.hash(), from, err);
+                            // The peer may be on a fork which branched off before the best header.
+                            if header.number() == start_number {
+                                let start_number = start_number.saturating_sub(MAX_HEADERS_PER_RESPONSE).max(1);
+                                self.send_request(
+                                    from,
+                                    RequestMessage::Headers {
+                                        start_number,
+                                        max_count: MAX_HEADERS_PER_RESPONSE,
+                                    },
+                                );
+                                return
+                            }
+                            break
+                        }
+                    }
+                }
+                self.request_headers();
+            }
+            (
+                RequestMessage::AccountProof {
+                    block_hash,
+                    address,
+                },
+                ResponseMessage::AccountProof(proof),
+            ) => {
+                if let Err(err) = client.import_account_proof(&block_hash, &address, &proof) {
+                    cwarn!(SYNC, "Invalid proof of {} at {} from {}: {}", address, block_hash, from, err);
+                }
+            }
+            _ => cinfo!(SYNC, "Unexpected light sync response from {}", from),
+        }
+    }
+}
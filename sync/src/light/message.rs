@@ -0,0 +1,287 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use ccore::Header;
+use ckey::Address;
+use ctypes::BlockNumber;
+use primitives::{Bytes, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
+
+const MESSAGE_ID_STATUS: u8 = 0x01;
+const MESSAGE_ID_GET_HEADERS: u8 = 0x02;
+const MESSAGE_ID_HEADERS: u8 = 0x03;
+const MESSAGE_ID_GET_HEADER_PROOF: u8 = 0x04;
+const MESSAGE_ID_HEADER_PROOF: u8 = 0x05;
+const MESSAGE_ID_GET_ACCOUNT_PROOF: u8 = 0x06;
+const MESSAGE_ID_ACCOUNT_PROOF: u8 = 0x07;
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Status {
+        total_score: U256,
+        best_number: BlockNumber,
+        genesis_hash: H256,
+    },
+    Request(u64, RequestMessage),
+    Response(u64, ResponseMessage),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestMessage {
+    Headers {
+        start_number: BlockNumber,
+        max_count: u64,
+    },
+    /// The proof of the canonical hash of the block in its CHT.
+    HeaderProof(BlockNumber),
+    AccountProof {
+        block_hash: H256,
+        address: Address,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResponseMessage {
+    Headers(Vec<Header>),
+    /// Empty if the CHT of the block is not complete yet.
+    HeaderProof(Vec<Bytes>),
+    /// Empty if the state of the block is not available.
+    AccountProof(Vec<Bytes>),
+}
+
+impl Encodable for Message {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            Message::Status {
+                total_score,
+                best_number,
+                genesis_hash,
+            } => {
+                s.begin_list(2);
+                s.append(&MESSAGE_ID_STATUS);
+
+                s.begin_list(3);
+                s.append(total_score);
+                s.append(best_number);
+                s.append(genesis_hash);
+            }
+            Message::Request(request_id, request) => {
+                s.begin_list(3);
+                s.append(&request.message_id());
+                s.append(request_id);
+                s.append(request);
+            }
+            Message::Response(response_id, response) => {
+                s.begin_list(3);
+                s.append(&response.message_id());
+                s.append(response_id);
+                s.append(response);
+            }
+        }
+    }
+}
+
+impl Decodable for Message {
+    fn decode(rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        let id = rlp.val_at(0)?;
+        if id == MESSAGE_ID_STATUS {
+            if rlp.item_count()? != 2 {
+                return Err(DecoderError::RlpIncorrectListLen)
+            }
+            let message = rlp.at(1)?;
+            if message.item_count()? != 3 {
+                return Err(DecoderError::RlpIncorrectListLen)
+            }
+            return Ok(Message::Status {
+                total_score: message.val_at(0)?,
+                best_number: message.val_at(1)?,
+                genesis_hash: message.val_at(2)?,
+            })
+        }
+
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen)
+        }
+        let request_id = rlp.val_at(1)?;
+        let message = rlp.at(2)?;
+        match id {
+            MESSAGE_ID_GET_HEADERS | MESSAGE_ID_GET_HEADER_PROOF | MESSAGE_ID_GET_ACCOUNT_PROOF => {
+                Ok(Message::Request(request_id, RequestMessage::decode(id, &message)?))
+            }
+            MESSAGE_ID_HEADERS | MESSAGE_ID_HEADER_PROOF | MESSAGE_ID_ACCOUNT_PROOF => {
+                Ok(Message::Response(request_id, ResponseMessage::decode(id, &message)?))
+            }
+            _ => Err(DecoderError::Custom("Unknown message id detected")),
+        }
+    }
+}
+
+impl Encodable for RequestMessage {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            RequestMessage::Headers {
+                start_number,
+                max_count,
+            } => {
+                s.begin_list(2);
+                s.append(start_number);
+                s.append(max_count);
+            }
+            RequestMessage::HeaderProof(number) => {
+                s.begin_list(1);
+                s.append(number);
+            }
+            RequestMessage::AccountProof {
+                block_hash,
+                address,
+            } => {
+                s.begin_list(2);
+                s.append(block_hash);
+                s.append(address);
+            }
+        };
+    }
+}
+
+impl RequestMessage {
+    pub fn message_id(&self) -> u8 {
+        match self {
+            RequestMessage::Headers {
+                ..
+            } => MESSAGE_ID_GET_HEADERS,
+            RequestMessage::HeaderProof(..) => MESSAGE_ID_GET_HEADER_PROOF,
+            RequestMessage::AccountProof {
+                ..
+            } => MESSAGE_ID_GET_ACCOUNT_PROOF,
+        }
+    }
+
+    fn decode(id: u8, rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        let message = match id {
+            MESSAGE_ID_GET_HEADERS => {
+                if rlp.item_count()? != 2 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                RequestMessage::Headers {
+                    start_number: rlp.val_at(0)?,
+                    max_count: rlp.val_at(1)?,
+                }
+            }
+            MESSAGE_ID_GET_HEADER_PROOF => {
+                if rlp.item_count()? != 1 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                RequestMessage::HeaderProof(rlp.val_at(0)?)
+            }
+            MESSAGE_ID_GET_ACCOUNT_PROOF => {
+                if rlp.item_count()? != 2 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                RequestMessage::AccountProof {
+                    block_hash: rlp.val_at(0)?,
+                    address: rlp.val_at(1)?,
+                }
+            }
+            _ => return Err(DecoderError::Custom("Unknown message id detected")),
+        };
+        Ok(message)
+    }
+}
+
+impl Encodable for ResponseMessage {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            ResponseMessage::Headers(headers) => {
+                s.append_list(headers);
+            }
+            ResponseMessage::HeaderProof(proof) | ResponseMessage::AccountProof(proof) => {
+                s.append_list::<Bytes, _>(proof);
+            }
+        };
+    }
+}
+
+impl ResponseMessage {
+    pub fn message_id(&self) -> u8 {
+        match self {
+            ResponseMessage::Headers(..) => MESSAGE_ID_HEADERS,
+            ResponseMessage::HeaderProof(..) => MESSAGE_ID_HEADER_PROOF,
+            ResponseMessage::AccountProof(..) => MESSAGE_ID_ACCOUNT_PROOF,
+        }
+    }
+
+    fn decode(id: u8, rlp: &UntrustedRlp) -> Result<Self, DecoderError> {
+        let message = match id {
+            MESSAGE_ID_HEADERS => ResponseMessage::Headers(rlp.as_list()?),
+            MESSAGE_ID_HEADER_PROOF => ResponseMessage::HeaderProof(rlp.as_list()?),
+            MESSAGE_ID_ACCOUNT_PROOF => ResponseMessage::AccountProof(rlp.as_list()?),
+            _ => return Err(DecoderError::Custom("Unknown message id detected")),
+        };
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_message_rlp() {
+        rlp_encode_and_decode_test!(Message::Status {
+            total_score: U256::default(),
+            best_number: 10,
+            genesis_hash: H256::default(),
+        });
+    }
+
+    #[test]
+    fn headers_request_rlp() {
+        rlp_encode_and_decode_test!(Message::Request(
+            1,
+            RequestMessage::Headers {
+                start_number: 100,
+                max_count: 100,
+            }
+        ));
+    }
+
+    #[test]
+    fn header_proof_request_rlp() {
+        rlp_encode_and_decode_test!(Message::Request(2, RequestMessage::HeaderProof(100)));
+    }
+
+    #[test]
+    fn account_proof_request_rlp() {
+        rlp_encode_and_decode_test!(Message::Request(
+            3,
+            RequestMessage::AccountProof {
+                block_hash: H256::default(),
+                address: Address::default(),
+            }
+        ));
+    }
+
+    #[test]
+    fn headers_response_rlp() {
+        rlp_encode_and_decode_test!(Message::Response(1, ResponseMessage::Headers(vec![Header::default()])));
+    }
+
+    #[test]
+    fn proof_responses_rlp() {
+        rlp_encode_and_decode_test!(Message::Response(2, ResponseMessage::HeaderProof(vec![vec![1, 2, 3]])));
+    }
+}
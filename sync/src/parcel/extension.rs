@@ -29,6 +29,8 @@ use super::message::Message;
 const BROADCAST_TIMER_TOKEN: TimerToken = 0;
 const BROADCAST_TIMER_INTERVAL: i64 = 1000;
 const MAX_HISTORY_SIZE: usize = 100;
+/// The pending parcels unknown to the peer are broadcast in a single message.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
 
 struct Peer {
     history_set: HashSet<H256>,
@@ -86,6 +88,15 @@ impl NetworkExtension for Extension {
         &VERSIONS
     }
 
+    fn dependencies(&self) -> &[&'static str] {
+        const DEPENDENCIES: &'static [&'static str] = &["block-propagation"];
+        &DEPENDENCIES
+    }
+
+    fn max_message_size(&self) -> usize {
+        MAX_MESSAGE_SIZE
+    }
+
     fn on_initialize(&self, api: Arc<Api>) {
         let mut api_lock = self.api.write();
         api.set_timer(BROADCAST_TIMER_TOKEN, Duration::milliseconds(BROADCAST_TIMER_INTERVAL))
@@ -101,7 +112,7 @@ impl NetworkExtension for Extension {
     }
 
     fn on_message(&self, token: &NodeId, data: &[u8]) {
-        if let Ok(received_message) = UntrustedRlp::new(data).as_val() {
+        if let Ok(received_message) = UntrustedRlp::new_checked(data).and_then(|rlp| rlp.as_val()) {
             match received_message {
                 Message::Parcels(parcels) => {
                     self.client.queue_parcels(
@@ -142,7 +153,9 @@ impl NetworkExtension for Extension {
 impl Extension {
     fn send_message(&self, token: &NodeId, message: Message) {
         let api = self.api.read();
-        api.as_ref().expect("Api must exist").send(token, &message.rlp_bytes());
+        if let Err(err) = api.as_ref().expect("Api must exist").send(token, &message.rlp_bytes()) {
+            cdebug!(SYNC_PARCEL, "Cannot send a message to {}: {:?}", token, err);
+        }
     }
 
     fn random_broadcast(&self) {
@@ -151,6 +164,7 @@ impl Extension {
             ctrace!(SYNC_PARCEL, "No parcels to propagate");
             return
         }
+        let mut broadcast = Vec::new();
         for (token, peer) in self.peers.read().iter() {
             let mut peer = peer.write();
             let unsent: Vec<_> = parcels
@@ -168,6 +182,10 @@ impl Extension {
             cdebug!(SYNC_PARCEL, "Send {} parcels to {}", unsent.len(), token);
             ctrace!(SYNC_PARCEL, "Send {:?}", unsent_hashes);
             self.send_message(token, Message::Parcels(unsent));
+            broadcast.extend(unsent_hashes);
+        }
+        if !broadcast.is_empty() {
+            self.client.note_parcels_broadcast(&broadcast);
         }
     }
 }
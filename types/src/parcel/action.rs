@@ -21,7 +21,7 @@ use primitives::{Bytes, H256, U256};
 use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
 use super::super::transaction::Transaction;
-use super::super::ShardId;
+use super::super::{BlockNumber, ShardId};
 
 const ASSET_TRANSACTION_GROUP: u8 = 1;
 const PAYMENT: u8 = 2;
@@ -29,6 +29,9 @@ const SET_REGULAR_KEY: u8 = 3;
 const CREATE_SHARD: u8 = 4;
 const SET_SHARD_OWNERS: u8 = 5;
 const SET_SHARD_USERS: u8 = 6;
+const REGISTER_NAME: u8 = 7;
+const UPDATE_NAME: u8 = 8;
+const TRANSFER_NAME: u8 = 9;
 const CUSTOM: u8 = 0xFF;
 
 #[derive(Debug, Clone, PartialEq, Eq, RlpDecodable, RlpEncodable)]
@@ -63,6 +66,20 @@ pub enum Action {
         shard_id: ShardId,
         users: Vec<Address>,
     },
+    /// Registers the name for `duration` blocks, or extends the registration if the sender owns it.
+    RegisterName {
+        name: String,
+        value: Bytes,
+        duration: BlockNumber,
+    },
+    UpdateName {
+        name: String,
+        value: Bytes,
+    },
+    TransferName {
+        name: String,
+        receiver: Address,
+    },
     Custom(Bytes),
 }
 
@@ -93,6 +110,19 @@ impl HeapSizeOf for Action {
                 shard_id: _,
                 users,
             } => users.heap_size_of_children(),
+            Action::RegisterName {
+                name,
+                value,
+                ..
+            }
+            | Action::UpdateName {
+                name,
+                value,
+            } => name.heap_size_of_children() + value.heap_size_of_children(),
+            Action::TransferName {
+                name,
+                ..
+            } => name.heap_size_of_children(),
             _ => 0,
         }
     }
@@ -150,6 +180,35 @@ impl Encodable for Action {
                 s.append(shard_id);
                 s.append_list(users);
             }
+            Action::RegisterName {
+                name,
+                value,
+                duration,
+            } => {
+                s.begin_list(4);
+                s.append(&REGISTER_NAME);
+                s.append(name);
+                s.append(value);
+                s.append(duration);
+            }
+            Action::UpdateName {
+                name,
+                value,
+            } => {
+                s.begin_list(3);
+                s.append(&UPDATE_NAME);
+                s.append(name);
+                s.append(value);
+            }
+            Action::TransferName {
+                name,
+                receiver,
+            } => {
+                s.begin_list(3);
+                s.append(&TRANSFER_NAME);
+                s.append(name);
+                s.append(receiver);
+            }
             Action::Custom(bytes) => {
                 s.begin_list(2);
                 s.append(&CUSTOM);
@@ -213,6 +272,34 @@ impl Decodable for Action {
                     users: rlp.list_at(2)?,
                 })
             }
+            REGISTER_NAME => {
+                if rlp.item_count()? != 4 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                Ok(Action::RegisterName {
+                    name: rlp.val_at(1)?,
+                    value: rlp.val_at(2)?,
+                    duration: rlp.val_at(3)?,
+                })
+            }
+            UPDATE_NAME => {
+                if rlp.item_count()? != 3 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                Ok(Action::UpdateName {
+                    name: rlp.val_at(1)?,
+                    value: rlp.val_at(2)?,
+                })
+            }
+            TRANSFER_NAME => {
+                if rlp.item_count()? != 3 {
+                    return Err(DecoderError::RlpIncorrectListLen)
+                }
+                Ok(Action::TransferName {
+                    name: rlp.val_at(1)?,
+                    receiver: rlp.val_at(2)?,
+                })
+            }
             CUSTOM => {
                 if rlp.item_count()? != 2 {
                     return Err(DecoderError::RlpIncorrectListLen)
@@ -243,4 +330,21 @@ mod tests {
             users: vec![Address::random(), Address::random()],
         });
     }
+
+    #[test]
+    fn encode_and_decode_name_actions() {
+        rlp_encode_and_decode_test!(Action::RegisterName {
+            name: "codechain".to_string(),
+            value: vec![1, 2, 3],
+            duration: 100,
+        });
+        rlp_encode_and_decode_test!(Action::UpdateName {
+            name: "codechain".to_string(),
+            value: vec![],
+        });
+        rlp_encode_and_decode_test!(Action::TransferName {
+            name: "codechain".to_string(),
+            receiver: Address::random(),
+        });
+    }
 }
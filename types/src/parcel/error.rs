@@ -22,7 +22,7 @@ use rlp::{Decodable, DecoderError, Encodable, RlpStream, UntrustedRlp};
 
 use super::super::transaction::Error as TransactionError;
 use super::super::util::unexpected::Mismatch;
-use super::super::ShardId;
+use super::super::{BlockNumber, ShardId};
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
 #[serde(tag = "type", content = "content")]
@@ -78,6 +78,33 @@ pub enum Error {
     InvalidTransaction(TransactionError),
     InsufficientPermission,
     NewOwnersMustContainSender,
+    /// The account would be left with a balance below the minimum balance of the chain.
+    BelowMinBalance {
+        address: Address,
+        balance: U256,
+        min_balance: U256,
+    },
+    /// The name is empty, too long or has characters other than lowercase letters, digits and hyphens.
+    InvalidName(String),
+    /// The name is registered to another account and not expired.
+    NameAlreadyRegistered(String),
+    /// The name is not registered or expired.
+    NameNotRegistered(String),
+    /// The sender already has as many parcels in the queue as it's allowed.
+    SenderLimitReached,
+    /// Parcel was not imported to the queue because the limit of the parcels with future nonces has been reached.
+    FutureLimitReached,
+    /// The action takes effect at or before the block which includes it.
+    ActivationTooEarly {
+        /// The earliest block number allowed.
+        minimal: BlockNumber,
+        got: BlockNumber,
+    },
+    /// The name would be registered for more blocks than `max`.
+    NameDurationTooLong {
+        max: BlockNumber,
+        got: BlockNumber,
+    },
 }
 
 const ERROR_ID_PARCEL_ALREADY_IMPORTED: u8 = 1u8;
@@ -101,6 +128,14 @@ const ERROR_ID_INVALID_TRANSFER_DESTINATION: u8 = 19u8;
 const ERROR_ID_INVALID_TRANSACTION: u8 = 20u8;
 const ERROR_ID_INSUFFICIENT_PERMISSION: u8 = 21u8;
 const ERROR_ID_NEW_OWNERS_MUST_CONTAIN_SENDER: u8 = 22u8;
+const ERROR_ID_BELOW_MIN_BALANCE: u8 = 23u8;
+const ERROR_ID_INVALID_NAME: u8 = 24u8;
+const ERROR_ID_NAME_ALREADY_REGISTERED: u8 = 25u8;
+const ERROR_ID_NAME_NOT_REGISTERED: u8 = 26u8;
+const ERROR_ID_SENDER_LIMIT_REACHED: u8 = 27u8;
+const ERROR_ID_FUTURE_LIMIT_REACHED: u8 = 28u8;
+const ERROR_ID_ACTIVATION_TOO_EARLY: u8 = 29u8;
+const ERROR_ID_NAME_DURATION_TOO_LONG: u8 = 30u8;
 
 impl Encodable for Error {
     fn rlp_append(&self, s: &mut RlpStream) {
@@ -140,6 +175,28 @@ impl Encodable for Error {
             Error::InvalidTransaction(err) => s.begin_list(2).append(&ERROR_ID_INVALID_TRANSACTION).append(err),
             Error::InsufficientPermission => s.begin_list(1).append(&ERROR_ID_INSUFFICIENT_PERMISSION),
             Error::NewOwnersMustContainSender => s.begin_list(1).append(&ERROR_ID_NEW_OWNERS_MUST_CONTAIN_SENDER),
+            Error::BelowMinBalance {
+                address,
+                balance,
+                min_balance,
+            } => {
+                s.begin_list(4).append(&ERROR_ID_BELOW_MIN_BALANCE).append(address).append(balance).append(min_balance)
+            }
+            Error::InvalidName(name) => s.begin_list(2).append(&ERROR_ID_INVALID_NAME).append(name),
+            Error::NameAlreadyRegistered(name) => {
+                s.begin_list(2).append(&ERROR_ID_NAME_ALREADY_REGISTERED).append(name)
+            }
+            Error::NameNotRegistered(name) => s.begin_list(2).append(&ERROR_ID_NAME_NOT_REGISTERED).append(name),
+            Error::SenderLimitReached => s.begin_list(1).append(&ERROR_ID_SENDER_LIMIT_REACHED),
+            Error::FutureLimitReached => s.begin_list(1).append(&ERROR_ID_FUTURE_LIMIT_REACHED),
+            Error::ActivationTooEarly {
+                minimal,
+                got,
+            } => s.begin_list(3).append(&ERROR_ID_ACTIVATION_TOO_EARLY).append(minimal).append(got),
+            Error::NameDurationTooLong {
+                max,
+                got,
+            } => s.begin_list(3).append(&ERROR_ID_NAME_DURATION_TOO_LONG).append(max).append(got),
         };
     }
 }
@@ -179,6 +236,24 @@ impl Decodable for Error {
             ERROR_ID_INVALID_TRANSACTION => Error::InvalidTransaction(rlp.val_at(1)?),
             ERROR_ID_INSUFFICIENT_PERMISSION => Error::InsufficientPermission,
             ERROR_ID_NEW_OWNERS_MUST_CONTAIN_SENDER => Error::NewOwnersMustContainSender,
+            ERROR_ID_BELOW_MIN_BALANCE => Error::BelowMinBalance {
+                address: rlp.val_at(1)?,
+                balance: rlp.val_at(2)?,
+                min_balance: rlp.val_at(3)?,
+            },
+            ERROR_ID_INVALID_NAME => Error::InvalidName(rlp.val_at(1)?),
+            ERROR_ID_NAME_ALREADY_REGISTERED => Error::NameAlreadyRegistered(rlp.val_at(1)?),
+            ERROR_ID_NAME_NOT_REGISTERED => Error::NameNotRegistered(rlp.val_at(1)?),
+            ERROR_ID_SENDER_LIMIT_REACHED => Error::SenderLimitReached,
+            ERROR_ID_FUTURE_LIMIT_REACHED => Error::FutureLimitReached,
+            ERROR_ID_ACTIVATION_TOO_EARLY => Error::ActivationTooEarly {
+                minimal: rlp.val_at(1)?,
+                got: rlp.val_at(2)?,
+            },
+            ERROR_ID_NAME_DURATION_TOO_LONG => Error::NameDurationTooLong {
+                max: rlp.val_at(1)?,
+                got: rlp.val_at(2)?,
+            },
             _ => return Err(DecoderError::Custom("Invalid parcel error")),
         })
     }
@@ -220,6 +295,24 @@ impl Display for Error {
             Error::InvalidTransaction(err) => format!("Parcel has an invalid transaction: {}", err).to_string(),
             Error::InsufficientPermission => "Sender doesn't have a permission".to_string(),
             Error::NewOwnersMustContainSender => "New owners must contain the sender".to_string(),
+            Error::BelowMinBalance {
+                address,
+                balance,
+                min_balance,
+            } => format!("{} would have {} but the minimum balance is {}", address, balance, min_balance),
+            Error::InvalidName(name) => format!("{:?} is an invalid name", name),
+            Error::NameAlreadyRegistered(name) => format!("{:?} is already registered", name),
+            Error::NameNotRegistered(name) => format!("{:?} is not registered", name),
+            Error::SenderLimitReached => "Too many parcels from the sender".into(),
+            Error::FutureLimitReached => "Too many parcels with future nonces".into(),
+            Error::ActivationTooEarly {
+                minimal,
+                got,
+            } => format!("The activation must be at least {} but it is {}", minimal, got),
+            Error::NameDurationTooLong {
+                max,
+                got,
+            } => format!("The name can be registered for at most {} blocks but it is {}", max, got),
         };
 
         f.write_fmt(format_args!("Parcel error ({})", msg))
@@ -70,12 +70,14 @@ extern crate parking_lot;
 extern crate slab;
 
 mod service;
+mod stats;
 mod worker;
 
 use mio::deprecated::{EventLoop, NotifyError};
 use mio::Token;
 use std::{error, fmt};
 
+pub use stats::{CallbackStats, CALLBACK_BUCKET_BOUNDS_MS};
 pub use worker::LOCAL_STACK_SIZE;
 
 #[derive(Debug)]
@@ -188,10 +190,12 @@ where
     }
 }
 
+pub use service::HandlerId;
 pub use service::IoChannel;
 pub use service::IoContext;
 pub use service::IoManager;
 pub use service::IoService;
+pub use service::DEFAULT_NUM_WORKERS;
 pub use service::StreamToken;
 pub use service::TimerToken;
 pub use service::TOKENS_PER_HANDLER;
@@ -230,4 +234,18 @@ mod tests {
         let service = IoService::<MyMessage>::start().expect("Error creating network service");
         service.register_handler(Arc::new(MyHandler)).unwrap();
     }
+
+    #[test]
+    fn callbacks_are_measured() {
+        let service = IoService::<MyMessage>::with_workers(1).expect("Error creating network service");
+        service.register_handler(Arc::new(MyHandler)).unwrap();
+        service.send_message(MyMessage {
+            data: 1,
+        }).unwrap();
+        ::std::thread::sleep(::std::time::Duration::from_millis(100));
+
+        let stats = service.handler_stats();
+        assert_eq!(1, stats[&0].calls);
+        assert_eq!(1, stats[&0].buckets.iter().sum::<u64>());
+    }
 }
@@ -20,6 +20,7 @@ use mio::timer::Timeout;
 use mio::*;
 use parking_lot::{Mutex, RwLock};
 use slab::Slab;
+use stats::{CallbackStats, IoStats};
 use std::collections::HashMap;
 use std::sync::{Arc, Weak};
 use std::sync::{Condvar as SCondvar, Mutex as SMutex};
@@ -38,6 +39,8 @@ pub type HandlerId = usize;
 /// Maximum number of tokens a handler can use
 pub const TOKENS_PER_HANDLER: usize = 16384;
 const MAX_HANDLERS: usize = 8;
+/// The number of the threads which run the callbacks of the handlers.
+pub const DEFAULT_NUM_WORKERS: usize = 4;
 
 /// Messages used to communicate with the event loop from other threads.
 #[derive(Clone)]
@@ -193,6 +196,7 @@ where
     workers: Vec<Worker>,
     worker_channel: chase_lev::Worker<Work<Message>>,
     work_ready: Arc<SCondvar>,
+    stats: Arc<IoStats>,
 }
 
 impl<Message> IoManager<Message>
@@ -203,9 +207,10 @@ where
     pub fn start(
         event_loop: &mut EventLoop<IoManager<Message>>,
         handlers: Arc<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>,
+        num_workers: usize,
+        stats: Arc<IoStats>,
     ) -> Result<(), IoError> {
         let (worker, stealer) = chase_lev::deque();
-        let num_workers = 4;
         let work_ready_mutex = Arc::new(SMutex::new(()));
         let work_ready = Arc::new(SCondvar::new());
         let workers = (0..num_workers)
@@ -216,6 +221,7 @@ where
                     IoChannel::new(event_loop.channel(), Arc::downgrade(&handlers)),
                     work_ready.clone(),
                     work_ready_mutex.clone(),
+                    Arc::clone(&stats),
                 )
             })
             .collect();
@@ -226,6 +232,7 @@ where
             worker_channel: worker,
             workers,
             work_ready,
+            stats,
         };
         event_loop.run(&mut io)?;
         Ok(())
@@ -323,6 +330,7 @@ where
             } => {
                 // TODO: flush event loop
                 self.handlers.write().remove(handler_id);
+                self.stats.remove(handler_id);
                 // unregister timers
                 let mut timers = self.timers.write();
                 let to_remove: Vec<_> =
@@ -527,6 +535,7 @@ where
     thread: Mutex<Option<JoinHandle<()>>>,
     host_channel: Mutex<Sender<IoMessage<Message>>>,
     handlers: Arc<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>,
+    stats: Arc<IoStats>,
 }
 
 impl<Message> IoService<Message>
@@ -535,19 +544,28 @@ where
 {
     /// Starts IO event loop
     pub fn start() -> Result<IoService<Message>, IoError> {
+        Self::with_workers(DEFAULT_NUM_WORKERS)
+    }
+
+    /// Starts IO event loop whose callbacks run on `num_workers` threads.
+    pub fn with_workers(num_workers: usize) -> Result<IoService<Message>, IoError> {
+        assert_ne!(0, num_workers, "IoService needs at least one worker");
         let mut config = EventLoopBuilder::new();
         config.messages_per_tick(1024);
         let mut event_loop = config.build().expect("Error creating event loop");
         let channel = event_loop.channel();
         let handlers = Arc::new(RwLock::new(Slab::new(MAX_HANDLERS)));
         let h = handlers.clone();
+        let stats = Arc::new(IoStats::default());
+        let s = Arc::clone(&stats);
         let thread = thread::spawn(move || {
-            IoManager::<Message>::start(&mut event_loop, h).expect("Error starting IO service");
+            IoManager::<Message>::start(&mut event_loop, h, num_workers, s).expect("Error starting IO service");
         });
         Ok(IoService {
             thread: Mutex::new(Some(thread)),
             host_channel: Mutex::new(channel),
             handlers,
+            stats,
         })
     }
 
@@ -586,6 +604,11 @@ where
     pub fn channel(&self) -> IoChannel<Message> {
         IoChannel::new(self.host_channel.lock().clone(), Arc::downgrade(&self.handlers))
     }
+
+    /// The execution time of the callbacks by the handlers.
+    pub fn handler_stats(&self) -> HashMap<HandlerId, CallbackStats> {
+        self.stats.snapshot()
+    }
 }
 
 impl<Message> Drop for IoService<Message>
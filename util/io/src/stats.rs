@@ -0,0 +1,105 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use service::HandlerId;
+
+/// The upper bounds, in milliseconds, of the buckets of `CallbackStats::buckets`.
+/// The last bucket counts the callbacks which took longer than the last bound.
+pub const CALLBACK_BUCKET_BOUNDS_MS: [u64; 4] = [1, 10, 100, 1000];
+
+/// The execution time of the callbacks of a handler.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CallbackStats {
+    pub calls: u64,
+    pub total: Duration,
+    pub max: Duration,
+    pub buckets: [u64; 5],
+}
+
+impl CallbackStats {
+    fn record(&mut self, elapsed: Duration) {
+        self.calls += 1;
+        self.total += elapsed;
+        if self.max < elapsed {
+            self.max = elapsed;
+        }
+        let elapsed_ms = as_millis(elapsed);
+        let index = CALLBACK_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| elapsed_ms < *bound)
+            .unwrap_or(CALLBACK_BUCKET_BOUNDS_MS.len());
+        self.buckets[index] += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct IoStats {
+    handlers: Mutex<HashMap<HandlerId, CallbackStats>>,
+}
+
+impl IoStats {
+    pub fn record(&self, handler_id: HandlerId, elapsed: Duration) {
+        self.handlers.lock().entry(handler_id).or_insert_with(Default::default).record(elapsed);
+    }
+
+    pub fn remove(&self, handler_id: HandlerId) {
+        self.handlers.lock().remove(&handler_id);
+    }
+
+    pub fn snapshot(&self) -> HashMap<HandlerId, CallbackStats> {
+        self.handlers.lock().clone()
+    }
+}
+
+pub fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_to_buckets() {
+        let stats = IoStats::default();
+        stats.record(0, Duration::from_millis(0));
+        stats.record(0, Duration::from_millis(5));
+        stats.record(0, Duration::from_millis(10));
+        stats.record(0, Duration::from_millis(2000));
+        stats.record(1, Duration::from_millis(150));
+
+        let snapshot = stats.snapshot();
+        let first = &snapshot[&0];
+        assert_eq!(4, first.calls);
+        assert_eq!(Duration::from_millis(2015), first.total);
+        assert_eq!(Duration::from_millis(2000), first.max);
+        assert_eq!([1, 1, 1, 0, 1], first.buckets);
+        assert_eq!([0, 0, 0, 1, 0], snapshot[&1].buckets);
+    }
+
+    #[test]
+    fn removed_handler_has_no_stats() {
+        let stats = IoStats::default();
+        stats.record(0, Duration::from_millis(3));
+        stats.remove(0);
+        assert!(stats.snapshot().is_empty());
+    }
+}
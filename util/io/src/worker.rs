@@ -16,15 +16,19 @@
 
 use crossbeam::sync::chase_lev;
 use service::{HandlerId, IoChannel, IoContext};
+use stats::{as_millis, IoStats};
 use std::cell::Cell;
 use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::Instant;
 use IoHandler;
 
 use std::sync::{Condvar as SCondvar, Mutex as SMutex};
 
 const STACK_SIZE: usize = 16 * 1024 * 1024;
+/// A callback taking longer than this blocks the other handlers of the service.
+const SLOW_CALLBACK_THRESHOLD_MS: u64 = 100;
 
 thread_local! {
     /// Stack size
@@ -41,6 +45,18 @@ pub enum WorkType<Message> {
     Message(Message),
 }
 
+impl<Message> WorkType<Message> {
+    fn name(&self) -> &'static str {
+        match self {
+            WorkType::Readable => "stream_readable",
+            WorkType::Writable => "stream_writable",
+            WorkType::Hup => "stream_hup",
+            WorkType::Timeout => "timeout",
+            WorkType::Message(_) => "message",
+        }
+    }
+}
+
 pub struct Work<Message> {
     pub work_type: WorkType<Message>,
     pub token: usize,
@@ -65,6 +81,7 @@ impl Worker {
         channel: IoChannel<Message>,
         wait: Arc<SCondvar>,
         wait_mutex: Arc<SMutex<()>>,
+        stats: Arc<IoStats>,
     ) -> Worker
     where
         Message: Send + Sync + Clone + 'static, {
@@ -81,7 +98,7 @@ impl Worker {
                 .name(format!("IO Worker #{}", index))
                 .spawn(move || {
                     LOCAL_STACK_SIZE.with(|val| val.set(STACK_SIZE));
-                    Worker::work_loop(stealer, channel.clone(), wait, wait_mutex.clone(), deleting)
+                    Worker::work_loop(stealer, channel.clone(), wait, wait_mutex.clone(), deleting, stats)
                 })
                 .expect("Error creating worker thread"),
         );
@@ -94,6 +111,7 @@ impl Worker {
         wait: Arc<SCondvar>,
         wait_mutex: Arc<SMutex<()>>,
         deleting: Arc<AtomicBool>,
+        stats: Arc<IoStats>,
     ) where
         Message: Send + Sync + Clone + 'static, {
         loop {
@@ -107,16 +125,18 @@ impl Worker {
 
             while !deleting.load(AtomicOrdering::Acquire) {
                 match stealer.steal() {
-                    chase_lev::Steal::Data(work) => Worker::do_work(work, channel.clone()),
+                    chase_lev::Steal::Data(work) => Worker::do_work(work, channel.clone(), &stats),
                     _ => break,
                 }
             }
         }
     }
 
-    fn do_work<Message>(work: Work<Message>, channel: IoChannel<Message>)
+    fn do_work<Message>(work: Work<Message>, channel: IoChannel<Message>, stats: &IoStats)
     where
         Message: Send + Sync + Clone + 'static, {
+        let work_name = work.work_type.name();
+        let started_at = Instant::now();
         match work.work_type {
             WorkType::Readable => {
                 if let Err(err) = work.handler.stream_readable(&IoContext::new(channel, work.handler_id), work.token) {
@@ -144,6 +164,19 @@ impl Worker {
                 }
             }
         }
+        let elapsed = started_at.elapsed();
+        stats.record(work.handler_id, elapsed);
+        let elapsed_ms = as_millis(elapsed);
+        if elapsed_ms >= SLOW_CALLBACK_THRESHOLD_MS {
+            cwarn!(
+                IO,
+                "{} of handler #{} took {}ms for token {}. It blocks the other callbacks of the worker.",
+                work_name,
+                work.handler_id,
+                elapsed_ms,
+                work.token
+            );
+        }
     }
 }
 
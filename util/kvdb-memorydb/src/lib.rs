@@ -135,4 +135,8 @@ impl KeyValueDB for InMemory {
     fn restore(&self, _new_db: &str) -> Result<()> {
         Err("Attempted to restore in-memory database".into())
     }
+
+    fn backup(&self, _path: &str) -> Result<()> {
+        Err("Attempted to back up in-memory database".into())
+    }
 }
@@ -31,7 +31,7 @@ use std::cmp;
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
-use std::{fs, io, mem, result};
+use std::{fs, io, iter, mem, result};
 
 use interleaved_ordered::{interleave_ordered, InterleaveOrdered};
 use parking_lot::{Mutex, MutexGuard, RwLock};
@@ -53,6 +53,9 @@ use std::process::Command;
 
 const DB_DEFAULT_MEMORY_BUDGET_MB: usize = 128;
 
+// The number of key-value pairs written at once while backing up.
+const BACKUP_BATCH_SIZE: usize = 1024;
+
 enum KeyState {
     Insert(DBValue),
     InsertCompressed(DBValue),
@@ -168,6 +171,8 @@ pub struct DatabaseConfig {
     pub columns: Option<u32>,
     /// Should we keep WAL enabled?
     pub wal: bool,
+    /// Open an existing database and reject all the writes to it.
+    pub read_only: bool,
 }
 
 impl DatabaseConfig {
@@ -196,6 +201,7 @@ impl Default for DatabaseConfig {
             compaction: CompactionProfile::default(),
             columns: None,
             wal: true,
+            read_only: false,
         }
     }
 }
@@ -239,6 +245,9 @@ fn col_config(config: &DatabaseConfig, block_opts: &BlockBasedOptions) -> Result
     opts.set_target_file_size_base(config.compaction.initial_file_size);
 
     opts.set_parsed_options("compression_per_level=")?;
+    if config.read_only {
+        opts.set_parsed_options("disable_auto_compactions=true")?;
+    }
 
     Ok(opts)
 }
@@ -292,12 +301,18 @@ impl Database {
             opts.set_parsed_options(&format!("rate_limiter_bytes_per_sec={}", rate_limit))?;
         }
         opts.set_use_fsync(false);
-        opts.create_if_missing(true);
+        opts.create_if_missing(!config.read_only);
         opts.set_max_open_files(config.max_open_files);
         opts.set_parsed_options("keep_log_file_num=1")?;
         opts.set_parsed_options("bytes_per_sync=1048576")?;
         opts.set_db_write_buffer_size(config.memory_budget_per_col() / 2);
         opts.increase_parallelism(cmp::max(1, ::num_cpus::get() as i32 / 2));
+        // The binding has no read-only open of RocksDB, so the database is opened as usual without any background
+        // write to the files. It still takes the lock, so a running node's database must be copied first.
+        if config.read_only {
+            opts.set_parsed_options("disable_auto_compactions=true")?;
+            opts.set_parsed_options("avoid_flush_during_recovery=true")?;
+        }
 
         let mut block_opts = BlockBasedOptions::new();
 
@@ -311,6 +326,9 @@ impl Database {
         // attempt database repair if it has been previously marked as corrupted
         let db_corrupted = Path::new(path).join(Database::CORRUPTION_FILE_NAME);
         if db_corrupted.exists() {
+            if config.read_only {
+                return Err("DB has been previously marked as corrupted. Open it writable to repair".into())
+            }
             warn!("DB has been previously marked as corrupted, attempting repair");
             DB::repair(&opts, path)?;
             fs::remove_file(db_corrupted)?;
@@ -344,6 +362,7 @@ impl Database {
                             .collect();
                         Ok(db)
                     }
+                    Err(e) if config.read_only => Err(e),
                     Err(_) => {
                         // retry and create CFs
                         match DB::open_cf(&opts, path, &[], &[]) {
@@ -365,7 +384,7 @@ impl Database {
 
         let db = match db {
             Ok(db) => db,
-            Err(ref s) if is_corrupted(s) => {
+            Err(ref s) if is_corrupted(s) && !config.read_only => {
                 warn!("DB corrupted: {}, attempting repair", s);
                 DB::repair(&opts, path)?;
 
@@ -381,6 +400,9 @@ impl Database {
                     }
                 }
             }
+            Err(ref s) if config.read_only && s.contains("LOCK") => {
+                return Err(format!("The database is used by another process. Open a copy of it read-only: {}", s).into())
+            }
             Err(s) => return Err(s.into()),
         };
         let num_cols = cfs.len();
@@ -405,6 +427,13 @@ impl Database {
         DBTransaction::new()
     }
 
+    fn check_writable(&self) -> Result<()> {
+        if self.config.read_only {
+            return Err("Database is opened read-only".into())
+        }
+        Ok(())
+    }
+
 
     fn to_overlay_column(col: Option<u32>) -> usize {
         col.map_or(0, |c| (c + 1) as usize)
@@ -497,6 +526,10 @@ impl Database {
 
     /// Commit buffered changes to database.
     pub fn flush(&self) -> Result<()> {
+        if self.config.read_only && self.overlay.read().iter().all(HashMap::is_empty) {
+            return Ok(())
+        }
+        self.check_writable()?;
         let mut lock = self.flushing_lock.lock();
         // If RocksDB batch allocation fails the thread gets terminated and the lock is released.
         // The value inside the lock is used to detect that.
@@ -512,6 +545,7 @@ impl Database {
 
     /// Commit parcel to database.
     pub fn write(&self, tr: DBTransaction) -> Result<()> {
+        self.check_writable()?;
         match *self.db.read() {
             Some(DBAndColumns {
                 ref db,
@@ -675,6 +709,7 @@ impl Database {
 
     /// Restore the database from a copy at given path.
     pub fn restore(&self, new_db: &str) -> Result<()> {
+        self.check_writable()?;
         self.close();
 
         let mut backup_db = PathBuf::from(&self.path);
@@ -714,6 +749,53 @@ impl Database {
         Ok(())
     }
 
+    /// Copy the flushed data into a new database at given path.
+    /// Every column is read from one snapshot, so the copy is consistent while the database is being written.
+    pub fn backup(&self, path: &str) -> Result<()> {
+        if Path::new(path).exists() {
+            return Err(format!("{} already exists", path).into())
+        }
+        match *self.db.read() {
+            Some(DBAndColumns {
+                ref db,
+                ref cfs,
+            }) => {
+                let backup = Database::open(
+                    &DatabaseConfig {
+                        read_only: false,
+                        ..self.config.clone()
+                    },
+                    path,
+                )?;
+
+                let snapshot = db.snapshot();
+                let mut read_opts = ReadOptions::new();
+                read_opts.set_verify_checksums(false);
+                read_opts.set_snapshot(&snapshot);
+
+                for col in iter::once(None).chain((0..cfs.len() as u32).map(Some)) {
+                    let iter = col.map_or_else(
+                        || db.iterator_opt(IteratorMode::Start, &read_opts),
+                        |c| {
+                            db.iterator_cf_opt(cfs[c as usize], IteratorMode::Start, &read_opts)
+                                .expect("iterator params are valid; qed")
+                        },
+                    );
+                    let mut batch = DBTransaction::with_capacity(BACKUP_BATCH_SIZE);
+                    for (key, value) in iter {
+                        batch.put(col, &key, &value);
+                        if batch.ops.len() == BACKUP_BATCH_SIZE {
+                            backup.write(mem::replace(&mut batch, DBTransaction::with_capacity(BACKUP_BATCH_SIZE)))?;
+                        }
+                    }
+                    backup.write(batch)?;
+                }
+                Ok(())
+            }
+            None => Err("Database is closed".into()),
+        }
+    }
+
     /// The number of non-default column families.
     pub fn num_columns(&self) -> u32 {
         self.db
@@ -732,6 +814,7 @@ impl Database {
 
     /// Drop a column family.
     pub fn drop_column(&self) -> Result<()> {
+        self.check_writable()?;
         match *self.db.write() {
             Some(DBAndColumns {
                 ref mut db,
@@ -750,6 +833,7 @@ impl Database {
 
     /// Add a column family.
     pub fn add_column(&self) -> Result<()> {
+        self.check_writable()?;
         match *self.db.write() {
             Some(DBAndColumns {
                 ref mut db,
@@ -805,6 +889,10 @@ impl KeyValueDB for Database {
     fn restore(&self, new_db: &str) -> Result<()> {
         Database::restore(self, new_db)
     }
+
+    fn backup(&self, path: &str) -> Result<()> {
+        Database::backup(self, path)
+    }
 }
 
 impl Drop for Database {
@@ -883,6 +971,60 @@ mod tests {
         test_db(&DatabaseConfig::default());
     }
 
+    #[test]
+    fn read_only_rejects_writes() {
+        let tempdir = TempDir::new("").unwrap();
+        let path = tempdir.path().to_str().unwrap();
+        let key = H256::from("02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc");
+        {
+            let db = Database::open_default(path).unwrap();
+            let mut batch = db.transaction();
+            batch.put(None, &key, b"cat");
+            db.write(batch).unwrap();
+        }
+
+        let config = DatabaseConfig {
+            read_only: true,
+            ..DatabaseConfig::default()
+        };
+        let db = Database::open(&config, path).unwrap();
+        assert_eq!(&*db.get(None, &key).unwrap().unwrap(), b"cat");
+        db.flush().unwrap();
+
+        let mut batch = db.transaction();
+        batch.delete(None, &key);
+        assert!(db.write(batch).is_err());
+
+        let mut batch = db.transaction();
+        batch.delete(None, &key);
+        db.write_buffered(batch);
+        assert!(db.flush().is_err());
+        assert!(db.add_column().is_err());
+    }
+
+    #[test]
+    fn read_only_fails_while_database_is_opened() {
+        let tempdir = TempDir::new("").unwrap();
+        let path = tempdir.path().to_str().unwrap();
+        let _db = Database::open_default(path).unwrap();
+
+        let config = DatabaseConfig {
+            read_only: true,
+            ..DatabaseConfig::default()
+        };
+        assert!(Database::open(&config, path).is_err());
+    }
+
+    #[test]
+    fn read_only_does_not_create_database() {
+        let tempdir = TempDir::new("").unwrap();
+        let config = DatabaseConfig {
+            read_only: true,
+            ..DatabaseConfig::default()
+        };
+        assert!(Database::open(&config, tempdir.path().join("db").to_str().unwrap()).is_err());
+    }
+
     #[test]
     #[cfg(target_os = "linux")]
     fn df_to_rotational() {
@@ -948,4 +1090,29 @@ mod tests {
             assert_eq!(db.num_columns(), 0);
         }
     }
+
+    #[test]
+    fn backup_copies_every_column() {
+        let config = DatabaseConfig::with_columns(Some(2));
+        let tempdir = TempDir::new("").unwrap();
+        let backup_path = tempdir.path().join("backup");
+
+        let db = Database::open(&config, tempdir.path().join("db").to_str().unwrap()).unwrap();
+        let mut batch = db.transaction();
+        batch.put(None, b"key0", b"value0");
+        batch.put(Some(1), b"key1", b"value1");
+        db.write(batch).unwrap();
+
+        db.backup(backup_path.to_str().unwrap()).unwrap();
+        assert!(db.backup(backup_path.to_str().unwrap()).is_err());
+
+        let mut batch = db.transaction();
+        batch.put(Some(1), b"key2", b"value2");
+        db.write(batch).unwrap();
+
+        let backup = Database::open(&config, backup_path.to_str().unwrap()).unwrap();
+        assert_eq!(&*backup.get(None, b"key0").unwrap().unwrap(), b"value0");
+        assert_eq!(&*backup.get(Some(1), b"key1").unwrap().unwrap(), b"value1");
+        assert!(backup.get(Some(1), b"key2").unwrap().is_none());
+    }
 }
@@ -179,4 +179,7 @@ pub trait KeyValueDB: Sync + Send {
 
     /// Attempt to replace this database with a new one located at the given path.
     fn restore(&self, new_db: &str) -> Result<()>;
+
+    /// Attempt to copy this database to a new one located at the given path.
+    fn backup(&self, path: &str) -> Result<()>;
 }
@@ -17,18 +17,25 @@
 extern crate atty;
 extern crate colored;
 extern crate env_logger;
+#[macro_use]
+extern crate lazy_static;
 extern crate log;
+#[macro_use]
+extern crate serde_json;
 extern crate time;
 
 mod logger;
 mod macros;
+mod span;
 
 use log::SetLoggerError;
 
+pub use logger::{reset_levels, set_level, set_levels};
 pub use logger::Config as LoggerConfig;
 use logger::Logger;
+pub use span::{current_span, enter_span, new_span, SpanGuard};
 
-pub use log::Level;
+pub use log::{Level, LevelFilter};
 
 pub fn init(config: &LoggerConfig) -> Result<(), SetLoggerError> {
     let logger = Logger::new(config);
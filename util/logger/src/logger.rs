@@ -14,59 +14,174 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use std::thread;
 use time;
 
 use atty;
 use colored::Colorize;
 use env_logger::filter::{Builder as FilterBuilder, Filter};
-use log::{LevelFilter, Log, Metadata, Record};
+use log::{self, LevelFilter, Log, Metadata, Record};
+
+use super::span::current_span;
+
+lazy_static! {
+    static ref FILTER: RwLock<TargetFilter> = RwLock::new(TargetFilter::new(None));
+}
+
+/// Increased whenever `FILTER` changes, so that the threads know their cached filters are stale.
+static FILTER_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The filter which the thread reads without taking the lock, and the generation it's cached at.
+    static CACHED_FILTER: RefCell<Option<(usize, Arc<Filter>)>> = RefCell::new(None);
+}
+
+/// Calls `f` with the current filter. The lock is taken only when the filter changes after the thread cached it.
+fn with_filter<T>(f: &Fn(&Filter) -> T) -> T {
+    let generation = FILTER_GENERATION.load(Ordering::Acquire);
+    let cached = CACHED_FILTER.try_with(|cached| {
+        let mut cached = cached.borrow_mut();
+        let is_stale = cached.as_ref().map_or(true, |(cached_generation, _)| *cached_generation != generation);
+        if is_stale {
+            *cached = Some((generation, Arc::clone(&FILTER.read().unwrap().filter)));
+        }
+        f(&cached.as_ref().expect("The filter is cached above").1)
+    });
+    // The thread local is gone while the thread exits.
+    cached.unwrap_or_else(|_| f(&FILTER.read().unwrap().filter))
+}
+
+/// Replaces the filter and tells the threads to drop their cached ones.
+fn update_filter(update: &Fn(&mut TargetFilter)) {
+    let mut filter = FILTER.write().unwrap();
+    update(&mut filter);
+    filter.rebuild();
+    log::set_max_level(filter.filter.filter());
+    FILTER_GENERATION.fetch_add(1, Ordering::Release);
+}
 
 pub struct Config {
     pub instance_id: usize,
+    /// The levels of the targets, in the same syntax as `RUST_LOG`. `RUST_LOG` takes precedence over them.
+    pub levels: Option<String>,
+    /// Writes the logs as JSON objects, one per line.
+    pub json: bool,
 }
 
 impl Config {
     pub fn new(instance_id: usize) -> Self {
         Self {
             instance_id,
+            levels: None,
+            json: false,
         }
     }
 }
 
-pub struct Logger {
-    instance_id: usize,
-    filter: Filter,
+/// The filter built from the initial levels and the levels changed at runtime.
+struct TargetFilter {
+    levels: Option<String>,
+    overrides: BTreeMap<String, LevelFilter>,
+    filter: Arc<Filter>,
 }
 
-impl Logger {
-    pub fn new(config: &Config) -> Self {
+impl TargetFilter {
+    fn new(levels: Option<String>) -> Self {
+        let mut filter = Self {
+            levels,
+            overrides: BTreeMap::new(),
+            filter: Arc::new(FilterBuilder::new().build()),
+        };
+        filter.rebuild();
+        filter
+    }
+
+    fn rebuild(&mut self) {
         let mut builder = FilterBuilder::new();
         builder.filter(None, LevelFilter::Info);
 
+        if let Some(levels) = &self.levels {
+            builder.parse(levels);
+        }
         if let Ok(rust_log) = env::var("RUST_LOG") {
             builder.parse(&rust_log);
         }
+        for (target, level) in &self.overrides {
+            builder.filter(Some(target.as_str()), *level);
+        }
+        self.filter = Arc::new(builder.build());
+    }
+}
+
+/// Changes the level of the target at runtime. It overrides both the configured levels and `RUST_LOG`.
+pub fn set_level(target: &str, level: LevelFilter) {
+    update_filter(&|filter| {
+        filter.overrides.insert(target.to_lowercase(), level);
+    });
+}
+
+/// Drops the levels changed by `set_level`, so that the configured levels and `RUST_LOG` apply again.
+pub fn reset_levels() {
+    update_filter(&|filter| filter.overrides.clear());
+}
+
+/// Replaces the configured levels at runtime. The levels changed by `set_level` are kept.
+pub fn set_levels(levels: Option<String>) {
+    update_filter(&|filter| filter.levels = levels.clone());
+}
+
+pub struct Logger {
+    instance_id: usize,
+    json: bool,
+}
+
+impl Logger {
+    pub fn new(config: &Config) -> Self {
+        update_filter(&|filter| {
+            filter.levels = config.levels.clone();
+            filter.overrides.clear();
+        });
 
         Self {
             instance_id: config.instance_id,
-            filter: builder.build(),
+            json: config.json,
         }
     }
 
     pub fn filter(&self) -> LevelFilter {
-        self.filter.filter()
+        with_filter(&|filter| filter.filter())
+    }
+
+    fn log_json(&self, record: &Record) {
+        let line = json!({
+            "instance": self.instance_id,
+            "timestamp": time::now_utc().rfc3339().to_string(),
+            "thread": thread::current().name().unwrap_or_default(),
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "span": current_span(),
+            "message": record.args().to_string(),
+        });
+        eprintln!("{}", line);
     }
 }
 
 impl Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.filter.enabled(metadata)
+        with_filter(&|filter| filter.enabled(metadata))
     }
 
     fn log(&self, record: &Record) {
-        if self.filter.matches(record) {
+        if with_filter(&|filter| filter.matches(record)) {
+            if self.json {
+                self.log_json(record);
+                return
+            }
             let thread_name = thread::current().name().unwrap_or_default().to_string();
             let timestamp = time::strftime("%Y-%m-%d %H:%M:%S %Z", &time::now()).unwrap();
 
@@ -85,9 +200,39 @@ impl Log for Logger {
             let log_level = record.level();
             let log_target = record.target();
             let log_message = record.args();
-            eprintln!("#{} {} {} {} {}  {}", instance_id, timestamp, thread_name, log_level, log_target, log_message);
+            match current_span() {
+                Some(span) => eprintln!(
+                    "#{} {} {} {} {} [{}]  {}",
+                    instance_id, timestamp, thread_name, log_level, log_target, span, log_message
+                ),
+                None => eprintln!(
+                    "#{} {} {} {} {}  {}",
+                    instance_id, timestamp, thread_name, log_level, log_target, log_message
+                ),
+            }
         }
     }
 
     fn flush(&self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, MetadataBuilder};
+
+    use super::*;
+
+    #[test]
+    fn levels_set_at_runtime_are_reset_to_the_configured_ones() {
+        let target = "logger_test";
+        let info = MetadataBuilder::new().target(target).level(Level::Info).build();
+        set_levels(Some(format!("{}=warn", target)));
+        assert!(!with_filter(&|filter| filter.enabled(&info)));
+
+        set_level(target, LevelFilter::Trace);
+        assert!(with_filter(&|filter| filter.enabled(&info)), "The cached filter must be updated");
+
+        reset_levels();
+        assert!(!with_filter(&|filter| filter.enabled(&info)));
+    }
+}
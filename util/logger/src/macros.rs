@@ -22,6 +22,9 @@ macro_rules! log_target {
     (CLIENT) => {
         "client"
     };
+    (CONFIG) => {
+        "config"
+    };
     (DISCOVERY) => {
         "discovery"
     };
@@ -31,6 +34,9 @@ macro_rules! log_target {
     (EXTERNAL_PARCEL) => {
         "external_parcel"
     };
+    (IMPORT_HOOK) => {
+        "import_hook"
+    };
     (IO) => {
         "io"
     };
@@ -0,0 +1,77 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+static LAST_SPAN: AtomicUsize = ATOMIC_USIZE_INIT;
+
+thread_local! {
+    static CURRENT_SPAN: Cell<Option<u64>> = Cell::new(None);
+}
+
+/// Returns a new correlation id, which is unique in the process.
+pub fn new_span() -> u64 {
+    (LAST_SPAN.fetch_add(1, Ordering::Relaxed) + 1) as u64
+}
+
+/// Returns the correlation id of the span the current thread is in.
+pub fn current_span() -> Option<u64> {
+    CURRENT_SPAN.with(|span| span.get())
+}
+
+/// Tags the logs of the current thread with the correlation id until the guard is dropped.
+pub fn enter_span(id: u64) -> SpanGuard {
+    let previous = CURRENT_SPAN.with(|span| span.replace(Some(id)));
+    SpanGuard {
+        previous,
+    }
+}
+
+pub struct SpanGuard {
+    previous: Option<u64>,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        CURRENT_SPAN.with(|span| span.set(self.previous));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_spans() {
+        assert_eq!(None, current_span());
+        {
+            let _outer = enter_span(1);
+            assert_eq!(Some(1), current_span());
+            {
+                let _inner = enter_span(2);
+                assert_eq!(Some(2), current_span());
+            }
+            assert_eq!(Some(1), current_span());
+        }
+        assert_eq!(None, current_span());
+    }
+
+    #[test]
+    fn new_spans_are_unique() {
+        assert_ne!(new_span(), new_span());
+    }
+}
@@ -33,12 +33,14 @@ use primitives::H256;
 
 mod nibbleslice;
 pub mod node;
+mod proof;
 mod skewed;
 pub mod triedb;
 pub mod triedbmut;
 pub mod triehash;
 
 pub use self::node::Node;
+pub use self::proof::verify_proof;
 pub use skewed::skewed_merkle_root;
 pub use triedb::TrieDB;
 pub use triedbmut::TrieDBMut;
@@ -0,0 +1,104 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+use std::collections::HashMap;
+
+use ccrypto::blake256;
+use primitives::{Bytes, H256};
+
+use super::nibbleslice::NibbleSlice;
+use super::node::Node as RlpNode;
+use super::{Result, TrieError};
+
+/// Verify the proof made by `TrieDB::get_proof` against the root.
+///
+/// Returns the value of the key, or `None` if the proof shows that the key doesn't exist.
+/// Returns an error if a node on the path is missing from the proof.
+pub fn verify_proof(root: &H256, key: &[u8], proof: &[Bytes]) -> Result<Option<Bytes>> {
+    let nodes: HashMap<H256, &[u8]> = proof.iter().map(|node| (blake256(node), node.as_slice())).collect();
+    let path = blake256(key);
+    let mut path = NibbleSlice::new(&path);
+    let mut hash = *root;
+    loop {
+        let node_rlp = *nodes.get(&hash).ok_or_else(|| Box::new(TrieError::IncompleteDatabase(hash)))?;
+        match RlpNode::decoded(node_rlp) {
+            Some(RlpNode::Leaf(partial, value)) => {
+                return Ok(if partial == path {
+                    Some(value.to_vec())
+                } else {
+                    None
+                })
+            }
+            Some(RlpNode::Branch(partial, children)) => {
+                if !path.starts_with(&partial) {
+                    return Ok(None)
+                }
+                match children[path.mid(partial.len()).at(0) as usize] {
+                    Some(child) => {
+                        hash = child;
+                        path = path.mid(partial.len() + 1);
+                    }
+                    None => return Ok(None),
+                }
+            }
+            None => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memorydb::MemoryDB;
+
+    use super::super::{TrieDB, TrieDBMut, TrieMut};
+    use super::*;
+
+    #[test]
+    fn proofs_of_existing_and_missing_keys() {
+        let mut memdb = MemoryDB::new();
+        let mut root = H256::new();
+        {
+            let mut t = TrieDBMut::new(&mut memdb, &mut root);
+            t.insert(b"A", b"ABC").unwrap();
+            t.insert(b"B", b"ABCBA").unwrap();
+            t.insert(b"C", b"CBA").unwrap();
+        }
+        let t = TrieDB::new(&memdb, &root).unwrap();
+
+        let proof = t.get_proof(b"B").unwrap();
+        assert_eq!(Ok(Some(b"ABCBA".to_vec())), verify_proof(&root, b"B", &proof));
+
+        let proof = t.get_proof(b"D").unwrap();
+        assert_eq!(Ok(None), verify_proof(&root, b"D", &proof));
+    }
+
+    #[test]
+    fn proof_without_the_root_is_rejected() {
+        let mut memdb = MemoryDB::new();
+        let mut root = H256::new();
+        {
+            let mut t = TrieDBMut::new(&mut memdb, &mut root);
+            t.insert(b"A", b"ABC").unwrap();
+            t.insert(b"B", b"ABCBA").unwrap();
+        }
+        let t = TrieDB::new(&memdb, &root).unwrap();
+
+        let mut proof = t.get_proof(b"A").unwrap();
+        proof.remove(0);
+        assert_eq!(Err(Box::new(TrieError::IncompleteDatabase(root))), verify_proof(&root, b"A", &proof));
+    }
+}
@@ -16,7 +16,7 @@
 
 use ccrypto::blake256;
 use hashdb::HashDB;
-use primitives::H256;
+use primitives::{Bytes, H256};
 
 use super::nibbleslice::NibbleSlice;
 use super::node::Node as RlpNode;
@@ -71,6 +71,74 @@ impl<'db> TrieDB<'db> {
         self.db
     }
 
+    /// Get the nodes on the path from the root to the key.
+    /// They prove the value of the key, or that the key doesn't exist. See `verify_proof`.
+    pub fn get_proof(&self, key: &[u8]) -> super::Result<Vec<Bytes>> {
+        let path = blake256(key);
+        let mut proof = Vec::new();
+        self.get_proof_aux(NibbleSlice::new(&path), Some(*self.root), &mut proof)?;
+        Ok(proof)
+    }
+
+    fn get_proof_aux(
+        &self,
+        path: NibbleSlice,
+        cur_node_hash: Option<H256>,
+        proof: &mut Vec<Bytes>,
+    ) -> super::Result<()> {
+        let hash = match cur_node_hash {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+        let node_rlp = self.db.get(&hash).ok_or_else(|| Box::new(TrieError::IncompleteDatabase(hash)))?;
+        let next = match RlpNode::decoded(&node_rlp) {
+            Some(RlpNode::Branch(partial, children)) if path.starts_with(&partial) => {
+                Some((path.mid(partial.len() + 1), children[path.mid(partial.len()).at(0) as usize]))
+            }
+            _ => None,
+        };
+        proof.push(node_rlp.to_vec());
+        match next {
+            Some((path, child)) => self.get_proof_aux(path, child, proof),
+            None => Ok(()),
+        }
+    }
+
+    /// Calls `f` with the path and the value of every leaf in the trie.
+    /// The keys are not stored in the trie, so each leaf is identified by its path, which is the hash of its key.
+    pub fn for_each_leaf<F>(&self, mut f: F) -> super::Result<()>
+    where
+        F: FnMut(&H256, &[u8]), {
+        let mut path = Vec::with_capacity(64);
+        self.for_each_leaf_aux(*self.root, &mut path, &mut f)
+    }
+
+    fn for_each_leaf_aux<F>(&self, hash: H256, path: &mut Vec<u8>, f: &mut F) -> super::Result<()>
+    where
+        F: FnMut(&H256, &[u8]), {
+        let node_rlp = self.db.get(&hash).ok_or_else(|| Box::new(TrieError::IncompleteDatabase(hash)))?;
+        let depth = path.len();
+        match RlpNode::decoded(&node_rlp) {
+            Some(RlpNode::Leaf(partial, value)) => {
+                path.extend((0..partial.len()).map(|i| partial.at(i)));
+                f(&nibbles_to_hash(path), value);
+            }
+            Some(RlpNode::Branch(partial, children)) => {
+                path.extend((0..partial.len()).map(|i| partial.at(i)));
+                for (index, child) in children.iter().enumerate() {
+                    if let Some(child) = *child {
+                        path.push(index as u8);
+                        self.for_each_leaf_aux(child, path, f)?;
+                        path.pop();
+                    }
+                }
+            }
+            None => {}
+        }
+        path.truncate(depth);
+        Ok(())
+    }
+
     /// Get auxiliary
     fn get_aux<Q: Query>(
         &self,
@@ -109,6 +177,15 @@ impl<'db> TrieDB<'db> {
     }
 }
 
+fn nibbles_to_hash(nibbles: &[u8]) -> H256 {
+    debug_assert_eq!(64, nibbles.len());
+    let mut hash = H256::zero();
+    for (byte, pair) in hash.iter_mut().zip(nibbles.chunks(2)) {
+        *byte = (pair[0] << 4) | pair[1];
+    }
+    hash
+}
+
 impl<'db> Trie for TrieDB<'db> {
     fn root(&self) -> &H256 {
         self.root
@@ -143,4 +220,40 @@ mod tests {
         assert_eq!(t.get(b"B"), Ok(Some(DBValue::from_slice(b"ABCBA"))));
         assert_eq!(t.get(b"C"), Ok(None));
     }
+
+    #[test]
+    fn for_each_leaf() {
+        let mut memdb = MemoryDB::new();
+        let mut root = H256::new();
+        let keys = vec![&b"A"[..], &b"B"[..], &b"AB"[..], &b"ABC"[..], &b"dog"[..]];
+        let value_of = |key: &[u8]| [key, &b"value"[..]].concat();
+        {
+            let mut t = TrieDBMut::new(&mut memdb, &mut root);
+            for key in &keys {
+                t.insert(key, &value_of(*key)).unwrap();
+            }
+        }
+
+        let t = TrieDB::new(&memdb, &root).unwrap();
+        let mut leaves = ::std::collections::HashMap::new();
+        t.for_each_leaf(|path, value| {
+            leaves.insert(*path, value.to_vec());
+        }).unwrap();
+        assert_eq!(keys.len(), leaves.len());
+        for key in &keys {
+            assert_eq!(Some(&value_of(*key)), leaves.get(&blake256(*key)));
+        }
+    }
+
+    #[test]
+    fn for_each_leaf_of_empty_trie() {
+        let mut memdb = MemoryDB::new();
+        let mut root = H256::new();
+        TrieDBMut::new(&mut memdb, &mut root);
+
+        let t = TrieDB::new(&memdb, &root).unwrap();
+        let mut count = 0;
+        t.for_each_leaf(|_, _| count += 1).unwrap();
+        assert_eq!(0, count);
+    }
 }
@@ -21,7 +21,7 @@ mod hash;
 
 pub use self::hash::{H1024, H128, H160, H256, H264, H32, H512, H520, H64};
 pub use ebytes::Bytes;
-pub use ethereum_types::{clean_0x, U128, U256, U512};
+pub use ethereum_types::{clean_0x, Bloom, BloomInput, U128, U256, U512};
 
 pub mod bytes {
     pub use ebytes::ToPretty;
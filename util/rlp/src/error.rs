@@ -34,6 +34,12 @@ pub enum DecoderError {
     RlpInvalidLength,
     /// A string MUST NOT be null terminated.
     RlpNullTerminatedString,
+    /// Lists are nested deeper than allowed.
+    RlpExceedsMaxDepth,
+    /// Data has more items than allowed.
+    RlpExceedsMaxItems,
+    /// Data is larger than allowed.
+    RlpExceedsMaxSize,
     /// Custom rlp decoding error.
     Custom(&'static str),
 }
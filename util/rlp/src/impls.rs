@@ -7,7 +7,7 @@
 // except according to those terms.
 
 use byteorder::{BigEndian, ByteOrder};
-use primitives::{H128, H160, H256, H512, H520, H64, U128, U256};
+use primitives::{Bloom, H128, H160, H256, H512, H520, H64, U128, U256};
 use std::{cmp, mem, str};
 use stream::RlpStream;
 use traits::{Decodable, Encodable};
@@ -262,6 +262,7 @@ impl_encodable_for_hash!(H160);
 impl_encodable_for_hash!(H256);
 impl_encodable_for_hash!(H512);
 impl_encodable_for_hash!(H520);
+impl_encodable_for_hash!(Bloom);
 
 impl_decodable_for_hash!(H64, 8);
 impl_decodable_for_hash!(H128, 16);
@@ -269,6 +270,7 @@ impl_decodable_for_hash!(H160, 20);
 impl_decodable_for_hash!(H256, 32);
 impl_decodable_for_hash!(H512, 64);
 impl_decodable_for_hash!(H520, 65);
+impl_decodable_for_hash!(Bloom, 256);
 
 macro_rules! impl_encodable_for_uint {
     ($name:ident, $size:expr) => {
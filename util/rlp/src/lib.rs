@@ -50,6 +50,7 @@ mod common;
 mod compression;
 mod error;
 mod impls;
+mod limits;
 mod rlpin;
 mod stream;
 mod traits;
@@ -60,6 +61,7 @@ use std::borrow::Borrow;
 
 pub use compression::RlpType;
 pub use error::DecoderError;
+pub use limits::{DecodeLimits, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ITEMS, DEFAULT_MAX_SIZE};
 pub use rlpin::{Rlp, RlpIterator};
 pub use stream::RlpStream;
 pub use traits::{Compressible, Decodable, Encodable};
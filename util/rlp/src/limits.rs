@@ -0,0 +1,155 @@
+// Copyright 2018 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use untrusted_rlp::PayloadInfo;
+use DecoderError;
+
+/// Default maximum nesting depth of lists.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// Default maximum number of items, counting every nested item.
+pub const DEFAULT_MAX_ITEMS: usize = 1 << 20;
+/// Default maximum size of the encoded bytes.
+pub const DEFAULT_MAX_SIZE: usize = 32 * 1024 * 1024;
+
+/// Bounds applied to untrusted input before it is decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodeLimits {
+    pub max_depth: usize,
+    pub max_items: usize,
+    pub max_size: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_items: DEFAULT_MAX_ITEMS,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Walks the whole structure without recursion and checks it against the limits.
+    ///
+    /// Every nested item must fit exactly in the payload of its parent list.
+    /// Bytes after the top-level item are not inspected.
+    pub fn check(&self, bytes: &[u8]) -> Result<(), DecoderError> {
+        if bytes.len() > self.max_size {
+            return Err(DecoderError::RlpExceedsMaxSize)
+        }
+
+        let mut list_ends: Vec<usize> = Vec::new();
+        let mut position = 0;
+        let mut items = 0;
+        loop {
+            while list_ends.last() == Some(&position) {
+                list_ends.pop();
+            }
+            if items != 0 && list_ends.is_empty() {
+                return Ok(())
+            }
+
+            let end = list_ends.last().cloned().unwrap_or_else(|| bytes.len());
+            let info = PayloadInfo::from(&bytes[position..end])?;
+            let item_end = position
+                .checked_add(info.header_len)
+                .and_then(|offset| offset.checked_add(info.value_len))
+                .ok_or(DecoderError::RlpInvalidLength)?;
+            if item_end > end {
+                return Err(DecoderError::RlpIsTooShort)
+            }
+
+            items += 1;
+            if items > self.max_items {
+                return Err(DecoderError::RlpExceedsMaxItems)
+            }
+
+            if bytes[position] >= 0xc0 {
+                if list_ends.len() >= self.max_depth {
+                    return Err(DecoderError::RlpExceedsMaxDepth)
+                }
+                list_ends.push(item_end);
+                position += info.header_len;
+            } else {
+                position = item_end;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested_lists(depth: usize) -> Vec<u8> {
+        let mut bytes = vec![0x80];
+        for _ in 0..depth {
+            let mut list = vec![0xc0 + bytes.len() as u8];
+            list.extend(bytes);
+            bytes = list;
+        }
+        bytes
+    }
+
+    #[test]
+    fn accepts_nested_lists_within_depth() {
+        let limits = DecodeLimits {
+            max_depth: 8,
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), limits.check(&nested_lists(8)));
+        assert_eq!(Err(DecoderError::RlpExceedsMaxDepth), limits.check(&nested_lists(9)));
+    }
+
+    #[test]
+    fn counts_every_nested_item() {
+        // [[a, b], c]
+        let bytes = vec![0xc4, 0xc2, 0x01, 0x02, 0x03];
+        let limits = DecodeLimits {
+            max_items: 5,
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), limits.check(&bytes));
+        let limits = DecodeLimits {
+            max_items: 4,
+            ..Default::default()
+        };
+        assert_eq!(Err(DecoderError::RlpExceedsMaxItems), limits.check(&bytes));
+    }
+
+    #[test]
+    fn rejects_oversized_input() {
+        let limits = DecodeLimits {
+            max_size: 3,
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), limits.check(&[0x82, 0x01, 0x02]));
+        assert_eq!(Err(DecoderError::RlpExceedsMaxSize), limits.check(&[0x83, 0x01, 0x02, 0x03]));
+    }
+
+    #[test]
+    fn rejects_item_overflowing_its_list() {
+        // The list claims 2 bytes but its only item claims 3.
+        assert_eq!(Err(DecoderError::RlpIsTooShort), DecodeLimits::default().check(&[0xc2, 0x82, 0x01, 0x02]));
+        assert_eq!(Err(DecoderError::RlpIsTooShort), DecodeLimits::default().check(&[0xc3, 0x01]));
+    }
+
+    #[test]
+    fn empty_input_is_too_short() {
+        assert_eq!(Err(DecoderError::RlpIsTooShort), DecodeLimits::default().check(&[]));
+    }
+}
@@ -10,7 +10,7 @@ use impls::decode_usize;
 use rustc_hex::ToHex;
 use std::cell::Cell;
 use std::fmt;
-use {Decodable, DecoderError};
+use {DecodeLimits, Decodable, DecoderError};
 
 /// rlp offset
 #[derive(Copy, Clone, Debug)]
@@ -150,6 +150,18 @@ where
         }
     }
 
+    /// Creates a view after checking the bytes against the default `DecodeLimits`.
+    ///
+    /// Use this for data received from peers or RPC clients.
+    pub fn new_checked(bytes: &'a [u8]) -> Result<UntrustedRlp<'a>, DecoderError> {
+        UntrustedRlp::new_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn new_with_limits(bytes: &'a [u8], limits: &DecodeLimits) -> Result<UntrustedRlp<'a>, DecoderError> {
+        limits.check(bytes)?;
+        Ok(UntrustedRlp::new(bytes))
+    }
+
     pub fn as_raw(&'view self) -> &'a [u8] {
         self.bytes
     }
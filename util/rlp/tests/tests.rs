@@ -517,3 +517,92 @@ fn rlp_stream_unbounded_list() {
     stream.complete_unbounded_list();
     assert!(stream.is_finished());
 }
+
+/// A small xorshift generator so the fuzz tests are reproducible without extra dependencies.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+fn walk(rlp: &UntrustedRlp, depth: usize) -> Result<usize, DecoderError> {
+    assert!(depth <= rlp::DEFAULT_MAX_DEPTH);
+    if rlp.is_list() {
+        let mut items = 1;
+        for item in rlp.iter() {
+            items += walk(&item, depth + 1)?;
+        }
+        Ok(items)
+    } else {
+        rlp.data()?;
+        Ok(1)
+    }
+}
+
+#[test]
+fn rlp_limits_fuzz_random_bytes() {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    for _ in 0..20_000 {
+        let len = (rng.next() % 64) as usize;
+        let bytes = rng.bytes(len);
+        if let Ok(rlp) = UntrustedRlp::new_checked(&bytes) {
+            let items = walk(&rlp, 0).unwrap();
+            assert!(items <= rlp::DEFAULT_MAX_ITEMS);
+        }
+    }
+}
+
+#[test]
+fn rlp_limits_fuzz_mutated_lists() {
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let animals = vec!["cat", "dog", "horse", "lorem ipsum dolor sit amet, consectetur adipisicing elit"];
+    let mut stream = RlpStream::new_list(3);
+    stream.append_list::<&str, &str>(&animals);
+    stream.begin_list(2).append(&U256::from(0xdead_beefu64)).append_list::<&str, &str>(&animals[..2]);
+    stream.append(&H160::default());
+    let valid = stream.out();
+    assert!(UntrustedRlp::new_checked(&valid).is_ok());
+
+    for _ in 0..20_000 {
+        let mut bytes = valid.clone();
+        for _ in 0..(rng.next() % 4 + 1) {
+            let index = (rng.next() as usize) % bytes.len();
+            bytes[index] = rng.next() as u8;
+        }
+        let cut = (rng.next() as usize) % (bytes.len() + 1);
+        bytes.truncate(cut);
+        if let Ok(rlp) = UntrustedRlp::new_checked(&bytes) {
+            walk(&rlp, 0).unwrap();
+        }
+    }
+}
+
+#[test]
+fn rlp_limits_reject_pathological_nesting() {
+    // One million nested empty-list headers, each claiming the rest of the buffer.
+    let depth = 1_000_000;
+    let mut bytes = Vec::with_capacity(depth * 4);
+    for i in 0..depth {
+        let len = (depth - i - 1) * 4;
+        bytes.extend_from_slice(&[0xfa, (len >> 16) as u8, (len >> 8) as u8, len as u8]);
+    }
+    assert_eq!(Err(DecoderError::RlpExceedsMaxDepth), UntrustedRlp::new_checked(&bytes).map(|_| ()));
+}
+
+#[test]
+fn rlp_limits_reject_too_many_items() {
+    let items = vec![0u8; rlp::DEFAULT_MAX_ITEMS];
+    let mut stream = RlpStream::new();
+    stream.append_list::<u8, u8>(&items);
+    let bytes = stream.out();
+    assert_eq!(Err(DecoderError::RlpExceedsMaxItems), UntrustedRlp::new_checked(&bytes).map(|_| ()));
+}